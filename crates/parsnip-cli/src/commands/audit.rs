@@ -0,0 +1,184 @@
+//! Audit log commands
+
+use std::io::Write;
+use std::path::PathBuf;
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clap::{Args, Subcommand};
+
+use crate::output::OutputFormat;
+use crate::{AppContext, Cli};
+use parsnip_storage::{AuditFilter, StorageBackend};
+
+#[derive(Args)]
+pub struct AuditArgs {
+    #[command(subcommand)]
+    pub command: AuditCommands,
+}
+
+#[derive(Subcommand)]
+pub enum AuditCommands {
+    /// List logged writes, oldest first
+    List {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Show only the most recent entries
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+    },
+    /// Export logged writes as NDJSON, one entry per line
+    Export {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Output file (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+pub struct FilterArgs {
+    /// Only writes since a time: an age like 30m, 12h, 1d or 2w, or a date
+    #[arg(long, value_parser = parse_since)]
+    pub since: Option<DateTime<Utc>>,
+    /// Only writes by this client name, token label or transport
+    #[arg(long)]
+    pub actor: Option<String>,
+    /// Only writes by this tool or command
+    #[arg(long)]
+    pub operation: Option<String>,
+}
+
+impl FilterArgs {
+    fn filter(&self) -> AuditFilter {
+        AuditFilter {
+            since: self.since,
+            actor: self.actor.clone(),
+            operation: self.operation.clone(),
+            limit: None,
+        }
+    }
+}
+
+/// Parse an age before now, an RFC 3339 time or a date
+fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+
+    let invalid = || {
+        format!(
+            "invalid time '{}': expected e.g. 30m, 12h, 1d, 2w or 2024-05-01",
+            value
+        )
+    };
+    let Some((split, _)) = value.char_indices().last() else {
+        return Err(invalid());
+    };
+    let (count, unit) = value.split_at(split);
+    let count: i64 = count.parse().map_err(|_| invalid())?;
+    let age = match unit {
+        "s" => Duration::seconds(count),
+        "m" => Duration::minutes(count),
+        "h" => Duration::hours(count),
+        "d" => Duration::days(count),
+        "w" => Duration::weeks(count),
+        _ => return Err(invalid()),
+    };
+    Ok(Utc::now() - age)
+}
+
+pub async fn run(args: &AuditArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Result<()> {
+    match &args.command {
+        AuditCommands::List { filter, limit } => {
+            let filter = filter.filter().with_limit(*limit);
+            let entries = ctx.storage.audit_entries(&filter).await?;
+            tracing::info!("Found {} audit entries", entries.len());
+
+            if OutputFormat::from(cli.format.as_str()) == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
+
+            if entries.is_empty() {
+                println!("No audit entries found");
+                return Ok(());
+            }
+            for entry in &entries {
+                println!(
+                    "#{} {} {} by {} ({} records)",
+                    entry.seq,
+                    entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    entry.operation,
+                    entry.actor,
+                    entry.affected.len()
+                );
+            }
+        }
+        AuditCommands::Export { filter, output } => {
+            let entries = ctx.storage.audit_entries(&filter.filter()).await?;
+            let mut content = String::new();
+            for entry in &entries {
+                content.push_str(&serde_json::to_string(entry)?);
+                content.push('\n');
+            }
+
+            match output {
+                Some(path) => {
+                    // The log holds everything ever written, so keep it private
+                    #[cfg(unix)]
+                    {
+                        let mut file = std::fs::OpenOptions::new()
+                            .write(true)
+                            .create(true)
+                            .truncate(true)
+                            .mode(0o600)
+                            .open(path)?;
+                        file.write_all(content.as_bytes())?;
+                    }
+                    #[cfg(not(unix))]
+                    {
+                        std::fs::write(path, &content)?;
+                    }
+                    println!("Exported {} audit entries to {:?}", entries.len(), path);
+                }
+                None => std::io::stdout().write_all(content.as_bytes())?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        let day_ago = parse_since("1d").unwrap();
+        let expected = Utc::now() - Duration::days(1);
+        assert!((expected - day_ago).num_seconds().abs() < 5);
+        assert!(parse_since("90m").unwrap() > day_ago);
+
+        assert_eq!(
+            parse_since("2024-05-01").unwrap().to_rfc3339(),
+            "2024-05-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_since("2024-05-01T12:00:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2024-05-01T10:00:00+00:00"
+        );
+        for invalid in ["", "d", "1y", "yesterday", "-"] {
+            assert!(parse_since(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Changes command: print the storage change log as NDJSON
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use crate::AppContext;
+use parsnip_storage::{ChangeRecord, StorageError};
+
+#[derive(Args)]
+pub struct ChangesArgs {
+    /// Start after this sequence number (0 is the beginning of the log)
+    #[arg(long, default_value = "0", conflicts_with = "checkpoint")]
+    pub after: u64,
+
+    /// Resume after the sequence number in this file, and write the last
+    /// one printed back to it
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Keep printing changes as they are written. With ReDB, run a daemon
+    /// so other processes can still write meanwhile
+    #[arg(long)]
+    pub follow: bool,
+}
+
+pub async fn run(args: &ChangesArgs, ctx: &AppContext) -> anyhow::Result<()> {
+    let after = match &args.checkpoint {
+        Some(path) => read_checkpoint(path)?,
+        None => args.after,
+    };
+    let mut subscription = ctx.storage.inner().clone().subscribe_changes(after);
+    tracing::debug!("Reading changes after #{}", after);
+
+    loop {
+        let records = if args.follow {
+            let mut records = vec![subscription.next().await.map_err(trimmed)?];
+            records.extend(subscription.drain().await.map_err(trimmed)?);
+            records
+        } else {
+            subscription.drain().await.map_err(trimmed)?
+        };
+        print_records(&records)?;
+        if let (Some(path), false) = (&args.checkpoint, records.is_empty()) {
+            write_checkpoint(path, subscription.position())?;
+        }
+        if !args.follow {
+            return Ok(());
+        }
+    }
+}
+
+/// Say how to go on when the records to print next are gone
+fn trimmed(error: StorageError) -> anyhow::Error {
+    match error {
+        StorageError::ChangesTrimmed(first) => anyhow::anyhow!(
+            "{}; resume with --after {} (or that number in the checkpoint file) to skip them",
+            error,
+            first - 1
+        ),
+        other => other.into(),
+    }
+}
+
+fn print_records(records: &[ChangeRecord]) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for record in records {
+        serde_json::to_writer(&mut stdout, record)?;
+        stdout.write_all(b"\n")?;
+    }
+    // Printed before the checkpoint moves past them
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Sequence number stored in a checkpoint file; 0 when there is none yet
+fn read_checkpoint(path: &Path) -> anyhow::Result<u64> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => content
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Checkpoint {:?} holds no sequence number", path)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => anyhow::bail!("Failed to read checkpoint {:?}: {}", path, e),
+    }
+}
+
+/// Replace the checkpoint file whole, so a crash never leaves half of one
+fn write_checkpoint(path: &Path, seq: u64) -> anyhow::Result<()> {
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, format!("{}\n", seq))?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("changes.checkpoint");
+
+        assert_eq!(read_checkpoint(&path).unwrap(), 0);
+        write_checkpoint(&path, 42).unwrap();
+        assert_eq!(read_checkpoint(&path).unwrap(), 42);
+        write_checkpoint(&path, 43).unwrap();
+        assert_eq!(read_checkpoint(&path).unwrap(), 43);
+
+        std::fs::write(&path, "not a number").unwrap();
+        assert!(read_checkpoint(&path).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Context pack command
+
+use clap::Args;
+
+use crate::output::OutputFormat;
+use crate::{AppContext, Cli};
+use parsnip_core::{ProjectId, SearchMode, SearchQuery};
+use parsnip_search::{
+    CharTokenEstimator, ContextBuilder, ContextQuery, ExactSearchEngine, FuzzySearchEngine,
+    SearchEngine, WordTokenEstimator, DEFAULT_CONTEXT_BUDGET,
+};
+use parsnip_storage::StorageBackend;
+
+#[derive(Args)]
+pub struct ContextArgs {
+    /// Topic to recall
+    pub query: String,
+
+    /// Token budget for the rendered context
+    #[arg(short, long, default_value_t = DEFAULT_CONTEXT_BUDGET)]
+    pub budget: usize,
+
+    /// Neighborhood depth around search hits (0-2)
+    #[arg(long, default_value = "1")]
+    pub hops: u32,
+
+    /// Maximum number of search hits to expand
+    #[arg(long, default_value = "5")]
+    pub seeds: usize,
+
+    /// Search mode: exact, fuzzy, fulltext, hybrid
+    #[arg(long, default_value = "fuzzy")]
+    pub mode: String,
+
+    /// Token estimate: chars (~4 chars/token) or words (~0.75 words/token)
+    #[arg(long, default_value = "chars")]
+    pub tokenizer: String,
+
+    /// Search all projects
+    #[arg(long)]
+    pub all_projects: bool,
+}
+
+async fn get_project_id(project_name: &str, ctx: &AppContext) -> anyhow::Result<ProjectId> {
+    if let Some(project) = ctx.storage.get_project(project_name).await? {
+        return Ok(project.id);
+    }
+    let project = parsnip_core::Project::new(project_name);
+    ctx.storage.save_project(&project).await?;
+    Ok(project.id)
+}
+
+pub async fn run(args: &ContextArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Result<()> {
+    let mode = match args.mode.as_str() {
+        "exact" => SearchMode::Exact,
+        "fulltext" => SearchMode::FullText,
+        "hybrid" => SearchMode::Hybrid,
+        _ => SearchMode::Fuzzy,
+    };
+    let mut search = SearchQuery::new(&args.query).with_mode(mode);
+
+    let (entities, relations) = if args.all_projects {
+        search = search.in_all_projects();
+        (
+            ctx.storage.get_all_entities_all_projects().await?,
+            ctx.storage.get_all_relations_all_projects().await?,
+        )
+    } else {
+        let project_id = get_project_id(&cli.project, ctx).await?;
+        search = search.in_project(project_id.clone());
+        (
+            ctx.storage.get_all_entities(&project_id).await?,
+            ctx.storage.get_all_relations(&project_id).await?,
+        )
+    };
+
+    let query = ContextQuery::new(search)
+        .with_budget(args.budget)
+        .with_hops(args.hops)
+        .with_max_seeds(args.seeds);
+
+    let fuzzy = FuzzySearchEngine::new();
+    let exact = ExactSearchEngine::new();
+    let engine: &dyn SearchEngine = match mode {
+        SearchMode::Fuzzy => &fuzzy,
+        #[cfg(feature = "fulltext")]
+        SearchMode::FullText | SearchMode::Hybrid => match ctx.fulltext {
+            Some(ref fulltext) => fulltext.as_ref(),
+            None => {
+                tracing::warn!("Full-text search not available, falling back to exact search");
+                &exact
+            }
+        },
+        _ => &exact,
+    };
+
+    let builder = ContextBuilder::new(engine);
+    let builder = match args.tokenizer.as_str() {
+        "words" => builder.with_estimator(WordTokenEstimator),
+        _ => builder.with_estimator(CharTokenEstimator::default()),
+    };
+
+    let pack = builder.build(&query, &entities, &relations).await?;
+
+    tracing::info!(
+        "Context for '{}': {} entities, {} of {} tokens",
+        args.query,
+        pack.entities.len(),
+        pack.tokens_used,
+        pack.budget
+    );
+
+    if OutputFormat::from(cli.format.as_str()) == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&pack)?);
+    } else if pack.entities.is_empty() {
+        println!("No context found for '{}'", args.query);
+    } else {
+        println!("{}", pack.render());
+    }
+
+    Ok(())
+}
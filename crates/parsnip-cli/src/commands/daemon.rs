@@ -0,0 +1,189 @@
+//! Daemon commands: share the database between processes
+//!
+//! While a daemon runs, every other command and `parsnip serve` reach the
+//! database through its socket instead of opening it.
+
+use clap::{Args, Subcommand};
+
+use crate::Cli;
+
+#[cfg(unix)]
+use std::time::Duration;
+
+#[cfg(unix)]
+use crate::output::OutputFormat;
+#[cfg(unix)]
+use parsnip_storage::{DaemonClient, DaemonServer, SOCKET_FILE};
+
+/// File in the data directory the background daemon logs to
+#[cfg(unix)]
+const LOG_FILE: &str = "daemon.log";
+
+/// How long to wait for the daemon to start or stop
+#[cfg(unix)]
+const WAIT: Duration = Duration::from_secs(5);
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    #[command(subcommand)]
+    pub command: DaemonCommands,
+}
+
+#[derive(Subcommand)]
+pub enum DaemonCommands {
+    /// Start a daemon owning the database, in the background
+    Start {
+        /// Run in the foreground until interrupted
+        #[arg(long)]
+        foreground: bool,
+    },
+    /// Stop the running daemon
+    Stop,
+    /// Show whether a daemon is running
+    Status,
+}
+
+#[cfg(not(unix))]
+pub async fn run(_args: &DaemonArgs, _cli: &Cli) -> anyhow::Result<()> {
+    anyhow::bail!("The daemon needs Unix domain sockets, which this platform lacks");
+}
+
+#[cfg(unix)]
+pub async fn run(args: &DaemonArgs, cli: &Cli) -> anyhow::Result<()> {
+    let data_dir = cli.data_dir();
+    let socket = data_dir.join(SOCKET_FILE);
+
+    match &args.command {
+        DaemonCommands::Start { foreground: true } => {
+            let backend = cli.backend();
+            let (storage, db_path) = crate::open_storage(&data_dir, backend)?;
+            tracing::info!("Daemon serving the {} backend ({:?})", backend, db_path);
+            DaemonServer::new(storage, &socket)
+                .with_backend(backend.to_string())
+                .run(terminated())
+                .await?;
+        }
+        DaemonCommands::Start { foreground: false } => {
+            if let Ok(client) = DaemonClient::connect(&socket).await {
+                let status = client.status().await?;
+                anyhow::bail!("A daemon is already running (pid {})", status.pid);
+            }
+            let child = spawn_background(cli)?;
+            let log = data_dir.join(LOG_FILE);
+            let client = wait_for(child, &socket)
+                .await
+                .map_err(|e| anyhow::anyhow!("{}; see {:?}", e, log))?;
+            let status = client.status().await?;
+            println!("Daemon started (pid {}) on {:?}", status.pid, socket);
+        }
+        DaemonCommands::Stop => {
+            let Ok(client) = DaemonClient::connect(&socket).await else {
+                anyhow::bail!("No daemon is running");
+            };
+            let status = client.status().await?;
+            client.shutdown().await?;
+
+            let deadline = tokio::time::Instant::now() + WAIT;
+            while socket.exists() {
+                if tokio::time::Instant::now() > deadline {
+                    anyhow::bail!("Daemon (pid {}) did not stop", status.pid);
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            println!("Daemon stopped (pid {})", status.pid);
+        }
+        DaemonCommands::Status => {
+            let status = match DaemonClient::connect(&socket).await {
+                Ok(client) => Some(client.status().await?),
+                Err(_) => None,
+            };
+            if OutputFormat::from(cli.format.as_str()) == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+                return Ok(());
+            }
+            match status {
+                Some(status) => println!(
+                    "Daemon running (pid {}, version {}, {} backend) since {} on {:?}",
+                    status.pid,
+                    status.version,
+                    status.backend,
+                    status.started.format("%Y-%m-%d %H:%M:%S"),
+                    socket
+                ),
+                None => println!("No daemon running"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Completes on Ctrl-C or SIGTERM
+#[cfg(unix)]
+async fn terminated() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut term = match signal(SignalKind::terminate()) {
+        Ok(term) => term,
+        Err(e) => {
+            tracing::warn!("Failed to listen for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = term.recv() => {}
+    }
+}
+
+/// Run `daemon start --foreground` detached, logging to the data directory
+#[cfg(unix)]
+fn spawn_background(cli: &Cli) -> anyhow::Result<std::process::Child> {
+    use std::os::unix::process::CommandExt;
+
+    let data_dir = cli.data_dir();
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(data_dir.join(LOG_FILE))?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .arg("--backend")
+        .arg(cli.backend().to_string());
+    if cli.verbose > 0 {
+        command.arg(format!("-{}", "v".repeat(cli.verbose as usize)));
+    }
+    command
+        .args(["daemon", "start", "--foreground"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(log)
+        // Keep running when the terminal's process group is interrupted
+        .process_group(0);
+    Ok(command.spawn()?)
+}
+
+/// Connect to a starting daemon once it listens
+#[cfg(unix)]
+async fn wait_for(
+    mut child: std::process::Child,
+    socket: &std::path::Path,
+) -> anyhow::Result<DaemonClient> {
+    let deadline = tokio::time::Instant::now() + WAIT;
+    loop {
+        if let Ok(client) = DaemonClient::connect(socket).await {
+            return Ok(client);
+        }
+        if let Some(status) = child.try_wait()? {
+            anyhow::bail!("Daemon exited at start ({})", status);
+        }
+        if tokio::time::Instant::now() > deadline {
+            anyhow::bail!("Daemon did not start within {:?}", WAIT);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
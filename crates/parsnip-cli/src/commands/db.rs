@@ -0,0 +1,73 @@
+//! Db commands: move the knowledge graph between storage backends
+
+use clap::{Args, Subcommand};
+
+use crate::config::Backend;
+use crate::output::OutputFormat;
+use crate::Cli;
+use parsnip_storage::copy_store;
+
+#[derive(Args)]
+pub struct DbArgs {
+    #[command(subcommand)]
+    pub command: DbCommands,
+}
+
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// Copy every project, entity, relation and audit entry into another
+    /// backend's database in the data directory
+    Convert {
+        /// Backend to copy from
+        #[arg(long, value_enum)]
+        from: Backend,
+
+        /// Backend to copy into; its database must be empty
+        #[arg(long, value_enum)]
+        to: Backend,
+    },
+}
+
+pub async fn run(args: &DbArgs, cli: &Cli) -> anyhow::Result<()> {
+    let data_dir = cli.data_dir();
+
+    match &args.command {
+        DbCommands::Convert { from, to } => {
+            if from == to {
+                anyhow::bail!("Nothing to convert: --from and --to are both {}", from);
+            }
+            if [from, to].contains(&&Backend::Memory) {
+                anyhow::bail!("The memory backend keeps nothing to convert from or to");
+            }
+            #[cfg(unix)]
+            if parsnip_storage::DaemonClient::connect(data_dir.join(parsnip_storage::SOCKET_FILE))
+                .await
+                .is_ok()
+            {
+                anyhow::bail!("A daemon holds the database; run `parsnip daemon stop` first");
+            }
+
+            let (source, source_path) = crate::open_storage(&data_dir, *from)?;
+            let (target, target_path) = crate::open_storage(&data_dir, *to)?;
+            let stats = copy_store(source.as_ref(), target.as_ref()).await?;
+            source.close().await?;
+            target.close().await?;
+            tracing::info!("Converted {:?} to {:?}", source_path, target_path);
+
+            if OutputFormat::from(cli.format.as_str()) == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+                return Ok(());
+            }
+            println!(
+                "Copied {} projects, {} entities, {} relations and {} audit entries from {} to {}",
+                stats.projects, stats.entities, stats.relations, stats.audit_entries, from, to
+            );
+            println!(
+                "Use it with `parsnip config set backend {}` or `--backend {}`",
+                to, to
+            );
+        }
+    }
+
+    Ok(())
+}
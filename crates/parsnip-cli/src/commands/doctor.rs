@@ -0,0 +1,98 @@
+//! Doctor command: consistency checks and repairs
+
+use clap::Args;
+
+use crate::output::OutputFormat;
+use crate::{AppContext, Cli};
+use parsnip_storage::{Doctor, DoctorReport, StorageBackend};
+
+#[cfg(feature = "fulltext")]
+use parsnip_search::SearchEngine;
+
+#[derive(Args)]
+pub struct DoctorArgs {
+    /// Repair fixable issues in a single transaction
+    #[arg(long)]
+    pub fix: bool,
+
+    /// Skip the full-text index check
+    #[arg(long)]
+    pub skip_index: bool,
+}
+
+pub async fn run(args: &DoctorArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Result<()> {
+    let storage: &dyn StorageBackend = ctx.storage.as_ref();
+    #[allow(unused_mut)]
+    let mut doctor = Doctor::new(storage);
+
+    #[cfg(feature = "fulltext")]
+    if !args.skip_index {
+        if let Some(ref fulltext) = ctx.fulltext {
+            doctor = doctor.with_index(fulltext.indexed_entity_ids()?);
+        }
+    }
+
+    let mut report = doctor.run().await?;
+    tracing::info!("Doctor found {} issues", report.issues.len());
+
+    if args.fix && !report.is_healthy() {
+        doctor.repair(&mut report).await?;
+
+        #[cfg(feature = "fulltext")]
+        if report.rebuild_index {
+            if let Some(ref fulltext) = ctx.fulltext {
+                let entities = ctx.storage.get_all_entities_all_projects().await?;
+                fulltext.rebuild_index(&entities).await?;
+                tracing::info!("Rebuilt full-text index with {} entities", entities.len());
+            }
+        }
+    }
+
+    if OutputFormat::from(cli.format.as_str()) == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    print_report(&report);
+    Ok(())
+}
+
+fn print_report(report: &DoctorReport) {
+    println!(
+        "Checked {} projects, {} entities, {} relations",
+        report.projects_checked, report.entities_checked, report.relations_checked
+    );
+
+    if report.is_healthy() {
+        println!("No issues found");
+        return;
+    }
+
+    println!(
+        "Found {} issues ({} fixable):",
+        report.issues.len(),
+        report.fixable_count()
+    );
+    for issue in &report.issues {
+        let project = issue
+            .project
+            .as_ref()
+            .map(|p| format!("{}: ", p))
+            .unwrap_or_default();
+        let unfixable = if issue.fixable { "" } else { " (manual)" };
+        println!(
+            "  [{}] {}{} - {}{}",
+            issue.kind, project, issue.subject, issue.detail, unfixable
+        );
+    }
+
+    println!();
+    if report.fixed {
+        println!("Repaired {} issues", report.fixable_count());
+        if report.rebuild_index {
+            println!("Rebuilt full-text index");
+        }
+    } else if report.fixable_count() > 0 {
+        println!("Run 'parsnip doctor --fix' to repair fixable issues");
+    }
+}
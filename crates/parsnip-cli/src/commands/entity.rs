@@ -3,7 +3,10 @@
 use clap::{Args, Subcommand};
 
 use crate::{AppContext, Cli};
-use parsnip_core::{Entity, ProjectId};
+use parsnip_core::{Direction, Entity, Project, ProjectId};
+use parsnip_search::LinkPredictor;
+use parsnip_storage::consolidate;
+use parsnip_storage::ops::{self, EntityUpdate, OpsError};
 use parsnip_storage::StorageBackend;
 
 #[derive(Args)]
@@ -77,19 +80,45 @@ pub enum EntityCommands {
         #[arg(long = "set-type")]
         set_type: Option<String>,
     },
+    /// Suggest related entities that are not linked yet
+    Related {
+        /// Entity name
+        name: String,
+        /// Maximum suggestions
+        #[arg(short, long, default_value = "10")]
+        limit: usize,
+        /// Minimum score (0.0-1.0)
+        #[arg(long, default_value = "0.1")]
+        min_score: f32,
+    },
+    /// Fold duplicate observations, keeping the originals in history
+    Consolidate {
+        /// Entity name
+        name: String,
+        /// Share of words two observations need in common to be duplicates (0.0-1.0)
+        #[arg(long, default_value_t = consolidate::DEFAULT_SIMILARITY)]
+        similarity: f64,
+        /// Show what would be merged without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 async fn get_project_id(project_name: &str, ctx: &AppContext) -> anyhow::Result<ProjectId> {
+    Ok(get_project(project_name, ctx).await?.id)
+}
+
+async fn get_project(project_name: &str, ctx: &AppContext) -> anyhow::Result<Project> {
     // Try to find existing project
     if let Some(project) = ctx.storage.get_project(project_name).await? {
-        return Ok(project.id);
+        return Ok(project);
     }
 
     // Create new project if it doesn't exist
-    let project = parsnip_core::Project::new(project_name);
+    let project = Project::new(project_name);
     ctx.storage.save_project(&project).await?;
     tracing::info!("Created new project: {}", project_name);
-    Ok(project.id)
+    Ok(project)
 }
 
 pub async fn run(args: &EntityArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Result<()> {
@@ -237,56 +266,140 @@ pub async fn run(args: &EntityArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Resu
             remove_tag,
             set_type,
         } => {
-            let project_id = get_project_id(&cli.project, ctx).await?;
+            let project = get_project(&cli.project, ctx).await?;
+            let update = EntityUpdate {
+                entity_type: set_type.clone(),
+                add_observations: add_obs.clone(),
+                add_tags: add_tag.clone(),
+                remove_tags: remove_tag.clone(),
+            };
 
-            match ctx.storage.get_entity(name, &project_id).await? {
-                Some(mut entity) => {
-                    let mut changes = Vec::new();
-
-                    // Add observations
-                    for obs in add_obs {
-                        entity.add_observation(obs);
-                        changes.push(format!("added observation: {}", obs));
+            match ops::update_entity(&*ctx.storage, &project, name, &update).await {
+                Ok(outcome) => {
+                    for tag in &outcome.missing_tags {
+                        println!("Tag '{}' not found on entity", tag);
                     }
-
-                    // Add tags
-                    for tag in add_tag {
-                        entity.add_tag(tag);
-                        changes.push(format!("added tag: {}", tag));
-                    }
-
-                    // Remove tags
-                    for tag in remove_tag {
-                        if entity.remove_tag(tag) {
-                            changes.push(format!("removed tag: {}", tag));
-                        } else {
-                            println!("Tag '{}' not found on entity", tag);
-                        }
-                    }
-
-                    // Set type
-                    if let Some(new_type) = set_type {
-                        entity.entity_type = parsnip_core::EntityType::new(new_type);
-                        changes.push(format!("set type: {}", new_type));
-                    }
-
-                    if changes.is_empty() {
+                    if outcome.changes.is_empty() {
                         println!("No changes specified");
                         return Ok(());
                     }
 
-                    ctx.storage.save_entity(&entity).await?;
-                    tracing::info!("Updated entity '{}': {:?}", name, changes);
+                    tracing::info!("Updated entity '{}': {:?}", name, outcome.changes);
 
                     println!("Updated entity '{}':", name);
-                    for change in &changes {
+                    for change in &outcome.changes {
                         println!("  - {}", change);
                     }
                 }
-                None => {
-                    println!("Entity '{}' not found in project '{}'", name, cli.project);
+                Err(e @ OpsError::EntityNotFound { .. }) => println!("{}", e),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        EntityCommands::Related {
+            name,
+            limit,
+            min_score,
+        } => {
+            let project_id = get_project_id(&cli.project, ctx).await?;
+
+            let Some(entity) = ctx.storage.get_entity(name, &project_id).await? else {
+                println!("Entity '{}' not found in project '{}'", name, cli.project);
+                return Ok(());
+            };
+
+            let entities = ctx.storage.get_all_entities(&project_id).await?;
+            let relations = ctx.storage.get_all_relations(&project_id).await?;
+            let suggestions = LinkPredictor::new()
+                .with_min_score(*min_score)
+                .suggest(&entity, &entities, &relations, *limit);
+
+            tracing::info!("Found {} related entities for {}", suggestions.len(), name);
+
+            if suggestions.is_empty() {
+                println!("No related entities found for '{}'", name);
+                return Ok(());
+            }
+
+            println!(
+                "Entities related to '{}' ({} found):",
+                name,
+                suggestions.len()
+            );
+            for s in &suggestions {
+                println!(
+                    "  {} ({})  score: {:.2}",
+                    s.entity.name, s.entity.entity_type.0, s.score
+                );
+                if !s.common_neighbors.is_empty() {
+                    println!("    common neighbors: {}", s.common_neighbors.join(", "));
+                }
+                if !s.shared_tags.is_empty() {
+                    println!("    shared tags: {}", s.shared_tags.join(", "));
+                }
+                if s.text_similarity > 0.0 {
+                    println!("    text similarity: {:.2}", s.text_similarity);
+                }
+                for rt in &s.relation_types {
+                    let (from, to) = match rt.direction {
+                        Direction::Incoming => (&s.entity.name, name),
+                        _ => (name, &s.entity.name),
+                    };
+                    println!(
+                        "    suggest: {} -[{}]-> {} (seen {}x)",
+                        from, rt.relation_type, to, rt.support
+                    );
+                }
+            }
+        }
+        EntityCommands::Consolidate {
+            name,
+            similarity,
+            dry_run,
+        } => {
+            let project = get_project(&cli.project, ctx).await?;
+            let Some(entity) = ctx.storage.get_entity(name, &project.id).await? else {
+                println!("Entity '{}' not found in project '{}'", name, cli.project);
+                return Ok(());
+            };
+
+            let merges = consolidate::dedupe(&entity, similarity.clamp(0.0, 1.0));
+            if merges.is_empty() {
+                println!("No duplicate observations on '{}'", name);
+                return Ok(());
+            }
+
+            for merge in &merges {
+                println!("Keep: {}", merge.merged.join("; "));
+                for observation in &merge.replaced {
+                    println!("  - {}", observation.content);
                 }
             }
+            let removed: isize = merges.iter().map(|m| -m.net_change()).sum();
+            if *dry_run {
+                println!("Dry run: {} observations would be removed", removed);
+                return Ok(());
+            }
+
+            let outcome = consolidate::apply(
+                &*ctx.storage,
+                &project,
+                name,
+                &merges,
+                "consolidated by dedupe",
+            )
+            .await?;
+            tracing::info!(
+                "Consolidated entity '{}': {} replaced",
+                name,
+                outcome.replaced
+            );
+            println!(
+                "Consolidated '{}': {} -> {} observations ({} kept in history)",
+                name,
+                entity.observations.len(),
+                outcome.entity.observations.len(),
+                outcome.entity.observation_history.len()
+            );
         }
     }
 
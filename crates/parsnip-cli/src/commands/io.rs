@@ -7,10 +7,11 @@ use std::path::PathBuf;
 use std::os::unix::fs::OpenOptionsExt;
 
 use clap::{Args, ValueEnum};
-use serde::{Deserialize, Serialize};
 
 use crate::{AppContext, Cli};
 use parsnip_core::{Entity, Project, Relation};
+use parsnip_storage::export::{export_projects, ExportData};
+use parsnip_storage::ops::{self, OpsError, SubgraphRequest};
 use parsnip_storage::StorageBackend;
 
 /// Export format
@@ -54,40 +55,14 @@ pub struct ExportArgs {
     /// Export format
     #[arg(short, long, default_value = "json")]
     pub format: ExportFormat,
-}
-
-/// Export format matching the knowledge graph structure
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ExportData {
-    pub version: String,
-    pub projects: Vec<ProjectExport>,
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ProjectExport {
-    pub name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub description: Option<String>,
-    pub entities: Vec<EntityExport>,
-    pub relations: Vec<RelationExport>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EntityExport {
-    pub name: String,
-    #[serde(rename = "entityType")]
-    pub entity_type: String,
-    pub observations: Vec<String>,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub tags: Vec<String>,
-}
+    /// Export only the neighborhood of this entity (repeatable)
+    #[arg(long, value_name = "ENTITY", conflicts_with = "all_projects")]
+    pub around: Vec<String>,
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RelationExport {
-    pub from: String,
-    pub to: String,
-    #[serde(rename = "relationType")]
-    pub relation_type: String,
+    /// Hops from the `--around` entities to include
+    #[arg(long, default_value = "1", requires = "around")]
+    pub depth: u32,
 }
 
 pub async fn run_import(args: &ImportArgs, _cli: &Cli, ctx: &AppContext) -> anyhow::Result<()> {
@@ -209,49 +184,18 @@ pub async fn run_export(args: &ExportArgs, cli: &Cli, ctx: &AppContext) -> anyho
         }
     };
 
-    let mut project_exports = Vec::new();
-
-    for project in projects {
-        let entities = ctx.storage.get_all_entities(&project.id).await?;
-        let relations = ctx.storage.get_all_relations(&project.id).await?;
-
-        let entity_exports: Vec<EntityExport> = entities
-            .iter()
-            .map(|e| EntityExport {
-                name: e.name.clone(),
-                entity_type: e.entity_type.0.clone(),
-                observations: e.observations.iter().map(|o| o.content.clone()).collect(),
-                tags: e.tags.clone(),
-            })
-            .collect();
-
-        let relation_exports: Vec<RelationExport> = relations
-            .iter()
-            .map(|r| RelationExport {
-                from: r.from_name.clone(),
-                to: r.to_name.clone(),
-                relation_type: r.relation_type.clone(),
-            })
-            .collect();
-
-        tracing::debug!(
-            "Exporting project '{}': {} entities, {} relations",
-            project.name,
-            entity_exports.len(),
-            relation_exports.len()
-        );
-
-        project_exports.push(ProjectExport {
-            name: project.name,
-            description: project.description,
-            entities: entity_exports,
-            relations: relation_exports,
-        });
-    }
-
-    let export_data = ExportData {
-        version: "1.0".to_string(),
-        projects: project_exports,
+    let export_data = if args.around.is_empty() {
+        export_projects(&*ctx.storage, &projects).await?
+    } else {
+        let request = SubgraphRequest::new(args.around.clone(), args.depth);
+        match ops::export_subgraph(&*ctx.storage, &projects[0], &request).await {
+            Ok(data) => data,
+            Err(e @ OpsError::EntityNotFound { .. }) => {
+                println!("{}", e);
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        }
     };
 
     let content = match args.format {
@@ -1,7 +1,13 @@
 //! CLI command implementations
 
+pub mod audit;
+pub mod changes;
 pub mod completions;
 pub mod config;
+pub mod context;
+pub mod daemon;
+pub mod db;
+pub mod doctor;
 pub mod entity;
 pub mod io;
 pub mod project;
@@ -3,7 +3,7 @@
 use clap::{Args, Subcommand};
 
 use crate::{AppContext, Cli};
-use parsnip_core::Project;
+use parsnip_storage::ops::{self, OpsError};
 use parsnip_storage::StorageBackend;
 
 #[derive(Args)]
@@ -72,23 +72,15 @@ pub async fn run(args: &ProjectArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Res
             }
         }
         ProjectCommands::Create { name, description } => {
-            // Check if project already exists
-            if ctx.storage.get_project(name).await?.is_some() {
-                println!("Project '{}' already exists", name);
-                return Ok(());
-            }
-
-            let mut project = Project::new(name);
-            if let Some(desc) = description {
-                project = project.with_description(desc);
-            }
-
-            ctx.storage.save_project(&project).await?;
-            tracing::info!("Created project: {}", name);
-
-            println!("Created project: {}", name);
-            if let Some(desc) = description {
-                println!("  description: {}", desc);
+            match ops::create_project(&*ctx.storage, name, description.as_deref()).await {
+                Ok(_) => {
+                    println!("Created project: {}", name);
+                    if let Some(desc) = description {
+                        println!("  description: {}", desc);
+                    }
+                }
+                Err(e @ OpsError::ProjectExists(_)) => println!("{}", e),
+                Err(e) => return Err(e.into()),
             }
         }
         ProjectCommands::Use { name } => {
@@ -114,93 +106,62 @@ pub async fn run(args: &ProjectArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Res
             );
         }
         ProjectCommands::Delete { name, force } => {
-            // Check if project exists
-            let project = match ctx.storage.get_project(name).await? {
-                Some(p) => p,
-                None => {
-                    println!("Project '{}' not found", name);
-                    return Ok(());
-                }
-            };
-
-            // Get entity count for warning
-            let entity_count = ctx.storage.get_all_entities(&project.id).await?.len();
-            let relation_count = ctx.storage.get_all_relations(&project.id).await?.len();
-
             if !force {
+                let stats = match ops::project_stats(&*ctx.storage, name).await {
+                    Ok(stats) => stats,
+                    Err(e @ OpsError::ProjectNotFound(_)) => {
+                        println!("{}", e);
+                        return Ok(());
+                    }
+                    Err(e) => return Err(e.into()),
+                };
                 println!(
                     "Project '{}' has {} entities and {} relations",
-                    name, entity_count, relation_count
+                    name, stats.entity_count, stats.relation_count
                 );
                 println!("Use --force to confirm deletion");
                 return Ok(());
             }
 
-            ctx.storage.delete_project(name).await?;
-            tracing::info!(
-                "Deleted project: {} ({} entities, {} relations)",
-                name,
-                entity_count,
-                relation_count
-            );
-            println!(
-                "Deleted project: {} ({} entities, {} relations)",
-                name, entity_count, relation_count
-            );
+            match ops::delete_project(&*ctx.storage, name).await {
+                Ok(stats) => println!(
+                    "Deleted project: {} ({} entities, {} relations)",
+                    name, stats.entity_count, stats.relation_count
+                ),
+                Err(e @ OpsError::ProjectNotFound(_)) => println!("{}", e),
+                Err(e) => return Err(e.into()),
+            }
         }
         ProjectCommands::Stats { name } => {
             let project_name = name.as_deref().unwrap_or(&cli.project);
 
-            let project = match ctx.storage.get_project(project_name).await? {
-                Some(p) => p,
-                None => {
-                    println!("Project '{}' not found", project_name);
+            let stats = match ops::project_stats(&*ctx.storage, project_name).await {
+                Ok(stats) => stats,
+                Err(e @ OpsError::ProjectNotFound(_)) => {
+                    println!("{}", e);
                     return Ok(());
                 }
+                Err(e) => return Err(e.into()),
             };
 
-            let entities = ctx.storage.get_all_entities(&project.id).await?;
-            let relations = ctx.storage.get_all_relations(&project.id).await?;
-
-            // Count entities by type
-            let mut type_counts: std::collections::HashMap<String, usize> =
-                std::collections::HashMap::new();
-            let mut total_observations = 0;
-            let mut total_tags = 0;
-
-            for entity in &entities {
-                *type_counts.entry(entity.entity_type.0.clone()).or_insert(0) += 1;
-                total_observations += entity.observations.len();
-                total_tags += entity.tags.len();
-            }
-
-            // Count relations by type
-            let mut rel_type_counts: std::collections::HashMap<String, usize> =
-                std::collections::HashMap::new();
-            for relation in &relations {
-                *rel_type_counts
-                    .entry(relation.relation_type.clone())
-                    .or_insert(0) += 1;
-            }
-
             tracing::info!("Stats for project: {}", project_name);
 
             println!("Stats for project '{}':", project_name);
-            if let Some(desc) = &project.description {
+            if let Some(desc) = &stats.project.description {
                 println!("  Description: {}", desc);
             }
-            println!("  Created: {}", project.created_at);
+            println!("  Created: {}", stats.project.created_at);
             println!();
-            println!("  Entities: {}", entities.len());
-            for (entity_type, count) in type_counts.iter() {
+            println!("  Entities: {}", stats.entity_count);
+            for (entity_type, count) in &stats.entity_types {
                 println!("    {}: {}", entity_type, count);
             }
             println!();
-            println!("  Observations: {}", total_observations);
-            println!("  Tags: {}", total_tags);
+            println!("  Observations: {}", stats.observation_count);
+            println!("  Tags: {}", stats.tag_count);
             println!();
-            println!("  Relations: {}", relations.len());
-            for (rel_type, count) in rel_type_counts.iter() {
+            println!("  Relations: {}", stats.relation_count);
+            for (rel_type, count) in &stats.relation_types {
                 println!("    {}: {}", rel_type, count);
             }
         }
@@ -5,7 +5,10 @@ use std::collections::HashMap;
 use clap::{Args, Subcommand};
 
 use crate::{AppContext, Cli};
-use parsnip_core::{Direction, ProjectId, Relation, TraversalEngine, TraversalQuery};
+use parsnip_core::{
+    Direction, Entity, PathNode, Project, ProjectId, Relation, TraversalEngine, TraversalQuery,
+};
+use parsnip_storage::ops::{self, OpsError, PathRequest, RelationFilter, Scope};
 use parsnip_storage::StorageBackend;
 
 #[derive(Args)]
@@ -67,6 +70,9 @@ pub enum RelationCommands {
         /// Filter by entity types (comma-separated)
         #[arg(short = 'e', long)]
         entity_types: Option<String>,
+        /// Follow relations into other projects
+        #[arg(long)]
+        cross_project: bool,
     },
     /// Find path between two entities
     FindPath {
@@ -86,17 +92,80 @@ pub enum RelationCommands {
         /// Maximum search depth
         #[arg(long, default_value = "10")]
         max_depth: u32,
+        /// Follow relations into other projects
+        #[arg(long)]
+        cross_project: bool,
+        /// Project containing the target entity (implies --cross-project)
+        #[arg(long)]
+        to_project: Option<String>,
     },
 }
 
+/// Load the traversal input: the current project, or every project when crossing boundaries
+async fn load_graph_data(
+    project_id: &ProjectId,
+    cross_project: bool,
+    ctx: &AppContext,
+) -> anyhow::Result<(Vec<Entity>, Vec<Relation>)> {
+    if cross_project {
+        Ok((
+            ctx.storage.get_all_entities_all_projects().await?,
+            ctx.storage.get_all_relations_all_projects().await?,
+        ))
+    } else {
+        Ok((
+            ctx.storage.get_all_entities(project_id).await?,
+            ctx.storage.get_all_relations(project_id).await?,
+        ))
+    }
+}
+
+/// Display a node as `name`, or `name (project)` when it lives outside the current project
+fn format_node(
+    node: &PathNode,
+    current: &ProjectId,
+    project_names: &HashMap<ProjectId, String>,
+) -> String {
+    if &node.project_id == current {
+        node.name.clone()
+    } else {
+        let project = project_names
+            .get(&node.project_id)
+            .map(String::as_str)
+            .unwrap_or("?");
+        format!("{} ({})", node.name, project)
+    }
+}
+
+async fn project_names(ctx: &AppContext) -> anyhow::Result<HashMap<ProjectId, String>> {
+    Ok(ctx
+        .storage
+        .get_all_projects()
+        .await?
+        .into_iter()
+        .map(|p| (p.id, p.name))
+        .collect())
+}
+
+/// Split a comma-separated option into trimmed values
+fn split_list(list: &Option<String>) -> Vec<String> {
+    list.as_deref()
+        .map(|l| l.split(',').map(|s| s.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
 async fn get_project_id(project_name: &str, ctx: &AppContext) -> anyhow::Result<ProjectId> {
+    Ok(get_project(project_name, ctx).await?.id)
+}
+
+async fn get_project(project_name: &str, ctx: &AppContext) -> anyhow::Result<Project> {
     if let Some(project) = ctx.storage.get_project(project_name).await? {
-        return Ok(project.id);
+        return Ok(project);
     }
-    let project = parsnip_core::Project::new(project_name);
+    let project = Project::new(project_name);
     ctx.storage.save_project(&project).await?;
     tracing::info!("Created new project: {}", project_name);
-    Ok(project.id)
+    Ok(project)
 }
 
 pub async fn run(args: &RelationArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Result<()> {
@@ -141,30 +210,13 @@ pub async fn run(args: &RelationArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Re
             }
         }
         RelationCommands::List { from, to, r#type } => {
-            let project_id = get_project_id(&cli.project, ctx).await?;
-            let relations = ctx.storage.get_all_relations(&project_id).await?;
-
-            let filtered: Vec<_> = relations
-                .into_iter()
-                .filter(|r| {
-                    if let Some(f) = from {
-                        if r.from_name != *f {
-                            return false;
-                        }
-                    }
-                    if let Some(t) = to {
-                        if r.to_name != *t {
-                            return false;
-                        }
-                    }
-                    if let Some(rt) = r#type {
-                        if r.relation_type.to_lowercase() != rt.to_lowercase() {
-                            return false;
-                        }
-                    }
-                    true
-                })
-                .collect();
+            let project = get_project(&cli.project, ctx).await?;
+            let filter = RelationFilter {
+                from: from.clone(),
+                to: to.clone(),
+                relation_type: r#type.clone(),
+            };
+            let filtered = ops::list_relations(&*ctx.storage, &project, &filter).await?;
 
             tracing::info!("Found {} relations", filtered.len());
 
@@ -203,6 +255,7 @@ pub async fn run(args: &RelationArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Re
             direction,
             relation_types,
             entity_types,
+            cross_project,
         } => {
             let project_id = get_project_id(&cli.project, ctx).await?;
 
@@ -221,28 +274,24 @@ pub async fn run(args: &RelationArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Re
 
             // Build query
             let mut query = TraversalQuery::new(start)
+                .in_project(project_id.clone())
                 .with_depth(*depth)
                 .with_direction(dir);
 
-            if let Some(ref rtypes) = relation_types {
-                let types: Vec<String> = rtypes.split(',').map(|s| s.trim().to_string()).collect();
-                query = query.filter_relation_types(types);
+            if *cross_project {
+                query = query.cross_project();
+            }
+
+            if relation_types.is_some() {
+                query = query.filter_relation_types(split_list(relation_types));
             }
 
-            if let Some(ref etypes) = entity_types {
-                let types: Vec<String> = etypes.split(',').map(|s| s.trim().to_string()).collect();
-                query = query.filter_entity_types(types);
+            if entity_types.is_some() {
+                query = query.filter_entity_types(split_list(entity_types));
             }
 
             // Load data
-            let entities: HashMap<String, _> = ctx
-                .storage
-                .get_all_entities(&project_id)
-                .await?
-                .into_iter()
-                .map(|e| (e.name.clone(), e))
-                .collect();
-            let relations = ctx.storage.get_all_relations(&project_id).await?;
+            let (entities, relations) = load_graph_data(&project_id, *cross_project, ctx).await?;
 
             tracing::info!(
                 "Traversing from {} (depth: {}, direction: {})",
@@ -266,7 +315,13 @@ pub async fn run(args: &RelationArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Re
             if result.visited_entities.len() <= 1 {
                 println!("  (no connected entities found)");
             } else {
-                println!("  Entities: {}", result.visited_entities.join(", "));
+                let names = project_names(ctx).await?;
+                let visited: Vec<String> = result
+                    .visited_entities
+                    .iter()
+                    .map(|n| format_node(n, &project_id, &names))
+                    .collect();
+                println!("  Entities: {}", visited.join(", "));
 
                 if !result.relations.is_empty() {
                     println!("  Relations:");
@@ -290,58 +345,30 @@ pub async fn run(args: &RelationArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Re
             relation_types,
             entity_types,
             max_depth,
+            cross_project,
+            to_project,
         } => {
-            let project_id = get_project_id(&cli.project, ctx).await?;
-
-            // Check if both entities exist
-            if ctx.storage.get_entity(from, &project_id).await?.is_none() {
-                println!("Entity '{}' not found in project '{}'", from, cli.project);
-                return Ok(());
-            }
-            if ctx.storage.get_entity(to, &project_id).await?.is_none() {
-                println!("Entity '{}' not found in project '{}'", to, cli.project);
-                return Ok(());
-            }
-
-            // Build query
-            let mut query = TraversalQuery::new(from)
-                .find_path_to(to)
-                .with_depth(*max_depth);
-
-            if *weighted {
-                query = query.weighted();
-            }
-
-            if let Some(ref rtypes) = relation_types {
-                let types: Vec<String> = rtypes.split(',').map(|s| s.trim().to_string()).collect();
-                query = query.filter_relation_types(types);
-            }
-
-            if let Some(ref etypes) = entity_types {
-                let types: Vec<String> = etypes.split(',').map(|s| s.trim().to_string()).collect();
-                query = query.filter_entity_types(types);
-            }
-
-            // Load data
-            let entities: HashMap<String, _> = ctx
-                .storage
-                .get_all_entities(&project_id)
-                .await?
-                .into_iter()
-                .map(|e| (e.name.clone(), e))
-                .collect();
-            let relations = ctx.storage.get_all_relations(&project_id).await?;
-
-            tracing::info!(
-                "Finding path from {} to {} (weighted: {}, max_depth: {})",
-                from,
-                to,
-                weighted,
-                max_depth
-            );
+            let project = get_project(&cli.project, ctx).await?;
+            let request = PathRequest {
+                to_project: to_project.clone(),
+                weighted: *weighted,
+                relation_types: split_list(relation_types),
+                entity_types: split_list(entity_types),
+                max_depth: *max_depth,
+                cross_project: *cross_project,
+                ..PathRequest::new(from, to)
+            };
 
-            // Execute path finding
-            let result = TraversalEngine::execute(&query, &entities, &relations);
+            let result =
+                match ops::find_path(&*ctx.storage, &Scope::all(), &project, &request).await {
+                    Ok(result) => result,
+                    Err(e @ (OpsError::EntityNotFound { .. } | OpsError::ProjectNotFound(_))) => {
+                        println!("{}", e);
+                        return Ok(());
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+            let project_id = project.id;
 
             if result.paths.is_empty() {
                 println!("No path found from '{}' to '{}'", from, to);
@@ -350,6 +377,7 @@ pub async fn run(args: &RelationArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Re
                     result.stats.nodes_visited, result.stats.edges_traversed
                 );
             } else {
+                let names = project_names(ctx).await?;
                 let algo = if *weighted { "Dijkstra" } else { "BFS" };
                 println!("Path found from '{}' to '{}' using {}:", from, to, algo);
 
@@ -360,7 +388,12 @@ pub async fn run(args: &RelationArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Re
                         path.length,
                         path.total_weight
                     );
-                    println!("  Route: {}", path.nodes.join(" -> "));
+                    let route: Vec<String> = path
+                        .nodes
+                        .iter()
+                        .map(|n| format_node(n, &project_id, &names))
+                        .collect();
+                    println!("  Route: {}", route.join(" -> "));
 
                     for edge in &path.edges {
                         let weight_str = edge
@@ -5,7 +5,7 @@ use std::collections::{HashMap, HashSet};
 use clap::Args;
 
 use crate::{AppContext, Cli};
-use parsnip_core::{ProjectId, Relation, SearchMode, SearchQuery};
+use parsnip_core::{GraphExpansion, ProjectId, Relation, SearchMode, SearchQuery};
 use parsnip_search::{ExactSearchEngine, FuzzySearchEngine, SearchEngine};
 use parsnip_storage::StorageBackend;
 
@@ -45,6 +45,22 @@ pub struct SearchArgs {
     /// Include relations in output
     #[arg(long)]
     pub include_relations: bool,
+
+    /// Also surface entities linked to direct hits (graph-aware re-ranking)
+    #[arg(long)]
+    pub expand: bool,
+
+    /// Expansion algorithm: spreading or pagerank
+    #[arg(long, default_value = "spreading")]
+    pub expand_algorithm: String,
+
+    /// Maximum hops for spreading activation
+    #[arg(long, default_value = "2")]
+    pub expand_hops: u32,
+
+    /// Score decay per hop (spreading) or damping factor (pagerank)
+    #[arg(long)]
+    pub expand_decay: Option<f32>,
 }
 
 async fn get_project_id(project_name: &str, ctx: &AppContext) -> anyhow::Result<ProjectId> {
@@ -112,35 +128,52 @@ pub async fn run(args: &SearchArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Resu
         ctx.storage.get_all_entities(&project_id).await?
     };
 
-    // Perform search based on mode
-    let results = match query.mode {
-        SearchMode::Fuzzy => {
-            let search_engine = FuzzySearchEngine::new();
-            search_engine.search(&query, &entities).await?
-        }
+    if args.expand {
+        let expansion = match args.expand_algorithm.as_str() {
+            "pagerank" | "ppr" => GraphExpansion::page_rank(args.expand_decay.unwrap_or(0.85)),
+            _ => GraphExpansion::spreading(args.expand_hops, args.expand_decay.unwrap_or(0.5)),
+        };
+        query = query.with_graph_expansion(expansion);
+    }
+
+    // Relations are only needed when propagating scores over the graph
+    let relations = if !args.expand {
+        Vec::new()
+    } else if args.all_projects {
+        ctx.storage.get_all_relations_all_projects().await?
+    } else {
+        let project_id = get_project_id(&cli.project, ctx).await?;
+        ctx.storage.get_all_relations(&project_id).await?
+    };
+
+    // Pick the search engine based on mode
+    let fuzzy = FuzzySearchEngine::new();
+    let exact = ExactSearchEngine::new();
+    let engine: &dyn SearchEngine = match query.mode {
+        SearchMode::Fuzzy => &fuzzy,
         #[cfg(feature = "fulltext")]
-        SearchMode::FullText | SearchMode::Hybrid => {
-            if let Some(ref fulltext) = ctx.fulltext {
-                use parsnip_search::SearchEngine;
-                fulltext.search(&query, &entities).await?
-            } else {
+        SearchMode::FullText | SearchMode::Hybrid => match ctx.fulltext {
+            Some(ref fulltext) => fulltext.as_ref(),
+            None => {
                 tracing::warn!("Full-text search not available, falling back to exact search");
-                let search_engine = ExactSearchEngine::new();
-                search_engine.search(&query, &entities).await?
+                &exact
             }
-        }
+        },
         #[cfg(not(feature = "fulltext"))]
         SearchMode::FullText | SearchMode::Hybrid => {
             tracing::warn!("Full-text search not enabled, falling back to exact search");
-            let search_engine = ExactSearchEngine::new();
-            search_engine.search(&query, &entities).await?
-        }
-        _ => {
-            let search_engine = ExactSearchEngine::new();
-            search_engine.search(&query, &entities).await?
+            &exact
         }
+        _ => &exact,
     };
 
+    let results: Vec<_> = engine
+        .search_with_graph(&query, &entities, &relations)
+        .await?
+        .into_iter()
+        .map(|hit| hit.entity)
+        .collect();
+
     let display_results: Vec<_> = results.into_iter().take(args.limit).collect();
 
     tracing::info!(
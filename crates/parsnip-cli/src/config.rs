@@ -1,6 +1,9 @@
 //! CLI configuration with TOML support
 
+use parsnip_mcp::Permissions;
+use parsnip_storage::RecordFormat;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// Get default config directory
@@ -43,6 +46,149 @@ pub struct Config {
     /// Default output format (table, json, csv)
     #[serde(default = "default_output_format")]
     pub output_format: String,
+
+    /// Storage backend of the data directory (redb, sqlite, memory)
+    #[serde(default)]
+    pub backend: Backend,
+
+    /// Format records are written in (json, binary, compressed); records in
+    /// any format are read
+    #[serde(default)]
+    pub encoding: RecordFormat,
+
+    /// Named permission profiles for tokens of `parsnip serve`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Permissions>,
+
+    /// Bearer tokens accepted by the HTTP transport
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<TokenConfig>,
+
+    /// TLS of the HTTP transport
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+}
+
+/// Storage backend holding the knowledge graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// `parsnip.redb` in the data directory
+    Redb,
+    /// `parsnip.sqlite` in the data directory
+    Sqlite,
+    /// Nothing kept once the command exits
+    Memory,
+}
+
+impl Backend {
+    /// Database file of the backend in a data directory
+    pub fn file_name(self) -> Option<&'static str> {
+        match self {
+            Self::Redb => Some("parsnip.redb"),
+            Self::Sqlite => Some("parsnip.sqlite"),
+            Self::Memory => None,
+        }
+    }
+}
+
+impl Default for Backend {
+    /// ReDB, unless it is compiled out
+    fn default() -> Self {
+        if cfg!(feature = "redb") || !cfg!(feature = "sqlite") {
+            Self::Redb
+        } else {
+            Self::Sqlite
+        }
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Redb => "redb",
+            Self::Sqlite => "sqlite",
+            Self::Memory => "memory",
+        })
+    }
+}
+
+impl std::str::FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "redb" => Ok(Self::Redb),
+            "sqlite" => Ok(Self::Sqlite),
+            "memory" => Ok(Self::Memory),
+            _ => anyhow::bail!("Invalid backend: {} (expected redb, sqlite or memory)", s),
+        }
+    }
+}
+
+/// A bearer token for the HTTP transport, as written in `[[tokens]]`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TokenConfig {
+    /// Name of the token in logs
+    pub label: String,
+    pub token: String,
+    /// Profile limiting the token; full access when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+impl std::fmt::Debug for TokenConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenConfig")
+            .field("label", &self.label)
+            .field("profile", &self.profile)
+            .finish_non_exhaustive()
+    }
+}
+
+/// TLS of the HTTP transport, as written in `[tls]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain served to clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    /// Private key of `cert`, in PEM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    /// Serve a self-signed certificate, kept in the data directory, when no
+    /// `cert` is given
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub self_signed: bool,
+    /// Host names and addresses the self-signed certificate is for, besides
+    /// localhost and the bound host
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub names: Vec<String>,
+    /// PEM file of the CAs whose client certificates are accepted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca: Option<PathBuf>,
+    /// Refuse connections without a client certificate
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_client_cert: bool,
+    /// Client certificates that authenticate requests, as bearer tokens do
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clients: Vec<ClientCertConfig>,
+}
+
+impl TlsConfig {
+    /// Whether the server is to serve HTTPS
+    pub fn enabled(&self) -> bool {
+        self.cert.is_some() || self.self_signed
+    }
+}
+
+/// A client certificate, as written in `[[tls.clients]]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientCertConfig {
+    /// Subject common name of the certificate
+    pub name: String,
+    /// Profile limiting the client; full access when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
 }
 
 fn default_project() -> String {
@@ -64,6 +210,11 @@ impl Default for Config {
             data_dir: None,
             log_level: default_log_level(),
             output_format: default_output_format(),
+            backend: Backend::default(),
+            encoding: RecordFormat::default(),
+            profiles: BTreeMap::new(),
+            tokens: Vec::new(),
+            tls: None,
         }
     }
 }
@@ -114,6 +265,8 @@ impl Config {
             "data_dir" => self.data_dir.as_ref().map(|p| p.display().to_string()),
             "log_level" => Some(self.log_level.clone()),
             "output_format" => Some(self.output_format.clone()),
+            "backend" => Some(self.backend.to_string()),
+            "encoding" => Some(self.encoding.to_string()),
             _ => None,
         }
     }
@@ -135,6 +288,14 @@ impl Config {
                 }
                 self.output_format = value.to_string();
             }
+            "backend" => self.backend = value.parse()?,
+            "encoding" => {
+                let format: RecordFormat = value.parse().map_err(anyhow::Error::msg)?;
+                if !format.is_available() {
+                    anyhow::bail!("The {} encoding needs --features zstd", format);
+                }
+                self.encoding = format;
+            }
             _ => anyhow::bail!("Unknown config key: {}", key),
         }
         Ok(())
@@ -142,7 +303,42 @@ impl Config {
 
     /// List all config keys
     pub fn keys() -> Vec<&'static str> {
-        vec!["default_project", "data_dir", "log_level", "output_format"]
+        vec![
+            "default_project",
+            "data_dir",
+            "log_level",
+            "output_format",
+            "backend",
+            "encoding",
+        ]
+    }
+
+    /// Permissions of a configured token, from its profile
+    #[cfg_attr(not(feature = "sse"), allow(dead_code))]
+    pub fn token_permissions(&self, token: &TokenConfig) -> anyhow::Result<Permissions> {
+        self.profile_permissions(token.profile.as_deref())
+            .map_err(|name| {
+                anyhow::anyhow!("Token '{}' uses unknown profile '{}'", token.label, name)
+            })
+    }
+
+    /// Permissions of a configured client certificate, from its profile
+    #[cfg_attr(not(feature = "tls"), allow(dead_code))]
+    pub fn client_permissions(&self, client: &ClientCertConfig) -> anyhow::Result<Permissions> {
+        self.profile_permissions(client.profile.as_deref())
+            .map_err(|name| {
+                anyhow::anyhow!("Client '{}' uses unknown profile '{}'", client.name, name)
+            })
+    }
+
+    /// Permissions of a profile, full access for none, or the name of an
+    /// unknown profile
+    #[cfg_attr(not(feature = "sse"), allow(dead_code))]
+    fn profile_permissions<'a>(&self, profile: Option<&'a str>) -> Result<Permissions, &'a str> {
+        match profile {
+            None => Ok(Permissions::new()),
+            Some(name) => self.profiles.get(name).cloned().ok_or(name),
+        }
     }
 
     /// Get the effective data directory
@@ -191,6 +387,124 @@ mod tests {
         assert_eq!(config.get("log_level"), Some("info".to_string()));
     }
 
+    #[test]
+    fn test_backend() {
+        let mut config = Config::default();
+        assert_eq!(config.get("backend"), Some(Backend::default().to_string()));
+
+        config.set("backend", "sqlite").unwrap();
+        assert_eq!(config.backend, Backend::Sqlite);
+        assert!(config.set("backend", "postgres").is_err());
+
+        let saved: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(saved.backend, Backend::Sqlite);
+        let loaded: Config = toml::from_str(r#"backend = "memory""#).unwrap();
+        assert_eq!(loaded.backend, Backend::Memory);
+        assert_eq!(Backend::Memory.file_name(), None);
+    }
+
+    #[test]
+    fn test_encoding() {
+        let mut config = Config::default();
+        assert_eq!(config.get("encoding"), Some("binary".to_string()));
+
+        config.set("encoding", "json").unwrap();
+        assert_eq!(config.encoding, RecordFormat::Json);
+        assert!(config.set("encoding", "msgpack").is_err());
+        assert_eq!(
+            config.set("encoding", "compressed").is_ok(),
+            RecordFormat::Compressed.is_available()
+        );
+
+        let loaded: Config = toml::from_str(r#"encoding = "json""#).unwrap();
+        assert_eq!(loaded.encoding, RecordFormat::Json);
+    }
+
+    #[test]
+    fn test_token_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            [profiles.recall]
+            read_only = true
+            projects = ["work", "personal"]
+
+            [[tokens]]
+            label = "laptop"
+            token = "t1"
+
+            [[tokens]]
+            label = "assistant"
+            token = "t2"
+            profile = "recall"
+
+            [[tokens]]
+            label = "typo"
+            token = "t3"
+            profile = "recal"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.default_project, "default");
+        assert_eq!(
+            config.token_permissions(&config.tokens[0]).unwrap(),
+            Permissions::new()
+        );
+        let recall = config.token_permissions(&config.tokens[1]).unwrap();
+        assert!(recall.read_only);
+        assert!(recall.allows_project("work"));
+        assert!(!recall.allows_project("secret"));
+        assert!(config.token_permissions(&config.tokens[2]).is_err());
+
+        let saved: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(saved.profiles, config.profiles);
+        assert_eq!(saved.tokens.len(), 3);
+    }
+
+    #[test]
+    fn test_tls_clients() {
+        let config: Config = toml::from_str(
+            r#"
+            [profiles.recall]
+            read_only = true
+
+            [tls]
+            self_signed = true
+            names = ["parsnip.lan", "192.168.1.5"]
+            client_ca = "/etc/parsnip/ca.pem"
+
+            [[tls.clients]]
+            name = "laptop"
+
+            [[tls.clients]]
+            name = "assistant"
+            profile = "recall"
+
+            [[tls.clients]]
+            name = "typo"
+            profile = "recal"
+            "#,
+        )
+        .unwrap();
+
+        let tls = config.tls.as_ref().unwrap();
+        assert!(tls.enabled());
+        assert!(!tls.require_client_cert);
+        assert_eq!(tls.names.len(), 2);
+        assert_eq!(
+            config.client_permissions(&tls.clients[0]).unwrap(),
+            Permissions::new()
+        );
+        assert!(
+            config
+                .client_permissions(&tls.clients[1])
+                .unwrap()
+                .read_only
+        );
+        assert!(config.client_permissions(&tls.clients[2]).is_err());
+        assert!(!TlsConfig::default().enabled());
+    }
+
     #[test]
     fn test_invalid_values() {
         let mut config = Config::default();
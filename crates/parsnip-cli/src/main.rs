@@ -3,22 +3,37 @@
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use clap::{Args, Parser, Subcommand};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 mod commands;
 mod config;
 mod output;
 
-use commands::{completions, config as config_cmd, entity, io, project, relation, search};
-use parsnip_mcp::McpServer;
+use commands::{
+    audit, changes, completions, config as config_cmd, context, daemon, db, doctor, entity, io,
+    project, relation, search,
+};
+use parsnip_mcp::prompts::PromptLibrary;
+use parsnip_mcp::{McpServer, Metrics, Permissions};
+use parsnip_storage::{
+    Actor, AuditedStorage, CachedStorage, ChangeFeed, ChangeWatcher, InstrumentedStorage,
+    StorageBackend, StorageCache, StorageMetrics, Transport,
+};
+
+#[cfg(unix)]
+use parsnip_storage::{DaemonClient, SOCKET_FILE};
 
 #[cfg(feature = "redb")]
 use parsnip_storage::RedbStorage;
 
-#[cfg(all(feature = "sqlite", not(feature = "redb")))]
+#[cfg(feature = "sqlite")]
 use parsnip_storage::SqliteStorage;
 
+use config::Backend;
+use parsnip_storage::MemoryStorage;
+
 #[cfg(feature = "fulltext")]
 use parsnip_search::FullTextSearchEngine;
 
@@ -50,10 +65,33 @@ pub struct Cli {
     #[arg(short, long, global = true)]
     pub quiet: bool,
 
+    /// Log line format
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "PARSNIP_LOG_FORMAT",
+        global = true
+    )]
+    pub log_format: LogFormat,
+
+    /// Storage backend (default: backend in config.toml, else redb)
+    #[arg(long, value_enum, env = "PARSNIP_BACKEND", global = true)]
+    pub backend: Option<Backend>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// How log lines are written
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with the current span and its parents
+    Json,
+}
+
 impl Cli {
     /// Get the data directory path
     pub fn data_dir(&self) -> PathBuf {
@@ -66,6 +104,12 @@ impl Cli {
                     .join("parsnip")
             })
     }
+
+    /// Storage backend given here, or else the configured one
+    pub fn backend(&self) -> Backend {
+        self.backend
+            .unwrap_or_else(|| config::Config::load().backend)
+    }
 }
 
 #[derive(Subcommand)]
@@ -76,14 +120,26 @@ pub enum Commands {
     Relation(relation::RelationArgs),
     /// Search the knowledge graph
     Search(search::SearchArgs),
+    /// Build a token-budgeted context pack for a topic
+    Context(context::ContextArgs),
     /// Manage projects
     Project(project::ProjectArgs),
     /// Import data from JSON file
     Import(io::ImportArgs),
     /// Export data to JSON file
     Export(io::ExportArgs),
+    /// Check storage consistency and optionally repair it
+    Doctor(doctor::DoctorArgs),
+    /// Show who changed the knowledge graph, and when
+    Audit(audit::AuditArgs),
+    /// Print the change log of the knowledge graph as NDJSON
+    Changes(changes::ChangesArgs),
     /// Start MCP server
     Serve(ServeArgs),
+    /// Share the database with other processes through a background daemon
+    Daemon(daemon::DaemonArgs),
+    /// Manage the database files of the storage backends
+    Db(db::DbArgs),
     /// Manage configuration
     Config(config_cmd::ConfigArgs),
     /// Generate shell completions
@@ -93,37 +149,178 @@ pub enum Commands {
 /// Arguments for the serve command
 #[derive(Args)]
 pub struct ServeArgs {
-    /// Transport type: stdio or sse
+    /// Transport type: stdio or http (sse is an alias)
     #[arg(short, long, default_value = "stdio")]
     pub transport: String,
 
-    /// Port for SSE transport (default: 3000)
+    /// Port for HTTP transport (default: 3000)
     #[arg(long, default_value = "3000")]
     pub port: u16,
 
-    /// Host to bind for SSE transport
+    /// Host to bind for HTTP transport
     #[arg(long, default_value = "127.0.0.1")]
     pub host: String,
 
-    /// Auth token for SSE transport (required for non-localhost)
+    /// Auth token for HTTP transport (required for non-localhost)
     #[arg(long, env = "PARSNIP_AUTH_TOKEN")]
     pub auth_token: Option<String>,
 
     /// Allow binding to non-localhost addresses (requires --auth-token)
     #[arg(long)]
     pub allow_remote: bool,
+
+    /// Also serve the legacy HTTP+SSE endpoints (/sse and /message)
+    #[arg(long)]
+    pub legacy_sse: bool,
+
+    /// Only serve tools that do not change the graph
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// Only serve these tools (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub allow_tools: Option<Vec<String>>,
+
+    /// Never serve these tools (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub deny_tools: Vec<String>,
+
+    /// Only expose these projects (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub projects: Option<Vec<String>>,
+
+    /// Memory for caching the entities and relations of recently used
+    /// projects, in MiB (0 turns the cache off)
+    #[arg(long, default_value = "64")]
+    pub cache_mb: usize,
+
+    /// Serve HTTPS with this PEM certificate chain
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Private key of --tls-cert, in PEM
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Serve HTTPS with a self-signed certificate kept in the data directory
+    #[arg(long, conflicts_with = "tls_cert")]
+    pub tls_self_signed: bool,
+
+    /// More host names or addresses for the self-signed certificate (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub tls_name: Vec<String>,
+
+    /// Accept client certificates signed by the CAs in this PEM file
+    #[arg(long)]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// Refuse connections without a client certificate (needs --tls-client-ca
+    /// or client_ca in config.toml)
+    #[arg(long)]
+    pub tls_require_client_cert: bool,
 }
 
-// Storage type alias based on feature
-#[cfg(feature = "redb")]
-pub type Storage = RedbStorage;
+impl ServeArgs {
+    /// Permissions applied to every client of the server
+    pub fn permissions(&self) -> Permissions {
+        let mut permissions = Permissions::new()
+            .with_read_only(self.read_only)
+            .with_denied_tools(&self.deny_tools);
+        if let Some(tools) = &self.allow_tools {
+            permissions = permissions.with_allowed_tools(tools);
+        }
+        if let Some(projects) = &self.projects {
+            permissions = permissions.with_projects(projects);
+        }
+        permissions
+    }
+
+    /// TLS settings of config.toml, overridden by those given here
+    #[cfg_attr(not(feature = "sse"), allow(dead_code))]
+    pub fn tls(&self, config: &config::Config) -> config::TlsConfig {
+        let mut tls = config.tls.clone().unwrap_or_default();
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            tls.cert = Some(cert.clone());
+            tls.key = Some(key.clone());
+        }
+        if self.tls_self_signed {
+            tls.cert = None;
+            tls.key = None;
+            tls.self_signed = true;
+        }
+        tls.names.extend(self.tls_name.iter().cloned());
+        if let Some(ca) = &self.tls_client_ca {
+            tls.client_ca = Some(ca.clone());
+        }
+        tls.require_client_cert |= self.tls_require_client_cert;
+        tls
+    }
+}
+
+/// TLS options of the HTTP transport bound to `host`
+#[cfg(feature = "tls")]
+fn tls_options(
+    tls: &config::TlsConfig,
+    config: &config::Config,
+    host: &str,
+    data_dir: &Path,
+) -> anyhow::Result<parsnip_mcp::TlsOptions> {
+    use parsnip_mcp::{ClientCert, ServerCert, TlsOptions};
+
+    let cert = match (&tls.cert, &tls.key) {
+        (Some(cert), Some(key)) => ServerCert::Files {
+            cert: cert.clone(),
+            key: key.clone(),
+        },
+        (Some(_), None) => anyhow::bail!("TLS certificate given without its key"),
+        _ => {
+            // Valid for local clients, the bound host and any names given
+            let mut names: Vec<String> = ["localhost", "127.0.0.1", "::1"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect();
+            let unspecified = host
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_unspecified());
+            let bound = (!unspecified).then_some(host);
+            for name in bound
+                .into_iter()
+                .chain(tls.names.iter().map(String::as_str))
+            {
+                if !names.iter().any(|known| known == name) {
+                    names.push(name.to_string());
+                }
+            }
+            ServerCert::SelfSigned {
+                dir: data_dir.join("tls"),
+                names,
+            }
+        }
+    };
 
-#[cfg(all(feature = "sqlite", not(feature = "redb")))]
-pub type Storage = SqliteStorage;
+    let mut options = TlsOptions::new(cert).with_require_client_cert(tls.require_client_cert);
+    match &tls.client_ca {
+        Some(ca) => options = options.with_client_ca(ca),
+        None if tls.require_client_cert || !tls.clients.is_empty() => {
+            anyhow::bail!("Client certificates need a CA: set --tls-client-ca or tls.client_ca")
+        }
+        None => {}
+    }
+    for client in &tls.clients {
+        options = options.with_client(
+            ClientCert::new(&client.name).with_permissions(config.client_permissions(client)?),
+        );
+    }
+    Ok(options)
+}
 
 /// Application context with storage and search backends
 pub struct AppContext {
-    pub storage: Arc<Storage>,
+    /// Storage noting what the command writes, for the audit log; the
+    /// daemon's when one is running
+    pub storage: Arc<AuditedStorage<dyn StorageBackend>>,
+    /// Database file backing `storage`; none for the memory backend
+    pub db_path: Option<PathBuf>,
     #[cfg(feature = "fulltext")]
     pub fulltext: Option<Arc<FullTextSearchEngine>>,
 }
@@ -151,23 +348,86 @@ fn create_secure_dir(path: &Path) -> std::io::Result<()> {
     }
 }
 
+/// Open the database of a backend in a data directory, for this process
+/// alone, with the file backing it
+///
+/// Records are written in the configured encoding.
+pub fn open_storage(
+    data_dir: &Path,
+    backend: Backend,
+) -> anyhow::Result<(Arc<dyn StorageBackend>, Option<PathBuf>)> {
+    let db_path = backend.file_name().map(|file| data_dir.join(file));
+    let format = config::Config::load().encoding;
+    if !format.is_available() {
+        anyhow::bail!(
+            "The {} encoding is not available. Rebuild with --features zstd",
+            format
+        );
+    }
+
+    let storage: Arc<dyn StorageBackend> = match (backend, &db_path) {
+        #[cfg(feature = "redb")]
+        (Backend::Redb, Some(db_path)) => {
+            tracing::debug!("Using ReDB database at: {:?}", db_path);
+            Arc::new(RedbStorage::open_with_format(db_path, format).map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to open {:?}: {}\n\
+                     If another parsnip process has it open, run `parsnip daemon start` \
+                     to share it",
+                    db_path,
+                    e
+                )
+            })?)
+        }
+        #[cfg(feature = "sqlite")]
+        (Backend::Sqlite, Some(db_path)) => {
+            tracing::debug!("Using SQLite database at: {:?}", db_path);
+            Arc::new(SqliteStorage::open_with_format(db_path, format)?)
+        }
+        (Backend::Memory, _) => {
+            tracing::debug!("Using in-memory storage; nothing is kept");
+            Arc::new(MemoryStorage::new())
+        }
+        (backend, _) => anyhow::bail!(
+            "The {} backend is not available. Rebuild with --features {}",
+            backend,
+            backend
+        ),
+    };
+    Ok((storage, db_path))
+}
+
 impl AppContext {
     pub async fn new(cli: &Cli) -> anyhow::Result<Self> {
         let data_dir = cli.data_dir();
         create_secure_dir(&data_dir)?;
 
-        #[cfg(feature = "redb")]
-        let storage = {
-            let db_path = data_dir.join("parsnip.redb");
-            tracing::debug!("Using ReDB database at: {:?}", db_path);
-            RedbStorage::open(&db_path)?
-        };
-
-        #[cfg(all(feature = "sqlite", not(feature = "redb")))]
-        let storage = {
-            let db_path = data_dir.join("parsnip.sqlite");
-            tracing::debug!("Using SQLite database at: {:?}", db_path);
-            SqliteStorage::open(&db_path)?
+        // Share the database of a running daemon rather than opening it
+        #[cfg(unix)]
+        let daemon = DaemonClient::connect(data_dir.join(SOCKET_FILE)).await.ok();
+        #[cfg(not(unix))]
+        let daemon: Option<Arc<dyn StorageBackend>> = None;
+        let backend = cli.backend();
+        let (storage, db_path): (Arc<dyn StorageBackend>, _) = match daemon {
+            Some(client) => {
+                // Serving another backend's store would read and write the
+                // wrong database
+                let status = client.status().await?;
+                if status.backend != backend.to_string() {
+                    anyhow::bail!(
+                        "The running daemon (pid {}) serves the {} backend, not {}. \
+                         Stop it with `parsnip daemon stop` or use --backend {}",
+                        status.pid,
+                        status.backend,
+                        backend,
+                        status.backend
+                    );
+                }
+                tracing::debug!("Using the database of the running daemon");
+                let db_path = backend.file_name().map(|file| data_dir.join(file));
+                (Arc::new(client), db_path)
+            }
+            None => open_storage(&data_dir, backend)?,
         };
 
         // Initialize full-text search index
@@ -188,19 +448,28 @@ impl AppContext {
         };
 
         Ok(Self {
-            storage: Arc::new(storage),
+            storage: Arc::new(AuditedStorage::new(storage, Actor::new(Transport::Cli))),
+            db_path,
             #[cfg(feature = "fulltext")]
             fulltext,
         })
     }
 }
 
-// Use current_thread runtime for faster CLI cold start
-// Multi-threaded runtime is overkill for CLI operations
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+fn main() -> anyhow::Result<()> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Servers answer many clients at once, on every core; other commands
+    // use a current_thread runtime for faster cold start
+    let mut runtime = match cli.command {
+        Commands::Serve(_) | Commands::Daemon(_) => tokio::runtime::Builder::new_multi_thread(),
+        _ => tokio::runtime::Builder::new_current_thread(),
+    };
+    runtime.enable_all().build()?.block_on(run(cli, matches))
+}
 
+async fn run(cli: Cli, matches: ArgMatches) -> anyhow::Result<()> {
     // Set up logging based on verbosity
     let filter = match cli.verbose {
         0 if cli.quiet => "error",
@@ -210,64 +479,222 @@ async fn main() -> anyhow::Result<()> {
         _ => "trace",
     };
 
-    tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| filter.into()))
-        .init();
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| filter.into());
+    match cli.log_format {
+        LogFormat::Text => tracing_subscriber::registry()
+            .with(fmt::layer())
+            .with(filter)
+            .init(),
+        LogFormat::Json => tracing_subscriber::registry()
+            .with(
+                fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true),
+            )
+            .with(filter)
+            .init(),
+    }
 
     tracing::debug!("Starting parsnip CLI");
 
+    // The daemon opens the database itself, or talks to the one running
+    if let Commands::Daemon(args) = &cli.command {
+        return daemon::run(args, &cli).await;
+    }
+    // Converting opens both databases itself
+    if let Commands::Db(args) = &cli.command {
+        return db::run(args, &cli).await;
+    }
+
     // Initialize storage
     let ctx = AppContext::new(&cli).await?;
 
-    match &cli.command {
-        Commands::Entity(args) => entity::run(args, &cli, &ctx).await?,
-        Commands::Relation(args) => relation::run(args, &cli, &ctx).await?,
-        Commands::Search(args) => search::run(args, &cli, &ctx).await?,
-        Commands::Project(args) => project::run(args, &cli, &ctx).await?,
-        Commands::Import(args) => io::run_import(args, &cli, &ctx).await?,
-        Commands::Export(args) => io::run_export(args, &cli, &ctx).await?,
-        Commands::Serve(args) => {
-            let server = Arc::new(McpServer::new(ctx.storage.clone()));
-            match args.transport.as_str() {
-                #[cfg(feature = "sse")]
-                "sse" | "http" => {
-                    let is_localhost =
-                        args.host == "127.0.0.1" || args.host == "localhost" || args.host == "::1";
-
-                    // Security: require --allow-remote for non-localhost binding
-                    if !is_localhost && !args.allow_remote {
-                        anyhow::bail!(
-                            "Binding to {} requires --allow-remote flag.\n\
-                             WARNING: This exposes your knowledge graph to the network!",
-                            args.host
-                        );
-                    }
-
-                    // Security: require auth token for non-localhost or if specified
-                    if !is_localhost && args.auth_token.is_none() {
-                        anyhow::bail!(
-                            "Non-localhost binding requires --auth-token or PARSNIP_AUTH_TOKEN env var"
-                        );
-                    }
-
-                    let addr = format!("{}:{}", args.host, args.port);
-                    tracing::info!("Starting MCP server with SSE transport on {}", addr);
-                    parsnip_mcp::run_sse_server(server, &addr, args.auth_token.clone()).await?;
-                }
-                #[cfg(not(feature = "sse"))]
-                "sse" | "http" => {
-                    anyhow::bail!("SSE transport not available. Rebuild with --features sse");
-                }
-                _ => {
-                    tracing::info!("Starting MCP server on stdio...");
-                    server.run_stdio().await?;
-                }
-            }
+    let result = match &cli.command {
+        Commands::Entity(args) => entity::run(args, &cli, &ctx).await,
+        Commands::Relation(args) => relation::run(args, &cli, &ctx).await,
+        Commands::Search(args) => search::run(args, &cli, &ctx).await,
+        Commands::Context(args) => context::run(args, &cli, &ctx).await,
+        Commands::Project(args) => project::run(args, &cli, &ctx).await,
+        Commands::Import(args) => io::run_import(args, &cli, &ctx).await,
+        Commands::Export(args) => io::run_export(args, &cli, &ctx).await,
+        Commands::Doctor(args) => doctor::run(args, &cli, &ctx).await,
+        Commands::Audit(args) => audit::run(args, &cli, &ctx).await,
+        Commands::Changes(args) => changes::run(args, &ctx).await,
+        Commands::Serve(args) => serve(args, &cli, &ctx).await,
+        Commands::Daemon(_) | Commands::Db(_) => unreachable!("handled before opening storage"),
+        Commands::Config(args) => config_cmd::run(args).await,
+        Commands::Completions(args) => completions::run(args),
+    };
+
+    // Log what the command wrote, even if it failed part way
+    let (operation, arguments) = invocation(&matches);
+    if let Err(e) = ctx.storage.record(&operation, arguments).await {
+        tracing::error!("Failed to append {} to the audit log: {}", operation, e);
+    }
+
+    result
+}
+
+/// Name of the subcommand run, e.g. `entity add`, and the arguments given
+/// to it on the command line or through the environment
+fn invocation(matches: &ArgMatches) -> (String, serde_json::Value) {
+    let mut command = Cli::command();
+    let mut names = Vec::new();
+    let mut leaf = matches;
+    while let Some((name, sub)) = leaf.subcommand() {
+        names.push(name.to_string());
+        command = command.find_subcommand(name).cloned().unwrap_or(command);
+        leaf = sub;
+    }
+
+    let mut arguments = serde_json::Map::new();
+    // Arguments of the command itself, not the groups clap derives for them
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        let given = matches!(
+            leaf.value_source(id),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        );
+        if !given {
+            continue;
         }
-        Commands::Config(args) => config_cmd::run(args).await?,
-        Commands::Completions(args) => completions::run(args)?,
+        let values: Vec<serde_json::Value> = leaf
+            .get_raw(id)
+            .into_iter()
+            .flatten()
+            .map(|value| value.to_string_lossy().into())
+            .collect();
+        let value = match values.len() {
+            // Flags carry no value
+            0 => serde_json::Value::Bool(true),
+            1 => values.into_iter().next().unwrap_or_default(),
+            _ => values.into(),
+        };
+        arguments.insert(id.to_string(), value);
+    }
+    (names.join(" "), arguments.into())
+}
+
+#[cfg_attr(not(feature = "fulltext"), allow(unused_variables))]
+async fn serve(args: &ServeArgs, cli: &Cli, ctx: &AppContext) -> anyhow::Result<()> {
+    // The server audits each client's writes itself
+    let storage = ctx.storage.inner();
+    // Notify resource subscribers of writes from any session or process
+    let changes = ChangeFeed::default();
+    let mut watcher = ChangeWatcher::new(storage.clone(), changes.clone());
+    // Team prompt templates live next to config.toml
+    let prompts = PromptLibrary::load_dir(&config::default_config_dir().join("prompts"));
+    // Time every storage call for /metrics and the request traces
+    let storage_metrics = Arc::new(StorageMetrics::new());
+    let storage = Arc::new(InstrumentedStorage::new(
+        storage.clone(),
+        storage_metrics.clone(),
+    ));
+    let mut metrics = Metrics::new().with_storage(storage_metrics);
+    // Answer repeated searches from memory, so the timings above are those
+    // of cache misses. The watcher drops the projects that writes by other
+    // processes touch
+    let cache = Arc::new(StorageCache::new().with_max_bytes(args.cache_mb * 1024 * 1024));
+    if cache.is_enabled() {
+        watcher = watcher.with_cache(cache.clone());
+        metrics = metrics.with_cache(cache.clone());
+    }
+    watcher.spawn();
+    let storage = CachedStorage::new(storage, cache);
+    if let Some(db_path) = &ctx.db_path {
+        metrics = metrics.with_store("database", db_path);
     }
+    #[cfg(feature = "fulltext")]
+    let metrics = metrics.with_store("fulltext_index", cli.data_dir().join("index"));
+    let transport = match args.transport.as_str() {
+        "sse" | "http" => Transport::Sse,
+        _ => Transport::Stdio,
+    };
+    let server = McpServer::new(Arc::new(storage))
+        .with_transport(transport)
+        .with_metrics(metrics)
+        .with_change_feed(changes)
+        .with_prompts(prompts)
+        .with_permissions(args.permissions());
+    // Catch typos that would silently leave a tool available
+    for name in args.allow_tools.iter().flatten().chain(&args.deny_tools) {
+        if server.tools().get(name).is_none() {
+            anyhow::bail!("Unknown tool: {}", name);
+        }
+    }
+    let server = Arc::new(server);
+    match args.transport.as_str() {
+        #[cfg(feature = "sse")]
+        "sse" | "http" => {
+            let is_localhost =
+                args.host == "127.0.0.1" || args.host == "localhost" || args.host == "::1";
+
+            // Security: require --allow-remote for non-localhost binding
+            if !is_localhost && !args.allow_remote {
+                anyhow::bail!(
+                    "Binding to {} requires --allow-remote flag.\n\
+                     WARNING: This exposes your knowledge graph to the network!",
+                    args.host
+                );
+            }
+
+            // Tokens from config.toml, each limited by its profile
+            let config = config::Config::load();
+            let mut options = parsnip_mcp::HttpOptions::new()
+                .with_auth_token(args.auth_token.clone())
+                .with_legacy_sse(args.legacy_sse);
+            for token in &config.tokens {
+                options = options.with_token(
+                    parsnip_mcp::AuthToken::new(&token.label, &token.token)
+                        .with_permissions(config.token_permissions(token)?),
+                );
+            }
+
+            let tls = args.tls(&config);
+            #[cfg(feature = "tls")]
+            if tls.enabled() {
+                options =
+                    options.with_tls(tls_options(&tls, &config, &args.host, &cli.data_dir())?);
+            }
+            #[cfg(not(feature = "tls"))]
+            if tls.enabled() {
+                anyhow::bail!("TLS not available. Rebuild with --features tls");
+            }
+            if !tls.enabled() && (tls.client_ca.is_some() || tls.require_client_cert) {
+                anyhow::bail!(
+                    "Client certificates need TLS: set --tls-cert and --tls-key, \
+                     or --tls-self-signed"
+                );
+            }
+
+            // Security: require auth token for non-localhost or if specified
+            if !is_localhost && !options.requires_auth() {
+                anyhow::bail!(
+                    "Non-localhost binding requires --auth-token, PARSNIP_AUTH_TOKEN \
+                     or [[tokens]] in config.toml"
+                );
+            }
 
+            if !is_localhost && !tls.enabled() {
+                tracing::warn!(
+                    "Serving {} without TLS: tokens and memory cross the network in cleartext",
+                    args.host
+                );
+            }
+
+            let addr = format!("{}:{}", args.host, args.port);
+            tracing::info!("Starting MCP server with HTTP transport on {}", addr);
+            parsnip_mcp::run_http_server(server, &addr, options).await?;
+        }
+        #[cfg(not(feature = "sse"))]
+        "sse" | "http" => {
+            anyhow::bail!("SSE transport not available. Rebuild with --features sse");
+        }
+        _ => {
+            tracing::info!("Starting MCP server on stdio...");
+            server.run_stdio().await?;
+        }
+    }
     Ok(())
 }
@@ -1,6 +1,7 @@
 //! Entity (node) types and operations
 
-use crate::observation::Observation;
+use crate::limits::MAX_OBSERVATION_HISTORY;
+use crate::observation::{ArchivedObservation, Observation, ObservationId};
 use crate::project::ProjectId;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -83,6 +84,11 @@ pub struct Entity {
     /// Observations (facts) about this entity
     pub observations: Vec<Observation>,
 
+    /// Observations replaced by consolidation, oldest first; only the last
+    /// [`MAX_OBSERVATION_HISTORY`] are kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub observation_history: Vec<ArchivedObservation>,
+
     /// Tags for categorization
     pub tags: Vec<String>,
 
@@ -115,6 +121,7 @@ impl Entity {
             name: name.into(),
             entity_type: entity_type.into(),
             observations: Vec::new(),
+            observation_history: Vec::new(),
             tags: Vec::new(),
             metadata: HashMap::new(),
             created_at: now,
@@ -154,6 +161,62 @@ impl Entity {
         }
     }
 
+    /// Replace some observations with others, moving them to the history
+    ///
+    /// The replacements take the place of the most recent replaced
+    /// observation. Ids that are not current observations are ignored; returns
+    /// how many observations were replaced. The history keeps the last
+    /// [`MAX_OBSERVATION_HISTORY`] replaced observations, since it is stored
+    /// and loaded with the entity; that is enough for every observation the
+    /// entity can hold, so only earlier replacements are ever dropped.
+    pub fn replace_observations(
+        &mut self,
+        replaced: &[ObservationId],
+        replacements: Vec<Observation>,
+        reason: &str,
+    ) -> usize {
+        let Some(last) = self
+            .observations
+            .iter()
+            .rposition(|o| replaced.contains(&o.id))
+        else {
+            return 0;
+        };
+
+        let now = Utc::now();
+        let replaced_by: Vec<ObservationId> = replacements.iter().map(|o| o.id.clone()).collect();
+        let mut replacements = Some(replacements);
+        let mut count = 0;
+        let mut kept = Vec::with_capacity(self.observations.len());
+        for (i, observation) in std::mem::take(&mut self.observations)
+            .into_iter()
+            .enumerate()
+        {
+            if !replaced.contains(&observation.id) {
+                kept.push(observation);
+                continue;
+            }
+            count += 1;
+            self.observation_history.push(ArchivedObservation {
+                observation,
+                replaced_by: replaced_by.clone(),
+                reason: reason.to_string(),
+                archived_at: now,
+            });
+            if i == last {
+                kept.extend(replacements.take().unwrap_or_default());
+            }
+        }
+        self.observations = kept;
+        let excess = self
+            .observation_history
+            .len()
+            .saturating_sub(MAX_OBSERVATION_HISTORY);
+        self.observation_history.drain(..excess);
+        self.updated_at = now;
+        count
+    }
+
     /// Check if entity has a specific tag
     pub fn has_tag(&self, tag: &str) -> bool {
         self.tags.iter().any(|t| t == tag)
@@ -235,4 +298,85 @@ mod tests {
         entity.remove_tag("mentor");
         assert!(!entity.has_tag("mentor"));
     }
+
+    #[test]
+    fn test_replace_observations() {
+        let mut entity = Entity::new(ProjectId::new(), "John_Smith", "person");
+        for content in [
+            "Likes tea",
+            "Works at Google",
+            "likes tea.",
+            "Lives in Oslo",
+        ] {
+            entity.add_observation(content);
+        }
+        let duplicates = vec![
+            entity.observations[0].id.clone(),
+            entity.observations[2].id.clone(),
+        ];
+
+        let merged = Observation::new("Likes tea");
+        let merged_id = merged.id.clone();
+        assert_eq!(
+            entity.replace_observations(&duplicates, vec![merged], "dedupe"),
+            2
+        );
+
+        let contents: Vec<&str> = entity
+            .observations
+            .iter()
+            .map(|o| o.content.as_str())
+            .collect();
+        assert_eq!(
+            contents,
+            vec!["Works at Google", "Likes tea", "Lives in Oslo"]
+        );
+        assert_eq!(entity.observation_history.len(), 2);
+        assert_eq!(
+            entity.observation_history[1].observation.content,
+            "likes tea."
+        );
+        assert_eq!(entity.observation_history[1].replaced_by, vec![merged_id]);
+
+        // Already replaced observations are not current any more
+        assert_eq!(
+            entity.replace_observations(&duplicates, Vec::new(), "dedupe"),
+            0
+        );
+
+        // History survives a round trip, and is absent when empty
+        let json = serde_json::to_value(&entity).unwrap();
+        let restored: Entity = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.observation_history.len(), 2);
+        let fresh = serde_json::to_value(Entity::new(ProjectId::new(), "x", "y")).unwrap();
+        assert!(fresh.get("observation_history").is_none());
+    }
+
+    #[test]
+    fn test_observation_history_is_capped() {
+        let mut entity = Entity::new(ProjectId::new(), "John_Smith", "person");
+        for i in 0..MAX_OBSERVATION_HISTORY + 5 {
+            let id = entity.add_observation(format!("Fact {}", i)).id.clone();
+            entity.replace_observations(&[id], Vec::new(), "dedupe");
+        }
+
+        // The oldest replaced observations go first
+        assert_eq!(entity.observation_history.len(), MAX_OBSERVATION_HISTORY);
+        assert_eq!(entity.observation_history[0].observation.content, "Fact 5");
+    }
+
+    #[test]
+    fn test_full_consolidation_keeps_every_original() {
+        let mut entity = Entity::new(ProjectId::new(), "John_Smith", "person");
+        let ids: Vec<ObservationId> = (0..crate::limits::MAX_OBSERVATIONS_PER_ENTITY)
+            .map(|i| entity.add_observation(format!("Fact {}", i)).id.clone())
+            .collect();
+        let merged = Observation::new("Every fact at once");
+        assert_eq!(
+            entity.replace_observations(&ids, vec![merged], "summarize"),
+            ids.len()
+        );
+        assert_eq!(entity.observation_history.len(), ids.len());
+        assert_eq!(entity.observation_history[0].observation.content, "Fact 0");
+    }
 }
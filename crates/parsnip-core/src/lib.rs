@@ -20,13 +20,17 @@ pub use limits::{
     validate_batch_entities, validate_batch_relations, validate_entity_name, validate_observation,
     validate_project_name, validate_tag, validate_traversal_depth, ValidationError,
     MAX_BATCH_ENTITIES, MAX_BATCH_RELATIONS, MAX_ENTITY_NAME_LEN, MAX_OBSERVATIONS_PER_ENTITY,
-    MAX_OBSERVATION_LEN, MAX_PROJECT_NAME_LEN, MAX_TAGS_PER_ENTITY, MAX_TAG_LEN,
-    MAX_TRAVERSAL_DEPTH, MAX_TRAVERSAL_NODES,
+    MAX_OBSERVATION_HISTORY, MAX_OBSERVATION_LEN, MAX_PROJECT_NAME_LEN, MAX_TAGS_PER_ENTITY,
+    MAX_TAG_LEN, MAX_TRAVERSAL_DEPTH, MAX_TRAVERSAL_NODES,
 };
-pub use observation::{Observation, ObservationId};
+pub use observation::{ArchivedObservation, Observation, ObservationId};
 pub use project::{Project, ProjectId};
-pub use query::{Pagination, ProjectScope, SearchMode, SearchQuery, TagMatchMode};
+pub use query::{
+    ExpansionAlgorithm, GraphExpansion, PaginatedResults, Pagination, PaginationInfo, ProjectScope,
+    SearchMode, SearchQuery, TagMatchMode,
+};
 pub use relation::{Direction, NewRelation, Relation, RelationId};
 pub use traversal::{
-    GraphPath, PathEdge, TraversalEngine, TraversalQuery, TraversalResult, TraversalStats,
+    GraphPath, NodeKey, PathEdge, PathNode, TraversalEngine, TraversalQuery, TraversalResult,
+    TraversalStats,
 };
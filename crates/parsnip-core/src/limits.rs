@@ -9,6 +9,10 @@ pub const MAX_OBSERVATION_LEN: usize = 64 * 1024;
 /// Maximum observations per entity (1000)
 pub const MAX_OBSERVATIONS_PER_ENTITY: usize = 1000;
 
+/// Maximum replaced observations kept per entity (1000), as many as an
+/// entity can hold so one consolidation never drops its own originals
+pub const MAX_OBSERVATION_HISTORY: usize = MAX_OBSERVATIONS_PER_ENTITY;
+
 /// Maximum entities in a batch create (100)
 pub const MAX_BATCH_ENTITIES: usize = 100;
 
@@ -72,6 +72,23 @@ impl Observation {
     }
 }
 
+/// An observation replaced by consolidation, kept for the record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedObservation {
+    #[serde(flatten)]
+    pub observation: Observation,
+
+    /// Observations that replaced this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replaced_by: Vec<ObservationId>,
+
+    /// Why it was replaced, e.g. the consolidation strategy
+    pub reason: String,
+
+    /// When it was replaced
+    pub archived_at: DateTime<Utc>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
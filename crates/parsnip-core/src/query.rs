@@ -82,6 +82,116 @@ impl Pagination {
     }
 }
 
+/// Algorithm used to propagate search scores to graph neighbors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpansionAlgorithm {
+    /// Spreading activation: each hop passes on a decayed share of the score
+    #[default]
+    Spreading,
+    /// Personalized PageRank seeded with the direct hits
+    PageRank,
+}
+
+/// Graph-aware re-ranking: propagate scores from direct hits to their neighbors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphExpansion {
+    /// Propagation algorithm
+    #[serde(default)]
+    pub algorithm: ExpansionAlgorithm,
+
+    /// Maximum hops from a direct hit (spreading activation)
+    #[serde(default = "default_expansion_hops")]
+    pub max_hops: u32,
+
+    /// Score multiplier per hop (spreading activation)
+    #[serde(default = "default_expansion_decay")]
+    pub decay: f32,
+
+    /// Probability of following an edge instead of restarting (PageRank)
+    #[serde(default = "default_damping")]
+    pub damping: f32,
+
+    /// Power iterations (PageRank)
+    #[serde(default = "default_iterations")]
+    pub iterations: usize,
+
+    /// Drop propagated results scoring below this fraction of the top score
+    #[serde(default = "default_min_score")]
+    pub min_score: f32,
+
+    /// Only propagate over these relation types (empty = all types)
+    #[serde(default)]
+    pub relation_types: Vec<String>,
+}
+
+fn default_expansion_hops() -> u32 {
+    2
+}
+
+fn default_expansion_decay() -> f32 {
+    0.5
+}
+
+fn default_damping() -> f32 {
+    0.85
+}
+
+fn default_iterations() -> usize {
+    20
+}
+
+fn default_min_score() -> f32 {
+    0.05
+}
+
+impl Default for GraphExpansion {
+    fn default() -> Self {
+        Self {
+            algorithm: ExpansionAlgorithm::default(),
+            max_hops: default_expansion_hops(),
+            decay: default_expansion_decay(),
+            damping: default_damping(),
+            iterations: default_iterations(),
+            min_score: default_min_score(),
+            relation_types: Vec::new(),
+        }
+    }
+}
+
+impl GraphExpansion {
+    /// Spreading activation with the given hop limit and per-hop decay
+    pub fn spreading(max_hops: u32, decay: f32) -> Self {
+        Self {
+            algorithm: ExpansionAlgorithm::Spreading,
+            max_hops,
+            decay: decay.clamp(0.0, 1.0),
+            ..Default::default()
+        }
+    }
+
+    /// Personalized PageRank with the given damping factor
+    pub fn page_rank(damping: f32) -> Self {
+        Self {
+            algorithm: ExpansionAlgorithm::PageRank,
+            damping: damping.clamp(0.0, 1.0),
+            ..Default::default()
+        }
+    }
+
+    /// Only propagate over these relation types
+    pub fn over_relation_types(mut self, types: Vec<String>) -> Self {
+        self.relation_types = types;
+        self
+    }
+
+    /// Set the minimum relative score for propagated results
+    pub fn with_min_score(mut self, min_score: f32) -> Self {
+        self.min_score = min_score.clamp(0.0, 1.0);
+        self
+    }
+}
+
 /// Search query builder
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SearchQuery {
@@ -128,6 +238,10 @@ pub struct SearchQuery {
     /// Include relations in results
     #[serde(default = "default_true")]
     pub include_relations: bool,
+
+    /// Propagate scores from direct hits to graph neighbors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graph_expansion: Option<GraphExpansion>,
 }
 
 fn default_fuzzy_threshold() -> f32 {
@@ -221,6 +335,12 @@ impl SearchQuery {
         self.pagination = Pagination::new(page, page_size);
         self
     }
+
+    /// Enable graph-aware expansion of search results
+    pub fn with_graph_expansion(mut self, expansion: GraphExpansion) -> Self {
+        self.graph_expansion = Some(expansion);
+        self
+    }
 }
 
 /// Paginated search results
@@ -235,6 +355,7 @@ pub struct PaginatedResults<T> {
 
 /// Pagination metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PaginationInfo {
     pub current_page: usize,
     pub page_size: usize,
@@ -279,6 +400,20 @@ mod tests {
         assert!(matches!(query.projects, ProjectScope::All));
     }
 
+    #[test]
+    fn test_graph_expansion_defaults() {
+        let query: SearchQuery = serde_json::from_str(
+            r#"{"text": "payments", "graph_expansion": {"algorithm": "pagerank"}}"#,
+        )
+        .unwrap();
+        let expansion = query.graph_expansion.unwrap();
+        assert_eq!(expansion.algorithm, ExpansionAlgorithm::PageRank);
+        assert_eq!(expansion.max_hops, 2);
+        assert!((expansion.damping - 0.85).abs() < f32::EPSILON);
+
+        assert!(SearchQuery::new("x").graph_expansion.is_none());
+    }
+
     #[test]
     fn test_pagination() {
         let pagination = Pagination::new(2, 50);
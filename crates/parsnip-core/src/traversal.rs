@@ -1,6 +1,7 @@
 //! Graph traversal types and algorithms
 
-use crate::entity::Entity;
+use crate::entity::{Entity, EntityId};
+use crate::project::ProjectId;
 use crate::relation::{Direction, Relation};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
@@ -12,10 +13,18 @@ pub struct TraversalQuery {
     /// Starting entity name
     pub start: String,
 
+    /// Project of the starting entity (None = first entity with a matching name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_project: Option<ProjectId>,
+
     /// Target entity name (for path finding, None for general traversal)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target: Option<String>,
 
+    /// Project of the target entity (None = same project as the start)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_project: Option<ProjectId>,
+
     /// Maximum traversal depth
     #[serde(default = "default_depth")]
     pub max_depth: u32,
@@ -32,6 +41,10 @@ pub struct TraversalQuery {
     #[serde(default)]
     pub relation_type_filter: Vec<String>,
 
+    /// Follow relations that cross project boundaries
+    #[serde(default)]
+    pub cross_project: bool,
+
     /// Use weighted shortest path (Dijkstra)
     #[serde(default)]
     pub use_weights: bool,
@@ -57,11 +70,14 @@ impl Default for TraversalQuery {
     fn default() -> Self {
         Self {
             start: String::new(),
+            start_project: None,
             target: None,
+            target_project: None,
             max_depth: default_depth(),
             direction: Direction::Both,
             entity_type_filter: Vec::new(),
             relation_type_filter: Vec::new(),
+            cross_project: false,
             use_weights: false,
             all_paths: false,
             max_paths: default_max_paths(),
@@ -78,12 +94,24 @@ impl TraversalQuery {
         }
     }
 
+    /// Resolve the starting entity in a specific project
+    pub fn in_project(mut self, project_id: ProjectId) -> Self {
+        self.start_project = Some(project_id);
+        self
+    }
+
     /// Set target for path finding
     pub fn find_path_to(mut self, target: impl Into<String>) -> Self {
         self.target = Some(target.into());
         self
     }
 
+    /// Resolve the target entity in a specific project
+    pub fn with_target_project(mut self, project_id: ProjectId) -> Self {
+        self.target_project = Some(project_id);
+        self
+    }
+
     /// Set maximum traversal depth
     pub fn with_depth(mut self, depth: u32) -> Self {
         self.max_depth = depth;
@@ -108,6 +136,12 @@ impl TraversalQuery {
         self
     }
 
+    /// Follow cross-project relations into other projects
+    pub fn cross_project(mut self) -> Self {
+        self.cross_project = true;
+        self
+    }
+
     /// Use weighted shortest path (Dijkstra algorithm)
     pub fn weighted(mut self) -> Self {
         self.use_weights = true;
@@ -122,11 +156,46 @@ impl TraversalQuery {
     }
 }
 
+/// Project-qualified identity of a graph node
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NodeKey {
+    pub project_id: ProjectId,
+    pub entity_id: EntityId,
+}
+
+impl NodeKey {
+    pub fn new(project_id: ProjectId, entity_id: EntityId) -> Self {
+        Self {
+            project_id,
+            entity_id,
+        }
+    }
+
+    /// Key of an existing entity
+    pub fn of(entity: &Entity) -> Self {
+        Self::new(entity.project_id.clone(), entity.id.clone())
+    }
+}
+
+/// A project-qualified node in a traversal result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathNode {
+    pub project_id: ProjectId,
+    pub entity_id: EntityId,
+    pub name: String,
+}
+
+impl PathNode {
+    pub fn key(&self) -> NodeKey {
+        NodeKey::new(self.project_id.clone(), self.entity_id.clone())
+    }
+}
+
 /// A single path through the graph
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GraphPath {
-    /// Ordered list of entity names in the path
-    pub nodes: Vec<String>,
+    /// Ordered list of nodes in the path
+    pub nodes: Vec<PathNode>,
 
     /// Relations connecting the nodes
     pub edges: Vec<PathEdge>,
@@ -138,15 +207,37 @@ pub struct GraphPath {
     pub length: usize,
 }
 
+impl GraphPath {
+    /// Entity names along the path
+    pub fn node_names(&self) -> Vec<&str> {
+        self.nodes.iter().map(|n| n.name.as_str()).collect()
+    }
+}
+
 /// Edge in a path
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathEdge {
     pub from: String,
+    pub from_project_id: ProjectId,
     pub to: String,
+    pub to_project_id: ProjectId,
     pub relation_type: String,
     pub weight: Option<f64>,
 }
 
+impl From<&Relation> for PathEdge {
+    fn from(rel: &Relation) -> Self {
+        Self {
+            from: rel.from_name.clone(),
+            from_project_id: rel.effective_from_project_id().clone(),
+            to: rel.to_name.clone(),
+            to_project_id: rel.effective_to_project_id().clone(),
+            relation_type: rel.relation_type.clone(),
+            weight: rel.weight,
+        }
+    }
+}
+
 /// Result of a traversal operation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TraversalResult {
@@ -160,8 +251,8 @@ pub struct TraversalResult {
     /// Found paths (for path finding)
     pub paths: Vec<GraphPath>,
 
-    /// Visited entities (for general traversal)
-    pub visited_entities: Vec<String>,
+    /// Visited nodes in discovery order (for general traversal)
+    pub visited_entities: Vec<PathNode>,
 
     /// All entities in result
     pub entities: Vec<Entity>,
@@ -186,7 +277,7 @@ pub struct TraversalStats {
 #[derive(Clone, PartialEq)]
 struct DijkstraState {
     cost: f64,
-    node: String,
+    node: NodeKey,
 }
 
 impl Eq for DijkstraState {}
@@ -207,37 +298,156 @@ impl PartialOrd for DijkstraState {
     }
 }
 
-/// Adjacency map for O(1) neighbor lookups
-struct AdjacencyMap<'a> {
-    outgoing: HashMap<&'a str, Vec<&'a Relation>>,
-    incoming: HashMap<&'a str, Vec<&'a Relation>>,
+/// Graph index keyed by `(ProjectId, EntityId)` for O(1) node and neighbor lookups
+struct GraphIndex<'a> {
+    entities: &'a [Entity],
+    by_key: HashMap<NodeKey, &'a Entity>,
+    by_name: HashMap<(&'a ProjectId, &'a str), NodeKey>,
+    names: HashMap<NodeKey, &'a str>,
+    outgoing: HashMap<NodeKey, Vec<(&'a Relation, NodeKey)>>,
+    incoming: HashMap<NodeKey, Vec<(&'a Relation, NodeKey)>>,
+    edges: Vec<(&'a Relation, NodeKey, NodeKey)>,
 }
 
-impl<'a> AdjacencyMap<'a> {
-    fn build(relations: &'a [Relation]) -> Self {
-        let mut outgoing: HashMap<&'a str, Vec<&'a Relation>> = HashMap::new();
-        let mut incoming: HashMap<&'a str, Vec<&'a Relation>> = HashMap::new();
+impl<'a> GraphIndex<'a> {
+    fn build(entities: &'a [Entity], relations: &'a [Relation]) -> Self {
+        let mut index = Self {
+            entities,
+            by_key: HashMap::with_capacity(entities.len()),
+            by_name: HashMap::with_capacity(entities.len()),
+            names: HashMap::with_capacity(entities.len()),
+            outgoing: HashMap::new(),
+            incoming: HashMap::new(),
+            edges: Vec::with_capacity(relations.len()),
+        };
+
+        for entity in entities {
+            let key = NodeKey::of(entity);
+            index
+                .by_name
+                .entry((&entity.project_id, entity.name.as_str()))
+                .or_insert_with(|| key.clone());
+            index.names.insert(key.clone(), &entity.name);
+            index.by_key.insert(key, entity);
+        }
 
         for rel in relations {
-            outgoing.entry(&rel.from_name).or_default().push(rel);
-            incoming.entry(&rel.to_name).or_default().push(rel);
+            let from = index.resolve(
+                rel.effective_from_project_id(),
+                &rel.from_id,
+                &rel.from_name,
+            );
+            let to = index.resolve(rel.effective_to_project_id(), &rel.to_id, &rel.to_name);
+
+            index
+                .outgoing
+                .entry(from.clone())
+                .or_default()
+                .push((rel, to.clone()));
+            index
+                .incoming
+                .entry(to.clone())
+                .or_default()
+                .push((rel, from.clone()));
+            index.edges.push((rel, from, to));
         }
 
-        Self { outgoing, incoming }
+        index
     }
 
-    fn get_neighbors(&self, node: &str, direction: &Direction) -> Vec<&'a Relation> {
-        match direction {
-            Direction::Outgoing => self.outgoing.get(node).cloned().unwrap_or_default(),
-            Direction::Incoming => self.incoming.get(node).cloned().unwrap_or_default(),
-            Direction::Both => {
-                let mut result = self.outgoing.get(node).cloned().unwrap_or_default();
-                if let Some(inc) = self.incoming.get(node) {
-                    result.extend(inc.iter().copied());
-                }
-                result
+    /// Resolve a relation endpoint to a node key
+    fn resolve(&mut self, project_id: &'a ProjectId, id: &EntityId, name: &'a str) -> NodeKey {
+        let key = NodeKey::new(project_id.clone(), id.clone());
+        if self.by_key.contains_key(&key) {
+            return key;
+        }
+
+        // Relations created from names carry generated IDs; fall back to the name
+        if let Some(existing) = self.by_name.get(&(project_id, name)) {
+            return existing.clone();
+        }
+
+        // Dangling endpoint: register it so other edges to the same name connect
+        self.by_name.insert((project_id, name), key.clone());
+        self.names.insert(key.clone(), name);
+        key
+    }
+
+    /// Find a node by name, optionally restricted to a project
+    fn find(&self, name: &str, project_id: Option<&ProjectId>) -> Option<NodeKey> {
+        match project_id {
+            Some(pid) => self.by_name.get(&(pid, name)).cloned(),
+            None => self
+                .entities
+                .iter()
+                .find(|e| e.name == name)
+                .map(NodeKey::of)
+                .or_else(|| {
+                    self.by_name
+                        .iter()
+                        .find(|((_, n), _)| *n == name)
+                        .map(|(_, key)| key.clone())
+                }),
+        }
+    }
+
+    fn node(&self, key: &NodeKey) -> PathNode {
+        PathNode {
+            project_id: key.project_id.clone(),
+            entity_id: key.entity_id.clone(),
+            name: self.names.get(key).copied().unwrap_or_default().to_string(),
+        }
+    }
+
+    fn get_neighbors(
+        &self,
+        node: &NodeKey,
+        direction: &Direction,
+    ) -> impl Iterator<Item = &(&'a Relation, NodeKey)> {
+        let outgoing = match direction {
+            Direction::Outgoing | Direction::Both => self.outgoing.get(node),
+            Direction::Incoming => None,
+        };
+        let incoming = match direction {
+            Direction::Incoming | Direction::Both => self.incoming.get(node),
+            Direction::Outgoing => None,
+        };
+        outgoing
+            .into_iter()
+            .flatten()
+            .chain(incoming.into_iter().flatten())
+    }
+
+    /// Whether the traversal may step over `rel` onto `next`
+    fn admits(
+        &self,
+        query: &TraversalQuery,
+        origin: &ProjectId,
+        rel: &Relation,
+        next: &NodeKey,
+    ) -> bool {
+        // Apply relation type filter
+        if !query.relation_type_filter.is_empty()
+            && !query.relation_type_filter.contains(&rel.relation_type)
+        {
+            return false;
+        }
+
+        // Stay inside the starting project unless asked otherwise
+        if !query.cross_project && &next.project_id != origin {
+            return false;
+        }
+
+        // Apply entity type filter
+        if let Some(entity) = self.by_key.get(next) {
+            if !query.entity_type_filter.is_empty()
+                && !query.entity_type_filter.contains(&entity.entity_type.0)
+            {
+                return false;
             }
         }
+
+        true
     }
 }
 
@@ -246,48 +456,84 @@ pub struct TraversalEngine;
 
 impl TraversalEngine {
     /// Execute a traversal query
+    ///
+    /// Nodes are identified by `(ProjectId, EntityId)`, so entities sharing a
+    /// name in different projects stay distinct. Pass entities and relations
+    /// from every project involved when `query.cross_project` is set.
     pub fn execute(
         query: &TraversalQuery,
-        entities: &HashMap<String, Entity>,
+        entities: &[Entity],
         relations: &[Relation],
     ) -> TraversalResult {
         tracing::debug!(
-            "Executing traversal: start={}, target={:?}, depth={}, direction={:?}",
+            "Executing traversal: start={}, target={:?}, depth={}, direction={:?}, cross_project={}",
             query.start,
             query.target,
             query.max_depth,
-            query.direction
+            query.direction,
+            query.cross_project
         );
 
-        // Build adjacency map once for O(1) neighbor lookups
-        let adj = AdjacencyMap::build(relations);
+        // Build the index once for O(1) node and neighbor lookups
+        let index = GraphIndex::build(entities, relations);
 
-        if query.target.is_some() {
-            if query.use_weights {
-                Self::dijkstra_path(query, entities, relations, &adj)
-            } else {
-                Self::bfs_path(query, entities, relations, &adj)
+        let Some(start) = index.find(&query.start, query.start_project.as_ref()) else {
+            tracing::debug!("Start entity '{}' not found", query.start);
+            return Self::build_result(query, vec![], &[], &index, TraversalStats::default());
+        };
+
+        match &query.target {
+            Some(target_name) => {
+                let target = Self::resolve_target(query, target_name, &start, &index);
+                match (target, query.use_weights) {
+                    (Some(target), true) => Self::dijkstra_path(query, &start, &target, &index),
+                    (Some(target), false) => Self::bfs_path(query, &start, &target, &index),
+                    (None, _) => {
+                        tracing::debug!("Target entity '{}' not found", target_name);
+                        Self::build_result(query, vec![], &[], &index, TraversalStats::default())
+                    }
+                }
             }
-        } else {
-            Self::filtered_bfs(query, entities, relations, &adj)
+            None => Self::filtered_bfs(query, &start, &index),
         }
     }
 
+    /// Resolve the target node: explicit project, then the start's project,
+    /// then anywhere when crossing projects
+    fn resolve_target(
+        query: &TraversalQuery,
+        name: &str,
+        start: &NodeKey,
+        index: &GraphIndex,
+    ) -> Option<NodeKey> {
+        if let Some(ref pid) = query.target_project {
+            return index.find(name, Some(pid));
+        }
+        index.find(name, Some(&start.project_id)).or_else(|| {
+            if query.cross_project {
+                index.find(name, None)
+            } else {
+                None
+            }
+        })
+    }
+
     /// BFS for unweighted shortest path
     fn bfs_path(
         query: &TraversalQuery,
-        entities: &HashMap<String, Entity>,
-        relations: &[Relation],
-        adj: &AdjacencyMap,
+        start: &NodeKey,
+        target: &NodeKey,
+        index: &GraphIndex,
     ) -> TraversalResult {
-        let target = query.target.as_ref().unwrap();
-        let mut visited: HashSet<String> = HashSet::new();
-        let mut parent: HashMap<String, (String, PathEdge)> = HashMap::new();
-        let mut queue: VecDeque<(String, u32)> = VecDeque::new();
+        let mut visited: HashSet<NodeKey> = HashSet::new();
+        let mut order: Vec<NodeKey> = Vec::new();
+        let mut parent: HashMap<NodeKey, (NodeKey, PathEdge)> = HashMap::new();
+        let mut queue: VecDeque<(NodeKey, u32)> = VecDeque::new();
         let mut stats = TraversalStats::default();
 
-        queue.push_back((query.start.clone(), 0));
-        visited.insert(query.start.clone());
+        queue.push_back((start.clone(), 0));
+        visited.insert(start.clone());
+        order.push(start.clone());
 
         while let Some((current, depth)) = queue.pop_front() {
             stats.nodes_visited += 1;
@@ -303,45 +549,16 @@ impl TraversalEngine {
                 continue;
             }
 
-            for rel in adj.get_neighbors(&current, &query.direction) {
+            for (rel, next) in index.get_neighbors(&current, &query.direction) {
                 stats.edges_traversed += 1;
 
-                // Apply relation type filter
-                if !query.relation_type_filter.is_empty()
-                    && !query.relation_type_filter.contains(&rel.relation_type)
-                {
+                if !index.admits(query, &start.project_id, rel, next) {
                     continue;
                 }
 
-                let next = if rel.from_name == current {
-                    &rel.to_name
-                } else {
-                    &rel.from_name
-                };
-
-                // Apply entity type filter
-                if let Some(entity) = entities.get(next) {
-                    if !query.entity_type_filter.is_empty()
-                        && !query.entity_type_filter.contains(&entity.entity_type.0)
-                    {
-                        continue;
-                    }
-                }
-
-                if !visited.contains(next) {
-                    visited.insert(next.clone());
-                    parent.insert(
-                        next.clone(),
-                        (
-                            current.clone(),
-                            PathEdge {
-                                from: rel.from_name.clone(),
-                                to: rel.to_name.clone(),
-                                relation_type: rel.relation_type.clone(),
-                                weight: rel.weight,
-                            },
-                        ),
-                    );
+                if visited.insert(next.clone()) {
+                    order.push(next.clone());
+                    parent.insert(next.clone(), (current.clone(), PathEdge::from(*rel)));
                     queue.push_back((next.clone(), depth + 1));
                 }
             }
@@ -349,31 +566,32 @@ impl TraversalEngine {
 
         // Reconstruct path
         let paths = if stats.path_found {
-            vec![Self::reconstruct_path(&query.start, target, &parent)]
+            vec![Self::reconstruct_path(start, target, &parent, index)]
         } else {
             vec![]
         };
 
-        Self::build_result(query, paths, &visited, entities, relations, stats)
+        Self::build_result(query, paths, &order, index, stats)
     }
 
     /// Dijkstra's algorithm for weighted shortest path
     fn dijkstra_path(
         query: &TraversalQuery,
-        entities: &HashMap<String, Entity>,
-        relations: &[Relation],
-        adj: &AdjacencyMap,
+        start: &NodeKey,
+        target: &NodeKey,
+        index: &GraphIndex,
     ) -> TraversalResult {
-        let target = query.target.as_ref().unwrap();
-        let mut dist: HashMap<String, f64> = HashMap::new();
-        let mut parent: HashMap<String, (String, PathEdge)> = HashMap::new();
+        let mut dist: HashMap<NodeKey, f64> = HashMap::new();
+        let mut order: Vec<NodeKey> = Vec::new();
+        let mut parent: HashMap<NodeKey, (NodeKey, PathEdge)> = HashMap::new();
         let mut heap = BinaryHeap::new();
         let mut stats = TraversalStats::default();
 
-        dist.insert(query.start.clone(), 0.0);
+        dist.insert(start.clone(), 0.0);
+        order.push(start.clone());
         heap.push(DijkstraState {
             cost: 0.0,
-            node: query.start.clone(),
+            node: start.clone(),
         });
 
         while let Some(DijkstraState { cost, node }) = heap.pop() {
@@ -390,48 +608,21 @@ impl TraversalEngine {
                 continue;
             }
 
-            for rel in adj.get_neighbors(&node, &query.direction) {
+            for (rel, next) in index.get_neighbors(&node, &query.direction) {
                 stats.edges_traversed += 1;
 
-                // Apply relation type filter
-                if !query.relation_type_filter.is_empty()
-                    && !query.relation_type_filter.contains(&rel.relation_type)
-                {
+                if !index.admits(query, &start.project_id, rel, next) {
                     continue;
                 }
 
-                let next = if rel.from_name == node {
-                    &rel.to_name
-                } else {
-                    &rel.from_name
-                };
-
-                // Apply entity type filter
-                if let Some(entity) = entities.get(next) {
-                    if !query.entity_type_filter.is_empty()
-                        && !query.entity_type_filter.contains(&entity.entity_type.0)
-                    {
-                        continue;
-                    }
-                }
-
                 let edge_weight = rel.weight.unwrap_or(1.0);
                 let new_cost = cost + edge_weight;
 
                 if new_cost < *dist.get(next).unwrap_or(&f64::INFINITY) {
-                    dist.insert(next.clone(), new_cost);
-                    parent.insert(
-                        next.clone(),
-                        (
-                            node.clone(),
-                            PathEdge {
-                                from: rel.from_name.clone(),
-                                to: rel.to_name.clone(),
-                                relation_type: rel.relation_type.clone(),
-                                weight: rel.weight,
-                            },
-                        ),
-                    );
+                    if dist.insert(next.clone(), new_cost).is_none() {
+                        order.push(next.clone());
+                    }
+                    parent.insert(next.clone(), (node.clone(), PathEdge::from(*rel)));
                     heap.push(DijkstraState {
                         cost: new_cost,
                         node: next.clone(),
@@ -441,28 +632,28 @@ impl TraversalEngine {
         }
 
         let paths = if stats.path_found {
-            vec![Self::reconstruct_path(&query.start, target, &parent)]
+            vec![Self::reconstruct_path(start, target, &parent, index)]
         } else {
             vec![]
         };
 
-        let visited: HashSet<String> = dist.keys().cloned().collect();
-        Self::build_result(query, paths, &visited, entities, relations, stats)
+        Self::build_result(query, paths, &order, index, stats)
     }
 
     /// Filtered BFS traversal (no target)
     fn filtered_bfs(
         query: &TraversalQuery,
-        entities: &HashMap<String, Entity>,
-        relations: &[Relation],
-        adj: &AdjacencyMap,
+        start: &NodeKey,
+        index: &GraphIndex,
     ) -> TraversalResult {
-        let mut visited: HashSet<String> = HashSet::new();
-        let mut queue: VecDeque<(String, u32)> = VecDeque::new();
+        let mut visited: HashSet<NodeKey> = HashSet::new();
+        let mut order: Vec<NodeKey> = Vec::new();
+        let mut queue: VecDeque<(NodeKey, u32)> = VecDeque::new();
         let mut stats = TraversalStats::default();
 
-        queue.push_back((query.start.clone(), 0));
-        visited.insert(query.start.clone());
+        queue.push_back((start.clone(), 0));
+        visited.insert(start.clone());
+        order.push(start.clone());
 
         while let Some((current, depth)) = queue.pop_front() {
             stats.nodes_visited += 1;
@@ -472,33 +663,15 @@ impl TraversalEngine {
                 continue;
             }
 
-            for rel in adj.get_neighbors(&current, &query.direction) {
+            for (rel, next) in index.get_neighbors(&current, &query.direction) {
                 stats.edges_traversed += 1;
 
-                // Apply relation type filter
-                if !query.relation_type_filter.is_empty()
-                    && !query.relation_type_filter.contains(&rel.relation_type)
-                {
+                if !index.admits(query, &start.project_id, rel, next) {
                     continue;
                 }
 
-                let next = if rel.from_name == current {
-                    &rel.to_name
-                } else {
-                    &rel.from_name
-                };
-
-                // Apply entity type filter
-                if let Some(entity) = entities.get(next) {
-                    if !query.entity_type_filter.is_empty()
-                        && !query.entity_type_filter.contains(&entity.entity_type.0)
-                    {
-                        continue;
-                    }
-                }
-
-                if !visited.contains(next) {
-                    visited.insert(next.clone());
+                if visited.insert(next.clone()) {
+                    order.push(next.clone());
                     queue.push_back((next.clone(), depth + 1));
                 }
             }
@@ -510,25 +683,26 @@ impl TraversalEngine {
             stats.edges_traversed
         );
 
-        Self::build_result(query, vec![], &visited, entities, relations, stats)
+        Self::build_result(query, vec![], &order, index, stats)
     }
 
     /// Reconstruct path from parent map
     fn reconstruct_path(
-        start: &str,
-        end: &str,
-        parent: &HashMap<String, (String, PathEdge)>,
+        start: &NodeKey,
+        end: &NodeKey,
+        parent: &HashMap<NodeKey, (NodeKey, PathEdge)>,
+        index: &GraphIndex,
     ) -> GraphPath {
-        let mut nodes = vec![end.to_string()];
+        let mut nodes = vec![index.node(end)];
         let mut edges = Vec::new();
-        let mut current = end.to_string();
+        let mut current = end.clone();
         let mut total_weight = 0.0;
 
-        while current != start {
+        while &current != start {
             if let Some((prev, edge)) = parent.get(&current) {
                 total_weight += edge.weight.unwrap_or(1.0);
                 edges.push(edge.clone());
-                nodes.push(prev.clone());
+                nodes.push(index.node(prev));
                 current = prev.clone();
             } else {
                 break;
@@ -550,22 +724,24 @@ impl TraversalEngine {
     fn build_result(
         query: &TraversalQuery,
         paths: Vec<GraphPath>,
-        visited: &HashSet<String>,
-        entities: &HashMap<String, Entity>,
-        relations: &[Relation],
+        visited: &[NodeKey],
+        index: &GraphIndex,
         stats: TraversalStats,
     ) -> TraversalResult {
-        let visited_entities: Vec<String> = visited.iter().cloned().collect();
+        let visited_set: HashSet<&NodeKey> = visited.iter().collect();
 
-        let result_entities: Vec<Entity> = visited_entities
+        let visited_entities: Vec<PathNode> = visited.iter().map(|key| index.node(key)).collect();
+
+        let result_entities: Vec<Entity> = visited
             .iter()
-            .filter_map(|name| entities.get(name).cloned())
+            .filter_map(|key| index.by_key.get(key).map(|e| (*e).clone()))
             .collect();
 
-        let result_relations: Vec<Relation> = relations
+        let result_relations: Vec<Relation> = index
+            .edges
             .iter()
-            .filter(|r| visited.contains(&r.from_name) && visited.contains(&r.to_name))
-            .cloned()
+            .filter(|(_, from, to)| visited_set.contains(from) && visited_set.contains(to))
+            .map(|(rel, _, _)| (*rel).clone())
             .collect();
 
         TraversalResult {
@@ -583,17 +759,19 @@ impl TraversalEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::project::ProjectId;
 
-    fn create_test_graph() -> (HashMap<String, Entity>, Vec<Relation>) {
+    fn visited(result: &TraversalResult, name: &str) -> bool {
+        result.visited_entities.iter().any(|n| n.name == name)
+    }
+
+    fn create_test_graph() -> (Vec<Entity>, Vec<Relation>) {
         let project_id = ProjectId::new();
 
         // Create entities: A, B, C, D, E, F
-        let mut entities = HashMap::new();
-        for name in ["A", "B", "C", "D", "E", "F"] {
-            let entity = Entity::new(project_id.clone(), name, "node");
-            entities.insert(name.to_string(), entity);
-        }
+        let entities: Vec<Entity> = ["A", "B", "C", "D", "E", "F"]
+            .iter()
+            .map(|name| Entity::new(project_id.clone(), *name, "node"))
+            .collect();
 
         // Create graph:
         // A --1.0--> B --2.0--> C --1.0--> D
@@ -620,7 +798,7 @@ mod tests {
 
         assert!(result.stats.path_found);
         assert_eq!(result.paths.len(), 1);
-        assert_eq!(result.paths[0].nodes, vec!["A", "B", "C", "D"]);
+        assert_eq!(result.paths[0].node_names(), vec!["A", "B", "C", "D"]);
         assert_eq!(result.paths[0].length, 3);
     }
 
@@ -636,7 +814,7 @@ mod tests {
         let result = TraversalEngine::execute(&query, &entities, &relations);
 
         assert!(result.stats.path_found);
-        assert_eq!(result.paths[0].nodes, vec!["A", "B", "C", "F"]);
+        assert_eq!(result.paths[0].node_names(), vec!["A", "B", "C", "F"]);
         assert!((result.paths[0].total_weight - 4.0).abs() < 0.001);
     }
 
@@ -647,24 +825,19 @@ mod tests {
         let result = TraversalEngine::execute(&query, &entities, &relations);
 
         // At depth 2 from A: A(0), B(1), C(2), E(2)
-        assert!(result.visited_entities.contains(&"A".to_string()));
-        assert!(result.visited_entities.contains(&"B".to_string()));
-        assert!(result.visited_entities.contains(&"C".to_string()));
-        assert!(result.visited_entities.contains(&"E".to_string()));
+        assert!(visited(&result, "A"));
+        assert!(visited(&result, "B"));
+        assert!(visited(&result, "C"));
+        assert!(visited(&result, "E"));
     }
 
     #[test]
     fn test_no_path_found() {
         let project_id = ProjectId::new();
-        let mut entities = HashMap::new();
-        entities.insert(
-            "A".to_string(),
+        let entities = vec![
             Entity::new(project_id.clone(), "A", "node"),
-        );
-        entities.insert(
-            "B".to_string(),
             Entity::new(project_id.clone(), "B", "node"),
-        );
+        ];
         // No relations - disconnected graph
 
         let query = TraversalQuery::new("A").find_path_to("B");
@@ -683,29 +856,26 @@ mod tests {
             .with_direction(Direction::Outgoing)
             .with_depth(1);
         let result = TraversalEngine::execute(&outgoing, &entities, &relations);
-        assert!(result.visited_entities.contains(&"C".to_string()));
-        assert!(result.visited_entities.contains(&"E".to_string()));
-        assert!(!result.visited_entities.contains(&"A".to_string()));
+        assert!(visited(&result, "C"));
+        assert!(visited(&result, "E"));
+        assert!(!visited(&result, "A"));
 
         // Incoming to B should only reach A
         let incoming = TraversalQuery::new("B")
             .with_direction(Direction::Incoming)
             .with_depth(1);
         let result = TraversalEngine::execute(&incoming, &entities, &relations);
-        assert!(result.visited_entities.contains(&"A".to_string()));
-        assert!(!result.visited_entities.contains(&"C".to_string()));
+        assert!(visited(&result, "A"));
+        assert!(!visited(&result, "C"));
     }
 
     #[test]
     fn test_relation_type_filter() {
         let project_id = ProjectId::new();
-        let mut entities = HashMap::new();
-        for name in ["A", "B", "C"] {
-            entities.insert(
-                name.to_string(),
-                Entity::new(project_id.clone(), name, "node"),
-            );
-        }
+        let entities: Vec<Entity> = ["A", "B", "C"]
+            .iter()
+            .map(|name| Entity::new(project_id.clone(), *name, "node"))
+            .collect();
 
         let relations = vec![
             Relation::from_names(project_id.clone(), "A", "B", "works_at"),
@@ -719,7 +889,116 @@ mod tests {
         let result = TraversalEngine::execute(&query, &entities, &relations);
 
         // Should reach B but not C
-        assert!(result.visited_entities.contains(&"B".to_string()));
-        assert!(!result.visited_entities.contains(&"C".to_string()));
+        assert!(visited(&result, "B"));
+        assert!(!visited(&result, "C"));
+    }
+
+    /// Two projects each with an `Alice`, linked by a cross-project relation:
+    /// work/Alice -> work/Acme, personal/Alice -> personal/Bob,
+    /// work/Alice -[same_as]-> personal/Alice
+    fn create_cross_project_graph() -> (ProjectId, ProjectId, Vec<Entity>, Vec<Relation>) {
+        let work = ProjectId::new();
+        let personal = ProjectId::new();
+
+        let work_alice = Entity::new(work.clone(), "Alice", "person");
+        let acme = Entity::new(work.clone(), "Acme", "company");
+        let home_alice = Entity::new(personal.clone(), "Alice", "person");
+        let bob = Entity::new(personal.clone(), "Bob", "person");
+
+        let relations = vec![
+            Relation::new(
+                work.clone(),
+                work_alice.id.clone(),
+                "Alice",
+                acme.id.clone(),
+                "Acme",
+                "works_at",
+            ),
+            Relation::new(
+                personal.clone(),
+                home_alice.id.clone(),
+                "Alice",
+                bob.id.clone(),
+                "Bob",
+                "knows",
+            ),
+            Relation::new_cross_project(
+                work.clone(),
+                work_alice.id.clone(),
+                "Alice",
+                work.clone(),
+                home_alice.id.clone(),
+                "Alice",
+                personal.clone(),
+                "same_as",
+            ),
+        ];
+
+        let entities = vec![work_alice, acme, home_alice, bob];
+        (work, personal, entities, relations)
+    }
+
+    #[test]
+    fn test_same_name_in_two_projects_stays_distinct() {
+        let (work, personal, entities, relations) = create_cross_project_graph();
+
+        let query = TraversalQuery::new("Alice").in_project(work.clone());
+        let result = TraversalEngine::execute(&query, &entities, &relations);
+        assert!(visited(&result, "Acme"));
+        assert!(!visited(&result, "Bob"));
+        assert_eq!(result.visited_entities.len(), 2);
+
+        let query = TraversalQuery::new("Alice").in_project(personal);
+        let result = TraversalEngine::execute(&query, &entities, &relations);
+        assert!(visited(&result, "Bob"));
+        assert!(!visited(&result, "Acme"));
+    }
+
+    #[test]
+    fn test_cross_project_traversal() {
+        let (work, personal, entities, relations) = create_cross_project_graph();
+
+        let query = TraversalQuery::new("Alice")
+            .in_project(work.clone())
+            .cross_project();
+        let result = TraversalEngine::execute(&query, &entities, &relations);
+
+        // Both Alices are visited as separate nodes
+        let alices: Vec<_> = result
+            .visited_entities
+            .iter()
+            .filter(|n| n.name == "Alice")
+            .collect();
+        assert_eq!(alices.len(), 2);
+        assert!(visited(&result, "Bob"));
+        assert_eq!(result.entities.len(), 4);
+        assert_eq!(result.relations.len(), 3);
+
+        let query = TraversalQuery::new("Alice")
+            .in_project(work.clone())
+            .find_path_to("Bob")
+            .with_target_project(personal.clone())
+            .cross_project();
+        let result = TraversalEngine::execute(&query, &entities, &relations);
+
+        assert!(result.stats.path_found);
+        let path = &result.paths[0];
+        assert_eq!(path.node_names(), vec!["Alice", "Alice", "Bob"]);
+        assert_eq!(path.nodes[0].project_id, work);
+        assert_eq!(path.nodes[1].project_id, personal);
+        assert_eq!(path.edges[0].to_project_id, personal);
+    }
+
+    #[test]
+    fn test_cross_project_requires_opt_in() {
+        let (work, personal, entities, relations) = create_cross_project_graph();
+
+        let query = TraversalQuery::new("Alice")
+            .in_project(work)
+            .find_path_to("Bob")
+            .with_target_project(personal);
+        let result = TraversalEngine::execute(&query, &entities, &relations);
+
+        assert!(!result.stats.path_found);
     }
 }
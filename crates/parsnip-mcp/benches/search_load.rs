@@ -0,0 +1,87 @@
+//! The same searches over a ReDB store, one at a time and all at once
+//!
+//! Run with `cargo bench -p parsnip-mcp --bench search_load`; the parallel
+//! searches should finish several times sooner on a multi-core machine.
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use parsnip_core::{Entity, Project};
+use parsnip_mcp::{McpServer, Permissions, DEFAULT_SESSION};
+use parsnip_storage::{RedbStorage, StorageBackend};
+
+const ENTITIES: usize = 1_000;
+const SEARCHES: usize = 32;
+const TOPICS: usize = 50;
+
+async fn search(server: Arc<McpServer<RedbStorage>>, i: usize) -> usize {
+    let args = serde_json::json!({
+        "query": format!("topic {} of", i % TOPICS),
+        "projectId": "load",
+        "searchMode": "exact",
+    });
+    let response = server
+        .call_tool(
+            DEFAULT_SESSION,
+            &Permissions::new(),
+            "search_knowledge",
+            args,
+        )
+        .await
+        .unwrap();
+    let value = serde_json::to_value(&response).unwrap();
+    let result: serde_json::Value =
+        serde_json::from_str(value["content"][0]["text"].as_str().unwrap()).unwrap();
+    result["pagination"]["totalCount"].as_u64().unwrap() as usize
+}
+
+fn bench_search_load(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let server = rt.block_on(async {
+        let storage = Arc::new(RedbStorage::open(dir.path().join("load.redb")).unwrap());
+        let project = Project::new("load");
+        storage.save_project(&project).await.unwrap();
+        let entities: Vec<Entity> = (0..ENTITIES)
+            .map(|i| {
+                let mut entity = Entity::new(project.id.clone(), format!("Note {}", i), "note");
+                entity.add_observation(format!("topic {} of the load test", i % TOPICS));
+                entity
+            })
+            .collect();
+        storage.save_entities_batch(&entities).await.unwrap();
+        Arc::new(McpServer::new(storage))
+    });
+
+    let mut group = c.benchmark_group("search_knowledge");
+    group.sample_size(20);
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                for i in 0..SEARCHES {
+                    assert_eq!(search(server.clone(), i).await, ENTITIES / TOPICS);
+                }
+            })
+        })
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                let searches: Vec<_> = (0..SEARCHES)
+                    .map(|i| tokio::spawn(search(server.clone(), i)))
+                    .collect();
+                for search in searches {
+                    assert_eq!(search.await.unwrap(), ENTITIES / TOPICS);
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_search_load);
+criterion_main!(benches);
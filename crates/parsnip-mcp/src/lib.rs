@@ -2,15 +2,33 @@
 //!
 //! Provides MCP server implementation for AI assistant integration.
 
-pub mod handlers;
+pub mod metrics;
+pub mod permissions;
+pub mod prompts;
+pub mod resources;
+pub mod sampling;
+pub mod schema;
 pub mod server;
+pub mod subscriptions;
 pub mod tools;
 pub mod transport;
 
+#[cfg(feature = "sse")]
+pub mod rest;
 #[cfg(feature = "sse")]
 pub mod sse;
+#[cfg(feature = "sse")]
+pub mod streamable;
+#[cfg(feature = "tls")]
+pub mod tls;
 
-pub use server::McpServer;
+pub use metrics::Metrics;
+pub use permissions::Permissions;
+pub use sampling::{ClientInfo, ClientPeer, Sampler, SamplingError};
+pub use server::{McpServer, DEFAULT_SESSION};
 
 #[cfg(feature = "sse")]
-pub use sse::run_sse_server;
+pub use sse::{run_http_server, AuthToken, HttpOptions};
+
+#[cfg(feature = "tls")]
+pub use tls::{ClientCert, ServerCert, TlsError, TlsOptions};
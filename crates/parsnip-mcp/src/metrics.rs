@@ -0,0 +1,326 @@
+//! Prometheus metrics of the server
+//!
+//! [`Metrics`] counts tool calls as the server handles them, and holds the
+//! [`StorageMetrics`] of an [`parsnip_storage::InstrumentedStorage`] and the
+//! [`StorageCache`] of a [`parsnip_storage::CachedStorage`] when the server
+//! has them. [`Metrics::render`] writes all of them in the Prometheus text
+//! format, along with gauges read at scrape time: entities and relations per
+//! project, and the size on disk of the database and search index.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use parsnip_storage::metrics::{Histogram, OperationStats};
+use parsnip_storage::{Scope, StorageBackend, StorageCache, StorageMetrics};
+
+/// Content type of [`Metrics::render`]'s output
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Counters of a running server
+#[derive(Debug, Default)]
+pub struct Metrics {
+    tools: Mutex<BTreeMap<String, OperationStats>>,
+    storage: Option<Arc<StorageMetrics>>,
+    cache: Option<Arc<StorageCache>>,
+    /// Files and directories whose size is reported, by name
+    stores: Vec<(String, PathBuf)>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the timings of an instrumented storage backend
+    pub fn with_storage(mut self, storage: Arc<StorageMetrics>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Report the hits, misses and size of a storage cache
+    pub fn with_cache(mut self, cache: Arc<StorageCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Report the size on disk of a file or directory, such as the search index
+    pub fn with_store(mut self, name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.stores.push((name.into(), path.into()));
+        self
+    }
+
+    /// Count a tool call; `ok` is false for rejected calls and error results
+    pub fn record_tool(&self, tool: &str, duration: Duration, ok: bool) {
+        self.tools
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(tool.to_string())
+            .or_default()
+            .record(duration, ok);
+    }
+
+    /// Stats of every tool called so far
+    pub fn tool_stats(&self) -> BTreeMap<String, OperationStats> {
+        self.tools.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Every metric in the Prometheus text format
+    ///
+    /// Counting entities reads every project in `scope`, so scrapes of large
+    /// graphs cost as much as exporting them.
+    pub async fn render(&self, storage: &dyn StorageBackend, scope: &Scope) -> String {
+        let mut out = Exposition::default();
+
+        let tools = self.tool_stats();
+        out.operations("parsnip_tool", "tool", "Tool calls", &tools);
+
+        if let Some(storage) = &self.storage {
+            let operations: BTreeMap<_, _> = storage
+                .snapshot()
+                .into_iter()
+                .map(|(op, stats)| (op.to_string(), stats))
+                .collect();
+            out.operations("parsnip_storage", "op", "Storage operations", &operations);
+        }
+
+        if let Some(cache) = &self.cache {
+            let stats = cache.stats();
+            for (name, help, kind, value) in [
+                (
+                    "parsnip_cache_hits_total",
+                    "Reads answered from the storage cache",
+                    "counter",
+                    stats.hits as f64,
+                ),
+                (
+                    "parsnip_cache_misses_total",
+                    "Reads the storage cache passed to the backend",
+                    "counter",
+                    stats.misses as f64,
+                ),
+                (
+                    "parsnip_cache_evictions_total",
+                    "Projects evicted from the storage cache",
+                    "counter",
+                    stats.evictions as f64,
+                ),
+                (
+                    "parsnip_cache_invalidations_total",
+                    "Projects dropped from the storage cache by writes",
+                    "counter",
+                    stats.invalidations as f64,
+                ),
+                (
+                    "parsnip_cache_bytes",
+                    "Estimated size of the storage cache",
+                    "gauge",
+                    stats.bytes as f64,
+                ),
+                (
+                    "parsnip_cache_projects",
+                    "Projects in the storage cache",
+                    "gauge",
+                    stats.projects as f64,
+                ),
+            ] {
+                out.family(name, help, kind);
+                out.sample(name, &[], value);
+            }
+        }
+
+        match project_counts(storage, scope).await {
+            Ok(counts) => {
+                out.family("parsnip_project_entities", "Entities per project", "gauge");
+                for (project, (entities, _)) in &counts {
+                    out.sample(
+                        "parsnip_project_entities",
+                        &[("project", project)],
+                        *entities as f64,
+                    );
+                }
+                out.family(
+                    "parsnip_project_relations",
+                    "Relations per project",
+                    "gauge",
+                );
+                for (project, (_, relations)) in &counts {
+                    out.sample(
+                        "parsnip_project_relations",
+                        &[("project", project)],
+                        *relations as f64,
+                    );
+                }
+            }
+            Err(e) => tracing::warn!("Failed to count entities for metrics: {}", e),
+        }
+
+        if !self.stores.is_empty() {
+            out.family("parsnip_store_size_bytes", "Size on disk", "gauge");
+            for (name, path) in &self.stores {
+                out.sample(
+                    "parsnip_store_size_bytes",
+                    &[("store", name)],
+                    disk_size(path) as f64,
+                );
+            }
+        }
+
+        out.text
+    }
+}
+
+/// Entities and relations of each visible project, by name
+async fn project_counts(
+    storage: &dyn StorageBackend,
+    scope: &Scope,
+) -> parsnip_storage::StorageResult<BTreeMap<String, (usize, usize)>> {
+    let mut counts = BTreeMap::new();
+    for project in scope.visible_projects(storage).await? {
+        let entities = storage.get_all_entities(&project.id).await?.len();
+        let relations = storage.get_all_relations(&project.id).await?.len();
+        counts.insert(project.name, (entities, relations));
+    }
+    Ok(counts)
+}
+
+/// Bytes taken by a file, or by the files under a directory
+fn disk_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Writer of the Prometheus text exposition format
+#[derive(Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+
+    fn histogram(&mut self, name: &str, label: (&str, &str), histogram: &Histogram) {
+        let bucket = format!("{}_bucket", name);
+        for (bound, count) in histogram.cumulative() {
+            let bound = bound.to_string();
+            self.sample(&bucket, &[label, ("le", &bound)], count as f64);
+        }
+        self.sample(&bucket, &[label, ("le", "+Inf")], histogram.count() as f64);
+        self.sample(&format!("{}_sum", name), &[label], histogram.sum());
+        self.sample(
+            &format!("{}_count", name),
+            &[label],
+            histogram.count() as f64,
+        );
+    }
+
+    /// Calls, errors and latencies of named operations
+    fn operations(
+        &mut self,
+        prefix: &str,
+        label: &str,
+        what: &str,
+        stats: &BTreeMap<String, OperationStats>,
+    ) {
+        let calls = format!("{}_calls_total", prefix);
+        self.family(&calls, &format!("{} by {}", what, label), "counter");
+        for (name, stats) in stats {
+            self.sample(&calls, &[(label, name)], stats.calls as f64);
+        }
+
+        let errors = format!("{}_errors_total", prefix);
+        self.family(&errors, &format!("{} that failed", what), "counter");
+        for (name, stats) in stats {
+            self.sample(&errors, &[(label, name)], stats.errors as f64);
+        }
+
+        let duration = format!("{}_duration_seconds", prefix);
+        self.family(&duration, &format!("Latency of {}", what), "histogram");
+        for (name, stats) in stats {
+            self.histogram(&duration, (label, name), &stats.latency);
+        }
+    }
+}
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsnip_core::{Entity, Project};
+    use parsnip_storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_render() {
+        let storage = MemoryStorage::new();
+        for name in ["work", "home \"main\""] {
+            let project = Project::new(name);
+            storage.save_project(&project).await.unwrap();
+            let entity = Entity::new(project.id.clone(), "Alice", "person");
+            storage.save_entity(&entity).await.unwrap();
+        }
+
+        let metrics = Metrics::new()
+            .with_store("missing", "/nonexistent/parsnip")
+            .with_cache(Arc::new(StorageCache::new()));
+        metrics.record_tool("search_knowledge", Duration::from_millis(2), true);
+        metrics.record_tool("search_knowledge", Duration::from_millis(20), false);
+
+        let text = metrics.render(&storage, &Scope::projects(["work"])).await;
+        assert!(text.contains("# TYPE parsnip_tool_calls_total counter\n"));
+        assert!(text.contains("parsnip_tool_calls_total{tool=\"search_knowledge\"} 2\n"));
+        assert!(text.contains("parsnip_tool_errors_total{tool=\"search_knowledge\"} 1\n"));
+        assert!(text.contains(
+            "parsnip_tool_duration_seconds_bucket{tool=\"search_knowledge\",le=\"0.005\"} 1\n"
+        ));
+        assert!(text.contains(
+            "parsnip_tool_duration_seconds_bucket{tool=\"search_knowledge\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(text.contains("parsnip_project_entities{project=\"work\"} 1\n"));
+        // Projects out of scope are not reported
+        assert!(!text.contains("home"));
+        assert!(text.contains("parsnip_store_size_bytes{store=\"missing\"} 0\n"));
+        // No storage metrics without an instrumented backend
+        assert!(!text.contains("parsnip_storage_"));
+        assert!(
+            text.contains("# TYPE parsnip_cache_hits_total counter\nparsnip_cache_hits_total 0\n")
+        );
+
+        assert_eq!(escape("a \"b\"\\\n"), "a \\\"b\\\"\\\\\\n");
+    }
+}
@@ -0,0 +1,212 @@
+//! What a client may do through the MCP server
+//!
+//! [`Permissions`] limit a caller to read-only tools, to an allow or deny
+//! list of tools, and to a set of projects. The server applies its own
+//! permissions to every request; the HTTP transport narrows them further with
+//! the profile of the token a request authenticated with (see
+//! [`Permissions::restrict`]). Profiles deserialize from `config.toml`:
+//!
+//! ```toml
+//! [profiles.recall]
+//! read_only = true
+//! projects = ["work", "personal"]
+//! ```
+
+use std::collections::BTreeSet;
+
+use parsnip_storage::Scope;
+use serde::{Deserialize, Serialize};
+
+/// JSON-RPC error code for a call the caller is not permitted to make
+pub const PERMISSION_DENIED: i32 = -32003;
+
+/// Tools and projects available to a caller; the default allows everything
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Permissions {
+    /// Only tools that do not write are available
+    pub read_only: bool,
+    /// Only these tools are available, when set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_tools: Option<BTreeSet<String>>,
+    /// These tools are never available
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub deny_tools: BTreeSet<String>,
+    /// Only these projects are visible, when set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projects: Option<BTreeSet<String>>,
+    /// Label of the token these were granted to, for the audit log
+    #[serde(skip)]
+    pub token: Option<String>,
+}
+
+impl Permissions {
+    /// Permissions allowing everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn with_allowed_tools<I, T>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.allow_tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_denied_tools<I, T>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.deny_tools = tools.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_projects<I, T>(mut self, projects: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.projects = Some(projects.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Attribute what is done with these permissions to a token
+    pub fn with_token(mut self, label: impl Into<String>) -> Self {
+        self.token = Some(label.into());
+        self
+    }
+
+    /// Whether a tool may be listed and called
+    pub fn allows_tool(&self, name: &str, read_only_tool: bool) -> bool {
+        if self.read_only && !read_only_tool {
+            return false;
+        }
+        if self.deny_tools.contains(name) {
+            return false;
+        }
+        match &self.allow_tools {
+            Some(allowed) => allowed.contains(name),
+            None => true,
+        }
+    }
+
+    /// Whether a project may be read, and written unless read-only
+    pub fn allows_project(&self, name: &str) -> bool {
+        match &self.projects {
+            Some(projects) => projects.contains(name),
+            None => true,
+        }
+    }
+
+    /// Whether some projects are hidden
+    pub fn is_project_scoped(&self) -> bool {
+        self.projects.is_some()
+    }
+
+    /// Projects visible to shared graph operations
+    pub fn scope(&self) -> Scope {
+        match &self.projects {
+            Some(projects) => Scope::projects(projects.iter().cloned()),
+            None => Scope::all(),
+        }
+    }
+
+    /// Permissions allowing only what both `self` and `other` allow
+    pub fn restrict(&self, other: &Permissions) -> Permissions {
+        Permissions {
+            read_only: self.read_only || other.read_only,
+            allow_tools: intersect(&self.allow_tools, &other.allow_tools),
+            deny_tools: self.deny_tools.union(&other.deny_tools).cloned().collect(),
+            projects: intersect(&self.projects, &other.projects),
+            token: other.token.clone().or_else(|| self.token.clone()),
+        }
+    }
+}
+
+/// Intersection of two optional sets, where `None` means unrestricted
+fn intersect(
+    a: &Option<BTreeSet<String>>,
+    b: &Option<BTreeSet<String>>,
+) -> Option<BTreeSet<String>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.intersection(b).cloned().collect()),
+        (Some(set), None) | (None, Some(set)) => Some(set.clone()),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_and_project_checks() {
+        let all = Permissions::new();
+        assert!(all.allows_tool("create_entities", false));
+        assert!(all.allows_project("anything"));
+
+        let recall = Permissions::new()
+            .with_read_only(true)
+            .with_denied_tools(["suggest_relations"])
+            .with_projects(["work", "personal"]);
+        assert!(recall.allows_tool("read_graph", true));
+        assert!(!recall.allows_tool("create_entities", false));
+        assert!(!recall.allows_tool("suggest_relations", true));
+        assert!(recall.allows_project("work"));
+        assert!(!recall.allows_project("secret"));
+
+        let search_only = Permissions::new().with_allowed_tools(["search_knowledge"]);
+        assert!(search_only.allows_tool("search_knowledge", true));
+        assert!(!search_only.allows_tool("read_graph", true));
+    }
+
+    #[test]
+    fn test_restrict_keeps_the_stricter_side() {
+        let server = Permissions::new()
+            .with_allowed_tools(["search_knowledge", "read_graph", "create_entities"])
+            .with_projects(["work", "personal"]);
+        let token = Permissions::new()
+            .with_read_only(true)
+            .with_allowed_tools(["read_graph", "traverse_graph"])
+            .with_denied_tools(["open_nodes"])
+            .with_projects(["personal", "secret"]);
+
+        let effective = server.restrict(&token);
+        assert!(effective.read_only);
+        assert_eq!(
+            effective.allow_tools,
+            Some(BTreeSet::from(["read_graph".to_string()]))
+        );
+        assert!(effective.deny_tools.contains("open_nodes"));
+        assert_eq!(
+            effective.projects,
+            Some(BTreeSet::from(["personal".to_string()]))
+        );
+
+        assert_eq!(Permissions::new().restrict(&token), token);
+    }
+
+    #[test]
+    fn test_profile_from_toml() {
+        let profile: Permissions =
+            toml::from_str("read_only = true\nprojects = [\"work\"]\n").unwrap();
+        assert_eq!(
+            profile,
+            Permissions::new()
+                .with_read_only(true)
+                .with_projects(["work"])
+        );
+        assert_eq!(
+            toml::from_str::<Permissions>("").unwrap(),
+            Permissions::new()
+        );
+    }
+}
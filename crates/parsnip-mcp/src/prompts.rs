@@ -0,0 +1,613 @@
+//! MCP prompts: parameterized conversation starters over live graph data
+//!
+//! Built-in prompts are assembled by the server from storage. Teams can add
+//! their own as TOML files in a prompt directory (`~/.parsnip/prompts` for
+//! the CLI):
+//!
+//! ```toml
+//! name = "standup"
+//! description = "Prepare a standup update"
+//! template = """
+//! Draft a standup update for {{project}} from this graph:
+//!
+//! {{resource parsnip://{project}/graph}}
+//! """
+//!
+//! [[arguments]]
+//! name = "project"
+//! required = true
+//! ```
+//!
+//! `{{name}}` inserts an argument value. `{{resource URI}}` inserts the
+//! markdown of a `parsnip://` resource, where `{name}` in the URI is replaced
+//! by the percent-encoded argument value.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use parsnip_core::{Entity, Observation};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::resources::{encode_segment, ResourceContents, ResourceError};
+
+/// Default age in days after which an observation counts as stale
+pub const DEFAULT_STALE_DAYS: i64 = 90;
+
+/// Entities listed by the stale facts prompt
+pub const STALE_ENTITY_LIMIT: usize = 50;
+
+/// Built-in prompt names
+pub const RECALL_ENTITY: &str = "recall_entity";
+pub const SUMMARIZE_PROJECT: &str = "summarize_project";
+pub const RECORD_MEETING_NOTES: &str = "record_meeting_notes";
+pub const REVIEW_STALE_FACTS: &str = "review_stale_facts";
+
+/// Prompt errors, mapped to JSON-RPC error codes
+#[derive(Debug, Error)]
+pub enum PromptError {
+    #[error("Unknown prompt: {0}")]
+    UnknownPrompt(String),
+
+    #[error("Missing required argument: {0}")]
+    MissingArgument(String),
+
+    #[error("Invalid argument {name}: {reason}")]
+    InvalidArgument { name: String, reason: String },
+
+    #[error("Invalid prompt template {}: {reason}", path.display())]
+    InvalidTemplate { path: PathBuf, reason: String },
+
+    #[error(transparent)]
+    Resource(#[from] ResourceError),
+}
+
+impl PromptError {
+    /// JSON-RPC error code for this error
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::UnknownPrompt(_) | Self::MissingArgument(_) | Self::InvalidArgument { .. } => {
+                -32602
+            }
+            Self::InvalidTemplate { .. } => -32603,
+            Self::Resource(e) => e.code(),
+        }
+    }
+}
+
+/// Entry in `prompts/list`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prompt {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+}
+
+/// Argument accepted by a prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl Prompt {
+    fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            arguments: Vec::new(),
+        }
+    }
+
+    fn with_argument(mut self, name: &str, description: &str, required: bool) -> Self {
+        self.arguments.push(PromptArgument {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            required,
+        });
+        self
+    }
+
+    /// Check that every required argument was given
+    pub fn check_arguments(&self, args: &HashMap<String, String>) -> Result<(), PromptError> {
+        for argument in self.arguments.iter().filter(|a| a.required) {
+            let given = args
+                .get(&argument.name)
+                .is_some_and(|v| !v.trim().is_empty());
+            if !given {
+                return Err(PromptError::MissingArgument(argument.name.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Message returned by `prompts/get`
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptMessage {
+    pub role: &'static str,
+    pub content: PromptContent,
+}
+
+impl PromptMessage {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            role: "user",
+            content: PromptContent::Text { text: text.into() },
+        }
+    }
+
+    pub fn resource(resource: ResourceContents) -> Self {
+        Self {
+            role: "user",
+            content: PromptContent::Resource { resource },
+        }
+    }
+}
+
+/// Content of a prompt message
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PromptContent {
+    Text { text: String },
+    Resource { resource: ResourceContents },
+}
+
+/// Prompts served without configuration
+pub fn builtin_prompts() -> Vec<Prompt> {
+    vec![
+        Prompt::new(
+            RECALL_ENTITY,
+            "Recall everything stored about an entity, with its relations",
+        )
+        .with_argument("entity", "Entity name", true)
+        .with_argument(
+            "project",
+            "Project to look in (default: all projects)",
+            false,
+        ),
+        Prompt::new(SUMMARIZE_PROJECT, "Summarize a project's knowledge graph").with_argument(
+            "project",
+            "Project name",
+            true,
+        ),
+        Prompt::new(
+            RECORD_MEETING_NOTES,
+            "Record meeting notes into the graph as entities, observations and relations",
+        )
+        .with_argument("notes", "Meeting notes", true)
+        .with_argument(
+            "project",
+            "Project to record into (default: default)",
+            false,
+        ),
+        Prompt::new(
+            REVIEW_STALE_FACTS,
+            "Review observations that have not been confirmed for a while",
+        )
+        .with_argument("project", "Project name", true)
+        .with_argument(
+            "days",
+            &format!(
+                "Age in days after which a fact is stale (default: {})",
+                DEFAULT_STALE_DAYS
+            ),
+            false,
+        ),
+    ]
+}
+
+/// Piece of a parsed template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Text(String),
+    /// `{{name}}`
+    Argument(String),
+    /// `{{resource URI}}`, with `{name}` placeholders still in the URI
+    Resource(String),
+}
+
+/// User-defined prompt loaded from a TOML file
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub prompt: Prompt,
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Deserialize)]
+struct TemplateFile {
+    #[serde(flatten)]
+    prompt: Prompt,
+    template: String,
+}
+
+impl PromptTemplate {
+    /// Parse a template file's contents
+    pub fn parse(source: &str, path: &Path) -> Result<Self, PromptError> {
+        let invalid = |reason: String| PromptError::InvalidTemplate {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let file: TemplateFile = toml::from_str(source).map_err(|e| invalid(e.to_string()))?;
+        if file.prompt.name.trim().is_empty() {
+            return Err(invalid("name is empty".to_string()));
+        }
+
+        let segments = parse_segments(&file.template).map_err(invalid)?;
+        for segment in &segments {
+            let names = match segment {
+                Segment::Argument(name) => vec![name.as_str()],
+                Segment::Resource(uri) => uri_placeholders(uri),
+                Segment::Text(_) => continue,
+            };
+            for name in names {
+                if !file.prompt.arguments.iter().any(|a| a.name == name) {
+                    return Err(invalid(format!("undeclared argument: {}", name)));
+                }
+            }
+        }
+
+        Ok(Self {
+            prompt: file.prompt,
+            segments,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, PromptError> {
+        let source = std::fs::read_to_string(path).map_err(|e| PromptError::InvalidTemplate {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        Self::parse(&source, path)
+    }
+}
+
+/// Split a template into text, `{{argument}}` and `{{resource URI}}` pieces
+fn parse_segments(template: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            segments.push(Segment::Text(rest[..start].to_string()));
+        }
+        let body = &rest[start + 2..];
+
+        // The tag ends at the first `}}` outside single-brace placeholders
+        let mut depth = 0usize;
+        let mut end = None;
+        let bytes = body.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'{' => depth += 1,
+                b'}' if depth > 0 => depth -= 1,
+                b'}' if bytes.get(i + 1) == Some(&b'}') => {
+                    end = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        let end = end.ok_or_else(|| "unclosed {{".to_string())?;
+
+        let tag = body[..end].trim();
+        match tag.split_once(char::is_whitespace) {
+            Some(("resource", uri)) => segments.push(Segment::Resource(uri.trim().to_string())),
+            None if !tag.is_empty() => segments.push(Segment::Argument(tag.to_string())),
+            _ => return Err(format!("invalid tag: {{{{{}}}}}", tag)),
+        }
+        rest = &body[end + 2..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_string()));
+    }
+    Ok(segments)
+}
+
+/// Argument names referenced as `{name}` in a resource URI
+fn uri_placeholders(uri: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = uri;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        names.push(&rest[start + 1..start + len]);
+        rest = &rest[start + len + 1..];
+    }
+    names
+}
+
+/// Fill `{name}` placeholders in a resource URI with encoded argument values
+pub fn expand_uri(uri: &str, args: &HashMap<String, String>) -> String {
+    let mut expanded = uri.to_string();
+    for name in uri_placeholders(uri) {
+        let value = args.get(name).map(String::as_str).unwrap_or_default();
+        expanded = expanded.replace(&format!("{{{}}}", name), &encode_segment(value));
+    }
+    expanded
+}
+
+/// Built-in and user-defined prompts
+///
+/// User-defined prompts replace built-ins of the same name.
+#[derive(Debug, Clone, Default)]
+pub struct PromptLibrary {
+    templates: Vec<PromptTemplate>,
+}
+
+impl PromptLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a user-defined prompt, replacing any with the same name
+    pub fn with_template(mut self, template: PromptTemplate) -> Self {
+        self.templates
+            .retain(|t| t.prompt.name != template.prompt.name);
+        self.templates.push(template);
+        self
+    }
+
+    /// Load every `*.toml` template in a directory
+    ///
+    /// A missing directory yields no templates; invalid files are skipped
+    /// with a warning so one bad file does not hide the rest.
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut library = Self::new();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return library;
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match PromptTemplate::load(&path) {
+                Ok(template) => {
+                    tracing::debug!("Loaded prompt {} from {:?}", template.prompt.name, path);
+                    library = library.with_template(template);
+                }
+                Err(e) => tracing::warn!("Skipping prompt: {}", e),
+            }
+        }
+        library
+    }
+
+    /// User-defined prompt by name
+    pub fn template(&self, name: &str) -> Option<&PromptTemplate> {
+        self.templates.iter().find(|t| t.prompt.name == name)
+    }
+
+    /// Every prompt: built-ins not overridden, then user-defined ones
+    pub fn list(&self) -> Vec<Prompt> {
+        builtin_prompts()
+            .into_iter()
+            .filter(|p| self.template(&p.name).is_none())
+            .chain(self.templates.iter().map(|t| t.prompt.clone()))
+            .collect()
+    }
+}
+
+/// Observations older than `cutoff`, per entity, stalest entity first
+pub fn stale_observations(
+    entities: &[Entity],
+    cutoff: DateTime<Utc>,
+) -> Vec<(&Entity, Vec<&Observation>)> {
+    let mut stale: Vec<(&Entity, Vec<&Observation>)> = entities
+        .iter()
+        .filter_map(|entity| {
+            let observations: Vec<&Observation> = entity
+                .observations
+                .iter()
+                .filter(|o| o.created_at < cutoff)
+                .collect();
+            (!observations.is_empty()).then_some((entity, observations))
+        })
+        .collect();
+
+    stale.sort_by_key(|(entity, observations)| {
+        (
+            observations.iter().map(|o| o.created_at).min(),
+            entity.name.clone(),
+        )
+    });
+    stale
+}
+
+/// Parse the `days` argument of the stale facts prompt
+pub fn parse_stale_days(args: &HashMap<String, String>) -> Result<i64, PromptError> {
+    match args.get("days").map(|d| d.trim()).filter(|d| !d.is_empty()) {
+        None => Ok(DEFAULT_STALE_DAYS),
+        Some(days) => days.parse::<i64>().ok().filter(|&d| d > 0).ok_or_else(|| {
+            PromptError::InvalidArgument {
+                name: "days".to_string(),
+                reason: format!("expected a positive number of days, got {}", days),
+            }
+        }),
+    }
+}
+
+/// Markdown list of stale observations for the stale facts prompt
+pub(crate) fn render_stale(
+    project: &str,
+    stale: &[(&Entity, Vec<&Observation>)],
+    days: i64,
+) -> String {
+    let mut out = format!("# Facts in {} older than {} days\n\n", project, days);
+    if stale.is_empty() {
+        out.push_str("No stale facts.\n");
+        return out;
+    }
+
+    for (entity, observations) in stale.iter().take(STALE_ENTITY_LIMIT) {
+        out.push_str(&format!("## {} ({})\n", entity.name, entity.entity_type.0));
+        for observation in observations {
+            out.push_str(&format!(
+                "- [{}] {}\n",
+                observation.created_at.format("%Y-%m-%d"),
+                observation.content
+            ));
+        }
+        out.push('\n');
+    }
+    if stale.len() > STALE_ENTITY_LIMIT {
+        out.push_str(&format!(
+            "...and {} more entities with stale facts\n",
+            stale.len() - STALE_ENTITY_LIMIT
+        ));
+    }
+    out
+}
+
+/// Markdown list of existing entities for the meeting notes prompt
+pub(crate) fn render_known_entities(project: &str, entities: &[Entity]) -> String {
+    let mut out = format!("# Entities already in {}\n\n", project);
+    if entities.is_empty() {
+        out.push_str("None yet.\n");
+    }
+    for entity in entities {
+        out.push_str(&format!("- {} ({})\n", entity.name, entity.entity_type.0));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsnip_core::ProjectId;
+
+    fn args(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_template() {
+        let source = r#"
+name = "standup"
+description = "Prepare a standup update"
+template = "Update for {{ project }}:\n{{resource parsnip://{project}/entity/{person}}}\nDone."
+
+[[arguments]]
+name = "project"
+required = true
+
+[[arguments]]
+name = "person"
+"#;
+        let template = PromptTemplate::parse(source, Path::new("standup.toml")).unwrap();
+        assert_eq!(template.prompt.name, "standup");
+        assert_eq!(
+            template.segments,
+            vec![
+                Segment::Text("Update for ".to_string()),
+                Segment::Argument("project".to_string()),
+                Segment::Text(":\n".to_string()),
+                Segment::Resource("parsnip://{project}/entity/{person}".to_string()),
+                Segment::Text("\nDone.".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            expand_uri(
+                "parsnip://{project}/entity/{person}",
+                &args(&[("project", "my work"), ("person", "Alice")])
+            ),
+            "parsnip://my%20work/entity/Alice"
+        );
+
+        assert!(template.prompt.check_arguments(&args(&[])).is_err());
+        assert!(template
+            .prompt
+            .check_arguments(&args(&[("project", "work")]))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let path = Path::new("bad.toml");
+        for source in [
+            "name = \"a\"\ntemplate = \"{{missing}}\"",
+            "name = \"a\"\ntemplate = \"{{resource parsnip://{missing}/graph}}\"",
+            "name = \"a\"\ntemplate = \"unclosed {{\"",
+            "name = \"\"\ntemplate = \"\"",
+            "template = \"no name\"",
+        ] {
+            let err = PromptTemplate::parse(source, path).unwrap_err();
+            assert_eq!(err.code(), -32603, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_library_overrides_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("summary.toml"),
+            "name = \"summarize_project\"\ntemplate = \"Custom\"",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("broken.toml"), "not toml").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let library = PromptLibrary::load_dir(dir.path());
+        let names: Vec<String> = library.list().into_iter().map(|p| p.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                RECALL_ENTITY,
+                RECORD_MEETING_NOTES,
+                REVIEW_STALE_FACTS,
+                SUMMARIZE_PROJECT
+            ]
+        );
+        assert!(library.template(SUMMARIZE_PROJECT).is_some());
+        assert_eq!(
+            PromptLibrary::load_dir(&dir.path().join("missing")).list(),
+            builtin_prompts()
+        );
+    }
+
+    #[test]
+    fn test_stale_observations() {
+        let now = Utc::now();
+        let mut alice = Entity::new(ProjectId::new(), "Alice", "person");
+        alice.add_observation("Works on billing");
+        alice.observations[0].created_at = now - chrono::Duration::days(200);
+        alice.add_observation("Moved to search");
+        let mut bob = Entity::new(ProjectId::new(), "Bob", "person");
+        bob.add_observation("Likes tea");
+        bob.observations[0].created_at = now - chrono::Duration::days(100);
+        let fresh = Entity::new(ProjectId::new(), "Carol", "person");
+
+        let entities = [bob, fresh, alice];
+        let stale = stale_observations(&entities, now - chrono::Duration::days(90));
+        let names: Vec<&str> = stale.iter().map(|(e, _)| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Alice", "Bob"]);
+        assert_eq!(stale[0].1.len(), 1);
+
+        let markdown = render_stale("work", &stale, 90);
+        assert!(markdown.contains("## Alice (person)"));
+        assert!(markdown.contains("Works on billing"));
+        assert!(!markdown.contains("Moved to search"));
+
+        assert_eq!(parse_stale_days(&args(&[])).unwrap(), DEFAULT_STALE_DAYS);
+        assert_eq!(parse_stale_days(&args(&[("days", "30")])).unwrap(), 30);
+        assert!(parse_stale_days(&args(&[("days", "-1")])).is_err());
+    }
+}
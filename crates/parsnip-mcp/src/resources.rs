@@ -0,0 +1,553 @@
+//! MCP resources: read-only views of projects, graphs and entities
+//!
+//! Resources are addressed by `parsnip://` URIs:
+//!
+//! - `parsnip://projects` lists every project
+//! - `parsnip://{project}/graph` is a project's entities and relations,
+//!   paged with `?page=N`
+//! - `parsnip://{project}/entity/{name}` is one entity with its relations
+//!
+//! Every resource renders as markdown by default, or as JSON with
+//! `?format=json`. Path segments are percent-encoded.
+
+use parsnip_core::{Entity, Relation};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::tools::{EntityResult, ProjectResult, RelationResult};
+
+/// URI scheme for all Parsnip resources
+pub const RESOURCE_SCHEME: &str = "parsnip://";
+
+/// Resources returned per `resources/list` page
+pub const RESOURCE_PAGE_SIZE: usize = 100;
+
+/// Entities per page of a graph resource
+pub const GRAPH_PAGE_SIZE: usize = 200;
+
+/// Resource errors, mapped to JSON-RPC error codes
+#[derive(Debug, Error)]
+pub enum ResourceError {
+    #[error("Invalid resource URI: {0}")]
+    InvalidUri(String),
+
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+
+    #[error("Resource not found: {0}")]
+    NotFound(String),
+
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+impl ResourceError {
+    /// JSON-RPC error code for this error
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::InvalidUri(_) | Self::InvalidCursor(_) => -32602,
+            Self::NotFound(_) => -32002,
+            Self::Storage(_) => -32603,
+        }
+    }
+}
+
+/// Rendering of a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResourceFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+impl ResourceFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Markdown => "text/markdown",
+            Self::Json => "application/json",
+        }
+    }
+}
+
+/// What a resource URI points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceTarget {
+    Projects,
+    Graph { project: String, page: usize },
+    Entity { project: String, name: String },
+}
+
+/// A parsed `parsnip://` URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceUri {
+    pub target: ResourceTarget,
+    pub format: ResourceFormat,
+}
+
+impl ResourceUri {
+    pub fn projects() -> Self {
+        Self {
+            target: ResourceTarget::Projects,
+            format: ResourceFormat::Markdown,
+        }
+    }
+
+    pub fn graph(project: impl Into<String>) -> Self {
+        Self {
+            target: ResourceTarget::Graph {
+                project: project.into(),
+                page: 1,
+            },
+            format: ResourceFormat::Markdown,
+        }
+    }
+
+    pub fn entity(project: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            target: ResourceTarget::Entity {
+                project: project.into(),
+                name: name.into(),
+            },
+            format: ResourceFormat::Markdown,
+        }
+    }
+
+    pub fn with_format(mut self, format: ResourceFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the graph page (ignored for other targets)
+    pub fn with_page(mut self, page: usize) -> Self {
+        if let ResourceTarget::Graph {
+            page: ref mut p, ..
+        } = self.target
+        {
+            *p = page.max(1);
+        }
+        self
+    }
+
+    /// Project the resource belongs to; `None` for the project list
+    pub fn project(&self) -> Option<&str> {
+        match &self.target {
+            ResourceTarget::Projects => None,
+            ResourceTarget::Graph { project, .. } | ResourceTarget::Entity { project, .. } => {
+                Some(project)
+            }
+        }
+    }
+
+    /// Parse a `parsnip://` URI
+    pub fn parse(uri: &str) -> Result<Self, ResourceError> {
+        let invalid = || ResourceError::InvalidUri(uri.to_string());
+        let rest = uri.strip_prefix(RESOURCE_SCHEME).ok_or_else(invalid)?;
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+
+        let mut format = ResourceFormat::Markdown;
+        let mut page = 1;
+        for pair in query.into_iter().flat_map(|q| q.split('&')) {
+            match pair.split_once('=') {
+                Some(("format", "json")) => format = ResourceFormat::Json,
+                Some(("format", "markdown" | "md")) => format = ResourceFormat::Markdown,
+                Some(("page", n)) => {
+                    page = n.parse().ok().filter(|&n| n >= 1).ok_or_else(invalid)?;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let segments: Vec<&str> = path.split('/').collect();
+        let target = match segments.as_slice() {
+            ["projects"] => ResourceTarget::Projects,
+            [project, "graph"] => ResourceTarget::Graph {
+                project: decode_segment(project).ok_or_else(invalid)?,
+                page,
+            },
+            [project, "entity", name] => ResourceTarget::Entity {
+                project: decode_segment(project).ok_or_else(invalid)?,
+                name: decode_segment(name).ok_or_else(invalid)?,
+            },
+            _ => return Err(invalid()),
+        };
+
+        Ok(Self { target, format })
+    }
+}
+
+impl std::fmt::Display for ResourceUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut params = Vec::new();
+        match &self.target {
+            ResourceTarget::Projects => write!(f, "{}projects", RESOURCE_SCHEME)?,
+            ResourceTarget::Graph { project, page } => {
+                write!(f, "{}{}/graph", RESOURCE_SCHEME, encode_segment(project))?;
+                if *page > 1 {
+                    params.push(format!("page={}", page));
+                }
+            }
+            ResourceTarget::Entity { project, name } => write!(
+                f,
+                "{}{}/entity/{}",
+                RESOURCE_SCHEME,
+                encode_segment(project),
+                encode_segment(name)
+            )?,
+        }
+        if self.format == ResourceFormat::Json {
+            params.push("format=json".to_string());
+        }
+        if !params.is_empty() {
+            write!(f, "?{}", params.join("&"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Percent-encode everything outside the RFC 3986 unreserved set
+pub(crate) fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn decode_segment(segment: &str) -> Option<String> {
+    if segment.is_empty() {
+        return None;
+    }
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Encode a list offset as an opaque cursor
+pub fn encode_cursor(offset: usize) -> String {
+    format!("offset:{}", offset)
+}
+
+/// Decode a cursor produced by [`encode_cursor`]
+pub fn decode_cursor(cursor: &str) -> Result<usize, ResourceError> {
+    cursor
+        .strip_prefix("offset:")
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| ResourceError::InvalidCursor(cursor.to_string()))
+}
+
+/// Encode the project and name of the last entity of a page as an opaque
+/// cursor, for listings ordered by name that resume after it
+pub fn encode_key_cursor(project: &str, name: &str) -> String {
+    format!("after:{}/{}", encode_segment(project), encode_segment(name))
+}
+
+/// Decode a cursor produced by [`encode_key_cursor`] into its project and
+/// entity name
+pub fn decode_key_cursor(cursor: &str) -> Result<(String, String), ResourceError> {
+    cursor
+        .strip_prefix("after:")
+        .and_then(|key| key.split_once('/'))
+        .and_then(|(project, name)| Some((decode_segment(project)?, decode_segment(name)?)))
+        .ok_or_else(|| ResourceError::InvalidCursor(cursor.to_string()))
+}
+
+/// Entry in `resources/list`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub mime_type: &'static str,
+}
+
+impl Resource {
+    pub fn new(uri: &ResourceUri, name: impl Into<String>) -> Self {
+        Self {
+            uri: uri.to_string(),
+            name: name.into(),
+            description: None,
+            mime_type: uri.format.mime_type(),
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+/// Entry in `resources/templates/list`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplate {
+    pub uri_template: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub mime_type: &'static str,
+}
+
+/// Contents returned by `resources/read`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    pub mime_type: &'static str,
+    pub text: String,
+}
+
+impl ResourceContents {
+    pub fn new(uri: &ResourceUri, text: String) -> Self {
+        Self {
+            uri: uri.to_string(),
+            mime_type: uri.format.mime_type(),
+            text,
+        }
+    }
+}
+
+/// Templates for addressing resources by project and entity name
+pub fn resource_templates() -> Vec<ResourceTemplate> {
+    vec![
+        ResourceTemplate {
+            uri_template: "parsnip://{project}/entity/{name}{?format}",
+            name: "Entity",
+            description: "An entity with its observations, tags and relations",
+            mime_type: "text/markdown",
+        },
+        ResourceTemplate {
+            uri_template: "parsnip://{project}/graph{?page,format}",
+            name: "Project graph",
+            description: "All entities and relations in a project, paged by entity",
+            mime_type: "text/markdown",
+        },
+    ]
+}
+
+/// Render the project list
+pub(crate) fn render_projects(projects: &[ProjectResult], format: ResourceFormat) -> String {
+    if format == ResourceFormat::Json {
+        return serde_json::to_string_pretty(&serde_json::json!({ "projects": projects }))
+            .unwrap_or_default();
+    }
+
+    let mut out = String::from("# Projects\n\n");
+    if projects.is_empty() {
+        out.push_str("No projects.\n");
+    }
+    for project in projects {
+        out.push_str(&format!(
+            "- **{}** ({} entities, {} relations) {}\n",
+            project.name,
+            project.entity_count,
+            project.relation_count,
+            ResourceUri::graph(&project.name)
+        ));
+        if let Some(description) = &project.description {
+            out.push_str(&format!("  {}\n", description));
+        }
+    }
+    out
+}
+
+/// Render one entity and the relations touching it
+pub(crate) fn render_entity(
+    project: &str,
+    entity: &Entity,
+    relations: &[Relation],
+    format: ResourceFormat,
+) -> String {
+    if format == ResourceFormat::Json {
+        let value = serde_json::json!({
+            "project": project,
+            "entity": EntityResult::from(entity),
+            "relations": relations.iter().map(RelationResult::from).collect::<Vec<_>>(),
+        });
+        return serde_json::to_string_pretty(&value).unwrap_or_default();
+    }
+
+    let mut out = format!("# {}\n\n", entity.name);
+    out.push_str(&format!("- Type: {}\n", entity.entity_type.0));
+    out.push_str(&format!("- Project: {}\n", project));
+    if !entity.tags.is_empty() {
+        out.push_str(&format!("- Tags: {}\n", entity.tags.join(", ")));
+    }
+    out.push_str(&format!("- Updated: {}\n", entity.updated_at.to_rfc3339()));
+
+    if !entity.observations.is_empty() {
+        out.push_str("\n## Observations\n\n");
+        for observation in &entity.observations {
+            out.push_str(&format!("- {}\n", observation.content));
+        }
+    }
+
+    if !relations.is_empty() {
+        out.push_str("\n## Relations\n\n");
+        for relation in relations {
+            out.push_str(&format!("- {}\n", relation_line(relation)));
+        }
+    }
+    out
+}
+
+/// Render one page of a project graph
+///
+/// `entities` is the page; `relations` are those touching it.
+pub(crate) fn render_graph(
+    project: &str,
+    entities: &[Entity],
+    relations: &[Relation],
+    page: usize,
+    total_pages: usize,
+    format: ResourceFormat,
+) -> String {
+    let next = (page < total_pages).then(|| {
+        ResourceUri::graph(project)
+            .with_page(page + 1)
+            .with_format(format)
+            .to_string()
+    });
+
+    if format == ResourceFormat::Json {
+        let value = serde_json::json!({
+            "project": project,
+            "entities": entities.iter().map(EntityResult::from).collect::<Vec<_>>(),
+            "relations": relations.iter().map(RelationResult::from).collect::<Vec<_>>(),
+            "page": page,
+            "totalPages": total_pages,
+            "nextUri": next,
+        });
+        return serde_json::to_string_pretty(&value).unwrap_or_default();
+    }
+
+    let mut out = format!("# {}\n\n", project);
+    if total_pages > 1 {
+        out.push_str(&format!("Page {} of {}\n\n", page, total_pages));
+    }
+
+    out.push_str("## Entities\n");
+    if entities.is_empty() {
+        out.push_str("\nNo entities.\n");
+    }
+    for entity in entities {
+        out.push_str(&format!(
+            "\n### {} ({})\n",
+            entity.name, entity.entity_type.0
+        ));
+        if !entity.tags.is_empty() {
+            out.push_str(&format!("Tags: {}\n", entity.tags.join(", ")));
+        }
+        for observation in &entity.observations {
+            out.push_str(&format!("- {}\n", observation.content));
+        }
+    }
+
+    if !relations.is_empty() {
+        out.push_str("\n## Relations\n\n");
+        for relation in relations {
+            out.push_str(&format!("- {}\n", relation_line(relation)));
+        }
+    }
+
+    if let Some(next) = next {
+        out.push_str(&format!("\nNext page: {}\n", next));
+    }
+    out
+}
+
+fn relation_line(relation: &Relation) -> String {
+    let line = format!(
+        "{} -[{}]-> {}",
+        relation.from_name, relation.relation_type, relation.to_name
+    );
+    match relation.weight {
+        Some(weight) => format!("{} (weight {})", line, weight),
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsnip_core::ProjectId;
+
+    #[test]
+    fn test_uri_round_trip() {
+        let uris = [
+            ResourceUri::projects(),
+            ResourceUri::graph("work").with_page(3),
+            ResourceUri::entity("my project", "John/Smith").with_format(ResourceFormat::Json),
+        ];
+        for uri in uris {
+            assert_eq!(ResourceUri::parse(&uri.to_string()).unwrap(), uri);
+        }
+
+        assert_eq!(
+            ResourceUri::entity("my project", "John/Smith").to_string(),
+            "parsnip://my%20project/entity/John%2FSmith"
+        );
+    }
+
+    #[test]
+    fn test_invalid_uris() {
+        for uri in [
+            "file:///etc/passwd",
+            "parsnip://",
+            "parsnip://work/entities",
+            "parsnip://work/graph?page=0",
+            "parsnip://work/graph?format=xml",
+            "parsnip://work/entity/%ZZ",
+        ] {
+            assert!(ResourceUri::parse(uri).is_err(), "{}", uri);
+        }
+        assert!(decode_cursor("bogus").is_err());
+        assert_eq!(decode_cursor(&encode_cursor(200)).unwrap(), 200);
+    }
+
+    #[test]
+    fn test_render_graph_links_next_page() {
+        let project_id = ProjectId::new();
+        let mut alice = Entity::new(project_id.clone(), "Alice", "person");
+        alice.add_observation("Leads the platform team");
+        let relations = vec![Relation::from_names(
+            project_id, "Alice", "Acme", "works_at",
+        )];
+
+        let markdown = render_graph(
+            "work",
+            &[alice.clone()],
+            &relations,
+            1,
+            2,
+            ResourceFormat::Markdown,
+        );
+        assert!(markdown.contains("### Alice (person)"));
+        assert!(markdown.contains("Alice -[works_at]-> Acme"));
+        assert!(markdown.contains("Next page: parsnip://work/graph?page=2"));
+
+        let json = render_graph("work", &[alice], &relations, 2, 2, ResourceFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["entities"][0]["name"], "Alice");
+        assert!(value["nextUri"].is_null());
+    }
+}
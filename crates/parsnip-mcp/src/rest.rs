@@ -0,0 +1,1526 @@
+//! REST API over the knowledge graph, served next to MCP on the HTTP transport
+//!
+//! | Method | Path | Mirrors tool |
+//! |---|---|---|
+//! | `GET` | `/projects/{project}/entities/{name}` | `open_nodes` |
+//! | `POST` | `/projects/{project}/entities/{name}` | `create_entities` |
+//! | `PATCH` | `/projects/{project}/entities/{name}` | `update_entity` |
+//! | `DELETE` | `/projects/{project}/entities/{name}` | `delete_entities` |
+//! | `GET` | `/projects/{project}/relations` | `list_relations` |
+//! | `POST` | `/projects/{project}/relations` | `create_relations` |
+//! | `DELETE` | `/projects/{project}/relations` | `delete_relations` |
+//! | `GET` | `/search?q=` | `search_knowledge` |
+//! | `GET` | `/traverse?start=` | `traverse_graph` |
+//!
+//! A route is allowed exactly when the tool it mirrors is, so token profiles
+//! and read-only mode apply unchanged. `GET` responses carry a strong `ETag`
+//! and answer a matching `If-None-Match` with `304 Not Modified`; `PATCH` and
+//! `DELETE` of an entity refuse with `412 Precondition Failed` when `If-Match`
+//! names another version, holding the entity's lock from the check to the
+//! write. `/openapi.json` describes every route, generated
+//! from the same table and types the handlers use.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Extension, Path, Query, State,
+    },
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use parsnip_core::{
+    validate_entity_name, validate_observation, validate_project_name, validate_tag, Entity,
+    Project, Relation, ValidationError,
+};
+use parsnip_storage::ops::{self, EntityUpdate, OpsError, RelationFilter};
+use parsnip_storage::{StorageBackend, StorageError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::permissions::Permissions;
+use crate::schema::input_schema;
+use crate::tools::{
+    ContentBlock, DirectionArg, Fields, ProjectError, SearchModeArg, ToolCallResponse, ToolContext,
+    ToolError,
+};
+use crate::McpServer;
+
+/// Session REST requests are made from
+pub const REST_SESSION: &str = "rest";
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Types
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// An entity as the REST API returns it
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RestEntity {
+    pub name: String,
+    pub entity_type: String,
+    /// Oldest first
+    pub observations: Vec<RestObservation>,
+    pub tags: Vec<String>,
+    /// RFC 3339 timestamp
+    pub created_at: String,
+    /// RFC 3339 timestamp
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RestObservation {
+    pub id: String,
+    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// RFC 3339 timestamp
+    pub created_at: String,
+}
+
+impl From<&Entity> for RestEntity {
+    fn from(entity: &Entity) -> Self {
+        Self {
+            name: entity.name.clone(),
+            entity_type: entity.entity_type.0.clone(),
+            observations: entity
+                .observations
+                .iter()
+                .map(|o| RestObservation {
+                    id: o.id.to_string(),
+                    content: o.content.clone(),
+                    source: o.source.clone(),
+                    created_at: o.created_at.to_rfc3339(),
+                })
+                .collect(),
+            tags: entity.tags.clone(),
+            created_at: entity.created_at.to_rfc3339(),
+            updated_at: entity.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Body of `POST /projects/{project}/entities/{name}`
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewEntity {
+    /// Entity type (e.g., person, project, concept)
+    pub entity_type: String,
+    #[serde(default)]
+    pub observations: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Body of `PATCH /projects/{project}/entities/{name}`
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityPatch {
+    /// New entity type
+    pub entity_type: Option<String>,
+    /// Observations to append
+    #[serde(default)]
+    pub add_observations: Vec<String>,
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+}
+
+/// A relation as the REST API returns it
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RestRelation {
+    pub from: String,
+    pub to: String,
+    pub relation_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+}
+
+impl From<Relation> for RestRelation {
+    fn from(relation: Relation) -> Self {
+        Self {
+            from: relation.from_name,
+            to: relation.to_name,
+            relation_type: relation.relation_type,
+            weight: relation.weight,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RelationList {
+    pub relations: Vec<RestRelation>,
+}
+
+/// Body of `POST /projects/{project}/relations`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewRelation {
+    /// Source entity name, in this project
+    pub from: String,
+    /// Target entity name
+    pub to: String,
+    /// Project containing the target entity (default: auto-detected if unique)
+    pub to_project: Option<String>,
+    /// Relationship type (e.g., works_at, manages, depends_on)
+    pub relation_type: String,
+}
+
+/// Query of `GET /projects/{project}/relations`
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RelationQuery {
+    /// Only relations from this entity
+    pub from: Option<String>,
+    /// Only relations to this entity
+    pub to: Option<String>,
+    /// Only relations of this type (case-insensitive)
+    #[serde(rename = "type")]
+    pub relation_type: Option<String>,
+}
+
+/// Query of `DELETE /projects/{project}/relations`
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RelationKey {
+    /// Source entity name
+    pub from: String,
+    /// Target entity name
+    pub to: String,
+    /// Relationship type
+    #[serde(rename = "type")]
+    pub relation_type: String,
+}
+
+/// Query of `GET /search`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    /// Search text
+    pub q: Option<String>,
+    /// Project to search; omit to search every project
+    pub project: Option<String>,
+    /// Search mode
+    #[serde(default)]
+    pub mode: SearchModeArg,
+    /// Comma-separated tags results must have
+    pub tags: Option<String>,
+    /// nextCursor from a previous response; takes precedence over page
+    pub cursor: Option<String>,
+    /// Page number (0-indexed) when no cursor is given
+    pub page: Option<usize>,
+    /// Results per page (default: 100, max: 1000)
+    #[schemars(range(min = 1, max = 1000))]
+    pub page_size: Option<usize>,
+    /// Entity fields to include: names, types (with tags) or full (default)
+    #[serde(default)]
+    pub fields: Fields,
+    /// Include only the most recent observations of each entity
+    pub max_observations: Option<usize>,
+}
+
+/// Query of `GET /traverse`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TraverseQuery {
+    /// Project of the starting entity (default: 'default')
+    pub project: Option<String>,
+    /// Starting entity name
+    pub start: String,
+    /// Target entity name for path finding
+    pub target: Option<String>,
+    /// Project containing the target entity (implies crossProject)
+    pub target_project: Option<String>,
+    /// Maximum traversal depth (default: 10)
+    pub max_depth: Option<u32>,
+    /// Traversal direction
+    #[serde(default)]
+    pub direction: DirectionArg,
+    /// Comma-separated relation types to follow
+    pub relation_types: Option<String>,
+    /// Comma-separated entity types to visit
+    pub entity_types: Option<String>,
+    /// Use weighted shortest path (Dijkstra) when finding paths
+    #[serde(default)]
+    pub weighted: bool,
+    /// Follow relations into other projects
+    #[serde(default)]
+    pub cross_project: bool,
+    /// Entity fields to include: names, types (with tags) or full (default)
+    #[serde(default)]
+    pub fields: Fields,
+    /// Include only the most recent observations of each entity
+    pub max_observations: Option<usize>,
+}
+
+/// Body of every error response
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ErrorBody {
+    pub error: String,
+    /// Problems with individual arguments, when there are several
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Errors
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Errors of REST requests, each with its HTTP status
+#[derive(Debug, Error)]
+pub enum RestError {
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{message}")]
+    BadRequest {
+        message: String,
+        details: Option<Value>,
+    },
+
+    #[error("Precondition failed: the current ETag is {0}")]
+    PreconditionFailed(String),
+
+    /// Only the message is kept; storage errors are large
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+impl RestError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::BadRequest {
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            Self::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for RestError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let mut response = (
+            status,
+            Json(ErrorBody {
+                error: self.to_string(),
+                details: match &self {
+                    Self::BadRequest { details, .. } => details.clone(),
+                    _ => None,
+                },
+            }),
+        )
+            .into_response();
+        if let Self::PreconditionFailed(etag) = &self {
+            if let Ok(value) = HeaderValue::from_str(etag) {
+                response.headers_mut().insert(header::ETAG, value);
+            }
+        }
+        response
+    }
+}
+
+impl From<ToolError> for RestError {
+    fn from(e: ToolError) -> Self {
+        match e {
+            ToolError::NotPermitted(_) => Self::Forbidden(e.to_string()),
+            ToolError::UnknownTool(_) => Self::NotFound(e.to_string()),
+            ToolError::InvalidArguments { .. } => Self::BadRequest {
+                details: e.data().and_then(|data| data.get("errors").cloned()),
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+impl From<ProjectError> for RestError {
+    fn from(e: ProjectError) -> Self {
+        match e {
+            ProjectError::NotPermitted(_) => Self::Forbidden(e.to_string()),
+            ProjectError::Storage(e) => e.into(),
+        }
+    }
+}
+
+impl From<OpsError> for RestError {
+    fn from(e: OpsError) -> Self {
+        match e {
+            OpsError::EntityNotFound { .. } | OpsError::ProjectNotFound(_) => {
+                Self::NotFound(e.to_string())
+            }
+            OpsError::ProjectExists(_) => Self::Conflict(e.to_string()),
+            OpsError::Invalid(e) => e.into(),
+            OpsError::Storage(e) => e.into(),
+        }
+    }
+}
+
+impl From<StorageError> for RestError {
+    fn from(e: StorageError) -> Self {
+        Self::Storage(e.to_string())
+    }
+}
+
+impl From<ValidationError> for RestError {
+    fn from(e: ValidationError) -> Self {
+        Self::bad_request(e.to_string())
+    }
+}
+
+impl From<JsonRejection> for RestError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::bad_request(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for RestError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::bad_request(rejection.body_text())
+    }
+}
+
+type RestResult = Result<Response, RestError>;
+
+// ─────────────────────────────────────────────────────────────────────────────
+// ETags
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Strong validator of a response body: its FNV-1a hash
+fn etag(body: &[u8]) -> String {
+    let hash = body.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("\"{:016x}\"", hash)
+}
+
+/// Whether a conditional header lists the ETag, or `*`
+///
+/// `If-None-Match` compares weakly, ignoring a `W/` prefix; `If-Match`
+/// compares strongly.
+fn etag_listed(value: &HeaderValue, etag: &str, weak: bool) -> bool {
+    let Ok(value) = value.to_str() else {
+        return false;
+    };
+    value.split(',').map(str::trim).any(|candidate| {
+        let candidate = match candidate.strip_prefix("W/") {
+            Some(stripped) if weak => stripped,
+            _ => candidate,
+        };
+        candidate == "*" || candidate == etag
+    })
+}
+
+/// A JSON body with its ETag
+struct Tagged {
+    body: Vec<u8>,
+    etag: String,
+}
+
+impl Tagged {
+    fn new<T: Serialize>(value: &T) -> Self {
+        let body = serde_json::to_vec(value).unwrap_or_default();
+        let etag = etag(&body);
+        Self { body, etag }
+    }
+
+    fn respond(self, status: StatusCode) -> Response {
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, "application/json")],
+            self.body,
+        )
+            .into_response();
+        if let Ok(value) = HeaderValue::from_str(&self.etag) {
+            response.headers_mut().insert(header::ETAG, value);
+        }
+        response
+    }
+
+    /// `304 Not Modified` if the client has this version, else the body
+    fn respond_to(self, headers: &HeaderMap) -> Response {
+        let fresh = headers
+            .get(header::IF_NONE_MATCH)
+            .is_some_and(|value| etag_listed(value, &self.etag, true));
+        if !fresh {
+            return self.respond(StatusCode::OK);
+        }
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        if let Ok(value) = HeaderValue::from_str(&self.etag) {
+            response.headers_mut().insert(header::ETAG, value);
+        }
+        response
+    }
+
+    /// Fail unless `If-Match`, when present, names this version
+    fn check_if_match(&self, headers: &HeaderMap) -> Result<(), RestError> {
+        match headers.get(header::IF_MATCH) {
+            Some(value) if !etag_listed(value, &self.etag, false) => {
+                Err(RestError::PreconditionFailed(self.etag.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Handlers
+// ─────────────────────────────────────────────────────────────────────────────
+
+type ServerState<S> = State<Arc<McpServer<S>>>;
+
+/// Permissions of the request's token, within the server's
+fn caller<S: StorageBackend + Send + Sync + 'static>(
+    server: &McpServer<S>,
+    token: Option<Extension<Permissions>>,
+) -> Permissions {
+    let token = token.map(|Extension(p)| p).unwrap_or_default();
+    server.permissions().restrict(&token)
+}
+
+/// Context for a route that mirrors `tool`, if the caller may use it
+fn authorize<S: StorageBackend + Send + Sync + 'static>(
+    server: &McpServer<S>,
+    token: Option<Extension<Permissions>>,
+    tool: &str,
+) -> Result<ToolContext, RestError> {
+    let permissions = caller(server, token);
+    let allowed = server
+        .tools()
+        .get(tool)
+        .is_some_and(|d| permissions.allows_tool(d.name, d.annotations.read_only_hint));
+    if !allowed {
+        return Err(ToolError::NotPermitted(tool.to_string()).into());
+    }
+    Ok(server.tool_context(REST_SESSION, &permissions))
+}
+
+/// Log a route's writes as a call of the tool it mirrors, with the path
+/// parameters and the body or query as arguments
+async fn audit(
+    ctx: &ToolContext,
+    tool: &str,
+    project: &str,
+    name: Option<&str>,
+    input: &impl Serialize,
+) {
+    let mut arguments = serde_json::to_value(input).unwrap_or_default();
+    if let Value::Object(map) = &mut arguments {
+        map.insert("projectId".to_string(), json!(project));
+        if let Some(name) = name {
+            map.insert("name".to_string(), json!(name));
+        }
+    }
+    if let Err(e) = ctx.record_audit(tool, arguments).await {
+        tracing::error!("Failed to append {} to the audit log: {}", tool, e);
+    }
+}
+
+async fn existing_project(ctx: &ToolContext, name: &str) -> Result<Project, RestError> {
+    ctx.find_project(name)
+        .await?
+        .ok_or_else(|| OpsError::ProjectNotFound(name.to_string()).into())
+}
+
+async fn existing_entity(
+    ctx: &ToolContext,
+    project: &Project,
+    name: &str,
+) -> Result<Entity, RestError> {
+    ctx.storage()
+        .get_entity(name, &project.id)
+        .await?
+        .ok_or_else(|| {
+            OpsError::EntityNotFound {
+                name: name.to_string(),
+                project: project.name.clone(),
+            }
+            .into()
+        })
+}
+
+/// JSON result of a tool call; error results are bad requests
+fn tool_json(result: Result<ToolCallResponse, ToolError>) -> Result<Value, RestError> {
+    let response = result?;
+    let text: String = response
+        .content
+        .into_iter()
+        .map(|block| match block {
+            ContentBlock::Text { text } => text,
+        })
+        .collect();
+    if response.is_error == Some(true) {
+        return Err(RestError::bad_request(text));
+    }
+    Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
+}
+
+/// Comma-separated list, without empty items
+fn split_list(list: Option<&str>) -> Option<Vec<String>> {
+    list.map(|list| {
+        list.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+/// Percent-encode a path segment
+fn encode_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+async fn get_entity<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    Path((project, name)): Path<(String, String)>,
+    token: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+) -> RestResult {
+    let ctx = authorize(&server, token, "open_nodes")?;
+    let project = existing_project(&ctx, &project).await?;
+    let entity = existing_entity(&ctx, &project, &name).await?;
+    Ok(Tagged::new(&RestEntity::from(&entity)).respond_to(&headers))
+}
+
+async fn create_entity<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    Path((project_name, name)): Path<(String, String)>,
+    token: Option<Extension<Permissions>>,
+    body: Result<Json<NewEntity>, JsonRejection>,
+) -> RestResult {
+    let ctx = authorize(&server, token, "create_entities")?;
+    let Json(body) = body?;
+    validate_project_name(&project_name)?;
+    validate_entity_name(&name)?;
+    for observation in &body.observations {
+        validate_observation(observation)?;
+    }
+    for tag in &body.tags {
+        validate_tag(tag)?;
+    }
+
+    let project = ctx.get_or_create_project(&project_name).await?;
+    let _guard = ctx.lock_entity(&project.id, &name).await;
+    if ctx
+        .storage()
+        .get_entity(&name, &project.id)
+        .await?
+        .is_some()
+    {
+        return Err(RestError::Conflict(format!(
+            "Entity '{}' already exists in project '{}'",
+            name, project.name
+        )));
+    }
+
+    let mut entity = Entity::new(project.id.clone(), &name, body.entity_type.as_str());
+    for observation in &body.observations {
+        entity.add_observation(observation);
+    }
+    for tag in &body.tags {
+        entity.add_tag(tag);
+    }
+    ctx.storage().save_entity(&entity).await?;
+    audit(&ctx, "create_entities", &project.name, Some(&name), &body).await;
+
+    let mut response = Tagged::new(&RestEntity::from(&entity)).respond(StatusCode::CREATED);
+    let location = format!(
+        "/projects/{}/entities/{}",
+        encode_segment(&project.name),
+        encode_segment(&entity.name)
+    );
+    if let Ok(value) = HeaderValue::from_str(&location) {
+        response.headers_mut().insert(header::LOCATION, value);
+    }
+    Ok(response)
+}
+
+async fn update_entity<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    Path((project, name)): Path<(String, String)>,
+    token: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+    body: Result<Json<EntityPatch>, JsonRejection>,
+) -> RestResult {
+    let ctx = authorize(&server, token, "update_entity")?;
+    let Json(body) = body?;
+    let project = existing_project(&ctx, &project).await?;
+    // Nothing else writes the entity between the check and the write
+    let _guard = ctx.lock_entity(&project.id, &name).await;
+    let entity = existing_entity(&ctx, &project, &name).await?;
+    Tagged::new(&RestEntity::from(&entity)).check_if_match(&headers)?;
+
+    let update = EntityUpdate {
+        entity_type: body.entity_type.clone(),
+        add_observations: body.add_observations.clone(),
+        add_tags: body.add_tags.clone(),
+        remove_tags: body.remove_tags.clone(),
+    };
+    let outcome = ops::update_entity(ctx.storage(), &project, &name, &update).await?;
+    audit(&ctx, "update_entity", &project.name, Some(&name), &body).await;
+    Ok(Tagged::new(&RestEntity::from(&outcome.entity)).respond(StatusCode::OK))
+}
+
+async fn delete_entity<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    Path((project, name)): Path<(String, String)>,
+    token: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+) -> RestResult {
+    let ctx = authorize(&server, token, "delete_entities")?;
+    let project = existing_project(&ctx, &project).await?;
+    let _guard = ctx.lock_entity(&project.id, &name).await;
+    let entity = existing_entity(&ctx, &project, &name).await?;
+    Tagged::new(&RestEntity::from(&entity)).check_if_match(&headers)?;
+
+    ctx.storage()
+        .delete_relations_for_entity(&name, &project.id)
+        .await?;
+    ctx.storage().delete_entity(&name, &project.id).await?;
+    audit(
+        &ctx,
+        "delete_entities",
+        &project.name,
+        Some(&name),
+        &json!({}),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn list_relations<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    Path(project): Path<String>,
+    token: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+    query: Result<Query<RelationQuery>, QueryRejection>,
+) -> RestResult {
+    let ctx = authorize(&server, token, "list_relations")?;
+    let Query(query) = query?;
+    let project = existing_project(&ctx, &project).await?;
+    let filter = RelationFilter {
+        from: query.from,
+        to: query.to,
+        relation_type: query.relation_type,
+    };
+    let relations = ops::list_relations(ctx.storage(), &project, &filter).await?;
+    let list = RelationList {
+        relations: relations.into_iter().map(RestRelation::from).collect(),
+    };
+    Ok(Tagged::new(&list).respond_to(&headers))
+}
+
+async fn create_relation<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    Path(project): Path<String>,
+    token: Option<Extension<Permissions>>,
+    body: Result<Json<NewRelation>, JsonRejection>,
+) -> RestResult {
+    let Json(body) = body?;
+    let token = token.map(|Extension(p)| p).unwrap_or_default();
+    // The tool resolves endpoints across projects
+    let relation = without_nulls(json!({
+        "from": body.from,
+        "fromProjectId": project,
+        "to": body.to,
+        "toProjectId": body.to_project,
+        "relationType": body.relation_type,
+    }));
+    let args = json!({"projectId": project, "relations": [relation]});
+    tool_json(
+        server
+            .call_tool(REST_SESSION, &token, "create_relations", args)
+            .await,
+    )?;
+
+    let relation = RestRelation {
+        from: body.from,
+        to: body.to,
+        relation_type: body.relation_type,
+        weight: None,
+    };
+    Ok(Tagged::new(&relation).respond(StatusCode::CREATED))
+}
+
+async fn delete_relation<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    Path(project): Path<String>,
+    token: Option<Extension<Permissions>>,
+    query: Result<Query<RelationKey>, QueryRejection>,
+) -> RestResult {
+    let ctx = authorize(&server, token, "delete_relations")?;
+    let Query(key) = query?;
+    let project = existing_project(&ctx, &project).await?;
+
+    let filter = RelationFilter {
+        from: Some(key.from.clone()),
+        to: Some(key.to.clone()),
+        relation_type: None,
+    };
+    let exists = ops::list_relations(ctx.storage(), &project, &filter)
+        .await?
+        .iter()
+        .any(|r| r.relation_type == key.relation_type);
+    if !exists {
+        return Err(RestError::NotFound(format!(
+            "Relation '{}' -[{}]-> '{}' not found in project '{}'",
+            key.from, key.relation_type, key.to, project.name
+        )));
+    }
+
+    ctx.storage()
+        .delete_relation(&key.from, &key.to, &key.relation_type, &project.id)
+        .await?;
+    audit(&ctx, "delete_relations", &project.name, None, &key).await;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn search<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    token: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+    query: Result<Query<SearchQuery>, QueryRejection>,
+) -> RestResult {
+    let Query(query) = query?;
+    let token = token.map(|Extension(p)| p).unwrap_or_default();
+    let args = json!({
+        "query": query.q,
+        "projectId": query.project,
+        "searchMode": query.mode,
+        "exactTags": split_list(query.tags.as_deref()),
+        "cursor": query.cursor,
+        "page": query.page,
+        "pageSize": query.page_size,
+        "fields": query.fields,
+        "maxObservations": query.max_observations,
+    });
+    let result = tool_json(
+        server
+            .call_tool(
+                REST_SESSION,
+                &token,
+                "search_knowledge",
+                without_nulls(args),
+            )
+            .await,
+    )?;
+    Ok(Tagged::new(&result).respond_to(&headers))
+}
+
+async fn traverse<S: StorageBackend + Send + Sync + 'static>(
+    State(server): ServerState<S>,
+    token: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+    query: Result<Query<TraverseQuery>, QueryRejection>,
+) -> RestResult {
+    let Query(query) = query?;
+    let token = token.map(|Extension(p)| p).unwrap_or_default();
+    let args = json!({
+        "projectId": query.project,
+        "start": query.start,
+        "target": query.target,
+        "targetProjectId": query.target_project,
+        "maxDepth": query.max_depth,
+        "direction": query.direction,
+        "relationTypeFilter": split_list(query.relation_types.as_deref()),
+        "entityTypeFilter": split_list(query.entity_types.as_deref()),
+        "useWeights": query.weighted,
+        "crossProject": query.cross_project,
+        "fields": query.fields,
+        "maxObservations": query.max_observations,
+    });
+    let result = tool_json(
+        server
+            .call_tool(REST_SESSION, &token, "traverse_graph", without_nulls(args))
+            .await,
+    )?;
+    Ok(Tagged::new(&result).respond_to(&headers))
+}
+
+/// Drop absent query parameters, which tool schemas do not accept as null
+fn without_nulls(mut args: Value) -> Value {
+    if let Some(object) = args.as_object_mut() {
+        object.retain(|_, value| !value.is_null());
+    }
+    args
+}
+
+async fn openapi_handler() -> Json<Value> {
+    Json(openapi())
+}
+
+/// Router serving the REST API and its OpenAPI document
+pub fn rest_router<S: StorageBackend + Send + Sync + 'static>(server: Arc<McpServer<S>>) -> Router {
+    Router::new()
+        .route("/openapi.json", get(openapi_handler))
+        .route(
+            "/projects/:project/entities/:name",
+            get(get_entity::<S>)
+                .post(create_entity::<S>)
+                .patch(update_entity::<S>)
+                .delete(delete_entity::<S>),
+        )
+        .route(
+            "/projects/:project/relations",
+            get(list_relations::<S>)
+                .post(create_relation::<S>)
+                .delete(delete_relation::<S>),
+        )
+        .route("/search", get(search::<S>))
+        .route("/traverse", get(traverse::<S>))
+        .with_state(server)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// OpenAPI
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// A named schema in `components.schemas`
+struct Component {
+    name: &'static str,
+    schema: Value,
+}
+
+impl Component {
+    fn of<T: JsonSchema>(name: &'static str) -> Self {
+        Self {
+            name,
+            schema: input_schema::<T>(),
+        }
+    }
+
+    fn reference(&self) -> Value {
+        json!({"$ref": format!("#/components/schemas/{}", self.name)})
+    }
+}
+
+/// A route as the OpenAPI document describes it
+struct Operation {
+    method: Method,
+    /// OpenAPI path template
+    path: &'static str,
+    summary: &'static str,
+    /// Tool whose permission the route needs
+    tool: &'static str,
+    query: Option<Value>,
+    body: Option<Component>,
+    status: StatusCode,
+    response: Option<Component>,
+}
+
+/// Every REST route
+fn operations() -> Vec<Operation> {
+    const ENTITY: &str = "/projects/{project}/entities/{name}";
+    const RELATIONS: &str = "/projects/{project}/relations";
+    let operation = |method, path, summary, tool, status| Operation {
+        method,
+        path,
+        summary,
+        tool,
+        query: None,
+        body: None,
+        status,
+        response: None,
+    };
+
+    vec![
+        Operation {
+            response: Some(Component::of::<RestEntity>("Entity")),
+            ..operation(
+                Method::GET,
+                ENTITY,
+                "Get an entity",
+                "open_nodes",
+                StatusCode::OK,
+            )
+        },
+        Operation {
+            body: Some(Component::of::<NewEntity>("NewEntity")),
+            response: Some(Component::of::<RestEntity>("Entity")),
+            ..operation(
+                Method::POST,
+                ENTITY,
+                "Create an entity, and its project if needed",
+                "create_entities",
+                StatusCode::CREATED,
+            )
+        },
+        Operation {
+            body: Some(Component::of::<EntityPatch>("EntityPatch")),
+            response: Some(Component::of::<RestEntity>("Entity")),
+            ..operation(
+                Method::PATCH,
+                ENTITY,
+                "Update an entity",
+                "update_entity",
+                StatusCode::OK,
+            )
+        },
+        operation(
+            Method::DELETE,
+            ENTITY,
+            "Delete an entity and its relations",
+            "delete_entities",
+            StatusCode::NO_CONTENT,
+        ),
+        Operation {
+            query: Some(input_schema::<RelationQuery>()),
+            response: Some(Component::of::<RelationList>("RelationList")),
+            ..operation(
+                Method::GET,
+                RELATIONS,
+                "List the relations of a project",
+                "list_relations",
+                StatusCode::OK,
+            )
+        },
+        Operation {
+            body: Some(Component::of::<NewRelation>("NewRelation")),
+            response: Some(Component::of::<RestRelation>("Relation")),
+            ..operation(
+                Method::POST,
+                RELATIONS,
+                "Create a relation",
+                "create_relations",
+                StatusCode::CREATED,
+            )
+        },
+        Operation {
+            query: Some(input_schema::<RelationKey>()),
+            ..operation(
+                Method::DELETE,
+                RELATIONS,
+                "Delete a relation",
+                "delete_relations",
+                StatusCode::NO_CONTENT,
+            )
+        },
+        Operation {
+            query: Some(input_schema::<SearchQuery>()),
+            ..operation(
+                Method::GET,
+                "/search",
+                "Search entities; the result of the search_knowledge tool",
+                "search_knowledge",
+                StatusCode::OK,
+            )
+        },
+        Operation {
+            query: Some(input_schema::<TraverseQuery>()),
+            ..operation(
+                Method::GET,
+                "/traverse",
+                "Traverse the graph from an entity; the result of the traverse_graph tool",
+                "traverse_graph",
+                StatusCode::OK,
+            )
+        },
+    ]
+}
+
+/// Parameters of an operation: its path segments, then its query
+fn parameters(operation: &Operation) -> Vec<Value> {
+    let mut parameters: Vec<Value> = operation
+        .path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": {"type": "string"},
+            })
+        })
+        .collect();
+
+    if let Some(query) = &operation.query {
+        let required = query["required"].as_array().cloned().unwrap_or_default();
+        if let Some(properties) = query["properties"].as_object() {
+            for (name, schema) in properties {
+                let mut schema = schema.clone();
+                let description = schema.as_object_mut().and_then(|s| s.remove("description"));
+                let mut parameter = json!({
+                    "name": name,
+                    "in": "query",
+                    "required": required.contains(&Value::String(name.clone())),
+                    "schema": schema,
+                });
+                if let Some(description) = description {
+                    parameter["description"] = description;
+                }
+                parameters.push(parameter);
+            }
+        }
+    }
+
+    let conditional = match operation.method {
+        Method::GET => Some(header::IF_NONE_MATCH),
+        Method::PATCH | Method::DELETE if operation.path.contains("/entities/") => {
+            Some(header::IF_MATCH)
+        }
+        _ => None,
+    };
+    if let Some(name) = conditional {
+        parameters.push(json!({
+            "name": name.as_str(),
+            "in": "header",
+            "required": false,
+            "schema": {"type": "string"},
+        }));
+    }
+    parameters
+}
+
+/// OpenAPI 3.0 document of the REST API
+pub fn openapi() -> Value {
+    let error = Component::of::<ErrorBody>("Error");
+    let mut paths = serde_json::Map::new();
+    let mut schemas = serde_json::Map::new();
+    schemas.insert(error.name.to_string(), error.schema.clone());
+
+    for operation in operations() {
+        let mut responses = serde_json::Map::new();
+        let mut success = json!({
+            "description": operation.status.canonical_reason().unwrap_or_default(),
+        });
+        if let Some(response) = &operation.response {
+            success["content"] = json!({"application/json": {"schema": response.reference()}});
+            success["headers"] = json!({"ETag": {"schema": {"type": "string"}}});
+        }
+        responses.insert(operation.status.as_u16().to_string(), success);
+        if operation.method == Method::GET {
+            responses.insert("304".to_string(), json!({"description": "Not Modified"}));
+        } else if operation.path.contains("/entities/") && operation.method != Method::POST {
+            responses.insert(
+                "412".to_string(),
+                json!({"description": "If-Match names another version"}),
+            );
+        }
+        responses.insert(
+            "default".to_string(),
+            json!({
+                "description": "Error",
+                "content": {"application/json": {"schema": error.reference()}},
+            }),
+        );
+
+        let mut entry = json!({
+            "summary": operation.summary,
+            "operationId": format!("{}_{}", operation.method.as_str().to_lowercase(), operation.tool),
+            "x-mcp-tool": operation.tool,
+            "parameters": parameters(&operation),
+            "responses": responses,
+        });
+        if let Some(body) = &operation.body {
+            entry["requestBody"] = json!({
+                "required": true,
+                "content": {"application/json": {"schema": body.reference()}},
+            });
+        }
+
+        for component in [&operation.body, &operation.response].into_iter().flatten() {
+            schemas.insert(component.name.to_string(), component.schema.clone());
+        }
+        let path = paths
+            .entry(operation.path)
+            .or_insert_with(|| Value::Object(Default::default()));
+        path[operation.method.as_str().to_lowercase()] = entry;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Parsnip REST API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearerAuth": {"type": "http", "scheme": "bearer"},
+            },
+        },
+        // Tokens are only required when the server is configured with some
+        "security": [{"bearerAuth": []}, {}],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sse::{create_router, AuthToken, HttpOptions};
+    use crate::tools::ToolRegistry;
+    use crate::DEFAULT_SESSION;
+    use axum::body::Body;
+    use axum::http::Request;
+    use parsnip_storage::MemoryStorage;
+    use tower::ServiceExt;
+
+    fn router() -> Router {
+        let server = Arc::new(McpServer::new(Arc::new(MemoryStorage::new())));
+        let options = HttpOptions::new()
+            .with_auth_token(Some("admin".to_string()))
+            .with_token(
+                AuthToken::new("recall", "recall")
+                    .with_permissions(Permissions::new().with_read_only(true)),
+            );
+        create_router(server, options)
+    }
+
+    async fn send(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> (StatusCode, HeaderMap, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, "Bearer admin");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = router
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, headers, json)
+    }
+
+    #[test]
+    fn test_etag_listed() {
+        let tag = etag(b"{}");
+        assert_eq!(tag, etag(b"{}"));
+        assert_ne!(tag, etag(b"[]"));
+
+        let listed =
+            |header: &str, weak| etag_listed(&HeaderValue::from_str(header).unwrap(), &tag, weak);
+        assert!(listed(&format!("\"x\", {}", tag), false));
+        assert!(listed("*", false));
+        assert!(listed(&format!("W/{}", tag), true));
+        assert!(!listed(&format!("W/{}", tag), false));
+        assert!(!listed("\"x\"", true));
+    }
+
+    #[tokio::test]
+    async fn test_entity_crud_with_etags() {
+        let router = router();
+        let uri = "/projects/work/entities/Alice%20Smith";
+
+        let (status, headers, body) = send(
+            &router,
+            Method::POST,
+            uri,
+            &[],
+            Some(json!({"entityType": "person", "observations": ["Likes tea"]})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(headers[header::LOCATION], uri);
+        assert_eq!(body["name"], "Alice Smith");
+        assert_eq!(body["observations"][0]["content"], "Likes tea");
+        let created = headers[header::ETAG].to_str().unwrap().to_string();
+
+        let (status, ..) = send(
+            &router,
+            Method::POST,
+            uri,
+            &[],
+            Some(json!({"entityType": "person"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, headers, _) = send(&router, Method::GET, uri, &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::ETAG], created.as_str());
+        let (status, ..) = send(
+            &router,
+            Method::GET,
+            uri,
+            &[("if-none-match", created.as_str())],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        let patch = json!({"addTags": ["friend"]});
+        let (status, headers, body) = send(
+            &router,
+            Method::PATCH,
+            uri,
+            &[("if-match", created.as_str())],
+            Some(patch.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["tags"], json!(["friend"]));
+        let updated = headers[header::ETAG].to_str().unwrap().to_string();
+        assert_ne!(updated, created);
+
+        // A stale version is refused, naming the current one
+        let (status, headers, _) = send(
+            &router,
+            Method::PATCH,
+            uri,
+            &[("if-match", created.as_str())],
+            Some(patch),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(headers[header::ETAG], updated.as_str());
+        let (status, ..) = send(
+            &router,
+            Method::DELETE,
+            uri,
+            &[("if-match", created.as_str())],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+        let (status, ..) = send(
+            &router,
+            Method::DELETE,
+            uri,
+            &[("if-match", updated.as_str())],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _, body) = send(&router, Method::GET, uri, &[], None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body["error"].as_str().unwrap().contains("Alice Smith"));
+    }
+
+    #[tokio::test]
+    async fn test_if_match_holds_the_entity_from_check_to_write() {
+        let storage = Arc::new(MemoryStorage::new());
+        let server = Arc::new(McpServer::new(storage.clone()));
+        let router = create_router(
+            server.clone(),
+            HttpOptions::new().with_auth_token(Some("admin".to_string())),
+        );
+        let uri = "/projects/work/entities/Alice";
+        let (_, headers, _) = send(
+            &router,
+            Method::POST,
+            uri,
+            &[],
+            Some(json!({"entityType": "person"})),
+        )
+        .await;
+        let version = headers[header::ETAG].to_str().unwrap().to_string();
+        let project = storage.get_project("work").await.unwrap().unwrap();
+
+        // Another call is rewriting the entity; the PATCH waits for it
+        let other = server.tool_context(DEFAULT_SESSION, &Permissions::new());
+        let guard = other.lock_entity(&project.id, "Alice").await;
+        let patch = tokio::spawn({
+            let router = router.clone();
+            async move {
+                send(
+                    &router,
+                    Method::PATCH,
+                    uri,
+                    &[("if-match", version.as_str())],
+                    Some(json!({"addTags": ["friend"]})),
+                )
+                .await
+                .0
+            }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!patch.is_finished());
+        let mut alice = storage
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .unwrap();
+        alice.add_observation("Moved to Berlin");
+        storage.save_entity(&alice).await.unwrap();
+        drop(guard);
+
+        // ...and then sees the version it named is gone
+        assert_eq!(patch.await.unwrap(), StatusCode::PRECONDITION_FAILED);
+        let alice = storage
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(alice.tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_relations_search_and_permissions() {
+        let router = router();
+        for name in ["Alice", "Acme"] {
+            let uri = format!("/projects/work/entities/{}", name);
+            let body = json!({"entityType": "thing", "observations": ["Based in Oslo"]});
+            let (status, ..) = send(&router, Method::POST, &uri, &[], Some(body)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let relation = json!({"from": "Alice", "to": "Acme", "relationType": "works_at"});
+        let (status, ..) = send(
+            &router,
+            Method::POST,
+            "/projects/work/relations",
+            &[],
+            Some(relation),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _, body) = send(
+            &router,
+            Method::GET,
+            "/projects/work/relations?from=Alice",
+            &[],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["relations"][0]["to"], "Acme");
+
+        let (status, _, body) = send(
+            &router,
+            Method::GET,
+            "/search?q=oslo&project=work&mode=fuzzy&fields=names",
+            &[],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["entities"].as_array().unwrap().len(), 2);
+        let (status, _, body) = send(
+            &router,
+            Method::GET,
+            "/traverse?project=work&start=Alice&direction=outgoing",
+            &[],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (status, _, body) = send(&router, Method::GET, "/search?mode=nope", &[], None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+
+        let delete = "/projects/work/relations?from=Alice&to=Acme&type=works_at";
+        let (status, ..) = send(&router, Method::DELETE, delete, &[], None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, ..) = send(&router, Method::DELETE, delete, &[], None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // The read-only profile may read but not write
+        let read_only = |method: Method, uri: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::AUTHORIZATION, "Bearer recall")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"entityType": "thing"}"#))
+                .unwrap()
+        };
+        let response = router
+            .clone()
+            .oneshot(read_only(Method::GET, "/projects/work/entities/Alice"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = router
+            .clone()
+            .oneshot(read_only(Method::POST, "/projects/work/entities/Bob"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_openapi_documents_every_route() {
+        let router = router();
+        let (status, _, document) = send(&router, Method::GET, "/openapi.json", &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(document["openapi"], "3.0.3");
+        let parameter = |path: &str, name: &str| {
+            document["paths"][path]["get"]["parameters"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|p| p["name"] == name)
+                .cloned()
+                .unwrap()
+        };
+        assert_eq!(parameter("/search", "q")["required"], false);
+        assert_eq!(parameter("/traverse", "start")["required"], true);
+        assert_eq!(parameter("/traverse", "direction")["in"], "query");
+        assert!(
+            document["components"]["schemas"]["Entity"]["properties"]["entityType"].is_object()
+        );
+
+        let tools = ToolRegistry::builtin();
+        for operation in operations() {
+            assert!(tools.get(operation.tool).is_some(), "{}", operation.tool);
+            let method = operation.method.as_str().to_lowercase();
+            assert!(document["paths"][operation.path][&method].is_object());
+
+            // Served, not rejected by the router for lack of a route
+            let uri = operation
+                .path
+                .replace("{project}", "nowhere")
+                .replace("{name}", "nobody");
+            let (status, _, body) = send(&router, operation.method, &uri, &[], None).await;
+            assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{}", uri);
+            assert!(
+                status.is_success() || body["error"].is_string(),
+                "{} {}",
+                uri,
+                status
+            );
+        }
+    }
+}
@@ -0,0 +1,371 @@
+//! Requests from the server to the client, for `sampling/createMessage`
+//!
+//! A transport that can carry server-to-client messages connects each
+//! session's outgoing channel with [`crate::McpServer::connect_client`],
+//! getting a [`ClientPeer`]. Requests sent through the peer wait for the
+//! client's response, which arrives as an ordinary incoming message and is
+//! routed back by id. Tools ask the client's model for completions through
+//! the [`Sampler`] trait, so tests can script the model's answers.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::transport::JsonRpcRequest;
+
+/// How long to wait for the client to answer a request
+pub const CLIENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Errors of requests to the client
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum SamplingError {
+    #[error("Client does not support sampling")]
+    Unsupported,
+
+    #[error("Client declined the request: {message}")]
+    Rejected { code: i64, message: String },
+
+    #[error("Client did not answer within {0:?}")]
+    Timeout(Duration),
+
+    #[error("Client disconnected")]
+    Disconnected,
+
+    #[error("Invalid response from client: {0}")]
+    InvalidResponse(String),
+}
+
+/// Who wrote a sampling message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// Content of a sampling message; only text is produced or read here
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SamplingContent {
+    Text {
+        text: String,
+    },
+    /// Images, audio and anything newer
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: SamplingContent,
+}
+
+impl SamplingMessage {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: SamplingContent::Text { text: text.into() },
+        }
+    }
+}
+
+/// Parameters of `sampling/createMessage`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageRequest {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Context the client may add from its other servers; always "none"
+    pub include_context: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    pub max_tokens: u32,
+}
+
+impl CreateMessageRequest {
+    pub fn new(messages: Vec<SamplingMessage>, max_tokens: u32) -> Self {
+        Self {
+            messages,
+            system_prompt: None,
+            include_context: "none",
+            temperature: None,
+            max_tokens,
+        }
+    }
+
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+}
+
+/// Result of `sampling/createMessage`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: SamplingContent,
+    /// Model the client chose
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+impl CreateMessageResult {
+    pub fn text(&self) -> Option<&str> {
+        match &self.content {
+            SamplingContent::Text { text } => Some(text),
+            SamplingContent::Other => None,
+        }
+    }
+}
+
+/// Something that completes messages with a model
+#[async_trait]
+pub trait Sampler: Send + Sync {
+    async fn create_message(
+        &self,
+        request: CreateMessageRequest,
+    ) -> Result<CreateMessageResult, SamplingError>;
+}
+
+/// What a client said about itself in `initialize`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Declared the `sampling` capability
+    pub sampling: bool,
+}
+
+impl ClientInfo {
+    /// Read `clientInfo` and `capabilities` from `initialize` params
+    pub fn from_initialize(params: &serde_json::Value) -> Self {
+        let field = |key: &str| {
+            params
+                .pointer(&format!("/clientInfo/{}", key))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        Self {
+            name: field("name"),
+            version: field("version"),
+            sampling: params.pointer("/capabilities/sampling").is_some(),
+        }
+    }
+}
+
+type Pending = oneshot::Sender<Result<serde_json::Value, SamplingError>>;
+
+/// The client end of a session, for requests from the server
+pub struct ClientPeer {
+    /// Serialized messages for the transport to deliver
+    outgoing: mpsc::UnboundedSender<String>,
+    pending: Mutex<HashMap<String, Pending>>,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+/// Forgets a request whose caller stopped waiting
+struct PendingGuard<'a> {
+    peer: &'a ClientPeer,
+    key: String,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.peer
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key);
+    }
+}
+
+impl ClientPeer {
+    pub fn new(outgoing: mpsc::UnboundedSender<String>) -> Self {
+        Self {
+            outgoing,
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            timeout: CLIENT_REQUEST_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send a request and wait for the client's result
+    pub async fn request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, SamplingError> {
+        // Prefixed so they cannot be mistaken for the client's own ids
+        let id = serde_json::Value::String(format!(
+            "parsnip-{}",
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        ));
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: id.clone(),
+            method: method.to_string(),
+            params,
+        };
+        let message = serde_json::to_string(&request)
+            .map_err(|e| SamplingError::InvalidResponse(e.to_string()))?;
+
+        let (tx, rx) = oneshot::channel();
+        let guard = PendingGuard {
+            peer: self,
+            key: id.to_string(),
+        };
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(guard.key.clone(), tx);
+        self.outgoing
+            .send(message)
+            .map_err(|_| SamplingError::Disconnected)?;
+
+        let result = match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(SamplingError::Disconnected),
+            Err(_) => Err(SamplingError::Timeout(self.timeout)),
+        };
+        drop(guard);
+        result
+    }
+
+    /// Hand a client response to the request waiting for it
+    ///
+    /// Returns false if no request of this peer has the response's id.
+    pub fn resolve(&self, response: &serde_json::Value) -> bool {
+        let Some(id) = response.get("id") else {
+            return false;
+        };
+        let Some(tx) = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id.to_string())
+        else {
+            return false;
+        };
+
+        let result = match (response.get("result"), response.get("error")) {
+            (_, Some(error)) => Err(SamplingError::Rejected {
+                code: error.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+                message: error
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            }),
+            (Some(result), None) => Ok(result.clone()),
+            (None, None) => Err(SamplingError::InvalidResponse(
+                "neither result nor error".to_string(),
+            )),
+        };
+        let _ = tx.send(result);
+        true
+    }
+}
+
+#[async_trait]
+impl Sampler for ClientPeer {
+    async fn create_message(
+        &self,
+        request: CreateMessageRequest,
+    ) -> Result<CreateMessageResult, SamplingError> {
+        let params = serde_json::to_value(&request)
+            .map_err(|e| SamplingError::InvalidResponse(e.to_string()))?;
+        let result = self.request("sampling/createMessage", params).await?;
+        serde_json::from_value(result).map_err(|e| SamplingError::InvalidResponse(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_info_from_initialize() {
+        let info = ClientInfo::from_initialize(&serde_json::json!({
+            "protocolVersion": "2025-06-18",
+            "capabilities": {"sampling": {}},
+            "clientInfo": {"name": "scripted", "version": "1.0"}
+        }));
+        assert_eq!(info.name.as_deref(), Some("scripted"));
+        assert!(info.sampling);
+        assert!(!ClientInfo::from_initialize(&serde_json::json!({})).sampling);
+    }
+
+    #[tokio::test]
+    async fn test_peer_routes_responses_by_id() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let peer = ClientPeer::new(tx);
+
+        let request = CreateMessageRequest::new(vec![SamplingMessage::user("Hi")], 10);
+        let (result, _) = tokio::join!(peer.create_message(request), async {
+            let sent: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+            assert_eq!(sent["method"], "sampling/createMessage");
+            assert_eq!(sent["params"]["maxTokens"], 10);
+            assert_eq!(sent["params"]["includeContext"], "none");
+
+            assert!(!peer.resolve(&serde_json::json!({"id": "other", "result": {}})));
+            assert!(peer.resolve(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": sent["id"],
+                "result": {
+                    "role": "assistant",
+                    "content": {"type": "text", "text": "Hello"},
+                    "model": "scripted-1",
+                    "stopReason": "endTurn"
+                }
+            })));
+        });
+        assert_eq!(result.unwrap().text(), Some("Hello"));
+
+        let request = CreateMessageRequest::new(vec![SamplingMessage::user("Hi")], 10);
+        let (result, _) = tokio::join!(peer.create_message(request), async {
+            let sent: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+            peer.resolve(&serde_json::json!({
+                "id": sent["id"],
+                "error": {"code": -1, "message": "User rejected sampling request"}
+            }));
+        });
+        assert!(matches!(
+            result,
+            Err(SamplingError::Rejected { code: -1, .. })
+        ));
+        assert!(peer.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_peer_times_out() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let peer = ClientPeer::new(tx).with_timeout(Duration::from_millis(10));
+        let result = peer.request("ping", serde_json::Value::Null).await;
+        assert_eq!(
+            result,
+            Err(SamplingError::Timeout(Duration::from_millis(10)))
+        );
+        assert!(peer.pending.lock().unwrap().is_empty());
+    }
+}
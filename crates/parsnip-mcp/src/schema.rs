@@ -0,0 +1,337 @@
+//! JSON schemas for tool arguments and validation against them
+//!
+//! Argument structs derive [`JsonSchema`]; [`input_schema`] turns that into
+//! the self-contained object schema advertised in `tools/list`. Before a tool
+//! runs, [`validate`] checks the call's arguments against the same schema so
+//! clients get every problem at once, each with a JSON pointer to where it is.
+
+use std::fmt;
+
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A problem with one value in a tool call's arguments
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationError {
+    /// JSON pointer to the offending value; empty for the arguments object
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Inlined object schema for an argument type, as sent in `tools/list`
+pub fn input_schema<T: JsonSchema>() -> Value {
+    let settings = SchemaSettings::draft07().with(|s| {
+        s.inline_subschemas = true;
+        s.option_add_null_type = false;
+        s.meta_schema = None;
+    });
+    let schema = settings.into_generator().into_root_schema_for::<T>();
+    let mut value = serde_json::to_value(schema).unwrap_or_default();
+
+    if let Some(object) = value.as_object_mut() {
+        // The tool description says what the arguments are for
+        object.remove("title");
+        object.remove("description");
+        object.remove("definitions");
+        // Argument-less tools still take an object
+        object
+            .entry("properties")
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+    value
+}
+
+/// Check a value against a schema from [`input_schema`]
+///
+/// Supports the keywords schemars emits for argument types: `type`,
+/// `properties`, `required`, `additionalProperties`, `items`, `enum`,
+/// `minimum`/`maximum`, `minItems`/`maxItems`, `minLength`/`maxLength`,
+/// `allOf` and `anyOf`.
+pub fn validate(schema: &Value, value: &Value) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    check(schema, value, "", &mut errors);
+    errors
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<ValidationError>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` accepts anything; `false` nothing
+        if schema == &Value::Bool(false) {
+            errors.push(ValidationError::new(path, "no value is allowed here"));
+        }
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            errors.push(ValidationError::new(
+                path,
+                format!("expected {}, got {}", types.join(" or "), type_name(value)),
+            ));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            errors.push(ValidationError::new(
+                path,
+                format!("expected one of {}", allowed.join(", ")),
+            ));
+        }
+    }
+
+    if let Some(n) = value.as_f64() {
+        if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+            if n < min {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("must be at least {}", min),
+                ));
+            }
+        }
+        if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+            if n > max {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("must be at most {}", max),
+                ));
+            }
+        }
+    }
+
+    if let Some(s) = value.as_str() {
+        let len = s.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+            if len < min {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("must be at least {} characters", min),
+                ));
+            }
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if len > max {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("must be at most {} characters", max),
+                ));
+            }
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        let len = items.len() as u64;
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if len < min {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("must have at least {} items", min),
+                ));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if len > max {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("must have at most {} items", max),
+                ));
+            }
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                check(item_schema, item, &format!("{}/{}", path, i), errors);
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    errors.push(ValidationError::new(
+                        pointer(path, name),
+                        "missing required property",
+                    ));
+                }
+            }
+        }
+        for (name, field) in object {
+            match properties.and_then(|p| p.get(name)) {
+                Some(field_schema) => check(field_schema, field, &pointer(path, name), errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => errors.push(ValidationError::new(
+                        pointer(path, name),
+                        "unknown property",
+                    )),
+                    Some(extra) => check(extra, field, &pointer(path, name), errors),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for sub in all {
+            check(sub, value, path, errors);
+        }
+    }
+
+    if let Some(any) = schema.get("anyOf").and_then(Value::as_array) {
+        let branches: Vec<Vec<ValidationError>> = any
+            .iter()
+            .map(|sub| {
+                let mut sub_errors = Vec::new();
+                check(sub, value, path, &mut sub_errors);
+                sub_errors
+            })
+            .collect();
+        if !branches.iter().any(Vec::is_empty) {
+            // Report the branch that got furthest
+            if let Some(closest) = branches.into_iter().min_by_key(Vec::len) {
+                errors.extend(closest);
+            }
+        }
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Append a property name to a JSON pointer, escaping per RFC 6901
+fn pointer(path: &str, name: &str) -> String {
+    format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    #[allow(dead_code)]
+    struct Args {
+        /// Project name
+        project_id: Option<String>,
+        items: Vec<Item>,
+        #[serde(default)]
+        mode: Mode,
+        #[schemars(range(min = 1, max = 10))]
+        limit: Option<usize>,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    #[allow(dead_code)]
+    struct Item {
+        entity_name: String,
+    }
+
+    #[derive(Default, Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        #[default]
+        Exact,
+        Fuzzy,
+    }
+
+    #[test]
+    fn test_input_schema_is_inlined() {
+        let schema = input_schema::<Args>();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], serde_json::json!(["items"]));
+        assert_eq!(schema["properties"]["projectId"]["type"], "string");
+        assert_eq!(
+            schema["properties"]["projectId"]["description"],
+            "Project name"
+        );
+        assert_eq!(
+            schema["properties"]["items"]["items"]["properties"]["entityName"]["type"],
+            "string"
+        );
+        assert_eq!(schema["properties"]["mode"]["default"], "exact");
+        assert!(schema.get("definitions").is_none());
+        assert!(schema.get("$schema").is_none());
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let schema = input_schema::<Args>();
+        assert!(validate(&schema, &serde_json::json!({"items": []})).is_empty());
+
+        let errors = validate(
+            &schema,
+            &serde_json::json!({
+                "projectId": 7,
+                "items": [{"entityName": "Alice"}, {}],
+                "mode": "semantic",
+                "limit": 20
+            }),
+        );
+        let mut found: Vec<(&str, &str)> = errors
+            .iter()
+            .map(|e| (e.path.as_str(), e.message.as_str()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("/items/1/entityName", "missing required property"),
+                ("/limit", "must be at most 10"),
+                ("/mode", "expected one of \"exact\", \"fuzzy\""),
+                ("/projectId", "expected string, got integer"),
+            ]
+        );
+    }
+}
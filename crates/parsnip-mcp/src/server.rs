@@ -1,77 +1,482 @@
 //! MCP server implementation
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use parsnip_core::{
-    validate_batch_entities, validate_batch_relations, validate_entity_name, validate_observation,
-    validate_project_name, validate_tag, validate_traversal_depth, Direction, Entity, Project,
-    Relation, SearchMode, SearchQuery, TraversalEngine, TraversalQuery, MAX_TRAVERSAL_DEPTH,
-};
-#[cfg(feature = "fulltext")]
-use parsnip_search::FullTextSearchEngine;
-use parsnip_search::{ExactSearchEngine, FuzzySearchEngine, SearchEngine};
-use parsnip_storage::StorageBackend;
-use serde::{Deserialize, Serialize};
+use futures::stream::{FuturesUnordered, StreamExt};
+use parsnip_core::{Entity, Relation};
+use parsnip_storage::{Actor, ChangeFeed, StorageBackend, StorageChange, Transport};
+use serde::Deserialize;
 use std::collections::HashMap;
-
-use crate::handlers::ToolCallResponse;
-use crate::tools::get_tools;
-use crate::transport::{JsonRpcRequest, JsonRpcResponse, StdioTransport};
+use tokio::sync::Notify;
+use tracing::Instrument;
+
+use crate::metrics::Metrics;
+use crate::permissions::Permissions;
+use crate::prompts::{
+    expand_uri, parse_stale_days, render_known_entities, render_stale, stale_observations,
+    PromptError, PromptLibrary, PromptMessage, PromptTemplate, Segment, RECALL_ENTITY,
+    RECORD_MEETING_NOTES, REVIEW_STALE_FACTS, SUMMARIZE_PROJECT,
+};
+use crate::resources::{
+    decode_cursor, encode_cursor, render_entity, render_graph, render_projects, resource_templates,
+    Resource, ResourceContents, ResourceError, ResourceTarget, ResourceUri, GRAPH_PAGE_SIZE,
+    RESOURCE_PAGE_SIZE,
+};
+use crate::sampling::{ClientInfo, ClientPeer};
+use crate::subscriptions::Subscriptions;
+use crate::tools::{
+    project_summaries, EntityLocks, Tool, ToolCallResponse, ToolContext, ToolError, ToolRegistry,
+};
+use crate::transport::{
+    negotiate_protocol_version, JsonRpcMessage, JsonRpcNotification, JsonRpcPayload, JsonRpcReply,
+    JsonRpcRequest, JsonRpcResponse, StdioTransport,
+};
 
 const SERVER_NAME: &str = "parsnip";
 const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Session used by the stdio transport and by callers without sessions
+pub const DEFAULT_SESSION: &str = "default";
+
 /// MCP Server for Parsnip
 pub struct McpServer<S: StorageBackend> {
     storage: Arc<S>,
+    subscriptions: Subscriptions,
+    changes: Option<ChangeFeed>,
+    prompts: PromptLibrary,
+    tools: ToolRegistry,
+    /// Applied to every request, on top of any the transport adds
+    permissions: Permissions,
+    /// Cancellation signals of requests being handled, by session and id
+    in_flight: Mutex<HashMap<(String, String), Arc<Notify>>>,
+    /// What each session's client declared in `initialize`
+    clients: Mutex<HashMap<String, ClientInfo>>,
+    /// Channels for server-to-client requests, by session
+    peers: Mutex<HashMap<String, Arc<ClientPeer>>>,
+    /// Held by calls that read, change and write back an entity
+    entity_locks: Arc<EntityLocks>,
+    metrics: Arc<Metrics>,
+    /// Transport named in the audit log
+    transport: Transport,
 }
 
 impl<S: StorageBackend + Send + Sync + 'static> McpServer<S> {
     pub fn new(storage: Arc<S>) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            subscriptions: Subscriptions::new(),
+            changes: None,
+            prompts: PromptLibrary::new(),
+            tools: ToolRegistry::builtin(),
+            permissions: Permissions::new(),
+            in_flight: Mutex::new(HashMap::new()),
+            clients: Mutex::new(HashMap::new()),
+            peers: Mutex::new(HashMap::new()),
+            entity_locks: Arc::new(EntityLocks::new()),
+            metrics: Arc::new(Metrics::new()),
+            transport: Transport::Stdio,
+        }
+    }
+
+    /// Serve user-defined prompts alongside the built-ins
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Serve this set of tools instead of the built-ins
+    pub fn with_tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Serve an extra tool, replacing a built-in with the same name
+    pub fn with_tool<T: Tool>(mut self, tool: T) -> Self {
+        self.tools.register(tool);
+        self
+    }
+
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    pub fn storage(&self) -> &Arc<S> {
+        &self.storage
+    }
+
+    /// Limit what every client of this server may do
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    /// Send resource notifications for changes published on this feed
+    pub fn with_change_feed(mut self, changes: ChangeFeed) -> Self {
+        self.changes = Some(changes);
+        self
+    }
+
+    pub fn change_feed(&self) -> Option<&ChangeFeed> {
+        self.changes.as_ref()
+    }
+
+    /// Record into these metrics, e.g. to report storage timings too
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Name this transport in the audit log; stdio by default
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
     }
 
     /// Start the MCP server on stdio
+    ///
+    /// Requests are handled concurrently, so a `notifications/cancelled`
+    /// can reach a long tool call while it runs.
     pub async fn run_stdio(&self) -> anyhow::Result<()> {
         tracing::info!("Starting MCP server on stdio");
 
-        // Keep one BufReader alive for entire session to avoid losing buffered data
-        let mut transport = StdioTransport::new();
+        // Read on a separate task: read_line is not cancel-safe, so it cannot
+        // race against change notifications in the select below
+        let (payload_tx, mut payload_rx) = tokio::sync::mpsc::channel(16);
+        let reader = tokio::spawn(async move {
+            // Keep one BufReader alive for entire session to avoid losing buffered data
+            let mut transport = StdioTransport::new();
+            loop {
+                let result = transport.read_payload().await;
+                let done = !matches!(result, Ok(Some(_)));
+                if payload_tx.send(result).await.is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        let mut changes = self.changes.as_ref().map(|feed| feed.subscribe());
+        let mut in_flight = FuturesUnordered::new();
+        let (client_tx, mut client_rx) = tokio::sync::mpsc::unbounded_channel();
+        self.connect_client(DEFAULT_SESSION, client_tx);
 
         loop {
-            match transport.read_request().await {
-                Ok(Some(request)) => {
-                    tracing::debug!("Received request: {:?}", request.method);
-                    let response = self.handle_request(request).await;
-                    if let Err(e) = StdioTransport::write_response(&response).await {
-                        tracing::error!("Failed to write response: {}", e);
+            tokio::select! {
+                result = payload_rx.recv() => match result {
+                    Some(Ok(Some(payload))) => {
+                        in_flight.push(self.handle_payload(DEFAULT_SESSION, payload));
+                    }
+                    Some(Ok(None)) | None => {
+                        tracing::info!("EOF on stdin, shutting down");
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        tracing::error!("Failed to read from stdin: {}", e);
+                        break;
+                    }
+                },
+                Some(reply) = in_flight.next(), if !in_flight.is_empty() => {
+                    if let Some(reply) = reply {
+                        if let Err(e) = StdioTransport::write_reply(&reply).await {
+                            tracing::error!("Failed to write response: {}", e);
+                        }
                     }
                 }
-                Ok(None) => {
-                    tracing::info!("EOF on stdin, shutting down");
-                    break;
+                Some(change) = next_change(&mut changes) => {
+                    for notification in self.notifications_for(DEFAULT_SESSION, &change) {
+                        if let Err(e) = StdioTransport::write_notification(&notification).await {
+                            tracing::error!("Failed to write notification: {}", e);
+                        }
+                    }
                 }
-                Err(e) => {
-                    tracing::error!("Failed to read request: {}", e);
-                    break;
+                Some(request) = client_rx.recv() => {
+                    if let Err(e) = StdioTransport::write_serialized(&request).await {
+                        tracing::error!("Failed to write request: {}", e);
+                    }
                 }
             }
         }
 
+        // Answer requests still running before exiting
+        while let Some(reply) = in_flight.next().await {
+            if let Some(reply) = reply {
+                if let Err(e) = StdioTransport::write_reply(&reply).await {
+                    tracing::error!("Failed to write response: {}", e);
+                }
+            }
+        }
+
+        reader.abort();
+        self.end_session(DEFAULT_SESSION);
         Ok(())
     }
 
+    /// Handle a single message or batch from a session
+    ///
+    /// Returns `None` when nothing needs to be sent back: the payload held
+    /// only notifications and responses, or its requests were cancelled.
+    pub async fn handle_payload(
+        &self,
+        session: &str,
+        payload: JsonRpcPayload,
+    ) -> Option<JsonRpcReply> {
+        self.handle_payload_as(session, &Permissions::new(), payload)
+            .await
+    }
+
+    /// Handle a payload from a caller with its own permissions, such as the
+    /// profile of an HTTP auth token
+    ///
+    /// The caller gets only what both its permissions and the server's allow.
+    pub async fn handle_payload_as(
+        &self,
+        session: &str,
+        permissions: &Permissions,
+        payload: JsonRpcPayload,
+    ) -> Option<JsonRpcReply> {
+        let permissions = &self.permissions.restrict(permissions);
+        match payload {
+            JsonRpcPayload::Single(message) => self
+                .handle_message(session, permissions, message)
+                .await
+                .map(JsonRpcReply::Single),
+            JsonRpcPayload::Batch(messages) => {
+                let responses: Vec<JsonRpcResponse> = futures::future::join_all(
+                    messages
+                        .into_iter()
+                        .map(|message| self.handle_message(session, permissions, message)),
+                )
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+                (!responses.is_empty()).then_some(JsonRpcReply::Batch(responses))
+            }
+        }
+    }
+
+    async fn handle_message(
+        &self,
+        session: &str,
+        permissions: &Permissions,
+        message: Result<JsonRpcMessage, JsonRpcResponse>,
+    ) -> Option<JsonRpcResponse> {
+        match message {
+            Err(response) => Some(response),
+            Ok(JsonRpcMessage::Request(request)) => {
+                self.handle_cancellable(session, permissions, request).await
+            }
+            Ok(JsonRpcMessage::Notification { method, params }) => {
+                self.handle_notification(session, &method, params);
+                None
+            }
+            Ok(JsonRpcMessage::Response(response)) => {
+                let peer = self.peer(session);
+                if !peer.is_some_and(|peer| peer.resolve(&response)) {
+                    tracing::debug!("Ignoring unexpected client response: {}", response);
+                }
+                None
+            }
+        }
+    }
+
+    /// Handle a request unless the client cancels it first
+    async fn handle_cancellable(
+        &self,
+        session: &str,
+        permissions: &Permissions,
+        request: JsonRpcRequest,
+    ) -> Option<JsonRpcResponse> {
+        let (key, cancelled) = self.track_request(session, &request.id);
+        let span = tracing::info_span!(
+            "request",
+            session = %session,
+            id = %request.id,
+            method = %request.method
+        );
+
+        let response = tokio::select! {
+            biased;
+            _ = cancelled.notified() => {
+                tracing::debug!("Request {} cancelled", key.1);
+                None
+            }
+            response = self.handle_request(session, permissions, request).instrument(span) => Some(response),
+        };
+
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&key);
+        response
+    }
+
+    /// Register a request so `notifications/cancelled` can reach it
+    fn track_request(
+        &self,
+        session: &str,
+        id: &serde_json::Value,
+    ) -> ((String, String), Arc<Notify>) {
+        let key = (session.to_string(), id.to_string());
+        let cancelled = Arc::new(Notify::new());
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.clone(), cancelled.clone());
+        (key, cancelled)
+    }
+
+    fn handle_notification(&self, session: &str, method: &str, params: serde_json::Value) {
+        match method {
+            "notifications/cancelled" => {
+                let Some(request_id) = params.get("requestId") else {
+                    return;
+                };
+                let key = (session.to_string(), request_id.to_string());
+                let in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+                // Unknown ids are fine: the request may already be answered
+                if let Some(cancelled) = in_flight.get(&key) {
+                    cancelled.notify_one();
+                }
+            }
+            "notifications/initialized" => {
+                tracing::debug!("Session {} initialized", session)
+            }
+            _ => tracing::debug!("Ignoring notification: {}", method),
+        }
+    }
+
     /// Handle a JSON-RPC request (public for SSE transport)
     pub async fn handle_request_public(&self, request: JsonRpcRequest) -> JsonRpcResponse {
-        self.handle_request(request).await
+        self.handle_request(DEFAULT_SESSION, &self.permissions, request)
+            .await
+    }
+
+    /// Handle a JSON-RPC request on behalf of a transport session
+    pub async fn handle_session_request(
+        &self,
+        session: &str,
+        request: JsonRpcRequest,
+    ) -> JsonRpcResponse {
+        self.handle_request(session, &self.permissions, request)
+            .await
+    }
+
+    /// Notifications a session should receive for a storage change
+    pub fn notifications_for(
+        &self,
+        session: &str,
+        change: &StorageChange,
+    ) -> Vec<JsonRpcNotification> {
+        self.subscriptions.notifications_for(session, change)
+    }
+
+    /// Forget a closed session's subscriptions and client
+    pub fn end_session(&self, session: &str) {
+        self.subscriptions.end_session(session);
+        self.clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(session);
+        self.peers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(session);
+    }
+
+    /// Let the server send requests to a session's client
+    ///
+    /// The transport delivers each message sent on `outgoing` to the client,
+    /// and passes the client's responses back through [`Self::handle_payload`].
+    pub fn connect_client(
+        &self,
+        session: &str,
+        outgoing: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Arc<ClientPeer> {
+        let peer = Arc::new(ClientPeer::new(outgoing));
+        self.peers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(session.to_string(), peer.clone());
+        peer
+    }
+
+    fn peer(&self, session: &str) -> Option<Arc<ClientPeer>> {
+        self.peers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(session)
+            .cloned()
+    }
+
+    /// What a session's client declared in `initialize`
+    pub fn client_info(&self, session: &str) -> Option<ClientInfo> {
+        self.clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(session)
+            .cloned()
     }
 
-    async fn handle_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+    async fn handle_request(
+        &self,
+        session: &str,
+        permissions: &Permissions,
+        request: JsonRpcRequest,
+    ) -> JsonRpcResponse {
         match request.method.as_str() {
-            "initialize" => self.handle_initialize(request.id).await,
+            "initialize" => {
+                self.handle_initialize(session, request.id, request.params)
+                    .await
+            }
             "initialized" => JsonRpcResponse::success(request.id, serde_json::json!({})),
-            "tools/list" => self.handle_tools_list(request.id).await,
-            "tools/call" => self.handle_tools_call(request.id, request.params).await,
+            "tools/list" => self.handle_tools_list(request.id, permissions).await,
+            "tools/call" => {
+                let response = self
+                    .handle_tools_call(session, permissions, request.id, request.params)
+                    .await;
+                // Tools may have written; check for changes without waiting a tick
+                if let Some(changes) = &self.changes {
+                    changes.wake();
+                }
+                response
+            }
+            "resources/list" => {
+                self.handle_resources_list(permissions, request.id, request.params)
+                    .await
+            }
+            "resources/read" => {
+                self.handle_resources_read(permissions, request.id, request.params)
+                    .await
+            }
+            "resources/templates/list" => self.handle_resource_templates_list(request.id).await,
+            "resources/subscribe" => self.handle_resources_subscribe(
+                session,
+                permissions,
+                request.id,
+                request.params,
+                true,
+            ),
+            "resources/unsubscribe" => self.handle_resources_subscribe(
+                session,
+                permissions,
+                request.id,
+                request.params,
+                false,
+            ),
+            "prompts/list" => self.handle_prompts_list(request.id).await,
+            "prompts/get" => {
+                self.handle_prompts_get(permissions, request.id, request.params)
+                    .await
+            }
             "ping" => JsonRpcResponse::success(request.id, serde_json::json!({})),
             _ => JsonRpcResponse::error(
                 request.id,
@@ -81,11 +486,36 @@ impl<S: StorageBackend + Send + Sync + 'static> McpServer<S> {
         }
     }
 
-    async fn handle_initialize(&self, id: serde_json::Value) -> JsonRpcResponse {
+    async fn handle_initialize(
+        &self,
+        session: &str,
+        id: serde_json::Value,
+        params: serde_json::Value,
+    ) -> JsonRpcResponse {
+        self.clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(session.to_string(), ClientInfo::from_initialize(&params));
+
+        let requested = params.get("protocolVersion").and_then(|v| v.as_str());
+        let version = negotiate_protocol_version(requested);
+        if requested != Some(version) {
+            tracing::info!(
+                "Client asked for protocol {:?}, answering {}",
+                requested,
+                version
+            );
+        }
+
         let result = serde_json::json!({
-            "protocolVersion": "2024-11-05",
+            "protocolVersion": version,
             "capabilities": {
-                "tools": {}
+                "tools": {},
+                "prompts": {},
+                "resources": {
+                    "subscribe": self.changes.is_some(),
+                    "listChanged": self.changes.is_some()
+                }
             },
             "serverInfo": {
                 "name": SERVER_NAME,
@@ -95,13 +525,84 @@ impl<S: StorageBackend + Send + Sync + 'static> McpServer<S> {
         JsonRpcResponse::success(id, result)
     }
 
-    async fn handle_tools_list(&self, id: serde_json::Value) -> JsonRpcResponse {
-        let tools = get_tools();
+    async fn handle_tools_list(
+        &self,
+        id: serde_json::Value,
+        permissions: &Permissions,
+    ) -> JsonRpcResponse {
+        let tools = self.tools.definitions_for(permissions);
         JsonRpcResponse::success(id, serde_json::json!({ "tools": tools }))
     }
 
+    /// What a tool called from a session may reach, with its writes audited
+    pub(crate) fn tool_context(&self, session: &str, permissions: &Permissions) -> ToolContext {
+        let client = self.client_info(session);
+        let mut actor = Actor::new(self.transport);
+        if let Some(name) = client.as_ref().and_then(|client| client.name.clone()) {
+            actor = actor.with_client(name);
+        }
+        if let Some(token) = &permissions.token {
+            actor = actor.with_token(token);
+        }
+
+        let mut ctx = ToolContext::new(self.storage.clone(), session)
+            .with_permissions(permissions.clone())
+            .with_entity_locks(self.entity_locks.clone())
+            .with_audit(actor);
+        // Sampling needs both the client's consent and a way to reach it
+        if client.is_some_and(|client| client.sampling) {
+            if let Some(peer) = self.peer(session) {
+                ctx = ctx.with_sampler(peer);
+            }
+        }
+        ctx
+    }
+
+    /// Call a tool outside of JSON-RPC, as the REST API does
+    ///
+    /// The caller gets only what both its permissions and the server's allow.
+    pub async fn call_tool(
+        &self,
+        session: &str,
+        permissions: &Permissions,
+        name: &str,
+        args: serde_json::Value,
+    ) -> Result<ToolCallResponse, ToolError> {
+        let permissions = self.permissions.restrict(permissions);
+        let ctx = self.tool_context(session, &permissions);
+        self.run_tool(&ctx, name, args).await
+    }
+
+    /// Run a tool in a span, counting the call in the metrics and logging
+    /// its writes in the audit log
+    async fn run_tool(
+        &self,
+        ctx: &ToolContext,
+        name: &str,
+        args: serde_json::Value,
+    ) -> Result<ToolCallResponse, ToolError> {
+        let started = Instant::now();
+        let result = self
+            .tools
+            .call(ctx, name, args.clone())
+            .instrument(tracing::debug_span!("tool", name))
+            .await;
+        // The writes are done either way, so a failed append only gets logged
+        if let Err(e) = ctx.record_audit(name, args).await {
+            tracing::error!("Failed to append {} to the audit log: {}", name, e);
+        }
+        // Unknown names are not counted, so clients cannot add series at will
+        if self.tools.get(name).is_some() {
+            let ok = matches!(&result, Ok(response) if response.is_error != Some(true));
+            self.metrics.record_tool(name, started.elapsed(), ok);
+        }
+        result
+    }
+
     async fn handle_tools_call(
         &self,
+        session: &str,
+        permissions: &Permissions,
         id: serde_json::Value,
         params: serde_json::Value,
     ) -> JsonRpcResponse {
@@ -123,21 +624,12 @@ impl<S: StorageBackend + Send + Sync + 'static> McpServer<S> {
             params.arguments
         );
 
-        let response = match params.name.as_str() {
-            "search_knowledge" => self.handle_search(params.arguments).await,
-            "create_entities" => self.handle_create_entities(params.arguments).await,
-            "add_observations" => self.handle_add_observations(params.arguments).await,
-            "create_relations" => self.handle_create_relations(params.arguments).await,
-            "delete_entities" => self.handle_delete_entities(params.arguments).await,
-            "delete_relations" => self.handle_delete_relations(params.arguments).await,
-            "delete_observations" => self.handle_delete_observations(params.arguments).await,
-            "read_graph" => self.handle_read_graph(params.arguments).await,
-            "open_nodes" => self.handle_open_nodes(params.arguments).await,
-            "add_tags" => self.handle_add_tags(params.arguments).await,
-            "remove_tags" => self.handle_remove_tags(params.arguments).await,
-            "traverse_graph" => self.handle_traverse_graph(params.arguments).await,
-            "list_projects" => self.handle_list_projects().await,
-            _ => ToolCallResponse::error(format!("Unknown tool: {}", params.name)),
+        let ctx = self.tool_context(session, permissions);
+        let response = match self.run_tool(&ctx, &params.name, params.arguments).await {
+            Ok(response) => response,
+            Err(e) => {
+                return JsonRpcResponse::error(id, e.code(), e.to_string()).with_data(e.data())
+            }
         };
 
         match serde_json::to_value(response) {
@@ -146,930 +638,994 @@ impl<S: StorageBackend + Send + Sync + 'static> McpServer<S> {
         }
     }
 
-    async fn get_or_create_project(&self, project_name: &str) -> anyhow::Result<Project> {
-        if let Some(project) = self.storage.get_project(project_name).await? {
-            return Ok(project);
-        }
-        let project = Project::new(project_name);
-        self.storage.save_project(&project).await?;
-        Ok(project)
-    }
+    // ─────────────────────────────────────────────────────────────────────────
+    // Resources
+    // ─────────────────────────────────────────────────────────────────────────
 
-    async fn handle_search(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct SearchArgs {
-            query: Option<String>,
-            project_id: Option<String>,
-            search_mode: Option<String>,
-            fuzzy_threshold: Option<f32>,
-            exact_tags: Option<Vec<String>>,
-            page: Option<usize>,
-            page_size: Option<usize>,
+    async fn handle_resources_list(
+        &self,
+        permissions: &Permissions,
+        id: serde_json::Value,
+        params: serde_json::Value,
+    ) -> JsonRpcResponse {
+        #[derive(Deserialize, Default)]
+        struct ListParams {
+            cursor: Option<String>,
         }
 
-        let args: SearchArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
+        let params: ListParams = serde_json::from_value(params).unwrap_or_default();
+        let offset = match params.cursor.as_deref().map(decode_cursor).transpose() {
+            Ok(offset) => offset.unwrap_or(0),
+            Err(e) => return JsonRpcResponse::error(id, e.code(), e.to_string()),
         };
 
-        // Get entities
-        let entities = if let Some(ref project_name) = args.project_id {
-            match self.get_or_create_project(project_name).await {
-                Ok(project) => match self.storage.get_all_entities(&project.id).await {
-                    Ok(e) => e,
-                    Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-                },
-                Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
-            }
-        } else {
-            match self.storage.get_all_entities_all_projects().await {
-                Ok(e) => e,
-                Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-            }
-        };
-
-        // Build query
-        let mut query = if let Some(ref text) = args.query {
-            SearchQuery::text(text)
-        } else {
-            SearchQuery::empty()
-        };
-
-        if let Some(ref tags) = args.exact_tags {
-            for tag in tags {
-                query = query.with_tag(tag);
-            }
-        }
-
-        if let Some(ref mode) = args.search_mode {
-            query = query.with_mode(match mode.as_str() {
-                "fuzzy" => SearchMode::Fuzzy,
-                "fulltext" => SearchMode::FullText,
-                "hybrid" => SearchMode::Hybrid,
-                _ => SearchMode::Exact,
-            });
-        }
-
-        if let Some(threshold) = args.fuzzy_threshold {
-            query = query.with_fuzzy_threshold(threshold);
-        }
-
-        if let Some(page) = args.page {
-            query = query.with_pagination(page, args.page_size.unwrap_or(100));
-        }
-
-        // Perform search
-        let results = match query.mode {
-            SearchMode::Fuzzy => {
-                let engine = FuzzySearchEngine::new();
-                engine.search(&query, &entities).await
-            }
-            #[cfg(feature = "fulltext")]
-            SearchMode::FullText | SearchMode::Hybrid => match FullTextSearchEngine::in_memory() {
-                Ok(engine) => engine.search(&query, &entities).await,
-                Err(e) => {
-                    tracing::warn!(
-                        "Failed to create fulltext engine: {}, falling back to exact",
-                        e
-                    );
-                    let engine = ExactSearchEngine::new();
-                    engine.search(&query, &entities).await
+        match self.list_resources(permissions, offset).await {
+            Ok((resources, next_cursor)) => {
+                let mut result = serde_json::json!({ "resources": resources });
+                if let Some(cursor) = next_cursor {
+                    result["nextCursor"] = serde_json::Value::String(cursor);
                 }
-            },
-            #[cfg(not(feature = "fulltext"))]
-            SearchMode::FullText | SearchMode::Hybrid => {
-                tracing::warn!("Fulltext search not enabled, falling back to exact");
-                let engine = ExactSearchEngine::new();
-                engine.search(&query, &entities).await
-            }
-            _ => {
-                let engine = ExactSearchEngine::new();
-                engine.search(&query, &entities).await
-            }
-        };
-
-        match results {
-            Ok(entities) => {
-                let result = SearchResult {
-                    entities: entities.iter().map(EntityResult::from).collect(),
-                    relations: vec![],
-                    pagination: PaginationInfo {
-                        current_page: args.page.unwrap_or(0),
-                        page_size: args.page_size.unwrap_or(100),
-                        total_count: entities.len(),
-                        total_pages: 1,
-                        has_next_page: false,
-                        has_previous_page: args.page.unwrap_or(0) > 0,
-                    },
-                };
-                ToolCallResponse::text(serde_json::to_string_pretty(&result).unwrap())
+                JsonRpcResponse::success(id, result)
             }
-            Err(e) => ToolCallResponse::error(format!("Search error: {}", e)),
+            Err(e) => JsonRpcResponse::error(id, e.code(), e.to_string()),
         }
     }
 
-    async fn handle_create_entities(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct CreateArgs {
-            project_id: Option<String>,
-            entities: Vec<EntityInput>,
-        }
-
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct EntityInput {
-            name: String,
-            entity_type: String,
-            observations: Vec<String>,
-            #[serde(default)]
-            tags: Vec<String>,
-        }
-
-        let args: CreateArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        // Validate batch size
-        if let Err(e) = validate_batch_entities(args.entities.len()) {
-            return ToolCallResponse::error(e.to_string());
-        }
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-
-        // Validate project name
-        if let Err(e) = validate_project_name(project_name) {
-            return ToolCallResponse::error(e.to_string());
-        }
-
-        let project = match self.get_or_create_project(project_name).await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
-        };
-
-        let mut created = 0;
-        for input in args.entities {
-            // Validate entity name
-            if let Err(e) = validate_entity_name(&input.name) {
-                return ToolCallResponse::error(e.to_string());
+    /// One page of resources: the project list, then each project's graph
+    /// followed by its entities, projects and entities ordered by name
+    async fn list_resources(
+        &self,
+        permissions: &Permissions,
+        offset: usize,
+    ) -> Result<(Vec<Resource>, Option<String>), ResourceError> {
+        let storage_err = |e: parsnip_storage::StorageError| ResourceError::Storage(e.to_string());
+
+        let mut projects = self.storage.get_all_projects().await.map_err(storage_err)?;
+        projects.retain(|p| permissions.allows_project(&p.name));
+        projects.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut resources = Vec::new();
+        let mut position = 0;
+        let end = offset + RESOURCE_PAGE_SIZE;
+        let mut push = |resource: Resource, resources: &mut Vec<Resource>| {
+            if position >= offset && position < end {
+                resources.push(resource);
             }
+            position += 1;
+        };
 
-            // Validate observations
-            for obs in &input.observations {
-                if let Err(e) = validate_observation(obs) {
-                    return ToolCallResponse::error(e.to_string());
-                }
-            }
+        push(
+            Resource::new(&ResourceUri::projects(), "Projects")
+                .with_description("All projects with entity and relation counts"),
+            &mut resources,
+        );
 
-            // Validate tags
-            for tag in &input.tags {
-                if let Err(e) = validate_tag(tag) {
-                    return ToolCallResponse::error(e.to_string());
-                }
-            }
+        for project in &projects {
+            push(
+                Resource::new(
+                    &ResourceUri::graph(&project.name),
+                    format!("{} graph", project.name),
+                )
+                .with_description(
+                    project
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Knowledge graph of {}", project.name)),
+                ),
+                &mut resources,
+            );
 
-            let mut entity =
-                Entity::new(project.id.clone(), &input.name, input.entity_type.as_str());
-            for obs in input.observations {
-                entity.add_observation(&obs);
-            }
-            for tag in input.tags {
-                entity.add_tag(&tag);
-            }
-            if let Err(e) = self.storage.save_entity(&entity).await {
-                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
+            let mut entities = self
+                .storage
+                .get_all_entities(&project.id)
+                .await
+                .map_err(storage_err)?;
+            entities.sort_by(|a, b| a.name.cmp(&b.name));
+            for entity in &entities {
+                push(
+                    Resource::new(
+                        &ResourceUri::entity(&project.name, &entity.name),
+                        &entity.name,
+                    )
+                    .with_description(format!(
+                        "{} in {} ({} observations)",
+                        entity.entity_type.0,
+                        project.name,
+                        entity.observations.len()
+                    )),
+                    &mut resources,
+                );
             }
-            created += 1;
         }
 
-        ToolCallResponse::text(format!("✅ SUCCESS: Created {} entities", created))
+        let next_cursor = (position > end).then(|| encode_cursor(end));
+        Ok((resources, next_cursor))
     }
 
-    async fn handle_add_observations(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct AddObsArgs {
-            project_id: Option<String>,
-            observations: Vec<ObservationInput>,
-        }
-
+    async fn handle_resources_read(
+        &self,
+        permissions: &Permissions,
+        id: serde_json::Value,
+        params: serde_json::Value,
+    ) -> JsonRpcResponse {
         #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct ObservationInput {
-            entity_name: String,
-            observations: Vec<String>,
+        struct ReadParams {
+            uri: String,
         }
 
-        let args: AddObsArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
+        let params: ReadParams = match serde_json::from_value(params) {
             Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+            Err(e) => return JsonRpcResponse::error(id, -32602, format!("Invalid params: {}", e)),
         };
 
-        let mut added = 0;
-        for input in args.observations {
-            let entity = match self
-                .storage
-                .get_entity(&input.entity_name, &project.id)
-                .await
-            {
-                Ok(Some(e)) => e,
-                Ok(None) => {
-                    return ToolCallResponse::error(format!(
-                        "Entity not found: {}",
-                        input.entity_name
-                    ))
-                }
-                Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-            };
+        let result = match ResourceUri::parse(&params.uri) {
+            Ok(uri) => self.read_resource(permissions, &uri).await,
+            Err(e) => Err(e),
+        };
 
-            let mut updated = entity;
-            for obs in &input.observations {
-                updated.add_observation(obs);
+        match result {
+            Ok(contents) => {
+                JsonRpcResponse::success(id, serde_json::json!({ "contents": [contents] }))
             }
-            if let Err(e) = self.storage.save_entity(&updated).await {
-                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
-            }
-            added += input.observations.len();
+            Err(e) => JsonRpcResponse::error(id, e.code(), e.to_string()),
         }
-
-        ToolCallResponse::text(format!("✅ SUCCESS: Added {} observations", added))
     }
 
-    async fn handle_create_relations(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct CreateRelArgs {
-            project_id: Option<String>,
-            relations: Vec<RelationInput>,
-        }
-
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct RelationInput {
-            from: String,
-            to: String,
-            relation_type: String,
+    /// Render a resource; projects the caller may not see are not found
+    async fn read_resource(
+        &self,
+        permissions: &Permissions,
+        uri: &ResourceUri,
+    ) -> Result<ResourceContents, ResourceError> {
+        let storage_err = |e: parsnip_storage::StorageError| ResourceError::Storage(e.to_string());
+
+        if let Some(project) = uri.project() {
+            if !permissions.allows_project(project) {
+                return Err(ResourceError::NotFound(uri.to_string()));
+            }
         }
 
-        let args: CreateRelArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        // Validate batch size
-        if let Err(e) = validate_batch_relations(args.relations.len()) {
-            return ToolCallResponse::error(e.to_string());
-        }
+        let text = match &uri.target {
+            ResourceTarget::Projects => {
+                let mut projects = project_summaries(self.storage.as_ref(), permissions)
+                    .await
+                    .map_err(storage_err)?;
+                projects.sort_by(|a, b| a.name.cmp(&b.name));
+                render_projects(&projects, uri.format)
+            }
+            ResourceTarget::Graph { project, page } => {
+                let stored = self
+                    .storage
+                    .get_project(project)
+                    .await
+                    .map_err(storage_err)?
+                    .ok_or_else(|| ResourceError::NotFound(uri.to_string()))?;
 
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
-        };
+                let mut entities = self
+                    .storage
+                    .get_all_entities(&stored.id)
+                    .await
+                    .map_err(storage_err)?;
+                entities.sort_by(|a, b| a.name.cmp(&b.name));
 
-        let mut created = 0;
-        for input in args.relations {
-            // Validate entity names
-            if let Err(e) = validate_entity_name(&input.from) {
-                return ToolCallResponse::error(e.to_string());
-            }
-            if let Err(e) = validate_entity_name(&input.to) {
-                return ToolCallResponse::error(e.to_string());
+                let total_pages = entities.len().div_ceil(GRAPH_PAGE_SIZE).max(1);
+                if *page > total_pages {
+                    return Err(ResourceError::NotFound(uri.to_string()));
+                }
+                let start = (page - 1) * GRAPH_PAGE_SIZE;
+                let entities: Vec<Entity> = entities
+                    .into_iter()
+                    .skip(start)
+                    .take(GRAPH_PAGE_SIZE)
+                    .collect();
+
+                // Relations are listed with the page holding their source, so a
+                // single page shows every relation
+                let names: std::collections::HashSet<&str> =
+                    entities.iter().map(|e| e.name.as_str()).collect();
+                let relations: Vec<Relation> = self
+                    .storage
+                    .get_all_relations(&stored.id)
+                    .await
+                    .map_err(storage_err)?
+                    .into_iter()
+                    .filter(|r| total_pages == 1 || names.contains(r.from_name.as_str()))
+                    .collect();
+
+                render_graph(
+                    project,
+                    &entities,
+                    &relations,
+                    *page,
+                    total_pages,
+                    uri.format,
+                )
             }
+            ResourceTarget::Entity { project, name } => {
+                let stored = self
+                    .storage
+                    .get_project(project)
+                    .await
+                    .map_err(storage_err)?
+                    .ok_or_else(|| ResourceError::NotFound(uri.to_string()))?;
+                let entity = self
+                    .storage
+                    .get_entity(name, &stored.id)
+                    .await
+                    .map_err(storage_err)?
+                    .ok_or_else(|| ResourceError::NotFound(uri.to_string()))?;
 
-            let relation = Relation::from_names(
-                project.id.clone(),
-                &input.from,
-                &input.to,
-                &input.relation_type,
-            );
-            if let Err(e) = self.storage.save_relation(&relation).await {
-                return ToolCallResponse::error(format!("Failed to save relation: {}", e));
+                // Include cross-project relations owned by other projects
+                let relations: Vec<Relation> = self
+                    .storage
+                    .get_relations_for_entity_global(name)
+                    .await
+                    .map_err(storage_err)?
+                    .into_iter()
+                    .filter(|r| {
+                        (r.from_name == entity.name && r.effective_from_project_id() == &stored.id)
+                            || (r.to_name == entity.name
+                                && r.effective_to_project_id() == &stored.id)
+                    })
+                    .collect();
+
+                render_entity(project, &entity, &relations, uri.format)
             }
-            created += 1;
-        }
+        };
 
-        ToolCallResponse::text(format!("✅ SUCCESS: Created {} relations", created))
+        Ok(ResourceContents::new(uri, text))
     }
 
-    async fn handle_delete_entities(&self, args: serde_json::Value) -> ToolCallResponse {
+    fn handle_resources_subscribe(
+        &self,
+        session: &str,
+        permissions: &Permissions,
+        id: serde_json::Value,
+        params: serde_json::Value,
+        subscribe: bool,
+    ) -> JsonRpcResponse {
         #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct DeleteArgs {
-            project_id: Option<String>,
-            entity_names: Vec<String>,
+        struct SubscribeParams {
+            uri: String,
         }
 
-        let args: DeleteArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
+        let params: SubscribeParams = match serde_json::from_value(params) {
             Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+            Err(e) => return JsonRpcResponse::error(id, -32602, format!("Invalid params: {}", e)),
         };
 
-        let mut deleted = 0;
-        for name in args.entity_names {
-            if let Err(e) = self
-                .storage
-                .delete_relations_for_entity(&name, &project.id)
-                .await
-            {
-                tracing::warn!("Failed to delete relations for {}: {}", name, e);
+        if subscribe {
+            // Don't reveal changes to projects the caller may not see
+            if let Ok(uri) = ResourceUri::parse(&params.uri) {
+                if uri
+                    .project()
+                    .is_some_and(|p| !permissions.allows_project(p))
+                {
+                    let e = ResourceError::NotFound(params.uri);
+                    return JsonRpcResponse::error(id, e.code(), e.to_string());
+                }
             }
-            if let Err(e) = self.storage.delete_entity(&name, &project.id).await {
-                return ToolCallResponse::error(format!("Failed to delete entity: {}", e));
+            if let Err(e) = self.subscriptions.subscribe(session, &params.uri) {
+                return JsonRpcResponse::error(id, e.code(), e.to_string());
             }
-            deleted += 1;
+            tracing::debug!("Session {} subscribed to {}", session, params.uri);
+        } else {
+            self.subscriptions.unsubscribe(session, &params.uri);
         }
 
-        ToolCallResponse::text(format!("✅ SUCCESS: Deleted {} entities", deleted))
+        JsonRpcResponse::success(id, serde_json::json!({}))
     }
 
-    async fn handle_delete_relations(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct DeleteRelArgs {
-            project_id: Option<String>,
-            relations: Vec<RelationRef>,
-        }
-
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct RelationRef {
-            from: String,
-            to: String,
-            relation_type: String,
-        }
-
-        let args: DeleteRelArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
-        };
+    async fn handle_resource_templates_list(&self, id: serde_json::Value) -> JsonRpcResponse {
+        JsonRpcResponse::success(
+            id,
+            serde_json::json!({ "resourceTemplates": resource_templates() }),
+        )
+    }
 
-        let mut deleted = 0;
-        for rel in args.relations {
-            if let Err(e) = self
-                .storage
-                .delete_relation(&rel.from, &rel.to, &rel.relation_type, &project.id)
-                .await
-            {
-                return ToolCallResponse::error(format!("Failed to delete relation: {}", e));
-            }
-            deleted += 1;
-        }
+    // ─────────────────────────────────────────────────────────────────────────
+    // Prompts
+    // ─────────────────────────────────────────────────────────────────────────
 
-        ToolCallResponse::text(format!("✅ SUCCESS: Deleted {} relations", deleted))
+    async fn handle_prompts_list(&self, id: serde_json::Value) -> JsonRpcResponse {
+        JsonRpcResponse::success(id, serde_json::json!({ "prompts": self.prompts.list() }))
     }
 
-    async fn handle_delete_observations(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct DeleteObsArgs {
-            project_id: Option<String>,
-            deletions: Vec<ObsDeletion>,
-        }
-
+    async fn handle_prompts_get(
+        &self,
+        permissions: &Permissions,
+        id: serde_json::Value,
+        params: serde_json::Value,
+    ) -> JsonRpcResponse {
         #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct ObsDeletion {
-            entity_name: String,
-            observations: Vec<String>,
+        struct GetParams {
+            name: String,
+            #[serde(default)]
+            arguments: HashMap<String, String>,
         }
 
-        let args: DeleteObsArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
+        let params: GetParams = match serde_json::from_value(params) {
             Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+            Err(e) => return JsonRpcResponse::error(id, -32602, format!("Invalid params: {}", e)),
         };
 
-        let mut deleted = 0;
-        for del in args.deletions {
-            let entity = match self.storage.get_entity(&del.entity_name, &project.id).await {
-                Ok(Some(e)) => e,
-                Ok(None) => {
-                    return ToolCallResponse::error(format!(
-                        "Entity not found: {}",
-                        del.entity_name
-                    ))
-                }
-                Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-            };
-
-            let mut updated = entity;
-            updated
-                .observations
-                .retain(|o| !del.observations.contains(&o.content));
-            deleted += del.observations.len();
-
-            if let Err(e) = self.storage.save_entity(&updated).await {
-                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
-            }
+        match self
+            .get_prompt(permissions, &params.name, &params.arguments)
+            .await
+        {
+            Ok((description, messages)) => JsonRpcResponse::success(
+                id,
+                serde_json::json!({ "description": description, "messages": messages }),
+            ),
+            Err(e) => JsonRpcResponse::error(id, e.code(), e.to_string()),
         }
-
-        ToolCallResponse::text(format!("✅ SUCCESS: Deleted {} observations", deleted))
     }
 
-    async fn handle_read_graph(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct ReadGraphArgs {
-            project_id: Option<String>,
-        }
-
-        let args: ReadGraphArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
-        };
-
-        let entities = match self.storage.get_all_entities(&project.id).await {
-            Ok(e) => e,
-            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-        };
-
-        let relations = match self.storage.get_all_relations(&project.id).await {
-            Ok(r) => r,
-            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+    /// Description and messages of a prompt filled with live graph data
+    async fn get_prompt(
+        &self,
+        permissions: &Permissions,
+        name: &str,
+        args: &HashMap<String, String>,
+    ) -> Result<(Option<String>, Vec<PromptMessage>), PromptError> {
+        let prompt = self
+            .prompts
+            .list()
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| PromptError::UnknownPrompt(name.to_string()))?;
+        prompt.check_arguments(args)?;
+
+        let arg = |key: &str| {
+            args.get(key)
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
         };
 
-        let result = GraphResult {
-            entities: entities.iter().map(EntityResult::from).collect(),
-            relations: relations.iter().map(RelationResult::from).collect(),
+        let messages = if let Some(template) = self.prompts.template(name) {
+            vec![PromptMessage::text(
+                self.render_template(permissions, template, args).await?,
+            )]
+        } else {
+            match name {
+                RECALL_ENTITY => {
+                    self.recall_entity_prompt(
+                        permissions,
+                        &arg("entity").unwrap_or_default(),
+                        arg("project"),
+                    )
+                    .await?
+                }
+                SUMMARIZE_PROJECT => {
+                    self.summarize_project_prompt(permissions, &arg("project").unwrap_or_default())
+                        .await?
+                }
+                RECORD_MEETING_NOTES => {
+                    self.meeting_notes_prompt(
+                        permissions,
+                        &arg("notes").unwrap_or_default(),
+                        &arg("project").unwrap_or_else(|| "default".to_string()),
+                    )
+                    .await?
+                }
+                REVIEW_STALE_FACTS => {
+                    self.stale_facts_prompt(
+                        permissions,
+                        &arg("project").unwrap_or_default(),
+                        parse_stale_days(args)?,
+                    )
+                    .await?
+                }
+                _ => return Err(PromptError::UnknownPrompt(name.to_string())),
+            }
         };
 
-        ToolCallResponse::text(serde_json::to_string_pretty(&result).unwrap())
+        Ok((prompt.description, messages))
     }
 
-    async fn handle_open_nodes(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct OpenNodesArgs {
-            project_id: Option<String>,
-            names: Vec<String>,
-        }
-
-        let args: OpenNodesArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
-        };
-
-        let mut entities = Vec::new();
-        let mut relations = Vec::new();
-
-        for name in &args.names {
-            if let Ok(Some(entity)) = self.storage.get_entity(name, &project.id).await {
-                entities.push(EntityResult::from(&entity));
-
-                if let Ok(rels) = self
-                    .storage
-                    .get_relations_for_entity(name, &project.id)
-                    .await
-                {
-                    for rel in rels {
-                        relations.push(RelationResult::from(&rel));
-                    }
+    async fn render_template(
+        &self,
+        permissions: &Permissions,
+        template: &PromptTemplate,
+        args: &HashMap<String, String>,
+    ) -> Result<String, PromptError> {
+        let mut text = String::new();
+        for segment in &template.segments {
+            match segment {
+                Segment::Text(t) => text.push_str(t),
+                Segment::Argument(name) => {
+                    text.push_str(args.get(name).map(String::as_str).unwrap_or_default())
+                }
+                Segment::Resource(uri) => {
+                    let uri = ResourceUri::parse(&expand_uri(uri, args))?;
+                    text.push_str(&self.read_resource(permissions, &uri).await?.text);
                 }
             }
         }
-
-        let result = GraphResult {
-            entities,
-            relations,
-        };
-        ToolCallResponse::text(serde_json::to_string_pretty(&result).unwrap())
+        Ok(text)
     }
 
-    async fn handle_add_tags(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct AddTagsArgs {
-            project_id: Option<String>,
-            updates: Vec<TagUpdate>,
-        }
-
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct TagUpdate {
-            entity_name: String,
-            tags: Vec<String>,
-        }
-
-        let args: AddTagsArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+    async fn recall_entity_prompt(
+        &self,
+        permissions: &Permissions,
+        entity: &str,
+        project: Option<String>,
+    ) -> Result<Vec<PromptMessage>, PromptError> {
+        // Without a project, recall the entity from every project that has it
+        let projects = match project {
+            Some(project) => vec![project],
+            None => {
+                let mut projects = self
+                    .storage
+                    .get_all_projects()
+                    .await
+                    .map_err(|e| ResourceError::Storage(e.to_string()))?;
+                projects.retain(|p| permissions.allows_project(&p.name));
+                projects.sort_by(|a, b| a.name.cmp(&b.name));
+                projects.into_iter().map(|p| p.name).collect()
+            }
         };
 
-        let mut added = 0;
-        for update in args.updates {
-            let entity = match self
-                .storage
-                .get_entity(&update.entity_name, &project.id)
+        let mut messages = Vec::new();
+        for project in &projects {
+            match self
+                .read_resource(permissions, &ResourceUri::entity(project, entity))
                 .await
             {
-                Ok(Some(e)) => e,
-                Ok(None) => {
-                    return ToolCallResponse::error(format!(
-                        "Entity not found: {}",
-                        update.entity_name
-                    ))
-                }
-                Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-            };
-
-            let mut updated = entity;
-            for tag in &update.tags {
-                updated.add_tag(tag);
-            }
-            added += update.tags.len();
-
-            if let Err(e) = self.storage.save_entity(&updated).await {
-                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
+                Ok(contents) => messages.push(PromptMessage::resource(contents)),
+                Err(ResourceError::NotFound(_)) => continue,
+                Err(e) => return Err(e.into()),
             }
         }
 
-        ToolCallResponse::text(format!("✅ SUCCESS: Added {} tags", added))
+        let instruction = if messages.is_empty() {
+            format!(
+                "Nothing is stored about \"{}\". Say so, and ask whether to record what is known about it.",
+                entity
+            )
+        } else {
+            format!(
+                "Recall everything known about \"{}\" from the memory above: who or what it is, \
+                 the facts recorded about it, and how it relates to other entities. \
+                 Point out facts that look contradictory or out of date.",
+                entity
+            )
+        };
+        messages.push(PromptMessage::text(instruction));
+        Ok(messages)
     }
 
-    async fn handle_remove_tags(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct RemoveTagsArgs {
-            project_id: Option<String>,
-            updates: Vec<TagUpdate>,
-        }
+    async fn summarize_project_prompt(
+        &self,
+        permissions: &Permissions,
+        project: &str,
+    ) -> Result<Vec<PromptMessage>, PromptError> {
+        let graph = self
+            .read_resource(permissions, &ResourceUri::graph(project))
+            .await?;
+        Ok(vec![
+            PromptMessage::resource(graph),
+            PromptMessage::text(format!(
+                "Summarize the \"{}\" project from its knowledge graph above. \
+                 Describe the main entities and how they connect, the key facts, \
+                 and any gaps worth filling. If the graph has more pages, read them \
+                 before summarizing.",
+                project
+            )),
+        ])
+    }
 
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct TagUpdate {
-            entity_name: String,
-            tags: Vec<String>,
+    async fn meeting_notes_prompt(
+        &self,
+        permissions: &Permissions,
+        notes: &str,
+        project: &str,
+    ) -> Result<Vec<PromptMessage>, PromptError> {
+        let storage_err = |e: parsnip_storage::StorageError| ResourceError::Storage(e.to_string());
+
+        if !permissions.allows_project(project) {
+            return Err(ResourceError::NotFound(ResourceUri::graph(project).to_string()).into());
         }
 
-        let args: RemoveTagsArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
-
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
-        };
-
-        let mut removed = 0;
-        for update in args.updates {
-            let entity = match self
+        // Listing existing entities lets the model reuse their names
+        let mut entities = match self
+            .storage
+            .get_project(project)
+            .await
+            .map_err(storage_err)?
+        {
+            Some(stored) => self
                 .storage
-                .get_entity(&update.entity_name, &project.id)
+                .get_all_entities(&stored.id)
                 .await
-            {
-                Ok(Some(e)) => e,
-                Ok(None) => {
-                    return ToolCallResponse::error(format!(
-                        "Entity not found: {}",
-                        update.entity_name
-                    ))
-                }
-                Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-            };
-
-            let mut updated = entity;
-            updated.tags.retain(|t| !update.tags.contains(t));
-            removed += update.tags.len();
-
-            if let Err(e) = self.storage.save_entity(&updated).await {
-                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
-            }
-        }
-
-        ToolCallResponse::text(format!("✅ SUCCESS: Removed {} tags", removed))
+                .map_err(storage_err)?,
+            None => Vec::new(),
+        };
+        entities.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(vec![
+            PromptMessage::text(render_known_entities(project, &entities)),
+            PromptMessage::text(format!(
+                "Record these meeting notes in the \"{}\" project:\n\n{}\n\n\
+                 Identify the people, decisions, action items and topics. \
+                 Reuse the names of existing entities listed above where they match. \
+                 Use create_entities for new entities, add_observations for new facts \
+                 about existing ones, and create_relations to connect them, \
+                 passing projectId \"{}\" to each tool.",
+                project, notes, project
+            )),
+        ])
     }
 
-    async fn handle_traverse_graph(&self, args: serde_json::Value) -> ToolCallResponse {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct TraverseArgs {
-            project_id: Option<String>,
-            start: String,
-            target: Option<String>,
-            max_depth: Option<u32>,
-            direction: Option<String>,
-            entity_type_filter: Option<Vec<String>>,
-            relation_type_filter: Option<Vec<String>>,
-            use_weights: Option<bool>,
-        }
-
-        let args: TraverseArgs = match serde_json::from_value(args) {
-            Ok(a) => a,
-            Err(e) => return ToolCallResponse::error(format!("Invalid arguments: {}", e)),
-        };
+    async fn stale_facts_prompt(
+        &self,
+        permissions: &Permissions,
+        project: &str,
+        days: i64,
+    ) -> Result<Vec<PromptMessage>, PromptError> {
+        let storage_err = |e: parsnip_storage::StorageError| ResourceError::Storage(e.to_string());
+
+        let stored = self
+            .storage
+            .get_project(project)
+            .await
+            .map_err(storage_err)?
+            .filter(|_| permissions.allows_project(project))
+            .ok_or_else(|| ResourceError::NotFound(ResourceUri::graph(project).to_string()))?;
+        let entities = self
+            .storage
+            .get_all_entities(&stored.id)
+            .await
+            .map_err(storage_err)?;
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
+        let stale = stale_observations(&entities, cutoff);
+
+        Ok(vec![
+            PromptMessage::text(render_stale(project, &stale, days)),
+            PromptMessage::text(format!(
+                "Review the facts above, which were recorded more than {} days ago. \
+                 For each, say whether it is likely still true. Ask me about the ones \
+                 you cannot judge. Then use delete_observations to remove outdated facts \
+                 and add_observations to record corrections, passing projectId \"{}\".",
+                days, project
+            )),
+        ])
+    }
+}
 
-        // Validate traversal depth (cap at MAX_TRAVERSAL_DEPTH)
-        let depth = args.max_depth.unwrap_or(10).min(MAX_TRAVERSAL_DEPTH);
-        if let Some(requested) = args.max_depth {
-            if let Err(e) = validate_traversal_depth(requested) {
-                tracing::warn!(
-                    "Depth {} exceeds max, capping at {}: {}",
-                    requested,
-                    MAX_TRAVERSAL_DEPTH,
-                    e
-                );
+/// Next change from an optional feed; pending forever without one
+pub(crate) async fn next_change(
+    changes: &mut Option<tokio::sync::broadcast::Receiver<StorageChange>>,
+) -> Option<StorageChange> {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let Some(rx) = changes.as_mut() else {
+        return std::future::pending().await;
+    };
+    loop {
+        match rx.recv().await {
+            Ok(change) => return Some(change),
+            Err(RecvError::Lagged(n)) => tracing::warn!("Dropped {} storage changes", n),
+            Err(RecvError::Closed) => {
+                *changes = None;
+                return std::future::pending().await;
             }
         }
+    }
+}
 
-        let project_name = args.project_id.as_deref().unwrap_or("default");
-        let project = match self.get_or_create_project(project_name).await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
-        };
-
-        // Load entities and relations
-        let entities: HashMap<String, Entity> =
-            match self.storage.get_all_entities(&project.id).await {
-                Ok(e) => e.into_iter().map(|ent| (ent.name.clone(), ent)).collect(),
-                Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-            };
+// Result types for JSON responses
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsnip_storage::MemoryStorage;
 
-        let relations = match self.storage.get_all_relations(&project.id).await {
-            Ok(r) => r,
-            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
-        };
+    fn server() -> McpServer<MemoryStorage> {
+        McpServer::new(Arc::new(MemoryStorage::new()))
+    }
 
-        // Check if start entity exists
-        if !entities.contains_key(&args.start) {
-            return ToolCallResponse::error(format!("Start entity '{}' not found", args.start));
-        }
+    #[tokio::test]
+    async fn test_notifications_get_no_response() {
+        let server = server();
 
-        // Check if target entity exists (if specified)
-        if let Some(ref target) = args.target {
-            if !entities.contains_key(target) {
-                return ToolCallResponse::error(format!("Target entity '{}' not found", target));
-            }
-        }
+        let payload =
+            JsonRpcPayload::parse(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#);
+        assert!(server
+            .handle_payload(DEFAULT_SESSION, payload)
+            .await
+            .is_none());
 
-        // Build traversal query
-        let direction = match args.direction.as_deref() {
-            Some("outgoing") => Direction::Outgoing,
-            Some("incoming") => Direction::Incoming,
-            _ => Direction::Both,
+        let payload = JsonRpcPayload::parse(
+            r#"[{"jsonrpc":"2.0","method":"notifications/initialized"},{"jsonrpc":"2.0","id":1,"method":"ping"},{"jsonrpc":"2.0","id":2,"method":"nope"}]"#,
+        );
+        let Some(JsonRpcReply::Batch(responses)) =
+            server.handle_payload(DEFAULT_SESSION, payload).await
+        else {
+            panic!("expected a batch reply");
         };
+        assert_eq!(responses.len(), 2);
+        assert!(responses[0].result.is_some());
+        assert_eq!(responses[1].error.as_ref().unwrap().code, -32601);
+    }
 
-        let mut query = TraversalQuery::new(&args.start)
-            .with_depth(depth)
-            .with_direction(direction);
-
-        if let Some(target) = args.target {
-            query = query.find_path_to(&target);
+    #[tokio::test]
+    async fn test_initialize_negotiates_version() {
+        let server = server();
+        for (requested, expected) in [
+            ("2024-11-05", "2024-11-05"),
+            (
+                "2030-01-01",
+                crate::transport::SUPPORTED_PROTOCOL_VERSIONS[0],
+            ),
+        ] {
+            let payload = JsonRpcPayload::parse(&format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"initialize","params":{{"protocolVersion":"{}"}}}}"#,
+                requested
+            ));
+            let Some(JsonRpcReply::Single(response)) =
+                server.handle_payload(DEFAULT_SESSION, payload).await
+            else {
+                panic!("expected a response");
+            };
+            assert_eq!(response.result.unwrap()["protocolVersion"], expected);
         }
+    }
 
-        if args.use_weights.unwrap_or(false) {
-            query = query.weighted();
-        }
+    #[tokio::test]
+    async fn test_cancellation_reaches_tracked_request() {
+        let server = server();
+        let (_, ours) = server.track_request(DEFAULT_SESSION, &serde_json::json!(9));
+        let (_, theirs) = server.track_request("other", &serde_json::json!(9));
 
-        if let Some(ref etypes) = args.entity_type_filter {
-            query = query.filter_entity_types(etypes.clone());
-        }
+        server.handle_notification(
+            DEFAULT_SESSION,
+            "notifications/cancelled",
+            serde_json::json!({ "requestId": 9, "reason": "user aborted" }),
+        );
 
-        if let Some(ref rtypes) = args.relation_type_filter {
-            query = query.filter_relation_types(rtypes.clone());
-        }
+        let wait = std::time::Duration::from_millis(50);
+        assert!(tokio::time::timeout(wait, ours.notified()).await.is_ok());
+        // Another session's request with the same id keeps running
+        assert!(tokio::time::timeout(wait, theirs.notified()).await.is_err());
+    }
 
-        tracing::info!(
-            "Traversing from '{}' (target: {:?}, depth: {}, direction: {:?})",
-            args.start,
-            query.target,
-            query.max_depth,
-            query.direction
+    #[tokio::test]
+    async fn test_tool_call_validation_error() {
+        let server = server();
+        let payload = JsonRpcPayload::parse(
+            r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"open_nodes","arguments":{"names":"Alice"}}}"#,
         );
-
-        // Execute traversal
-        let result = TraversalEngine::execute(&query, &entities, &relations);
-
-        // Convert to JSON response
-        let response = TraversalResultJson {
-            paths: result
-                .paths
-                .iter()
-                .map(|p| PathJson {
-                    nodes: p.nodes.clone(),
-                    edges: p
-                        .edges
-                        .iter()
-                        .map(|e| PathEdgeJson {
-                            from: e.from.clone(),
-                            to: e.to.clone(),
-                            relation_type: e.relation_type.clone(),
-                            weight: e.weight,
-                        })
-                        .collect(),
-                    total_weight: p.total_weight,
-                    length: p.length,
-                })
-                .collect(),
-            visited_entities: result.visited_entities.clone(),
-            entities: result.entities.iter().map(EntityResult::from).collect(),
-            relations: result.relations.iter().map(RelationResult::from).collect(),
-            stats: TraversalStatsJson {
-                nodes_visited: result.stats.nodes_visited,
-                edges_traversed: result.stats.edges_traversed,
-                max_depth_reached: result.stats.max_depth_reached,
-            },
+        let Some(JsonRpcReply::Single(response)) =
+            server.handle_payload(DEFAULT_SESSION, payload).await
+        else {
+            panic!("expected a response");
         };
 
-        ToolCallResponse::text(serde_json::to_string_pretty(&response).unwrap())
+        let error = serde_json::to_value(response.error.unwrap()).unwrap();
+        assert_eq!(error["code"], -32602);
+        assert_eq!(
+            error["data"],
+            serde_json::json!({
+                "tool": "open_nodes",
+                "errors": [{"path": "/names", "message": "expected array, got string"}]
+            })
+        );
     }
 
-    async fn handle_list_projects(&self) -> ToolCallResponse {
-        let projects = match self.storage.get_all_projects().await {
-            Ok(p) => p,
-            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+    async fn request(
+        server: &McpServer<MemoryStorage>,
+        permissions: &Permissions,
+        body: &str,
+    ) -> JsonRpcResponse {
+        let payload = JsonRpcPayload::parse(body);
+        let Some(JsonRpcReply::Single(response)) = server
+            .handle_payload_as(DEFAULT_SESSION, permissions, payload)
+            .await
+        else {
+            panic!("expected a response");
         };
+        response
+    }
 
-        let mut results = Vec::new();
-        for project in projects {
-            let entity_count = self
-                .storage
-                .get_all_entities(&project.id)
+    #[tokio::test]
+    async fn test_permissions_scope_requests() {
+        let storage = Arc::new(MemoryStorage::new());
+        for name in ["work", "secret"] {
+            storage
+                .save_project(&parsnip_core::Project::new(name))
                 .await
-                .map(|e| e.len())
-                .unwrap_or(0);
-            let relation_count = self
-                .storage
-                .get_all_relations(&project.id)
-                .await
-                .map(|r| r.len())
-                .unwrap_or(0);
-
-            results.push(ProjectResult {
-                name: project.name,
-                description: project.description,
-                entity_count,
-                relation_count,
-                created_at: project.created_at.to_rfc3339(),
-            });
+                .unwrap();
         }
+        let server = McpServer::new(storage)
+            .with_permissions(Permissions::new().with_projects(["work", "secret"]));
+        let token = Permissions::new()
+            .with_read_only(true)
+            .with_projects(["work"]);
+
+        let response = request(&server, &token,
+            r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"create_entities","arguments":{"projectId":"work","entities":[]}}}"#,
+        )
+        .await;
+        assert_eq!(
+            response.error.unwrap().code,
+            crate::permissions::PERMISSION_DENIED
+        );
 
-        let response = ProjectListResult { projects: results };
-        ToolCallResponse::text(serde_json::to_string_pretty(&response).unwrap())
+        let response = request(&server, &token,
+            r#"{"jsonrpc":"2.0","id":2,"method":"resources/read","params":{"uri":"parsnip://secret/graph"}}"#,
+        )
+        .await;
+        assert_eq!(response.error.unwrap().code, -32002);
+
+        let response = request(
+            &server,
+            &token,
+            r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"list_projects"}}"#,
+        )
+        .await;
+        let text = response.result.unwrap()["content"][0]["text"].to_string();
+        assert!(text.contains("work") && !text.contains("secret"));
     }
-}
 
-// Result types for JSON responses
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct SearchResult {
-    entities: Vec<EntityResult>,
-    relations: Vec<RelationResult>,
-    pagination: PaginationInfo,
-}
-
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct GraphResult {
-    entities: Vec<EntityResult>,
-    relations: Vec<RelationResult>,
-}
+    #[tokio::test]
+    async fn test_tool_writes_are_audited() {
+        let storage = Arc::new(MemoryStorage::new());
+        let server = McpServer::new(storage.clone()).with_transport(Transport::Sse);
+        let initialize = serde_json::json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": {"clientInfo": {"name": "claude-desktop", "version": "1.0"}}
+        });
+        send(&server, "s1", initialize).await;
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct EntityResult {
-    name: String,
-    entity_type: String,
-    observations: Vec<String>,
-    tags: Vec<String>,
-}
+        let token = Permissions::new().with_token("laptop");
+        let args = serde_json::json!({
+            "projectId": "work",
+            "entities": [{"name": "Alice", "entityType": "person", "observations": []}]
+        });
+        server
+            .call_tool("s1", &token, "create_entities", args.clone())
+            .await
+            .unwrap();
+        // Reads leave no entry
+        server
+            .call_tool(
+                "s1",
+                &token,
+                "open_nodes",
+                serde_json::json!({"projectId": "work", "names": ["Alice"]}),
+            )
+            .await
+            .unwrap();
+
+        let entries = storage
+            .audit_entries(&parsnip_storage::AuditFilter::new())
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.operation, "create_entities");
+        assert_eq!(entry.arguments, args);
+        // The project created on first use and the entity
+        assert_eq!(entry.affected.len(), 2);
+        assert_eq!(
+            entry.actor,
+            Actor::new(Transport::Sse)
+                .with_client("claude-desktop")
+                .with_token("laptop")
+        );
+    }
 
-impl From<&Entity> for EntityResult {
-    fn from(e: &Entity) -> Self {
-        Self {
-            name: e.name.clone(),
-            entity_type: e.entity_type.0.clone(),
-            observations: e.observations.iter().map(|o| o.content.clone()).collect(),
-            tags: e.tags.clone(),
-        }
+    /// Send one message from a session, returning the reply if any
+    async fn send(
+        server: &McpServer<MemoryStorage>,
+        session: &str,
+        message: serde_json::Value,
+    ) -> Option<JsonRpcReply> {
+        let payload = JsonRpcPayload::parse(&message.to_string());
+        server.handle_payload(session, payload).await
     }
-}
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct RelationResult {
-    from: String,
-    to: String,
-    relation_type: String,
-}
+    /// Call a tool and parse the JSON it answers with
+    async fn call_tool(
+        server: &McpServer<MemoryStorage>,
+        session: &str,
+        arguments: serde_json::Value,
+    ) -> serde_json::Value {
+        let message = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": "consolidate_observations", "arguments": arguments}
+        });
+        let Some(JsonRpcReply::Single(response)) = send(server, session, message).await else {
+            panic!("expected a response");
+        };
+        let result = response.result.unwrap();
+        let text = result["content"][0]["text"].as_str().unwrap();
+        serde_json::from_str(text).unwrap_or_else(|_| panic!("not JSON: {}", text))
+    }
 
-impl From<&Relation> for RelationResult {
-    fn from(r: &Relation) -> Self {
-        Self {
-            from: r.from_name.clone(),
-            to: r.to_name.clone(),
-            relation_type: r.relation_type.clone(),
+    /// A client answering each request it receives with the next scripted
+    /// reply: a completion, or an error message. Returns the requests.
+    async fn scripted_client(
+        server: &McpServer<MemoryStorage>,
+        session: &str,
+        requests: &mut tokio::sync::mpsc::UnboundedReceiver<String>,
+        replies: &[Result<&str, &str>],
+    ) -> Vec<serde_json::Value> {
+        let mut received = Vec::new();
+        for reply in replies {
+            let request: serde_json::Value =
+                serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+            let response = match reply {
+                Ok(text) => serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": {
+                        "role": "assistant",
+                        "content": {"type": "text", "text": text},
+                        "model": "scripted-model",
+                        "stopReason": "endTurn"
+                    }
+                }),
+                Err(message) => serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": {"code": -1, "message": message}
+                }),
+            };
+            assert!(send(server, session, response).await.is_none());
+            received.push(request);
         }
+        received
     }
-}
-
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct PaginationInfo {
-    current_page: usize,
-    page_size: usize,
-    total_count: usize,
-    total_pages: usize,
-    has_next_page: bool,
-    has_previous_page: bool,
-}
-
-// Traversal result types
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct TraversalResultJson {
-    paths: Vec<PathJson>,
-    visited_entities: Vec<String>,
-    entities: Vec<EntityResult>,
-    relations: Vec<RelationResult>,
-    stats: TraversalStatsJson,
-}
-
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct PathJson {
-    nodes: Vec<String>,
-    edges: Vec<PathEdgeJson>,
-    total_weight: f64,
-    length: usize,
-}
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct PathEdgeJson {
-    from: String,
-    to: String,
-    relation_type: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    weight: Option<f64>,
-}
+    #[tokio::test]
+    async fn test_consolidate_with_scripted_sampling_client() {
+        let storage = Arc::new(MemoryStorage::new());
+        let project = parsnip_core::Project::new("work");
+        storage.save_project(&project).await.unwrap();
+        let mut alice = Entity::new(project.id.clone(), "Alice", "person");
+        for fact in [
+            "Works at Acme",
+            "works at Acme.",
+            "Likes tea",
+            "Drinks tea every morning",
+        ] {
+            alice.add_observation(fact);
+        }
+        storage.save_entity(&alice).await.unwrap();
+        let server = McpServer::new(storage.clone());
+
+        let (tx, mut requests) = tokio::sync::mpsc::unbounded_channel();
+        server.connect_client("s1", tx);
+        send(
+            &server,
+            "s1",
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": "2025-06-18",
+                    "capabilities": {"sampling": {}},
+                    "clientInfo": {"name": "scripted", "version": "1.0"}
+                }
+            }),
+        )
+        .await;
+        assert_eq!(
+            server.client_info("s1").unwrap().name.as_deref(),
+            Some("scripted")
+        );
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct TraversalStatsJson {
-    nodes_visited: usize,
-    edges_traversed: usize,
-    max_depth_reached: u32,
-}
+        // Dry run: the model's merge is previewed, nothing changes
+        let args = serde_json::json!({"projectId": "work", "name": "Alice", "dryRun": true});
+        let merged: [Result<&str, &str>; 1] = [Ok("Works at Acme\nDrinks tea every morning")];
+        let (preview, sent) = tokio::join!(
+            call_tool(&server, "s1", args),
+            scripted_client(&server, "s1", &mut requests, &merged)
+        );
+        assert_eq!(preview["strategy"], "sampling");
+        assert_eq!(preview["model"], "scripted-model");
+        assert_eq!(preview["observationsAfter"], 2);
+        assert_eq!(sent[0]["method"], "sampling/createMessage");
+        let prompt = sent[0]["params"]["messages"][0]["content"]["text"]
+            .as_str()
+            .unwrap();
+        assert!(prompt.contains("1. Works at Acme\n") && prompt.contains("4. Drinks tea"));
+        let stored = storage.get_entity("Alice", &project.id).await.unwrap();
+        assert_eq!(stored.unwrap().observations.len(), 4);
+
+        // A declined request falls back to dedupe
+        let args = serde_json::json!({"projectId": "work", "name": "Alice", "dryRun": true});
+        let (fallback, _) = tokio::join!(
+            call_tool(&server, "s1", args),
+            scripted_client(&server, "s1", &mut requests, &[Err("User declined")])
+        );
+        assert_eq!(fallback["strategy"], "dedupe");
+        assert!(fallback["fallbackReason"]
+            .as_str()
+            .unwrap()
+            .contains("User declined"));
+        assert_eq!(fallback["observationsAfter"], 3);
+
+        // Applied: the originals move to the history
+        let args = serde_json::json!({"projectId": "work", "name": "Alice"});
+        let (applied, _) = tokio::join!(
+            call_tool(&server, "s1", args),
+            scripted_client(&server, "s1", &mut requests, &merged)
+        );
+        assert_eq!(applied["observationsAfter"], 2);
+        let stored = storage
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let contents: Vec<&str> = stored
+            .observations
+            .iter()
+            .map(|o| o.content.as_str())
+            .collect();
+        assert_eq!(contents, vec!["Works at Acme", "Drinks tea every morning"]);
+        assert_eq!(
+            stored.observations[0].source.as_deref(),
+            Some("consolidated by scripted-model")
+        );
+        assert_eq!(stored.observation_history.len(), 4);
+
+        // A session without sampling never sees a request
+        let (tx, mut other) = tokio::sync::mpsc::unbounded_channel();
+        server.connect_client("s2", tx);
+        let args = serde_json::json!({"projectId": "work", "name": "Alice", "strategy": "auto"});
+        let result = call_tool(&server, "s2", args).await;
+        assert_eq!(result["strategy"], "dedupe");
+        assert!(other.try_recv().is_err());
+        server.end_session("s1");
+        assert!(server.client_info("s1").is_none());
+    }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ProjectListResult {
-    projects: Vec<ProjectResult>,
-}
+    #[tokio::test]
+    async fn test_consolidate_waits_for_the_entity_lock() {
+        let storage = Arc::new(MemoryStorage::new());
+        let project = parsnip_core::Project::new("work");
+        storage.save_project(&project).await.unwrap();
+        let mut alice = Entity::new(project.id.clone(), "Alice", "person");
+        alice.add_observation("Works at Acme");
+        alice.add_observation("works at Acme.");
+        storage.save_entity(&alice).await.unwrap();
+        let server = McpServer::new(storage.clone());
+
+        // Another call is rewriting Alice; consolidation waits for it
+        let other = server.tool_context("s2", &Permissions::new());
+        let guard = other.lock_entity(&project.id, "Alice").await;
+        let args = serde_json::json!({"projectId": "work", "name": "Alice", "strategy": "dedupe"});
+        let (result, _) = tokio::join!(call_tool(&server, "s1", args), async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            let mut alice = storage
+                .get_entity("Alice", &project.id)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(alice.observations.len(), 2);
+            alice.add_observation("Likes tea");
+            storage.save_entity(&alice).await.unwrap();
+            drop(guard);
+        });
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ProjectResult {
-    name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    description: Option<String>,
-    entity_count: usize,
-    relation_count: usize,
-    created_at: String,
+        // ...and keeps what it wrote
+        assert_eq!(result["observationsAfter"], 2);
+        let stored = storage
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let contents: Vec<&str> = stored
+            .observations
+            .iter()
+            .map(|o| o.content.as_str())
+            .collect();
+        assert_eq!(contents, vec!["works at Acme.", "Likes tea"]);
+    }
 }
@@ -1,15 +1,23 @@
-//! SSE transport for MCP server
+//! HTTP transport for MCP server
 //!
-//! Implements MCP over HTTP with SSE for server-to-client events.
+//! Serves the streamable HTTP transport on `/mcp` (see [`crate::streamable`])
+//! and the REST API (see [`crate::rest`]), with Prometheus metrics on
+//! `/metrics`.
+//! The legacy HTTP+SSE endpoints, `/sse` for events and `/message` for
+//! requests, are available with [`HttpOptions::with_legacy_sse`].
+//! With the `tls` feature, [`HttpOptions::with_tls`] serves over HTTPS (see
+//! [`crate::tls`]).
 
 #[cfg(feature = "sse")]
-use std::sync::Arc;
+use std::collections::HashMap;
+#[cfg(feature = "sse")]
+use std::sync::{Arc, RwLock};
 
 #[cfg(feature = "sse")]
 use axum::{
     body::Body,
-    extract::State,
-    http::{header, HeaderMap, Method, Request, StatusCode},
+    extract::{Extension, Query, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode},
     middleware::{self, Next},
     response::{
         sse::{Event, Sse},
@@ -26,16 +34,39 @@ use futures::stream::Stream;
 use parsnip_storage::StorageBackend;
 
 #[cfg(feature = "sse")]
-use tokio::sync::broadcast;
+use tokio::sync::mpsc;
 
 #[cfg(feature = "sse")]
 use tower_http::cors::CorsLayer;
 
+#[cfg(feature = "sse")]
+use tracing::Instrument;
+
 #[cfg(feature = "sse")]
 use tower_http::limit::RequestBodyLimitLayer;
 
 #[cfg(feature = "sse")]
-use crate::transport::JsonRpcRequest;
+use crate::metrics::CONTENT_TYPE as METRICS_CONTENT_TYPE;
+
+#[cfg(feature = "sse")]
+use crate::permissions::Permissions;
+
+#[cfg(feature = "tls")]
+use crate::tls::{ClientCert, TlsOptions};
+
+#[cfg(feature = "sse")]
+use crate::transport::{JsonRpcPayload, INVALID_REQUEST};
+
+#[cfg(feature = "sse")]
+use crate::server::next_change;
+
+#[cfg(feature = "sse")]
+use crate::rest::rest_router;
+
+#[cfg(feature = "sse")]
+use crate::streamable::{
+    rpc_error, streamable_router, SessionStore, PROTOCOL_VERSION_HEADER, SESSION_HEADER,
+};
 
 #[cfg(feature = "sse")]
 use crate::McpServer;
@@ -44,32 +75,172 @@ use crate::McpServer;
 #[cfg(feature = "sse")]
 const MAX_BODY_SIZE: usize = 1024 * 1024;
 
+/// Header carrying the ID of an HTTP request
+#[cfg(feature = "sse")]
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-chosen request ID that is kept
+#[cfg(feature = "sse")]
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// A bearer token clients may authenticate with, and what it permits
+#[cfg(feature = "sse")]
+#[derive(Clone)]
+pub struct AuthToken {
+    /// Name of the token in logs
+    pub label: String,
+    pub token: String,
+    pub permissions: Permissions,
+}
+
+#[cfg(feature = "sse")]
+impl AuthToken {
+    /// A token permitting everything the server allows
+    pub fn new(label: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            token: token.into(),
+            permissions: Permissions::new(),
+        }
+    }
+
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+}
+
+#[cfg(feature = "sse")]
+impl std::fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keep the secret out of logs
+        f.debug_struct("AuthToken")
+            .field("label", &self.label)
+            .field("permissions", &self.permissions)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Options of the HTTP transport
+#[cfg(feature = "sse")]
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    /// Bearer tokens accepted on every endpoint but `/health`; when empty,
+    /// requests need no token
+    pub tokens: Vec<AuthToken>,
+    /// Also serve the legacy `/sse` and `/message` endpoints
+    pub legacy_sse: bool,
+    /// Serve over TLS
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsOptions>,
+}
+
+#[cfg(feature = "sse")]
+impl HttpOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept a token permitting everything the server allows
+    pub fn with_auth_token(mut self, auth_token: Option<String>) -> Self {
+        if let Some(token) = auth_token {
+            self.tokens.push(AuthToken::new("default", token));
+        }
+        self
+    }
+
+    /// Accept a token with its own permissions
+    pub fn with_token(mut self, token: AuthToken) -> Self {
+        self.tokens.push(token);
+        self
+    }
+
+    /// Whether requests must carry a bearer token or a configured client
+    /// certificate
+    pub fn requires_auth(&self) -> bool {
+        #[cfg(feature = "tls")]
+        if self.tls.as_ref().is_some_and(|tls| !tls.clients.is_empty()) {
+            return true;
+        }
+        !self.tokens.is_empty()
+    }
+
+    pub fn with_legacy_sse(mut self, legacy_sse: bool) -> Self {
+        self.legacy_sse = legacy_sse;
+        self
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+}
+
 /// SSE transport state
 #[cfg(feature = "sse")]
 pub struct SseState<S: StorageBackend> {
     server: Arc<McpServer<S>>,
-    event_tx: broadcast::Sender<String>,
-    auth_token: Option<String>,
+    /// Each open legacy `/sse` session
+    streams: RwLock<HashMap<String, LegacyStream>>,
+    tokens: Vec<AuthToken>,
+    /// Whether requests must be authenticated, by token or certificate
+    requires_auth: bool,
 }
 
 #[cfg(feature = "sse")]
 impl<S: StorageBackend + Send + Sync + 'static> SseState<S> {
-    pub fn new(server: Arc<McpServer<S>>, auth_token: Option<String>) -> Self {
-        let (event_tx, _) = broadcast::channel(100);
+    pub fn new(server: Arc<McpServer<S>>, tokens: Vec<AuthToken>) -> Self {
         Self {
             server,
-            event_tx,
-            auth_token,
+            streams: RwLock::new(HashMap::new()),
+            requires_auth: !tokens.is_empty(),
+            tokens,
         }
     }
 }
 
-/// Auth middleware - validates Bearer token if configured
+/// An open legacy `/sse` session
+#[cfg(feature = "sse")]
+struct LegacyStream {
+    /// Responses and requests to send on the event stream
+    tx: mpsc::UnboundedSender<String>,
+    /// Permissions of the request that opened it; only the same caller may
+    /// post into it
+    owner: Permissions,
+}
+
+/// Compare two byte strings in time independent of where they differ
+#[cfg(feature = "sse")]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The configured token matching a presented one
+///
+/// Every token is compared, so the time taken does not reveal which one
+/// came closest.
+#[cfg(feature = "sse")]
+fn match_token<'a>(tokens: &'a [AuthToken], presented: &str) -> Option<&'a AuthToken> {
+    tokens.iter().fold(None, |found, candidate| {
+        let matches = constant_time_eq(candidate.token.as_bytes(), presented.as_bytes());
+        found.or(matches.then_some(candidate))
+    })
+}
+
+/// Auth middleware - validates the Bearer token if any are configured and
+/// attaches the token's [`Permissions`] to the request
+///
+/// Requests over a connection authenticated by a configured client
+/// certificate need no token and carry the certificate's permissions.
 #[cfg(feature = "sse")]
 async fn auth_middleware<S: StorageBackend + Send + Sync + 'static>(
     State(state): State<Arc<SseState<S>>>,
     headers: HeaderMap,
-    request: Request<Body>,
+    mut request: Request<Body>,
     next: Next,
 ) -> Response {
     // Skip auth for health endpoint
@@ -77,10 +248,18 @@ async fn auth_middleware<S: StorageBackend + Send + Sync + 'static>(
         return next.run(request).await;
     }
 
+    #[cfg(feature = "tls")]
+    if let Some(client) = request.extensions().get::<ClientCert>() {
+        tracing::debug!("Request authenticated with certificate {}", client.name);
+        let permissions = client.permissions.clone().with_token(&client.name);
+        request.extensions_mut().insert(permissions);
+        return next.run(request).await;
+    }
+
     // If no auth token configured, allow all requests (localhost mode)
-    let Some(expected_token) = &state.auth_token else {
+    if !state.requires_auth {
         return next.run(request).await;
-    };
+    }
 
     // Check Authorization header
     let auth_header = headers
@@ -88,14 +267,15 @@ async fn auth_middleware<S: StorageBackend + Send + Sync + 'static>(
         .and_then(|v| v.to_str().ok());
 
     match auth_header {
-        Some(auth) if auth.starts_with("Bearer ") => {
-            let token = &auth[7..];
-            if token == expected_token {
+        Some(auth) if auth.starts_with("Bearer ") => match match_token(&state.tokens, &auth[7..]) {
+            Some(token) => {
+                tracing::debug!("Request authenticated with token {}", token.label);
+                let permissions = token.permissions.clone().with_token(&token.label);
+                request.extensions_mut().insert(permissions);
                 next.run(request).await
-            } else {
-                (StatusCode::UNAUTHORIZED, "Invalid token").into_response()
             }
-        }
+            None => (StatusCode::UNAUTHORIZED, "Invalid token").into_response(),
+        },
         _ => (
             StatusCode::UNAUTHORIZED,
             "Missing or invalid Authorization header",
@@ -104,13 +284,53 @@ async fn auth_middleware<S: StorageBackend + Send + Sync + 'static>(
     }
 }
 
-/// Create the SSE router
+/// Run each request in a span with a request ID, echoed in the response
+///
+/// The ID is the client's `X-Request-Id` when it sent a usable one, so
+/// logs can be matched with a proxy's; otherwise a new ULID.
 #[cfg(feature = "sse")]
-pub fn create_sse_router<S: StorageBackend + Send + Sync + 'static>(
+async fn request_id_middleware(request: Request<Body>, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| ulid::Ulid::new().to_string());
+    let span = tracing::info_span!(
+        "http",
+        request_id = %id,
+        method = %request.method(),
+        path = %request.uri().path()
+    );
+
+    let started = std::time::Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    span.in_scope(|| {
+        tracing::debug!(
+            status = response.status().as_u16(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Request finished"
+        )
+    });
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Create the HTTP router
+#[cfg(feature = "sse")]
+pub fn create_router<S: StorageBackend + Send + Sync + 'static>(
     server: Arc<McpServer<S>>,
-    auth_token: Option<String>,
+    options: HttpOptions,
 ) -> Router {
-    let state = Arc::new(SseState::new(server, auth_token));
+    let sessions = Arc::new(SessionStore::new(server.clone()));
+    let requires_auth = options.requires_auth();
+    let mut state = SseState::new(server, options.tokens);
+    state.requires_auth = requires_auth;
+    let state = Arc::new(state);
+    let session_header = HeaderName::from_static(SESSION_HEADER);
 
     // Restrictive CORS: only allow localhost origins
     let cors = CorsLayer::new()
@@ -120,20 +340,55 @@ pub fn create_sse_router<S: StorageBackend + Send + Sync + 'static>(
             "http://localhost:8080".parse().unwrap(),
             "http://127.0.0.1:8080".parse().unwrap(),
         ])
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]);
-
-    Router::new()
-        .route("/sse", get(sse_handler::<S>))
-        .route("/message", post(message_handler::<S>))
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            header::IF_MATCH,
+            header::IF_NONE_MATCH,
+            HeaderName::from_static("last-event-id"),
+            HeaderName::from_static(PROTOCOL_VERSION_HEADER),
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            session_header.clone(),
+        ])
+        .expose_headers([
+            session_header,
+            header::ETAG,
+            header::LOCATION,
+            HeaderName::from_static(REQUEST_ID_HEADER),
+        ]);
+
+    let mut router = Router::new()
         .route("/health", get(health_handler))
-        .layer(middleware::from_fn_with_state(
-            state.clone(),
-            auth_middleware::<S>,
-        ))
-        .with_state(state)
+        .merge(rest_router(state.server.clone()))
+        .merge(streamable_router(sessions))
+        .merge(
+            Router::new()
+                .route("/metrics", get(metrics_handler::<S>))
+                .with_state(state.clone()),
+        );
+
+    if options.legacy_sse {
+        router = router.merge(
+            Router::new()
+                .route("/sse", get(sse_handler::<S>))
+                .route("/message", post(message_handler::<S>))
+                .with_state(state.clone()),
+        );
+    }
+
+    router
+        .layer(middleware::from_fn_with_state(state, auth_middleware::<S>))
         .layer(cors)
         .layer(RequestBodyLimitLayer::new(MAX_BODY_SIZE))
+        .layer(middleware::from_fn(request_id_middleware))
 }
 
 /// Health check endpoint
@@ -146,15 +401,67 @@ async fn health_handler() -> impl IntoResponse {
     }))
 }
 
-/// SSE endpoint for server-to-client events
+/// Prometheus metrics of the server, with per-project counts for the
+/// projects the caller may see
+#[cfg(feature = "sse")]
+async fn metrics_handler<S: StorageBackend + Send + Sync + 'static>(
+    State(state): State<Arc<SseState<S>>>,
+    permissions: Option<Extension<Permissions>>,
+) -> impl IntoResponse {
+    let server = &state.server;
+    let caller = permissions.map(|Extension(p)| p).unwrap_or_default();
+    let scope = server.permissions().restrict(&caller).scope();
+    let text = server
+        .metrics()
+        .render(server.storage().as_ref(), &scope)
+        .await;
+    ([(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], text)
+}
+
+/// Forgets a legacy session when its event stream is dropped
+#[cfg(feature = "sse")]
+struct SessionGuard<S: StorageBackend + Send + Sync + 'static> {
+    state: Arc<SseState<S>>,
+    session: String,
+}
+
+#[cfg(feature = "sse")]
+impl<S: StorageBackend + Send + Sync + 'static> Drop for SessionGuard<S> {
+    fn drop(&mut self) {
+        tracing::debug!("SSE session {} closed", self.session);
+        self.state
+            .streams
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.session);
+        self.state.server.end_session(&self.session);
+    }
+}
+
+/// Legacy SSE endpoint for server-to-client events
+///
+/// Each connection is a session: the endpoint event carries its ID, and the
+/// responses to its messages and notifications for its subscriptions arrive
+/// on this stream only.
 #[cfg(feature = "sse")]
 async fn sse_handler<S: StorageBackend + Send + Sync + 'static>(
     State(state): State<Arc<SseState<S>>>,
+    permissions: Option<Extension<Permissions>>,
 ) -> Sse<impl Stream<Item = Result<Event, std::convert::Infallible>>> {
-    let mut rx = state.event_tx.subscribe();
+    let owner = permissions.map(|Extension(p)| p).unwrap_or_default();
+    let mut changes = state.server.change_feed().map(|feed| feed.subscribe());
+    let session = ulid::Ulid::new().to_string();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    // Requests to the client, such as sampling, go out on the same stream
+    state.server.connect_client(&session, tx.clone());
+    state
+        .streams
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(session.clone(), LegacyStream { tx, owner });
 
     // Send initial endpoint message
-    let endpoint_url = "/message";
+    let endpoint_url = format!("/message?sessionId={}", session);
     let initial_event = serde_json::json!({
         "jsonrpc": "2.0",
         "method": "endpoint",
@@ -164,23 +471,32 @@ async fn sse_handler<S: StorageBackend + Send + Sync + 'static>(
     });
 
     let initial_msg = serde_json::to_string(&initial_event).unwrap();
+    let guard = SessionGuard {
+        state: state.clone(),
+        session,
+    };
 
     let stream = async_stream::stream! {
+        let guard = guard;
+
         // Send endpoint info first
         yield Ok(Event::default().event("endpoint").data(initial_msg));
 
-        // Then stream events from broadcast channel
+        // Then stream responses and this session's notifications
         loop {
-            match rx.recv().await {
-                Ok(msg) => {
-                    yield Ok(Event::default().event("message").data(msg));
-                }
-                Err(broadcast::error::RecvError::Lagged(_)) => {
-                    tracing::warn!("SSE client lagged behind");
-                    continue;
-                }
-                Err(broadcast::error::RecvError::Closed) => {
-                    break;
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(msg) => {
+                        yield Ok(Event::default().event("message").data(msg));
+                    }
+                    None => break,
+                },
+                Some(change) = next_change(&mut changes) => {
+                    for notification in guard.state.server.notifications_for(&guard.session, &change) {
+                        if let Ok(json) = serde_json::to_string(&notification) {
+                            yield Ok(Event::default().event("message").data(json));
+                        }
+                    }
                 }
             }
         }
@@ -189,40 +505,356 @@ async fn sse_handler<S: StorageBackend + Send + Sync + 'static>(
     Sse::new(stream)
 }
 
-/// Message endpoint for client requests
+/// Query parameters of the message endpoint
+#[cfg(feature = "sse")]
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageQuery {
+    session_id: Option<String>,
+}
+
+/// Where a legacy message for a session is answered, if the caller opened it
+#[cfg(feature = "sse")]
+fn legacy_stream<S: StorageBackend>(
+    state: &SseState<S>,
+    session: &str,
+    caller: &Permissions,
+) -> Option<mpsc::UnboundedSender<String>> {
+    let streams = state.streams.read().unwrap_or_else(|e| e.into_inner());
+    let stream = streams.get(session)?;
+    if stream.owner != *caller {
+        tracing::warn!(
+            "SSE session {} used by a caller that did not open it",
+            session
+        );
+        return None;
+    }
+    Some(stream.tx.clone())
+}
+
+/// Legacy message endpoint for client requests
 #[cfg(feature = "sse")]
 async fn message_handler<S: StorageBackend + Send + Sync + 'static>(
     State(state): State<Arc<SseState<S>>>,
-    Json(request): Json<JsonRpcRequest>,
-) -> impl IntoResponse {
-    tracing::debug!("Received SSE request: {:?}", request.method);
+    Query(query): Query<MessageQuery>,
+    permissions: Option<Extension<Permissions>>,
+    body: String,
+) -> Response {
+    let permissions = permissions.map(|Extension(p)| p).unwrap_or_default();
+    // Each client posts into the session its own /sse stream opened
+    let Some(session) = query.session_id else {
+        return rpc_error(
+            StatusCode::BAD_REQUEST,
+            INVALID_REQUEST,
+            "Missing sessionId; open /sse for the message endpoint",
+        );
+    };
+    let Some(tx) = legacy_stream(&state, &session, &permissions) else {
+        return rpc_error(StatusCode::NOT_FOUND, -32001, "Session not found");
+    };
 
-    let response = state.server.handle_request_public(request).await;
+    let payload = JsonRpcPayload::parse(&body);
+    let Some(reply) = state
+        .server
+        .handle_payload_as(&session, &permissions, payload)
+        .await
+    else {
+        return StatusCode::ACCEPTED.into_response();
+    };
 
-    // Also broadcast to SSE clients if they want to see responses
-    if let Ok(json) = serde_json::to_string(&response) {
-        let _ = state.event_tx.send(json);
+    // Deliver the reply on the session's own stream too
+    if let Ok(json) = serde_json::to_string(&reply) {
+        let _ = tx.send(json);
     }
 
-    Json(response)
+    Json(reply).into_response()
 }
 
-/// Run the SSE server
+/// Run the HTTP server
 #[cfg(feature = "sse")]
-pub async fn run_sse_server<S: StorageBackend + Send + Sync + 'static>(
+pub async fn run_http_server<S: StorageBackend + Send + Sync + 'static>(
     server: Arc<McpServer<S>>,
     addr: &str,
-    auth_token: Option<String>,
+    options: HttpOptions,
 ) -> anyhow::Result<()> {
-    let router = create_sse_router(server, auth_token);
+    let legacy_sse = options.legacy_sse;
+    #[cfg(feature = "tls")]
+    let tls = options.tls.clone();
+    #[cfg(feature = "tls")]
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    #[cfg(not(feature = "tls"))]
+    let scheme = "http";
+    let router = create_router(server, options);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    tracing::info!("MCP SSE server listening on {}", addr);
-    tracing::info!("  SSE endpoint: http://{}/sse", addr);
-    tracing::info!("  Message endpoint: http://{}/message", addr);
-    tracing::info!("  Health check: http://{}/health", addr);
+    tracing::info!("MCP HTTP server listening on {}", addr);
+    tracing::info!("  MCP endpoint: {}://{}/mcp", scheme, addr);
+    tracing::info!("  REST API: {}://{}/openapi.json", scheme, addr);
+    tracing::info!("  Metrics: {}://{}/metrics", scheme, addr);
+    if legacy_sse {
+        tracing::info!("  Legacy SSE endpoint: {}://{}/sse", scheme, addr);
+        tracing::info!("  Legacy message endpoint: {}://{}/message", scheme, addr);
+    }
+    tracing::info!("  Health check: {}://{}/health", scheme, addr);
 
+    #[cfg(feature = "tls")]
+    if let Some(tls) = tls {
+        crate::tls::serve(listener, router, tls).await?;
+        return Ok(());
+    }
     axum::serve(listener, router).await?;
 
     Ok(())
 }
+
+#[cfg(all(test, feature = "sse"))]
+mod tests {
+    use super::*;
+    use axum::body::BodyDataStream;
+    use futures::StreamExt;
+    use parsnip_storage::MemoryStorage;
+    use tower::ServiceExt;
+
+    /// Open a legacy `/sse` session, returning its ID and the event stream,
+    /// which keeps the session open until dropped
+    async fn open_legacy_session(router: &Router, token: &str) -> (String, BodyDataStream) {
+        let request = Request::get("/sse")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut events = response.into_body().into_data_stream();
+        let endpoint = events.next().await.unwrap().unwrap();
+        let endpoint = String::from_utf8(endpoint.to_vec()).unwrap();
+        let session = endpoint
+            .split("sessionId=")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap()
+            .to_string();
+        (session, events)
+    }
+
+    fn legacy_message(session: Option<&str>, token: Option<&str>) -> Request<Body> {
+        let uri = match session {
+            Some(session) => format!("/message?sessionId={}", session),
+            None => "/message".to_string(),
+        };
+        let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        request
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#,
+            ))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_match_token() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+
+        let tokens = vec![
+            AuthToken::new("admin", "a-token"),
+            AuthToken::new("recall", "r-token")
+                .with_permissions(Permissions::new().with_read_only(true)),
+        ];
+        let matched = match_token(&tokens, "r-token").unwrap();
+        assert_eq!(matched.label, "recall");
+        assert!(matched.permissions.read_only);
+        assert!(match_token(&tokens, "x-token").is_none());
+        assert!(match_token(&tokens, "").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_token_profile_limits_tools() {
+        let server = Arc::new(McpServer::new(Arc::new(MemoryStorage::new())));
+        let options = HttpOptions::new()
+            .with_legacy_sse(true)
+            .with_auth_token(Some("admin".to_string()))
+            .with_token(
+                AuthToken::new("recall", "recall")
+                    .with_permissions(Permissions::new().with_read_only(true)),
+            );
+        let router = create_router(server, options);
+        let (session, _events) = open_legacy_session(&router, "admin").await;
+
+        for token in [None, Some("nope")] {
+            let request = legacy_message(Some(&session), token);
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let mut counts = Vec::new();
+        let mut streams = Vec::new();
+        for token in ["admin", "recall"] {
+            let (session, events) = open_legacy_session(&router, token).await;
+            streams.push(events);
+            let response = router
+                .clone()
+                .oneshot(legacy_message(Some(&session), Some(token)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let reply: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            counts.push(reply["result"]["tools"].as_array().unwrap().len());
+        }
+        assert_eq!(counts, vec![23, 11]);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sessions_are_bound_to_their_token() {
+        let server = Arc::new(McpServer::new(Arc::new(MemoryStorage::new())));
+        let options = HttpOptions::new()
+            .with_legacy_sse(true)
+            .with_token(AuthToken::new("alice", "alice-token"))
+            .with_token(AuthToken::new("bob", "bob-token"));
+        let router = create_router(server, options);
+        let (session, mut events) = open_legacy_session(&router, "alice-token").await;
+
+        // No shared session to fall back to, and no posting into another's
+        let response = router
+            .clone()
+            .oneshot(legacy_message(None, Some("alice-token")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        for (session, token) in [(session.as_str(), "bob-token"), ("unknown", "alice-token")] {
+            let request = legacy_message(Some(session), Some(token));
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        let request = legacy_message(Some(&session), Some("alice-token"));
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let reply = events.next().await.unwrap().unwrap();
+        assert!(String::from_utf8(reply.to_vec())
+            .unwrap()
+            .contains("\"tools\""));
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_bound_to_their_token() {
+        let server = Arc::new(McpServer::new(Arc::new(MemoryStorage::new())));
+        let options = HttpOptions::new()
+            .with_token(AuthToken::new("alice", "alice-token"))
+            .with_token(AuthToken::new("bob", "bob-token"));
+        let router = create_router(server, options);
+
+        let request = |method: Method, token: &str, session: Option<&str>, body: &str| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri("/mcp")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::ACCEPT, "application/json, text/event-stream")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token));
+            if let Some(session) = session {
+                request = request.header(SESSION_HEADER, session);
+            }
+            request.body(Body::from(body.to_string())).unwrap()
+        };
+        let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+
+        let response = router
+            .clone()
+            .oneshot(request(
+                Method::POST,
+                "alice-token",
+                None,
+                r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        // Another token cannot post into, stream from, or end the session
+        for method in [Method::POST, Method::GET, Method::DELETE] {
+            let response = router
+                .clone()
+                .oneshot(request(method, "bob-token", Some(&session), ping))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        for method in [Method::POST, Method::GET] {
+            let response = router
+                .clone()
+                .oneshot(request(method, "alice-token", Some(&session), ping))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_and_request_ids() {
+        let server = Arc::new(McpServer::new(Arc::new(MemoryStorage::new())));
+        let router = create_router(server, HttpOptions::new());
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = router.clone().oneshot(get("/search?q=x")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[REQUEST_ID_HEADER].len(), 26);
+
+        let request = Request::get("/metrics")
+            .header(REQUEST_ID_HEADER, "proxy-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "proxy-42");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("parsnip_tool_calls_total{tool=\"search_knowledge\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_report_only_the_callers_projects() {
+        let storage = Arc::new(MemoryStorage::new());
+        for name in ["work", "home"] {
+            storage
+                .save_project(&parsnip_core::Project::new(name))
+                .await
+                .unwrap();
+        }
+        let server = Arc::new(McpServer::new(storage));
+        let options = HttpOptions::new()
+            .with_token(AuthToken::new("admin", "admin-token"))
+            .with_token(
+                AuthToken::new("work", "work-token")
+                    .with_permissions(Permissions::new().with_projects(["work"])),
+            );
+        let router = create_router(server, options);
+
+        let mut scraped = Vec::new();
+        for token in ["admin-token", "work-token"] {
+            let request = Request::get("/metrics")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            scraped.push(String::from_utf8(body.to_vec()).unwrap());
+        }
+        assert!(scraped[0].contains("project=\"home\""));
+        assert!(scraped[1].contains("project=\"work\""));
+        assert!(!scraped[1].contains("project=\"home\""));
+    }
+}
@@ -0,0 +1,644 @@
+//! Streamable HTTP transport: one `/mcp` endpoint with sessions
+//!
+//! - `POST /mcp` carries a JSON-RPC message. `initialize` opens a session
+//!   whose ID comes back in the `Mcp-Session-Id` header, and every later
+//!   message must send it. Responses are JSON, or a one-event SSE stream for
+//!   clients that only accept `text/event-stream`.
+//! - `GET /mcp` opens the session's event stream for server notifications.
+//!   Events carry IDs; reconnecting with `Last-Event-ID` replays the events
+//!   the client missed while they are still buffered.
+//! - `DELETE /mcp` ends the session.
+//!
+//! Each session only ever sees its own responses and notifications, and
+//! only the caller that opened it, by token or client certificate, can use
+//! it; to anyone else it does not exist.
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Extension, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::post,
+    Json, Router,
+};
+use futures::stream::Stream;
+use parsnip_storage::StorageBackend;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+
+use crate::permissions::Permissions;
+use crate::server::next_change;
+use crate::transport::{
+    JsonRpcPayload, JsonRpcResponse, INVALID_REQUEST, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use crate::McpServer;
+
+/// Header carrying the session ID
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Header carrying the negotiated protocol version
+pub const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// Events kept per session for `Last-Event-ID` replay
+pub const EVENT_BUFFER_SIZE: usize = 256;
+
+/// Sessions idle this long, with no open stream, are dropped
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Server-to-client message with its ID in the session's event sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamEvent {
+    pub id: u64,
+    pub data: String,
+}
+
+impl StreamEvent {
+    fn to_sse(&self) -> Event {
+        Event::default()
+            .id(self.id.to_string())
+            .event("message")
+            .data(&self.data)
+    }
+}
+
+/// Recent events of a session, oldest first
+#[derive(Debug, Default)]
+struct EventLog {
+    next_id: u64,
+    events: VecDeque<StreamEvent>,
+}
+
+impl EventLog {
+    fn push(&mut self, data: String) -> StreamEvent {
+        self.next_id += 1;
+        let event = StreamEvent {
+            id: self.next_id,
+            data,
+        };
+        if self.events.len() == EVENT_BUFFER_SIZE {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.clone());
+        event
+    }
+
+    fn after(&self, last_id: u64) -> Vec<StreamEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.id > last_id)
+            .cloned()
+            .collect()
+    }
+}
+
+/// One client session
+pub struct Session {
+    id: String,
+    /// Permissions, with the token label, of the caller that opened it
+    owner: Permissions,
+    log: Mutex<EventLog>,
+    tx: broadcast::Sender<StreamEvent>,
+    last_seen: Mutex<Instant>,
+    /// Set when the session ends, closing its event streams
+    closed: watch::Sender<bool>,
+}
+
+impl Session {
+    fn new(id: String, owner: Permissions) -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self {
+            id,
+            owner,
+            log: Mutex::new(EventLog::default()),
+            tx,
+            last_seen: Mutex::new(Instant::now()),
+            closed: watch::Sender::new(false),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Permissions of the caller that opened the session
+    pub fn owner(&self) -> &Permissions {
+        &self.owner
+    }
+
+    /// Whether a request authenticated with `caller` may use the session
+    fn belongs_to(&self, caller: &Permissions) -> bool {
+        self.owner == *caller
+    }
+
+    /// Buffer an event and send it to open event streams
+    pub fn publish(&self, data: String) -> StreamEvent {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        let event = log.push(data);
+        // Sent under the lock so a stream opening now sees it exactly once
+        let _ = self.tx.send(event.clone());
+        event
+    }
+
+    /// Buffer an event delivered on its own stream (a POST response)
+    fn record(&self, data: String) -> StreamEvent {
+        self.log
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(data)
+    }
+
+    /// Buffered events after `last_id`, and a receiver for the ones to come
+    fn resume(&self, last_id: u64) -> (Vec<StreamEvent>, broadcast::Receiver<StreamEvent>) {
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        (log.after(last_id), self.tx.subscribe())
+    }
+
+    fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    fn touch(&self) {
+        *self.last_seen.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    fn is_idle(&self, timeout: Duration) -> bool {
+        let last_seen = *self.last_seen.lock().unwrap_or_else(|e| e.into_inner());
+        self.tx.receiver_count() == 0 && last_seen.elapsed() >= timeout
+    }
+}
+
+struct SessionEntry {
+    session: Arc<Session>,
+    /// Forwards storage change notifications into the session
+    forwarder: Option<JoinHandle<()>>,
+    /// Forwards server-to-client requests, such as sampling, into the session
+    requests: JoinHandle<()>,
+}
+
+/// Open sessions of the streamable HTTP transport
+pub struct SessionStore<S: StorageBackend> {
+    server: Arc<McpServer<S>>,
+    sessions: RwLock<HashMap<String, SessionEntry>>,
+    idle_timeout: Duration,
+}
+
+impl<S: StorageBackend + Send + Sync + 'static> SessionStore<S> {
+    pub fn new(server: Arc<McpServer<S>>) -> Self {
+        Self {
+            server,
+            sessions: RwLock::new(HashMap::new()),
+            idle_timeout: SESSION_IDLE_TIMEOUT,
+        }
+    }
+
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Open a session owned by `owner`, dropping idle ones
+    pub fn create(&self, owner: Permissions) -> Arc<Session> {
+        let idle: Vec<String> = {
+            let sessions = self.sessions.read().unwrap_or_else(|e| e.into_inner());
+            sessions
+                .iter()
+                .filter(|(_, entry)| entry.session.is_idle(self.idle_timeout))
+                .map(|(id, _)| id.clone())
+                .collect()
+        };
+        for id in idle {
+            tracing::debug!("Dropping idle session {}", id);
+            self.remove(&id);
+        }
+
+        let session = Arc::new(Session::new(ulid::Ulid::new().to_string(), owner));
+        let forwarder = self.server.change_feed().map(|feed| {
+            let mut changes = Some(feed.subscribe());
+            let server = self.server.clone();
+            let session = session.clone();
+            tokio::spawn(async move {
+                while let Some(change) = next_change(&mut changes).await {
+                    for notification in server.notifications_for(session.id(), &change) {
+                        if let Ok(json) = serde_json::to_string(&notification) {
+                            session.publish(json);
+                        }
+                    }
+                }
+            })
+        });
+
+        let (client_tx, mut client_rx) = mpsc::unbounded_channel();
+        self.server.connect_client(session.id(), client_tx);
+        let requests = {
+            let session = session.clone();
+            tokio::spawn(async move {
+                while let Some(request) = client_rx.recv().await {
+                    session.publish(request);
+                }
+            })
+        };
+
+        let mut sessions = self.sessions.write().unwrap_or_else(|e| e.into_inner());
+        sessions.insert(
+            session.id.clone(),
+            SessionEntry {
+                session: session.clone(),
+                forwarder,
+                requests,
+            },
+        );
+        tracing::debug!("Opened session {}", session.id);
+        session
+    }
+
+    /// Look up a session of `caller`, marking it as used
+    ///
+    /// Sessions opened by another caller are not found.
+    pub fn get(&self, id: &str, caller: &Permissions) -> Option<Arc<Session>> {
+        let sessions = self.sessions.read().unwrap_or_else(|e| e.into_inner());
+        let session = sessions.get(id)?.session.clone();
+        if !session.belongs_to(caller) {
+            tracing::warn!("Session {} used by a caller that did not open it", id);
+            return None;
+        }
+        session.touch();
+        Some(session)
+    }
+
+    /// End a session; false if it was not open
+    pub fn remove(&self, id: &str) -> bool {
+        let entry = self
+            .sessions
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+        let Some(entry) = entry else {
+            return false;
+        };
+        if let Some(forwarder) = entry.forwarder {
+            forwarder.abort();
+        }
+        entry.requests.abort();
+        entry.session.close();
+        self.server.end_session(id);
+        tracing::debug!("Closed session {}", id);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Routes of the `/mcp` endpoint
+pub fn streamable_router<S: StorageBackend + Send + Sync + 'static>(
+    sessions: Arc<SessionStore<S>>,
+) -> Router {
+    Router::new()
+        .route(
+            "/mcp",
+            post(post_handler::<S>)
+                .get(get_handler::<S>)
+                .delete(delete_handler::<S>),
+        )
+        .with_state(sessions)
+}
+
+/// JSON-RPC error as an HTTP response
+pub(crate) fn rpc_error(status: StatusCode, code: i32, message: impl Into<String>) -> Response {
+    let response = JsonRpcResponse::error(serde_json::Value::Null, code, message.into());
+    (status, Json(response)).into_response()
+}
+
+/// Why a request's session cannot be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionError {
+    Missing,
+    NotFound,
+}
+
+impl IntoResponse for SessionError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Missing => rpc_error(
+                StatusCode::BAD_REQUEST,
+                INVALID_REQUEST,
+                "Missing Mcp-Session-Id header",
+            ),
+            Self::NotFound => rpc_error(StatusCode::NOT_FOUND, -32001, "Session not found"),
+        }
+    }
+}
+
+/// Session named by the request headers, if `caller` opened it
+fn find_session<S: StorageBackend + Send + Sync + 'static>(
+    sessions: &SessionStore<S>,
+    headers: &HeaderMap,
+    caller: &Permissions,
+) -> Result<Arc<Session>, SessionError> {
+    let id = headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or(SessionError::Missing)?;
+    sessions.get(id, caller).ok_or(SessionError::NotFound)
+}
+
+/// Whether the client takes a JSON body (otherwise it wants SSE)
+fn accepts_json(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    accept.contains("application/json")
+        || accept.contains("*/*")
+        || !accept.contains("text/event-stream")
+}
+
+async fn post_handler<S: StorageBackend + Send + Sync + 'static>(
+    State(sessions): State<Arc<SessionStore<S>>>,
+    permissions: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let payload = JsonRpcPayload::parse(&body);
+    // Set by the auth layer from the request's token
+    let permissions = permissions.map(|Extension(p)| p).unwrap_or_default();
+    if let JsonRpcPayload::Single(Err(response)) = payload {
+        return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+    }
+
+    // Clients send the negotiated version on every request after initialize
+    if let Some(version) = headers
+        .get(PROTOCOL_VERSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+    {
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+            return rpc_error(
+                StatusCode::BAD_REQUEST,
+                INVALID_REQUEST,
+                format!("Unsupported protocol version: {}", version),
+            );
+        }
+    }
+
+    let initialize = payload
+        .as_request()
+        .is_some_and(|request| request.method == "initialize");
+    let session = if initialize {
+        sessions.create(permissions.clone())
+    } else {
+        match find_session(&sessions, &headers, &permissions) {
+            Ok(session) => session,
+            Err(e) => return e.into_response(),
+        }
+    };
+
+    // Notifications, client responses and cancelled requests get no body
+    let Some(reply) = sessions
+        .server
+        .handle_payload_as(session.id(), &permissions, payload)
+        .await
+    else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+
+    let mut http_response = if accepts_json(&headers) {
+        Json(reply).into_response()
+    } else {
+        let data = serde_json::to_string(&reply).unwrap_or_default();
+        let event = session.record(data);
+        let stream = futures::stream::once(async move { Ok::<_, Infallible>(event.to_sse()) });
+        Sse::new(stream).into_response()
+    };
+
+    if let Ok(value) = HeaderValue::from_str(session.id()) {
+        http_response.headers_mut().insert(SESSION_HEADER, value);
+    }
+    http_response
+}
+
+async fn get_handler<S: StorageBackend + Send + Sync + 'static>(
+    State(sessions): State<Arc<SessionStore<S>>>,
+    permissions: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, SessionError> {
+    let permissions = permissions.map(|Extension(p)| p).unwrap_or_default();
+    let session = find_session(&sessions, &headers, &permissions)?;
+    let last_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+
+    // A fresh stream starts with new events; a resumed one replays missed ones
+    let (missed, mut rx) = match last_id {
+        Some(last_id) => session.resume(last_id),
+        None => (Vec::new(), session.tx.subscribe()),
+    };
+    let mut closed = session.closed.subscribe();
+    if !missed.is_empty() {
+        tracing::debug!(
+            "Replaying {} events to session {}",
+            missed.len(),
+            session.id()
+        );
+    }
+
+    let stream = async_stream::stream! {
+        // Holding the session keeps it from being swept while streaming
+        let _session = session;
+        for event in missed {
+            yield Ok(event.to_sse());
+        }
+        while !*closed.borrow_and_update() {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Ok(event) => yield Ok(event.to_sse()),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("Event stream lagged, {} events dropped", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                // Ends the stream once the session is deleted
+                _ = closed.changed() => {}
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn delete_handler<S: StorageBackend + Send + Sync + 'static>(
+    State(sessions): State<Arc<SessionStore<S>>>,
+    permissions: Option<Extension<Permissions>>,
+    headers: HeaderMap,
+) -> Response {
+    let permissions = permissions.map(|Extension(p)| p).unwrap_or_default();
+    match find_session(&sessions, &headers, &permissions) {
+        Ok(session) => {
+            sessions.remove(session.id());
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use parsnip_storage::MemoryStorage;
+    use tower::ServiceExt;
+
+    fn store() -> Arc<SessionStore<MemoryStorage>> {
+        let server = Arc::new(McpServer::new(Arc::new(MemoryStorage::new())));
+        Arc::new(SessionStore::new(server))
+    }
+
+    fn post(session: Option<&str>, body: &str) -> Request<Body> {
+        let mut request = Request::post("/mcp")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json, text/event-stream");
+        if let Some(session) = session {
+            request = request.header(SESSION_HEADER, session);
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn json_body(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_event_log_replay() {
+        let session = Session::new("s".to_string(), Permissions::new());
+        for i in 0..EVENT_BUFFER_SIZE + 2 {
+            session.publish(format!("event {}", i));
+        }
+
+        let (missed, _rx) = session.resume(EVENT_BUFFER_SIZE as u64);
+        let ids: Vec<u64> = missed.iter().map(|e| e.id).collect();
+        assert_eq!(
+            ids,
+            vec![EVENT_BUFFER_SIZE as u64 + 1, EVENT_BUFFER_SIZE as u64 + 2]
+        );
+
+        // The oldest events have been evicted
+        let (all, _rx) = session.resume(0);
+        assert_eq!(all.len(), EVENT_BUFFER_SIZE);
+        assert_eq!(all[0].id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_session_lifecycle() {
+        let sessions = store();
+        let router = streamable_router(sessions.clone());
+
+        // Requests other than initialize need a session
+        let response = router
+            .clone()
+            .oneshot(post(None, r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = router
+            .clone()
+            .oneshot(post(
+                None,
+                r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(sessions.len(), 1);
+
+        let response = router
+            .clone()
+            .oneshot(post(
+                Some(&session),
+                r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json_body(response).await["id"], 2);
+
+        let response = router
+            .clone()
+            .oneshot(post(
+                Some(&session),
+                r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let response = router
+            .clone()
+            .oneshot(post(
+                Some(&session),
+                r#"[{"jsonrpc":"2.0","id":3,"method":"ping"},{"jsonrpc":"2.0","method":"notifications/initialized"}]"#,
+            ))
+            .await
+            .unwrap();
+        let batch = json_body(response).await;
+        assert_eq!(batch.as_array().unwrap().len(), 1);
+        assert_eq!(batch[0]["id"], 3);
+
+        let delete = Request::delete("/mcp")
+            .header(SESSION_HEADER, &session)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(delete).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(sessions.is_empty());
+
+        let response = router
+            .oneshot(post(
+                Some(&session),
+                r#"{"jsonrpc":"2.0","id":3,"method":"ping"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_are_dropped() {
+        let server = Arc::new(McpServer::new(Arc::new(MemoryStorage::new())));
+        let sessions = SessionStore::new(server).with_idle_timeout(Duration::ZERO);
+
+        let first = sessions.create(Permissions::new());
+        let stream = first.resume(0);
+        let second = sessions.create(Permissions::new());
+        // The first session has an open stream, so it survives
+        assert_eq!(sessions.len(), 2);
+
+        drop(stream);
+        sessions.create(Permissions::new());
+        assert!(sessions.get(first.id(), &Permissions::new()).is_none());
+        assert!(sessions.get(second.id(), &Permissions::new()).is_none());
+        assert_eq!(sessions.len(), 1);
+    }
+}
@@ -0,0 +1,205 @@
+//! Per-session resource subscriptions
+//!
+//! Sessions subscribe to resource URIs with `resources/subscribe`. Each
+//! storage change is mapped to the resources it affects, and a session gets
+//! `notifications/resources/updated` for every subscribed URI among them,
+//! whatever format or page it subscribed with. Creating or deleting a
+//! project or entity changes `resources/list`, which every session hears
+//! about through `notifications/resources/list_changed`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use parsnip_storage::{ChangeKind, StorageChange};
+
+use crate::resources::{ResourceError, ResourceFormat, ResourceUri};
+use crate::transport::JsonRpcNotification;
+
+/// Subscribed URIs per session
+#[derive(Default)]
+pub struct Subscriptions {
+    sessions: RwLock<HashMap<String, HashSet<String>>>,
+}
+
+impl Subscriptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe a session to a resource URI
+    pub fn subscribe(&self, session: &str, uri: &str) -> Result<(), ResourceError> {
+        ResourceUri::parse(uri)?;
+        let mut sessions = self.sessions.write().unwrap_or_else(|e| e.into_inner());
+        sessions
+            .entry(session.to_string())
+            .or_default()
+            .insert(uri.to_string());
+        Ok(())
+    }
+
+    /// Unsubscribe a session from a resource URI
+    pub fn unsubscribe(&self, session: &str, uri: &str) {
+        let mut sessions = self.sessions.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(uris) = sessions.get_mut(session) {
+            uris.remove(uri);
+            if uris.is_empty() {
+                sessions.remove(session);
+            }
+        }
+    }
+
+    /// Drop every subscription of a closed session
+    pub fn end_session(&self, session: &str) {
+        let mut sessions = self.sessions.write().unwrap_or_else(|e| e.into_inner());
+        sessions.remove(session);
+    }
+
+    /// URIs a session is subscribed to, sorted
+    pub fn subscribed(&self, session: &str) -> Vec<String> {
+        let sessions = self.sessions.read().unwrap_or_else(|e| e.into_inner());
+        let mut uris: Vec<String> = sessions
+            .get(session)
+            .map(|uris| uris.iter().cloned().collect())
+            .unwrap_or_default();
+        uris.sort();
+        uris
+    }
+
+    /// Notifications a session should receive for a storage change
+    pub fn notifications_for(
+        &self,
+        session: &str,
+        change: &StorageChange,
+    ) -> Vec<JsonRpcNotification> {
+        let mut notifications = Vec::new();
+
+        let affected = affected_resources(change);
+        for uri in self.subscribed(session) {
+            let Ok(parsed) = ResourceUri::parse(&uri) else {
+                continue;
+            };
+            if affected.contains(&canonical(parsed)) {
+                notifications.push(JsonRpcNotification::new(
+                    "notifications/resources/updated",
+                    serde_json::json!({ "uri": uri }),
+                ));
+            }
+        }
+
+        if changes_resource_list(change) {
+            notifications.push(JsonRpcNotification::new(
+                "notifications/resources/list_changed",
+                serde_json::Value::Null,
+            ));
+        }
+
+        notifications
+    }
+}
+
+/// Strip format and page so equivalent URIs compare equal
+fn canonical(uri: ResourceUri) -> ResourceUri {
+    uri.with_format(ResourceFormat::Markdown).with_page(1)
+}
+
+fn affected_resources(change: &StorageChange) -> Vec<ResourceUri> {
+    match change {
+        StorageChange::Project { name, .. } => {
+            vec![ResourceUri::projects(), ResourceUri::graph(name)]
+        }
+        StorageChange::Entity { project, name, .. } => vec![
+            ResourceUri::projects(),
+            ResourceUri::graph(project),
+            ResourceUri::entity(project, name),
+        ],
+        StorageChange::Relation {
+            project,
+            from,
+            from_project,
+            to,
+            to_project,
+            ..
+        } => vec![
+            ResourceUri::projects(),
+            ResourceUri::graph(project),
+            ResourceUri::entity(from_project, from),
+            ResourceUri::entity(to_project, to),
+        ],
+    }
+}
+
+fn changes_resource_list(change: &StorageChange) -> bool {
+    match change {
+        StorageChange::Project { kind, .. } | StorageChange::Entity { kind, .. } => {
+            *kind != ChangeKind::Updated
+        }
+        StorageChange::Relation { .. } => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn methods(notifications: &[JsonRpcNotification]) -> Vec<(&str, Option<&str>)> {
+        notifications
+            .iter()
+            .map(|n| (n.method.as_str(), n.params["uri"].as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_updated_only_for_subscribed_resources() {
+        let subscriptions = Subscriptions::new();
+        subscriptions
+            .subscribe("a", "parsnip://work/entity/Alice?format=json")
+            .unwrap();
+        subscriptions
+            .subscribe("b", "parsnip://home/graph")
+            .unwrap();
+        assert!(subscriptions.subscribe("a", "https://example.com").is_err());
+
+        let change = StorageChange::Relation {
+            project: "work".to_string(),
+            from: "Alice".to_string(),
+            from_project: "work".to_string(),
+            to: "Bob".to_string(),
+            to_project: "home".to_string(),
+            relation_type: "knows".to_string(),
+            kind: ChangeKind::Created,
+        };
+
+        assert_eq!(
+            methods(&subscriptions.notifications_for("a", &change)),
+            vec![(
+                "notifications/resources/updated",
+                Some("parsnip://work/entity/Alice?format=json")
+            )]
+        );
+        // The relation is owned by work, so home's graph is unchanged
+        assert!(subscriptions.notifications_for("b", &change).is_empty());
+
+        subscriptions.end_session("a");
+        assert!(subscriptions.notifications_for("a", &change).is_empty());
+    }
+
+    #[test]
+    fn test_list_changed_on_create_and_delete() {
+        let subscriptions = Subscriptions::new();
+        let entity = |kind| StorageChange::Entity {
+            project: "work".to_string(),
+            name: "Alice".to_string(),
+            kind,
+        };
+
+        for kind in [ChangeKind::Created, ChangeKind::Deleted] {
+            assert_eq!(
+                methods(&subscriptions.notifications_for("a", &entity(kind))),
+                vec![("notifications/resources/list_changed", None)]
+            );
+        }
+        assert!(subscriptions
+            .notifications_for("a", &entity(ChangeKind::Updated))
+            .is_empty());
+    }
+}
@@ -0,0 +1,578 @@
+//! TLS for the HTTP transport
+//!
+//! Serves the router of [`crate::sse`] over rustls, with the server
+//! certificate read from PEM files or generated and kept on disk
+//! ([`ServerCert::SelfSigned`]). Clients may present certificates signed by
+//! a configured CA; those whose common name is listed in
+//! [`TlsOptions::clients`] are authenticated with that entry's
+//! [`Permissions`], as a bearer token would be.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::Request;
+use axum::Router;
+use hyper::body::Incoming;
+use hyper_util::rt::TokioIo;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+use x509_parser::extensions::GeneralName;
+
+use crate::permissions::Permissions;
+
+/// File name of a generated certificate, in [`ServerCert::SelfSigned`]'s directory
+pub const SELF_SIGNED_CERT: &str = "cert.pem";
+
+/// File name of a generated certificate's private key
+pub const SELF_SIGNED_KEY: &str = "key.pem";
+
+/// Time a client gets to finish the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors of setting up TLS
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("Failed to access {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid PEM in {path:?}: {message}")]
+    Pem { path: PathBuf, message: String },
+
+    #[error("No certificate found in {0:?}")]
+    NoCertificate(PathBuf),
+
+    #[error("Failed to generate certificate: {0}")]
+    Generate(#[from] rcgen::Error),
+
+    #[error("Invalid client CA: {0}")]
+    ClientCa(String),
+
+    #[error("Invalid TLS configuration: {0}")]
+    Config(#[from] rustls::Error),
+}
+
+/// Where the server's certificate comes from
+#[derive(Debug, Clone)]
+pub enum ServerCert {
+    /// A PEM certificate chain and its private key
+    Files { cert: PathBuf, key: PathBuf },
+    /// A self-signed certificate for these host names and addresses, kept in
+    /// a directory so clients can trust it once
+    SelfSigned { dir: PathBuf, names: Vec<String> },
+}
+
+/// A client certificate, by subject common name, and what it permits
+#[derive(Debug, Clone)]
+pub struct ClientCert {
+    pub name: String,
+    pub permissions: Permissions,
+}
+
+impl ClientCert {
+    /// A client permitted everything the server allows
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            permissions: Permissions::new(),
+        }
+    }
+
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+}
+
+/// TLS options of the HTTP transport
+#[derive(Debug, Clone)]
+pub struct TlsOptions {
+    pub cert: ServerCert,
+    /// PEM file of the CAs whose client certificates are accepted
+    pub client_ca: Option<PathBuf>,
+    /// Refuse connections without a client certificate
+    pub require_client_cert: bool,
+    /// Client certificates that authenticate requests
+    pub clients: Vec<ClientCert>,
+}
+
+impl TlsOptions {
+    pub fn new(cert: ServerCert) -> Self {
+        Self {
+            cert,
+            client_ca: None,
+            require_client_cert: false,
+            clients: Vec::new(),
+        }
+    }
+
+    pub fn with_client_ca(mut self, path: impl Into<PathBuf>) -> Self {
+        self.client_ca = Some(path.into());
+        self
+    }
+
+    pub fn with_require_client_cert(mut self, require: bool) -> Self {
+        self.require_client_cert = require;
+        self
+    }
+
+    pub fn with_client(mut self, client: ClientCert) -> Self {
+        self.clients.push(client);
+        self
+    }
+
+    /// Load or generate the certificates and build the rustls configuration
+    pub fn server_config(&self) -> Result<ServerConfig, TlsError> {
+        let (certs, key) = match &self.cert {
+            ServerCert::Files { cert, key } => (read_certs(cert)?, read_key(key)?),
+            ServerCert::SelfSigned { dir, names } => self_signed(dir, names)?,
+        };
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier = match &self.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(path)? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| TlsError::ClientCa(e.to_string()))?;
+                }
+                let builder =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone());
+                let builder = if self.require_client_cert {
+                    builder
+                } else {
+                    builder.allow_unauthenticated()
+                };
+                builder
+                    .build()
+                    .map_err(|e| TlsError::ClientCa(e.to_string()))?
+            }
+            None => WebPkiClientVerifier::no_client_auth(),
+        };
+
+        let mut config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    /// The configured client a certificate belongs to
+    fn client(&self, cert: &CertificateDer<'_>) -> Option<&ClientCert> {
+        let name = common_name(cert)?;
+        let client = self.clients.iter().find(|client| client.name == name);
+        if client.is_none() {
+            tracing::debug!("Client certificate '{}' is not configured", name);
+        }
+        client
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|source| TlsError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_slice_iter(&read(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::Pem {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate(path.to_path_buf()));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>, TlsError> {
+    PrivateKeyDer::from_pem_slice(&read(path)?).map_err(|e| TlsError::Pem {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+/// Subject common name of a certificate
+fn common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).ok()?;
+    let name = cert.subject().iter_common_name().next()?;
+    name.as_str().ok().map(str::to_string)
+}
+
+/// Whether a certificate is valid for all these names
+fn covers(cert: &CertificateDer<'_>, names: &[String]) -> bool {
+    let Ok((_, cert)) = x509_parser::parse_x509_certificate(cert) else {
+        return false;
+    };
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return false;
+    };
+    names.iter().all(|name| {
+        let ip = name.parse::<IpAddr>().ok();
+        san.value.general_names.iter().any(|general| match general {
+            GeneralName::DNSName(dns) => dns.eq_ignore_ascii_case(name),
+            GeneralName::IPAddress(bytes) => ip.is_some_and(|ip| match ip {
+                IpAddr::V4(v4) => *bytes == v4.octets(),
+                IpAddr::V6(v6) => *bytes == v6.octets(),
+            }),
+            _ => false,
+        })
+    })
+}
+
+/// SHA-256 fingerprint of a certificate, as colon-separated hex
+pub fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    ring::digest::digest(&ring::digest::SHA256, cert)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// The self-signed certificate in a directory, generated when missing or
+/// not valid for all of `names`
+fn self_signed(
+    dir: &Path,
+    names: &[String],
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), TlsError> {
+    let cert_path = dir.join(SELF_SIGNED_CERT);
+    let key_path = dir.join(SELF_SIGNED_KEY);
+
+    if cert_path.exists() && key_path.exists() {
+        let certs = read_certs(&cert_path)?;
+        if covers(&certs[0], names) {
+            tracing::info!(
+                "Using self-signed certificate {:?} (SHA-256 {})",
+                cert_path,
+                fingerprint(&certs[0])
+            );
+            return Ok((certs, read_key(&key_path)?));
+        }
+        tracing::warn!(
+            "Self-signed certificate {:?} does not cover {}, generating a new one",
+            cert_path,
+            names.join(", ")
+        );
+    }
+
+    let generated = rcgen::generate_simple_self_signed(names.to_vec())?;
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| TlsError::Io { path, source }
+    };
+    std::fs::create_dir_all(dir).map_err(io_error(dir))?;
+    std::fs::write(&cert_path, generated.cert.pem()).map_err(io_error(&cert_path))?;
+    write_private(&key_path, &generated.key_pair.serialize_pem()).map_err(io_error(&key_path))?;
+
+    let cert = generated.cert.der().clone();
+    tracing::info!(
+        "Generated self-signed certificate {:?} for {} (SHA-256 {})",
+        cert_path,
+        names.join(", "),
+        fingerprint(&cert)
+    );
+    let key = PrivateKeyDer::Pkcs8(generated.key_pair.serialize_der().into());
+    Ok((vec![cert], key))
+}
+
+/// Write a file only its owner can read
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?
+            .write_all(content.as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::write(path, content)
+    }
+}
+
+/// Serve a router over TLS until the listener fails
+///
+/// Requests on a connection whose client certificate is configured carry
+/// its [`ClientCert`] as an extension, which the auth middleware accepts in
+/// place of a bearer token.
+pub(crate) async fn serve(
+    listener: TcpListener,
+    router: Router,
+    options: TlsOptions,
+) -> Result<(), TlsError> {
+    let acceptor = TlsAcceptor::from(Arc::new(options.server_config()?));
+    let options = Arc::new(options);
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let router = router.clone();
+        let options = options.clone();
+
+        tokio::spawn(async move {
+            // A client that stalls the handshake would otherwise hold its
+            // socket and task forever
+            let stream =
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => stream,
+                    Ok(Err(e)) => {
+                        tracing::debug!("TLS handshake with {} failed: {}", peer, e);
+                        return;
+                    }
+                    Err(_) => {
+                        tracing::debug!("TLS handshake with {} timed out", peer);
+                        return;
+                    }
+                };
+            let client = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| options.client(cert))
+                .cloned();
+            if let Some(client) = &client {
+                tracing::debug!("{} authenticated as client {}", peer, client.name);
+            }
+
+            let service = hyper::service::service_fn(move |request: Request<Incoming>| {
+                let mut request = request.map(Body::new);
+                if let Some(client) = &client {
+                    request.extensions_mut().insert(client.clone());
+                }
+                router.clone().oneshot(request)
+            });
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await
+            {
+                tracing::debug!("Connection with {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sse::{create_router, HttpOptions};
+    use crate::McpServer;
+    use parsnip_storage::MemoryStorage;
+    use rcgen::{
+        BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+    };
+    use rustls::pki_types::ServerName;
+    use rustls::ClientConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_self_signed_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = names(&["localhost", "127.0.0.1"]);
+
+        let (first, _) = self_signed(dir.path(), &local).unwrap();
+        assert!(covers(&first[0], &local));
+        assert!(!covers(&first[0], &names(&["192.168.1.5"])));
+
+        // Reused while it covers the names, replaced once it does not
+        let (again, _) = self_signed(dir.path(), &names(&["localhost"])).unwrap();
+        assert_eq!(fingerprint(&again[0]), fingerprint(&first[0]));
+        let lan = names(&["localhost", "192.168.1.5"]);
+        let (replaced, _) = self_signed(dir.path(), &lan).unwrap();
+        assert_ne!(fingerprint(&replaced[0]), fingerprint(&first[0]));
+        assert!(covers(&replaced[0], &lan));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.path().join(SELF_SIGNED_KEY))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let options = TlsOptions::new(ServerCert::Files {
+            cert: "/nonexistent/cert.pem".into(),
+            key: "/nonexistent/key.pem".into(),
+        });
+        assert!(matches!(options.server_config(), Err(TlsError::Io { .. })));
+    }
+
+    /// Open a TLS connection, optionally with a client certificate
+    async fn connect(
+        addr: std::net::SocketAddr,
+        server_cert: CertificateDer<'static>,
+        client: Option<(CertificateDer<'static>, PrivateKeyDer<'static>)>,
+    ) -> tokio_rustls::client::TlsStream<tokio::net::TcpStream> {
+        let mut roots = RootCertStore::empty();
+        roots.add(server_cert).unwrap();
+        let builder =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots);
+        let config = match client {
+            Some((cert, key)) => builder.with_client_auth_cert(vec![cert], key).unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap()
+    }
+
+    /// Open a legacy session and POST `tools/list` into it over TLS,
+    /// optionally with a client certificate, and return the status line and
+    /// body of the first response that is not a success
+    async fn list_tools(
+        addr: std::net::SocketAddr,
+        server_cert: CertificateDer<'static>,
+        client: Option<(CertificateDer<'static>, PrivateKeyDer<'static>)>,
+    ) -> (String, String) {
+        let client_copy = client
+            .as_ref()
+            .map(|(cert, key)| (cert.clone(), key.clone_key()));
+        let mut events = connect(addr, server_cert.clone(), client_copy).await;
+        events
+            .write_all(b"GET /sse HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut head = Vec::new();
+        let session = loop {
+            let mut chunk = [0; 1024];
+            let read = events.read(&mut chunk).await.unwrap();
+            head.extend_from_slice(&chunk[..read]);
+            let text = String::from_utf8_lossy(&head).to_string();
+            if read == 0 || !text.starts_with("HTTP/1.1 200") {
+                let (head, body) = text.split_once("\r\n\r\n").unwrap_or((&text, ""));
+                return (head.lines().next().unwrap().to_string(), body.to_string());
+            }
+            if let Some(session) = text
+                .split("sessionId=")
+                .nth(1)
+                .and_then(|rest| rest.split_once('"'))
+            {
+                break session.0.to_string();
+            }
+        };
+
+        let mut stream = connect(addr, server_cert, client).await;
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#;
+        let request = format!(
+            "POST /message?sessionId={} HTTP/1.1\r\nHost: localhost\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            session,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A CA and a client certificate it signed for "laptop"
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "parsnip test ca");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let ca_path = dir.path().join("ca.pem");
+        std::fs::write(&ca_path, ca.pem()).unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        client_params
+            .distinguished_name
+            .push(DnType::CommonName, "laptop");
+        client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client_cert = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+        let client = || {
+            Some((
+                client_cert.der().clone(),
+                PrivateKeyDer::Pkcs8(client_key.serialize_der().into()),
+            ))
+        };
+
+        let tls = TlsOptions::new(ServerCert::SelfSigned {
+            dir: dir.path().join("server"),
+            names: names(&["localhost"]),
+        })
+        .with_client_ca(&ca_path)
+        .with_client(
+            ClientCert::new("laptop").with_permissions(Permissions::new().with_read_only(true)),
+        );
+        let (server_certs, _) =
+            self_signed(&dir.path().join("server"), &names(&["localhost"])).unwrap();
+        let server_cert = server_certs[0].clone();
+
+        // A bearer token is still required of clients without a certificate
+        let server = Arc::new(McpServer::new(Arc::new(MemoryStorage::new())));
+        let options = HttpOptions::new()
+            .with_legacy_sse(true)
+            .with_auth_token(Some("admin".to_string()))
+            .with_tls(tls.clone());
+        let router = create_router(server, options);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, router, tls));
+
+        let (status, _) = list_tools(addr, server_cert.clone(), None).await;
+        assert!(status.contains("401"), "{}", status);
+
+        let (status, body) = list_tools(addr, server_cert, client()).await;
+        assert!(status.contains("200"), "{}", status);
+        let reply: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(reply["result"]["tools"].as_array().unwrap().len(), 11);
+    }
+}
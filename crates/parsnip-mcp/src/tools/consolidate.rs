@@ -0,0 +1,290 @@
+//! Tool that consolidates the observations of an entity
+//!
+//! With a client that supports sampling, the entity's observations are sent
+//! in chunks to the client's model, which answers with the merged facts.
+//! Otherwise, or on request, exact and near-duplicate observations are folded
+//! deterministically. Either way the originals move to the entity's
+//! observation history.
+
+use async_trait::async_trait;
+use parsnip_core::Entity;
+use parsnip_storage::consolidate::{self, Merge, DEFAULT_CHUNK_SIZE, DEFAULT_SIMILARITY};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{Tool, ToolCallResponse, ToolContext, DEFAULT_PROJECT};
+use crate::sampling::{CreateMessageRequest, Sampler, SamplingError, SamplingMessage};
+
+const SYSTEM_PROMPT: &str = "You consolidate facts stored in a knowledge graph. \
+Merge the numbered observations about an entity into as few observations as \
+possible without losing information: combine duplicates and facts that say the \
+same thing, keep the most recent value when facts conflict, and keep every \
+distinct detail. Reply with the merged observations only, one per line, with \
+no numbering or commentary.";
+
+/// Most tokens a model may answer a chunk with
+const MAX_SAMPLING_TOKENS: u32 = 4096;
+
+/// How observations are merged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    #[default]
+    Auto,
+    Sampling,
+    Dedupe,
+}
+
+/// Arguments of `consolidate_observations`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsolidateArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    /// Entity whose observations to consolidate
+    pub name: String,
+    /// auto (default): the client's model if it supports sampling, else dedupe; sampling: the model only; dedupe: fold duplicates without a model
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Show the merges without changing the entity
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Share of words two observations need in common to be duplicates (default: 0.8)
+    #[schemars(range(min = 0.0, max = 1.0))]
+    pub similarity: Option<f64>,
+    /// Observations sent to the model per request (default: 50)
+    #[schemars(range(min = 2, max = 1000))]
+    pub chunk_size: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MergeResult {
+    replaced: Vec<String>,
+    merged: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConsolidateResult {
+    entity: String,
+    strategy: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    /// Why dedupe ran instead of the model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fallback_reason: Option<String>,
+    dry_run: bool,
+    observations_before: usize,
+    observations_after: usize,
+    merges: Vec<MergeResult>,
+    /// Merges not applied because the entity changed meanwhile
+    #[serde(skip_serializing_if = "is_zero")]
+    skipped: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Merges planned by one strategy
+struct Plan {
+    strategy: &'static str,
+    model: Option<String>,
+    merges: Vec<Merge>,
+}
+
+pub struct ConsolidateObservations;
+
+#[async_trait]
+impl Tool for ConsolidateObservations {
+    type Args = ConsolidateArgs;
+    const NAME: &'static str = "consolidate_observations";
+    const DESCRIPTION: &'static str = "Merge redundant observations of an entity. Uses the client's model through sampling when available, otherwise folds exact and near-duplicate observations. Replaced observations are kept in the entity's history. Use dryRun to preview.";
+
+    async fn call(&self, ctx: &ToolContext, args: ConsolidateArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.find_project(project_name).await {
+            Ok(Some(p)) => p,
+            Ok(None) => {
+                return ToolCallResponse::error(format!("Project '{}' not found", project_name))
+            }
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+        let entity = match ctx.storage().get_entity(&args.name, &project.id).await {
+            Ok(Some(e)) => e,
+            Ok(None) => return ToolCallResponse::error(format!("Entity not found: {}", args.name)),
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        let chunk_size = args.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+        let similarity = args.similarity.unwrap_or(DEFAULT_SIMILARITY);
+        let dedupe = || Plan {
+            strategy: "dedupe",
+            model: None,
+            merges: consolidate::dedupe(&entity, similarity),
+        };
+
+        let mut fallback_reason = None;
+        let plan = match (args.strategy, ctx.sampler()) {
+            (Strategy::Dedupe, _) => dedupe(),
+            (Strategy::Sampling, None) => {
+                return ToolCallResponse::error(format!(
+                    "{}; use strategy 'dedupe' instead",
+                    SamplingError::Unsupported
+                ))
+            }
+            (Strategy::Sampling, Some(sampler)) => {
+                match sample_merges(sampler, &entity, chunk_size).await {
+                    Ok(plan) => plan,
+                    Err(e) => return ToolCallResponse::error(format!("Sampling failed: {}", e)),
+                }
+            }
+            (Strategy::Auto, None) => {
+                fallback_reason = Some(SamplingError::Unsupported.to_string());
+                dedupe()
+            }
+            (Strategy::Auto, Some(sampler)) => {
+                match sample_merges(sampler, &entity, chunk_size).await {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        fallback_reason = Some(e.to_string());
+                        dedupe()
+                    }
+                }
+            }
+        };
+
+        let before = entity.observations.len();
+        let mut result = ConsolidateResult {
+            entity: entity.name.clone(),
+            strategy: plan.strategy,
+            model: plan.model.clone(),
+            fallback_reason,
+            dry_run: args.dry_run,
+            observations_before: before,
+            observations_after: before
+                .saturating_add_signed(plan.merges.iter().map(Merge::net_change).sum()),
+            merges: plan
+                .merges
+                .iter()
+                .map(|merge| MergeResult {
+                    replaced: merge.replaced.iter().map(|o| o.content.clone()).collect(),
+                    merged: merge.merged.clone(),
+                })
+                .collect(),
+            skipped: 0,
+        };
+        if args.dry_run || plan.merges.is_empty() {
+            return ToolCallResponse::json(&result);
+        }
+
+        let reason = match &plan.model {
+            Some(model) => format!("consolidated by {}", model),
+            None => "consolidated by dedupe".to_string(),
+        };
+        // Not held while the model answers; apply skips merges of
+        // observations that changed meanwhile
+        let _guard = ctx.lock_entity(&project.id, &entity.name).await;
+        match consolidate::apply(ctx.storage(), &project, &entity.name, &plan.merges, &reason).await
+        {
+            Ok(outcome) => {
+                result.observations_after = outcome.entity.observations.len();
+                result.skipped = outcome.skipped;
+                ToolCallResponse::json(&result)
+            }
+            Err(e) => ToolCallResponse::error(format!("Failed to consolidate: {}", e)),
+        }
+    }
+}
+
+/// Ask the model to merge each chunk of the entity's observations
+async fn sample_merges(
+    sampler: &dyn Sampler,
+    entity: &Entity,
+    chunk_size: usize,
+) -> Result<Plan, SamplingError> {
+    let mut plan = Plan {
+        strategy: "sampling",
+        model: None,
+        merges: Vec::new(),
+    };
+
+    for chunk in consolidate::chunks(entity, chunk_size) {
+        let mut prompt = format!(
+            "Entity: {} ({})\n\nObservations, oldest first:\n",
+            entity.name, entity.entity_type.0
+        );
+        for (i, observation) in chunk.iter().enumerate() {
+            prompt.push_str(&format!("{}. {}\n", i + 1, observation.content));
+        }
+        let size: usize = chunk.iter().map(|o| o.content.len()).sum();
+        let max_tokens = (size / 4 + 256).min(MAX_SAMPLING_TOKENS as usize) as u32;
+
+        let request = CreateMessageRequest::new(vec![SamplingMessage::user(prompt)], max_tokens)
+            .with_system_prompt(SYSTEM_PROMPT)
+            .with_temperature(0.0);
+        let response = sampler.create_message(request).await?;
+        let text = response
+            .text()
+            .ok_or_else(|| SamplingError::InvalidResponse("expected text content".to_string()))?;
+
+        let merged = parse_observations(text);
+        if merged.is_empty() || merged.len() > chunk.len() {
+            return Err(SamplingError::InvalidResponse(format!(
+                "{} observations merged into {}",
+                chunk.len(),
+                merged.len()
+            )));
+        }
+        plan.model.get_or_insert(response.model);
+        let unchanged =
+            merged.len() == chunk.len() && merged.iter().zip(&chunk).all(|(m, o)| *m == o.content);
+        if !unchanged {
+            plan.merges.push(Merge {
+                replaced: chunk,
+                merged,
+            });
+        }
+    }
+
+    Ok(plan)
+}
+
+/// One observation per non-empty line, without list markers
+fn parse_observations(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = line
+                .strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))
+                .or_else(|| line.strip_prefix("• "))
+                .unwrap_or(line);
+            let numbered = line
+                .split_once(". ")
+                .filter(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+            numbered.map_or(line, |(_, rest)| rest).trim().to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_observations() {
+        let text = "- Works at Acme Corp\n\n2. Likes tea\n* Lives in Oslo.\nVersion 2. Beta\n";
+        assert_eq!(
+            parse_observations(text),
+            vec![
+                "Works at Acme Corp",
+                "Likes tea",
+                "Lives in Oslo.",
+                "Version 2. Beta"
+            ]
+        );
+    }
+}
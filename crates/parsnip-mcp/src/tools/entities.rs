@@ -0,0 +1,442 @@
+//! Tools that create, change and delete entities
+
+use async_trait::async_trait;
+use parsnip_core::{
+    validate_batch_entities, validate_entity_name, validate_observation, validate_project_name,
+    validate_tag, Entity,
+};
+use parsnip_storage::ops::{self, EntityUpdate};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{EntityResult, Tool, ToolCallResponse, ToolContext, DEFAULT_PROJECT};
+
+/// Load an entity to update, or the error response to return
+async fn load_entity(
+    ctx: &ToolContext,
+    name: &str,
+    project: &parsnip_core::ProjectId,
+) -> Result<Entity, ToolCallResponse> {
+    match ctx.storage().get_entity(name, project).await {
+        Ok(Some(e)) => Ok(e),
+        Ok(None) => Err(ToolCallResponse::error(format!(
+            "Entity not found: {}",
+            name
+        ))),
+        Err(e) => Err(ToolCallResponse::error(format!("Storage error: {}", e))),
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// create_entities
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `create_entities`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateEntitiesArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    pub entities: Vec<EntityInput>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityInput {
+    /// Unique entity name
+    pub name: String,
+    /// Entity type (person, technology, project, company, concept, event, preference)
+    pub entity_type: String,
+    /// Factual statements about the entity
+    pub observations: Vec<String>,
+    /// Optional tags for categorization
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+pub struct CreateEntities;
+
+#[async_trait]
+impl Tool for CreateEntities {
+    type Args = CreateEntitiesArgs;
+    const NAME: &'static str = "create_entities";
+    const DESCRIPTION: &'static str = "Create new entities with observations and optional tags. Use a single call for multiple entities.";
+
+    async fn call(&self, ctx: &ToolContext, args: CreateEntitiesArgs) -> ToolCallResponse {
+        // Validate batch size
+        if let Err(e) = validate_batch_entities(args.entities.len()) {
+            return ToolCallResponse::error(e.to_string());
+        }
+
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+
+        // Validate project name
+        if let Err(e) = validate_project_name(project_name) {
+            return ToolCallResponse::error(e.to_string());
+        }
+
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut created = 0;
+        for input in args.entities {
+            // Validate entity name
+            if let Err(e) = validate_entity_name(&input.name) {
+                return ToolCallResponse::error(e.to_string());
+            }
+
+            // Validate observations
+            for obs in &input.observations {
+                if let Err(e) = validate_observation(obs) {
+                    return ToolCallResponse::error(e.to_string());
+                }
+            }
+
+            // Validate tags
+            for tag in &input.tags {
+                if let Err(e) = validate_tag(tag) {
+                    return ToolCallResponse::error(e.to_string());
+                }
+            }
+
+            let mut entity =
+                Entity::new(project.id.clone(), &input.name, input.entity_type.as_str());
+            for obs in input.observations {
+                entity.add_observation(&obs);
+            }
+            for tag in input.tags {
+                entity.add_tag(&tag);
+            }
+            let _guard = ctx.lock_entity(&project.id, &entity.name).await;
+            if let Err(e) = ctx.storage().save_entity(&entity).await {
+                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
+            }
+            created += 1;
+        }
+
+        ToolCallResponse::text(format!("✅ SUCCESS: Created {} entities", created))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// add_observations / delete_observations
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `add_observations`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AddObservationsArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    pub observations: Vec<ObservationInput>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservationInput {
+    /// Exact name of existing entity
+    pub entity_name: String,
+    /// New factual statements to add
+    pub observations: Vec<String>,
+}
+
+pub struct AddObservations;
+
+#[async_trait]
+impl Tool for AddObservations {
+    type Args = AddObservationsArgs;
+    const NAME: &'static str = "add_observations";
+    const DESCRIPTION: &'static str = "Add new observations to existing entities.";
+
+    async fn call(&self, ctx: &ToolContext, args: AddObservationsArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut added = 0;
+        for input in args.observations {
+            let _guard = ctx.lock_entity(&project.id, &input.entity_name).await;
+            let mut updated = match load_entity(ctx, &input.entity_name, &project.id).await {
+                Ok(e) => e,
+                Err(response) => return response,
+            };
+            for obs in &input.observations {
+                updated.add_observation(obs);
+            }
+            if let Err(e) = ctx.storage().save_entity(&updated).await {
+                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
+            }
+            added += input.observations.len();
+        }
+
+        ToolCallResponse::text(format!("✅ SUCCESS: Added {} observations", added))
+    }
+}
+
+/// Arguments of `delete_observations`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteObservationsArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    pub deletions: Vec<ObservationDeletion>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservationDeletion {
+    pub entity_name: String,
+    /// Exact observation strings to remove
+    pub observations: Vec<String>,
+}
+
+pub struct DeleteObservations;
+
+#[async_trait]
+impl Tool for DeleteObservations {
+    type Args = DeleteObservationsArgs;
+    const NAME: &'static str = "delete_observations";
+    const DESCRIPTION: &'static str =
+        "Delete specific observations from entities while preserving the entity.";
+
+    async fn call(&self, ctx: &ToolContext, args: DeleteObservationsArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut deleted = 0;
+        for del in args.deletions {
+            let _guard = ctx.lock_entity(&project.id, &del.entity_name).await;
+            let mut updated = match load_entity(ctx, &del.entity_name, &project.id).await {
+                Ok(e) => e,
+                Err(response) => return response,
+            };
+            updated
+                .observations
+                .retain(|o| !del.observations.contains(&o.content));
+            deleted += del.observations.len();
+
+            if let Err(e) = ctx.storage().save_entity(&updated).await {
+                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
+            }
+        }
+
+        ToolCallResponse::text(format!("✅ SUCCESS: Deleted {} observations", deleted))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// delete_entities
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `delete_entities`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteEntitiesArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    /// Entity names to delete
+    pub entity_names: Vec<String>,
+}
+
+pub struct DeleteEntities;
+
+#[async_trait]
+impl Tool for DeleteEntities {
+    type Args = DeleteEntitiesArgs;
+    const NAME: &'static str = "delete_entities";
+    const DESCRIPTION: &'static str = "Permanently delete entities and all their relationships.";
+
+    async fn call(&self, ctx: &ToolContext, args: DeleteEntitiesArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut deleted = 0;
+        for name in args.entity_names {
+            let _guard = ctx.lock_entity(&project.id, &name).await;
+            if let Err(e) = ctx
+                .storage()
+                .delete_relations_for_entity(&name, &project.id)
+                .await
+            {
+                tracing::warn!("Failed to delete relations for {}: {}", name, e);
+            }
+            if let Err(e) = ctx.storage().delete_entity(&name, &project.id).await {
+                return ToolCallResponse::error(format!("Failed to delete entity: {}", e));
+            }
+            deleted += 1;
+        }
+
+        ToolCallResponse::text(format!("✅ SUCCESS: Deleted {} entities", deleted))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// add_tags / remove_tags
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `add_tags` and `remove_tags`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TagsArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    pub updates: Vec<TagUpdate>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TagUpdate {
+    pub entity_name: String,
+    pub tags: Vec<String>,
+}
+
+pub struct AddTags;
+
+#[async_trait]
+impl Tool for AddTags {
+    type Args = TagsArgs;
+    const NAME: &'static str = "add_tags";
+    const DESCRIPTION: &'static str =
+        "Add categorical tags to existing entities for filtering and organization.";
+
+    async fn call(&self, ctx: &ToolContext, args: TagsArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut added = 0;
+        for update in args.updates {
+            let _guard = ctx.lock_entity(&project.id, &update.entity_name).await;
+            let mut updated = match load_entity(ctx, &update.entity_name, &project.id).await {
+                Ok(e) => e,
+                Err(response) => return response,
+            };
+            for tag in &update.tags {
+                updated.add_tag(tag);
+            }
+            added += update.tags.len();
+
+            if let Err(e) = ctx.storage().save_entity(&updated).await {
+                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
+            }
+        }
+
+        ToolCallResponse::text(format!("✅ SUCCESS: Added {} tags", added))
+    }
+}
+
+pub struct RemoveTags;
+
+#[async_trait]
+impl Tool for RemoveTags {
+    type Args = TagsArgs;
+    const NAME: &'static str = "remove_tags";
+    const DESCRIPTION: &'static str = "Remove specific tags from entities.";
+
+    async fn call(&self, ctx: &ToolContext, args: TagsArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut removed = 0;
+        for update in args.updates {
+            let _guard = ctx.lock_entity(&project.id, &update.entity_name).await;
+            let mut updated = match load_entity(ctx, &update.entity_name, &project.id).await {
+                Ok(e) => e,
+                Err(response) => return response,
+            };
+            updated.tags.retain(|t| !update.tags.contains(t));
+            removed += update.tags.len();
+
+            if let Err(e) = ctx.storage().save_entity(&updated).await {
+                return ToolCallResponse::error(format!("Failed to save entity: {}", e));
+            }
+        }
+
+        ToolCallResponse::text(format!("✅ SUCCESS: Removed {} tags", removed))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// update_entity
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `update_entity`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateEntityArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    /// Entity to update
+    pub name: String,
+    /// New entity type
+    pub entity_type: Option<String>,
+    /// Observations to add
+    #[serde(default)]
+    pub add_observations: Vec<String>,
+    /// Tags to add
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    /// Tags to remove
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateEntityResult {
+    entity: EntityResult,
+    changes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    missing_tags: Vec<String>,
+}
+
+pub struct UpdateEntity;
+
+#[async_trait]
+impl Tool for UpdateEntity {
+    type Args = UpdateEntityArgs;
+    const NAME: &'static str = "update_entity";
+    const DESCRIPTION: &'static str = "Update an existing entity in one call: change its type, add observations, and add or remove tags. Returns the updated entity and the changes made.";
+
+    async fn call(&self, ctx: &ToolContext, args: UpdateEntityArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.find_project(project_name).await {
+            Ok(Some(p)) => p,
+            Ok(None) => {
+                return ToolCallResponse::error(format!("Project '{}' not found", project_name))
+            }
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let update = EntityUpdate {
+            entity_type: args.entity_type,
+            add_observations: args.add_observations,
+            add_tags: args.add_tags,
+            remove_tags: args.remove_tags,
+        };
+        let _guard = ctx.lock_entity(&project.id, &args.name).await;
+        match ops::update_entity(ctx.storage(), &project, &args.name, &update).await {
+            Ok(outcome) => ToolCallResponse::json(&UpdateEntityResult {
+                entity: EntityResult::from(&outcome.entity),
+                changes: outcome.changes.iter().map(ToString::to_string).collect(),
+                missing_tags: outcome.missing_tags,
+            }),
+            Err(e) => ToolCallResponse::error(e.to_string()),
+        }
+    }
+}
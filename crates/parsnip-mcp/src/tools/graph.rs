@@ -0,0 +1,702 @@
+//! Tools that read and traverse the graph
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use parsnip_core::{
+    validate_traversal_depth, Direction, PaginationInfo, PathNode, Project, ProjectId,
+    TraversalEngine, TraversalQuery, TraversalResult, MAX_TRAVERSAL_DEPTH,
+};
+use parsnip_storage::ops::{self, PathRequest, SubgraphRequest};
+use parsnip_storage::StorageResult;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::output::{paginate, Page};
+use super::{
+    EntityResult, OutputArgs, PageArgs, RelationResult, Tool, ToolCallResponse, ToolContext,
+    DEFAULT_PROJECT,
+};
+use crate::resources::encode_key_cursor;
+
+// ─────────────────────────────────────────────────────────────────────────────
+// read_graph / open_nodes
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `read_graph`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadGraphArgs {
+    /// Project identifier (default: 'default')
+    pub project_id: Option<String>,
+    #[serde(flatten)]
+    pub page: PageArgs,
+    #[serde(flatten)]
+    pub output: OutputArgs,
+}
+
+/// One page of entities with their relations
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphPage {
+    entities: Vec<EntityResult>,
+    relations: Vec<RelationResult>,
+    pagination: PaginationInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+}
+
+impl GraphPage {
+    fn new(page: Page<(EntityResult, Vec<RelationResult>)>) -> Self {
+        let mut entities = Vec::with_capacity(page.items.len());
+        let mut relations = Vec::new();
+        for (entity, entity_relations) in page.items {
+            entities.push(entity);
+            relations.extend(entity_relations);
+        }
+        Self {
+            entities,
+            relations,
+            pagination: page.pagination,
+            next_cursor: page.next_cursor,
+            truncated: page.truncated,
+        }
+    }
+}
+
+pub struct ReadGraph;
+
+#[async_trait]
+impl Tool for ReadGraph {
+    type Args = ReadGraphArgs;
+    const NAME: &'static str = "read_graph";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "Retrieve the knowledge graph of a project one page of entities at a time, ordered by name, with the relations going out of them. Pass nextCursor back as cursor for the next page; use fields, maxObservations and maxBytes/maxTokens to keep responses small.";
+
+    async fn call(&self, ctx: &ToolContext, args: ReadGraphArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let after = match args.page.after_key() {
+            Ok(Some((project, _))) if project != project_name => {
+                return ToolCallResponse::error(format!(
+                    "Cursor is for project '{}', not '{}'",
+                    project, project_name
+                ))
+            }
+            Ok(after) => after.map(|(_, name)| name),
+            Err(e) => return ToolCallResponse::error(e),
+        };
+
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut entities = match ctx.storage().get_all_entities(&project.id).await {
+            Ok(e) => e,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        let mut relations = match ctx.storage().get_all_relations(&project.id).await {
+            Ok(r) => r,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        // Pages resume after the last name returned, so writes between calls
+        // neither skip nor repeat entities; each relation is listed with the
+        // last entity whose name does not sort after its source, so every
+        // relation appears on exactly one page
+        entities.sort_by(|a, b| a.name.cmp(&b.name));
+        relations.sort_by(|a, b| a.from_name.cmp(&b.from_name));
+        let offset = match &after {
+            Some(name) => entities.partition_point(|e| e.name <= *name),
+            None => args.page.pagination().offset(),
+        };
+        let relations_of = |i: usize| {
+            let after = match (i.checked_sub(1), &after) {
+                (_, Some(name)) if i == offset => Some(name),
+                (Some(prev), _) => Some(&entities[prev].name),
+                (None, _) => None,
+            };
+            let start = match after {
+                Some(name) => relations.partition_point(|r| r.from_name <= *name),
+                None => 0,
+            };
+            let end = match entities.get(i + 1) {
+                Some(_) => relations.partition_point(|r| r.from_name <= entities[i].name),
+                None => relations.len(),
+            };
+            relations[start..end]
+                .iter()
+                .map(RelationResult::from)
+                .collect::<Vec<_>>()
+        };
+
+        let mut budget = args.output.budget();
+        let page = paginate(
+            entities.len(),
+            offset,
+            &args.page.pagination(),
+            &mut budget,
+            |i| (args.output.entity(&entities[i]), relations_of(i)),
+        );
+        let mut page = GraphPage::new(page);
+        if page.next_cursor.is_some() {
+            let last = &entities[offset + page.entities.len() - 1];
+            page.next_cursor = Some(encode_key_cursor(&project.name, &last.name));
+        }
+
+        ToolCallResponse::json(&page)
+    }
+}
+
+/// Arguments of `open_nodes`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenNodesArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    /// Exact entity names to retrieve
+    pub names: Vec<String>,
+    #[serde(flatten)]
+    pub page: PageArgs,
+    #[serde(flatten)]
+    pub output: OutputArgs,
+}
+
+pub struct OpenNodes;
+
+#[async_trait]
+impl Tool for OpenNodes {
+    type Args = OpenNodesArgs;
+    const NAME: &'static str = "open_nodes";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "Retrieve specific entities by exact names along with their relationships. Pages and budgets like read_graph; names that don't exist are skipped.";
+
+    async fn call(&self, ctx: &ToolContext, args: OpenNodesArgs) -> ToolCallResponse {
+        let offset = match args.page.offset() {
+            Ok(o) => o,
+            Err(e) => return ToolCallResponse::error(e),
+        };
+
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut nodes = Vec::new();
+        for name in &args.names {
+            if let Ok(Some(entity)) = ctx.storage().get_entity(name, &project.id).await {
+                let relations = ctx
+                    .storage()
+                    .get_relations_for_entity(name, &project.id)
+                    .await
+                    .unwrap_or_default();
+                nodes.push((entity, relations));
+            }
+        }
+
+        let mut budget = args.output.budget();
+        let page = paginate(
+            nodes.len(),
+            offset,
+            &args.page.pagination(),
+            &mut budget,
+            |i| {
+                let (entity, relations) = &nodes[i];
+                (
+                    args.output.entity(entity),
+                    relations.iter().map(RelationResult::from).collect(),
+                )
+            },
+        );
+
+        ToolCallResponse::json(&GraphPage::new(page))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// traverse_graph
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `traverse_graph`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TraverseArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    /// Starting entity name
+    pub start: String,
+    /// Target entity name for path finding (optional)
+    pub target: Option<String>,
+    /// Project containing the target entity (default: same as projectId; implies crossProject)
+    pub target_project_id: Option<String>,
+    /// Maximum traversal depth
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    /// Traversal direction
+    #[serde(default)]
+    pub direction: DirectionArg,
+    /// Filter traversal to these entity types only
+    pub entity_type_filter: Option<Vec<String>>,
+    /// Filter traversal to these relation types only
+    pub relation_type_filter: Option<Vec<String>>,
+    /// Use weighted shortest path (Dijkstra) when finding paths
+    #[serde(default)]
+    pub use_weights: bool,
+    /// Follow relations into other projects
+    #[serde(default)]
+    pub cross_project: bool,
+    #[serde(flatten)]
+    pub output: OutputArgs,
+}
+
+fn default_max_depth() -> u32 {
+    10
+}
+
+/// Which relations to follow from each entity
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DirectionArg {
+    Outgoing,
+    Incoming,
+    #[default]
+    Both,
+}
+
+impl From<DirectionArg> for Direction {
+    fn from(direction: DirectionArg) -> Self {
+        match direction {
+            DirectionArg::Outgoing => Direction::Outgoing,
+            DirectionArg::Incoming => Direction::Incoming,
+            DirectionArg::Both => Direction::Both,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraversalResultJson {
+    paths: Vec<PathJson>,
+    visited_entities: Vec<PathNodeJson>,
+    entities: Vec<EntityResult>,
+    relations: Vec<RelationResult>,
+    stats: TraversalStatsJson,
+    /// The budget left out some entities or relations
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PathJson {
+    nodes: Vec<PathNodeJson>,
+    edges: Vec<PathEdgeJson>,
+    total_weight: f64,
+    length: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PathNodeJson {
+    name: String,
+    project: String,
+    entity_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PathEdgeJson {
+    from: String,
+    from_project: String,
+    to: String,
+    to_project: String,
+    relation_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight: Option<f64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraversalStatsJson {
+    nodes_visited: usize,
+    edges_traversed: usize,
+    max_depth_reached: u32,
+}
+
+pub struct TraverseGraph;
+
+#[async_trait]
+impl Tool for TraverseGraph {
+    type Args = TraverseArgs;
+    const NAME: &'static str = "traverse_graph";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "Traverse the knowledge graph from a starting entity. Supports path finding between entities, filtered traversal by entity/relation types, weighted shortest path (Dijkstra), and following cross-project relations. Nodes are returned with their project.";
+
+    async fn call(&self, ctx: &ToolContext, args: TraverseArgs) -> ToolCallResponse {
+        let storage = ctx.storage();
+
+        // Validate traversal depth (cap at MAX_TRAVERSAL_DEPTH)
+        let depth = args.max_depth.min(MAX_TRAVERSAL_DEPTH);
+        if let Err(e) = validate_traversal_depth(args.max_depth) {
+            tracing::warn!(
+                "Depth {} exceeds max, capping at {}: {}",
+                args.max_depth,
+                MAX_TRAVERSAL_DEPTH,
+                e
+            );
+        }
+
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let target_project = match args.target_project_id.as_deref() {
+            Some(name) if name != project.name => match ctx.find_project(name).await {
+                Ok(Some(p)) => p,
+                Ok(None) => {
+                    return ToolCallResponse::error(format!("Project '{}' not found", name))
+                }
+                Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+            },
+            _ => project.clone(),
+        };
+
+        // A target in another project only makes sense when crossing boundaries
+        let cross_project = args.cross_project || target_project.id != project.id;
+
+        // Load entities and relations
+        let loaded = if cross_project {
+            match ctx.all_entities().await {
+                Ok(e) => ctx.all_relations().await.map(|r| (e, r)),
+                Err(e) => Err(e),
+            }
+        } else {
+            match storage.get_all_entities(&project.id).await {
+                Ok(e) => storage.get_all_relations(&project.id).await.map(|r| (e, r)),
+                Err(e) => Err(e),
+            }
+        };
+        let (entities, relations) = match loaded {
+            Ok(data) => data,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        let exists = |name: &str, project: &Project| {
+            entities
+                .iter()
+                .any(|e| e.name == name && e.project_id == project.id)
+        };
+
+        // Check if start entity exists
+        if !exists(&args.start, &project) {
+            return ToolCallResponse::error(format!("Start entity '{}' not found", args.start));
+        }
+
+        // Check if target entity exists (if specified)
+        if let Some(ref target) = args.target {
+            if !exists(target, &target_project) {
+                return ToolCallResponse::error(format!("Target entity '{}' not found", target));
+            }
+        }
+
+        // Build traversal query
+        let mut query = TraversalQuery::new(&args.start)
+            .in_project(project.id.clone())
+            .with_depth(depth)
+            .with_direction(args.direction.into());
+
+        if let Some(target) = args.target {
+            query = query
+                .find_path_to(&target)
+                .with_target_project(target_project.id.clone());
+        }
+
+        if cross_project {
+            query = query.cross_project();
+        }
+
+        if args.use_weights {
+            query = query.weighted();
+        }
+
+        if let Some(ref etypes) = args.entity_type_filter {
+            query = query.filter_entity_types(etypes.clone());
+        }
+
+        if let Some(ref rtypes) = args.relation_type_filter {
+            query = query.filter_relation_types(rtypes.clone());
+        }
+
+        tracing::info!(
+            "Traversing from '{}' (target: {:?}, depth: {}, direction: {:?}, cross_project: {})",
+            args.start,
+            query.target,
+            query.max_depth,
+            query.direction,
+            query.cross_project
+        );
+
+        // Execute traversal
+        let result = TraversalEngine::execute(&query, &entities, &relations);
+
+        // Resolve project names for qualified node output
+        let project_names = match project_names(ctx, &project, cross_project).await {
+            Ok(names) => names,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        ToolCallResponse::json(&traversal_json(&result, &project_names, &args.output))
+    }
+}
+
+/// Names of the projects a traversal may have visited
+async fn project_names(
+    ctx: &ToolContext,
+    project: &Project,
+    cross_project: bool,
+) -> StorageResult<HashMap<ProjectId, String>> {
+    if cross_project {
+        Ok(ctx
+            .visible_projects()
+            .await?
+            .into_iter()
+            .map(|p| (p.id, p.name))
+            .collect())
+    } else {
+        Ok(HashMap::from([(project.id.clone(), project.name.clone())]))
+    }
+}
+
+/// Convert a traversal result, qualifying nodes with their project name
+///
+/// Paths and visited nodes are always complete; entities and then relations
+/// are added while they fit the budget.
+fn traversal_json(
+    result: &TraversalResult,
+    project_names: &HashMap<ProjectId, String>,
+    output: &OutputArgs,
+) -> TraversalResultJson {
+    let project_name_of = |id: &ProjectId| project_names.get(id).cloned().unwrap_or_default();
+    let node_json = |node: &PathNode| PathNodeJson {
+        name: node.name.clone(),
+        project: project_name_of(&node.project_id),
+        entity_id: node.entity_id.to_string(),
+    };
+
+    let mut response = TraversalResultJson {
+        paths: result
+            .paths
+            .iter()
+            .map(|p| PathJson {
+                nodes: p.nodes.iter().map(node_json).collect(),
+                edges: p
+                    .edges
+                    .iter()
+                    .map(|e| PathEdgeJson {
+                        from: e.from.clone(),
+                        from_project: project_name_of(&e.from_project_id),
+                        to: e.to.clone(),
+                        to_project: project_name_of(&e.to_project_id),
+                        relation_type: e.relation_type.clone(),
+                        weight: e.weight,
+                    })
+                    .collect(),
+                total_weight: p.total_weight,
+                length: p.length,
+            })
+            .collect(),
+        visited_entities: result.visited_entities.iter().map(node_json).collect(),
+        entities: Vec::new(),
+        relations: Vec::new(),
+        stats: TraversalStatsJson {
+            nodes_visited: result.stats.nodes_visited,
+            edges_traversed: result.stats.edges_traversed,
+            max_depth_reached: result.stats.max_depth_reached,
+        },
+        truncated: false,
+    };
+
+    let mut budget = output.budget();
+    budget.admit(&response);
+    for entity in &result.entities {
+        let entity = output.entity(entity);
+        if !budget.admit(&entity) {
+            response.truncated = true;
+            return response;
+        }
+        response.entities.push(entity);
+    }
+    for relation in &result.relations {
+        let relation = RelationResult::from(relation);
+        if !budget.admit(&relation) {
+            response.truncated = true;
+            return response;
+        }
+        response.relations.push(relation);
+    }
+    response
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// find_path
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `find_path`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FindPathArgs {
+    /// Project of the source entity (default: 'default')
+    pub project_id: Option<String>,
+    /// Source entity name
+    pub from: String,
+    /// Target entity name
+    pub to: String,
+    /// Project containing the target entity (default: same as projectId; implies crossProject)
+    pub to_project_id: Option<String>,
+    /// Shortest total weight (Dijkstra) instead of fewest hops
+    #[serde(default)]
+    pub weighted: bool,
+    /// Only follow these relation types
+    #[serde(default)]
+    pub relation_types: Vec<String>,
+    /// Only pass through these entity types
+    #[serde(default)]
+    pub entity_types: Vec<String>,
+    /// Maximum path length
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    /// Follow relations into other projects
+    #[serde(default)]
+    pub cross_project: bool,
+    #[serde(flatten)]
+    pub output: OutputArgs,
+}
+
+pub struct FindPath;
+
+#[async_trait]
+impl Tool for FindPath {
+    type Args = FindPathArgs;
+    const NAME: &'static str = "find_path";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "Find the shortest path between two entities, by hops or by total relation weight (Dijkstra), optionally filtered by relation and entity types and crossing into other projects.";
+
+    async fn call(&self, ctx: &ToolContext, args: FindPathArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.find_project(project_name).await {
+            Ok(Some(p)) => p,
+            Ok(None) => {
+                return ToolCallResponse::error(format!("Project '{}' not found", project_name))
+            }
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let request = PathRequest {
+            to_project: args.to_project_id,
+            weighted: args.weighted,
+            relation_types: args.relation_types,
+            entity_types: args.entity_types,
+            max_depth: args.max_depth.min(MAX_TRAVERSAL_DEPTH),
+            cross_project: args.cross_project,
+            ..PathRequest::new(args.from, args.to)
+        };
+        let scope = ctx.permissions().scope();
+        let result = match ops::find_path(ctx.storage(), &scope, &project, &request).await {
+            Ok(result) => result,
+            Err(e) => return ToolCallResponse::error(e.to_string()),
+        };
+
+        let cross_project = request.cross_project || request.to_project.is_some();
+        let project_names = match project_names(ctx, &project, cross_project).await {
+            Ok(names) => names,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        ToolCallResponse::json(&traversal_json(&result, &project_names, &args.output))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// export_subgraph
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `export_subgraph`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSubgraphArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    /// Entities the subgraph grows from
+    pub entities: Vec<String>,
+    /// Hops from the starting entities to include
+    #[serde(default = "default_export_depth")]
+    pub depth: u32,
+    /// Which relations to follow
+    #[serde(default)]
+    pub direction: DirectionArg,
+    /// Only follow these relation types
+    #[serde(default)]
+    pub relation_types: Vec<String>,
+    /// Refuse exports larger than this many bytes
+    pub max_bytes: Option<usize>,
+    /// Like maxBytes, in tokens (estimated at 4 bytes per token)
+    pub max_tokens: Option<usize>,
+}
+
+fn default_export_depth() -> u32 {
+    1
+}
+
+pub struct ExportSubgraph;
+
+#[async_trait]
+impl Tool for ExportSubgraph {
+    type Args = ExportSubgraphArgs;
+    const NAME: &'static str = "export_subgraph";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "Export the neighborhood of some entities (entities within the given number of hops and the relations among them) in the format read by `parsnip import`.";
+
+    async fn call(&self, ctx: &ToolContext, args: ExportSubgraphArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.find_project(project_name).await {
+            Ok(Some(p)) => p,
+            Ok(None) => {
+                return ToolCallResponse::error(format!("Project '{}' not found", project_name))
+            }
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let request = SubgraphRequest {
+            direction: args.direction.into(),
+            relation_types: args.relation_types,
+            ..SubgraphRequest::new(args.entities, args.depth.min(MAX_TRAVERSAL_DEPTH))
+        };
+        let data = match ops::export_subgraph(ctx.storage(), &project, &request).await {
+            Ok(data) => data,
+            Err(e) => return ToolCallResponse::error(e.to_string()),
+        };
+
+        // An export is all or nothing, so a budget can only refuse it
+        let output = OutputArgs {
+            max_bytes: args.max_bytes,
+            max_tokens: args.max_tokens,
+            ..Default::default()
+        };
+        if let Some(limit) = output.budget().limit() {
+            let size = serde_json::to_vec(&data).map(|v| v.len()).unwrap_or(0);
+            if size > limit {
+                return ToolCallResponse::error(format!(
+                    "Export is {} bytes, over the budget of {}. Lower depth, or page through read_graph.",
+                    size, limit
+                ));
+            }
+        }
+        ToolCallResponse::json(&data)
+    }
+}
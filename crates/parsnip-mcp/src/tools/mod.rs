@@ -0,0 +1,1055 @@
+//! MCP tools and the registry that serves them
+//!
+//! Each tool is a type implementing [`Tool`]. Its argument struct derives
+//! [`JsonSchema`], which gives both the `inputSchema` in `tools/list` and the
+//! schema that arguments are validated against before the tool runs:
+//!
+//! ```ignore
+//! #[derive(Deserialize, JsonSchema)]
+//! #[serde(rename_all = "camelCase")]
+//! struct EchoArgs {
+//!     /// Text to send back
+//!     text: String,
+//! }
+//!
+//! struct Echo;
+//!
+//! #[async_trait]
+//! impl Tool for Echo {
+//!     type Args = EchoArgs;
+//!     const NAME: &'static str = "echo";
+//!     const DESCRIPTION: &'static str = "Send the text back.";
+//!
+//!     async fn call(&self, _ctx: &ToolContext, args: EchoArgs) -> ToolCallResponse {
+//!         ToolCallResponse::text(args.text)
+//!     }
+//! }
+//!
+//! let server = McpServer::new(storage).with_tool(Echo);
+//! ```
+
+mod consolidate;
+mod entities;
+mod graph;
+mod output;
+mod projects;
+mod relations;
+mod search;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use parsnip_core::{Entity, Project, ProjectId, Relation};
+use parsnip_storage::{
+    Actor, AuditEntry, AuditedStorage, Scope, StorageBackend, StorageError, StorageResult,
+};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::OwnedMutexGuard;
+
+use crate::permissions::{Permissions, PERMISSION_DENIED};
+use crate::sampling::Sampler;
+use crate::schema::{input_schema, validate, ValidationError};
+
+pub use consolidate::{ConsolidateObservations, Strategy};
+pub use entities::{
+    AddObservations, AddTags, CreateEntities, DeleteEntities, DeleteObservations, RemoveTags,
+    UpdateEntity,
+};
+pub use graph::{DirectionArg, ExportSubgraph, FindPath, OpenNodes, ReadGraph, TraverseGraph};
+pub use output::{Fields, OutputArgs, PageArgs, BYTES_PER_TOKEN};
+pub use projects::{CreateProject, DeleteProject, GetProjectStats, ListProjects};
+pub use relations::{CreateRelations, DeleteRelations, ListRelations};
+pub use search::{GetContext, SearchKnowledge, SearchModeArg, SuggestRelations};
+
+pub(crate) use projects::project_summaries;
+
+/// Project used when a call does not name one
+pub const DEFAULT_PROJECT: &str = "default";
+
+/// A tool clients can call through `tools/call`
+#[async_trait]
+pub trait Tool: Send + Sync + 'static {
+    /// Arguments, deserialized from the call after passing schema validation
+    type Args: DeserializeOwned + JsonSchema + Send;
+
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+    /// Whether the tool only reads, so read-only clients may call it
+    const READ_ONLY: bool = false;
+
+    async fn call(&self, ctx: &ToolContext, args: Self::Args) -> ToolCallResponse;
+}
+
+/// Locks on single entities, shared by the calls a server handles
+///
+/// A call that reads an entity, changes it and writes it back holds the
+/// entity's lock throughout, so no other call writes it in between. Locks
+/// nobody holds or waits for are dropped.
+#[derive(Debug, Default)]
+pub struct EntityLocks {
+    locks: Mutex<HashMap<(ProjectId, String), EntityLock>>,
+}
+
+type EntityLock = Arc<tokio::sync::Mutex<()>>;
+
+impl EntityLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the lock on an entity, held until the guard is dropped
+    pub async fn lock(&self, project_id: &ProjectId, name: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks
+                .entry((project_id.clone(), name.to_string()))
+                .or_default()
+                .clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+/// What a tool call can reach: the storage, the calling session and what
+/// the caller is permitted to do
+pub struct ToolContext {
+    storage: Arc<dyn StorageBackend>,
+    session: String,
+    permissions: Permissions,
+    sampler: Option<Arc<dyn Sampler>>,
+    /// Notes what the call writes, when it is audited
+    audit: Option<Arc<AuditedStorage<dyn StorageBackend>>>,
+    locks: Arc<EntityLocks>,
+}
+
+impl ToolContext {
+    pub fn new(storage: Arc<dyn StorageBackend>, session: impl Into<String>) -> Self {
+        Self {
+            storage,
+            session: session.into(),
+            permissions: Permissions::new(),
+            sampler: None,
+            audit: None,
+            locks: Arc::new(EntityLocks::new()),
+        }
+    }
+
+    /// Share entity locks with the other calls of a server
+    pub fn with_entity_locks(mut self, locks: Arc<EntityLocks>) -> Self {
+        self.locks = locks;
+        self
+    }
+
+    /// Attribute the call's writes to `actor` in the audit log
+    ///
+    /// Nothing is logged until [`Self::record_audit`].
+    pub fn with_audit(mut self, actor: Actor) -> Self {
+        let audited = Arc::new(AuditedStorage::new(self.storage.clone(), actor));
+        self.storage = audited.clone();
+        self.audit = Some(audited);
+        self
+    }
+
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Let tools ask the client's model for completions
+    pub fn with_sampler(mut self, sampler: Arc<dyn Sampler>) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    pub fn storage(&self) -> &dyn StorageBackend {
+        self.storage.as_ref()
+    }
+
+    /// Transport session the call came from
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    /// The client's model, if the client supports sampling
+    pub fn sampler(&self) -> Option<&dyn Sampler> {
+        self.sampler.as_deref()
+    }
+
+    /// Keep other calls from writing an entity until the guard is dropped
+    pub async fn lock_entity(&self, project_id: &ProjectId, name: &str) -> OwnedMutexGuard<()> {
+        self.locks.lock(project_id, name).await
+    }
+
+    /// Look up a project by name, creating it on first use
+    ///
+    /// Read-only callers get an empty project that is not saved.
+    pub async fn get_or_create_project(&self, name: &str) -> Result<Project, ProjectError> {
+        if let Some(project) = self.find_project(name).await? {
+            return Ok(project);
+        }
+        let project = Project::new(name);
+        if !self.permissions.read_only {
+            self.storage.save_project(&project).await?;
+        }
+        Ok(project)
+    }
+
+    /// Look up an existing project the caller may see
+    pub async fn find_project(&self, name: &str) -> Result<Option<Project>, ProjectError> {
+        if !self.permissions.allows_project(name) {
+            return Err(ProjectError::NotPermitted(name.to_string()));
+        }
+        Ok(self.storage.get_project(name).await?)
+    }
+
+    /// Projects the caller may read
+    pub fn scope(&self) -> Scope {
+        self.permissions.scope()
+    }
+
+    /// Every project the caller may see
+    pub async fn visible_projects(&self) -> StorageResult<Vec<Project>> {
+        self.scope().visible_projects(self.storage()).await
+    }
+
+    /// Entities of every project the caller may see
+    pub async fn all_entities(&self) -> StorageResult<Vec<Entity>> {
+        self.scope().entities(self.storage()).await
+    }
+
+    /// Relations whose endpoints are both in projects the caller may see
+    pub async fn all_relations(&self) -> StorageResult<Vec<Relation>> {
+        self.scope().relations(self.storage()).await
+    }
+
+    /// Log what was written since the last record as one operation
+    ///
+    /// Does nothing for unaudited contexts or when nothing was written.
+    pub async fn record_audit(
+        &self,
+        operation: &str,
+        arguments: serde_json::Value,
+    ) -> StorageResult<Option<AuditEntry>> {
+        match &self.audit {
+            Some(audit) => audit.record(operation, arguments).await,
+            None => Ok(None),
+        }
+    }
+}
+
+/// Errors resolving the project a tool works on
+#[derive(Debug, Error)]
+pub enum ProjectError {
+    #[error("Access to project '{0}' is not permitted")]
+    NotPermitted(String),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+impl ProjectError {
+    /// JSON-RPC error code for this error
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::NotPermitted(_) => PERMISSION_DENIED,
+            Self::Storage(_) => -32603,
+        }
+    }
+}
+
+/// Entry in `tools/list`
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolDefinition {
+    pub name: &'static str,
+    pub description: &'static str,
+    #[serde(rename = "inputSchema")]
+    pub input_schema: serde_json::Value,
+    pub annotations: ToolAnnotations,
+}
+
+/// Hints about a tool's behavior for clients
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    /// The tool does not change the graph
+    pub read_only_hint: bool,
+}
+
+/// MCP tool call request
+#[derive(Debug, Deserialize)]
+pub struct ToolCallRequest {
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// MCP tool call response
+#[derive(Debug, Serialize)]
+pub struct ToolCallResponse {
+    pub content: Vec<ContentBlock>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "isError")]
+    pub is_error: Option<bool>,
+}
+
+/// Content block for responses
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum ContentBlock {
+    #[serde(rename = "text")]
+    Text { text: String },
+}
+
+impl ToolCallResponse {
+    pub fn text(content: impl Into<String>) -> Self {
+        Self {
+            content: vec![ContentBlock::Text {
+                text: content.into(),
+            }],
+            is_error: None,
+        }
+    }
+
+    pub fn json<T: Serialize>(data: &T) -> Self {
+        match serde_json::to_string_pretty(data) {
+            Ok(json) => Self::text(json),
+            Err(e) => Self::error(format!("JSON serialization error: {}", e)),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            content: vec![ContentBlock::Text {
+                text: message.into(),
+            }],
+            is_error: Some(true),
+        }
+    }
+}
+
+/// Errors calling a tool, mapped to JSON-RPC error codes
+#[derive(Debug, Error)]
+pub enum ToolError {
+    #[error("Unknown tool: {0}")]
+    UnknownTool(String),
+
+    #[error("Tool not permitted: {0}")]
+    NotPermitted(String),
+
+    #[error("Invalid arguments for {tool}: {}", join_errors(errors))]
+    InvalidArguments {
+        tool: String,
+        errors: Vec<ValidationError>,
+    },
+}
+
+impl ToolError {
+    /// JSON-RPC error code for this error
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::UnknownTool(_) | Self::InvalidArguments { .. } => -32602,
+            Self::NotPermitted(_) => PERMISSION_DENIED,
+        }
+    }
+
+    /// Structured `error.data` for clients that want to point at fields
+    pub fn data(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::UnknownTool(_) | Self::NotPermitted(_) => None,
+            Self::InvalidArguments { tool, errors } => Some(serde_json::json!({
+                "tool": tool,
+                "errors": errors,
+            })),
+        }
+    }
+}
+
+fn join_errors(errors: &[ValidationError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Object-safe view of a [`Tool`] taking raw JSON arguments
+#[async_trait]
+trait ErasedTool: Send + Sync {
+    async fn call_json(
+        &self,
+        ctx: &ToolContext,
+        args: serde_json::Value,
+    ) -> Result<ToolCallResponse, serde_json::Error>;
+}
+
+#[async_trait]
+impl<T: Tool> ErasedTool for T {
+    async fn call_json(
+        &self,
+        ctx: &ToolContext,
+        args: serde_json::Value,
+    ) -> Result<ToolCallResponse, serde_json::Error> {
+        let args = serde_json::from_value(args)?;
+        Ok(self.call(ctx, args).await)
+    }
+}
+
+struct RegisteredTool {
+    definition: ToolDefinition,
+    tool: Arc<dyn ErasedTool>,
+}
+
+/// Tools served by an MCP server, in `tools/list` order
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<RegisteredTool>,
+}
+
+impl ToolRegistry {
+    /// Registry without any tools
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with every built-in Parsnip tool
+    pub fn builtin() -> Self {
+        Self::new()
+            .with_tool(SearchKnowledge)
+            .with_tool(CreateEntities)
+            .with_tool(AddObservations)
+            .with_tool(CreateRelations)
+            .with_tool(DeleteEntities)
+            .with_tool(DeleteObservations)
+            .with_tool(DeleteRelations)
+            .with_tool(ReadGraph)
+            .with_tool(OpenNodes)
+            .with_tool(AddTags)
+            .with_tool(RemoveTags)
+            .with_tool(TraverseGraph)
+            .with_tool(ListProjects)
+            .with_tool(GetContext)
+            .with_tool(SuggestRelations)
+            .with_tool(UpdateEntity)
+            .with_tool(FindPath)
+            .with_tool(ListRelations)
+            .with_tool(GetProjectStats)
+            .with_tool(CreateProject)
+            .with_tool(DeleteProject)
+            .with_tool(ExportSubgraph)
+            .with_tool(ConsolidateObservations)
+    }
+
+    /// Add a tool, replacing any registered tool with the same name
+    pub fn register<T: Tool>(&mut self, tool: T) {
+        let registered = RegisteredTool {
+            definition: ToolDefinition {
+                name: T::NAME,
+                description: T::DESCRIPTION,
+                input_schema: input_schema::<T::Args>(),
+                annotations: ToolAnnotations {
+                    read_only_hint: T::READ_ONLY,
+                },
+            },
+            tool: Arc::new(tool),
+        };
+        match self.tools.iter_mut().find(|t| t.definition.name == T::NAME) {
+            Some(existing) => *existing = registered,
+            None => self.tools.push(registered),
+        }
+    }
+
+    pub fn with_tool<T: Tool>(mut self, tool: T) -> Self {
+        self.register(tool);
+        self
+    }
+
+    /// Definitions for `tools/list`
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|t| t.definition.clone()).collect()
+    }
+
+    /// Definitions of the tools a caller may use
+    pub fn definitions_for(&self, permissions: &Permissions) -> Vec<ToolDefinition> {
+        self.tools
+            .iter()
+            .map(|t| &t.definition)
+            .filter(|d| permissions.allows_tool(d.name, d.annotations.read_only_hint))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ToolDefinition> {
+        self.tools
+            .iter()
+            .map(|t| &t.definition)
+            .find(|d| d.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Check the caller may use the tool, validate arguments against its
+    /// schema, then run it
+    ///
+    /// Missing arguments are treated as an empty object.
+    pub async fn call(
+        &self,
+        ctx: &ToolContext,
+        name: &str,
+        args: serde_json::Value,
+    ) -> Result<ToolCallResponse, ToolError> {
+        let registered = self
+            .tools
+            .iter()
+            .find(|t| t.definition.name == name)
+            .ok_or_else(|| ToolError::UnknownTool(name.to_string()))?;
+
+        let definition = &registered.definition;
+        if !ctx
+            .permissions()
+            .allows_tool(definition.name, definition.annotations.read_only_hint)
+        {
+            return Err(ToolError::NotPermitted(name.to_string()));
+        }
+
+        let args = if args.is_null() {
+            serde_json::json!({})
+        } else {
+            args
+        };
+
+        let errors = validate(&registered.definition.input_schema, &args);
+        if !errors.is_empty() {
+            return Err(ToolError::InvalidArguments {
+                tool: name.to_string(),
+                errors,
+            });
+        }
+
+        // The schema can't express everything serde checks
+        registered
+            .tool
+            .call_json(ctx, args)
+            .await
+            .map_err(|e| ToolError::InvalidArguments {
+                tool: name.to_string(),
+                errors: vec![ValidationError::new("", e.to_string())],
+            })
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Results shared by tools and resources
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EntityResult {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entity_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    observations: Option<Vec<String>>,
+    /// Observations left out by `maxObservations`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    more_observations: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<Vec<String>>,
+}
+
+impl From<&Entity> for EntityResult {
+    fn from(e: &Entity) -> Self {
+        Self {
+            name: e.name.clone(),
+            entity_type: Some(e.entity_type.0.clone()),
+            observations: Some(e.observations.iter().map(|o| o.content.clone()).collect()),
+            more_observations: None,
+            tags: Some(e.tags.clone()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RelationResult {
+    from: String,
+    to: String,
+    relation_type: String,
+}
+
+impl From<&Relation> for RelationResult {
+    fn from(r: &Relation) -> Self {
+        Self {
+            from: r.from_name.clone(),
+            to: r.to_name.clone(),
+            relation_type: r.relation_type.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProjectResult {
+    pub(crate) name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) description: Option<String>,
+    pub(crate) entity_count: usize,
+    pub(crate) relation_count: usize,
+    pub(crate) created_at: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsnip_storage::MemoryStorage;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct EchoArgs {
+        /// Text to send back
+        text: String,
+        #[schemars(range(min = 1, max = 3))]
+        #[serde(default = "one")]
+        times: usize,
+    }
+
+    fn one() -> usize {
+        1
+    }
+
+    struct Echo;
+
+    #[async_trait]
+    impl Tool for Echo {
+        type Args = EchoArgs;
+        const NAME: &'static str = "echo";
+        const DESCRIPTION: &'static str = "Send the text back.";
+
+        async fn call(&self, ctx: &ToolContext, args: EchoArgs) -> ToolCallResponse {
+            ToolCallResponse::text(format!(
+                "{}: {}",
+                ctx.session(),
+                args.text.repeat(args.times)
+            ))
+        }
+    }
+
+    fn context() -> ToolContext {
+        ToolContext::new(Arc::new(MemoryStorage::new()), "s1")
+    }
+
+    #[test]
+    fn test_builtin_definitions() {
+        let registry = ToolRegistry::builtin();
+        assert_eq!(registry.len(), 23);
+
+        let definitions = serde_json::to_value(registry.definitions()).unwrap();
+        for definition in definitions.as_array().unwrap() {
+            assert_eq!(
+                definition["inputSchema"]["type"], "object",
+                "{}",
+                definition
+            );
+            assert!(definition["inputSchema"]["properties"].is_object());
+        }
+
+        let create = registry.get("create_entities").unwrap();
+        assert_eq!(
+            create.input_schema["required"],
+            serde_json::json!(["entities"])
+        );
+        assert_eq!(
+            create.input_schema["properties"]["entities"]["items"]["required"],
+            serde_json::json!(["entityType", "name", "observations"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registry_validates_and_dispatches() {
+        let registry = ToolRegistry::new().with_tool(Echo);
+        assert_eq!(registry.definitions()[0].name, "echo");
+
+        let response = registry
+            .call(
+                &context(),
+                "echo",
+                serde_json::json!({"text": "hi", "times": 2}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(response).unwrap()["content"][0]["text"],
+            "s1: hihi"
+        );
+
+        let err = registry
+            .call(&context(), "echo", serde_json::json!({"times": 5}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), -32602);
+        assert_eq!(
+            err.data().unwrap(),
+            serde_json::json!({
+                "tool": "echo",
+                "errors": [
+                    {"path": "/text", "message": "missing required property"},
+                    {"path": "/times", "message": "must be at most 3"}
+                ]
+            })
+        );
+
+        assert!(matches!(
+            registry
+                .call(&context(), "nope", serde_json::Value::Null)
+                .await,
+            Err(ToolError::UnknownTool(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_permissions_hide_and_deny_tools() {
+        let registry = ToolRegistry::builtin().with_tool(Echo);
+        let read_only = Permissions::new()
+            .with_read_only(true)
+            .with_denied_tools(["suggest_relations"]);
+
+        let names: Vec<&str> = registry
+            .definitions_for(&read_only)
+            .iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "search_knowledge",
+                "read_graph",
+                "open_nodes",
+                "traverse_graph",
+                "list_projects",
+                "get_context",
+                "find_path",
+                "list_relations",
+                "project_stats",
+                "export_subgraph",
+            ]
+        );
+
+        let ctx = context().with_permissions(read_only);
+        let err = registry
+            .call(&ctx, "echo", serde_json::json!({"text": "hi"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::NotPermitted(_)));
+        assert_eq!(err.code(), PERMISSION_DENIED);
+    }
+
+    #[tokio::test]
+    async fn test_context_scopes_projects() {
+        let storage = Arc::new(MemoryStorage::new());
+        for name in ["work", "secret"] {
+            let project = Project::new(name);
+            storage.save_project(&project).await.unwrap();
+            storage
+                .save_entity(&Entity::new(project.id.clone(), "Alice", "person"))
+                .await
+                .unwrap();
+        }
+
+        let ctx = ToolContext::new(storage.clone(), "s1").with_permissions(
+            Permissions::new()
+                .with_read_only(true)
+                .with_projects(["work"]),
+        );
+        assert_eq!(ctx.visible_projects().await.unwrap().len(), 1);
+        assert_eq!(ctx.all_entities().await.unwrap().len(), 1);
+        assert!(matches!(
+            ctx.get_or_create_project("secret").await,
+            Err(ProjectError::NotPermitted(_))
+        ));
+
+        // Read-only callers don't create projects
+        let ctx = ToolContext::new(storage.clone(), "s1")
+            .with_permissions(Permissions::new().with_read_only(true));
+        let new = ctx.get_or_create_project("new").await.unwrap();
+        assert_eq!(new.name, "new");
+        assert!(ctx.find_project("new").await.unwrap().is_none());
+        assert_eq!(storage.get_all_projects().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cli_parity_tools() {
+        let registry = ToolRegistry::builtin();
+        let ctx = context();
+        let call = |name: &'static str, args: serde_json::Value| {
+            let registry = &registry;
+            let ctx = &ctx;
+            async move {
+                let response = registry.call(ctx, name, args).await.unwrap();
+                let response = serde_json::to_value(response).unwrap();
+                let text = response["content"][0]["text"].as_str().unwrap().to_string();
+                (response["isError"] == true, text)
+            }
+        };
+
+        let (failed, _) = call("create_project", serde_json::json!({"projectId": "work"})).await;
+        assert!(!failed);
+        let (failed, text) = call("create_project", serde_json::json!({"projectId": "work"})).await;
+        assert!(failed);
+        assert_eq!(text, "Project 'work' already exists");
+
+        call(
+            "create_entities",
+            serde_json::json!({"projectId": "work", "entities": [
+                {"name": "Alice", "entityType": "person", "observations": []},
+                {"name": "Bob", "entityType": "person", "observations": []},
+            ]}),
+        )
+        .await;
+        call(
+            "create_relations",
+            serde_json::json!({"projectId": "work", "relations": [
+                {"from": "Alice", "to": "Bob", "relationType": "knows"},
+            ]}),
+        )
+        .await;
+
+        let (_, text) = call(
+            "update_entity",
+            serde_json::json!({"projectId": "work", "name": "Alice", "entityType": "engineer"}),
+        )
+        .await;
+        let updated: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(updated["entity"]["entityType"], "engineer");
+        assert_eq!(
+            updated["changes"],
+            serde_json::json!(["set type: engineer"])
+        );
+
+        let (_, text) = call(
+            "find_path",
+            serde_json::json!({"projectId": "work", "from": "Bob", "to": "Alice"}),
+        )
+        .await;
+        let path: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(path["paths"][0]["length"], 1);
+
+        let (_, text) = call("project_stats", serde_json::json!({"projectId": "work"})).await;
+        let stats: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(
+            stats["entityTypes"],
+            serde_json::json!({"engineer": 1, "person": 1})
+        );
+
+        let (_, text) = call(
+            "export_subgraph",
+            serde_json::json!({"projectId": "work", "entities": ["Bob"]}),
+        )
+        .await;
+        let export: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(
+            export["projects"][0]["entities"].as_array().unwrap().len(),
+            2
+        );
+        assert_eq!(
+            export["projects"][0]["relations"][0]["relationType"],
+            "knows"
+        );
+
+        let (_, text) = call("delete_project", serde_json::json!({"projectId": "work"})).await;
+        assert!(text.contains("confirm: true"));
+        let (failed, _) = call(
+            "delete_project",
+            serde_json::json!({"projectId": "work", "confirm": true}),
+        )
+        .await;
+        assert!(!failed);
+        let (failed, text) = call("project_stats", serde_json::json!({"projectId": "work"})).await;
+        assert!(failed);
+        assert_eq!(text, "Project 'work' not found");
+    }
+
+    #[tokio::test]
+    async fn test_read_graph_pages_with_cursor() {
+        let storage = Arc::new(MemoryStorage::new());
+        let project = Project::new("big");
+        storage.save_project(&project).await.unwrap();
+        for i in 0..25 {
+            let mut entity = Entity::new(project.id.clone(), format!("e{:02}", i), "node");
+            entity.add_observation("first");
+            entity.add_observation("second");
+            storage.save_entity(&entity).await.unwrap();
+        }
+        for (from, to) in [
+            ("e00", "e01"),
+            ("e12", "e13"),
+            ("e24", "e00"),
+            ("ghost", "e05"),
+        ] {
+            storage
+                .save_relation(&Relation::from_names(project.id.clone(), from, to, "next"))
+                .await
+                .unwrap();
+        }
+
+        let registry = ToolRegistry::builtin();
+        let ctx = ToolContext::new(storage, "s1");
+        let mut args = serde_json::json!({
+            "projectId": "big",
+            "pageSize": 10,
+            "fields": "types",
+        });
+        let mut entities = Vec::new();
+        let mut relations = 0;
+        let mut pages = 0;
+        loop {
+            let response = registry
+                .call(&ctx, "read_graph", args.clone())
+                .await
+                .unwrap();
+            let text = serde_json::to_value(response).unwrap()["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            let page: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(page["pagination"]["totalCount"], 25);
+            for entity in page["entities"].as_array().unwrap() {
+                assert!(entity.get("observations").is_none());
+                entities.push(entity["name"].as_str().unwrap().to_string());
+            }
+            relations += page["relations"].as_array().unwrap().len();
+            pages += 1;
+            match page["nextCursor"].as_str() {
+                Some(cursor) => args["cursor"] = cursor.into(),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+        assert_eq!(entities.len(), 25);
+        assert!(entities.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(relations, 4);
+
+        // A byte budget ends the page early but keeps the cursor going
+        let response = registry
+            .call(
+                &ctx,
+                "read_graph",
+                serde_json::json!({"projectId": "big", "maxBytes": 300, "maxObservations": 1}),
+            )
+            .await
+            .unwrap();
+        let text = serde_json::to_value(response).unwrap()["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let page: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(page["truncated"], true);
+        assert_eq!(page["entities"][0]["moreObservations"], 1);
+        assert_eq!(
+            page["entities"][0]["observations"],
+            serde_json::json!(["second"])
+        );
+        let shown = page["entities"].as_array().unwrap().len();
+        assert!((1..10).contains(&shown));
+        assert_eq!(page["nextCursor"], format!("after:big/e{:02}", shown - 1));
+    }
+
+    #[tokio::test]
+    async fn test_read_graph_cursor_survives_writes() {
+        let storage = Arc::new(MemoryStorage::new());
+        let project = Project::new("big");
+        storage.save_project(&project).await.unwrap();
+        for i in 0..20 {
+            let entity = Entity::new(project.id.clone(), format!("e{:02}", i), "node");
+            storage.save_entity(&entity).await.unwrap();
+        }
+        storage
+            .save_relation(&Relation::from_names(
+                project.id.clone(),
+                "e10",
+                "e00",
+                "next",
+            ))
+            .await
+            .unwrap();
+
+        let registry = ToolRegistry::builtin();
+        let ctx = ToolContext::new(storage.clone(), "s1");
+        let read = |args: serde_json::Value| {
+            let registry = &registry;
+            let ctx = &ctx;
+            async move {
+                let response = registry.call(ctx, "read_graph", args).await.unwrap();
+                let text = serde_json::to_value(response).unwrap()["content"][0]["text"]
+                    .as_str()
+                    .unwrap()
+                    .to_string();
+                serde_json::from_str::<serde_json::Value>(&text).unwrap()
+            }
+        };
+        let names = |page: &serde_json::Value| {
+            page["entities"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let first = read(serde_json::json!({"projectId": "big", "pageSize": 10})).await;
+        assert_eq!(names(&first).last().unwrap(), "e09");
+        let cursor = first["nextCursor"].as_str().unwrap().to_string();
+
+        // Deleting and adding entities before the cursor moves no later ones
+        // onto or off the next page
+        storage.delete_entity("e03", &project.id).await.unwrap();
+        storage.delete_entity("e09", &project.id).await.unwrap();
+        storage
+            .save_entity(&Entity::new(project.id.clone(), "e05a", "node"))
+            .await
+            .unwrap();
+        let second =
+            read(serde_json::json!({"projectId": "big", "pageSize": 10, "cursor": cursor})).await;
+        let expected: Vec<String> = (10..20).map(|i| format!("e{:02}", i)).collect();
+        assert_eq!(names(&second), expected);
+        assert_eq!(second["relations"].as_array().unwrap().len(), 1);
+        assert!(second.get("nextCursor").is_none());
+
+        // A cursor only resumes the project it came from
+        let response = registry
+            .call(
+                &ctx,
+                "read_graph",
+                serde_json::json!({"projectId": "other", "cursor": cursor}),
+            )
+            .await
+            .unwrap();
+        let response = serde_json::to_value(response).unwrap();
+        assert_eq!(response["isError"], true);
+        assert!(response["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("Cursor is for project 'big'"));
+    }
+
+    #[test]
+    fn test_register_replaces_same_name() {
+        let mut registry = ToolRegistry::builtin();
+        registry.register(Echo);
+        registry.register(Echo);
+        assert_eq!(registry.len(), 24);
+        assert_eq!(registry.definitions().last().unwrap().name, "echo");
+    }
+}
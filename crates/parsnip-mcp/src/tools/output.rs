@@ -0,0 +1,283 @@
+//! Response shaping for tools that return entities
+//!
+//! Large projects don't fit in one tool response. Tools returning entities
+//! flatten [`OutputArgs`] into their arguments: which entity fields to
+//! include, how many observations per entity, and a byte or token budget.
+//! Listing tools also flatten [`PageArgs`] and page through results with an
+//! opaque cursor over [`Pagination`] offsets, reporting [`PaginationInfo`].
+
+use parsnip_core::{Entity, Pagination, PaginationInfo};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::EntityResult;
+use crate::resources::{decode_cursor, decode_key_cursor, encode_cursor};
+
+/// Bytes per token when a budget is given in tokens
+pub const BYTES_PER_TOKEN: usize = 4;
+
+/// Entity fields included in a response: names only, names with types and
+/// tags, or everything including observations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Fields {
+    Names,
+    Types,
+    #[default]
+    Full,
+}
+
+/// How entities are rendered into a response
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputArgs {
+    /// Entity fields to include: names, types (with tags) or full (default)
+    #[serde(default)]
+    pub fields: Fields,
+    /// Include only the most recent observations of each entity; the rest are counted in moreObservations
+    pub max_observations: Option<usize>,
+    /// Stop adding entities once the response would exceed this many bytes
+    pub max_bytes: Option<usize>,
+    /// Like maxBytes, in tokens (estimated at 4 bytes per token)
+    pub max_tokens: Option<usize>,
+}
+
+impl OutputArgs {
+    /// Render an entity with the requested fields and observation cap
+    pub(crate) fn entity(&self, entity: &Entity) -> EntityResult {
+        let mut result = EntityResult::from(entity);
+        match self.fields {
+            Fields::Names => {
+                result.entity_type = None;
+                result.observations = None;
+                result.tags = None;
+            }
+            Fields::Types => result.observations = None,
+            Fields::Full => {}
+        }
+        if let (Some(observations), Some(max)) = (&mut result.observations, self.max_observations) {
+            if observations.len() > max {
+                let more = observations.len() - max;
+                observations.drain(..more);
+                result.more_observations = Some(more);
+            }
+        }
+        result
+    }
+
+    /// The smaller of the byte and token budgets
+    pub(crate) fn budget(&self) -> Budget {
+        let tokens = self.max_tokens.map(|t| t.saturating_mul(BYTES_PER_TOKEN));
+        let limit = match (self.max_bytes, tokens) {
+            (Some(bytes), Some(tokens)) => Some(bytes.min(tokens)),
+            (bytes, tokens) => bytes.or(tokens),
+        };
+        Budget { limit, used: 0 }
+    }
+}
+
+/// Running size of a response against its byte budget
+#[derive(Debug, Clone)]
+pub(crate) struct Budget {
+    limit: Option<usize>,
+    used: usize,
+}
+
+impl Budget {
+    /// Count an item's JSON size if it fits
+    ///
+    /// The first item always fits, so paging through results makes progress
+    /// even when a single entity is over budget.
+    pub(crate) fn admit<T: Serialize>(&mut self, item: &T) -> bool {
+        let Some(limit) = self.limit else {
+            return true;
+        };
+        let size = serde_json::to_vec(item).map(|v| v.len()).unwrap_or(0);
+        if self.used > 0 && self.used + size > limit {
+            return false;
+        }
+        self.used += size;
+        true
+    }
+
+    /// Most bytes allowed, if limited
+    pub(crate) fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+}
+
+/// Which page of a listing to return
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PageArgs {
+    /// nextCursor from a previous response; takes precedence over page
+    pub cursor: Option<String>,
+    /// Page number (0-indexed) when no cursor is given
+    pub page: Option<usize>,
+    /// Results per page (default: 100, max: 1000)
+    #[schemars(range(min = 1, max = 1000))]
+    pub page_size: Option<usize>,
+}
+
+impl PageArgs {
+    pub(crate) fn pagination(&self) -> Pagination {
+        let default = Pagination::default();
+        Pagination::new(
+            self.page.unwrap_or(default.page),
+            self.page_size.unwrap_or(default.page_size).max(1),
+        )
+    }
+
+    /// Offset of the first result to return
+    pub(crate) fn offset(&self) -> Result<usize, String> {
+        match &self.cursor {
+            Some(cursor) => decode_cursor(cursor).map_err(|e| e.to_string()),
+            None => Ok(self.pagination().offset()),
+        }
+    }
+
+    /// Project and name of the entity a key cursor resumes after
+    pub(crate) fn after_key(&self) -> Result<Option<(String, String)>, String> {
+        self.cursor
+            .as_deref()
+            .map(decode_key_cursor)
+            .transpose()
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// One page of rendered results
+pub(crate) struct Page<R> {
+    pub(crate) items: Vec<R>,
+    pub(crate) pagination: PaginationInfo,
+    pub(crate) next_cursor: Option<String>,
+    /// The budget ended the page before its page size
+    pub(crate) truncated: bool,
+}
+
+/// Render the page of `total` results starting at `offset`
+///
+/// `render` is called with the index of each result in turn until the page
+/// is full, the results run out, or the next rendered result would exceed the
+/// budget.
+pub(crate) fn paginate<R: Serialize>(
+    total: usize,
+    offset: usize,
+    pagination: &Pagination,
+    budget: &mut Budget,
+    mut render: impl FnMut(usize) -> R,
+) -> Page<R> {
+    let end = total.min(offset.saturating_add(pagination.page_size));
+    let mut items = Vec::new();
+    let mut next = offset.min(total);
+    while next < end {
+        let item = render(next);
+        if !budget.admit(&item) {
+            break;
+        }
+        items.push(item);
+        next += 1;
+    }
+
+    Page {
+        items,
+        pagination: PaginationInfo::new(offset / pagination.page_size, pagination.page_size, total),
+        next_cursor: (next < total).then(|| encode_cursor(next)),
+        truncated: next < end,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsnip_core::ProjectId;
+
+    #[test]
+    fn test_entity_projection() {
+        let mut entity = Entity::new(ProjectId::new(), "Alice", "person");
+        for i in 0..5 {
+            entity.add_observation(format!("fact {}", i));
+        }
+        entity.add_tag("team");
+
+        let names = OutputArgs {
+            fields: Fields::Names,
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(names.entity(&entity)).unwrap(),
+            serde_json::json!({"name": "Alice"})
+        );
+
+        let capped = OutputArgs {
+            max_observations: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(capped.entity(&entity)).unwrap(),
+            serde_json::json!({
+                "name": "Alice",
+                "entityType": "person",
+                "observations": ["fact 3", "fact 4"],
+                "moreObservations": 3,
+                "tags": ["team"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_paginate_with_cursor_and_budget() {
+        let items: Vec<String> = (0..10).map(|i| format!("item-{}", i)).collect();
+        let pagination = Pagination::new(0, 4);
+
+        let page = paginate(
+            items.len(),
+            0,
+            &pagination,
+            &mut Budget {
+                limit: None,
+                used: 0,
+            },
+            |i| items[i].clone(),
+        );
+        assert_eq!(page.items.len(), 4);
+        assert_eq!(page.pagination.total_pages, 3);
+        assert!(!page.truncated);
+        let cursor = page.next_cursor.unwrap();
+
+        let offset = PageArgs {
+            cursor: Some(cursor),
+            ..Default::default()
+        }
+        .offset()
+        .unwrap();
+        assert_eq!(offset, 4);
+
+        // "item-4" is 8 bytes with quotes; a 20 byte budget fits two
+        let mut budget = OutputArgs {
+            max_tokens: Some(5),
+            ..Default::default()
+        }
+        .budget();
+        let page = paginate(items.len(), offset, &pagination, &mut budget, |i| {
+            items[i].clone()
+        });
+        assert_eq!(page.items, vec!["item-4", "item-5"]);
+        assert!(page.truncated);
+        assert_eq!(page.next_cursor, Some(encode_cursor(6)));
+
+        let last = paginate(
+            items.len(),
+            8,
+            &pagination,
+            &mut Budget {
+                limit: None,
+                used: 0,
+            },
+            |i| items[i].clone(),
+        );
+        assert_eq!(last.items.len(), 2);
+        assert!(last.next_cursor.is_none());
+        assert!(!last.pagination.has_next_page);
+    }
+}
@@ -0,0 +1,232 @@
+//! Tools that list, inspect, create and delete projects
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use parsnip_storage::ops::{self, OpsError, ProjectStats};
+use parsnip_storage::{StorageBackend, StorageResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{ProjectResult, Tool, ToolCallResponse, ToolContext};
+use crate::permissions::Permissions;
+
+/// Check the caller may see a project, or the error response to return
+async fn check_project(ctx: &ToolContext, name: &str) -> Result<(), ToolCallResponse> {
+    match ctx.find_project(name).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(ToolCallResponse::error(format!("Project error: {}", e))),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectStatsResult {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    created_at: String,
+    entity_count: usize,
+    observation_count: usize,
+    tag_count: usize,
+    relation_count: usize,
+    entity_types: BTreeMap<String, usize>,
+    relation_types: BTreeMap<String, usize>,
+}
+
+impl From<ProjectStats> for ProjectStatsResult {
+    fn from(stats: ProjectStats) -> Self {
+        Self {
+            name: stats.project.name,
+            description: stats.project.description,
+            created_at: stats.project.created_at.to_rfc3339(),
+            entity_count: stats.entity_count,
+            observation_count: stats.observation_count,
+            tag_count: stats.tag_count,
+            relation_count: stats.relation_count,
+            entity_types: stats.entity_types,
+            relation_types: stats.relation_types,
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// list_projects
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `list_projects`: none
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListProjectsArgs {}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectListResult {
+    projects: Vec<ProjectResult>,
+}
+
+pub struct ListProjects;
+
+#[async_trait]
+impl Tool for ListProjects {
+    type Args = ListProjectsArgs;
+    const NAME: &'static str = "list_projects";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "List all projects with entity and relation counts.";
+
+    async fn call(&self, ctx: &ToolContext, _args: ListProjectsArgs) -> ToolCallResponse {
+        match project_summaries(ctx.storage(), ctx.permissions()).await {
+            Ok(projects) => ToolCallResponse::json(&ProjectListResult { projects }),
+            Err(e) => ToolCallResponse::error(format!("Storage error: {}", e)),
+        }
+    }
+}
+
+/// Every project the caller may see, with its entity and relation counts
+pub(crate) async fn project_summaries(
+    storage: &dyn StorageBackend,
+    permissions: &Permissions,
+) -> StorageResult<Vec<ProjectResult>> {
+    let projects = storage.get_all_projects().await?;
+
+    let mut results = Vec::new();
+    for project in projects {
+        if !permissions.allows_project(&project.name) {
+            continue;
+        }
+
+        let entity_count = storage
+            .get_all_entities(&project.id)
+            .await
+            .map(|e| e.len())
+            .unwrap_or(0);
+        let relation_count = storage
+            .get_all_relations(&project.id)
+            .await
+            .map(|r| r.len())
+            .unwrap_or(0);
+
+        results.push(ProjectResult {
+            name: project.name,
+            description: project.description,
+            entity_count,
+            relation_count,
+            created_at: project.created_at.to_rfc3339(),
+        });
+    }
+
+    Ok(results)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// project_stats
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `project_stats`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectStatsArgs {
+    /// Project name
+    pub project_id: String,
+}
+
+pub struct GetProjectStats;
+
+#[async_trait]
+impl Tool for GetProjectStats {
+    type Args = ProjectStatsArgs;
+    const NAME: &'static str = "project_stats";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "Show statistics for a project: entity, observation, tag and relation counts, with entities and relations broken down by type.";
+
+    async fn call(&self, ctx: &ToolContext, args: ProjectStatsArgs) -> ToolCallResponse {
+        if let Err(response) = check_project(ctx, &args.project_id).await {
+            return response;
+        }
+        match ops::project_stats(ctx.storage(), &args.project_id).await {
+            Ok(stats) => ToolCallResponse::json(&ProjectStatsResult::from(stats)),
+            Err(e) => ToolCallResponse::error(e.to_string()),
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// create_project / delete_project
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `create_project`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateProjectArgs {
+    /// Project name
+    pub project_id: String,
+    /// What the project holds
+    pub description: Option<String>,
+}
+
+pub struct CreateProject;
+
+#[async_trait]
+impl Tool for CreateProject {
+    type Args = CreateProjectArgs;
+    const NAME: &'static str = "create_project";
+    const DESCRIPTION: &'static str =
+        "Create an empty project, optionally with a description. Fails if the project exists.";
+
+    async fn call(&self, ctx: &ToolContext, args: CreateProjectArgs) -> ToolCallResponse {
+        if let Err(response) = check_project(ctx, &args.project_id).await {
+            return response;
+        }
+        match ops::create_project(ctx.storage(), &args.project_id, args.description.as_deref())
+            .await
+        {
+            Ok(project) => {
+                ToolCallResponse::text(format!("✅ SUCCESS: Created project '{}'", project.name))
+            }
+            Err(e) => ToolCallResponse::error(e.to_string()),
+        }
+    }
+}
+
+/// Arguments of `delete_project`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteProjectArgs {
+    /// Project name
+    pub project_id: String,
+    /// Really delete; without it the project is left alone and its size reported
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+pub struct DeleteProject;
+
+#[async_trait]
+impl Tool for DeleteProject {
+    type Args = DeleteProjectArgs;
+    const NAME: &'static str = "delete_project";
+    const DESCRIPTION: &'static str = "Delete a project with all its entities and relations. Requires confirm: true; without it, reports what would be deleted.";
+
+    async fn call(&self, ctx: &ToolContext, args: DeleteProjectArgs) -> ToolCallResponse {
+        if let Err(response) = check_project(ctx, &args.project_id).await {
+            return response;
+        }
+
+        let result = if args.confirm {
+            ops::delete_project(ctx.storage(), &args.project_id).await
+        } else {
+            ops::project_stats(ctx.storage(), &args.project_id).await
+        };
+        match result {
+            Ok(stats) if args.confirm => ToolCallResponse::text(format!(
+                "✅ SUCCESS: Deleted project '{}' ({} entities, {} relations)",
+                args.project_id, stats.entity_count, stats.relation_count
+            )),
+            Ok(stats) => ToolCallResponse::text(format!(
+                "Project '{}' has {} entities and {} relations. Call again with confirm: true to delete it.",
+                args.project_id, stats.entity_count, stats.relation_count
+            )),
+            Err(e @ OpsError::ProjectNotFound(_)) => ToolCallResponse::error(e.to_string()),
+            Err(e) => ToolCallResponse::error(format!("Failed to delete project: {}", e)),
+        }
+    }
+}
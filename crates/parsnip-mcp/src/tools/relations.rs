@@ -0,0 +1,299 @@
+//! Tools that create and delete relations
+
+use async_trait::async_trait;
+use parsnip_core::{validate_batch_relations, validate_entity_name, Entity, Project, Relation};
+use parsnip_storage::ops::{self, RelationFilter};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{Tool, ToolCallResponse, ToolContext, DEFAULT_PROJECT};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// create_relations
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `create_relations`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateRelationsArgs {
+    /// Default project for entities (default: 'default')
+    pub project_id: Option<String>,
+    pub relations: Vec<RelationInput>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationInput {
+    /// Source entity name
+    pub from: String,
+    /// Project containing the source entity (optional, auto-detected if unique)
+    pub from_project_id: Option<String>,
+    /// Target entity name
+    pub to: String,
+    /// Project containing the target entity (optional, auto-detected if unique)
+    pub to_project_id: Option<String>,
+    /// Relationship type (e.g., works_at, manages, depends_on)
+    pub relation_type: String,
+}
+
+/// Find the entity an endpoint refers to
+///
+/// Priority: the named project, then the relation's project, then a unique
+/// match in any project. `None` means no entity has the name yet; the
+/// relation then points at the name in the relation's project.
+async fn find_endpoint(
+    ctx: &ToolContext,
+    name: &str,
+    project_name: Option<&str>,
+    current: &Project,
+) -> Result<Option<Entity>, String> {
+    let storage = ctx.storage();
+
+    if let Some(project_name) = project_name {
+        let project = match ctx.find_project(project_name).await {
+            Ok(Some(p)) => p,
+            Ok(None) => return Err(format!("Project '{}' not found", project_name)),
+            Err(e) => return Err(format!("Project error: {}", e)),
+        };
+        return match storage.get_entity(name, &project.id).await {
+            Ok(Some(e)) => Ok(Some(e)),
+            Ok(None) => Err(format!(
+                "Entity '{}' not found in project '{}'",
+                name, project_name
+            )),
+            Err(e) => Err(format!("Storage error: {}", e)),
+        };
+    }
+
+    match storage.get_entity(name, &current.id).await {
+        Ok(Some(e)) => return Ok(Some(e)),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Storage error: {}", e)),
+    }
+
+    let mut matches: Vec<Entity> = ctx
+        .all_entities()
+        .await
+        .map_err(|e| format!("Storage error: {}", e))?
+        .into_iter()
+        .filter(|e| e.name == name)
+        .collect();
+
+    match matches.len() {
+        0 => Ok(None),
+        1 => Ok(matches.pop()),
+        _ => {
+            let mut projects = Vec::new();
+            for entity in &matches {
+                let project = match storage.get_project_by_id(&entity.project_id).await {
+                    Ok(Some(p)) => p.name,
+                    _ => entity.project_id.to_string(),
+                };
+                projects.push(project);
+            }
+            Err(format!(
+                "Entity '{}' found in multiple projects: {}. Specify fromProjectId/toProjectId.",
+                name,
+                projects.join(", ")
+            ))
+        }
+    }
+}
+
+pub struct CreateRelations;
+
+#[async_trait]
+impl Tool for CreateRelations {
+    type Args = CreateRelationsArgs;
+    const NAME: &'static str = "create_relations";
+    const DESCRIPTION: &'static str = "Create directional relationships between entities. Supports cross-project relations by specifying fromProjectId/toProjectId.";
+
+    async fn call(&self, ctx: &ToolContext, args: CreateRelationsArgs) -> ToolCallResponse {
+        // Validate batch size
+        if let Err(e) = validate_batch_relations(args.relations.len()) {
+            return ToolCallResponse::error(e.to_string());
+        }
+
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut created = 0;
+        for input in args.relations {
+            // Validate entity names
+            if let Err(e) = validate_entity_name(&input.from) {
+                return ToolCallResponse::error(e.to_string());
+            }
+            if let Err(e) = validate_entity_name(&input.to) {
+                return ToolCallResponse::error(e.to_string());
+            }
+
+            let from =
+                match find_endpoint(ctx, &input.from, input.from_project_id.as_deref(), &project)
+                    .await
+                {
+                    Ok(e) => e,
+                    Err(msg) => return ToolCallResponse::error(msg),
+                };
+            let to = match find_endpoint(ctx, &input.to, input.to_project_id.as_deref(), &project)
+                .await
+            {
+                Ok(e) => e,
+                Err(msg) => return ToolCallResponse::error(msg),
+            };
+
+            let mut relation = Relation::from_names(
+                project.id.clone(),
+                &input.from,
+                &input.to,
+                &input.relation_type,
+            );
+            if let Some(from) = from {
+                relation.from_id = from.id;
+                if from.project_id != project.id {
+                    relation.from_project_id = Some(from.project_id);
+                }
+            }
+            if let Some(to) = to {
+                relation.to_id = to.id;
+                if to.project_id != project.id {
+                    relation.to_project_id = Some(to.project_id);
+                }
+            }
+
+            if let Err(e) = ctx.storage().save_relation(&relation).await {
+                return ToolCallResponse::error(format!("Failed to save relation: {}", e));
+            }
+            created += 1;
+        }
+
+        ToolCallResponse::text(format!("✅ SUCCESS: Created {} relations", created))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// delete_relations
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `delete_relations`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteRelationsArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    pub relations: Vec<RelationRef>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationRef {
+    pub from: String,
+    pub to: String,
+    pub relation_type: String,
+}
+
+pub struct DeleteRelations;
+
+#[async_trait]
+impl Tool for DeleteRelations {
+    type Args = DeleteRelationsArgs;
+    const NAME: &'static str = "delete_relations";
+    const DESCRIPTION: &'static str = "Delete specific relationships between entities.";
+
+    async fn call(&self, ctx: &ToolContext, args: DeleteRelationsArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let mut deleted = 0;
+        for rel in args.relations {
+            if let Err(e) = ctx
+                .storage()
+                .delete_relation(&rel.from, &rel.to, &rel.relation_type, &project.id)
+                .await
+            {
+                return ToolCallResponse::error(format!("Failed to delete relation: {}", e));
+            }
+            deleted += 1;
+        }
+
+        ToolCallResponse::text(format!("✅ SUCCESS: Deleted {} relations", deleted))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// list_relations
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `list_relations`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ListRelationsArgs {
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    /// Only relations from this entity
+    pub from: Option<String>,
+    /// Only relations to this entity
+    pub to: Option<String>,
+    /// Only relations of this type (case-insensitive)
+    pub relation_type: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RelationListResult {
+    relations: Vec<ListedRelation>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListedRelation {
+    from: String,
+    to: String,
+    relation_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight: Option<f64>,
+}
+
+pub struct ListRelations;
+
+#[async_trait]
+impl Tool for ListRelations {
+    type Args = ListRelationsArgs;
+    const NAME: &'static str = "list_relations";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str =
+        "List the relations of a project, optionally filtered by source, target and type.";
+
+    async fn call(&self, ctx: &ToolContext, args: ListRelationsArgs) -> ToolCallResponse {
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let filter = RelationFilter {
+            from: args.from,
+            to: args.to,
+            relation_type: args.relation_type,
+        };
+        match ops::list_relations(ctx.storage(), &project, &filter).await {
+            Ok(relations) => ToolCallResponse::json(&RelationListResult {
+                relations: relations
+                    .into_iter()
+                    .map(|r| ListedRelation {
+                        from: r.from_name,
+                        to: r.to_name,
+                        relation_type: r.relation_type,
+                        weight: r.weight,
+                    })
+                    .collect(),
+            }),
+            Err(e) => ToolCallResponse::error(format!("Storage error: {}", e)),
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! Search, recall and link suggestion tools
+
+use async_trait::async_trait;
+use parsnip_core::{GraphExpansion, Pagination, PaginationInfo, SearchMode, SearchQuery};
+#[cfg(feature = "fulltext")]
+use parsnip_search::FullTextSearchEngine;
+use parsnip_search::{
+    ContextBuilder, ContextQuery, ExactSearchEngine, FuzzySearchEngine, LinkPredictor,
+    RelationTypeSuggestion, SearchEngine, DEFAULT_CONTEXT_BUDGET,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::output::paginate;
+use super::{
+    EntityResult, OutputArgs, PageArgs, RelationResult, Tool, ToolCallResponse, ToolContext,
+    DEFAULT_PROJECT,
+};
+
+/// Search mode accepted by tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchModeArg {
+    #[default]
+    Exact,
+    Fuzzy,
+    Fulltext,
+    Hybrid,
+}
+
+impl From<SearchModeArg> for SearchMode {
+    fn from(mode: SearchModeArg) -> Self {
+        match mode {
+            SearchModeArg::Exact => SearchMode::Exact,
+            SearchModeArg::Fuzzy => SearchMode::Fuzzy,
+            SearchModeArg::Fulltext => SearchMode::FullText,
+            SearchModeArg::Hybrid => SearchMode::Hybrid,
+        }
+    }
+}
+
+/// Search engine for a mode, falling back to exact when fulltext is unavailable
+struct Engines {
+    fuzzy: FuzzySearchEngine,
+    exact: ExactSearchEngine,
+    #[cfg(feature = "fulltext")]
+    fulltext: Option<FullTextSearchEngine>,
+}
+
+impl Engines {
+    fn for_mode(mode: SearchMode) -> Self {
+        #[cfg(feature = "fulltext")]
+        let fulltext = match mode {
+            SearchMode::FullText | SearchMode::Hybrid => match FullTextSearchEngine::in_memory() {
+                Ok(engine) => Some(engine),
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to create fulltext engine: {}, falling back to exact",
+                        e
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+        #[cfg(not(feature = "fulltext"))]
+        if matches!(mode, SearchMode::FullText | SearchMode::Hybrid) {
+            tracing::warn!("Fulltext search not enabled, falling back to exact");
+        }
+
+        Self {
+            fuzzy: FuzzySearchEngine::new(),
+            exact: ExactSearchEngine::new(),
+            #[cfg(feature = "fulltext")]
+            fulltext,
+        }
+    }
+
+    fn engine(&self, mode: SearchMode) -> &dyn SearchEngine {
+        match mode {
+            SearchMode::Fuzzy => &self.fuzzy,
+            #[cfg(feature = "fulltext")]
+            SearchMode::FullText | SearchMode::Hybrid => match self.fulltext {
+                Some(ref engine) => engine,
+                None => &self.exact,
+            },
+            _ => &self.exact,
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// search_knowledge
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `search_knowledge`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchArgs {
+    /// Search text
+    pub query: Option<String>,
+    /// Project name for data isolation (default: 'default'). Omit to search all projects.
+    pub project_id: Option<String>,
+    /// Search mode
+    #[serde(default)]
+    pub search_mode: SearchModeArg,
+    /// Fuzzy threshold (0.0-1.0, default: 0.3)
+    #[schemars(range(min = 0.0, max = 1.0))]
+    pub fuzzy_threshold: Option<f64>,
+    /// Tags for exact-match filtering
+    pub exact_tags: Option<Vec<String>>,
+    /// Also surface entities linked to direct hits by propagating scores over relations
+    pub graph_expansion: Option<ExpansionArgs>,
+    #[serde(flatten)]
+    pub page: PageArgs,
+    #[serde(flatten)]
+    pub output: OutputArgs,
+}
+
+/// Score propagation algorithm
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpansionAlgorithm {
+    #[default]
+    Spreading,
+    Pagerank,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpansionArgs {
+    #[serde(default)]
+    pub algorithm: ExpansionAlgorithm,
+    /// Maximum hops for spreading activation
+    #[serde(default = "default_max_hops")]
+    pub max_hops: u32,
+    /// Score multiplier per hop for spreading activation
+    #[serde(default = "default_decay")]
+    pub decay: f64,
+    /// PageRank damping factor
+    #[serde(default = "default_damping")]
+    pub damping: f64,
+    /// Only propagate over these relation types
+    pub relation_types: Option<Vec<String>>,
+}
+
+fn default_max_hops() -> u32 {
+    2
+}
+
+fn default_decay() -> f64 {
+    0.5
+}
+
+fn default_damping() -> f64 {
+    0.85
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResult {
+    entities: Vec<EntityResult>,
+    relations: Vec<RelationResult>,
+    pagination: PaginationInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+}
+
+pub struct SearchKnowledge;
+
+#[async_trait]
+impl Tool for SearchKnowledge {
+    type Args = SearchArgs;
+    const NAME: &'static str = "search_knowledge";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str =
+        "Search entities by text or tags across projects. Omit projectId to search all projects.";
+
+    async fn call(&self, ctx: &ToolContext, args: SearchArgs) -> ToolCallResponse {
+        let storage = ctx.storage();
+        let offset = match args.page.offset() {
+            Ok(o) => o,
+            Err(e) => return ToolCallResponse::error(e),
+        };
+
+        // Get entities, plus relations when propagating scores over the graph
+        let expand = args.graph_expansion.is_some();
+        let loaded = if let Some(ref project_name) = args.project_id {
+            match ctx.get_or_create_project(project_name).await {
+                Ok(project) => match storage.get_all_entities(&project.id).await {
+                    Ok(e) if expand => storage.get_all_relations(&project.id).await.map(|r| (e, r)),
+                    Ok(e) => Ok((e, Vec::new())),
+                    Err(e) => Err(e),
+                },
+                Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+            }
+        } else {
+            match ctx.all_entities().await {
+                Ok(e) if expand => ctx.all_relations().await.map(|r| (e, r)),
+                Ok(e) => Ok((e, Vec::new())),
+                Err(e) => Err(e),
+            }
+        };
+        let (entities, relations) = match loaded {
+            Ok(data) => data,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        // Build query
+        let mut query = if let Some(ref text) = args.query {
+            SearchQuery::text(text)
+        } else {
+            SearchQuery::empty()
+        };
+
+        if let Some(ref tags) = args.exact_tags {
+            for tag in tags {
+                query = query.with_tag(tag);
+            }
+        }
+
+        query = query.with_mode(args.search_mode.into());
+
+        if let Some(threshold) = args.fuzzy_threshold {
+            query = query.with_fuzzy_threshold(threshold as f32);
+        }
+
+        // Rank everything, then page here so the total count is known
+        query.pagination = Pagination {
+            page: 0,
+            page_size: entities.len().max(1),
+        };
+
+        if let Some(expansion) = args.graph_expansion {
+            let mut config = match expansion.algorithm {
+                ExpansionAlgorithm::Pagerank => GraphExpansion::page_rank(expansion.damping as f32),
+                ExpansionAlgorithm::Spreading => {
+                    GraphExpansion::spreading(expansion.max_hops, expansion.decay as f32)
+                }
+            };
+            if let Some(types) = expansion.relation_types {
+                config = config.over_relation_types(types);
+            }
+            query = query.with_graph_expansion(config);
+        }
+
+        // Perform search
+        let engines = Engines::for_mode(query.mode);
+        let results = engines
+            .engine(query.mode)
+            .search_with_graph(&query, &entities, &relations)
+            .await
+            .map(|hits| hits.into_iter().map(|h| h.entity).collect::<Vec<_>>());
+
+        match results {
+            Ok(entities) => {
+                let mut budget = args.output.budget();
+                let page = paginate(
+                    entities.len(),
+                    offset,
+                    &args.page.pagination(),
+                    &mut budget,
+                    |i| args.output.entity(&entities[i]),
+                );
+                let result = SearchResult {
+                    entities: page.items,
+                    relations: vec![],
+                    pagination: page.pagination,
+                    next_cursor: page.next_cursor,
+                    truncated: page.truncated,
+                };
+                ToolCallResponse::json(&result)
+            }
+            Err(e) => ToolCallResponse::error(format!("Search error: {}", e)),
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// get_context
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Output format of `get_context`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// Arguments of `get_context`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextArgs {
+    /// Topic to recall
+    pub query: String,
+    /// Project name (omit to search all projects)
+    pub project_id: Option<String>,
+    /// Token budget for the rendered context
+    #[serde(default = "default_budget")]
+    pub budget: usize,
+    /// Neighborhood depth around search hits
+    #[serde(default = "default_hops")]
+    #[schemars(range(min = 0, max = 2))]
+    pub hops: u32,
+    /// Maximum number of search hits to expand
+    #[serde(default = "default_max_seeds")]
+    pub max_seeds: usize,
+    /// Search mode for finding seeds
+    #[serde(default = "default_context_mode")]
+    pub search_mode: SearchModeArg,
+    /// Output format
+    #[serde(default)]
+    pub format: ContextFormat,
+}
+
+fn default_budget() -> usize {
+    DEFAULT_CONTEXT_BUDGET
+}
+
+fn default_hops() -> u32 {
+    1
+}
+
+fn default_max_seeds() -> usize {
+    5
+}
+
+fn default_context_mode() -> SearchModeArg {
+    SearchModeArg::Fuzzy
+}
+
+pub struct GetContext;
+
+#[async_trait]
+impl Tool for GetContext {
+    type Args = ContextArgs;
+    const NAME: &'static str = "get_context";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "Recall a topic as a compact, ranked context pack: top search hits, their 1-2 hop neighborhood, and the most relevant recent observations, rendered as markdown within a token budget.";
+
+    async fn call(&self, ctx: &ToolContext, args: ContextArgs) -> ToolCallResponse {
+        let storage = ctx.storage();
+        let mode = SearchMode::from(args.search_mode);
+        let mut search = SearchQuery::text(&args.query).with_mode(mode);
+
+        let loaded = if let Some(ref project_name) = args.project_id {
+            match ctx.get_or_create_project(project_name).await {
+                Ok(project) => {
+                    search = search.in_project(project.id.clone());
+                    match storage.get_all_entities(&project.id).await {
+                        Ok(e) => match storage.get_all_relations(&project.id).await {
+                            Ok(r) => Ok((e, r)),
+                            Err(e) => Err(e),
+                        },
+                        Err(e) => Err(e),
+                    }
+                }
+                Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+            }
+        } else {
+            match ctx.all_entities().await {
+                Ok(e) => match ctx.all_relations().await {
+                    Ok(r) => Ok((e, r)),
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            }
+        };
+        let (entities, relations) = match loaded {
+            Ok(data) => data,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        let query = ContextQuery::new(search)
+            .with_budget(args.budget)
+            .with_hops(args.hops)
+            .with_max_seeds(args.max_seeds);
+
+        let engines = Engines::for_mode(mode);
+        let pack = match ContextBuilder::new(engines.engine(mode))
+            .build(&query, &entities, &relations)
+            .await
+        {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Search error: {}", e)),
+        };
+
+        match args.format {
+            ContextFormat::Json => ToolCallResponse::json(&pack),
+            _ if pack.entities.is_empty() => {
+                ToolCallResponse::text(format!("No context found for '{}'", args.query))
+            }
+            ContextFormat::Markdown => ToolCallResponse::text(pack.render()),
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// suggest_relations
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Arguments of `suggest_relations`
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestArgs {
+    /// Entity to find missing links for
+    pub entity_name: String,
+    /// Project name for data isolation (default: 'default')
+    pub project_id: Option<String>,
+    /// Maximum suggestions
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    /// Minimum score
+    #[serde(default = "default_min_score")]
+    #[schemars(range(min = 0.0, max = 1.0))]
+    pub min_score: f64,
+}
+
+fn default_limit() -> usize {
+    10
+}
+
+fn default_min_score() -> f64 {
+    0.1
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SuggestRelationsResult {
+    entity: String,
+    suggestions: Vec<SuggestionJson>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SuggestionJson {
+    name: String,
+    entity_type: String,
+    score: f32,
+    common_neighbors: Vec<String>,
+    adamic_adar: f32,
+    shared_tags: Vec<String>,
+    text_similarity: f32,
+    relation_types: Vec<RelationTypeSuggestion>,
+}
+
+pub struct SuggestRelations;
+
+#[async_trait]
+impl Tool for SuggestRelations {
+    type Args = SuggestArgs;
+    const NAME: &'static str = "suggest_relations";
+    const READ_ONLY: bool = true;
+    const DESCRIPTION: &'static str = "Suggest entities that are probably related to the given entity but not linked yet. Scores use common neighbors, Adamic-Adar, shared tags and observation text similarity, and include relation types seen between similarly typed entities.";
+
+    async fn call(&self, ctx: &ToolContext, args: SuggestArgs) -> ToolCallResponse {
+        let storage = ctx.storage();
+        let project_name = args.project_id.as_deref().unwrap_or(DEFAULT_PROJECT);
+        let project = match ctx.get_or_create_project(project_name).await {
+            Ok(p) => p,
+            Err(e) => return ToolCallResponse::error(format!("Project error: {}", e)),
+        };
+
+        let entities = match storage.get_all_entities(&project.id).await {
+            Ok(e) => e,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+        let relations = match storage.get_all_relations(&project.id).await {
+            Ok(r) => r,
+            Err(e) => return ToolCallResponse::error(format!("Storage error: {}", e)),
+        };
+
+        let Some(target) = entities.iter().find(|e| e.name == args.entity_name) else {
+            return ToolCallResponse::error(format!("Entity '{}' not found", args.entity_name));
+        };
+
+        let suggestions = LinkPredictor::new()
+            .with_min_score(args.min_score as f32)
+            .suggest(target, &entities, &relations, args.limit);
+
+        let response = SuggestRelationsResult {
+            entity: args.entity_name.clone(),
+            suggestions: suggestions
+                .into_iter()
+                .map(|s| SuggestionJson {
+                    name: s.entity.name,
+                    entity_type: s.entity.entity_type.0,
+                    score: s.score,
+                    common_neighbors: s.common_neighbors,
+                    adamic_adar: s.adamic_adar,
+                    shared_tags: s.shared_tags,
+                    text_similarity: s.text_similarity,
+                    relation_types: s.relation_types,
+                })
+                .collect(),
+        };
+
+        ToolCallResponse::json(&response)
+    }
+}
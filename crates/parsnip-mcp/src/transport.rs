@@ -1,15 +1,39 @@
 //! MCP transport implementations
+//!
+//! Incoming lines and HTTP bodies are parsed as JSON-RPC 2.0 payloads: a
+//! single message or a batch array. Malformed input becomes an error
+//! response rather than a transport failure.
 
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
-/// JSON-RPC request
-#[derive(Debug, Deserialize)]
+/// Invalid JSON
+pub const PARSE_ERROR: i32 = -32700;
+/// JSON that is not a valid JSON-RPC message
+pub const INVALID_REQUEST: i32 = -32600;
+
+/// Protocol versions this server speaks, newest first
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Version to answer `initialize` with
+///
+/// The client's version if supported, otherwise the newest one we support,
+/// which the client may reject by disconnecting.
+pub fn negotiate_protocol_version(requested: Option<&str>) -> &'static str {
+    SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .find(|v| Some(**v) == requested)
+        .copied()
+        .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0])
+}
+
+/// JSON-RPC request, from the client or, for sampling, to it
+#[derive(Debug, Deserialize, Serialize)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
     pub id: serde_json::Value,
     pub method: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
     pub params: serde_json::Value,
 }
 
@@ -24,11 +48,162 @@ pub struct JsonRpcResponse {
     pub error: Option<JsonRpcError>,
 }
 
+/// JSON-RPC notification (server to client, no response expected)
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: &'static str,
+    pub method: String,
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub params: serde_json::Value,
+}
+
+impl JsonRpcNotification {
+    pub fn new(method: impl Into<String>, params: serde_json::Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+/// Incoming JSON-RPC message
+#[derive(Debug)]
+pub enum JsonRpcMessage {
+    /// Expects a response
+    Request(JsonRpcRequest),
+    /// Has no `id` and gets no response
+    Notification {
+        method: String,
+        params: serde_json::Value,
+    },
+    /// Client reply to a server-initiated request
+    Response(serde_json::Value),
+}
+
+impl JsonRpcMessage {
+    /// Validate one message, or build the error response for it
+    pub fn from_value(value: serde_json::Value) -> Result<Self, JsonRpcResponse> {
+        let serde_json::Value::Object(mut object) = value else {
+            return Err(JsonRpcResponse::error(
+                serde_json::Value::Null,
+                INVALID_REQUEST,
+                "Invalid request: expected an object",
+            ));
+        };
+
+        // Only string, number and null ids can be echoed back
+        let id = object.remove("id");
+        let reply_id = match &id {
+            Some(id @ (serde_json::Value::String(_) | serde_json::Value::Number(_))) => id.clone(),
+            _ => serde_json::Value::Null,
+        };
+        let invalid = |message: &str| {
+            Err(JsonRpcResponse::error(
+                reply_id.clone(),
+                INVALID_REQUEST,
+                format!("Invalid request: {}", message),
+            ))
+        };
+
+        if object.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+            return invalid("jsonrpc must be \"2.0\"");
+        }
+        if matches!(
+            id,
+            Some(
+                serde_json::Value::Bool(_)
+                    | serde_json::Value::Array(_)
+                    | serde_json::Value::Object(_)
+            )
+        ) {
+            return invalid("id must be a string, number or null");
+        }
+
+        let params = object.remove("params").unwrap_or(serde_json::Value::Null);
+        match (object.remove("method"), id) {
+            (Some(serde_json::Value::String(method)), Some(id)) => {
+                Ok(Self::Request(JsonRpcRequest {
+                    jsonrpc: "2.0".to_string(),
+                    id,
+                    method,
+                    params,
+                }))
+            }
+            (Some(serde_json::Value::String(method)), None) => {
+                Ok(Self::Notification { method, params })
+            }
+            (Some(_), _) => invalid("method must be a string"),
+            (None, Some(id)) if object.contains_key("result") || object.contains_key("error") => {
+                object.insert("id".to_string(), id);
+                Ok(Self::Response(serde_json::Value::Object(object)))
+            }
+            (None, _) => invalid("missing method"),
+        }
+    }
+}
+
+/// A single message or a batch, each either valid or already an error
+#[derive(Debug)]
+pub enum JsonRpcPayload {
+    Single(Result<JsonRpcMessage, JsonRpcResponse>),
+    Batch(Vec<Result<JsonRpcMessage, JsonRpcResponse>>),
+}
+
+impl JsonRpcPayload {
+    /// Parse a line or body; never fails, errors become responses
+    pub fn parse(text: &str) -> Self {
+        let value: serde_json::Value = match serde_json::from_str(text) {
+            Ok(value) => value,
+            Err(e) => {
+                return Self::Single(Err(JsonRpcResponse::error(
+                    serde_json::Value::Null,
+                    PARSE_ERROR,
+                    format!("Parse error: {}", e),
+                )))
+            }
+        };
+
+        match value {
+            serde_json::Value::Array(items) if items.is_empty() => {
+                Self::Single(Err(JsonRpcResponse::error(
+                    serde_json::Value::Null,
+                    INVALID_REQUEST,
+                    "Invalid request: empty batch",
+                )))
+            }
+            serde_json::Value::Array(items) => {
+                Self::Batch(items.into_iter().map(JsonRpcMessage::from_value).collect())
+            }
+            value => Self::Single(JsonRpcMessage::from_value(value)),
+        }
+    }
+
+    /// The request, if this is a single request
+    pub fn as_request(&self) -> Option<&JsonRpcRequest> {
+        match self {
+            Self::Single(Ok(JsonRpcMessage::Request(request))) => Some(request),
+            _ => None,
+        }
+    }
+}
+
+/// Response to a payload: one response, or the non-empty responses of a batch
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum JsonRpcReply {
+    Single(JsonRpcResponse),
+    Batch(Vec<JsonRpcResponse>),
+}
+
 /// JSON-RPC error
 #[derive(Debug, Serialize)]
 pub struct JsonRpcError {
     pub code: i32,
     pub message: String,
+    /// Boxed to keep responses small; most errors carry no data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Box<serde_json::Value>>,
 }
 
 impl JsonRpcResponse {
@@ -49,9 +224,18 @@ impl JsonRpcResponse {
             error: Some(JsonRpcError {
                 code,
                 message: message.into(),
+                data: None,
             }),
         }
     }
+
+    /// Attach structured details to an error response
+    pub fn with_data(mut self, data: Option<serde_json::Value>) -> Self {
+        if let Some(error) = self.error.as_mut() {
+            error.data = data.map(Box::new);
+        }
+        self
+    }
 }
 
 /// Stdio transport for MCP
@@ -73,27 +257,129 @@ impl StdioTransport {
         }
     }
 
-    /// Read a JSON-RPC request from stdin
-    pub async fn read_request(&mut self) -> std::io::Result<Option<JsonRpcRequest>> {
-        let mut line = String::new();
-
-        if self.reader.read_line(&mut line).await? == 0 {
-            return Ok(None);
+    /// Read the next JSON-RPC payload from stdin, skipping blank lines
+    ///
+    /// Only I/O errors are returned as `Err`; malformed JSON is a payload
+    /// holding the error response.
+    pub async fn read_payload(&mut self) -> std::io::Result<Option<JsonRpcPayload>> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                return Ok(Some(JsonRpcPayload::parse(&line)));
+            }
         }
-
-        let request: JsonRpcRequest = serde_json::from_str(&line)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-
-        Ok(Some(request))
     }
 
     /// Write a JSON-RPC response to stdout
     pub async fn write_response(response: &JsonRpcResponse) -> std::io::Result<()> {
+        Self::write_message(response).await
+    }
+
+    /// Write a JSON-RPC reply (single or batch) to stdout
+    pub async fn write_reply(reply: &JsonRpcReply) -> std::io::Result<()> {
+        Self::write_message(reply).await
+    }
+
+    /// Write a JSON-RPC notification to stdout
+    pub async fn write_notification(notification: &JsonRpcNotification) -> std::io::Result<()> {
+        Self::write_message(notification).await
+    }
+
+    /// Write an already serialized message to stdout
+    pub async fn write_serialized(json: &str) -> std::io::Result<()> {
         let mut stdout = tokio::io::stdout();
-        let json = serde_json::to_string(response)?;
         stdout.write_all(json.as_bytes()).await?;
         stdout.write_all(b"\n").await?;
         stdout.flush().await?;
         Ok(())
     }
+
+    async fn write_message<T: Serialize>(message: &T) -> std::io::Result<()> {
+        Self::write_serialized(&serde_json::to_string(message)?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(message: &Result<JsonRpcMessage, JsonRpcResponse>) -> Option<i32> {
+        message
+            .as_ref()
+            .err()
+            .and_then(|r| r.error.as_ref())
+            .map(|e| e.code)
+    }
+
+    #[test]
+    fn test_parse_single_messages() {
+        let payload = JsonRpcPayload::parse(r#"{"jsonrpc":"2.0","id":7,"method":"ping"}"#);
+        assert_eq!(payload.as_request().unwrap().id, 7);
+
+        let payload =
+            JsonRpcPayload::parse(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#);
+        assert!(matches!(
+            payload,
+            JsonRpcPayload::Single(Ok(JsonRpcMessage::Notification { ref method, .. }))
+                if method == "notifications/initialized"
+        ));
+
+        let payload = JsonRpcPayload::parse(r#"{"jsonrpc":"2.0","id":"a","result":{}}"#);
+        assert!(matches!(
+            payload,
+            JsonRpcPayload::Single(Ok(JsonRpcMessage::Response(_)))
+        ));
+
+        let JsonRpcPayload::Single(message) = JsonRpcPayload::parse("{not json") else {
+            panic!("expected a single error");
+        };
+        assert_eq!(error_code(&message), Some(PARSE_ERROR));
+
+        for invalid in [
+            r#"{"jsonrpc":"1.0","id":1,"method":"ping"}"#,
+            r#"{"jsonrpc":"2.0","id":1}"#,
+            r#"{"jsonrpc":"2.0","id":{},"method":"ping"}"#,
+            r#"{"jsonrpc":"2.0","id":1,"method":5}"#,
+            "[]",
+            "42",
+        ] {
+            let JsonRpcPayload::Single(message) = JsonRpcPayload::parse(invalid) else {
+                panic!("expected a single error for {}", invalid);
+            };
+            assert_eq!(error_code(&message), Some(INVALID_REQUEST), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let payload = JsonRpcPayload::parse(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"ping"},{"jsonrpc":"2.0","method":"notifications/initialized"},1]"#,
+        );
+        let JsonRpcPayload::Batch(messages) = payload else {
+            panic!("expected a batch");
+        };
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(messages[0], Ok(JsonRpcMessage::Request(_))));
+        assert!(matches!(
+            messages[1],
+            Ok(JsonRpcMessage::Notification { .. })
+        ));
+        assert_eq!(error_code(&messages[2]), Some(INVALID_REQUEST));
+    }
+
+    #[test]
+    fn test_negotiate_protocol_version() {
+        assert_eq!(negotiate_protocol_version(Some("2024-11-05")), "2024-11-05");
+        assert_eq!(
+            negotiate_protocol_version(Some("1999-01-01")),
+            SUPPORTED_PROTOCOL_VERSIONS[0]
+        );
+        assert_eq!(
+            negotiate_protocol_version(None),
+            SUPPORTED_PROTOCOL_VERSIONS[0]
+        );
+    }
 }
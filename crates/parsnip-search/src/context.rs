@@ -0,0 +1,531 @@
+//! Token-budgeted context packs
+//!
+//! Combines search hits with their graph neighborhood, ranks observations by
+//! relevance and recency, and renders the best of them within a token budget.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::traits::{Result, SearchEngine};
+use parsnip_core::{Entity, NodeKey, Relation, SearchQuery, TraversalEngine, TraversalQuery};
+
+/// Default token budget for a context pack
+pub const DEFAULT_CONTEXT_BUDGET: usize = 2000;
+
+/// Maximum neighborhood expansion depth
+pub const MAX_CONTEXT_HOPS: u32 = 2;
+
+/// Estimates how many tokens a piece of text costs
+pub trait TokenEstimator: Send + Sync {
+    fn estimate(&self, text: &str) -> usize;
+}
+
+/// Character-based estimate (~4 characters per token for English text)
+#[derive(Debug, Clone, Copy)]
+pub struct CharTokenEstimator {
+    pub chars_per_token: f32,
+}
+
+impl Default for CharTokenEstimator {
+    fn default() -> Self {
+        Self {
+            chars_per_token: 4.0,
+        }
+    }
+}
+
+impl TokenEstimator for CharTokenEstimator {
+    fn estimate(&self, text: &str) -> usize {
+        let chars = text.chars().count() as f32;
+        (chars / self.chars_per_token.max(1.0)).ceil() as usize
+    }
+}
+
+/// Word-based estimate (~0.75 words per token)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WordTokenEstimator;
+
+impl TokenEstimator for WordTokenEstimator {
+    fn estimate(&self, text: &str) -> usize {
+        let words = text.split_whitespace().count() as f32;
+        (words * 4.0 / 3.0).ceil() as usize
+    }
+}
+
+/// Context pack request
+#[derive(Debug, Clone)]
+pub struct ContextQuery {
+    /// Search used to find seed entities
+    pub search: SearchQuery,
+
+    /// Token budget for the rendered pack
+    pub budget: usize,
+
+    /// Neighborhood expansion depth (clamped to MAX_CONTEXT_HOPS)
+    pub hops: u32,
+
+    /// Maximum number of search hits used as seeds
+    pub max_seeds: usize,
+
+    /// Score multiplier applied per hop away from a seed
+    pub hop_decay: f32,
+
+    /// Observation age (days) at which the recency boost halves
+    pub recency_half_life_days: f32,
+}
+
+impl ContextQuery {
+    pub fn new(search: SearchQuery) -> Self {
+        Self {
+            search,
+            budget: DEFAULT_CONTEXT_BUDGET,
+            hops: 1,
+            max_seeds: 5,
+            hop_decay: 0.5,
+            recency_half_life_days: 30.0,
+        }
+    }
+
+    /// Set the token budget
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Set the expansion depth
+    pub fn with_hops(mut self, hops: u32) -> Self {
+        self.hops = hops.min(MAX_CONTEXT_HOPS);
+        self
+    }
+
+    /// Set the number of search hits used as seeds
+    pub fn with_max_seeds(mut self, max_seeds: usize) -> Self {
+        self.max_seeds = max_seeds.max(1);
+        self
+    }
+}
+
+/// An entity included in a context pack
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextEntity {
+    pub name: String,
+    pub entity_type: String,
+    /// Hops from the nearest search hit (0 = direct hit)
+    pub distance: u32,
+    pub score: f32,
+    pub observations: Vec<String>,
+    /// Observations that did not fit in the budget
+    pub omitted_observations: usize,
+}
+
+/// A relation between two entities of a context pack
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextRelation {
+    pub from: String,
+    pub to: String,
+    pub relation_type: String,
+}
+
+/// Ranked, budgeted bundle of entities, observations and relations
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextPack {
+    pub query: String,
+    pub budget: usize,
+    pub tokens_used: usize,
+    pub truncated: bool,
+    pub entities: Vec<ContextEntity>,
+    pub relations: Vec<ContextRelation>,
+    /// Rendered markdown text
+    text: String,
+}
+
+impl ContextPack {
+    /// Rendered markdown text
+    pub fn render(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Candidate entity with its rank score
+struct Candidate<'a> {
+    entity: &'a Entity,
+    distance: u32,
+    score: f32,
+}
+
+/// Builds context packs from a search engine and the graph
+pub struct ContextBuilder<'a> {
+    engine: &'a dyn SearchEngine,
+    estimator: Box<dyn TokenEstimator>,
+}
+
+impl<'a> ContextBuilder<'a> {
+    pub fn new(engine: &'a dyn SearchEngine) -> Self {
+        Self {
+            engine,
+            estimator: Box::new(CharTokenEstimator::default()),
+        }
+    }
+
+    /// Use a custom token estimator
+    pub fn with_estimator(mut self, estimator: impl TokenEstimator + 'static) -> Self {
+        self.estimator = Box::new(estimator);
+        self
+    }
+
+    /// Cost of a rendered line, including its line break
+    fn line_cost(&self, line: &str) -> usize {
+        self.estimator.estimate(&format!("{}\n", line))
+    }
+
+    /// Build a context pack
+    ///
+    /// Output is deterministic for the same inputs: ties are broken by name
+    /// and recency is measured against the newest candidate observation
+    /// rather than the wall clock.
+    pub async fn build(
+        &self,
+        query: &ContextQuery,
+        entities: &[Entity],
+        relations: &[Relation],
+    ) -> Result<ContextPack> {
+        let hits = self.engine.search(&query.search, entities).await?;
+        let candidates = Self::expand(query, &hits, entities, relations);
+
+        tracing::debug!(
+            "Context: {} search hits, {} candidates after {}-hop expansion",
+            hits.len(),
+            candidates.len(),
+            query.hops
+        );
+
+        Ok(self.assemble(query, &candidates, relations))
+    }
+
+    /// Rank seeds by search position and expand them through the graph
+    fn expand<'e>(
+        query: &ContextQuery,
+        hits: &[Entity],
+        entities: &'e [Entity],
+        relations: &[Relation],
+    ) -> Vec<Candidate<'e>> {
+        let by_key: HashMap<NodeKey, &Entity> =
+            entities.iter().map(|e| (NodeKey::of(e), e)).collect();
+        let mut best: HashMap<NodeKey, (u32, f32)> = HashMap::new();
+
+        for (rank, hit) in hits.iter().take(query.max_seeds).enumerate() {
+            let seed_score = 1.0 / (1.0 + rank as f32);
+            let key = NodeKey::of(hit);
+            Self::offer(&mut best, key, 0, seed_score);
+
+            // One traversal per depth so each neighbor gets its hop distance
+            let mut seen: HashSet<NodeKey> = HashSet::new();
+            for hop in 1..=query.hops.min(MAX_CONTEXT_HOPS) {
+                let traversal = TraversalQuery::new(&hit.name)
+                    .in_project(hit.project_id.clone())
+                    .with_depth(hop);
+                let result = TraversalEngine::execute(&traversal, entities, relations);
+                let score = seed_score * query.hop_decay.powi(hop as i32);
+                for node in result.visited_entities.iter().skip(1) {
+                    let key = node.key();
+                    if by_key.contains_key(&key) && seen.insert(key.clone()) {
+                        Self::offer(&mut best, key, hop, score);
+                    }
+                }
+            }
+        }
+
+        let mut candidates: Vec<Candidate> = best
+            .into_iter()
+            .filter_map(|(key, (distance, score))| {
+                by_key.get(&key).map(|entity| Candidate {
+                    entity,
+                    distance,
+                    score,
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.distance.cmp(&b.distance))
+                .then_with(|| a.entity.name.cmp(&b.entity.name))
+                .then_with(|| a.entity.id.0.cmp(&b.entity.id.0))
+        });
+        candidates
+    }
+
+    fn offer(best: &mut HashMap<NodeKey, (u32, f32)>, key: NodeKey, distance: u32, score: f32) {
+        let entry = best.entry(key).or_insert((distance, score));
+        if score > entry.1 {
+            *entry = (distance.min(entry.0), score);
+        } else {
+            entry.0 = entry.0.min(distance);
+        }
+    }
+
+    /// Score observations and greedily fill the budget
+    fn assemble(
+        &self,
+        query: &ContextQuery,
+        candidates: &[Candidate],
+        relations: &[Relation],
+    ) -> ContextPack {
+        let query_text = query.search.text.clone().unwrap_or_default();
+        let terms: Vec<String> = query_text
+            .split_whitespace()
+            .map(|t| t.to_lowercase())
+            .collect();
+
+        let newest = candidates
+            .iter()
+            .flat_map(|c| c.entity.observations.iter())
+            .map(|o| o.created_at.timestamp())
+            .max()
+            .unwrap_or_default();
+        let half_life_secs = (query.recency_half_life_days.max(0.01) * 86_400.0) as f64;
+
+        // (score, candidate index, observation index)
+        let mut scored: Vec<(f32, usize, usize)> = Vec::new();
+        for (ci, candidate) in candidates.iter().enumerate() {
+            for (oi, obs) in candidate.entity.observations.iter().enumerate() {
+                let relevance = term_overlap(&obs.content, &terms);
+                let age = (newest - obs.created_at.timestamp()).max(0) as f64;
+                let recency = 0.5f64.powf(age / half_life_secs) as f32;
+                let score = candidate.score * (0.5 + 0.5 * relevance) * (0.5 + 0.5 * recency);
+                scored.push((score, ci, oi));
+            }
+        }
+        scored.sort_by(|a, b| {
+            b.0.total_cmp(&a.0)
+                .then_with(|| a.1.cmp(&b.1))
+                .then_with(|| a.2.cmp(&b.2))
+        });
+
+        let header = format!("# Context: {}", query_text);
+        let mut used = self.line_cost(&header);
+        let mut included: Vec<bool> = vec![false; candidates.len()];
+        let mut selected: Vec<Vec<usize>> = vec![Vec::new(); candidates.len()];
+        let mut truncated = used > query.budget;
+
+        // Entities without observations still count as context when they fit
+        let mut order: Vec<(f32, usize, Option<usize>)> = scored
+            .iter()
+            .map(|&(s, ci, oi)| (s, ci, Some(oi)))
+            .collect();
+        for (ci, candidate) in candidates.iter().enumerate() {
+            if candidate.entity.observations.is_empty() {
+                order.push((candidate.score * 0.25, ci, None));
+            }
+        }
+        order.sort_by(|a, b| {
+            b.0.total_cmp(&a.0)
+                .then_with(|| a.1.cmp(&b.1))
+                .then_with(|| a.2.cmp(&b.2))
+        });
+
+        for (_, ci, oi) in order {
+            if truncated && used >= query.budget {
+                break;
+            }
+            let mut cost = 0;
+            if !included[ci] {
+                cost += self.line_cost(&entity_heading(&candidates[ci]));
+            }
+            if let Some(oi) = oi {
+                let obs = &candidates[ci].entity.observations[oi];
+                cost += self.line_cost(&observation_line(&obs.content));
+            }
+            if used + cost > query.budget {
+                truncated = true;
+                continue;
+            }
+            used += cost;
+            included[ci] = true;
+            if let Some(oi) = oi {
+                selected[ci].push(oi);
+            }
+        }
+
+        let mut lines = vec![header];
+        let mut pack_entities = Vec::new();
+        let mut names: HashMap<NodeKey, &str> = HashMap::new();
+        for (ci, candidate) in candidates.iter().enumerate() {
+            if !included[ci] {
+                continue;
+            }
+            names.insert(NodeKey::of(candidate.entity), &candidate.entity.name);
+            lines.push(entity_heading(candidate));
+            let mut observations = Vec::new();
+            for &oi in &selected[ci] {
+                let content = &candidate.entity.observations[oi].content;
+                lines.push(observation_line(content));
+                observations.push(content.clone());
+            }
+            pack_entities.push(ContextEntity {
+                name: candidate.entity.name.clone(),
+                entity_type: candidate.entity.entity_type.0.clone(),
+                distance: candidate.distance,
+                score: candidate.score,
+                omitted_observations: candidate.entity.observations.len() - observations.len(),
+                observations,
+            });
+        }
+
+        // Relations among included entities, as budget allows
+        let mut pack_relations = Vec::new();
+        let mut relation_lines = Vec::new();
+        let heading = "## Relations".to_string();
+        let mut heading_cost = self.line_cost(&heading);
+        for rel in relations {
+            let from = NodeKey::new(rel.effective_from_project_id().clone(), rel.from_id.clone());
+            let to = NodeKey::new(rel.effective_to_project_id().clone(), rel.to_id.clone());
+            let (Some(from), Some(to)) = (names.get(&from), names.get(&to)) else {
+                continue;
+            };
+            let line = format!("- {} -[{}]-> {}", from, rel.relation_type, to);
+            let cost = heading_cost + self.line_cost(&line);
+            if used + cost > query.budget {
+                truncated = true;
+                continue;
+            }
+            used += cost;
+            heading_cost = 0;
+            relation_lines.push(line);
+            pack_relations.push(ContextRelation {
+                from: from.to_string(),
+                to: to.to_string(),
+                relation_type: rel.relation_type.clone(),
+            });
+        }
+        if !relation_lines.is_empty() {
+            lines.push(heading);
+            lines.extend(relation_lines);
+        }
+
+        ContextPack {
+            query: query_text,
+            budget: query.budget,
+            tokens_used: used,
+            truncated,
+            entities: pack_entities,
+            relations: pack_relations,
+            text: lines.join("\n"),
+        }
+    }
+}
+
+fn entity_heading(candidate: &Candidate) -> String {
+    format!(
+        "## {} ({})",
+        candidate.entity.name, candidate.entity.entity_type.0
+    )
+}
+
+fn observation_line(content: &str) -> String {
+    format!("- {}", content)
+}
+
+/// Fraction of query terms present in the text (1.0 when there are no terms)
+fn term_overlap(text: &str, terms: &[String]) -> f32 {
+    if terms.is_empty() {
+        return 1.0;
+    }
+    let text = text.to_lowercase();
+    let matched = terms.iter().filter(|t| text.contains(t.as_str())).count();
+    matched as f32 / terms.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExactSearchEngine;
+    use parsnip_core::ProjectId;
+
+    fn create_graph() -> (Vec<Entity>, Vec<Relation>) {
+        let project_id = ProjectId::new();
+
+        let mut alice = Entity::new(project_id.clone(), "Alice", "person");
+        alice.add_observation("Writes Rust at Acme");
+        alice.add_observation("Likes hiking");
+
+        let mut acme = Entity::new(project_id.clone(), "Acme", "company");
+        acme.add_observation("Builds developer tools");
+
+        let mut bob = Entity::new(project_id.clone(), "Bob", "person");
+        bob.add_observation("Manages the platform team at Acme");
+
+        let relations = vec![
+            Relation::new(
+                project_id.clone(),
+                alice.id.clone(),
+                "Alice",
+                acme.id.clone(),
+                "Acme",
+                "works_at",
+            ),
+            Relation::new(
+                project_id.clone(),
+                bob.id.clone(),
+                "Bob",
+                acme.id.clone(),
+                "Acme",
+                "works_at",
+            ),
+        ];
+
+        (vec![alice, acme, bob], relations)
+    }
+
+    #[tokio::test]
+    async fn test_context_includes_neighborhood() {
+        let (entities, relations) = create_graph();
+        let engine = ExactSearchEngine::new();
+        let query = ContextQuery::new(SearchQuery::new("Alice")).with_hops(2);
+
+        let pack = ContextBuilder::new(&engine)
+            .build(&query, &entities, &relations)
+            .await
+            .unwrap();
+
+        let names: Vec<&str> = pack.entities.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Alice", "Acme", "Bob"]);
+        assert_eq!(pack.entities[1].distance, 1);
+        assert_eq!(pack.entities[2].distance, 2);
+        assert_eq!(pack.relations.len(), 2);
+        assert!(!pack.truncated);
+        assert!(pack.render().contains("## Alice (person)"));
+    }
+
+    #[tokio::test]
+    async fn test_context_respects_budget() {
+        let (entities, relations) = create_graph();
+        let engine = ExactSearchEngine::new();
+        let query = ContextQuery::new(SearchQuery::new("Alice"))
+            .with_hops(2)
+            .with_budget(20);
+
+        let builder = ContextBuilder::new(&engine);
+        let pack = builder.build(&query, &entities, &relations).await.unwrap();
+
+        assert!(pack.truncated);
+        assert!(pack.tokens_used <= 20);
+        assert!(CharTokenEstimator::default().estimate(pack.render()) <= 20);
+        assert_eq!(pack.entities[0].name, "Alice");
+
+        // Same inputs, same output
+        let again = builder.build(&query, &entities, &relations).await.unwrap();
+        assert_eq!(pack.render(), again.render());
+    }
+
+    #[test]
+    fn test_token_estimators() {
+        assert_eq!(CharTokenEstimator::default().estimate("abcdefgh"), 2);
+        assert_eq!(CharTokenEstimator::default().estimate("abcdefghi"), 3);
+        assert_eq!(WordTokenEstimator.estimate("one two three"), 4);
+        assert_eq!(WordTokenEstimator.estimate(""), 0);
+    }
+}
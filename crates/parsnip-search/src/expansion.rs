@@ -0,0 +1,233 @@
+//! Graph-aware search expansion
+//!
+//! Re-ranks search results by propagating scores from direct hits to their
+//! neighbors over weighted relations, so entities linked to a match surface
+//! even when their own text does not mention the query.
+
+use std::collections::HashMap;
+
+use crate::graph::Adjacency;
+use crate::traits::SearchHit;
+use parsnip_core::{Entity, ExpansionAlgorithm, GraphExpansion, NodeKey, Relation, SearchQuery};
+
+/// Propagate scores from ranked search hits to their graph neighbors
+///
+/// `hits` are the direct matches in rank order; `entities` is the candidate
+/// set the search ran over. Returns hits sorted by combined score, with
+/// neighbors that pass the query's entity type filter appended where they rank.
+pub fn expand_hits(
+    query: &SearchQuery,
+    expansion: &GraphExpansion,
+    hits: &[Entity],
+    entities: &[Entity],
+    relations: &[Relation],
+) -> Vec<SearchHit> {
+    let index: HashMap<NodeKey, usize> = entities
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (NodeKey::of(e), i))
+        .collect();
+
+    // Seed scores decrease with rank
+    let mut seeds = vec![0.0f32; entities.len()];
+    for (rank, hit) in hits.iter().enumerate() {
+        if let Some(&i) = index.get(&NodeKey::of(hit)) {
+            seeds[i] = seeds[i].max(1.0 / (1.0 + rank as f32));
+        }
+    }
+
+    let adjacency = Adjacency::build(entities, relations, &expansion.relation_types);
+    let scores = match expansion.algorithm {
+        ExpansionAlgorithm::Spreading => spreading_activation(&adjacency, &seeds, expansion),
+        ExpansionAlgorithm::PageRank => personalized_page_rank(&adjacency, &seeds, expansion),
+    };
+
+    let top = scores.iter().cloned().fold(0.0f32, f32::max);
+    let threshold = top * expansion.min_score;
+
+    let mut results: Vec<(usize, f32)> = scores
+        .into_iter()
+        .enumerate()
+        .filter(|&(i, score)| {
+            if seeds[i] > 0.0 {
+                return true;
+            }
+            score > 0.0 && score >= threshold && passes_type_filter(&entities[i], query)
+        })
+        .collect();
+    results.sort_by(|a, b| {
+        b.1.total_cmp(&a.1)
+            .then_with(|| entities[a.0].name.cmp(&entities[b.0].name))
+            .then_with(|| a.0.cmp(&b.0))
+    });
+
+    results
+        .into_iter()
+        .map(|(i, score)| SearchHit {
+            entity: entities[i].clone(),
+            score,
+        })
+        .collect()
+}
+
+/// Each hop passes `decay` times the activation on, split by edge weight
+fn spreading_activation(
+    adjacency: &Adjacency,
+    seeds: &[f32],
+    expansion: &GraphExpansion,
+) -> Vec<f32> {
+    let mut scores = seeds.to_vec();
+    let mut frontier = seeds.to_vec();
+
+    for _ in 0..expansion.max_hops {
+        let mut next = vec![0.0f32; seeds.len()];
+        for (node, &activation) in frontier.iter().enumerate() {
+            if activation <= 0.0 {
+                continue;
+            }
+            for &(neighbor, weight) in &adjacency.neighbors[node] {
+                next[neighbor] += activation * expansion.decay * adjacency.share(node, weight);
+            }
+        }
+        for (score, added) in scores.iter_mut().zip(&next) {
+            *score += added;
+        }
+        frontier = next;
+    }
+
+    scores
+}
+
+/// Power iteration of PageRank with restarts to the seed distribution
+fn personalized_page_rank(
+    adjacency: &Adjacency,
+    seeds: &[f32],
+    expansion: &GraphExpansion,
+) -> Vec<f32> {
+    let total: f32 = seeds.iter().sum();
+    if total <= 0.0 {
+        return vec![0.0; seeds.len()];
+    }
+    let restart: Vec<f32> = seeds.iter().map(|s| s / total).collect();
+    let damping = expansion.damping;
+
+    let mut rank = restart.clone();
+    for _ in 0..expansion.iterations {
+        let mut next: Vec<f32> = restart.iter().map(|r| r * (1.0 - damping)).collect();
+        let mut dangling = 0.0;
+        for (node, &mass) in rank.iter().enumerate() {
+            if adjacency.neighbors[node].is_empty() {
+                dangling += mass;
+                continue;
+            }
+            for &(neighbor, weight) in &adjacency.neighbors[node] {
+                next[neighbor] += damping * mass * adjacency.share(node, weight);
+            }
+        }
+        // Mass stuck on isolated nodes restarts at the seeds
+        for (value, r) in next.iter_mut().zip(&restart) {
+            *value += damping * dangling * r;
+        }
+        rank = next;
+    }
+
+    // Scale so the best node scores 1.0, comparable with the other scorers
+    let max = rank.iter().cloned().fold(0.0f32, f32::max);
+    if max > 0.0 {
+        rank.iter_mut().for_each(|r| *r /= max);
+    }
+    rank
+}
+
+fn passes_type_filter(entity: &Entity, query: &SearchQuery) -> bool {
+    query.entity_types.is_empty()
+        || query
+            .entity_types
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(&entity.entity_type.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExactSearchEngine, SearchEngine};
+    use parsnip_core::ProjectId;
+
+    /// Payments_Service <-on_call- Dana, Payments_Service -depends_on-> Ledger,
+    /// plus an unrelated Marketing_Site
+    fn create_graph() -> (Vec<Entity>, Vec<Relation>) {
+        let project_id = ProjectId::new();
+
+        let mut payments = Entity::new(project_id.clone(), "Payments_Service", "service");
+        payments.add_observation("Payments outage on Friday");
+        let mut dana = Entity::new(project_id.clone(), "Dana", "person");
+        dana.add_observation("Primary on-call engineer");
+        let ledger = Entity::new(project_id.clone(), "Ledger", "service");
+        let marketing = Entity::new(project_id.clone(), "Marketing_Site", "service");
+
+        let relations = vec![
+            Relation::from_names(project_id.clone(), "Dana", "Payments_Service", "on_call"),
+            Relation::from_names(
+                project_id.clone(),
+                "Payments_Service",
+                "Ledger",
+                "depends_on",
+            )
+            .with_weight(0.5),
+        ];
+
+        (vec![payments, dana, ledger, marketing], relations)
+    }
+
+    #[tokio::test]
+    async fn test_spreading_activation_surfaces_neighbors() {
+        let (entities, relations) = create_graph();
+        let query = SearchQuery::new("payments outage")
+            .with_graph_expansion(GraphExpansion::spreading(2, 0.5));
+
+        let hits = ExactSearchEngine::new()
+            .search_with_graph(&query, &entities, &relations)
+            .await
+            .unwrap();
+        let names: Vec<&str> = hits.iter().map(|h| h.entity.name.as_str()).collect();
+
+        // Dana has the heavier edge, so ranks above Ledger
+        assert_eq!(names, vec!["Payments_Service", "Dana", "Ledger"]);
+        assert!(hits[1].score > hits[2].score);
+    }
+
+    #[test]
+    fn test_page_rank_and_type_filter() {
+        let (entities, relations) = create_graph();
+        let query = SearchQuery::new("payments outage")
+            .with_entity_type("person")
+            .with_graph_expansion(GraphExpansion::page_rank(0.85));
+
+        // The type filter applies to the direct search too, so seed explicitly
+        let hits = expand_hits(
+            &query,
+            query.graph_expansion.as_ref().unwrap(),
+            &entities[..1],
+            &entities,
+            &relations,
+        );
+        let names: Vec<&str> = hits.iter().map(|h| h.entity.name.as_str()).collect();
+
+        assert_eq!(names, vec!["Payments_Service", "Dana"]);
+        assert!((hits[0].score - 1.0).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_without_expansion_returns_direct_hits() {
+        let (entities, relations) = create_graph();
+        let query = SearchQuery::new("payments outage");
+
+        let hits = ExactSearchEngine::new()
+            .search_with_graph(&query, &entities, &relations)
+            .await
+            .unwrap();
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entity.name, "Payments_Service");
+    }
+}
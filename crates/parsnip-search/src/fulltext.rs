@@ -4,9 +4,9 @@ use async_trait::async_trait;
 use std::path::Path;
 use std::sync::RwLock;
 use tantivy::{
-    collector::TopDocs,
+    collector::{DocSetCollector, TopDocs},
     directory::MmapDirectory,
-    query::QueryParser,
+    query::{AllQuery, QueryParser},
     schema::{Field, Schema, Value, STORED, STRING, TEXT},
     Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument,
 };
@@ -100,6 +100,30 @@ impl FullTextSearchEngine {
         })
     }
 
+    /// Entity ID of every document in the index, including duplicates
+    pub fn indexed_entity_ids(&self) -> Result<Vec<String>> {
+        self.reader
+            .reload()
+            .map_err(|e| SearchError::Index(e.to_string()))?;
+        let searcher = self.reader.searcher();
+        let addresses = searcher
+            .search(&AllQuery, &DocSetCollector)
+            .map_err(|e| SearchError::Query(e.to_string()))?;
+
+        let mut ids = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            let doc: TantivyDocument = searcher
+                .doc(address)
+                .map_err(|e| SearchError::Internal(e.to_string()))?;
+            if let Some(id) = doc.get_first(self.entity_id_field).and_then(|v| v.as_str()) {
+                ids.push(id.to_string());
+            }
+        }
+        ids.sort_unstable();
+
+        Ok(ids)
+    }
+
     fn create_document(&self, entity: &Entity) -> TantivyDocument {
         let mut doc = TantivyDocument::new();
         doc.add_text(self.entity_id_field, entity.id.to_string());
@@ -263,4 +287,20 @@ mod tests {
         assert!(!results.is_empty());
         assert_eq!(results[0].name, "John_Smith");
     }
+
+    #[tokio::test]
+    async fn test_indexed_entity_ids() {
+        let engine = FullTextSearchEngine::in_memory().unwrap();
+        let project_id = ProjectId::new();
+        let entity = parsnip_core::Entity::new(project_id.clone(), "Acme", "company");
+
+        engine.index_entity(&entity, &project_id).await.unwrap();
+        engine.index_entity(&entity, &project_id).await.unwrap();
+
+        // Re-indexing replaces the document by entity ID
+        assert_eq!(
+            engine.indexed_entity_ids().unwrap(),
+            vec![entity.id.to_string()]
+        );
+    }
 }
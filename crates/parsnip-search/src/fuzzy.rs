@@ -115,7 +115,7 @@ impl SearchEngine for FuzzySearchEngine {
             .collect();
 
         // Sort by score descending
-        scored.sort_by(|a, b| b.1.cmp(&a.1));
+        scored.sort_by_key(|s| std::cmp::Reverse(s.1));
 
         Ok(scored.into_iter().map(|(e, _)| e).collect())
     }
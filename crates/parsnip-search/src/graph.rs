@@ -0,0 +1,75 @@
+//! Entity adjacency shared by the graph-aware scorers
+
+use std::collections::HashMap;
+
+use parsnip_core::{Entity, NodeKey, ProjectId, Relation};
+
+/// Weighted, undirected adjacency over entity indices
+pub(crate) struct Adjacency {
+    /// Neighbor index and relation weight, one entry per relation
+    pub neighbors: Vec<Vec<(usize, f32)>>,
+    /// Resolved directed edges as (from, to, relation index)
+    pub edges: Vec<(usize, usize, usize)>,
+}
+
+impl Adjacency {
+    /// Build adjacency, keeping only the given relation types (empty = all)
+    pub fn build(entities: &[Entity], relations: &[Relation], relation_types: &[String]) -> Self {
+        let by_key: HashMap<NodeKey, usize> = entities
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (NodeKey::of(e), i))
+            .collect();
+        let by_name: HashMap<(&ProjectId, &str), usize> = entities
+            .iter()
+            .enumerate()
+            .map(|(i, e)| ((&e.project_id, e.name.as_str()), i))
+            .collect();
+
+        // Prefer IDs; fall back to names for relations created without them
+        let resolve = |project_id: &ProjectId, id, name: &str| {
+            by_key
+                .get(&NodeKey::new(project_id.clone(), id))
+                .or_else(|| by_name.get(&(project_id, name)))
+                .copied()
+        };
+
+        let mut neighbors = vec![Vec::new(); entities.len()];
+        let mut edges = Vec::new();
+        for (ri, rel) in relations.iter().enumerate() {
+            if !relation_types.is_empty() && !relation_types.contains(&rel.relation_type) {
+                continue;
+            }
+            let from = resolve(
+                rel.effective_from_project_id(),
+                rel.from_id.clone(),
+                &rel.from_name,
+            );
+            let to = resolve(
+                rel.effective_to_project_id(),
+                rel.to_id.clone(),
+                &rel.to_name,
+            );
+            if let (Some(from), Some(to)) = (from, to) {
+                if from != to {
+                    let weight = rel.weight.unwrap_or(1.0).max(0.0) as f32;
+                    neighbors[from].push((to, weight));
+                    neighbors[to].push((from, weight));
+                    edges.push((from, to, ri));
+                }
+            }
+        }
+
+        Self { neighbors, edges }
+    }
+
+    /// Fraction of a node's outgoing mass that flows along one edge
+    pub fn share(&self, node: usize, weight: f32) -> f32 {
+        let total: f32 = self.neighbors[node].iter().map(|(_, w)| w).sum();
+        if total > 0.0 {
+            weight / total
+        } else {
+            0.0
+        }
+    }
+}
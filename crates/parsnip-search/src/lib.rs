@@ -1,9 +1,14 @@
 //! Parsnip Search - Search engines for knowledge graph
 //!
-//! Provides exact search, fuzzy search (nucleo), full-text search (tantivy), vector search.
+//! Provides exact search, fuzzy search (nucleo), full-text search (tantivy), vector search,
+//! graph-aware re-ranking, and token-budgeted context packs built on top of them.
 
+pub mod context;
 pub mod error;
 pub mod exact;
+pub mod expansion;
+mod graph;
+pub mod related;
 pub mod traits;
 
 #[cfg(feature = "fuzzy")]
@@ -18,8 +23,14 @@ pub mod hybrid;
 #[cfg(feature = "vector")]
 pub mod vector;
 
+pub use context::{
+    CharTokenEstimator, ContextBuilder, ContextEntity, ContextPack, ContextQuery, ContextRelation,
+    TokenEstimator, WordTokenEstimator, DEFAULT_CONTEXT_BUDGET, MAX_CONTEXT_HOPS,
+};
 pub use error::{SearchError, SearchResult};
 pub use exact::ExactSearchEngine;
+pub use expansion::expand_hits;
+pub use related::{LinkPredictor, LinkSuggestion, LinkWeights, RelationTypeSuggestion};
 pub use traits::{SearchEngine, SearchHit};
 
 #[cfg(feature = "fuzzy")]
@@ -0,0 +1,416 @@
+//! Related-entity recommendations and link prediction
+//!
+//! Scores entities not yet connected to a target by graph proximity (common
+//! neighbors, Adamic–Adar), shared tags and text similarity, and suggests
+//! relation types seen between similarly typed entities.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::graph::Adjacency;
+use parsnip_core::{Direction, Entity, NodeKey, Relation};
+
+/// Relative weight of each signal in the combined score
+#[derive(Debug, Clone, Copy)]
+pub struct LinkWeights {
+    pub common_neighbors: f32,
+    pub adamic_adar: f32,
+    pub shared_tags: f32,
+    pub text_similarity: f32,
+}
+
+impl Default for LinkWeights {
+    fn default() -> Self {
+        Self {
+            common_neighbors: 0.2,
+            adamic_adar: 0.35,
+            shared_tags: 0.15,
+            text_similarity: 0.3,
+        }
+    }
+}
+
+/// A relation type observed between entities of the same types
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationTypeSuggestion {
+    pub relation_type: String,
+    /// Outgoing: target -> candidate, Incoming: candidate -> target
+    pub direction: Direction,
+    /// Number of existing edges supporting this suggestion
+    pub support: usize,
+}
+
+/// A not-yet-connected entity that is probably related to the target
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkSuggestion {
+    pub entity: Entity,
+    pub score: f32,
+    pub common_neighbors: Vec<String>,
+    pub adamic_adar: f32,
+    pub shared_tags: Vec<String>,
+    pub text_similarity: f32,
+    pub relation_types: Vec<RelationTypeSuggestion>,
+}
+
+/// Link predictor over an in-memory graph
+#[derive(Debug, Clone, Default)]
+pub struct LinkPredictor {
+    weights: LinkWeights,
+    min_score: f32,
+}
+
+/// Raw signals for one candidate before normalization
+struct Signals {
+    index: usize,
+    common: Vec<usize>,
+    adamic_adar: f32,
+    tags: Vec<String>,
+    tag_jaccard: f32,
+    text: f32,
+}
+
+impl LinkPredictor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set signal weights
+    pub fn with_weights(mut self, weights: LinkWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Drop suggestions scoring below this value (0.0-1.0)
+    pub fn with_min_score(mut self, min_score: f32) -> Self {
+        self.min_score = min_score.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Suggest up to `limit` entities to link with `target`
+    ///
+    /// Candidates are the other entities in `entities` that share no relation
+    /// with the target. Pass the entities and relations of the target's project
+    /// (or of several projects for cross-project suggestions).
+    pub fn suggest(
+        &self,
+        target: &Entity,
+        entities: &[Entity],
+        relations: &[Relation],
+        limit: usize,
+    ) -> Vec<LinkSuggestion> {
+        let target_key = NodeKey::of(target);
+        let Some(t) = entities.iter().position(|e| NodeKey::of(e) == target_key) else {
+            return Vec::new();
+        };
+
+        let adjacency = Adjacency::build(entities, relations, &[]);
+        let neighbor_sets: Vec<HashSet<usize>> = adjacency
+            .neighbors
+            .iter()
+            .map(|n| n.iter().map(|&(i, _)| i).collect())
+            .collect();
+        let text_scores = text_similarity(t, entities);
+
+        let target_tags: HashSet<String> = target.tags.iter().map(|t| t.to_lowercase()).collect();
+
+        let mut signals: Vec<Signals> = Vec::new();
+        for (i, candidate) in entities.iter().enumerate() {
+            if i == t || neighbor_sets[t].contains(&i) {
+                continue;
+            }
+
+            let mut common: Vec<usize> = neighbor_sets[t]
+                .intersection(&neighbor_sets[i])
+                .copied()
+                .collect();
+            common.sort_by(|a, b| entities[*a].name.cmp(&entities[*b].name));
+            let adamic_adar: f32 = common
+                .iter()
+                .map(|&z| 1.0 / (neighbor_sets[z].len().max(2) as f32).ln())
+                .sum();
+
+            let candidate_tags: HashSet<String> =
+                candidate.tags.iter().map(|t| t.to_lowercase()).collect();
+            let mut tags: Vec<String> =
+                target_tags.intersection(&candidate_tags).cloned().collect();
+            tags.sort();
+            let union = target_tags.union(&candidate_tags).count();
+            let tag_jaccard = if union > 0 {
+                tags.len() as f32 / union as f32
+            } else {
+                0.0
+            };
+
+            signals.push(Signals {
+                index: i,
+                common,
+                adamic_adar,
+                tags,
+                tag_jaccard,
+                text: text_scores[i],
+            });
+        }
+
+        // Normalize unbounded signals against the best candidate
+        let max_common = signals.iter().map(|s| s.common.len()).max().unwrap_or(0) as f32;
+        let max_aa = signals.iter().map(|s| s.adamic_adar).fold(0.0, f32::max);
+        let norm = |value: f32, max: f32| if max > 0.0 { value / max } else { 0.0 };
+
+        let relation_types = RelationTypeModel::learn(entities, relations, &adjacency);
+
+        let mut suggestions: Vec<LinkSuggestion> = signals
+            .into_iter()
+            .map(|s| {
+                let score = self.weights.common_neighbors * norm(s.common.len() as f32, max_common)
+                    + self.weights.adamic_adar * norm(s.adamic_adar, max_aa)
+                    + self.weights.shared_tags * s.tag_jaccard
+                    + self.weights.text_similarity * s.text;
+                (s, score)
+            })
+            .filter(|(_, score)| *score > 0.0 && *score >= self.min_score)
+            .map(|(s, score)| LinkSuggestion {
+                entity: entities[s.index].clone(),
+                score,
+                common_neighbors: s.common.iter().map(|&z| entities[z].name.clone()).collect(),
+                adamic_adar: s.adamic_adar,
+                shared_tags: s.tags,
+                text_similarity: s.text,
+                relation_types: relation_types.suggest(target, &entities[s.index]),
+            })
+            .collect();
+
+        suggestions.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.entity.name.cmp(&b.entity.name))
+        });
+        suggestions.truncate(limit);
+        suggestions
+    }
+}
+
+/// Relation type counts between entity types, learned from existing edges
+struct RelationTypeModel {
+    /// (from type, to type) -> relation type -> count
+    by_types: HashMap<(String, String), BTreeMap<String, usize>>,
+}
+
+impl RelationTypeModel {
+    fn learn(entities: &[Entity], relations: &[Relation], adjacency: &Adjacency) -> Self {
+        let mut by_types: HashMap<(String, String), BTreeMap<String, usize>> = HashMap::new();
+        for &(from, to, ri) in &adjacency.edges {
+            let key = (
+                entities[from].entity_type.0.to_lowercase(),
+                entities[to].entity_type.0.to_lowercase(),
+            );
+            *by_types
+                .entry(key)
+                .or_default()
+                .entry(relations[ri].relation_type.clone())
+                .or_default() += 1;
+        }
+        Self { by_types }
+    }
+
+    fn suggest(&self, target: &Entity, candidate: &Entity) -> Vec<RelationTypeSuggestion> {
+        let t = target.entity_type.0.to_lowercase();
+        let c = candidate.entity_type.0.to_lowercase();
+
+        // Same types: the learned counts already cover both directions
+        let mut keys = vec![((t.clone(), c.clone()), Direction::Outgoing)];
+        if t != c {
+            keys.push(((c, t), Direction::Incoming));
+        }
+
+        let mut suggestions = Vec::new();
+        for (key, direction) in keys {
+            if let Some(counts) = self.by_types.get(&key) {
+                suggestions.extend(counts.iter().map(|(relation_type, &support)| {
+                    RelationTypeSuggestion {
+                        relation_type: relation_type.clone(),
+                        direction,
+                        support,
+                    }
+                }));
+            }
+        }
+
+        // Stable sort keeps outgoing before incoming on ties
+        suggestions.sort_by_key(|s| std::cmp::Reverse(s.support));
+        suggestions.truncate(3);
+        suggestions
+    }
+}
+
+/// Similarity of every entity to the target, in 0.0-1.0
+///
+/// Uses embedding cosine similarity when both entities have embeddings,
+/// otherwise BM25 with the target's text as the query, normalized by the best
+/// candidate.
+fn text_similarity(target: usize, entities: &[Entity]) -> Vec<f32> {
+    const K1: f32 = 1.2;
+    const B: f32 = 0.75;
+
+    let docs: Vec<Vec<String>> = entities.iter().map(tokenize_entity).collect();
+    let n = docs.len() as f32;
+    let avg_len = docs.iter().map(|d| d.len()).sum::<usize>() as f32 / n.max(1.0);
+
+    let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+    for doc in &docs {
+        let unique: HashSet<&str> = doc.iter().map(String::as_str).collect();
+        for term in unique {
+            *doc_freq.entry(term).or_default() += 1;
+        }
+    }
+
+    let query_terms: HashSet<&str> = docs[target].iter().map(String::as_str).collect();
+
+    let mut bm25: Vec<f32> = docs
+        .iter()
+        .map(|doc| {
+            let mut tf: HashMap<&str, usize> = HashMap::new();
+            for term in doc {
+                *tf.entry(term.as_str()).or_default() += 1;
+            }
+            let len_norm = 1.0 - B + B * doc.len() as f32 / avg_len.max(1.0);
+            query_terms
+                .iter()
+                .filter_map(|term| tf.get(term).map(|&f| (term, f as f32)))
+                .map(|(term, f)| {
+                    let df = doc_freq.get(term).copied().unwrap_or(0) as f32;
+                    let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    idf * f * (K1 + 1.0) / (f + K1 * len_norm)
+                })
+                .sum()
+        })
+        .collect();
+    bm25[target] = 0.0;
+
+    let max = bm25.iter().cloned().fold(0.0f32, f32::max);
+    entities
+        .iter()
+        .enumerate()
+        .map(
+            |(i, entity)| match (&entities[target].embedding, &entity.embedding) {
+                (Some(a), Some(b)) if i != target => cosine_similarity(a, b).max(0.0),
+                _ if max > 0.0 => bm25[i] / max,
+                _ => 0.0,
+            },
+        )
+        .collect()
+}
+
+fn tokenize_entity(entity: &Entity) -> Vec<String> {
+    let mut text = entity.name.replace('_', " ");
+    for obs in &entity.observations {
+        text.push(' ');
+        text.push_str(&obs.content);
+    }
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.len() > 2)
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsnip_core::ProjectId;
+
+    /// Alice and Carol both know Bob and Dan; Alice -works_at-> Acme;
+    /// Carol is not yet linked to Acme
+    fn create_graph() -> (Vec<Entity>, Vec<Relation>) {
+        let project_id = ProjectId::new();
+        let entity = |name: &str, entity_type: &str, obs: &str, tags: &[&str]| {
+            let mut e = Entity::new(project_id.clone(), name, entity_type);
+            e.add_observation(obs);
+            for tag in tags {
+                e.add_tag(*tag);
+            }
+            e
+        };
+
+        let entities = vec![
+            entity("Alice", "person", "Rust compiler engineer", &["rust"]),
+            entity("Bob", "person", "Product manager", &[]),
+            entity("Carol", "person", "Works on the Rust compiler", &["rust"]),
+            entity("Dan", "person", "Designer", &[]),
+            entity("Acme", "company", "Rust developer tools company", &[]),
+            entity("Globex", "company", "Sells widgets", &[]),
+        ];
+
+        let relations = vec![
+            Relation::from_names(project_id.clone(), "Alice", "Bob", "knows"),
+            Relation::from_names(project_id.clone(), "Alice", "Dan", "knows"),
+            Relation::from_names(project_id.clone(), "Carol", "Bob", "knows"),
+            Relation::from_names(project_id.clone(), "Carol", "Dan", "knows"),
+            Relation::from_names(project_id.clone(), "Alice", "Acme", "works_at"),
+        ];
+
+        (entities, relations)
+    }
+
+    #[test]
+    fn test_suggests_unlinked_similar_entity() {
+        let (entities, relations) = create_graph();
+        let suggestions = LinkPredictor::new().suggest(&entities[0], &entities, &relations, 5);
+
+        // Bob, Dan and Acme are already linked to Alice
+        let names: Vec<&str> = suggestions.iter().map(|s| s.entity.name.as_str()).collect();
+        assert_eq!(names[0], "Carol");
+        assert!(!names.contains(&"Bob"));
+        assert!(!names.contains(&"Acme"));
+
+        let carol = &suggestions[0];
+        assert_eq!(carol.common_neighbors, vec!["Bob", "Dan"]);
+        assert_eq!(carol.shared_tags, vec!["rust"]);
+        assert!(carol.text_similarity > 0.0);
+        assert_eq!(carol.relation_types[0].relation_type, "knows");
+        assert_eq!(carol.relation_types[0].support, 4);
+    }
+
+    #[test]
+    fn test_relation_types_follow_entity_types() {
+        let (entities, relations) = create_graph();
+
+        // Carol (person) -> Acme (company) should suggest works_at
+        let suggestions = LinkPredictor::new().suggest(&entities[2], &entities, &relations, 10);
+        let acme = suggestions
+            .iter()
+            .find(|s| s.entity.name == "Acme")
+            .unwrap();
+        assert_eq!(acme.relation_types[0].relation_type, "works_at");
+        assert_eq!(acme.relation_types[0].direction, Direction::Outgoing);
+    }
+
+    #[test]
+    fn test_min_score_and_limit() {
+        let (entities, relations) = create_graph();
+        let suggestions = LinkPredictor::new().with_min_score(0.5).suggest(
+            &entities[0],
+            &entities,
+            &relations,
+            5,
+        );
+        let names: Vec<&str> = suggestions.iter().map(|s| s.entity.name.as_str()).collect();
+        assert_eq!(names, vec!["Carol"]);
+
+        let suggestions = LinkPredictor::new().suggest(&entities[0], &entities, &relations, 1);
+        assert_eq!(suggestions.len(), 1);
+    }
+}
@@ -1,7 +1,7 @@
 //! Search engine traits
 
 use async_trait::async_trait;
-use parsnip_core::{Entity, ProjectId, SearchQuery};
+use parsnip_core::{Entity, Pagination, ProjectId, Relation, SearchQuery};
 
 pub use crate::error::{SearchError, SearchResult as Result};
 
@@ -18,6 +18,44 @@ pub trait SearchEngine: Send + Sync {
     /// Search entities based on query
     async fn search(&self, query: &SearchQuery, entities: &[Entity]) -> Result<Vec<Entity>>;
 
+    /// Search, then propagate scores to graph neighbors when `query.graph_expansion` is set
+    ///
+    /// Without expansion, hits are scored by rank. With expansion, the direct
+    /// search runs unpaginated and the query's pagination applies to the
+    /// re-ranked results.
+    async fn search_with_graph(
+        &self,
+        query: &SearchQuery,
+        entities: &[Entity],
+        relations: &[Relation],
+    ) -> Result<Vec<SearchHit>> {
+        let Some(ref expansion) = query.graph_expansion else {
+            let results = self.search(query, entities).await?;
+            return Ok(results
+                .into_iter()
+                .enumerate()
+                .map(|(rank, entity)| SearchHit {
+                    entity,
+                    score: 1.0 / (1.0 + rank as f32),
+                })
+                .collect());
+        };
+
+        let mut direct = query.clone();
+        direct.pagination = Pagination {
+            page: 0,
+            page_size: entities.len().max(1),
+        };
+        let hits = self.search(&direct, entities).await?;
+        let expanded = crate::expansion::expand_hits(query, expansion, &hits, entities, relations);
+
+        Ok(expanded
+            .into_iter()
+            .skip(query.pagination.offset())
+            .take(query.pagination.page_size)
+            .collect())
+    }
+
     /// Index an entity (optional for stateless engines)
     async fn index_entity(&self, _entity: &Entity, _project_id: &ProjectId) -> Result<()> {
         Ok(())
@@ -0,0 +1,122 @@
+//! Record encodings compared: decode time, project load time and file size
+//!
+//! Run with `cargo bench -p parsnip-storage --features sqlite,zstd --bench encoding`;
+//! the file sizes are printed before the timings.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use parsnip_core::{Entity, Project, ProjectId, Relation};
+use parsnip_storage::{RecordFormat, RedbStorage, StorageBackend, StoredRecord};
+use tokio::runtime::Runtime;
+
+const ENTITIES: usize = 2_000;
+const OBSERVATIONS: usize = 8;
+
+fn formats() -> Vec<RecordFormat> {
+    [
+        RecordFormat::Json,
+        RecordFormat::Binary,
+        RecordFormat::Compressed,
+    ]
+    .into_iter()
+    .filter(|format| format.is_available())
+    .collect()
+}
+
+/// An entity with observations the length of a note taken in a session
+fn entity(project_id: &ProjectId, i: usize) -> Entity {
+    let mut entity = Entity::new(project_id.clone(), format!("Entity {}", i), "concept");
+    for j in 0..OBSERVATIONS {
+        entity.add_observation(format!(
+            "Observation {} of entity {}: the storage layer keeps every record of a \
+             project in one table, keyed by project and name, so a search loads them all",
+            j, i
+        ));
+    }
+    entity.tags = vec!["bench".to_string(), format!("group-{}", i % 10)];
+    entity
+}
+
+fn graph(project: &Project) -> (Vec<Entity>, Vec<Relation>) {
+    let entities: Vec<Entity> = (0..ENTITIES).map(|i| entity(&project.id, i)).collect();
+    let relations = entities
+        .windows(2)
+        .map(|pair| {
+            Relation::from_names(project.id.clone(), &pair[0].name, &pair[1].name, "follows")
+        })
+        .collect();
+    (entities, relations)
+}
+
+async fn fill(storage: &dyn StorageBackend, project: &Project) {
+    let (entities, relations) = graph(project);
+    storage.save_project(project).await.unwrap();
+    storage.save_entities_batch(&entities).await.unwrap();
+    storage.save_relations_batch(&relations).await.unwrap();
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let project = Project::new("bench");
+    let entity = entity(&project.id, 0);
+    let mut group = c.benchmark_group("decode_entity");
+    for format in formats() {
+        let bytes = entity.encode(format).unwrap();
+        println!("entity as {}: {} bytes", format, bytes.len());
+        group.bench_function(BenchmarkId::from_parameter(format), |b| {
+            b.iter(|| Entity::decode(black_box(&bytes)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_load(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let project = Project::new("bench");
+
+    let mut stores: Vec<(String, Arc<dyn StorageBackend>)> = Vec::new();
+    for format in formats() {
+        let path = dir.path().join(format!("{}.redb", format));
+        let storage = Arc::new(RedbStorage::open_with_format(&path, format).unwrap());
+        rt.block_on(fill(storage.as_ref(), &project));
+        println!("redb as {}: {} bytes", format, file_size(&path));
+        stores.push((format!("redb/{}", format), storage));
+
+        #[cfg(feature = "sqlite")]
+        {
+            let path = dir.path().join(format!("{}.sqlite", format));
+            let storage =
+                Arc::new(parsnip_storage::SqliteStorage::open_with_format(&path, format).unwrap());
+            rt.block_on(fill(storage.as_ref(), &project));
+            // Counts what is still in the write-ahead log too
+            let wal = path.with_extension("sqlite-wal");
+            println!(
+                "sqlite as {}: {} bytes",
+                format,
+                file_size(&path) + file_size(&wal)
+            );
+            stores.push((format!("sqlite/{}", format), storage));
+        }
+    }
+
+    let mut group = c.benchmark_group("get_all_entities");
+    group.sample_size(20);
+    for (name, storage) in &stores {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                let entities = rt.block_on(storage.get_all_entities(&project.id)).unwrap();
+                assert_eq!(entities.len(), ENTITIES);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode, bench_load);
+criterion_main!(benches);
@@ -0,0 +1,552 @@
+//! Audit log of writes, attributed to whoever made them
+//!
+//! [`AuditedStorage`] wraps a backend for one caller, an [`Actor`]: the
+//! transport the caller came in on, the name its MCP client gave and the label
+//! of the token it authenticated with. It notes the IDs of every record the
+//! caller writes, and [`AuditedStorage::record`] appends them to the log as one
+//! [`AuditEntry`] along with the operation and its arguments. Backends only
+//! ever append entries; none can be changed or removed.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parsnip_core::{Entity, Graph, Project, ProjectId, Relation};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::changelog::ChangeRecord;
+use crate::doctor::{RecordScan, RepairPlan};
+use crate::error::StorageResult;
+use crate::traits::StorageBackend;
+
+/// How a write reached the storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Cli,
+    Stdio,
+    Sse,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cli => write!(f, "cli"),
+            Self::Stdio => write!(f, "stdio"),
+            Self::Sse => write!(f, "sse"),
+        }
+    }
+}
+
+/// Who made a write
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Actor {
+    pub transport: Transport,
+    /// Name the MCP client declared in `initialize`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Label of the token the request authenticated with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Actor {
+    pub fn new(transport: Transport) -> Self {
+        Self {
+            transport,
+            client: None,
+            token: None,
+        }
+    }
+
+    pub fn with_client(mut self, client: impl Into<String>) -> Self {
+        self.client = Some(client.into());
+        self
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Whether `name` is the client's name, the token's label or the transport
+    pub fn is(&self, name: &str) -> bool {
+        self.client.as_deref() == Some(name)
+            || self.token.as_deref() == Some(name)
+            || self.transport.to_string() == name
+    }
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.transport)?;
+        if let Some(client) = &self.client {
+            write!(f, " client={}", client)?;
+        }
+        if let Some(token) = &self.token {
+            write!(f, " token={}", token)?;
+        }
+        Ok(())
+    }
+}
+
+/// One operation that wrote to the storage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, starting at 1; assigned by the backend
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Tool or command that made the writes
+    pub operation: String,
+    pub arguments: serde_json::Value,
+    /// IDs of the projects, entities and relations written or deleted
+    pub affected: Vec<String>,
+    pub actor: Actor,
+}
+
+impl AuditEntry {
+    /// An entry to append, timestamped now
+    pub fn new(
+        operation: impl Into<String>,
+        arguments: serde_json::Value,
+        affected: Vec<String>,
+        actor: Actor,
+    ) -> Self {
+        Self {
+            seq: 0,
+            timestamp: Utc::now(),
+            operation: operation.into(),
+            arguments,
+            affected,
+            actor,
+        }
+    }
+}
+
+/// Which audit entries to list
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditFilter {
+    /// Only entries at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries of this client, token or transport (see [`Actor::is`])
+    pub actor: Option<String>,
+    pub operation: Option<String>,
+    /// Only the most recent entries, at most this many
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    pub fn with_operation(mut self, operation: impl Into<String>) -> Self {
+        self.operation = Some(operation.into());
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        let too_old = self.since.is_some_and(|since| entry.timestamp < since);
+        let other_actor = self
+            .actor
+            .as_deref()
+            .is_some_and(|actor| !entry.actor.is(actor));
+        let other_operation = self
+            .operation
+            .as_deref()
+            .is_some_and(|operation| entry.operation != operation);
+        !(too_old || other_actor || other_operation)
+    }
+
+    /// The entries passing the filter, oldest first
+    pub fn apply(&self, entries: impl IntoIterator<Item = AuditEntry>) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> =
+            entries.into_iter().filter(|e| self.matches(e)).collect();
+        if let Some(limit) = self.limit {
+            let excess = entries.len().saturating_sub(limit);
+            entries.drain(..excess);
+        }
+        entries
+    }
+}
+
+/// A backend noting what one caller writes
+///
+/// Deletes look the records up first, so their IDs can be noted; deleting
+/// something that does not exist notes nothing.
+pub struct AuditedStorage<S: StorageBackend + ?Sized> {
+    inner: Arc<S>,
+    actor: Actor,
+    /// IDs written since the last [`Self::record`]
+    affected: Mutex<BTreeSet<String>>,
+}
+
+impl<S: StorageBackend + ?Sized> AuditedStorage<S> {
+    pub fn new(inner: Arc<S>, actor: Actor) -> Self {
+        Self {
+            inner,
+            actor,
+            affected: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn inner(&self) -> &Arc<S> {
+        &self.inner
+    }
+
+    pub fn actor(&self) -> &Actor {
+        &self.actor
+    }
+
+    fn note<I, T>(&self, ids: I)
+    where
+        I: IntoIterator<Item = T>,
+        T: ToString,
+    {
+        self.affected
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(ids.into_iter().map(|id| id.to_string()));
+    }
+
+    /// Append the writes noted so far to the log as one operation
+    ///
+    /// Returns the appended entry, or `None` when nothing was written.
+    pub async fn record(
+        &self,
+        operation: &str,
+        arguments: serde_json::Value,
+    ) -> StorageResult<Option<AuditEntry>> {
+        let affected =
+            std::mem::take(&mut *self.affected.lock().unwrap_or_else(|e| e.into_inner()));
+        if affected.is_empty() {
+            return Ok(None);
+        }
+        let mut entry = AuditEntry::new(
+            operation,
+            arguments,
+            affected.into_iter().collect(),
+            self.actor.clone(),
+        );
+        entry.seq = self.inner.append_audit(&entry).await?;
+        tracing::debug!(
+            "Audit #{}: {} by {} ({} records)",
+            entry.seq,
+            entry.operation,
+            entry.actor,
+            entry.affected.len()
+        );
+        Ok(Some(entry))
+    }
+}
+
+#[async_trait]
+impl<S: StorageBackend + ?Sized> StorageBackend for AuditedStorage<S> {
+    async fn initialize(&self) -> StorageResult<()> {
+        self.inner.initialize().await
+    }
+
+    async fn close(&self) -> StorageResult<()> {
+        self.inner.close().await
+    }
+
+    async fn health_check(&self) -> StorageResult<bool> {
+        self.inner.health_check().await
+    }
+
+    async fn save_entity(&self, entity: &Entity) -> StorageResult<()> {
+        self.inner.save_entity(entity).await?;
+        self.note([&entity.id]);
+        Ok(())
+    }
+
+    async fn get_entity(
+        &self,
+        name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Option<Entity>> {
+        self.inner.get_entity(name, project_id).await
+    }
+
+    async fn get_all_entities(&self, project_id: &ProjectId) -> StorageResult<Vec<Entity>> {
+        self.inner.get_all_entities(project_id).await
+    }
+
+    async fn get_all_entities_all_projects(&self) -> StorageResult<Vec<Entity>> {
+        self.inner.get_all_entities_all_projects().await
+    }
+
+    async fn delete_entity(&self, name: &str, project_id: &ProjectId) -> StorageResult<()> {
+        let existing = self.inner.get_entity(name, project_id).await?;
+        self.inner.delete_entity(name, project_id).await?;
+        self.note(existing.map(|e| e.id));
+        Ok(())
+    }
+
+    async fn save_relation(&self, relation: &Relation) -> StorageResult<()> {
+        self.inner.save_relation(relation).await?;
+        self.note([&relation.id]);
+        Ok(())
+    }
+
+    async fn get_relations_for_entity(
+        &self,
+        entity_name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Vec<Relation>> {
+        self.inner
+            .get_relations_for_entity(entity_name, project_id)
+            .await
+    }
+
+    async fn get_all_relations(&self, project_id: &ProjectId) -> StorageResult<Vec<Relation>> {
+        self.inner.get_all_relations(project_id).await
+    }
+
+    async fn get_all_relations_all_projects(&self) -> StorageResult<Vec<Relation>> {
+        self.inner.get_all_relations_all_projects().await
+    }
+
+    async fn get_relations_for_entity_global(
+        &self,
+        entity_name: &str,
+    ) -> StorageResult<Vec<Relation>> {
+        self.inner
+            .get_relations_for_entity_global(entity_name)
+            .await
+    }
+
+    async fn delete_relation(
+        &self,
+        from: &str,
+        to: &str,
+        relation_type: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<()> {
+        let existing: Vec<Relation> = self
+            .inner
+            .get_relations_for_entity(from, project_id)
+            .await?
+            .into_iter()
+            .filter(|r| r.from_name == from && r.to_name == to && r.relation_type == relation_type)
+            .collect();
+        self.inner
+            .delete_relation(from, to, relation_type, project_id)
+            .await?;
+        self.note(existing.iter().map(|r| &r.id));
+        Ok(())
+    }
+
+    async fn delete_relations_for_entity(
+        &self,
+        entity_name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<()> {
+        let existing = self
+            .inner
+            .get_relations_for_entity(entity_name, project_id)
+            .await?;
+        self.inner
+            .delete_relations_for_entity(entity_name, project_id)
+            .await?;
+        self.note(existing.iter().map(|r| &r.id));
+        Ok(())
+    }
+
+    async fn save_project(&self, project: &Project) -> StorageResult<()> {
+        self.inner.save_project(project).await?;
+        self.note([&project.id]);
+        Ok(())
+    }
+
+    async fn get_project(&self, name: &str) -> StorageResult<Option<Project>> {
+        self.inner.get_project(name).await
+    }
+
+    async fn get_project_by_id(&self, id: &ProjectId) -> StorageResult<Option<Project>> {
+        self.inner.get_project_by_id(id).await
+    }
+
+    async fn get_all_projects(&self) -> StorageResult<Vec<Project>> {
+        self.inner.get_all_projects().await
+    }
+
+    /// Notes the project and everything in it
+    async fn delete_project(&self, name: &str) -> StorageResult<()> {
+        let Some(project) = self.inner.get_project(name).await? else {
+            return self.inner.delete_project(name).await;
+        };
+        let entities = self.inner.get_all_entities(&project.id).await?;
+        let relations = self.inner.get_all_relations(&project.id).await?;
+        self.inner.delete_project(name).await?;
+        self.note([&project.id]);
+        self.note(entities.iter().map(|e| &e.id));
+        self.note(relations.iter().map(|r| &r.id));
+        Ok(())
+    }
+
+    async fn load_graph(&self, project_id: &ProjectId) -> StorageResult<Graph> {
+        self.inner.load_graph(project_id).await
+    }
+
+    async fn save_graph(&self, graph: &Graph, project_id: &ProjectId) -> StorageResult<()> {
+        self.inner.save_graph(graph, project_id).await?;
+        self.note(graph.entities.iter().map(|e| &e.id));
+        self.note(graph.relations.iter().map(|r| &r.id));
+        Ok(())
+    }
+
+    async fn save_entities_batch(&self, entities: &[Entity]) -> StorageResult<()> {
+        self.inner.save_entities_batch(entities).await?;
+        self.note(entities.iter().map(|e| &e.id));
+        Ok(())
+    }
+
+    async fn save_relations_batch(&self, relations: &[Relation]) -> StorageResult<()> {
+        self.inner.save_relations_batch(relations).await?;
+        self.note(relations.iter().map(|r| &r.id));
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> StorageResult<RecordScan> {
+        self.inner.scan_records().await
+    }
+
+    /// Notes saved and deleted records; removed raw records have no ID
+    async fn apply_repair(&self, plan: &RepairPlan) -> StorageResult<()> {
+        self.inner.apply_repair(plan).await?;
+        self.note(plan.save_entities.iter().map(|e| &e.id));
+        self.note(
+            plan.save_relations
+                .iter()
+                .chain(&plan.delete_relations)
+                .map(|r| &r.id),
+        );
+        Ok(())
+    }
+
+    async fn append_audit(&self, entry: &AuditEntry) -> StorageResult<u64> {
+        self.inner.append_audit(entry).await
+    }
+
+    async fn audit_entries(&self, filter: &AuditFilter) -> StorageResult<Vec<AuditEntry>> {
+        self.inner.audit_entries(filter).await
+    }
+
+    async fn changes_since(&self, after: u64, limit: usize) -> StorageResult<Vec<ChangeRecord>> {
+        self.inner.changes_since(after, limit).await
+    }
+
+    async fn latest_change(&self) -> StorageResult<u64> {
+        self.inner.latest_change().await
+    }
+
+    fn change_signal(&self) -> Option<watch::Receiver<u64>> {
+        self.inner.change_signal()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_audited_storage_records_writes() {
+        let storage = Arc::new(MemoryStorage::new());
+        let actor = Actor::new(Transport::Sse)
+            .with_client("claude-desktop")
+            .with_token("laptop");
+        let audited = AuditedStorage::new(storage.clone(), actor);
+
+        let project = Project::new("work");
+        audited.save_project(&project).await.unwrap();
+        let alice = Entity::new(project.id.clone(), "Alice", "person");
+        audited.save_entity(&alice).await.unwrap();
+        // Reads and deletes of missing records note nothing
+        audited.get_entity("Alice", &project.id).await.unwrap();
+        audited.delete_entity("Bob", &project.id).await.unwrap();
+
+        let entry = audited
+            .record("create_entities", serde_json::json!({"name": "Alice"}))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.seq, 1);
+        assert_eq!(entry.affected.len(), 2);
+        assert!(entry.affected.contains(&alice.id.to_string()));
+        // Nothing written since
+        assert!(audited
+            .record("search_knowledge", serde_json::Value::Null)
+            .await
+            .unwrap()
+            .is_none());
+
+        audited.delete_project("work").await.unwrap();
+        let entry = audited
+            .record("delete_project", serde_json::Value::Null)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.seq, 2);
+        assert!(entry.affected.contains(&alice.id.to_string()));
+
+        let entries = storage.audit_entries(&AuditFilter::new()).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].actor.client.as_deref(), Some("claude-desktop"));
+    }
+
+    #[test]
+    fn test_audit_filter() {
+        let entry = |operation: &str, client: &str, minutes_ago: i64| {
+            let mut entry = AuditEntry::new(
+                operation,
+                serde_json::Value::Null,
+                Vec::new(),
+                Actor::new(Transport::Stdio).with_client(client),
+            );
+            entry.timestamp = Utc::now() - chrono::Duration::minutes(minutes_ago);
+            entry
+        };
+        let entries = vec![
+            entry("create_entities", "cursor", 90),
+            entry("delete_entities", "claude-desktop", 30),
+            entry("add_observations", "claude-desktop", 10),
+            entry("create_entities", "claude-desktop", 5),
+        ];
+
+        let recent = AuditFilter::new().with_since(Utc::now() - chrono::Duration::hours(1));
+        assert_eq!(recent.apply(entries.clone()).len(), 3);
+        let desktop = AuditFilter::new().with_actor("claude-desktop");
+        assert_eq!(
+            desktop.clone().with_limit(2).apply(entries.clone())[0].operation,
+            "add_observations"
+        );
+        assert!(AuditFilter::new().with_actor("stdio").matches(&entries[0]));
+        assert_eq!(
+            AuditFilter::new()
+                .with_operation("create_entities")
+                .apply(entries)
+                .len(),
+            2
+        );
+    }
+}
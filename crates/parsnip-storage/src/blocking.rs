@@ -0,0 +1,15 @@
+//! Running database work off the async worker threads
+
+use crate::error::{StorageError, StorageResult};
+
+/// Run blocking database work on tokio's blocking pool, so a slow query or
+/// a writer waiting for its turn never stalls the tasks of other requests
+pub(crate) async fn run<T, F>(f: F) -> StorageResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> StorageResult<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| StorageError::Database(format!("Storage task failed: {}", e)))?
+}
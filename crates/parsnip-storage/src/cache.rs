@@ -0,0 +1,885 @@
+//! In-memory cache in front of a backend
+//!
+//! [`CachedStorage`] wraps a backend and answers reads of a project's
+//! entities and relations from a shared [`StorageCache`], loading a project
+//! whole on the first miss. Reads across all projects are cached the same
+//! way, as one more entry. Relations are indexed by entity name, so
+//! neighbour lookups need no scan. Writes through the wrapper drop what they
+//! touch; writes made by other handles or processes are only seen once
+//! [`StorageCache::apply`] drops the projects their change log records name,
+//! which a [`crate::ChangeWatcher`] given the cache does.
+//!
+//! Entries are shared as `Arc`s, taken under the cache lock, but a hit still
+//! copies the records it returns once the lock is released: the
+//! [`StorageBackend`] methods hand out owned lists, which callers filter and
+//! change in place. What a hit saves is the backend query and the decoding.
+//!
+//! The cache holds at most a number of projects and an estimated number of
+//! bytes, evicting the least recently used project beyond either. A cache
+//! with no room for either passes every read straight to the backend.
+
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use parsnip_core::{ArchivedObservation, Entity, Graph, Observation, Project, ProjectId, Relation};
+use tokio::sync::watch;
+
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::changelog::{ChangeEvent, ChangeRecord};
+use crate::changes::ChangeKind;
+use crate::doctor::{RecordScan, RepairPlan};
+use crate::error::StorageResult;
+use crate::traits::StorageBackend;
+
+/// Default bound on the estimated size of cached records
+pub const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Default bound on the number of cached projects
+pub const DEFAULT_MAX_PROJECTS: usize = 64;
+
+/// Counters and size of a [`StorageCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Reads answered from the cache
+    pub hits: u64,
+    /// Reads that went to the backend
+    pub misses: u64,
+    /// Projects dropped to stay within the limits
+    pub evictions: u64,
+    /// Projects dropped because they were written to
+    pub invalidations: u64,
+    /// Estimated size of the cached records
+    pub bytes: usize,
+    /// Projects with records in the cache, counting the records of all
+    /// projects together as one
+    pub projects: usize,
+}
+
+impl CacheStats {
+    /// Share of reads answered from the cache; 0 before any read
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            reads => self.hits as f64 / reads as f64,
+        }
+    }
+}
+
+/// A project's entities, by name
+struct Entities {
+    list: Vec<Entity>,
+    by_name: HashMap<String, usize>,
+    bytes: usize,
+}
+
+impl Entities {
+    fn new(list: Vec<Entity>) -> Self {
+        let by_name = list
+            .iter()
+            .enumerate()
+            .map(|(i, entity)| (entity.name.clone(), i))
+            .collect();
+        let bytes = list.iter().map(entity_size).sum();
+        Self {
+            list,
+            by_name,
+            bytes,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Entity> {
+        self.by_name.get(name).map(|&i| &self.list[i])
+    }
+}
+
+/// A project's relations, with the ones at each end of an entity by name
+struct Relations {
+    list: Vec<Relation>,
+    adjacency: HashMap<String, Vec<usize>>,
+    bytes: usize,
+}
+
+impl Relations {
+    fn new(list: Vec<Relation>) -> Self {
+        let mut adjacency: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, relation) in list.iter().enumerate() {
+            adjacency
+                .entry(relation.from_name.clone())
+                .or_default()
+                .push(i);
+            if relation.to_name != relation.from_name {
+                adjacency
+                    .entry(relation.to_name.clone())
+                    .or_default()
+                    .push(i);
+            }
+        }
+        let bytes = list.iter().map(relation_size).sum::<usize>()
+            + adjacency
+                .iter()
+                .map(|(name, ends)| name.len() + ends.len() * size_of::<usize>())
+                .sum::<usize>();
+        Self {
+            list,
+            adjacency,
+            bytes,
+        }
+    }
+
+    /// Relations from or to an entity, in stored order
+    fn of(&self, name: &str) -> Vec<Relation> {
+        self.adjacency
+            .get(name)
+            .map(|ends| ends.iter().map(|&i| self.list[i].clone()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// What is cached of one project
+#[derive(Default)]
+struct ProjectEntry {
+    entities: Option<Arc<Entities>>,
+    relations: Option<Arc<Relations>>,
+    /// Clock reading of the last use
+    used: u64,
+}
+
+impl ProjectEntry {
+    fn bytes(&self) -> usize {
+        self.entities.as_ref().map_or(0, |e| e.bytes)
+            + self.relations.as_ref().map_or(0, |r| r.bytes)
+    }
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// By project; `None` holds the records of all projects together
+    projects: HashMap<Option<ProjectId>, ProjectEntry>,
+    /// Every project, as `get_all_projects` returned them
+    project_list: Option<Arc<Vec<Project>>>,
+    bytes: usize,
+    clock: u64,
+    /// Bumped by every invalidation, so a load that raced a write is not
+    /// stored
+    generation: u64,
+    stats: CacheStats,
+}
+
+/// Records cached for a [`CachedStorage`], shared so the server can report
+/// and clear them
+pub struct StorageCache {
+    max_bytes: usize,
+    max_projects: usize,
+    state: Mutex<CacheState>,
+}
+
+impl StorageCache {
+    pub fn new() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_projects: DEFAULT_MAX_PROJECTS,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Bound the estimated size of cached records; a project larger than
+    /// this on its own is never cached
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_max_projects(mut self, max_projects: usize) -> Self {
+        self.max_projects = max_projects;
+        self
+    }
+
+    /// Whether the limits leave room for anything
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes > 0 && self.max_projects > 0
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.lock();
+        CacheStats {
+            bytes: state.bytes,
+            projects: state.projects.len(),
+            ..state.stats
+        }
+    }
+
+    /// Drop the cached records of a project, and of all projects together
+    pub fn invalidate(&self, project_id: &ProjectId) {
+        let mut state = self.lock();
+        state.generation += 1;
+        for key in [Some(project_id.clone()), None] {
+            if let Some(entry) = state.projects.remove(&key) {
+                state.bytes -= entry.bytes();
+                state.stats.invalidations += 1;
+            }
+        }
+    }
+
+    /// Drop everything cached
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.generation += 1;
+        state.stats.invalidations += state.projects.len() as u64;
+        state.projects.clear();
+        state.project_list = None;
+        state.bytes = 0;
+    }
+
+    /// Drop what a change log event says was written: the project it names,
+    /// and the project list for project changes
+    pub fn apply(&self, event: &ChangeEvent) {
+        match event {
+            ChangeEvent::Project { kind, .. } => {
+                self.invalidate_projects();
+                if *kind == ChangeKind::Deleted {
+                    self.invalidate(event.project_id());
+                }
+            }
+            ChangeEvent::Entity { .. } | ChangeEvent::Relation { .. } => {
+                self.invalidate(event.project_id())
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// What `pick` finds cached of a project, or else the generation to
+    /// [store](Self::store) a load under
+    fn lookup<T>(
+        &self,
+        project_id: &Option<ProjectId>,
+        pick: impl FnOnce(&ProjectEntry) -> Option<Arc<T>>,
+    ) -> Result<Arc<T>, u64> {
+        let mut state = self.lock();
+        state.clock += 1;
+        let clock = state.clock;
+        let found = state.projects.get_mut(project_id).and_then(|entry| {
+            entry.used = clock;
+            pick(entry)
+        });
+        match found {
+            Some(found) => {
+                state.stats.hits += 1;
+                Ok(found)
+            }
+            None => {
+                state.stats.misses += 1;
+                Err(state.generation)
+            }
+        }
+    }
+
+    /// Add what was loaded at `generation` to a project's entry, unless a
+    /// write came in meanwhile or it would not fit
+    fn store(
+        &self,
+        project_id: &Option<ProjectId>,
+        generation: u64,
+        fill: impl FnOnce(&mut ProjectEntry),
+    ) {
+        let mut state = self.lock();
+        if state.generation != generation {
+            return;
+        }
+        let clock = state.clock;
+        let entry = state.projects.entry(project_id.clone()).or_default();
+        let before = entry.bytes();
+        fill(entry);
+        entry.used = clock;
+        let after = entry.bytes();
+        if after > self.max_bytes {
+            state.projects.remove(project_id);
+            state.bytes -= before;
+            return;
+        }
+        state.bytes = state.bytes - before + after;
+        self.evict(&mut state, project_id);
+    }
+
+    /// Drop the least recently used projects other than `keep` until the
+    /// cache is within its limits
+    fn evict(&self, state: &mut CacheState, keep: &Option<ProjectId>) {
+        while state.bytes > self.max_bytes || state.projects.len() > self.max_projects {
+            let oldest = state
+                .projects
+                .iter()
+                .filter(|(project_id, _)| *project_id != keep)
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(project_id, _)| project_id.clone());
+            let Some(oldest) = oldest else {
+                break;
+            };
+            if let Some(entry) = state.projects.remove(&oldest) {
+                state.bytes -= entry.bytes();
+                state.stats.evictions += 1;
+            }
+        }
+    }
+
+    fn projects(&self) -> Result<Arc<Vec<Project>>, u64> {
+        let mut state = self.lock();
+        match state.project_list.clone() {
+            Some(projects) => {
+                state.stats.hits += 1;
+                Ok(projects)
+            }
+            None => {
+                state.stats.misses += 1;
+                Err(state.generation)
+            }
+        }
+    }
+
+    fn store_projects(&self, generation: u64, projects: Arc<Vec<Project>>) {
+        let mut state = self.lock();
+        if state.generation == generation {
+            state.project_list = Some(projects);
+        }
+    }
+
+    fn invalidate_projects(&self) {
+        let mut state = self.lock();
+        state.generation += 1;
+        state.project_list = None;
+    }
+}
+
+impl std::fmt::Debug for StorageCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StorageCache")
+            .field("max_bytes", &self.max_bytes)
+            .field("max_projects", &self.max_projects)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl Default for StorageCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A backend whose project reads are cached
+pub struct CachedStorage<S: StorageBackend + ?Sized> {
+    inner: Arc<S>,
+    cache: Arc<StorageCache>,
+}
+
+impl<S: StorageBackend + ?Sized> CachedStorage<S> {
+    pub fn new(inner: Arc<S>, cache: Arc<StorageCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    pub fn inner(&self) -> &Arc<S> {
+        &self.inner
+    }
+
+    pub fn cache(&self) -> &Arc<StorageCache> {
+        &self.cache
+    }
+
+    /// Entities of a project, or of all projects for `None`
+    async fn entities(&self, project_id: Option<&ProjectId>) -> StorageResult<Arc<Entities>> {
+        let key = project_id.cloned();
+        let generation = match self.cache.lookup(&key, |e| e.entities.clone()) {
+            Ok(entities) => return Ok(entities),
+            Err(generation) => generation,
+        };
+        let entities = match project_id {
+            Some(project_id) => self.inner.get_all_entities(project_id).await?,
+            None => self.inner.get_all_entities_all_projects().await?,
+        };
+        let entities = Arc::new(Entities::new(entities));
+        self.cache.store(&key, generation, |entry| {
+            entry.entities = Some(entities.clone());
+        });
+        Ok(entities)
+    }
+
+    /// Relations of a project, or of all projects for `None`
+    async fn relations(&self, project_id: Option<&ProjectId>) -> StorageResult<Arc<Relations>> {
+        let key = project_id.cloned();
+        let generation = match self.cache.lookup(&key, |e| e.relations.clone()) {
+            Ok(relations) => return Ok(relations),
+            Err(generation) => generation,
+        };
+        let relations = match project_id {
+            Some(project_id) => self.inner.get_all_relations(project_id).await?,
+            None => self.inner.get_all_relations_all_projects().await?,
+        };
+        let relations = Arc::new(Relations::new(relations));
+        self.cache.store(&key, generation, |entry| {
+            entry.relations = Some(relations.clone());
+        });
+        Ok(relations)
+    }
+
+    async fn projects(&self) -> StorageResult<Arc<Vec<Project>>> {
+        let generation = match self.cache.projects() {
+            Ok(projects) => return Ok(projects),
+            Err(generation) => generation,
+        };
+        let projects = Arc::new(self.inner.get_all_projects().await?);
+        self.cache.store_projects(generation, projects.clone());
+        Ok(projects)
+    }
+
+    /// Drop the projects records were written to, once the write is done
+    /// whether or not it succeeded
+    fn invalidate<'a>(&self, project_ids: impl IntoIterator<Item = &'a ProjectId>) {
+        let project_ids: HashSet<&ProjectId> = project_ids.into_iter().collect();
+        for project_id in project_ids {
+            self.cache.invalidate(project_id);
+        }
+    }
+}
+
+#[async_trait]
+impl<S: StorageBackend + ?Sized> StorageBackend for CachedStorage<S> {
+    async fn initialize(&self) -> StorageResult<()> {
+        self.inner.initialize().await
+    }
+
+    async fn close(&self) -> StorageResult<()> {
+        self.cache.clear();
+        self.inner.close().await
+    }
+
+    async fn health_check(&self) -> StorageResult<bool> {
+        self.inner.health_check().await
+    }
+
+    async fn save_entity(&self, entity: &Entity) -> StorageResult<()> {
+        let result = self.inner.save_entity(entity).await;
+        self.invalidate([&entity.project_id]);
+        result
+    }
+
+    /// Served from the project's entities when they are cached, but does not
+    /// load them
+    async fn get_entity(
+        &self,
+        name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Option<Entity>> {
+        if !self.cache.is_enabled() {
+            return self.inner.get_entity(name, project_id).await;
+        }
+        match self
+            .cache
+            .lookup(&Some(project_id.clone()), |e| e.entities.clone())
+        {
+            Ok(entities) => Ok(entities.get(name).cloned()),
+            Err(_) => self.inner.get_entity(name, project_id).await,
+        }
+    }
+
+    async fn get_all_entities(&self, project_id: &ProjectId) -> StorageResult<Vec<Entity>> {
+        if !self.cache.is_enabled() {
+            return self.inner.get_all_entities(project_id).await;
+        }
+        Ok(self.entities(Some(project_id)).await?.list.clone())
+    }
+
+    async fn get_all_entities_all_projects(&self) -> StorageResult<Vec<Entity>> {
+        if !self.cache.is_enabled() {
+            return self.inner.get_all_entities_all_projects().await;
+        }
+        Ok(self.entities(None).await?.list.clone())
+    }
+
+    async fn delete_entity(&self, name: &str, project_id: &ProjectId) -> StorageResult<()> {
+        let result = self.inner.delete_entity(name, project_id).await;
+        self.invalidate([project_id]);
+        result
+    }
+
+    async fn save_relation(&self, relation: &Relation) -> StorageResult<()> {
+        let result = self.inner.save_relation(relation).await;
+        self.invalidate([&relation.project_id]);
+        result
+    }
+
+    async fn get_relations_for_entity(
+        &self,
+        entity_name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Vec<Relation>> {
+        if !self.cache.is_enabled() {
+            return self
+                .inner
+                .get_relations_for_entity(entity_name, project_id)
+                .await;
+        }
+        Ok(self.relations(Some(project_id)).await?.of(entity_name))
+    }
+
+    async fn get_all_relations(&self, project_id: &ProjectId) -> StorageResult<Vec<Relation>> {
+        if !self.cache.is_enabled() {
+            return self.inner.get_all_relations(project_id).await;
+        }
+        Ok(self.relations(Some(project_id)).await?.list.clone())
+    }
+
+    async fn get_all_relations_all_projects(&self) -> StorageResult<Vec<Relation>> {
+        if !self.cache.is_enabled() {
+            return self.inner.get_all_relations_all_projects().await;
+        }
+        Ok(self.relations(None).await?.list.clone())
+    }
+
+    async fn get_relations_for_entity_global(
+        &self,
+        entity_name: &str,
+    ) -> StorageResult<Vec<Relation>> {
+        if !self.cache.is_enabled() {
+            return self
+                .inner
+                .get_relations_for_entity_global(entity_name)
+                .await;
+        }
+        Ok(self.relations(None).await?.of(entity_name))
+    }
+
+    async fn delete_relation(
+        &self,
+        from: &str,
+        to: &str,
+        relation_type: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<()> {
+        let result = self
+            .inner
+            .delete_relation(from, to, relation_type, project_id)
+            .await;
+        self.invalidate([project_id]);
+        result
+    }
+
+    async fn delete_relations_for_entity(
+        &self,
+        entity_name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<()> {
+        let result = self
+            .inner
+            .delete_relations_for_entity(entity_name, project_id)
+            .await;
+        self.invalidate([project_id]);
+        result
+    }
+
+    async fn save_project(&self, project: &Project) -> StorageResult<()> {
+        let result = self.inner.save_project(project).await;
+        self.cache.invalidate_projects();
+        result
+    }
+
+    async fn get_project(&self, name: &str) -> StorageResult<Option<Project>> {
+        if !self.cache.is_enabled() {
+            return self.inner.get_project(name).await;
+        }
+        let projects = self.projects().await?;
+        Ok(projects.iter().find(|p| p.name == name).cloned())
+    }
+
+    async fn get_project_by_id(&self, id: &ProjectId) -> StorageResult<Option<Project>> {
+        if !self.cache.is_enabled() {
+            return self.inner.get_project_by_id(id).await;
+        }
+        let projects = self.projects().await?;
+        Ok(projects.iter().find(|p| &p.id == id).cloned())
+    }
+
+    async fn get_all_projects(&self) -> StorageResult<Vec<Project>> {
+        if !self.cache.is_enabled() {
+            return self.inner.get_all_projects().await;
+        }
+        Ok(self.projects().await?.as_ref().clone())
+    }
+
+    async fn delete_project(&self, name: &str) -> StorageResult<()> {
+        let result = self.inner.delete_project(name).await;
+        self.cache.clear();
+        result
+    }
+
+    async fn save_graph(&self, graph: &Graph, project_id: &ProjectId) -> StorageResult<()> {
+        let result = self.inner.save_graph(graph, project_id).await;
+        self.invalidate(
+            graph
+                .entities
+                .iter()
+                .map(|e| &e.project_id)
+                .chain(graph.relations.iter().map(|r| &r.project_id))
+                .chain([project_id]),
+        );
+        result
+    }
+
+    async fn save_entities_batch(&self, entities: &[Entity]) -> StorageResult<()> {
+        let result = self.inner.save_entities_batch(entities).await;
+        self.invalidate(entities.iter().map(|e| &e.project_id));
+        result
+    }
+
+    async fn save_relations_batch(&self, relations: &[Relation]) -> StorageResult<()> {
+        let result = self.inner.save_relations_batch(relations).await;
+        self.invalidate(relations.iter().map(|r| &r.project_id));
+        result
+    }
+
+    async fn scan_records(&self) -> StorageResult<RecordScan> {
+        self.inner.scan_records().await
+    }
+
+    async fn apply_repair(&self, plan: &RepairPlan) -> StorageResult<()> {
+        let result = self.inner.apply_repair(plan).await;
+        self.cache.clear();
+        result
+    }
+
+    async fn append_audit(&self, entry: &AuditEntry) -> StorageResult<u64> {
+        self.inner.append_audit(entry).await
+    }
+
+    async fn audit_entries(&self, filter: &AuditFilter) -> StorageResult<Vec<AuditEntry>> {
+        self.inner.audit_entries(filter).await
+    }
+
+    async fn changes_since(&self, after: u64, limit: usize) -> StorageResult<Vec<ChangeRecord>> {
+        self.inner.changes_since(after, limit).await
+    }
+
+    async fn latest_change(&self) -> StorageResult<u64> {
+        self.inner.latest_change().await
+    }
+
+    fn change_signal(&self) -> Option<watch::Receiver<u64>> {
+        self.inner.change_signal()
+    }
+}
+
+/// Rough size of an entity in memory, counting its heap data
+fn entity_size(entity: &Entity) -> usize {
+    size_of::<Entity>()
+        + entity.name.len()
+        + entity.entity_type.as_str().len()
+        + entity
+            .observations
+            .iter()
+            .map(observation_size)
+            .sum::<usize>()
+        + entity
+            .observation_history
+            .iter()
+            .map(|archived| {
+                size_of::<ArchivedObservation>() - size_of::<Observation>()
+                    + observation_size(&archived.observation)
+                    + archived.reason.len()
+                    + archived.replaced_by.len() * 16
+            })
+            .sum::<usize>()
+        + entity
+            .tags
+            .iter()
+            .map(|tag| size_of::<String>() + tag.len())
+            .sum::<usize>()
+        + entity
+            .metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.to_string().len() + 2 * size_of::<String>())
+            .sum::<usize>()
+        + entity
+            .embedding
+            .as_ref()
+            .map_or(0, |e| e.len() * size_of::<f32>())
+}
+
+fn observation_size(observation: &Observation) -> usize {
+    size_of::<Observation>()
+        + observation.content.len()
+        + observation.source.as_ref().map_or(0, String::len)
+}
+
+fn relation_size(relation: &Relation) -> usize {
+    size_of::<Relation>()
+        + relation.from_name.len()
+        + relation.to_name.len()
+        + relation.relation_type.len()
+        + relation
+            .metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.to_string().len() + 2 * size_of::<String>())
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStorage;
+    use crate::metrics::{InstrumentedStorage, StorageMetrics};
+
+    fn cached(cache: StorageCache) -> (CachedStorage<dyn StorageBackend>, Arc<StorageMetrics>) {
+        let metrics = Arc::new(StorageMetrics::new());
+        let inner: Arc<dyn StorageBackend> = Arc::new(InstrumentedStorage::new(
+            Arc::new(MemoryStorage::new()),
+            metrics.clone(),
+        ));
+        (CachedStorage::new(inner, Arc::new(cache)), metrics)
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_and_invalidation() {
+        let (storage, metrics) = cached(StorageCache::new());
+        let project = Project::new("work");
+        storage.save_project(&project).await.unwrap();
+        for name in ["Alice", "Bob", "Carol"] {
+            let entity = Entity::new(project.id.clone(), name, "person");
+            storage.save_entity(&entity).await.unwrap();
+        }
+        let relation = Relation::from_names(project.id.clone(), "Alice", "Bob", "knows");
+        storage.save_relation(&relation).await.unwrap();
+
+        // The first search loads the project, the next ones are answered
+        // without the backend
+        for _ in 0..3 {
+            assert_eq!(
+                storage.get_all_entities(&project.id).await.unwrap().len(),
+                3
+            );
+            let bob = storage.get_relations_for_entity("Bob", &project.id).await;
+            assert_eq!(bob.unwrap().len(), 1);
+        }
+        assert!(storage
+            .get_entity("Carol", &project.id)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(storage
+            .get_relations_for_entity("Carol", &project.id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(storage.get_project("work").await.unwrap().is_some());
+        let calls = metrics.snapshot();
+        assert_eq!(calls["get_all_entities"].calls, 1);
+        assert_eq!(calls["get_all_relations"].calls, 1);
+        assert!(!calls.contains_key("get_entity"));
+        assert!(!calls.contains_key("get_relations_for_entity"));
+        let stats = storage.cache().stats();
+        assert_eq!((stats.hits, stats.misses), (6, 3));
+        assert_eq!(stats.projects, 1);
+        assert!(stats.bytes > 0);
+
+        // A write through the wrapper is seen by the next read
+        storage.delete_entity("Carol", &project.id).await.unwrap();
+        assert_eq!(
+            storage.get_all_entities(&project.id).await.unwrap().len(),
+            2
+        );
+        assert!(storage
+            .get_entity("Carol", &project.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(metrics.snapshot()["get_all_entities"].calls, 2);
+        assert_eq!(storage.cache().stats().invalidations, 1);
+
+        storage.cache().clear();
+        assert_eq!(storage.cache().stats().bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cache_all_projects() {
+        let (storage, metrics) = cached(StorageCache::new());
+        let work = Project::new("work");
+        let home = Project::new("home");
+        for project in [&work, &home] {
+            storage.save_project(project).await.unwrap();
+            let entity = Entity::new(project.id.clone(), "Alice", "person");
+            storage.save_entity(&entity).await.unwrap();
+        }
+        let relation = Relation::from_names(home.id.clone(), "Alice", "Bob", "knows");
+        storage.save_relation(&relation).await.unwrap();
+
+        for _ in 0..2 {
+            let entities = storage.get_all_entities_all_projects().await.unwrap();
+            assert_eq!(entities.len(), 2);
+            let bob = storage.get_relations_for_entity_global("Bob").await;
+            assert_eq!(bob.unwrap().len(), 1);
+        }
+        let calls = metrics.snapshot();
+        assert_eq!(calls["get_all_entities_all_projects"].calls, 1);
+        assert_eq!(calls["get_all_relations_all_projects"].calls, 1);
+        assert!(!calls.contains_key("get_relations_for_entity_global"));
+
+        // A write to any project drops the records of all of them
+        storage.get_all_entities(&work.id).await.unwrap();
+        storage.delete_entity("Alice", &home.id).await.unwrap();
+        assert_eq!(
+            storage.get_all_entities_all_projects().await.unwrap().len(),
+            1
+        );
+        assert_eq!(metrics.snapshot()["get_all_entities_all_projects"].calls, 2);
+        // while other projects stay cached
+        storage.get_all_entities(&work.id).await.unwrap();
+        assert_eq!(metrics.snapshot()["get_all_entities"].calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_limits() {
+        let (storage, _) = cached(StorageCache::new().with_max_projects(2));
+        let mut projects = Vec::new();
+        for name in ["a", "b", "c"] {
+            let project = Project::new(name);
+            storage.save_project(&project).await.unwrap();
+            let entity = Entity::new(project.id.clone(), "Alice", "person");
+            storage.save_entity(&entity).await.unwrap();
+            projects.push(project);
+        }
+
+        storage.get_all_entities(&projects[0].id).await.unwrap();
+        storage.get_all_entities(&projects[1].id).await.unwrap();
+        // Using "a" again leaves "b" the least recently used
+        storage.get_all_entities(&projects[0].id).await.unwrap();
+        storage.get_all_entities(&projects[2].id).await.unwrap();
+        let stats = storage.cache().stats();
+        assert_eq!((stats.projects, stats.evictions), (2, 1));
+        storage.get_all_entities(&projects[0].id).await.unwrap();
+        assert_eq!(storage.cache().stats().hits, 2);
+
+        // A project over the byte limit on its own is not kept
+        let (storage, _) = cached(StorageCache::new().with_max_bytes(16));
+        let project = &projects[0];
+        storage.save_project(project).await.unwrap();
+        let entity = Entity::new(project.id.clone(), "Alice", "person");
+        storage.save_entity(&entity).await.unwrap();
+        storage.get_all_entities(&project.id).await.unwrap();
+        storage.get_all_entities(&project.id).await.unwrap();
+        let stats = storage.cache().stats();
+        assert_eq!((stats.hits, stats.misses, stats.bytes), (0, 2, 0));
+
+        // Without room, reads go straight to the backend
+        let (storage, metrics) = cached(StorageCache::new().with_max_bytes(0));
+        storage.save_project(project).await.unwrap();
+        storage.save_entity(&entity).await.unwrap();
+        assert!(storage
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(metrics.snapshot()["get_entity"].calls, 1);
+        assert_eq!(storage.cache().stats(), CacheStats::default());
+    }
+}
@@ -0,0 +1,355 @@
+//! Change log: the ordered record of every write a backend makes
+//!
+//! Backends append [`ChangeEvent`]s to their change log in the same
+//! transaction as the write itself, numbered by a sequence that only grows
+//! and is never reused. A consumer remembers the sequence number of the last
+//! record it handled and resumes after it with
+//! [`StorageBackend::changes_since`] or a [`ChangeSubscription`], so nothing
+//! is missed across restarts. [`crate::ChangeWatcher`] follows it to
+//! publish changes on a [`crate::ChangeFeed`].
+//!
+//! The log keeps the latest [`CHANGE_LOG_CAPACITY`] records; appending past
+//! that trims the oldest. A consumer that falls further behind gets
+//! [`StorageError::ChangesTrimmed`] rather than silently skipping records.
+//!
+//! One event type per kind of record, with a [`ChangeKind`], stands for the
+//! entity and relation upserts and deletes and the project changes a feed
+//! has to carry: the kind keeps creates apart from updates, which
+//! notifications name differently.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parsnip_core::{Entity, Project, ProjectId, Relation};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::changes::{ChangeKind, DEFAULT_POLL_INTERVAL};
+use crate::error::{StorageError, StorageResult};
+use crate::traits::StorageBackend;
+
+/// Records a subscription fetches at a time
+const PAGE_SIZE: usize = 256;
+
+/// Records a change log keeps before trimming the oldest
+pub const CHANGE_LOG_CAPACITY: u64 = 100_000;
+
+/// Sequence number of the oldest record a log of `capacity` keeps once it
+/// holds `latest`
+pub(crate) fn first_kept(latest: u64, capacity: u64) -> u64 {
+    latest.saturating_sub(capacity.max(1)) + 1
+}
+
+/// Fails when records right after `after` were trimmed from a log whose
+/// oldest record is `first`
+pub(crate) fn check_trimmed(after: u64, first: Option<u64>) -> StorageResult<()> {
+    match first {
+        Some(first) if after + 1 < first => Err(StorageError::ChangesTrimmed(first)),
+        _ => Ok(()),
+    }
+}
+
+/// A write to a stored record, by identity only
+///
+/// Events name what changed and how, not what it holds now, so the log stays
+/// small however large the records get; read the record for its contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ChangeEvent {
+    #[serde(rename_all = "camelCase")]
+    Entity {
+        project_id: ProjectId,
+        name: String,
+        kind: ChangeKind,
+    },
+    /// Ends in other projects are named for cross-project relations
+    #[serde(rename_all = "camelCase")]
+    Relation {
+        project_id: ProjectId,
+        from: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from_project_id: Option<ProjectId>,
+        to: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        to_project_id: Option<ProjectId>,
+        relation_type: String,
+        kind: ChangeKind,
+    },
+    #[serde(rename_all = "camelCase")]
+    Project {
+        project_id: ProjectId,
+        name: String,
+        kind: ChangeKind,
+    },
+}
+
+impl ChangeEvent {
+    pub fn entity(entity: &Entity, kind: ChangeKind) -> Self {
+        Self::Entity {
+            project_id: entity.project_id.clone(),
+            name: entity.name.clone(),
+            kind,
+        }
+    }
+
+    pub fn relation(relation: &Relation, kind: ChangeKind) -> Self {
+        Self::Relation {
+            project_id: relation.project_id.clone(),
+            from: relation.from_name.clone(),
+            from_project_id: relation.from_project_id.clone(),
+            to: relation.to_name.clone(),
+            to_project_id: relation.to_project_id.clone(),
+            relation_type: relation.relation_type.clone(),
+            kind,
+        }
+    }
+
+    pub fn project(project: &Project, kind: ChangeKind) -> Self {
+        Self::Project {
+            project_id: project.id.clone(),
+            name: project.name.clone(),
+            kind,
+        }
+    }
+
+    /// The same change, of another kind
+    pub(crate) fn with_kind(mut self, new_kind: ChangeKind) -> Self {
+        match &mut self {
+            Self::Entity { kind, .. }
+            | Self::Relation { kind, .. }
+            | Self::Project { kind, .. } => *kind = new_kind,
+        }
+        self
+    }
+
+    pub fn kind(&self) -> ChangeKind {
+        match self {
+            Self::Entity { kind, .. }
+            | Self::Relation { kind, .. }
+            | Self::Project { kind, .. } => *kind,
+        }
+    }
+
+    /// Project the changed record belongs to
+    pub fn project_id(&self) -> &ProjectId {
+        match self {
+            Self::Entity { project_id, .. }
+            | Self::Relation { project_id, .. }
+            | Self::Project { project_id, .. } => project_id,
+        }
+    }
+}
+
+/// A [`ChangeEvent`] as stored in the change log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: ChangeEvent,
+}
+
+impl ChangeRecord {
+    /// A record to append, timestamped now; the backend assigns `seq`
+    pub fn new(event: ChangeEvent) -> Self {
+        Self {
+            seq: 0,
+            timestamp: Utc::now(),
+            event,
+        }
+    }
+}
+
+/// Follows a backend's change log from a sequence number onwards
+///
+/// When the backend has a [change signal](StorageBackend::change_signal) a
+/// caught-up subscription wakes as soon as a write commits; it also polls,
+/// which is all it does for backends written by other processes.
+pub struct ChangeSubscription {
+    storage: Arc<dyn StorageBackend>,
+    after: u64,
+    pending: VecDeque<ChangeRecord>,
+    signal: Option<watch::Receiver<u64>>,
+    poll_interval: Duration,
+}
+
+impl ChangeSubscription {
+    /// Follow the records after sequence number `after`; 0 starts at the
+    /// beginning of the log
+    pub fn new(storage: Arc<dyn StorageBackend>, after: u64) -> Self {
+        let signal = storage.change_signal();
+        Self {
+            storage,
+            after,
+            pending: VecDeque::new(),
+            signal,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Sequence number of the last record returned, to resume from
+    pub fn position(&self) -> u64 {
+        self.after
+    }
+
+    /// The next record, waiting for one if the log has no more yet
+    ///
+    /// Fails with [`StorageError::ChangesTrimmed`] once when the records
+    /// after the position are gone, moving on to the oldest one left.
+    pub async fn next(&mut self) -> StorageResult<ChangeRecord> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                self.after = record.seq;
+                return Ok(record);
+            }
+            // Anything signalled from here on is a commit the fetch may miss
+            if let Some(signal) = self.signal.as_mut() {
+                signal.borrow_and_update();
+            }
+            self.pending = self.fetch(self.after).await?.into();
+            if self.pending.is_empty() {
+                self.wait().await;
+            }
+        }
+    }
+
+    /// Records after the current position that are already in the log,
+    /// without waiting for more
+    pub async fn drain(&mut self) -> StorageResult<Vec<ChangeRecord>> {
+        let mut records: Vec<ChangeRecord> = self.pending.drain(..).collect();
+        loop {
+            let after = records.last().map_or(self.after, |record| record.seq);
+            let page = self.fetch(after).await?;
+            let done = page.len() < PAGE_SIZE;
+            records.extend(page);
+            if done {
+                break;
+            }
+        }
+        if let Some(last) = records.last() {
+            self.after = last.seq;
+        }
+        Ok(records)
+    }
+
+    async fn fetch(&mut self, after: u64) -> StorageResult<Vec<ChangeRecord>> {
+        let page = self.storage.changes_since(after, PAGE_SIZE).await;
+        if let Err(StorageError::ChangesTrimmed(first)) = &page {
+            self.after = first - 1;
+        }
+        page
+    }
+
+    async fn wait(&mut self) {
+        let Some(signal) = self.signal.as_mut() else {
+            tokio::time::sleep(self.poll_interval).await;
+            return;
+        };
+        let closed = tokio::select! {
+            changed = signal.changed() => changed.is_err(),
+            _ = tokio::time::sleep(self.poll_interval) => false,
+        };
+        if closed {
+            // The backend is gone; polling finds out if it comes to matter
+            self.signal = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStorage;
+
+    #[test]
+    fn test_record_format() {
+        let project = Project::new("work");
+        let relation = Relation::from_names(project.id.clone(), "Alice", "Bob", "knows");
+        let record = ChangeRecord {
+            seq: 7,
+            ..ChangeRecord::new(ChangeEvent::relation(&relation, ChangeKind::Deleted))
+        };
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["seq"], 7);
+        assert_eq!(json["type"], "relation");
+        assert_eq!(json["kind"], "deleted");
+        assert_eq!(json["relationType"], "knows");
+        assert!(json.get("fromProjectId").is_none());
+
+        let back: ChangeRecord = serde_json::from_value(json).unwrap();
+        assert_eq!(back.seq, 7);
+        assert_eq!(back.event, record.event);
+        assert_eq!(back.event.project_id(), &project.id);
+
+        // Only the identity of a record is logged, however large it is
+        let mut entity = Entity::new(project.id.clone(), "Alice", "person");
+        for i in 0..100 {
+            entity.add_observation(format!("A long observation about Alice, number {}", i));
+        }
+        let json =
+            serde_json::to_string(&ChangeEvent::entity(&entity, ChangeKind::Created)).unwrap();
+        assert!(json.len() < 200, "{}", json);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_resumes() {
+        let storage = Arc::new(MemoryStorage::new());
+        let project = Project::new("work");
+        storage.save_project(&project).await.unwrap();
+        storage
+            .save_entity(&Entity::new(project.id.clone(), "Alice", "person"))
+            .await
+            .unwrap();
+
+        let mut subscription = ChangeSubscription::new(storage.clone(), 0);
+        assert_eq!(subscription.drain().await.unwrap().len(), 2);
+        let checkpoint = subscription.position();
+        assert_eq!(checkpoint, 2);
+
+        // A caught-up subscription wakes for the next write
+        let waiting = tokio::spawn(async move { subscription.next().await.unwrap() });
+        storage.delete_entity("Alice", &project.id).await.unwrap();
+        let record = tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.seq, 3);
+        assert_eq!(record.event.kind(), ChangeKind::Deleted);
+
+        // Resuming from the checkpoint sees the same record
+        let mut resumed = ChangeSubscription::new(storage, checkpoint);
+        assert_eq!(resumed.next().await.unwrap().seq, 3);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_reports_trimmed_records() {
+        let storage = Arc::new(MemoryStorage::new().with_change_capacity(2));
+        let project = Project::new("work");
+        storage.save_project(&project).await.unwrap();
+        for name in ["Alice", "Bob"] {
+            storage
+                .save_entity(&Entity::new(project.id.clone(), name, "person"))
+                .await
+                .unwrap();
+        }
+
+        // Reported once, then the subscription carries on from what is left
+        let mut subscription = ChangeSubscription::new(storage, 0);
+        assert!(matches!(
+            subscription.drain().await,
+            Err(StorageError::ChangesTrimmed(2))
+        ));
+        assert_eq!(subscription.position(), 1);
+        let records = subscription.drain().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(subscription.position(), 3);
+    }
+}
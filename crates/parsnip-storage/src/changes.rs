@@ -0,0 +1,369 @@
+//! Change feed for stored projects, entities and relations
+//!
+//! [`ChangeWatcher`] follows a backend's change log and publishes each
+//! write on a [`ChangeFeed`]. Because the log is written in the same
+//! transaction as the records, it also sees writes made by other handles or
+//! processes (another server, the CLI, an import).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::StorageCache;
+use crate::changelog::{ChangeEvent, ChangeSubscription};
+use crate::error::StorageError;
+use crate::traits::StorageBackend;
+use parsnip_core::ProjectId;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Notify};
+
+/// Default interval between change checks
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What happened to a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A change to a stored record, identified by project and entity names
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StorageChange {
+    Project {
+        name: String,
+        kind: ChangeKind,
+    },
+    Entity {
+        project: String,
+        name: String,
+        kind: ChangeKind,
+    },
+    #[serde(rename_all = "camelCase")]
+    Relation {
+        project: String,
+        from: String,
+        from_project: String,
+        to: String,
+        to_project: String,
+        relation_type: String,
+        kind: ChangeKind,
+    },
+}
+
+impl ChangeKind {
+    /// Kind of a write that replaced a record if there was one
+    pub fn of_upsert(replaced: bool) -> Self {
+        if replaced {
+            Self::Updated
+        } else {
+            Self::Created
+        }
+    }
+}
+
+impl StorageChange {
+    pub fn kind(&self) -> ChangeKind {
+        match self {
+            Self::Project { kind, .. }
+            | Self::Entity { kind, .. }
+            | Self::Relation { kind, .. } => *kind,
+        }
+    }
+}
+
+/// Broadcast channel of storage changes
+///
+/// Cloning shares the channel. Writers can call [`ChangeFeed::wake`] after a
+/// mutation so the watcher checks immediately, even when the backend does
+/// not signal its writes.
+#[derive(Clone)]
+pub struct ChangeFeed {
+    tx: broadcast::Sender<StorageChange>,
+    wake: Arc<Notify>,
+}
+
+impl ChangeFeed {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self {
+            tx,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<StorageChange> {
+        self.tx.subscribe()
+    }
+
+    pub fn publish(&self, change: StorageChange) {
+        // No receivers is fine; nobody is listening yet
+        let _ = self.tx.send(change);
+    }
+
+    /// Ask the watcher to check for changes now
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+/// Publishes the records of a backend's change log on a [`ChangeFeed`]
+///
+/// Follows the log from its end when the watcher starts, naming each
+/// record's project as the feed does. Given a cache, it first drops the
+/// project each record names from it.
+pub struct ChangeWatcher {
+    storage: Arc<dyn StorageBackend>,
+    feed: ChangeFeed,
+    cache: Option<Arc<StorageCache>>,
+    interval: Duration,
+    /// Project names by ID, kept after a project is deleted
+    names: HashMap<ProjectId, String>,
+}
+
+impl ChangeWatcher {
+    pub fn new(storage: Arc<dyn StorageBackend>, feed: ChangeFeed) -> Self {
+        Self {
+            storage,
+            feed,
+            cache: None,
+            interval: DEFAULT_POLL_INTERVAL,
+            names: HashMap::new(),
+        }
+    }
+
+    /// Keep `cache` up to date with writes other handles and processes make
+    pub fn with_cache(mut self, cache: Arc<StorageCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Poll the log this often when the backend does not signal writes
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Run the watcher until the runtime shuts down
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    async fn run(mut self) {
+        let after = loop {
+            match self.storage.latest_change().await {
+                Ok(seq) => break seq,
+                Err(StorageError::ChangesTrimmed(first)) => {
+                    // The subscription moved on; what it skipped is unknown
+                    tracing::warn!(
+                        "Change watcher fell behind the change log, which starts at #{}",
+                        first
+                    );
+                    if let Some(cache) = &self.cache {
+                        cache.clear();
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Change watcher failed to read the change log: {}", e);
+                    tokio::time::sleep(self.interval).await;
+                }
+            }
+        };
+        let mut subscription =
+            ChangeSubscription::new(self.storage.clone(), after).with_poll_interval(self.interval);
+        let wake = self.feed.wake.clone();
+
+        loop {
+            let record = tokio::select! {
+                record = subscription.next() => record,
+                // Writes through a daemon do not signal; look again now
+                _ = wake.notified() => continue,
+            };
+            match record {
+                Ok(record) => {
+                    if let Some(cache) = &self.cache {
+                        cache.apply(&record.event);
+                    }
+                    let change = self.describe(record.event).await;
+                    tracing::debug!("Storage change: {:?}", change);
+                    self.feed.publish(change);
+                }
+                Err(e) => {
+                    tracing::warn!("Change watcher failed to read the change log: {}", e);
+                    tokio::time::sleep(self.interval).await;
+                }
+            }
+        }
+    }
+
+    /// A change log event with its projects named
+    async fn describe(&mut self, event: ChangeEvent) -> StorageChange {
+        match event {
+            ChangeEvent::Project {
+                project_id,
+                name,
+                kind,
+            } => {
+                self.names.insert(project_id, name.clone());
+                StorageChange::Project { name, kind }
+            }
+            ChangeEvent::Entity {
+                project_id,
+                name,
+                kind,
+            } => StorageChange::Entity {
+                project: self.project_name(&project_id).await,
+                name,
+                kind,
+            },
+            ChangeEvent::Relation {
+                project_id,
+                from,
+                from_project_id,
+                to,
+                to_project_id,
+                relation_type,
+                kind,
+            } => StorageChange::Relation {
+                project: self.project_name(&project_id).await,
+                from,
+                from_project: self
+                    .project_name(from_project_id.as_ref().unwrap_or(&project_id))
+                    .await,
+                to,
+                to_project: self
+                    .project_name(to_project_id.as_ref().unwrap_or(&project_id))
+                    .await,
+                relation_type,
+                kind,
+            },
+        }
+    }
+
+    /// Name of a project, or its ID if it was deleted before the watcher
+    /// saw it
+    async fn project_name(&mut self, id: &ProjectId) -> String {
+        if let Some(name) = self.names.get(id) {
+            return name.clone();
+        }
+        let name = match self.storage.get_project_by_id(id).await {
+            Ok(Some(project)) => project.name,
+            _ => id.to_string(),
+        };
+        self.names.insert(id.clone(), name.clone());
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CachedStorage, MemoryStorage};
+    use parsnip_core::{Entity, Project, Relation};
+
+    async fn next(rx: &mut broadcast::Receiver<StorageChange>) -> StorageChange {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_watcher_publishes_the_change_log() {
+        let storage = Arc::new(MemoryStorage::new());
+        let work = Project::new("work");
+        let home = Project::new("home");
+        storage.save_project(&work).await.unwrap();
+        storage.save_project(&home).await.unwrap();
+
+        let feed = ChangeFeed::default();
+        let mut rx = feed.subscribe();
+        let handle = ChangeWatcher::new(storage.clone(), feed.clone())
+            .with_interval(Duration::from_secs(60))
+            .spawn();
+        // Let the watcher find the end of the log; earlier writes are not
+        // published
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut alice = Entity::new(work.id.clone(), "Alice", "person");
+        storage.save_entity(&alice).await.unwrap();
+        alice.add_observation("Joined the platform team");
+        storage.save_entity(&alice).await.unwrap();
+        let mut knows = Relation::from_names(work.id.clone(), "Alice", "Bob", "knows");
+        knows.to_project_id = Some(home.id.clone());
+        storage.save_relation(&knows).await.unwrap();
+
+        let entity = |kind| StorageChange::Entity {
+            project: "work".to_string(),
+            name: "Alice".to_string(),
+            kind,
+        };
+        assert_eq!(next(&mut rx).await, entity(ChangeKind::Created));
+        assert_eq!(next(&mut rx).await, entity(ChangeKind::Updated));
+        assert_eq!(
+            next(&mut rx).await,
+            StorageChange::Relation {
+                project: "work".to_string(),
+                from: "Alice".to_string(),
+                from_project: "work".to_string(),
+                to: "Bob".to_string(),
+                to_project: "home".to_string(),
+                relation_type: "knows".to_string(),
+                kind: ChangeKind::Created,
+            }
+        );
+
+        // Deleted records keep their project name
+        storage.delete_project("work").await.unwrap();
+        let mut changes = Vec::new();
+        for _ in 0..3 {
+            changes.push(next(&mut rx).await);
+        }
+        assert!(changes.iter().all(|c| c.kind() == ChangeKind::Deleted));
+        assert_eq!(changes[0], entity(ChangeKind::Deleted));
+        assert!(matches!(&changes[2], StorageChange::Project { name, .. } if name == "work"));
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_watcher_invalidates_the_cache_per_project() {
+        let storage = Arc::new(MemoryStorage::new());
+        let work = Project::new("work");
+        let home = Project::new("home");
+        storage.save_project(&work).await.unwrap();
+        storage.save_project(&home).await.unwrap();
+
+        let cache = Arc::new(StorageCache::new());
+        let cached = CachedStorage::new(storage.clone(), cache.clone());
+        let feed = ChangeFeed::default();
+        let mut rx = feed.subscribe();
+        let handle = ChangeWatcher::new(storage.clone(), feed)
+            .with_cache(cache.clone())
+            .with_interval(Duration::from_secs(60))
+            .spawn();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(cached.get_all_entities(&work.id).await.unwrap().is_empty());
+        assert!(cached.get_all_entities(&home.id).await.unwrap().is_empty());
+
+        // A write that bypasses the cache drops only its own project
+        storage
+            .save_entity(&Entity::new(work.id.clone(), "Alice", "person"))
+            .await
+            .unwrap();
+        next(&mut rx).await;
+        assert_eq!(cached.get_all_entities(&work.id).await.unwrap().len(), 1);
+        let hits = cache.stats().hits;
+        cached.get_all_entities(&home.id).await.unwrap();
+        assert_eq!(cache.stats().hits, hits + 1);
+        handle.abort();
+    }
+}
@@ -0,0 +1,268 @@
+//! Consolidating the observations of an entity
+//!
+//! Consolidation replaces groups of observations with fewer, merged ones and
+//! moves the originals to the entity's observation history. The merges come
+//! either from [`dedupe`], which folds exact and near-duplicate observations
+//! without any model, or from a caller asking a model to summarize the
+//! [`chunks`] of an entity. Both are applied with [`apply`], which checks the
+//! entity still holds what was planned.
+
+use std::collections::BTreeSet;
+
+use crate::ops::{OpsError, OpsResult};
+use crate::traits::StorageBackend;
+use parsnip_core::{validate_observation, Entity, Observation, ObservationId, Project};
+
+/// Word overlap at which [`dedupe`] treats two observations as duplicates
+pub const DEFAULT_SIMILARITY: f64 = 0.8;
+
+/// Observations per chunk handed to a model
+pub const DEFAULT_CHUNK_SIZE: usize = 50;
+
+/// Observations to replace, and the observations replacing them
+#[derive(Debug, Clone)]
+pub struct Merge {
+    pub replaced: Vec<Observation>,
+    pub merged: Vec<String>,
+}
+
+impl Merge {
+    /// Observations gained (negative: removed) by applying this merge
+    pub fn net_change(&self) -> isize {
+        self.merged.len() as isize - self.replaced.len() as isize
+    }
+}
+
+/// Lowercased words of an observation, without punctuation
+fn words(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Jaccard similarity of two word sets
+fn similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let shared = a.intersection(b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+/// Merges folding duplicate observations into one
+///
+/// Observations are duplicates when they have the same words, ignoring case
+/// and punctuation, or share at least `threshold` of their words. Each group
+/// keeps its longest wording, the most recent one on ties. Deterministic:
+/// the same observations always give the same merges.
+pub fn dedupe(entity: &Entity, threshold: f64) -> Vec<Merge> {
+    let observations = &entity.observations;
+    let words: Vec<BTreeSet<String>> = observations.iter().map(|o| words(&o.content)).collect();
+
+    // Single-link grouping in observation order
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for i in 0..observations.len() {
+        let group = groups.iter_mut().find(|group| {
+            group
+                .iter()
+                .any(|&j| similarity(&words[i], &words[j]) >= threshold)
+        });
+        match group {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|group| {
+            let kept = group
+                .iter()
+                .copied()
+                .max_by_key(|&i| (observations[i].content.len(), i))
+                .unwrap_or(group[0]);
+            Merge {
+                merged: vec![observations[kept].content.clone()],
+                replaced: group.iter().map(|&i| observations[i].clone()).collect(),
+            }
+        })
+        .collect()
+}
+
+/// An entity's observations in chunks of at most `size`, oldest first,
+/// leaving out a final chunk of one that has nothing to merge with
+pub fn chunks(entity: &Entity, size: usize) -> Vec<Vec<Observation>> {
+    entity
+        .observations
+        .chunks(size.max(2))
+        .filter(|chunk| chunk.len() > 1)
+        .map(<[Observation]>::to_vec)
+        .collect()
+}
+
+/// Result of [`apply`]
+#[derive(Debug, Clone)]
+pub struct ConsolidationOutcome {
+    /// The entity after consolidation
+    pub entity: Entity,
+    /// Observations moved to the history
+    pub replaced: usize,
+    /// Merged observations added
+    pub added: usize,
+    /// Merges left out because their observations changed since planning
+    pub skipped: usize,
+}
+
+/// Apply merges to an entity, saving it if any applied
+///
+/// A merge applies only if every observation it replaces is still on the
+/// entity, so merges planned before a slow model call cannot drop
+/// observations edited in the meantime.
+pub async fn apply(
+    storage: &dyn StorageBackend,
+    project: &Project,
+    name: &str,
+    merges: &[Merge],
+    reason: &str,
+) -> OpsResult<ConsolidationOutcome> {
+    for merge in merges {
+        for observation in &merge.merged {
+            validate_observation(observation)?;
+        }
+    }
+
+    let mut entity = storage
+        .get_entity(name, &project.id)
+        .await?
+        .ok_or_else(|| OpsError::EntityNotFound {
+            name: name.to_string(),
+            project: project.name.clone(),
+        })?;
+
+    let mut outcome = ConsolidationOutcome {
+        entity: entity.clone(),
+        replaced: 0,
+        added: 0,
+        skipped: 0,
+    };
+    for merge in merges {
+        let current = merge.replaced.iter().all(|replaced| {
+            entity
+                .observations
+                .iter()
+                .any(|o| o.id == replaced.id && o.content == replaced.content)
+        });
+        if !current {
+            outcome.skipped += 1;
+            continue;
+        }
+
+        let ids: Vec<ObservationId> = merge.replaced.iter().map(|o| o.id.clone()).collect();
+        let merged: Vec<Observation> = merge
+            .merged
+            .iter()
+            .map(|content| Observation::new(content.clone()).with_source(reason))
+            .collect();
+        outcome.added += merged.len();
+        outcome.replaced += entity.replace_observations(&ids, merged, reason);
+    }
+
+    if outcome.replaced > 0 {
+        storage.save_entity(&entity).await?;
+    }
+    outcome.entity = entity;
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStorage;
+    use parsnip_core::ProjectId;
+
+    fn entity(observations: &[&str]) -> Entity {
+        let mut entity = Entity::new(ProjectId::new(), "Alice", "person");
+        for observation in observations {
+            entity.add_observation(*observation);
+        }
+        entity
+    }
+
+    #[test]
+    fn test_dedupe_groups_near_duplicates() {
+        let entity = entity(&[
+            "Works at Acme",
+            "Likes tea",
+            "works at Acme.",
+            "Works at Acme Corp",
+            "Lives in Oslo",
+            "likes TEA",
+        ]);
+
+        let merges = dedupe(&entity, 0.7);
+        assert_eq!(merges.len(), 2);
+        assert_eq!(merges[0].replaced.len(), 3);
+        assert_eq!(merges[0].merged, vec!["Works at Acme Corp"]);
+        assert_eq!(merges[0].net_change(), -2);
+        // Equal length: the most recent wording wins
+        assert_eq!(merges[1].merged, vec!["likes TEA"]);
+
+        // Exact matching only
+        assert_eq!(dedupe(&entity, 1.0).len(), 2);
+        assert_eq!(dedupe(&entity, 1.0)[0].replaced.len(), 2);
+        assert!(dedupe(&Entity::new(ProjectId::new(), "x", "y"), 0.5).is_empty());
+    }
+
+    #[test]
+    fn test_chunks() {
+        let entity = entity(&["a", "b", "c", "d", "e"]);
+        let chunks = chunks(&entity, 2);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1][0].content, "c");
+        assert_eq!(super::chunks(&entity, 10)[0].len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_apply_keeps_history_and_skips_stale_merges() {
+        let storage = MemoryStorage::new();
+        let project = Project::new("work");
+        storage.save_project(&project).await.unwrap();
+        let mut alice = entity(&["Likes tea", "likes tea", "Lives in Oslo"]);
+        alice.project_id = project.id.clone();
+        storage.save_entity(&alice).await.unwrap();
+
+        let mut merges = dedupe(&alice, DEFAULT_SIMILARITY);
+        let mut stale = merges[0].clone();
+        stale.replaced[0].content = "edited since".to_string();
+        merges.push(stale);
+
+        let outcome = apply(&storage, &project, "Alice", &merges, "dedupe")
+            .await
+            .unwrap();
+        assert_eq!(
+            (outcome.replaced, outcome.added, outcome.skipped),
+            (2, 1, 1)
+        );
+
+        let stored = storage
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let contents: Vec<&str> = stored
+            .observations
+            .iter()
+            .map(|o| o.content.as_str())
+            .collect();
+        assert_eq!(contents, vec!["likes tea", "Lives in Oslo"]);
+        assert_eq!(stored.observations[0].source.as_deref(), Some("dedupe"));
+        assert_eq!(stored.observation_history.len(), 2);
+
+        assert!(matches!(
+            apply(&storage, &project, "Nobody", &[], "dedupe").await,
+            Err(OpsError::EntityNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+//! Copying a whole store from one backend to another
+//!
+//! Records are copied as they are, keeping their IDs and timestamps, so a
+//! store converted from ReDB to SQLite and back is the store it started as.
+//! Relations between projects are kept, and the audit log is replayed in
+//! order, so sequence numbers match as long as the target starts empty.
+//! The change log is not copied: the target logs the copy as it saves it.
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::audit::AuditFilter;
+use crate::error::StorageError;
+use crate::traits::StorageBackend;
+
+/// Errors of copying a store
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    #[error("Target store is not empty ({0} records)")]
+    TargetNotEmpty(usize),
+
+    #[error("{0} records of the source do not decode and would be lost")]
+    Undecodable(usize),
+
+    #[error("Copied {expected} {kind} but the target holds {found}")]
+    Mismatch {
+        kind: &'static str,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// What [`copy_store`] copied
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CopyStats {
+    pub projects: usize,
+    pub entities: usize,
+    pub relations: usize,
+    pub audit_entries: usize,
+}
+
+/// Copy every project, entity, relation and audit entry of `from` into the
+/// empty store `to`, then check the target holds all of them
+pub async fn copy_store(
+    from: &dyn StorageBackend,
+    to: &dyn StorageBackend,
+) -> Result<CopyStats, ConvertError> {
+    let existing = to.scan_records().await?;
+    let existing = existing.projects.len()
+        + existing.entities.len()
+        + existing.relations.len()
+        + existing.undecodable.len()
+        + to.audit_entries(&AuditFilter::new()).await?.len();
+    if existing > 0 {
+        return Err(ConvertError::TargetNotEmpty(existing));
+    }
+
+    let scan = from.scan_records().await?;
+    if !scan.undecodable.is_empty() {
+        return Err(ConvertError::Undecodable(scan.undecodable.len()));
+    }
+    let audit = from.audit_entries(&AuditFilter::new()).await?;
+
+    for project in &scan.projects {
+        to.save_project(project).await?;
+    }
+    to.save_entities_batch(&scan.entities).await?;
+    to.save_relations_batch(&scan.relations).await?;
+    for entry in &audit {
+        to.append_audit(entry).await?;
+    }
+    tracing::debug!(
+        "Copied {} projects, {} entities, {} relations and {} audit entries",
+        scan.projects.len(),
+        scan.entities.len(),
+        scan.relations.len(),
+        audit.len()
+    );
+
+    let stats = CopyStats {
+        projects: scan.projects.len(),
+        entities: scan.entities.len(),
+        relations: scan.relations.len(),
+        audit_entries: audit.len(),
+    };
+    let copied = to.scan_records().await?;
+    let found = CopyStats {
+        projects: copied.projects.len(),
+        entities: copied.entities.len(),
+        relations: copied.relations.len(),
+        audit_entries: to.audit_entries(&AuditFilter::new()).await?.len(),
+    };
+    for (kind, expected, found) in [
+        ("projects", stats.projects, found.projects),
+        ("entities", stats.entities, found.entities),
+        ("relations", stats.relations, found.relations),
+        ("audit entries", stats.audit_entries, found.audit_entries),
+    ] {
+        if expected != found {
+            return Err(ConvertError::Mismatch {
+                kind,
+                expected,
+                found,
+            });
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{Actor, AuditEntry, Transport};
+    use crate::memory::MemoryStorage;
+    use parsnip_core::{Entity, Project, Relation};
+    use serde_json::Value;
+
+    /// Every record of a store as JSON, in a stable order
+    async fn dump(storage: &dyn StorageBackend) -> Vec<Value> {
+        let scan = storage.scan_records().await.unwrap();
+        let mut records: Vec<Value> = scan
+            .projects
+            .iter()
+            .map(|p| serde_json::to_value(p).unwrap())
+            .chain(
+                scan.entities
+                    .iter()
+                    .map(|e| serde_json::to_value(e).unwrap()),
+            )
+            .chain(
+                scan.relations
+                    .iter()
+                    .map(|r| serde_json::to_value(r).unwrap()),
+            )
+            .collect();
+        records.sort_by_key(|record| record["id"].to_string());
+        let audit = storage.audit_entries(&AuditFilter::new()).await.unwrap();
+        records.extend(audit.iter().map(|a| serde_json::to_value(a).unwrap()));
+        records
+    }
+
+    async fn sample() -> MemoryStorage {
+        let storage = MemoryStorage::new();
+        let work = Project::new("work");
+        let home = Project::new("home");
+        storage.save_project(&work).await.unwrap();
+        storage.save_project(&home).await.unwrap();
+
+        let mut alice = Entity::new(work.id.clone(), "Alice", "person");
+        alice.add_observation("likes tea");
+        alice.tags = vec!["team".to_string()];
+        let bob = Entity::new(home.id.clone(), "Bob", "person");
+        storage.save_entity(&alice).await.unwrap();
+        storage.save_entity(&bob).await.unwrap();
+
+        // Across projects
+        let mut relation = Relation::new_cross_project(
+            work.id.clone(),
+            alice.id.clone(),
+            "Alice",
+            work.id.clone(),
+            bob.id.clone(),
+            "Bob",
+            home.id.clone(),
+            "knows",
+        );
+        relation.weight = Some(0.5);
+        storage.save_relation(&relation).await.unwrap();
+
+        for operation in ["entity add", "relation add"] {
+            let entry = AuditEntry::new(
+                operation,
+                Value::Null,
+                vec![alice.id.to_string()],
+                Actor::new(Transport::Cli),
+            );
+            storage.append_audit(&entry).await.unwrap();
+        }
+        storage
+    }
+
+    #[tokio::test]
+    async fn test_copy_is_lossless() {
+        let source = sample().await;
+        let target = MemoryStorage::new();
+
+        let stats = copy_store(&source, &target).await.unwrap();
+        assert_eq!(
+            stats,
+            CopyStats {
+                projects: 2,
+                entities: 2,
+                relations: 1,
+                audit_entries: 2,
+            }
+        );
+        assert_eq!(dump(&source).await, dump(&target).await);
+
+        // Copying again would duplicate the audit log
+        assert!(matches!(
+            copy_store(&source, &target).await,
+            Err(ConvertError::TargetNotEmpty(7))
+        ));
+    }
+
+    #[cfg(all(feature = "redb", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_redb_sqlite_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = sample().await;
+        let redb = crate::redb::RedbStorage::open(dir.path().join("parsnip.redb")).unwrap();
+        let sqlite = crate::sqlite::SqliteStorage::open(dir.path().join("parsnip.sqlite")).unwrap();
+        let back = MemoryStorage::new();
+
+        copy_store(&source, &redb).await.unwrap();
+        copy_store(&redb, &sqlite).await.unwrap();
+        copy_store(&sqlite, &back).await.unwrap();
+        assert_eq!(dump(&source).await, dump(&back).await);
+    }
+}
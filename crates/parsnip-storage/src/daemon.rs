@@ -0,0 +1,694 @@
+//! Sharing one store between processes through a daemon
+//!
+//! ReDB lets a single process open a database, so a [`DaemonServer`] owns
+//! the backend and answers [`StorageBackend`] calls over a Unix domain
+//! socket, and each [`DaemonClient`] is a backend that makes those calls.
+//! Requests and responses are JSON objects, one per line; a client sends one
+//! request at a time and reads its response before the next.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parsnip_core::{Entity, Graph, Project, ProjectId, Relation};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinSet;
+
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::changelog::ChangeRecord;
+use crate::doctor::{RecordScan, RepairPlan};
+use crate::error::{StorageError, StorageResult};
+use crate::traits::StorageBackend;
+
+/// File name of the daemon's socket in the data directory
+pub const SOCKET_FILE: &str = "parsnip.sock";
+
+/// A call to the daemon: a [`StorageBackend`] method or a control request
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum Request {
+    Status,
+    Shutdown,
+    Initialize,
+    HealthCheck,
+    SaveEntity(Entity),
+    GetEntity {
+        name: String,
+        project_id: ProjectId,
+    },
+    GetAllEntities(ProjectId),
+    GetAllEntitiesAllProjects,
+    DeleteEntity {
+        name: String,
+        project_id: ProjectId,
+    },
+    SaveRelation(Relation),
+    GetRelationsForEntity {
+        entity_name: String,
+        project_id: ProjectId,
+    },
+    GetAllRelations(ProjectId),
+    GetAllRelationsAllProjects,
+    GetRelationsForEntityGlobal(String),
+    DeleteRelation {
+        from: String,
+        to: String,
+        relation_type: String,
+        project_id: ProjectId,
+    },
+    DeleteRelationsForEntity {
+        entity_name: String,
+        project_id: ProjectId,
+    },
+    SaveProject(Project),
+    GetProject(String),
+    GetProjectById(ProjectId),
+    GetAllProjects,
+    DeleteProject(String),
+    SaveGraph {
+        entities: Vec<Entity>,
+        relations: Vec<Relation>,
+        project_id: ProjectId,
+    },
+    SaveEntitiesBatch(Vec<Entity>),
+    SaveRelationsBatch(Vec<Relation>),
+    ScanRecords,
+    ApplyRepair(RepairPlan),
+    AppendAudit(AuditEntry),
+    AuditEntries(AuditFilter),
+    ChangesSince {
+        after: u64,
+        limit: usize,
+    },
+    LatestChange,
+}
+
+/// Answer to a [`Request`]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ok(Value),
+    Error(RemoteError),
+}
+
+/// A [`StorageError`] as sent over the socket
+#[derive(Debug, Serialize, Deserialize)]
+struct RemoteError {
+    kind: String,
+    message: String,
+}
+
+impl From<&StorageError> for RemoteError {
+    fn from(error: &StorageError) -> Self {
+        let (kind, message) = match error {
+            StorageError::EntityNotFound(name) => ("entity_not_found", name.clone()),
+            StorageError::ProjectNotFound(name) => ("project_not_found", name.clone()),
+            StorageError::DuplicateEntity(name) => ("duplicate_entity", name.clone()),
+            StorageError::DuplicateProject(name) => ("duplicate_project", name.clone()),
+            StorageError::Migration(message) => ("migration", message.clone()),
+            StorageError::Connection(message) => ("connection", message.clone()),
+            StorageError::Transaction(message) => ("transaction", message.clone()),
+            StorageError::Database(message) => ("database", message.clone()),
+            StorageError::ChangesTrimmed(first) => ("changes_trimmed", first.to_string()),
+            other => ("database", other.to_string()),
+        };
+        Self {
+            kind: kind.to_string(),
+            message,
+        }
+    }
+}
+
+impl From<RemoteError> for StorageError {
+    fn from(error: RemoteError) -> Self {
+        let message = error.message;
+        match error.kind.as_str() {
+            "entity_not_found" => Self::EntityNotFound(message),
+            "project_not_found" => Self::ProjectNotFound(message),
+            "duplicate_entity" => Self::DuplicateEntity(message),
+            "duplicate_project" => Self::DuplicateProject(message),
+            "migration" => Self::Migration(message),
+            "connection" => Self::Connection(message),
+            "transaction" => Self::Transaction(message),
+            "changes_trimmed" => match message.parse() {
+                Ok(first) => Self::ChangesTrimmed(first),
+                Err(_) => Self::Database(message),
+            },
+            _ => Self::Database(message),
+        }
+    }
+}
+
+/// What a running daemon reports about itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub version: String,
+    pub started: DateTime<Utc>,
+    /// Name of the backend served, e.g. `redb`; empty if not given
+    #[serde(default)]
+    pub backend: String,
+}
+
+/// Serves a backend on a Unix domain socket
+pub struct DaemonServer {
+    storage: Arc<dyn StorageBackend>,
+    socket: PathBuf,
+    backend: String,
+}
+
+impl DaemonServer {
+    pub fn new(storage: Arc<dyn StorageBackend>, socket: impl Into<PathBuf>) -> Self {
+        Self {
+            storage,
+            socket: socket.into(),
+            backend: String::new(),
+        }
+    }
+
+    /// Name the backend served, so clients can check it is the one they want
+    pub fn with_backend(mut self, backend: impl Into<String>) -> Self {
+        self.backend = backend.into();
+        self
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Serve until `shutdown` completes or a client asks the daemon to stop,
+    /// then close the backend and remove the socket
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> StorageResult<()> {
+        if DaemonClient::connect(&self.socket).await.is_ok() {
+            return Err(StorageError::Connection(format!(
+                "a daemon is already listening on {:?}",
+                self.socket
+            )));
+        }
+        // Left behind by a daemon that did not exit cleanly
+        match std::fs::remove_file(&self.socket) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        let listener = UnixListener::bind(&self.socket)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.socket, std::fs::Permissions::from_mode(0o600))?;
+        }
+        tracing::info!("Daemon listening on {:?}", self.socket);
+
+        let status = DaemonStatus {
+            pid: std::process::id(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started: Utc::now(),
+            backend: self.backend.clone(),
+        };
+        let stop = Arc::new(Notify::new());
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let storage = self.storage.clone();
+                        let status = status.clone();
+                        let stop = stop.clone();
+                        connections.spawn(async move {
+                            let served = serve_connection(stream, storage.as_ref(), &status, &stop);
+                            if let Err(e) = served.await {
+                                tracing::debug!("Daemon connection failed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => tracing::warn!("Failed to accept daemon connection: {}", e),
+                },
+                // Forget connections that have ended
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = stop.notified() => break,
+                _ = &mut shutdown => break,
+            }
+        }
+
+        // Other clients find the daemon gone rather than the backend closed
+        connections.shutdown().await;
+        tracing::info!("Daemon stopping");
+        let closed = self.storage.close().await;
+        // Release the database before the socket goes, so that clients
+        // seeing it gone can open the database themselves
+        drop(self.storage);
+        let _ = std::fs::remove_file(&self.socket);
+        closed
+    }
+}
+
+/// Answer the requests of one client until it disconnects
+async fn serve_connection(
+    stream: UnixStream,
+    storage: &dyn StorageBackend,
+    status: &DaemonStatus,
+    stop: &Notify,
+) -> StorageResult<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let request = serde_json::from_str::<Request>(&line);
+        let stopping = matches!(request, Ok(Request::Shutdown));
+        let result = match request {
+            Ok(Request::Status) => serde_json::to_value(status).map_err(Into::into),
+            Ok(Request::Shutdown) => Ok(Value::Null),
+            Ok(request) => dispatch(storage, request).await,
+            Err(e) => Err(StorageError::Connection(format!("invalid request: {}", e))),
+        };
+        let response = match result {
+            Ok(value) => Response::Ok(value),
+            Err(e) => Response::Error(RemoteError::from(&e)),
+        };
+
+        let mut json = serde_json::to_string(&response)?;
+        json.push('\n');
+        writer.write_all(json.as_bytes()).await?;
+        if stopping {
+            stop.notify_one();
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Make a storage call on behalf of a client
+async fn dispatch(storage: &dyn StorageBackend, request: Request) -> StorageResult<Value> {
+    fn value(result: impl Serialize) -> StorageResult<Value> {
+        Ok(serde_json::to_value(result)?)
+    }
+
+    match request {
+        Request::Status | Request::Shutdown => Ok(Value::Null),
+        Request::Initialize => value(storage.initialize().await?),
+        Request::HealthCheck => value(storage.health_check().await?),
+        Request::SaveEntity(entity) => value(storage.save_entity(&entity).await?),
+        Request::GetEntity { name, project_id } => {
+            value(storage.get_entity(&name, &project_id).await?)
+        }
+        Request::GetAllEntities(project_id) => value(storage.get_all_entities(&project_id).await?),
+        Request::GetAllEntitiesAllProjects => value(storage.get_all_entities_all_projects().await?),
+        Request::DeleteEntity { name, project_id } => {
+            value(storage.delete_entity(&name, &project_id).await?)
+        }
+        Request::SaveRelation(relation) => value(storage.save_relation(&relation).await?),
+        Request::GetRelationsForEntity {
+            entity_name,
+            project_id,
+        } => value(
+            storage
+                .get_relations_for_entity(&entity_name, &project_id)
+                .await?,
+        ),
+        Request::GetAllRelations(project_id) => {
+            value(storage.get_all_relations(&project_id).await?)
+        }
+        Request::GetAllRelationsAllProjects => {
+            value(storage.get_all_relations_all_projects().await?)
+        }
+        Request::GetRelationsForEntityGlobal(entity_name) => value(
+            storage
+                .get_relations_for_entity_global(&entity_name)
+                .await?,
+        ),
+        Request::DeleteRelation {
+            from,
+            to,
+            relation_type,
+            project_id,
+        } => value(
+            storage
+                .delete_relation(&from, &to, &relation_type, &project_id)
+                .await?,
+        ),
+        Request::DeleteRelationsForEntity {
+            entity_name,
+            project_id,
+        } => value(
+            storage
+                .delete_relations_for_entity(&entity_name, &project_id)
+                .await?,
+        ),
+        Request::SaveProject(project) => value(storage.save_project(&project).await?),
+        Request::GetProject(name) => value(storage.get_project(&name).await?),
+        Request::GetProjectById(id) => value(storage.get_project_by_id(&id).await?),
+        Request::GetAllProjects => value(storage.get_all_projects().await?),
+        Request::DeleteProject(name) => value(storage.delete_project(&name).await?),
+        Request::SaveGraph {
+            entities,
+            relations,
+            project_id,
+        } => {
+            let graph = Graph {
+                entities,
+                relations,
+            };
+            value(storage.save_graph(&graph, &project_id).await?)
+        }
+        Request::SaveEntitiesBatch(entities) => {
+            value(storage.save_entities_batch(&entities).await?)
+        }
+        Request::SaveRelationsBatch(relations) => {
+            value(storage.save_relations_batch(&relations).await?)
+        }
+        Request::ScanRecords => value(storage.scan_records().await?),
+        Request::ApplyRepair(plan) => value(storage.apply_repair(&plan).await?),
+        Request::AppendAudit(entry) => value(storage.append_audit(&entry).await?),
+        Request::AuditEntries(filter) => value(storage.audit_entries(&filter).await?),
+        Request::ChangesSince { after, limit } => value(storage.changes_since(after, limit).await?),
+        Request::LatestChange => value(storage.latest_change().await?),
+    }
+}
+
+/// One client connection, used by a single request at a time
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+/// A backend whose calls are made by a running daemon
+pub struct DaemonClient {
+    socket: PathBuf,
+    connection: Mutex<Connection>,
+}
+
+impl DaemonClient {
+    /// Connect to the daemon listening on a socket, failing when there is none
+    pub async fn connect(socket: impl AsRef<Path>) -> StorageResult<Self> {
+        let socket = socket.as_ref().to_path_buf();
+        let (reader, writer) = UnixStream::connect(&socket).await?.into_split();
+        tracing::debug!("Connected to daemon on {:?}", socket);
+        Ok(Self {
+            socket,
+            connection: Mutex::new(Connection {
+                reader: BufReader::new(reader),
+                writer,
+            }),
+        })
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// What the daemon reports about itself
+    pub async fn status(&self) -> StorageResult<DaemonStatus> {
+        self.call(Request::Status).await
+    }
+
+    /// Ask the daemon to stop serving and close the backend
+    pub async fn shutdown(&self) -> StorageResult<()> {
+        self.call::<Value>(Request::Shutdown).await.map(|_| ())
+    }
+
+    async fn call<T: DeserializeOwned>(&self, request: Request) -> StorageResult<T> {
+        let mut json = serde_json::to_string(&request)?;
+        json.push('\n');
+
+        let mut connection = self.connection.lock().await;
+        connection.writer.write_all(json.as_bytes()).await?;
+        let mut line = String::new();
+        if connection.reader.read_line(&mut line).await? == 0 {
+            return Err(StorageError::Connection(
+                "daemon closed the connection".to_string(),
+            ));
+        }
+        drop(connection);
+
+        match serde_json::from_str(&line)? {
+            Response::Ok(value) => Ok(serde_json::from_value(value)?),
+            Response::Error(error) => Err(error.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for DaemonClient {
+    async fn initialize(&self) -> StorageResult<()> {
+        self.call(Request::Initialize).await
+    }
+
+    /// The daemon's backend stays open for its other clients
+    async fn close(&self) -> StorageResult<()> {
+        Ok(())
+    }
+
+    async fn health_check(&self) -> StorageResult<bool> {
+        self.call(Request::HealthCheck).await
+    }
+
+    async fn save_entity(&self, entity: &Entity) -> StorageResult<()> {
+        self.call(Request::SaveEntity(entity.clone())).await
+    }
+
+    async fn get_entity(
+        &self,
+        name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Option<Entity>> {
+        self.call(Request::GetEntity {
+            name: name.to_string(),
+            project_id: project_id.clone(),
+        })
+        .await
+    }
+
+    async fn get_all_entities(&self, project_id: &ProjectId) -> StorageResult<Vec<Entity>> {
+        self.call(Request::GetAllEntities(project_id.clone())).await
+    }
+
+    async fn get_all_entities_all_projects(&self) -> StorageResult<Vec<Entity>> {
+        self.call(Request::GetAllEntitiesAllProjects).await
+    }
+
+    async fn delete_entity(&self, name: &str, project_id: &ProjectId) -> StorageResult<()> {
+        self.call(Request::DeleteEntity {
+            name: name.to_string(),
+            project_id: project_id.clone(),
+        })
+        .await
+    }
+
+    async fn save_relation(&self, relation: &Relation) -> StorageResult<()> {
+        self.call(Request::SaveRelation(relation.clone())).await
+    }
+
+    async fn get_relations_for_entity(
+        &self,
+        entity_name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Vec<Relation>> {
+        self.call(Request::GetRelationsForEntity {
+            entity_name: entity_name.to_string(),
+            project_id: project_id.clone(),
+        })
+        .await
+    }
+
+    async fn get_all_relations(&self, project_id: &ProjectId) -> StorageResult<Vec<Relation>> {
+        self.call(Request::GetAllRelations(project_id.clone()))
+            .await
+    }
+
+    async fn get_all_relations_all_projects(&self) -> StorageResult<Vec<Relation>> {
+        self.call(Request::GetAllRelationsAllProjects).await
+    }
+
+    async fn get_relations_for_entity_global(
+        &self,
+        entity_name: &str,
+    ) -> StorageResult<Vec<Relation>> {
+        self.call(Request::GetRelationsForEntityGlobal(
+            entity_name.to_string(),
+        ))
+        .await
+    }
+
+    async fn delete_relation(
+        &self,
+        from: &str,
+        to: &str,
+        relation_type: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<()> {
+        self.call(Request::DeleteRelation {
+            from: from.to_string(),
+            to: to.to_string(),
+            relation_type: relation_type.to_string(),
+            project_id: project_id.clone(),
+        })
+        .await
+    }
+
+    async fn delete_relations_for_entity(
+        &self,
+        entity_name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<()> {
+        self.call(Request::DeleteRelationsForEntity {
+            entity_name: entity_name.to_string(),
+            project_id: project_id.clone(),
+        })
+        .await
+    }
+
+    async fn save_project(&self, project: &Project) -> StorageResult<()> {
+        self.call(Request::SaveProject(project.clone())).await
+    }
+
+    async fn get_project(&self, name: &str) -> StorageResult<Option<Project>> {
+        self.call(Request::GetProject(name.to_string())).await
+    }
+
+    async fn get_project_by_id(&self, id: &ProjectId) -> StorageResult<Option<Project>> {
+        self.call(Request::GetProjectById(id.clone())).await
+    }
+
+    async fn get_all_projects(&self) -> StorageResult<Vec<Project>> {
+        self.call(Request::GetAllProjects).await
+    }
+
+    async fn delete_project(&self, name: &str) -> StorageResult<()> {
+        self.call(Request::DeleteProject(name.to_string())).await
+    }
+
+    async fn save_graph(&self, graph: &Graph, project_id: &ProjectId) -> StorageResult<()> {
+        self.call(Request::SaveGraph {
+            entities: graph.entities.clone(),
+            relations: graph.relations.clone(),
+            project_id: project_id.clone(),
+        })
+        .await
+    }
+
+    async fn save_entities_batch(&self, entities: &[Entity]) -> StorageResult<()> {
+        self.call(Request::SaveEntitiesBatch(entities.to_vec()))
+            .await
+    }
+
+    async fn save_relations_batch(&self, relations: &[Relation]) -> StorageResult<()> {
+        self.call(Request::SaveRelationsBatch(relations.to_vec()))
+            .await
+    }
+
+    async fn scan_records(&self) -> StorageResult<RecordScan> {
+        self.call(Request::ScanRecords).await
+    }
+
+    async fn apply_repair(&self, plan: &RepairPlan) -> StorageResult<()> {
+        self.call(Request::ApplyRepair(plan.clone())).await
+    }
+
+    async fn append_audit(&self, entry: &AuditEntry) -> StorageResult<u64> {
+        self.call(Request::AppendAudit(entry.clone())).await
+    }
+
+    async fn audit_entries(&self, filter: &AuditFilter) -> StorageResult<Vec<AuditEntry>> {
+        self.call(Request::AuditEntries(filter.clone())).await
+    }
+
+    /// Other processes write through the daemon, so subscribers poll it
+    async fn changes_since(&self, after: u64, limit: usize) -> StorageResult<Vec<ChangeRecord>> {
+        self.call(Request::ChangesSince { after, limit }).await
+    }
+
+    async fn latest_change(&self) -> StorageResult<u64> {
+        self.call(Request::LatestChange).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{Actor, Transport};
+    use crate::memory::MemoryStorage;
+
+    #[test]
+    fn test_errors_keep_their_kind() {
+        let sent = StorageError::EntityNotFound("Alice".to_string());
+        assert!(matches!(
+            StorageError::from(RemoteError::from(&sent)),
+            StorageError::EntityNotFound(name) if name == "Alice"
+        ));
+        let sent = StorageError::Io(std::io::Error::other("disk full"));
+        assert!(matches!(
+            StorageError::from(RemoteError::from(&sent)),
+            StorageError::Database(message) if message == "IO error: disk full"
+        ));
+        let sent = StorageError::ChangesTrimmed(42);
+        assert!(matches!(
+            StorageError::from(RemoteError::from(&sent)),
+            StorageError::ChangesTrimmed(42)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_clients_share_the_daemon_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join(SOCKET_FILE);
+        assert!(DaemonClient::connect(&socket).await.is_err());
+
+        let storage = Arc::new(MemoryStorage::new());
+        let server = DaemonServer::new(storage.clone(), &socket).with_backend("memory");
+        let daemon = tokio::spawn(server.run(std::future::pending()));
+        while DaemonClient::connect(&socket).await.is_err() {
+            tokio::task::yield_now().await;
+        }
+
+        let cli = DaemonClient::connect(&socket).await.unwrap();
+        let shim = DaemonClient::connect(&socket).await.unwrap();
+        let status = cli.status().await.unwrap();
+        assert_eq!(status.pid, std::process::id());
+        assert_eq!(status.backend, "memory");
+
+        let project = Project::new("work");
+        cli.save_project(&project).await.unwrap();
+        let entity = Entity::new(project.id.clone(), "Alice", "person");
+        cli.save_entity(&entity).await.unwrap();
+
+        let seen = shim
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(seen.id, entity.id);
+        assert!(storage
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(shim.get_project("missing").await.unwrap().is_none());
+
+        let entry = AuditEntry::new(
+            "entity add",
+            Value::Null,
+            vec![entity.id.to_string()],
+            Actor::new(Transport::Cli),
+        );
+        assert_eq!(cli.append_audit(&entry).await.unwrap(), 1);
+        let entries = shim.audit_entries(&AuditFilter::new()).await.unwrap();
+        assert_eq!(entries.len(), 1);
+
+        // A second daemon refuses to take over the socket
+        let second = DaemonServer::new(Arc::new(MemoryStorage::new()), &socket);
+        assert!(second.run(std::future::pending()).await.is_err());
+
+        shim.shutdown().await.unwrap();
+        daemon.await.unwrap().unwrap();
+        assert!(!socket.exists());
+        assert!(cli.get_all_projects().await.is_err());
+    }
+}
@@ -0,0 +1,678 @@
+//! Consistency checks and repairs for stored graphs
+//!
+//! The doctor scans every record in a backend and reports problems that the
+//! normal write paths cannot prevent after the fact: relations whose endpoints
+//! were deleted (including cross-project edges left behind by a project
+//! delete), relations whose cached entity IDs disagree with the stored entity,
+//! duplicate observations, data written before the current limits, full-text
+//! index drift and records that no longer decode. Each fixable issue also
+//! contributes to a [`RepairPlan`] that backends apply in one transaction.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::error::{StorageError, StorageResult};
+use crate::traits::StorageBackend;
+use parsnip_core::{
+    validate_entity_name, validate_observation, validate_tag, Entity, Project, ProjectId, Relation,
+    MAX_OBSERVATIONS_PER_ENTITY, MAX_TAGS_PER_ENTITY,
+};
+use serde::{Deserialize, Serialize};
+
+/// Table a stored record belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordKind {
+    Project,
+    Entity,
+    Relation,
+}
+
+impl std::fmt::Display for RecordKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Project => write!(f, "project"),
+            Self::Entity => write!(f, "entity"),
+            Self::Relation => write!(f, "relation"),
+        }
+    }
+}
+
+/// A stored record that failed to decode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndecodableRecord {
+    pub kind: RecordKind,
+    /// Backend-specific key of the record
+    pub key: String,
+    pub error: String,
+    /// Written in a layout from a newer version rather than corrupt
+    #[serde(default)]
+    pub newer_layout: bool,
+}
+
+impl UndecodableRecord {
+    /// Records a failed decode, telling newer layouts from corruption
+    pub fn new(kind: RecordKind, key: String, error: &StorageError) -> Self {
+        Self {
+            kind,
+            key,
+            error: error.to_string(),
+            newer_layout: matches!(error, StorageError::UnknownLayout(_)),
+        }
+    }
+}
+
+/// Every record in a backend, decoded where possible
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordScan {
+    pub projects: Vec<Project>,
+    pub entities: Vec<Entity>,
+    pub relations: Vec<Relation>,
+    pub undecodable: Vec<UndecodableRecord>,
+}
+
+/// Storage changes that resolve the fixable issues of a diagnosis
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepairPlan {
+    /// Raw records to remove
+    pub remove_records: Vec<UndecodableRecord>,
+    /// Relations to delete, keyed by project, endpoint names and type
+    pub delete_relations: Vec<Relation>,
+    /// Entities to overwrite with their repaired form
+    pub save_entities: Vec<Entity>,
+    /// Relations to overwrite with their repaired form
+    pub save_relations: Vec<Relation>,
+}
+
+impl RepairPlan {
+    pub fn is_empty(&self) -> bool {
+        self.remove_records.is_empty()
+            && self.delete_relations.is_empty()
+            && self.save_entities.is_empty()
+            && self.save_relations.is_empty()
+    }
+}
+
+/// Category of a detected problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    DanglingRelation,
+    IdMismatch,
+    DuplicateObservation,
+    LimitViolation,
+    IndexDrift,
+    UndecodableRecord,
+}
+
+impl std::fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DanglingRelation => write!(f, "dangling_relation"),
+            Self::IdMismatch => write!(f, "id_mismatch"),
+            Self::DuplicateObservation => write!(f, "duplicate_observation"),
+            Self::LimitViolation => write!(f, "limit_violation"),
+            Self::IndexDrift => write!(f, "index_drift"),
+            Self::UndecodableRecord => write!(f, "undecodable_record"),
+        }
+    }
+}
+
+/// A single detected problem
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    /// Project name, or ID when the project no longer exists
+    pub project: Option<String>,
+    /// Entity name, relation triple or record key
+    pub subject: String,
+    pub detail: String,
+    /// Whether `--fix` resolves it
+    pub fixable: bool,
+}
+
+/// Result of a doctor run
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoctorReport {
+    pub projects_checked: usize,
+    pub entities_checked: usize,
+    pub relations_checked: usize,
+    pub issues: Vec<Issue>,
+    /// Whether the full-text index has to be rebuilt
+    pub rebuild_index: bool,
+    /// Whether the repair plan has been applied
+    pub fixed: bool,
+    #[serde(skip)]
+    pub plan: RepairPlan,
+}
+
+impl DoctorReport {
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Number of issues of the given kind
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues.iter().filter(|i| i.kind == kind).count()
+    }
+
+    /// Number of issues that `--fix` resolves
+    pub fn fixable_count(&self) -> usize {
+        self.issues.iter().filter(|i| i.fixable).count()
+    }
+}
+
+/// Runs consistency checks against a storage backend
+pub struct Doctor<'a> {
+    storage: &'a dyn StorageBackend,
+    indexed_ids: Option<Vec<String>>,
+}
+
+impl<'a> Doctor<'a> {
+    pub fn new(storage: &'a dyn StorageBackend) -> Self {
+        Self {
+            storage,
+            indexed_ids: None,
+        }
+    }
+
+    /// Also check the full-text index, given the entity ID of every indexed document
+    pub fn with_index(mut self, indexed_ids: Vec<String>) -> Self {
+        self.indexed_ids = Some(indexed_ids);
+        self
+    }
+
+    /// Scan storage and report every issue found
+    pub async fn run(&self) -> StorageResult<DoctorReport> {
+        let scan = self.storage.scan_records().await?;
+        Ok(diagnose(scan, self.indexed_ids.as_deref()))
+    }
+
+    /// Apply the report's repair plan to storage
+    ///
+    /// The index rebuild flagged by `rebuild_index` is left to the caller,
+    /// since the search engine lives outside the storage layer.
+    pub async fn repair(&self, report: &mut DoctorReport) -> StorageResult<()> {
+        if !report.plan.is_empty() {
+            self.storage.apply_repair(&report.plan).await?;
+        }
+        report.fixed = true;
+        Ok(())
+    }
+}
+
+/// Check a scan of storage, optionally against the IDs in the full-text index
+pub fn diagnose(mut scan: RecordScan, indexed_ids: Option<&[String]>) -> DoctorReport {
+    // Backends return records in key or hash order; sort for a stable report
+    scan.projects.sort_by(|a, b| a.name.cmp(&b.name));
+    scan.entities.sort_by(|a, b| {
+        (a.project_id.to_string(), &a.name).cmp(&(b.project_id.to_string(), &b.name))
+    });
+    scan.relations.sort_by_key(relation_subject);
+
+    let project_names: HashMap<&ProjectId, &str> = scan
+        .projects
+        .iter()
+        .map(|p| (&p.id, p.name.as_str()))
+        .collect();
+    let label = |id: &ProjectId| -> String {
+        project_names
+            .get(id)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| id.to_string())
+    };
+
+    let mut report = DoctorReport {
+        projects_checked: scan.projects.len(),
+        entities_checked: scan.entities.len(),
+        relations_checked: scan.relations.len(),
+        ..Default::default()
+    };
+
+    for record in &scan.undecodable {
+        // Deleting a record a newer version can read would lose data
+        let detail = if record.newer_layout {
+            format!("{}; upgrade to read it", record.error)
+        } else {
+            record.error.clone()
+        };
+        report.issues.push(Issue {
+            kind: IssueKind::UndecodableRecord,
+            project: None,
+            subject: format!("{} {}", record.kind, record.key),
+            detail,
+            fixable: !record.newer_layout,
+        });
+        if !record.newer_layout {
+            report.plan.remove_records.push(record.clone());
+        }
+    }
+
+    check_relations(&scan, &project_names, &label, &mut report);
+
+    for entity in &scan.entities {
+        check_entity(entity, &label, &mut report);
+    }
+
+    // Search engines build an empty index on first use, so that is not drift
+    if let Some(indexed_ids) = indexed_ids.filter(|ids| !ids.is_empty()) {
+        check_index(&scan.entities, indexed_ids, &label, &mut report);
+    }
+
+    report
+}
+
+fn check_relations(
+    scan: &RecordScan,
+    project_names: &HashMap<&ProjectId, &str>,
+    label: &dyn Fn(&ProjectId) -> String,
+    report: &mut DoctorReport,
+) {
+    let entities: HashMap<(&ProjectId, &str), &Entity> = scan
+        .entities
+        .iter()
+        .map(|e| ((&e.project_id, e.name.as_str()), e))
+        .collect();
+
+    for relation in &scan.relations {
+        let subject = relation_subject(relation);
+        let project = Some(label(&relation.project_id));
+
+        let from_project = relation.effective_from_project_id();
+        let to_project = relation.effective_to_project_id();
+        let from = entities.get(&(from_project, relation.from_name.as_str()));
+        let to = entities.get(&(to_project, relation.to_name.as_str()));
+
+        let mut missing = Vec::new();
+        if !project_names.contains_key(&relation.project_id) {
+            missing.push(format!("owning project {}", relation.project_id));
+        }
+        if from.is_none() {
+            missing.push(endpoint_description(
+                "source",
+                &relation.from_name,
+                from_project,
+                project_names,
+            ));
+        }
+        if to.is_none() {
+            missing.push(endpoint_description(
+                "target",
+                &relation.to_name,
+                to_project,
+                project_names,
+            ));
+        }
+
+        if !missing.is_empty() {
+            report.issues.push(Issue {
+                kind: IssueKind::DanglingRelation,
+                project,
+                subject,
+                detail: format!("missing {}", missing.join(", ")),
+                fixable: true,
+            });
+            report.plan.delete_relations.push(relation.clone());
+            continue;
+        }
+
+        let (from, to) = (from.unwrap(), to.unwrap());
+        let mut mismatches = Vec::new();
+        if relation.from_id != from.id {
+            mismatches.push(format!(
+                "from_id {} != {} ({})",
+                relation.from_id, from.id, from.name
+            ));
+        }
+        if relation.to_id != to.id {
+            mismatches.push(format!(
+                "to_id {} != {} ({})",
+                relation.to_id, to.id, to.name
+            ));
+        }
+
+        if !mismatches.is_empty() {
+            report.issues.push(Issue {
+                kind: IssueKind::IdMismatch,
+                project,
+                subject,
+                detail: mismatches.join(", "),
+                fixable: true,
+            });
+            let mut repaired = relation.clone();
+            repaired.from_id = from.id.clone();
+            repaired.to_id = to.id.clone();
+            report.plan.save_relations.push(repaired);
+        }
+    }
+}
+
+fn check_entity(entity: &Entity, label: &dyn Fn(&ProjectId) -> String, report: &mut DoctorReport) {
+    let project = Some(label(&entity.project_id));
+    let mut issue = |kind: IssueKind, detail: String, fixable: bool| {
+        report.issues.push(Issue {
+            kind,
+            project: project.clone(),
+            subject: entity.name.clone(),
+            detail,
+            fixable,
+        });
+    };
+
+    let mut repaired = entity.clone();
+    let mut changed = false;
+
+    // Renaming would change the storage key and orphan relations, so only report
+    if let Err(e) = validate_entity_name(&entity.name) {
+        issue(IssueKind::LimitViolation, e.to_string(), false);
+    }
+
+    let mut seen = HashSet::new();
+    let before = repaired.observations.len();
+    repaired
+        .observations
+        .retain(|o| seen.insert(o.content.trim().to_string()));
+    let duplicates = before - repaired.observations.len();
+    if duplicates > 0 {
+        issue(
+            IssueKind::DuplicateObservation,
+            format!("{} duplicate observation(s)", duplicates),
+            true,
+        );
+        changed = true;
+    }
+
+    // Limits are only reported: meeting them would mean cutting or dropping
+    // what the user stored, which is theirs to decide
+    for observation in &repaired.observations {
+        if let Err(e) = validate_observation(&observation.content) {
+            issue(
+                IssueKind::LimitViolation,
+                format!("observation {}: {}", observation.id, e),
+                false,
+            );
+        }
+    }
+    let before = repaired.observations.len();
+    repaired
+        .observations
+        .retain(|o| !o.content.trim().is_empty());
+    changed |= repaired.observations.len() != before;
+
+    let count = repaired.observations.len();
+    if count > MAX_OBSERVATIONS_PER_ENTITY {
+        issue(
+            IssueKind::LimitViolation,
+            format!(
+                "{} observations (max {}); consolidate or remove some",
+                count, MAX_OBSERVATIONS_PER_ENTITY
+            ),
+            false,
+        );
+    }
+
+    for tag in &repaired.tags {
+        if let Err(e) = validate_tag(tag) {
+            issue(
+                IssueKind::LimitViolation,
+                format!("tag '{}': {}", tag, e),
+                false,
+            );
+        }
+    }
+    if repaired.tags.len() > MAX_TAGS_PER_ENTITY {
+        issue(
+            IssueKind::LimitViolation,
+            format!("{} tags (max {})", repaired.tags.len(), MAX_TAGS_PER_ENTITY),
+            false,
+        );
+    }
+
+    if changed {
+        report.plan.save_entities.push(repaired);
+    }
+}
+
+fn check_index(
+    entities: &[Entity],
+    indexed_ids: &[String],
+    label: &dyn Fn(&ProjectId) -> String,
+    report: &mut DoctorReport,
+) {
+    let mut indexed: HashMap<&str, usize> = HashMap::new();
+    for id in indexed_ids {
+        *indexed.entry(id.as_str()).or_insert(0) += 1;
+    }
+    let stored: HashSet<String> = entities.iter().map(|e| e.id.to_string()).collect();
+
+    let mut drift = Vec::new();
+    for entity in entities {
+        let id = entity.id.to_string();
+        match indexed.get(id.as_str()) {
+            None => drift.push((
+                Some(label(&entity.project_id)),
+                entity.name.clone(),
+                "not in full-text index".to_string(),
+            )),
+            Some(&n) if n > 1 => drift.push((
+                Some(label(&entity.project_id)),
+                entity.name.clone(),
+                format!("indexed {} times", n),
+            )),
+            _ => {}
+        }
+    }
+
+    let mut stale: Vec<&str> = indexed
+        .keys()
+        .copied()
+        .filter(|id| !stored.contains(*id))
+        .collect();
+    stale.sort_unstable();
+    for id in stale {
+        drift.push((
+            None,
+            id.to_string(),
+            "index document for missing entity".to_string(),
+        ));
+    }
+
+    report.rebuild_index = !drift.is_empty();
+    for (project, subject, detail) in drift {
+        report.issues.push(Issue {
+            kind: IssueKind::IndexDrift,
+            project,
+            subject,
+            detail,
+            fixable: true,
+        });
+    }
+}
+
+fn relation_subject(relation: &Relation) -> String {
+    format!(
+        "{} -[{}]-> {}",
+        relation.from_name, relation.relation_type, relation.to_name
+    )
+}
+
+fn endpoint_description(
+    role: &str,
+    name: &str,
+    project_id: &ProjectId,
+    project_names: &HashMap<&ProjectId, &str>,
+) -> String {
+    match project_names.get(project_id) {
+        Some(project) => format!("{} '{}' in {}", role, name, project),
+        None => format!("{} '{}' in deleted project {}", role, name, project_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStorage;
+    use parsnip_core::{MAX_OBSERVATION_LEN, MAX_TAG_LEN};
+
+    async fn seeded() -> (MemoryStorage, Project, Project) {
+        let storage = MemoryStorage::new();
+        let work = Project::new("work");
+        let home = Project::new("home");
+        storage.save_project(&work).await.unwrap();
+        storage.save_project(&home).await.unwrap();
+
+        let alice = Entity::new(work.id.clone(), "Alice", "person");
+        let acme = Entity::new(work.id.clone(), "Acme", "company");
+        let bob = Entity::new(home.id.clone(), "Bob", "person");
+        storage.save_entity(&alice).await.unwrap();
+        storage.save_entity(&acme).await.unwrap();
+        storage.save_entity(&bob).await.unwrap();
+
+        let works_at = Relation::new(
+            work.id.clone(),
+            alice.id.clone(),
+            "Alice",
+            acme.id.clone(),
+            "Acme",
+            "works_at",
+        );
+        let knows = Relation::new_cross_project(
+            work.id.clone(),
+            alice.id.clone(),
+            "Alice",
+            work.id.clone(),
+            bob.id.clone(),
+            "Bob",
+            home.id.clone(),
+            "knows",
+        );
+        storage.save_relation(&works_at).await.unwrap();
+        storage.save_relation(&knows).await.unwrap();
+
+        (storage, work, home)
+    }
+
+    #[tokio::test]
+    async fn test_healthy_graph_has_no_issues() {
+        let (storage, _, _) = seeded().await;
+        let report = Doctor::new(&storage).run().await.unwrap();
+
+        assert!(report.is_healthy(), "{:?}", report.issues);
+        assert_eq!(report.projects_checked, 2);
+        assert_eq!(report.entities_checked, 3);
+        assert_eq!(report.relations_checked, 2);
+    }
+
+    #[tokio::test]
+    async fn test_detects_and_repairs_graph_issues() {
+        let (storage, work, home) = seeded().await;
+
+        // Deleting the other project leaves the cross-project edge behind
+        storage.delete_project("home").await.unwrap();
+        let _ = home;
+
+        // Name-based relations carry random IDs
+        storage
+            .save_relation(&Relation::from_names(
+                work.id.clone(),
+                "Acme",
+                "Alice",
+                "employs",
+            ))
+            .await
+            .unwrap();
+
+        let mut alice = storage
+            .get_entity("Alice", &work.id)
+            .await
+            .unwrap()
+            .unwrap();
+        alice.add_observation("Likes tea");
+        alice.add_observation("Likes tea ");
+        storage.save_entity(&alice).await.unwrap();
+
+        let doctor = Doctor::new(&storage);
+        let mut report = doctor.run().await.unwrap();
+        assert_eq!(report.count(IssueKind::DanglingRelation), 1);
+        assert_eq!(report.count(IssueKind::IdMismatch), 1);
+        assert_eq!(report.count(IssueKind::DuplicateObservation), 1);
+        assert_eq!(report.fixable_count(), report.issues.len());
+
+        doctor.repair(&mut report).await.unwrap();
+        assert!(report.fixed);
+        assert!(doctor.run().await.unwrap().is_healthy());
+
+        let alice = storage
+            .get_entity("Alice", &work.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(alice.observations.len(), 1);
+        let relations = storage.get_all_relations(&work.id).await.unwrap();
+        assert_eq!(relations.len(), 2);
+        let employs = relations
+            .iter()
+            .find(|r| r.relation_type == "employs")
+            .unwrap();
+        assert_eq!(employs.to_id, alice.id);
+    }
+
+    #[tokio::test]
+    async fn test_limit_violations_keep_data() {
+        let (storage, work, _) = seeded().await;
+        let mut alice = storage
+            .get_entity("Alice", &work.id)
+            .await
+            .unwrap()
+            .unwrap();
+        for i in 0..MAX_OBSERVATIONS_PER_ENTITY + 2 {
+            alice.add_observation(format!("Fact {}", i));
+        }
+        let long = "x".repeat(MAX_OBSERVATION_LEN + 10);
+        alice.observations[0].content = long.clone();
+        let tag = "t".repeat(MAX_TAG_LEN + 10);
+        alice.add_tag(tag.clone());
+        storage.save_entity(&alice).await.unwrap();
+
+        let doctor = Doctor::new(&storage);
+        let mut report = doctor.run().await.unwrap();
+        assert_eq!(report.count(IssueKind::LimitViolation), 3);
+        assert_eq!(report.fixable_count(), 0);
+
+        // Nothing is cut or dropped to meet the limits
+        doctor.repair(&mut report).await.unwrap();
+        let repaired = storage
+            .get_entity("Alice", &work.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(repaired.observations.len(), MAX_OBSERVATIONS_PER_ENTITY + 2);
+        assert_eq!(repaired.observations[0].content, long);
+        assert_eq!(repaired.tags, vec![tag]);
+    }
+
+    #[tokio::test]
+    async fn test_detects_index_drift() {
+        let (storage, work, _) = seeded().await;
+        let alice = storage
+            .get_entity("Alice", &work.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let indexed = vec![
+            alice.id.to_string(),
+            alice.id.to_string(),
+            "01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string(),
+        ];
+        let report = Doctor::new(&storage)
+            .with_index(indexed)
+            .run()
+            .await
+            .unwrap();
+
+        // Alice twice, Acme and Bob missing, one stale document
+        assert_eq!(report.count(IssueKind::IndexDrift), 4);
+        assert!(report.rebuild_index);
+    }
+}
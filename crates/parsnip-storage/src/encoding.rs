@@ -0,0 +1,576 @@
+//! Encoding of stored projects, entities and relations
+//!
+//! A record is stored either as JSON, as every record was before schema
+//! version 2, or in a versioned binary layout: one tag byte naming the
+//! layout, then the record in [postcard]. Binary records leave out field
+//! names and store IDs and timestamps as numbers rather than text, so they
+//! are smaller and decode several times faster. [`RecordFormat::Compressed`]
+//! also compresses the text of an entity's observations with zstd.
+//!
+//! Decoding looks at the first byte, so records of any format can sit side
+//! by side in one table. The audit log and the change log stay JSON.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use parsnip_core::project::ProjectSettings;
+use parsnip_core::{
+    ArchivedObservation, Entity, EntityId, Observation, ObservationId, Project, ProjectId,
+    Relation, RelationId,
+};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::error::{StorageError, StorageResult};
+
+/// Tag byte of the first binary layout
+const BINARY_V1: u8 = 1;
+
+/// Tag bytes below this are reserved for binary layouts, so a record that
+/// starts with one this version does not know came from a newer version
+const RESERVED_TAGS: u8 = 0x20;
+
+/// Observation text shorter than this is not worth compressing
+#[cfg(feature = "zstd")]
+const MIN_COMPRESSED_TEXT: usize = 256;
+
+/// How a backend writes records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordFormat {
+    /// JSON, which SQL tools can read with `json_extract`
+    Json,
+    /// Versioned binary layout
+    #[default]
+    Binary,
+    /// Binary, with observation text compressed with zstd
+    Compressed,
+}
+
+impl RecordFormat {
+    /// Whether this build can write the format
+    pub fn is_available(self) -> bool {
+        self != Self::Compressed || cfg!(feature = "zstd")
+    }
+}
+
+impl fmt::Display for RecordFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Json => "json",
+            Self::Binary => "binary",
+            Self::Compressed => "compressed",
+        })
+    }
+}
+
+impl FromStr for RecordFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "binary" => Ok(Self::Binary),
+            "compressed" | "zstd" => Ok(Self::Compressed),
+            _ => Err(format!(
+                "Unknown record format '{}'. Valid: json, binary, compressed",
+                s
+            )),
+        }
+    }
+}
+
+/// A record a backend stores
+pub trait StoredRecord: Sized {
+    fn encode(&self, format: RecordFormat) -> StorageResult<Vec<u8>>;
+
+    /// Decode a record in any format
+    fn decode(bytes: &[u8]) -> StorageResult<Self>;
+}
+
+impl StoredRecord for Entity {
+    fn encode(&self, format: RecordFormat) -> StorageResult<Vec<u8>> {
+        match format {
+            RecordFormat::Json => Ok(serde_json::to_vec(self)?),
+            RecordFormat::Binary => binary(&EntityV1::new(self, false)?),
+            RecordFormat::Compressed => binary(&EntityV1::new(self, true)?),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> StorageResult<Self> {
+        match Layout::of(bytes)? {
+            Layout::Json => Ok(serde_json::from_slice(bytes)?),
+            Layout::BinaryV1(body) => from_postcard::<EntityV1>(body)?.into_entity(),
+        }
+    }
+}
+
+impl StoredRecord for Relation {
+    fn encode(&self, format: RecordFormat) -> StorageResult<Vec<u8>> {
+        match format {
+            RecordFormat::Json => Ok(serde_json::to_vec(self)?),
+            RecordFormat::Binary | RecordFormat::Compressed => binary(&RelationV1::new(self)?),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> StorageResult<Self> {
+        match Layout::of(bytes)? {
+            Layout::Json => Ok(serde_json::from_slice(bytes)?),
+            Layout::BinaryV1(body) => from_postcard::<RelationV1>(body)?.into_relation(),
+        }
+    }
+}
+
+impl StoredRecord for Project {
+    fn encode(&self, format: RecordFormat) -> StorageResult<Vec<u8>> {
+        match format {
+            RecordFormat::Json => Ok(serde_json::to_vec(self)?),
+            RecordFormat::Binary | RecordFormat::Compressed => binary(&ProjectV1::new(self)),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> StorageResult<Self> {
+        match Layout::of(bytes)? {
+            Layout::Json => Ok(serde_json::from_slice(bytes)?),
+            Layout::BinaryV1(body) => from_postcard::<ProjectV1>(body)?.into_project(),
+        }
+    }
+}
+
+/// Layout of stored bytes, read from the first byte
+enum Layout<'a> {
+    Json,
+    BinaryV1(&'a [u8]),
+}
+
+impl<'a> Layout<'a> {
+    fn of(bytes: &'a [u8]) -> StorageResult<Self> {
+        match bytes.first() {
+            Some(b'{') => Ok(Self::Json),
+            Some(&BINARY_V1) => Ok(Self::BinaryV1(&bytes[1..])),
+            Some(&tag) if tag < RESERVED_TAGS => Err(StorageError::UnknownLayout(tag)),
+            Some(tag) => Err(StorageError::Encoding(format!(
+                "unknown record layout {:#04x}",
+                tag
+            ))),
+            None => Err(StorageError::Encoding("empty record".to_string())),
+        }
+    }
+}
+
+fn binary(record: &impl Serialize) -> StorageResult<Vec<u8>> {
+    postcard::to_extend(record, vec![BINARY_V1]).map_err(|e| StorageError::Encoding(e.to_string()))
+}
+
+fn from_postcard<'a, T: Deserialize<'a>>(body: &'a [u8]) -> StorageResult<T> {
+    postcard::from_bytes(body).map_err(|e| StorageError::Encoding(e.to_string()))
+}
+
+/// Seconds and nanoseconds since the epoch, which keeps every digit JSON does
+type Timestamp = (i64, u32);
+
+fn timestamp(time: &DateTime<Utc>) -> Timestamp {
+    (time.timestamp(), time.timestamp_subsec_nanos())
+}
+
+fn from_timestamp((secs, nanos): Timestamp) -> StorageResult<DateTime<Utc>> {
+    DateTime::from_timestamp(secs, nanos)
+        .ok_or_else(|| StorageError::Encoding(format!("timestamp out of range: {}", secs)))
+}
+
+/// Metadata as JSON text, since its values are arbitrary JSON; empty when
+/// there is none
+fn metadata(map: &HashMap<String, serde_json::Value>) -> StorageResult<String> {
+    if map.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(serde_json::to_string(map)?)
+}
+
+fn from_metadata(text: &str) -> StorageResult<HashMap<String, serde_json::Value>> {
+    if text.is_empty() {
+        return Ok(HashMap::new());
+    }
+    Ok(serde_json::from_str(text)?)
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProjectV1 {
+    id: u128,
+    name: String,
+    description: Option<String>,
+    created_at: Timestamp,
+    fulltext_enabled: bool,
+    fuzzy_threshold: f32,
+}
+
+impl ProjectV1 {
+    fn new(project: &Project) -> Self {
+        Self {
+            id: project.id.0 .0,
+            name: project.name.clone(),
+            description: project.description.clone(),
+            created_at: timestamp(&project.created_at),
+            fulltext_enabled: project.settings.fulltext_enabled,
+            fuzzy_threshold: project.settings.fuzzy_threshold,
+        }
+    }
+
+    fn into_project(self) -> StorageResult<Project> {
+        Ok(Project {
+            id: ProjectId(Ulid(self.id)),
+            name: self.name,
+            description: self.description,
+            created_at: from_timestamp(self.created_at)?,
+            settings: ProjectSettings {
+                fulltext_enabled: self.fulltext_enabled,
+                fuzzy_threshold: self.fuzzy_threshold,
+            },
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RelationV1 {
+    id: u128,
+    project_id: u128,
+    from_id: u128,
+    from_name: String,
+    from_project_id: Option<u128>,
+    to_id: u128,
+    to_name: String,
+    to_project_id: Option<u128>,
+    relation_type: String,
+    weight: Option<f64>,
+    metadata: String,
+    created_at: Timestamp,
+}
+
+impl RelationV1 {
+    fn new(relation: &Relation) -> StorageResult<Self> {
+        Ok(Self {
+            id: relation.id.0 .0,
+            project_id: relation.project_id.0 .0,
+            from_id: relation.from_id.0 .0,
+            from_name: relation.from_name.clone(),
+            from_project_id: relation.from_project_id.as_ref().map(|id| id.0 .0),
+            to_id: relation.to_id.0 .0,
+            to_name: relation.to_name.clone(),
+            to_project_id: relation.to_project_id.as_ref().map(|id| id.0 .0),
+            relation_type: relation.relation_type.clone(),
+            weight: relation.weight,
+            metadata: metadata(&relation.metadata)?,
+            created_at: timestamp(&relation.created_at),
+        })
+    }
+
+    fn into_relation(self) -> StorageResult<Relation> {
+        Ok(Relation {
+            id: RelationId(Ulid(self.id)),
+            project_id: ProjectId(Ulid(self.project_id)),
+            from_id: EntityId(Ulid(self.from_id)),
+            from_name: self.from_name,
+            from_project_id: self.from_project_id.map(|id| ProjectId(Ulid(id))),
+            to_id: EntityId(Ulid(self.to_id)),
+            to_name: self.to_name,
+            to_project_id: self.to_project_id.map(|id| ProjectId(Ulid(id))),
+            relation_type: self.relation_type,
+            weight: self.weight,
+            metadata: from_metadata(&self.metadata)?,
+            created_at: from_timestamp(self.created_at)?,
+        })
+    }
+}
+
+/// An observation without its content, which is kept in [`Text`]
+#[derive(Serialize, Deserialize)]
+struct ObservationV1 {
+    id: u128,
+    source: Option<String>,
+    confidence: Option<f32>,
+    created_at: Timestamp,
+}
+
+impl ObservationV1 {
+    fn new(observation: &Observation) -> Self {
+        Self {
+            id: observation.id.0 .0,
+            source: observation.source.clone(),
+            confidence: observation.confidence,
+            created_at: timestamp(&observation.created_at),
+        }
+    }
+
+    fn into_observation(self, content: String) -> StorageResult<Observation> {
+        Ok(Observation {
+            id: ObservationId(Ulid(self.id)),
+            content,
+            source: self.source,
+            confidence: self.confidence,
+            created_at: from_timestamp(self.created_at)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchivedV1 {
+    observation: ObservationV1,
+    replaced_by: Vec<u128>,
+    reason: String,
+    archived_at: Timestamp,
+}
+
+/// Contents of an entity's observations, then of its archived ones
+#[derive(Serialize, Deserialize)]
+enum Text {
+    Plain(Vec<String>),
+    /// The postcard encoding of the contents, compressed with zstd
+    Zstd(Vec<u8>),
+}
+
+impl Text {
+    fn new(contents: Vec<String>, compress: bool) -> StorageResult<Self> {
+        #[cfg(feature = "zstd")]
+        if compress && contents.iter().map(String::len).sum::<usize>() >= MIN_COMPRESSED_TEXT {
+            let plain = postcard::to_stdvec(&contents)
+                .map_err(|e| StorageError::Encoding(e.to_string()))?;
+            let compressed = zstd::bulk::compress(&plain, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+            return Ok(Self::Zstd(compressed));
+        }
+        #[cfg(not(feature = "zstd"))]
+        if compress {
+            return Err(StorageError::Encoding(
+                "compressed records need zstd support; rebuild with --features zstd".to_string(),
+            ));
+        }
+        Ok(Self::Plain(contents))
+    }
+
+    fn into_contents(self) -> StorageResult<Vec<String>> {
+        match self {
+            Self::Plain(contents) => Ok(contents),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(compressed) => {
+                let plain = zstd::stream::decode_all(compressed.as_slice())?;
+                from_postcard(&plain)
+            }
+            #[cfg(not(feature = "zstd"))]
+            Self::Zstd(_) => Err(StorageError::Encoding(
+                "record is compressed with zstd; rebuild with --features zstd".to_string(),
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EntityV1 {
+    id: u128,
+    project_id: u128,
+    name: String,
+    entity_type: String,
+    observations: Vec<ObservationV1>,
+    observation_history: Vec<ArchivedV1>,
+    text: Text,
+    tags: Vec<String>,
+    metadata: String,
+    created_at: Timestamp,
+    updated_at: Timestamp,
+    embedding: Option<Vec<f32>>,
+}
+
+impl EntityV1 {
+    fn new(entity: &Entity, compress: bool) -> StorageResult<Self> {
+        let contents = entity
+            .observations
+            .iter()
+            .chain(entity.observation_history.iter().map(|a| &a.observation))
+            .map(|observation| observation.content.clone())
+            .collect();
+
+        Ok(Self {
+            id: entity.id.0 .0,
+            project_id: entity.project_id.0 .0,
+            name: entity.name.clone(),
+            entity_type: entity.entity_type.0.clone(),
+            observations: entity.observations.iter().map(ObservationV1::new).collect(),
+            observation_history: entity
+                .observation_history
+                .iter()
+                .map(|archived| ArchivedV1 {
+                    observation: ObservationV1::new(&archived.observation),
+                    replaced_by: archived.replaced_by.iter().map(|id| id.0 .0).collect(),
+                    reason: archived.reason.clone(),
+                    archived_at: timestamp(&archived.archived_at),
+                })
+                .collect(),
+            text: Text::new(contents, compress)?,
+            tags: entity.tags.clone(),
+            metadata: metadata(&entity.metadata)?,
+            created_at: timestamp(&entity.created_at),
+            updated_at: timestamp(&entity.updated_at),
+            embedding: entity.embedding.clone(),
+        })
+    }
+
+    fn into_entity(self) -> StorageResult<Entity> {
+        let mut contents = self.text.into_contents()?.into_iter();
+        if contents.len() != self.observations.len() + self.observation_history.len() {
+            return Err(StorageError::Encoding(format!(
+                "entity {} has {} observation texts for {} observations",
+                self.name,
+                contents.len(),
+                self.observations.len() + self.observation_history.len()
+            )));
+        }
+
+        let observations = self
+            .observations
+            .into_iter()
+            .zip(contents.by_ref())
+            .map(|(observation, content)| observation.into_observation(content))
+            .collect::<StorageResult<Vec<_>>>()?;
+        let observation_history = self
+            .observation_history
+            .into_iter()
+            .zip(contents)
+            .map(|(archived, content)| {
+                Ok(ArchivedObservation {
+                    observation: archived.observation.into_observation(content)?,
+                    replaced_by: archived
+                        .replaced_by
+                        .into_iter()
+                        .map(|id| ObservationId(Ulid(id)))
+                        .collect(),
+                    reason: archived.reason,
+                    archived_at: from_timestamp(archived.archived_at)?,
+                })
+            })
+            .collect::<StorageResult<Vec<_>>>()?;
+
+        Ok(Entity {
+            id: EntityId(Ulid(self.id)),
+            project_id: ProjectId(Ulid(self.project_id)),
+            name: self.name,
+            entity_type: self.entity_type.into(),
+            observations,
+            observation_history,
+            tags: self.tags,
+            metadata: from_metadata(&self.metadata)?,
+            created_at: from_timestamp(self.created_at)?,
+            updated_at: from_timestamp(self.updated_at)?,
+            embedding: self.embedding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_entity() -> Entity {
+        let project = ProjectId::new();
+        let mut entity = Entity::new(project, "Alice", "person");
+        for i in 0..20 {
+            entity.add_observation(format!("Alice mentioned the quarterly roadmap, item {}", i));
+        }
+        entity.observations[0] = Observation::new("Works at Google")
+            .with_source("chat")
+            .with_confidence(0.75);
+        let replaced = entity.observations.remove(1);
+        entity.observation_history.push(ArchivedObservation {
+            replaced_by: vec![entity.observations[0].id.clone()],
+            observation: replaced,
+            reason: "dedupe".to_string(),
+            archived_at: Utc::now(),
+        });
+        entity.tags = vec!["team".to_string()];
+        entity
+            .metadata
+            .insert("rank".to_string(), json!({"score": 1.5, "list": [1, null]}));
+        entity.embedding = Some(vec![0.25, -1.0]);
+        entity
+    }
+
+    fn formats() -> Vec<RecordFormat> {
+        [
+            RecordFormat::Json,
+            RecordFormat::Binary,
+            RecordFormat::Compressed,
+        ]
+        .into_iter()
+        .filter(|format| format.is_available())
+        .collect()
+    }
+
+    #[test]
+    fn test_roundtrip_is_lossless() {
+        let entity = sample_entity();
+        let mut relation = Relation::from_names(entity.project_id.clone(), "Alice", "Bob", "knows");
+        relation.to_project_id = Some(ProjectId::new());
+        relation.weight = Some(0.1);
+        let project = Project::new("work").with_description("day job");
+
+        for format in formats() {
+            let back = Entity::decode(&entity.encode(format).unwrap()).unwrap();
+            assert_eq!(
+                serde_json::to_value(&back).unwrap(),
+                serde_json::to_value(&entity).unwrap(),
+                "{}",
+                format
+            );
+            let back = Relation::decode(&relation.encode(format).unwrap()).unwrap();
+            assert_eq!(
+                serde_json::to_value(&back).unwrap(),
+                serde_json::to_value(&relation).unwrap()
+            );
+            let back = Project::decode(&project.encode(format).unwrap()).unwrap();
+            assert_eq!(
+                serde_json::to_value(&back).unwrap(),
+                serde_json::to_value(&project).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_binary_is_smaller() {
+        let entity = sample_entity();
+        let json = entity.encode(RecordFormat::Json).unwrap();
+        let binary = entity.encode(RecordFormat::Binary).unwrap();
+        assert_eq!(binary[0], BINARY_V1);
+        assert!(binary.len() < json.len() * 3 / 4);
+
+        #[cfg(feature = "zstd")]
+        {
+            let compressed = entity.encode(RecordFormat::Compressed).unwrap();
+            assert!(compressed.len() < binary.len() * 3 / 4);
+            // Short text is left alone
+            let short = Entity::new(ProjectId::new(), "Bob", "person");
+            assert_eq!(
+                short.encode(RecordFormat::Compressed).unwrap(),
+                short.encode(RecordFormat::Binary).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_layout() {
+        for bytes in [&b""[..], b"not json", &[BINARY_V1, 0xff, 0xff]] {
+            assert!(matches!(
+                Entity::decode(bytes),
+                Err(StorageError::Encoding(_))
+            ));
+        }
+        assert!(matches!(
+            Entity::decode(&[BINARY_V1 + 1, 0]),
+            Err(StorageError::UnknownLayout(2))
+        ));
+        assert_eq!(
+            "zstd".parse::<RecordFormat>().unwrap(),
+            RecordFormat::Compressed
+        );
+        assert!("xml".parse::<RecordFormat>().is_err());
+    }
+}
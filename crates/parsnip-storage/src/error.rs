@@ -14,6 +14,15 @@ pub enum StorageError {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("Record encoding error: {0}")]
+    Encoding(String),
+
+    #[error("Record layout {0:#04x} is unknown; it was written by a newer version")]
+    UnknownLayout(u8),
+
+    #[error("Change log starts at #{0}; the records before it were trimmed")]
+    ChangesTrimmed(u64),
+
     #[error("Entity not found: {0}")]
     EntityNotFound(String),
 
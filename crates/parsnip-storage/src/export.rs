@@ -0,0 +1,108 @@
+//! Portable JSON export of projects
+//!
+//! The format written by `parsnip export` and read by `parsnip import`: each
+//! project with its entities and relations, referring to entities by name.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::StorageResult;
+use crate::traits::StorageBackend;
+use parsnip_core::{Entity, Project, Relation};
+
+/// Version written to new exports
+pub const EXPORT_VERSION: &str = "1.0";
+
+/// Export format matching the knowledge graph structure
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportData {
+    pub version: String,
+    pub projects: Vec<ProjectExport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectExport {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub entities: Vec<EntityExport>,
+    pub relations: Vec<RelationExport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EntityExport {
+    pub name: String,
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+    pub observations: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelationExport {
+    pub from: String,
+    pub to: String,
+    #[serde(rename = "relationType")]
+    pub relation_type: String,
+}
+
+impl From<&Entity> for EntityExport {
+    fn from(e: &Entity) -> Self {
+        Self {
+            name: e.name.clone(),
+            entity_type: e.entity_type.0.clone(),
+            observations: e.observations.iter().map(|o| o.content.clone()).collect(),
+            tags: e.tags.clone(),
+        }
+    }
+}
+
+impl From<&Relation> for RelationExport {
+    fn from(r: &Relation) -> Self {
+        Self {
+            from: r.from_name.clone(),
+            to: r.to_name.clone(),
+            relation_type: r.relation_type.clone(),
+        }
+    }
+}
+
+impl ProjectExport {
+    pub fn new(project: &Project, entities: &[Entity], relations: &[Relation]) -> Self {
+        Self {
+            name: project.name.clone(),
+            description: project.description.clone(),
+            entities: entities.iter().map(EntityExport::from).collect(),
+            relations: relations.iter().map(RelationExport::from).collect(),
+        }
+    }
+}
+
+impl ExportData {
+    pub fn new(projects: Vec<ProjectExport>) -> Self {
+        Self {
+            version: EXPORT_VERSION.to_string(),
+            projects,
+        }
+    }
+}
+
+/// Export whole projects
+pub async fn export_projects(
+    storage: &dyn StorageBackend,
+    projects: &[Project],
+) -> StorageResult<ExportData> {
+    let mut exports = Vec::new();
+    for project in projects {
+        let entities = storage.get_all_entities(&project.id).await?;
+        let relations = storage.get_all_relations(&project.id).await?;
+        tracing::debug!(
+            "Exporting project '{}': {} entities, {} relations",
+            project.name,
+            entities.len(),
+            relations.len()
+        );
+        exports.push(ProjectExport::new(project, &entities, &relations));
+    }
+    Ok(ExportData::new(exports))
+}
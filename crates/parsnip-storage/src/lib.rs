@@ -5,8 +5,23 @@
 
 #![allow(clippy::result_large_err)]
 
+pub mod audit;
+#[cfg(any(feature = "redb", feature = "sqlite"))]
+mod blocking;
+pub mod cache;
+pub mod changelog;
+pub mod changes;
+pub mod consolidate;
+pub mod convert;
+#[cfg(unix)]
+pub mod daemon;
+pub mod doctor;
+pub mod encoding;
 pub mod error;
+pub mod export;
+pub mod metrics;
 pub mod migration;
+pub mod ops;
 pub mod traits;
 
 #[cfg(feature = "redb")]
@@ -17,8 +32,24 @@ pub mod sqlite;
 
 pub mod memory;
 
+pub use audit::{Actor, AuditEntry, AuditFilter, AuditedStorage, Transport};
+pub use cache::{CacheStats, CachedStorage, StorageCache};
+pub use changelog::{ChangeEvent, ChangeRecord, ChangeSubscription, CHANGE_LOG_CAPACITY};
+pub use changes::{ChangeFeed, ChangeKind, ChangeWatcher, StorageChange, DEFAULT_POLL_INTERVAL};
+pub use convert::{copy_store, ConvertError, CopyStats};
+#[cfg(unix)]
+pub use daemon::{DaemonClient, DaemonServer, DaemonStatus, SOCKET_FILE};
+pub use doctor::{
+    Doctor, DoctorReport, Issue, IssueKind, RecordKind, RecordScan, RepairPlan, UndecodableRecord,
+};
+pub use encoding::{RecordFormat, StoredRecord};
 pub use error::{StorageError, StorageResult};
+pub use export::{
+    export_projects, EntityExport, ExportData, ProjectExport, RelationExport, EXPORT_VERSION,
+};
+pub use metrics::{InstrumentedStorage, StorageMetrics};
 pub use migration::{Migratable, SchemaVersion, CURRENT_VERSION};
+pub use ops::{OpsError, OpsResult, Scope};
 pub use traits::StorageBackend;
 
 #[cfg(feature = "redb")]
@@ -1,11 +1,15 @@
 //! In-memory storage backend for testing
 
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::changelog::{check_trimmed, first_kept, ChangeEvent, ChangeRecord, CHANGE_LOG_CAPACITY};
+use crate::changes::ChangeKind;
 use crate::error::{StorageError, StorageResult};
 use crate::traits::StorageBackend;
 use async_trait::async_trait;
 use parsnip_core::{Entity, Graph, Project, ProjectId, Relation};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::RwLock;
+use tokio::sync::watch;
 
 /// In-memory storage backend
 ///
@@ -14,6 +18,10 @@ pub struct MemoryStorage {
     entities: RwLock<HashMap<(ProjectId, String), Entity>>,
     relations: RwLock<Vec<Relation>>,
     projects: RwLock<HashMap<String, Project>>,
+    audit: RwLock<Vec<AuditEntry>>,
+    changes: RwLock<VecDeque<ChangeRecord>>,
+    change_capacity: u64,
+    signal: watch::Sender<u64>,
 }
 
 impl MemoryStorage {
@@ -22,8 +30,56 @@ impl MemoryStorage {
             entities: RwLock::new(HashMap::new()),
             relations: RwLock::new(Vec::new()),
             projects: RwLock::new(HashMap::new()),
+            audit: RwLock::new(Vec::new()),
+            changes: RwLock::new(VecDeque::new()),
+            change_capacity: CHANGE_LOG_CAPACITY,
+            signal: watch::channel(0).0,
         }
     }
+
+    /// Keep this many change log records rather than [`CHANGE_LOG_CAPACITY`]
+    pub fn with_change_capacity(mut self, capacity: u64) -> Self {
+        self.change_capacity = capacity;
+        self
+    }
+
+    /// Append events to the change log
+    ///
+    /// Called with the lock of the records they describe still held, so the
+    /// log is in the order the writes were made.
+    fn log_changes(&self, events: impl IntoIterator<Item = ChangeEvent>) -> StorageResult<()> {
+        let mut changes = self
+            .changes
+            .write()
+            .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
+        let before = changes.back().map_or(0, |record| record.seq);
+        let mut seq = before;
+        for event in events {
+            seq += 1;
+            changes.push_back(ChangeRecord {
+                seq,
+                ..ChangeRecord::new(event)
+            });
+        }
+        if seq > before {
+            let first = first_kept(seq, self.change_capacity);
+            while changes.front().is_some_and(|record| record.seq < first) {
+                changes.pop_front();
+            }
+            self.signal.send_replace(seq);
+        }
+        Ok(())
+    }
+
+    /// Take the relations matching `predicate` out of `relations`
+    fn remove_relations(
+        relations: &mut Vec<Relation>,
+        predicate: impl Fn(&Relation) -> bool,
+    ) -> Vec<Relation> {
+        let (removed, kept) = relations.drain(..).partition(|r| predicate(r));
+        *relations = kept;
+        removed
+    }
 }
 
 impl Default for MemoryStorage {
@@ -53,11 +109,13 @@ impl StorageBackend for MemoryStorage {
             .entities
             .write()
             .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
-        entities.insert(
-            (entity.project_id.clone(), entity.name.clone()),
-            entity.clone(),
-        );
-        Ok(())
+        let replaced = entities
+            .insert(
+                (entity.project_id.clone(), entity.name.clone()),
+                entity.clone(),
+            )
+            .is_some();
+        self.log_changes([ChangeEvent::entity(entity, ChangeKind::of_upsert(replaced))])
     }
 
     async fn get_entity(
@@ -99,8 +157,12 @@ impl StorageBackend for MemoryStorage {
             .entities
             .write()
             .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
-        entities.remove(&(project_id.clone(), name.to_string()));
-        Ok(())
+        let removed = entities.remove(&(project_id.clone(), name.to_string()));
+        self.log_changes(
+            removed
+                .as_ref()
+                .map(|entity| ChangeEvent::entity(entity, ChangeKind::Deleted)),
+        )
     }
 
     // Relation operations
@@ -121,6 +183,7 @@ impl StorageBackend for MemoryStorage {
 
         if !exists {
             relations.push(relation.clone());
+            self.log_changes([ChangeEvent::relation(relation, ChangeKind::Created)])?;
         }
         Ok(())
     }
@@ -190,13 +253,17 @@ impl StorageBackend for MemoryStorage {
             .relations
             .write()
             .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
-        relations.retain(|r| {
-            !(r.project_id == *project_id
+        let removed = Self::remove_relations(&mut relations, |r| {
+            r.project_id == *project_id
                 && r.from_name == from
                 && r.to_name == to
-                && r.relation_type == relation_type)
+                && r.relation_type == relation_type
         });
-        Ok(())
+        self.log_changes(
+            removed
+                .iter()
+                .map(|relation| ChangeEvent::relation(relation, ChangeKind::Deleted)),
+        )
     }
 
     async fn delete_relations_for_entity(
@@ -208,11 +275,14 @@ impl StorageBackend for MemoryStorage {
             .relations
             .write()
             .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
-        relations.retain(|r| {
-            !(r.project_id == *project_id
-                && (r.from_name == entity_name || r.to_name == entity_name))
+        let removed = Self::remove_relations(&mut relations, |r| {
+            r.project_id == *project_id && (r.from_name == entity_name || r.to_name == entity_name)
         });
-        Ok(())
+        self.log_changes(
+            removed
+                .iter()
+                .map(|relation| ChangeEvent::relation(relation, ChangeKind::Deleted)),
+        )
     }
 
     // Project operations
@@ -222,8 +292,13 @@ impl StorageBackend for MemoryStorage {
             .projects
             .write()
             .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
-        projects.insert(project.name.clone(), project.clone());
-        Ok(())
+        let replaced = projects
+            .insert(project.name.clone(), project.clone())
+            .is_some();
+        self.log_changes([ChangeEvent::project(
+            project,
+            ChangeKind::of_upsert(replaced),
+        )])
     }
 
     async fn get_project(&self, name: &str) -> StorageResult<Option<Project>> {
@@ -266,7 +341,15 @@ impl StorageBackend for MemoryStorage {
                     .entities
                     .write()
                     .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
-                entities.retain(|(pid, _), _| pid != &project.id);
+                let mut removed = Vec::new();
+                entities.retain(|(pid, _), entity| {
+                    let keep = pid != &project.id;
+                    if !keep {
+                        removed.push(ChangeEvent::entity(entity, ChangeKind::Deleted));
+                    }
+                    keep
+                });
+                self.log_changes(removed)?;
             }
 
             // Delete all relations
@@ -275,7 +358,13 @@ impl StorageBackend for MemoryStorage {
                     .relations
                     .write()
                     .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
-                relations.retain(|r| r.project_id != project.id);
+                let removed =
+                    Self::remove_relations(&mut relations, |r| r.project_id == project.id);
+                self.log_changes(
+                    removed
+                        .iter()
+                        .map(|relation| ChangeEvent::relation(relation, ChangeKind::Deleted)),
+                )?;
             }
 
             // Delete project
@@ -284,7 +373,9 @@ impl StorageBackend for MemoryStorage {
                     .projects
                     .write()
                     .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
-                projects.remove(name);
+                if let Some(project) = projects.remove(name) {
+                    self.log_changes([ChangeEvent::project(&project, ChangeKind::Deleted)])?;
+                }
             }
         }
 
@@ -300,6 +391,52 @@ impl StorageBackend for MemoryStorage {
         }
         Ok(())
     }
+
+    async fn append_audit(&self, entry: &AuditEntry) -> StorageResult<u64> {
+        let mut audit = self
+            .audit
+            .write()
+            .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
+        let seq = audit.len() as u64 + 1;
+        audit.push(AuditEntry {
+            seq,
+            ..entry.clone()
+        });
+        Ok(seq)
+    }
+
+    async fn audit_entries(&self, filter: &AuditFilter) -> StorageResult<Vec<AuditEntry>> {
+        let audit = self
+            .audit
+            .read()
+            .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
+        Ok(filter.apply(audit.iter().cloned()))
+    }
+
+    async fn changes_since(&self, after: u64, limit: usize) -> StorageResult<Vec<ChangeRecord>> {
+        let changes = self
+            .changes
+            .read()
+            .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
+        let first = changes.front().map(|record| record.seq);
+        check_trimmed(after, first)?;
+        // Sequence numbers have no holes, so record `seq` sits at index
+        // `seq - first`
+        let start = after.saturating_sub(first.unwrap_or(1) - 1) as usize;
+        Ok(changes.iter().skip(start).take(limit).cloned().collect())
+    }
+
+    async fn latest_change(&self) -> StorageResult<u64> {
+        let changes = self
+            .changes
+            .read()
+            .map_err(|e| StorageError::Database(format!("Lock error: {}", e)))?;
+        Ok(changes.back().map_or(0, |record| record.seq))
+    }
+
+    fn change_signal(&self) -> Option<watch::Receiver<u64>> {
+        Some(self.signal.subscribe())
+    }
 }
 
 #[cfg(test)]
@@ -333,4 +470,59 @@ mod tests {
         let retrieved = storage.get_entity("TestEntity", &project.id).await.unwrap();
         assert!(retrieved.is_none());
     }
+
+    #[tokio::test]
+    async fn test_memory_change_log() {
+        let storage = MemoryStorage::new();
+        let project = Project::new("test-project");
+        storage.save_project(&project).await.unwrap();
+        storage
+            .save_entity(&Entity::new(project.id.clone(), "Alice", "person"))
+            .await
+            .unwrap();
+        storage.delete_project("test-project").await.unwrap();
+        // Already gone
+        storage.delete_project("test-project").await.unwrap();
+
+        let records = storage.changes_since(0, 100).await.unwrap();
+        assert_eq!(records.len(), 4);
+        let alice = Entity::new(project.id.clone(), "Alice", "person");
+        assert_eq!(
+            records[1].event,
+            ChangeEvent::entity(&alice, ChangeKind::Created)
+        );
+        assert_eq!(
+            records[2].event,
+            ChangeEvent::entity(&alice, ChangeKind::Deleted)
+        );
+        assert_eq!(
+            records[3].event,
+            ChangeEvent::project(&project, ChangeKind::Deleted)
+        );
+        assert_eq!(storage.latest_change().await.unwrap(), 4);
+        assert_eq!(storage.changes_since(1, 1).await.unwrap()[0].seq, 2);
+        assert!(storage.changes_since(5, 100).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_memory_change_log_is_trimmed() {
+        let storage = MemoryStorage::new().with_change_capacity(3);
+        let project = Project::new("test-project");
+        storage.save_project(&project).await.unwrap();
+        for name in ["Alice", "Bob", "Carol", "Dave"] {
+            storage
+                .save_entity(&Entity::new(project.id.clone(), name, "person"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(storage.latest_change().await.unwrap(), 5);
+        let records = storage.changes_since(2, 100).await.unwrap();
+        let seqs: Vec<u64> = records.iter().map(|record| record.seq).collect();
+        assert_eq!(seqs, [3, 4, 5]);
+        assert!(matches!(
+            storage.changes_since(1, 100).await,
+            Err(StorageError::ChangesTrimmed(3))
+        ));
+    }
 }
@@ -0,0 +1,392 @@
+//! Timings of storage operations
+//!
+//! [`InstrumentedStorage`] wraps a backend, timing every call into shared
+//! [`StorageMetrics`] and running it inside a `storage` tracing span, so a
+//! slow operation shows up both in the server's metrics and in the trace of
+//! the request that made it.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parsnip_core::{Entity, Graph, Project, ProjectId, Relation};
+use tokio::sync::watch;
+use tracing::Instrument;
+
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::changelog::ChangeRecord;
+use crate::doctor::{RecordScan, RepairPlan};
+use crate::error::StorageResult;
+use crate::traits::StorageBackend;
+
+/// Upper bounds of the latency buckets, in seconds
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Distribution of latencies over [`LATENCY_BUCKETS`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// Observations per bucket, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Each bucket's upper bound with the observations at or below it
+    pub fn cumulative(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+        LATENCY_BUCKETS
+            .iter()
+            .zip(&self.buckets)
+            .map(|(&bound, &n)| {
+                total += n;
+                (bound, total)
+            })
+            .collect()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Total of all observations, in seconds
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+}
+
+/// Calls of one operation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OperationStats {
+    pub calls: u64,
+    pub errors: u64,
+    pub latency: Histogram,
+}
+
+impl OperationStats {
+    pub fn record(&mut self, duration: Duration, ok: bool) {
+        self.calls += 1;
+        if !ok {
+            self.errors += 1;
+        }
+        self.latency.observe(duration);
+    }
+}
+
+/// Stats of each storage operation, by method name
+#[derive(Debug, Default)]
+pub struct StorageMetrics {
+    operations: Mutex<BTreeMap<&'static str, OperationStats>>,
+}
+
+impl StorageMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, operation: &'static str, duration: Duration, ok: bool) {
+        self.operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(operation)
+            .or_default()
+            .record(duration, ok);
+    }
+
+    /// Stats of every operation called so far
+    pub fn snapshot(&self) -> BTreeMap<&'static str, OperationStats> {
+        self.operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// A backend whose calls are timed and traced
+pub struct InstrumentedStorage<S: StorageBackend + ?Sized> {
+    inner: Arc<S>,
+    metrics: Arc<StorageMetrics>,
+}
+
+impl<S: StorageBackend + ?Sized> InstrumentedStorage<S> {
+    pub fn new(inner: Arc<S>, metrics: Arc<StorageMetrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    pub fn inner(&self) -> &Arc<S> {
+        &self.inner
+    }
+
+    pub fn metrics(&self) -> &Arc<StorageMetrics> {
+        &self.metrics
+    }
+
+    async fn timed<T>(
+        &self,
+        operation: &'static str,
+        call: impl Future<Output = StorageResult<T>>,
+    ) -> StorageResult<T> {
+        let span = tracing::debug_span!("storage", op = operation);
+        let started = Instant::now();
+        let result = call.instrument(span).await;
+        self.metrics
+            .record(operation, started.elapsed(), result.is_ok());
+        result
+    }
+}
+
+#[async_trait]
+impl<S: StorageBackend + ?Sized> StorageBackend for InstrumentedStorage<S> {
+    async fn initialize(&self) -> StorageResult<()> {
+        self.timed("initialize", self.inner.initialize()).await
+    }
+
+    async fn close(&self) -> StorageResult<()> {
+        self.timed("close", self.inner.close()).await
+    }
+
+    async fn health_check(&self) -> StorageResult<bool> {
+        self.timed("health_check", self.inner.health_check()).await
+    }
+
+    async fn save_entity(&self, entity: &Entity) -> StorageResult<()> {
+        self.timed("save_entity", self.inner.save_entity(entity))
+            .await
+    }
+
+    async fn get_entity(
+        &self,
+        name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Option<Entity>> {
+        self.timed("get_entity", self.inner.get_entity(name, project_id))
+            .await
+    }
+
+    async fn get_all_entities(&self, project_id: &ProjectId) -> StorageResult<Vec<Entity>> {
+        self.timed("get_all_entities", self.inner.get_all_entities(project_id))
+            .await
+    }
+
+    async fn get_all_entities_all_projects(&self) -> StorageResult<Vec<Entity>> {
+        self.timed(
+            "get_all_entities_all_projects",
+            self.inner.get_all_entities_all_projects(),
+        )
+        .await
+    }
+
+    async fn delete_entity(&self, name: &str, project_id: &ProjectId) -> StorageResult<()> {
+        self.timed("delete_entity", self.inner.delete_entity(name, project_id))
+            .await
+    }
+
+    async fn save_relation(&self, relation: &Relation) -> StorageResult<()> {
+        self.timed("save_relation", self.inner.save_relation(relation))
+            .await
+    }
+
+    async fn get_relations_for_entity(
+        &self,
+        entity_name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Vec<Relation>> {
+        self.timed(
+            "get_relations_for_entity",
+            self.inner.get_relations_for_entity(entity_name, project_id),
+        )
+        .await
+    }
+
+    async fn get_all_relations(&self, project_id: &ProjectId) -> StorageResult<Vec<Relation>> {
+        self.timed(
+            "get_all_relations",
+            self.inner.get_all_relations(project_id),
+        )
+        .await
+    }
+
+    async fn get_all_relations_all_projects(&self) -> StorageResult<Vec<Relation>> {
+        self.timed(
+            "get_all_relations_all_projects",
+            self.inner.get_all_relations_all_projects(),
+        )
+        .await
+    }
+
+    async fn get_relations_for_entity_global(
+        &self,
+        entity_name: &str,
+    ) -> StorageResult<Vec<Relation>> {
+        self.timed(
+            "get_relations_for_entity_global",
+            self.inner.get_relations_for_entity_global(entity_name),
+        )
+        .await
+    }
+
+    async fn delete_relation(
+        &self,
+        from: &str,
+        to: &str,
+        relation_type: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<()> {
+        self.timed(
+            "delete_relation",
+            self.inner
+                .delete_relation(from, to, relation_type, project_id),
+        )
+        .await
+    }
+
+    async fn delete_relations_for_entity(
+        &self,
+        entity_name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<()> {
+        self.timed(
+            "delete_relations_for_entity",
+            self.inner
+                .delete_relations_for_entity(entity_name, project_id),
+        )
+        .await
+    }
+
+    async fn save_project(&self, project: &Project) -> StorageResult<()> {
+        self.timed("save_project", self.inner.save_project(project))
+            .await
+    }
+
+    async fn get_project(&self, name: &str) -> StorageResult<Option<Project>> {
+        self.timed("get_project", self.inner.get_project(name))
+            .await
+    }
+
+    async fn get_project_by_id(&self, id: &ProjectId) -> StorageResult<Option<Project>> {
+        self.timed("get_project_by_id", self.inner.get_project_by_id(id))
+            .await
+    }
+
+    async fn get_all_projects(&self) -> StorageResult<Vec<Project>> {
+        self.timed("get_all_projects", self.inner.get_all_projects())
+            .await
+    }
+
+    async fn delete_project(&self, name: &str) -> StorageResult<()> {
+        self.timed("delete_project", self.inner.delete_project(name))
+            .await
+    }
+
+    async fn load_graph(&self, project_id: &ProjectId) -> StorageResult<Graph> {
+        self.timed("load_graph", self.inner.load_graph(project_id))
+            .await
+    }
+
+    async fn save_graph(&self, graph: &Graph, project_id: &ProjectId) -> StorageResult<()> {
+        self.timed("save_graph", self.inner.save_graph(graph, project_id))
+            .await
+    }
+
+    async fn save_entities_batch(&self, entities: &[Entity]) -> StorageResult<()> {
+        self.timed(
+            "save_entities_batch",
+            self.inner.save_entities_batch(entities),
+        )
+        .await
+    }
+
+    async fn save_relations_batch(&self, relations: &[Relation]) -> StorageResult<()> {
+        self.timed(
+            "save_relations_batch",
+            self.inner.save_relations_batch(relations),
+        )
+        .await
+    }
+
+    async fn scan_records(&self) -> StorageResult<RecordScan> {
+        self.timed("scan_records", self.inner.scan_records()).await
+    }
+
+    async fn apply_repair(&self, plan: &RepairPlan) -> StorageResult<()> {
+        self.timed("apply_repair", self.inner.apply_repair(plan))
+            .await
+    }
+
+    async fn append_audit(&self, entry: &AuditEntry) -> StorageResult<u64> {
+        self.timed("append_audit", self.inner.append_audit(entry))
+            .await
+    }
+
+    async fn audit_entries(&self, filter: &AuditFilter) -> StorageResult<Vec<AuditEntry>> {
+        self.timed("audit_entries", self.inner.audit_entries(filter))
+            .await
+    }
+
+    async fn changes_since(&self, after: u64, limit: usize) -> StorageResult<Vec<ChangeRecord>> {
+        self.timed("changes_since", self.inner.changes_since(after, limit))
+            .await
+    }
+
+    async fn latest_change(&self) -> StorageResult<u64> {
+        self.timed("latest_change", self.inner.latest_change())
+            .await
+    }
+
+    fn change_signal(&self) -> Option<watch::Receiver<u64>> {
+        self.inner.change_signal()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStorage;
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.observe(Duration::from_micros(200));
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_secs(10));
+
+        let cumulative = histogram.cumulative();
+        assert_eq!(cumulative[0], (0.0005, 1));
+        assert_eq!(cumulative[3], (0.005, 2));
+        // Over the last bound: only in the count
+        assert_eq!(cumulative.last().unwrap().1, 2);
+        assert_eq!(histogram.count(), 3);
+        assert!(histogram.sum() > 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_instrumented_storage_records_calls() {
+        let metrics = Arc::new(StorageMetrics::new());
+        let storage = InstrumentedStorage::new(Arc::new(MemoryStorage::new()), metrics.clone());
+
+        let project = Project::new("work");
+        storage.save_project(&project).await.unwrap();
+        assert!(storage.get_project("work").await.unwrap().is_some());
+        assert!(storage.get_project("home").await.unwrap().is_none());
+
+        let stats = metrics.snapshot();
+        assert_eq!(stats["save_project"].calls, 1);
+        assert_eq!(stats["get_project"].calls, 2);
+        assert_eq!(stats["get_project"].errors, 0);
+        assert_eq!(stats["get_project"].latency.count(), 2);
+        assert!(!stats.contains_key("delete_project"));
+    }
+}
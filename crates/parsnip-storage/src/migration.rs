@@ -5,7 +5,7 @@
 use crate::StorageResult;
 
 /// Current schema version
-pub const CURRENT_VERSION: u32 = 1;
+pub const CURRENT_VERSION: u32 = 2;
 
 /// Schema migration information
 #[derive(Debug, Clone)]
@@ -16,10 +16,16 @@ pub struct SchemaVersion {
 
 /// All schema versions with their migrations
 pub fn get_migrations() -> Vec<SchemaVersion> {
-    vec![SchemaVersion {
-        version: 1,
-        description: "Initial schema with entities, relations, and projects",
-    }]
+    vec![
+        SchemaVersion {
+            version: 1,
+            description: "Initial schema with entities, relations, and projects",
+        },
+        SchemaVersion {
+            version: 2,
+            description: "Records in the configured format; versioned binary by default",
+        },
+    ]
 }
 
 /// Migration trait for storage backends
@@ -81,6 +87,10 @@ mod tests {
 
     #[test]
     fn test_current_version() {
-        assert_eq!(CURRENT_VERSION, 1);
+        assert_eq!(CURRENT_VERSION, 2);
+        assert_eq!(
+            get_migrations().last().map(|m| m.version),
+            Some(CURRENT_VERSION)
+        );
     }
 }
@@ -0,0 +1,695 @@
+//! Graph operations shared by the CLI commands and the MCP tools
+//!
+//! Each operation works on any [`StorageBackend`] and returns structured
+//! results; rendering them as terminal output or tool responses is left to
+//! the caller. Operations that read more than one project take a [`Scope`]
+//! limiting which projects they may see.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::error::{StorageError, StorageResult};
+use crate::export::{ExportData, ProjectExport};
+use crate::traits::StorageBackend;
+use parsnip_core::{
+    validate_observation, validate_project_name, validate_tag, Direction, Entity, EntityType,
+    Project, ProjectId, Relation, TraversalEngine, TraversalQuery, TraversalResult,
+    ValidationError,
+};
+
+/// Errors of shared graph operations
+#[derive(Debug, Error)]
+pub enum OpsError {
+    #[error("Entity '{name}' not found in project '{project}'")]
+    EntityNotFound { name: String, project: String },
+
+    #[error("Project '{0}' not found")]
+    ProjectNotFound(String),
+
+    #[error("Project '{0}' already exists")]
+    ProjectExists(String),
+
+    #[error(transparent)]
+    Invalid(#[from] ValidationError),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+pub type OpsResult<T> = Result<T, OpsError>;
+
+/// Projects an operation may read
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scope {
+    /// `None` means every project
+    projects: Option<BTreeSet<String>>,
+}
+
+impl Scope {
+    /// Every project
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Only the named projects
+    pub fn projects<I, T>(projects: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            projects: Some(projects.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    pub fn allows(&self, project: &str) -> bool {
+        match &self.projects {
+            Some(projects) => projects.contains(project),
+            None => true,
+        }
+    }
+
+    /// Look up a project; hidden projects are not found
+    pub async fn project(
+        &self,
+        storage: &dyn StorageBackend,
+        name: &str,
+    ) -> StorageResult<Option<Project>> {
+        if !self.allows(name) {
+            return Ok(None);
+        }
+        storage.get_project(name).await
+    }
+
+    /// Every visible project
+    pub async fn visible_projects(
+        &self,
+        storage: &dyn StorageBackend,
+    ) -> StorageResult<Vec<Project>> {
+        let mut projects = storage.get_all_projects().await?;
+        projects.retain(|p| self.allows(&p.name));
+        Ok(projects)
+    }
+
+    /// Entities of every visible project
+    pub async fn entities(&self, storage: &dyn StorageBackend) -> StorageResult<Vec<Entity>> {
+        let mut entities = storage.get_all_entities_all_projects().await?;
+        if self.projects.is_some() {
+            let visible = self.project_ids(storage).await?;
+            entities.retain(|e| visible.contains(&e.project_id));
+        }
+        Ok(entities)
+    }
+
+    /// Relations whose endpoints are both in visible projects
+    pub async fn relations(&self, storage: &dyn StorageBackend) -> StorageResult<Vec<Relation>> {
+        let mut relations = storage.get_all_relations_all_projects().await?;
+        if self.projects.is_some() {
+            let visible = self.project_ids(storage).await?;
+            relations.retain(|r| {
+                visible.contains(r.effective_from_project_id())
+                    && visible.contains(r.effective_to_project_id())
+            });
+        }
+        Ok(relations)
+    }
+
+    async fn project_ids(&self, storage: &dyn StorageBackend) -> StorageResult<HashSet<ProjectId>> {
+        Ok(self
+            .visible_projects(storage)
+            .await?
+            .into_iter()
+            .map(|p| p.id)
+            .collect())
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Entities
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Changes to make to an existing entity
+#[derive(Debug, Clone, Default)]
+pub struct EntityUpdate {
+    pub entity_type: Option<String>,
+    pub add_observations: Vec<String>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+}
+
+impl EntityUpdate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_type(mut self, entity_type: impl Into<String>) -> Self {
+        self.entity_type = Some(entity_type.into());
+        self
+    }
+
+    pub fn with_observations(mut self, observations: Vec<String>) -> Self {
+        self.add_observations = observations;
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.add_tags = tags;
+        self
+    }
+
+    pub fn without_tags(mut self, tags: Vec<String>) -> Self {
+        self.remove_tags = tags;
+        self
+    }
+}
+
+/// One change made by [`update_entity`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityChange {
+    AddedObservation(String),
+    AddedTag(String),
+    RemovedTag(String),
+    SetType(String),
+}
+
+impl fmt::Display for EntityChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AddedObservation(o) => write!(f, "added observation: {}", o),
+            Self::AddedTag(t) => write!(f, "added tag: {}", t),
+            Self::RemovedTag(t) => write!(f, "removed tag: {}", t),
+            Self::SetType(t) => write!(f, "set type: {}", t),
+        }
+    }
+}
+
+/// Result of [`update_entity`]
+#[derive(Debug, Clone)]
+pub struct EntityUpdateOutcome {
+    /// The entity after the update
+    pub entity: Entity,
+    pub changes: Vec<EntityChange>,
+    /// Tags asked to be removed that the entity did not have
+    pub missing_tags: Vec<String>,
+}
+
+/// Apply an [`EntityUpdate`], saving the entity if anything changed
+pub async fn update_entity(
+    storage: &dyn StorageBackend,
+    project: &Project,
+    name: &str,
+    update: &EntityUpdate,
+) -> OpsResult<EntityUpdateOutcome> {
+    for observation in &update.add_observations {
+        validate_observation(observation)?;
+    }
+    for tag in &update.add_tags {
+        validate_tag(tag)?;
+    }
+
+    let mut entity = storage
+        .get_entity(name, &project.id)
+        .await?
+        .ok_or_else(|| OpsError::EntityNotFound {
+            name: name.to_string(),
+            project: project.name.clone(),
+        })?;
+
+    let mut changes = Vec::new();
+    let mut missing_tags = Vec::new();
+
+    for observation in &update.add_observations {
+        entity.add_observation(observation);
+        changes.push(EntityChange::AddedObservation(observation.clone()));
+    }
+    for tag in &update.add_tags {
+        entity.add_tag(tag);
+        changes.push(EntityChange::AddedTag(tag.clone()));
+    }
+    for tag in &update.remove_tags {
+        if entity.remove_tag(tag) {
+            changes.push(EntityChange::RemovedTag(tag.clone()));
+        } else {
+            missing_tags.push(tag.clone());
+        }
+    }
+    if let Some(entity_type) = &update.entity_type {
+        entity.entity_type = EntityType::new(entity_type);
+        changes.push(EntityChange::SetType(entity_type.clone()));
+    }
+
+    if !changes.is_empty() {
+        storage.save_entity(&entity).await?;
+    }
+
+    Ok(EntityUpdateOutcome {
+        entity,
+        changes,
+        missing_tags,
+    })
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Relations
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Which relations [`list_relations`] returns; empty matches everything
+#[derive(Debug, Clone, Default)]
+pub struct RelationFilter {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Matched case-insensitively
+    pub relation_type: Option<String>,
+}
+
+impl RelationFilter {
+    pub fn matches(&self, relation: &Relation) -> bool {
+        if self.from.as_ref().is_some_and(|f| &relation.from_name != f) {
+            return false;
+        }
+        if self.to.as_ref().is_some_and(|t| &relation.to_name != t) {
+            return false;
+        }
+        if let Some(relation_type) = &self.relation_type {
+            if relation.relation_type.to_lowercase() != relation_type.to_lowercase() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Relations of a project matching a filter
+pub async fn list_relations(
+    storage: &dyn StorageBackend,
+    project: &Project,
+    filter: &RelationFilter,
+) -> StorageResult<Vec<Relation>> {
+    let mut relations = storage.get_all_relations(&project.id).await?;
+    relations.retain(|r| filter.matches(r));
+    Ok(relations)
+}
+
+/// A path to find between two entities
+#[derive(Debug, Clone)]
+pub struct PathRequest {
+    pub from: String,
+    pub to: String,
+    /// Project of the target entity; the source's project when absent
+    pub to_project: Option<String>,
+    /// Shortest total weight (Dijkstra) instead of fewest hops
+    pub weighted: bool,
+    pub relation_types: Vec<String>,
+    pub entity_types: Vec<String>,
+    pub max_depth: u32,
+    /// Follow relations into other projects
+    pub cross_project: bool,
+}
+
+impl PathRequest {
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            to_project: None,
+            weighted: false,
+            relation_types: Vec::new(),
+            entity_types: Vec::new(),
+            max_depth: 10,
+            cross_project: false,
+        }
+    }
+}
+
+/// Find paths between two entities
+///
+/// Crossing into another project is implied by a target in another project
+/// and only reaches projects in the scope.
+pub async fn find_path(
+    storage: &dyn StorageBackend,
+    scope: &Scope,
+    project: &Project,
+    request: &PathRequest,
+) -> OpsResult<TraversalResult> {
+    let target_project = match request.to_project.as_deref() {
+        Some(name) if name != project.name => scope
+            .project(storage, name)
+            .await?
+            .ok_or_else(|| OpsError::ProjectNotFound(name.to_string()))?,
+        _ => project.clone(),
+    };
+
+    for (name, project) in [(&request.from, project), (&request.to, &target_project)] {
+        if storage.get_entity(name, &project.id).await?.is_none() {
+            return Err(OpsError::EntityNotFound {
+                name: name.clone(),
+                project: project.name.clone(),
+            });
+        }
+    }
+
+    let mut query = TraversalQuery::new(&request.from)
+        .in_project(project.id.clone())
+        .find_path_to(&request.to)
+        .with_target_project(target_project.id.clone())
+        .with_depth(request.max_depth);
+    if request.cross_project || target_project.id != project.id {
+        query = query.cross_project();
+    }
+    if request.weighted {
+        query = query.weighted();
+    }
+    if !request.relation_types.is_empty() {
+        query = query.filter_relation_types(request.relation_types.clone());
+    }
+    if !request.entity_types.is_empty() {
+        query = query.filter_entity_types(request.entity_types.clone());
+    }
+
+    let (entities, relations) = if query.cross_project {
+        (
+            scope.entities(storage).await?,
+            scope.relations(storage).await?,
+        )
+    } else {
+        (
+            storage.get_all_entities(&project.id).await?,
+            storage.get_all_relations(&project.id).await?,
+        )
+    };
+
+    tracing::info!(
+        "Finding path from {} to {} (weighted: {}, max_depth: {})",
+        request.from,
+        request.to,
+        request.weighted,
+        request.max_depth
+    );
+    Ok(TraversalEngine::execute(&query, &entities, &relations))
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Projects
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Size of a project, with counts per entity and relation type
+#[derive(Debug, Clone)]
+pub struct ProjectStats {
+    pub project: Project,
+    pub entity_count: usize,
+    pub observation_count: usize,
+    pub tag_count: usize,
+    pub relation_count: usize,
+    pub entity_types: BTreeMap<String, usize>,
+    pub relation_types: BTreeMap<String, usize>,
+}
+
+/// Statistics of a project
+pub async fn project_stats(storage: &dyn StorageBackend, name: &str) -> OpsResult<ProjectStats> {
+    let project = storage
+        .get_project(name)
+        .await?
+        .ok_or_else(|| OpsError::ProjectNotFound(name.to_string()))?;
+
+    let entities = storage.get_all_entities(&project.id).await?;
+    let relations = storage.get_all_relations(&project.id).await?;
+
+    let mut entity_types = BTreeMap::new();
+    for entity in &entities {
+        *entity_types
+            .entry(entity.entity_type.0.clone())
+            .or_insert(0) += 1;
+    }
+    let mut relation_types = BTreeMap::new();
+    for relation in &relations {
+        *relation_types
+            .entry(relation.relation_type.clone())
+            .or_insert(0) += 1;
+    }
+
+    Ok(ProjectStats {
+        project,
+        entity_count: entities.len(),
+        observation_count: entities.iter().map(|e| e.observations.len()).sum(),
+        tag_count: entities.iter().map(|e| e.tags.len()).sum(),
+        relation_count: relations.len(),
+        entity_types,
+        relation_types,
+    })
+}
+
+/// Create an empty project
+pub async fn create_project(
+    storage: &dyn StorageBackend,
+    name: &str,
+    description: Option<&str>,
+) -> OpsResult<Project> {
+    validate_project_name(name)?;
+    if storage.get_project(name).await?.is_some() {
+        return Err(OpsError::ProjectExists(name.to_string()));
+    }
+
+    let mut project = Project::new(name);
+    if let Some(description) = description {
+        project = project.with_description(description);
+    }
+    storage.save_project(&project).await?;
+    tracing::info!("Created project: {}", name);
+    Ok(project)
+}
+
+/// Delete a project with its entities and relations, returning what it held
+pub async fn delete_project(storage: &dyn StorageBackend, name: &str) -> OpsResult<ProjectStats> {
+    let stats = project_stats(storage, name).await?;
+    storage.delete_project(name).await?;
+    tracing::info!(
+        "Deleted project: {} ({} entities, {} relations)",
+        name,
+        stats.entity_count,
+        stats.relation_count
+    );
+    Ok(stats)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Subgraph export
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// The neighborhood of some entities to export
+#[derive(Debug, Clone)]
+pub struct SubgraphRequest {
+    /// Entities the subgraph grows from
+    pub roots: Vec<String>,
+    /// Hops from the roots
+    pub depth: u32,
+    pub direction: Direction,
+    /// Only follow these relation types, when not empty
+    pub relation_types: Vec<String>,
+}
+
+impl SubgraphRequest {
+    pub fn new(roots: Vec<String>, depth: u32) -> Self {
+        Self {
+            roots,
+            depth,
+            direction: Direction::Both,
+            relation_types: Vec::new(),
+        }
+    }
+}
+
+/// Export the entities within reach of the roots and the relations among
+/// them, in the format of [`crate::export`]
+pub async fn export_subgraph(
+    storage: &dyn StorageBackend,
+    project: &Project,
+    request: &SubgraphRequest,
+) -> OpsResult<ExportData> {
+    let entities = storage.get_all_entities(&project.id).await?;
+    let relations: Vec<Relation> = storage
+        .get_all_relations(&project.id)
+        .await?
+        .into_iter()
+        .filter(|r| {
+            r.effective_from_project_id() == &project.id
+                && r.effective_to_project_id() == &project.id
+        })
+        .collect();
+
+    let mut names = HashSet::new();
+    for root in &request.roots {
+        if !entities.iter().any(|e| &e.name == root) {
+            return Err(OpsError::EntityNotFound {
+                name: root.clone(),
+                project: project.name.clone(),
+            });
+        }
+        let mut query = TraversalQuery::new(root)
+            .in_project(project.id.clone())
+            .with_depth(request.depth)
+            .with_direction(request.direction);
+        if !request.relation_types.is_empty() {
+            query = query.filter_relation_types(request.relation_types.clone());
+        }
+        let result = TraversalEngine::execute(&query, &entities, &relations);
+        names.extend(result.visited_entities.into_iter().map(|n| n.name));
+    }
+
+    let mut entities: Vec<Entity> = entities
+        .into_iter()
+        .filter(|e| names.contains(&e.name))
+        .collect();
+    entities.sort_by(|a, b| a.name.cmp(&b.name));
+    let relations: Vec<Relation> = relations
+        .into_iter()
+        .filter(|r| names.contains(&r.from_name) && names.contains(&r.to_name))
+        .filter(|r| {
+            request.relation_types.is_empty() || request.relation_types.contains(&r.relation_type)
+        })
+        .collect();
+
+    Ok(ExportData::new(vec![ProjectExport::new(
+        project, &entities, &relations,
+    )]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStorage;
+
+    async fn seeded() -> (MemoryStorage, Project) {
+        let storage = MemoryStorage::new();
+        let project = create_project(&storage, "work", Some("Work notes"))
+            .await
+            .unwrap();
+        for (name, kind) in [
+            ("Alice", "person"),
+            ("Bob", "person"),
+            ("Carol", "person"),
+            ("Acme", "company"),
+        ] {
+            storage
+                .save_entity(&Entity::new(project.id.clone(), name, kind))
+                .await
+                .unwrap();
+        }
+        for (from, to, kind, weight) in [
+            ("Alice", "Bob", "knows", 5.0),
+            ("Bob", "Carol", "knows", 1.0),
+            ("Alice", "Carol", "knows", 10.0),
+            ("Carol", "Acme", "works_at", 1.0),
+        ] {
+            storage
+                .save_relation(
+                    &Relation::from_names(project.id.clone(), from, to, kind).with_weight(weight),
+                )
+                .await
+                .unwrap();
+        }
+        (storage, project)
+    }
+
+    #[tokio::test]
+    async fn test_update_entity() {
+        let (storage, project) = seeded().await;
+        let update = EntityUpdate::new()
+            .with_type("engineer")
+            .with_observations(vec!["Writes Rust".to_string()])
+            .with_tags(vec!["team".to_string()])
+            .without_tags(vec!["absent".to_string()]);
+
+        let outcome = update_entity(&storage, &project, "Alice", &update)
+            .await
+            .unwrap();
+        assert_eq!(outcome.changes.len(), 3);
+        assert_eq!(outcome.changes[2].to_string(), "set type: engineer");
+        assert_eq!(outcome.missing_tags, vec!["absent"]);
+
+        let stored = storage
+            .get_entity("Alice", &project.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.entity_type.0, "engineer");
+        assert!(stored.has_tag("team"));
+
+        assert!(matches!(
+            update_entity(&storage, &project, "Nobody", &update).await,
+            Err(OpsError::EntityNotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_find_path_and_list_relations() {
+        let (storage, project) = seeded().await;
+
+        let mut request = PathRequest::new("Alice", "Carol");
+        let result = find_path(&storage, &Scope::all(), &project, &request)
+            .await
+            .unwrap();
+        assert_eq!(result.paths[0].length, 1);
+
+        request.weighted = true;
+        let result = find_path(&storage, &Scope::all(), &project, &request)
+            .await
+            .unwrap();
+        assert_eq!(result.paths[0].node_names(), vec!["Alice", "Bob", "Carol"]);
+
+        request.to_project = Some("hidden".to_string());
+        assert!(matches!(
+            find_path(&storage, &Scope::projects(["work"]), &project, &request).await,
+            Err(OpsError::ProjectNotFound(_))
+        ));
+
+        let filter = RelationFilter {
+            from: Some("Alice".to_string()),
+            relation_type: Some("KNOWS".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            list_relations(&storage, &project, &filter)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_projects_and_subgraph() {
+        let (storage, project) = seeded().await;
+
+        assert!(matches!(
+            create_project(&storage, "work", None).await,
+            Err(OpsError::ProjectExists(_))
+        ));
+
+        let stats = project_stats(&storage, "work").await.unwrap();
+        assert_eq!(stats.entity_count, 4);
+        assert_eq!(stats.relation_count, 4);
+        assert_eq!(stats.entity_types["person"], 3);
+        assert_eq!(stats.relation_types["works_at"], 1);
+
+        let export = export_subgraph(
+            &storage,
+            &project,
+            &SubgraphRequest::new(vec!["Acme".to_string()], 1),
+        )
+        .await
+        .unwrap();
+        let names: Vec<&str> = export.projects[0]
+            .entities
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Acme", "Carol"]);
+        assert_eq!(export.projects[0].relations.len(), 1);
+
+        let deleted = delete_project(&storage, "work").await.unwrap();
+        assert_eq!(deleted.entity_count, 4);
+        assert!(storage.get_project("work").await.unwrap().is_none());
+    }
+}
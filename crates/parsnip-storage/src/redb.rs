@@ -1,26 +1,62 @@
 //! ReDB storage backend
-
+//!
+//! Every call runs one ReDB transaction on the blocking pool. Read
+//! transactions work on a snapshot and run in parallel with each other and
+//! with the writer; ReDB itself lets one write transaction in at a time.
+//! Write transactions append what they changed to the change log before
+//! committing, so the log and the records never disagree.
+
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::blocking;
+use crate::changelog::{check_trimmed, first_kept, ChangeEvent, ChangeRecord, CHANGE_LOG_CAPACITY};
+use crate::changes::ChangeKind;
+use crate::doctor::{RecordKind, RecordScan, RepairPlan, UndecodableRecord};
+use crate::encoding::{RecordFormat, StoredRecord};
 use crate::error::{StorageError, StorageResult};
+use crate::migration::{Migratable, CURRENT_VERSION};
 use crate::traits::StorageBackend;
 use async_trait::async_trait;
 use parsnip_core::{Entity, Graph, Project, ProjectId, Relation};
-use redb::{Database, ReadableTable, TableDefinition};
+use redb::{
+    Database, ReadTransaction, ReadableTable, ReadableTableMetadata, TableDefinition,
+    WriteTransaction,
+};
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::Arc;
+use tokio::sync::watch;
 
 // Table definitions
 const ENTITIES: TableDefinition<&str, &[u8]> = TableDefinition::new("entities");
 const RELATIONS: TableDefinition<&str, &[u8]> = TableDefinition::new("relations");
 const PROJECTS: TableDefinition<&str, &[u8]> = TableDefinition::new("projects");
+/// Append-only, keyed by sequence number
+const AUDIT_LOG: TableDefinition<u64, &[u8]> = TableDefinition::new("audit_log");
+/// Append-only, keyed by sequence number
+const CHANGES: TableDefinition<u64, &[u8]> = TableDefinition::new("changes");
+/// Settings of the database itself, such as its schema version
+const META: TableDefinition<&str, u64> = TableDefinition::new("meta");
+
+const SCHEMA_VERSION: &str = "schema_version";
 
 /// ReDB storage backend
 pub struct RedbStorage {
-    db: Mutex<Database>,
+    db: Arc<Database>,
+    /// Format new and migrated records are written in
+    format: RecordFormat,
+    /// Sequence number of the last change committed
+    signal: watch::Sender<u64>,
+    change_capacity: u64,
 }
 
 impl RedbStorage {
     /// Open or create a ReDB database at the given path
     pub fn open(path: impl AsRef<Path>) -> StorageResult<Self> {
+        Self::open_with_format(path, RecordFormat::default())
+    }
+
+    /// Open or create a ReDB database that writes records in `format`,
+    /// migrating an older database first
+    pub fn open_with_format(path: impl AsRef<Path>, format: RecordFormat) -> StorageResult<Self> {
         let db = Database::create(path).map_err(|e| StorageError::Database(e.to_string()))?;
 
         // Initialize tables
@@ -32,13 +68,153 @@ impl RedbStorage {
                 let _ = write_txn.open_table(ENTITIES);
                 let _ = write_txn.open_table(RELATIONS);
                 let _ = write_txn.open_table(PROJECTS);
+                let _ = write_txn.open_table(AUDIT_LOG);
+                let _ = write_txn.open_table(CHANGES);
+                let mut meta = write_txn.open_table(META)?;
+                // A new database starts at the current version; one without
+                // a version predates versioning
+                if meta.get(SCHEMA_VERSION)?.is_none() {
+                    let version = if Self::is_empty(&write_txn)? {
+                        CURRENT_VERSION
+                    } else {
+                        1
+                    };
+                    meta.insert(SCHEMA_VERSION, u64::from(version))?;
+                }
             }
             write_txn
                 .commit()
                 .map_err(|e| StorageError::Database(e.to_string()))?;
         }
 
-        Ok(Self { db: Mutex::new(db) })
+        let last_change = {
+            let read_txn = db
+                .begin_read()
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            let table = read_txn.open_table(CHANGES)?;
+            let last = table.last()?.map(|(seq, _)| seq.value());
+            last.unwrap_or(0)
+        };
+
+        let storage = Self {
+            db: Arc::new(db),
+            format,
+            signal: watch::channel(last_change).0,
+            change_capacity: CHANGE_LOG_CAPACITY,
+        };
+        storage.migrate_to_latest()?;
+        Ok(storage)
+    }
+
+    /// Keep this many change log records rather than [`CHANGE_LOG_CAPACITY`]
+    pub fn with_change_capacity(mut self, capacity: u64) -> Self {
+        self.change_capacity = capacity;
+        self
+    }
+
+    /// Whether the database holds no records
+    fn is_empty(write_txn: &WriteTransaction) -> StorageResult<bool> {
+        for definition in [PROJECTS, ENTITIES, RELATIONS] {
+            if !write_txn.open_table(definition)?.is_empty()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Write every record of a table again in `format`, leaving the ones
+    /// that do not decode for the doctor
+    fn reencode<T: StoredRecord>(
+        write_txn: &WriteTransaction,
+        definition: TableDefinition<&str, &[u8]>,
+        format: RecordFormat,
+    ) -> StorageResult<usize> {
+        let mut table = write_txn.open_table(definition)?;
+        let mut records = Vec::new();
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            if let Ok(record) = T::decode(value.value()) {
+                records.push((key.value().to_string(), record.encode(format)?));
+            }
+        }
+        for (key, value) in &records {
+            table.insert(key.as_str(), value.as_slice())?;
+        }
+        Ok(records.len())
+    }
+
+    /// Run `f` in a read transaction on the blocking pool
+    async fn read<T, F>(&self, f: F) -> StorageResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ReadTransaction) -> StorageResult<T> + Send + 'static,
+    {
+        let db = self.db.clone();
+        blocking::run(move || {
+            let read_txn = db
+                .begin_read()
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            f(&read_txn)
+        })
+        .await
+    }
+
+    /// Run `f` in a write transaction on the blocking pool, committing when
+    /// it succeeds and aborting when it fails
+    ///
+    /// The events `f` pushes are appended to the change log in the same
+    /// transaction.
+    async fn write<T, F>(&self, f: F) -> StorageResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&WriteTransaction, &mut Vec<ChangeEvent>) -> StorageResult<T> + Send + 'static,
+    {
+        let db = self.db.clone();
+        let capacity = self.change_capacity;
+        let (result, last_change) = blocking::run(move || {
+            let write_txn = db
+                .begin_write()
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            let mut events = Vec::new();
+            let result = f(&write_txn, &mut events)?;
+            let last_change = Self::log_changes(&write_txn, events, capacity)?;
+            write_txn.commit()?;
+            Ok((result, last_change))
+        })
+        .await?;
+
+        if let Some(seq) = last_change {
+            self.signal.send_replace(seq);
+        }
+        Ok(result)
+    }
+
+    /// Append events to the change log, trimming it to `capacity` records,
+    /// and return the last sequence number
+    fn log_changes(
+        write_txn: &WriteTransaction,
+        events: Vec<ChangeEvent>,
+        capacity: u64,
+    ) -> StorageResult<Option<u64>> {
+        if events.is_empty() {
+            return Ok(None);
+        }
+
+        let mut table = write_txn.open_table(CHANGES)?;
+        let mut seq = match table.last()? {
+            Some((last, _)) => last.value(),
+            None => 0,
+        };
+        for event in events {
+            seq += 1;
+            let value = serde_json::to_vec(&ChangeRecord {
+                seq,
+                ..ChangeRecord::new(event)
+            })?;
+            table.insert(seq, value.as_slice())?;
+        }
+        table.retain_in(..first_kept(seq, capacity), |_, _| false)?;
+        Ok(Some(seq))
     }
 
     fn make_entity_key(project_id: &ProjectId, name: &str) -> String {
@@ -48,6 +224,120 @@ impl RedbStorage {
     fn make_relation_key(project_id: &ProjectId, from: &str, to: &str, rel_type: &str) -> String {
         format!("{}:{}:{}:{}", project_id, from, to, rel_type)
     }
+
+    fn relation_key(relation: &Relation) -> String {
+        Self::make_relation_key(
+            &relation.project_id,
+            &relation.from_name,
+            &relation.to_name,
+            &relation.relation_type,
+        )
+    }
+
+    fn table_for(kind: RecordKind) -> TableDefinition<'static, &'static str, &'static [u8]> {
+        match kind {
+            RecordKind::Project => PROJECTS,
+            RecordKind::Entity => ENTITIES,
+            RecordKind::Relation => RELATIONS,
+        }
+    }
+
+    /// Decode the values whose keys start with `prefix`, reading only that
+    /// range of the table
+    fn records<T: StoredRecord>(
+        table: &impl ReadableTable<&'static str, &'static [u8]>,
+        prefix: &str,
+    ) -> StorageResult<Vec<T>> {
+        let mut records = Vec::new();
+        for entry in table.range(prefix..)? {
+            let (key, value) = entry?;
+            if !key.value().starts_with(prefix) {
+                break;
+            }
+            records.push(T::decode(value.value())?);
+        }
+        Ok(records)
+    }
+
+    /// Keys starting with `prefix`
+    fn keys(
+        table: &impl ReadableTable<&'static str, &'static [u8]>,
+        prefix: &str,
+    ) -> StorageResult<Vec<String>> {
+        let mut keys = Vec::new();
+        for entry in table.range(prefix..)? {
+            let (key, _) = entry?;
+            if !key.value().starts_with(prefix) {
+                break;
+            }
+            keys.push(key.value().to_string());
+        }
+        Ok(keys)
+    }
+
+    /// Decode every value in a table, setting aside the ones that fail
+    fn scan_table<T: StoredRecord>(
+        read_txn: &ReadTransaction,
+        kind: RecordKind,
+        undecodable: &mut Vec<UndecodableRecord>,
+    ) -> StorageResult<Vec<T>> {
+        let table = read_txn.open_table(Self::table_for(kind))?;
+
+        let mut records = Vec::new();
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            match T::decode(value.value()) {
+                Ok(record) => records.push(record),
+                Err(e) => {
+                    undecodable.push(UndecodableRecord::new(kind, key.value().to_string(), &e))
+                }
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+impl Migratable for RedbStorage {
+    fn get_schema_version(&self) -> StorageResult<u32> {
+        let read_txn = self
+            .db
+            .begin_read()
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        let version = read_txn.open_table(META)?.get(SCHEMA_VERSION)?;
+        let version = version.map_or(1, |version| version.value());
+        u32::try_from(version).map_err(|e| StorageError::Migration(e.to_string()))
+    }
+
+    fn set_schema_version(&self, version: u32) -> StorageResult<()> {
+        let write_txn = self
+            .db
+            .begin_write()
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        write_txn
+            .open_table(META)?
+            .insert(SCHEMA_VERSION, u64::from(version))?;
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn run_migration(&self, version: u32) -> StorageResult<()> {
+        match version {
+            2 => {
+                let write_txn = self
+                    .db
+                    .begin_write()
+                    .map_err(|e| StorageError::Database(e.to_string()))?;
+                let count = Self::reencode::<Project>(&write_txn, PROJECTS, self.format)?
+                    + Self::reencode::<Entity>(&write_txn, ENTITIES, self.format)?
+                    + Self::reencode::<Relation>(&write_txn, RELATIONS, self.format)?;
+                write_txn.commit()?;
+                tracing::info!("Rewrote {} records as {}", count, self.format);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 #[async_trait]
@@ -66,22 +356,16 @@ impl StorageBackend for RedbStorage {
 
     async fn save_entity(&self, entity: &Entity) -> StorageResult<()> {
         let key = Self::make_entity_key(&entity.project_id, &entity.name);
-        let value = serde_json::to_vec(entity)?;
+        let value = entity.encode(self.format)?;
+        let event = ChangeEvent::entity(entity, ChangeKind::Created);
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        {
+        self.write(move |write_txn, events| {
             let mut table = write_txn.open_table(ENTITIES)?;
-            table.insert(key.as_str(), value.as_slice())?;
-        }
-        write_txn.commit()?;
-
-        Ok(())
+            let replaced = table.insert(key.as_str(), value.as_slice())?.is_some();
+            events.push(event.with_kind(ChangeKind::of_upsert(replaced)));
+            Ok(())
+        })
+        .await
     }
 
     async fn get_entity(
@@ -91,109 +375,59 @@ impl StorageBackend for RedbStorage {
     ) -> StorageResult<Option<Entity>> {
         let key = Self::make_entity_key(project_id, name);
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(ENTITIES)?;
-
-        if let Some(value) = table.get(key.as_str())? {
-            let entity: Entity = serde_json::from_slice(value.value())?;
-            Ok(Some(entity))
-        } else {
-            Ok(None)
-        }
+        self.read(move |read_txn| {
+            let table = read_txn.open_table(ENTITIES)?;
+            let entity = match table.get(key.as_str())? {
+                Some(value) => Some(Entity::decode(value.value())?),
+                None => None,
+            };
+            Ok(entity)
+        })
+        .await
     }
 
     async fn get_all_entities(&self, project_id: &ProjectId) -> StorageResult<Vec<Entity>> {
         let prefix = format!("{}:", project_id);
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(ENTITIES)?;
-
-        let mut entities = Vec::new();
-        for entry in table.iter()? {
-            let (key, value) = entry?;
-            if key.value().starts_with(&prefix) {
-                let entity: Entity = serde_json::from_slice(value.value())?;
-                entities.push(entity);
-            }
-        }
-
-        Ok(entities)
+        self.read(move |read_txn| Self::records(&read_txn.open_table(ENTITIES)?, &prefix))
+            .await
     }
 
     async fn get_all_entities_all_projects(&self) -> StorageResult<Vec<Entity>> {
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(ENTITIES)?;
-
-        let mut entities = Vec::new();
-        for entry in table.iter()? {
-            let (_, value) = entry?;
-            let entity: Entity = serde_json::from_slice(value.value())?;
-            entities.push(entity);
-        }
-
-        Ok(entities)
+        self.read(|read_txn| Self::records(&read_txn.open_table(ENTITIES)?, ""))
+            .await
     }
 
     async fn delete_entity(&self, name: &str, project_id: &ProjectId) -> StorageResult<()> {
         let key = Self::make_entity_key(project_id, name);
+        let deleted = ChangeEvent::Entity {
+            project_id: project_id.clone(),
+            name: name.to_string(),
+            kind: ChangeKind::Deleted,
+        };
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        {
+        self.write(move |write_txn, events| {
             let mut table = write_txn.open_table(ENTITIES)?;
-            table.remove(key.as_str())?;
-        }
-        write_txn.commit()?;
-
-        Ok(())
+            if table.remove(key.as_str())?.is_some() {
+                events.push(deleted);
+            }
+            Ok(())
+        })
+        .await
     }
 
     async fn save_relation(&self, relation: &Relation) -> StorageResult<()> {
-        let key = Self::make_relation_key(
-            &relation.project_id,
-            &relation.from_name,
-            &relation.to_name,
-            &relation.relation_type,
-        );
-        let value = serde_json::to_vec(relation)?;
+        let key = Self::relation_key(relation);
+        let value = relation.encode(self.format)?;
+        let event = ChangeEvent::relation(relation, ChangeKind::Created);
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        {
+        self.write(move |write_txn, events| {
             let mut table = write_txn.open_table(RELATIONS)?;
-            table.insert(key.as_str(), value.as_slice())?;
-        }
-        write_txn.commit()?;
-
-        Ok(())
+            let replaced = table.insert(key.as_str(), value.as_slice())?.is_some();
+            events.push(event.with_kind(ChangeKind::of_upsert(replaced)));
+            Ok(())
+        })
+        .await
     }
 
     async fn get_relations_for_entity(
@@ -201,98 +435,46 @@ impl StorageBackend for RedbStorage {
         entity_name: &str,
         project_id: &ProjectId,
     ) -> StorageResult<Vec<Relation>> {
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(RELATIONS)?;
-
         let prefix = format!("{}:", project_id);
-        let mut relations = Vec::new();
-
-        for entry in table.iter()? {
-            let (key, value) = entry?;
-            if key.value().starts_with(&prefix) {
-                let relation: Relation = serde_json::from_slice(value.value())?;
-                if relation.from_name == entity_name || relation.to_name == entity_name {
-                    relations.push(relation);
-                }
-            }
-        }
-
-        Ok(relations)
+        let entity_name = entity_name.to_string();
+
+        self.read(move |read_txn| {
+            let relations: Vec<Relation> =
+                Self::records(&read_txn.open_table(RELATIONS)?, &prefix)?;
+            Ok(relations
+                .into_iter()
+                .filter(|r| r.from_name == entity_name || r.to_name == entity_name)
+                .collect())
+        })
+        .await
     }
 
     async fn get_all_relations(&self, project_id: &ProjectId) -> StorageResult<Vec<Relation>> {
         let prefix = format!("{}:", project_id);
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(RELATIONS)?;
-
-        let mut relations = Vec::new();
-        for entry in table.iter()? {
-            let (key, value) = entry?;
-            if key.value().starts_with(&prefix) {
-                let relation: Relation = serde_json::from_slice(value.value())?;
-                relations.push(relation);
-            }
-        }
-
-        Ok(relations)
+        self.read(move |read_txn| Self::records(&read_txn.open_table(RELATIONS)?, &prefix))
+            .await
     }
 
     async fn get_all_relations_all_projects(&self) -> StorageResult<Vec<Relation>> {
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(RELATIONS)?;
-
-        let mut relations = Vec::new();
-        for entry in table.iter()? {
-            let (_, value) = entry?;
-            let relation: Relation = serde_json::from_slice(value.value())?;
-            relations.push(relation);
-        }
-
-        Ok(relations)
+        self.read(|read_txn| Self::records(&read_txn.open_table(RELATIONS)?, ""))
+            .await
     }
 
     async fn get_relations_for_entity_global(
         &self,
         entity_name: &str,
     ) -> StorageResult<Vec<Relation>> {
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(RELATIONS)?;
-
-        let mut relations = Vec::new();
-        for entry in table.iter()? {
-            let (_, value) = entry?;
-            let relation: Relation = serde_json::from_slice(value.value())?;
-            if relation.from_name == entity_name || relation.to_name == entity_name {
-                relations.push(relation);
-            }
-        }
-
-        Ok(relations)
+        let entity_name = entity_name.to_string();
+
+        self.read(move |read_txn| {
+            let relations: Vec<Relation> = Self::records(&read_txn.open_table(RELATIONS)?, "")?;
+            Ok(relations
+                .into_iter()
+                .filter(|r| r.from_name == entity_name || r.to_name == entity_name)
+                .collect())
+        })
+        .await
     }
 
     async fn delete_relation(
@@ -303,21 +485,28 @@ impl StorageBackend for RedbStorage {
         project_id: &ProjectId,
     ) -> StorageResult<()> {
         let key = Self::make_relation_key(project_id, from, to, relation_type);
+        let deleted = ChangeEvent::Relation {
+            project_id: project_id.clone(),
+            from: from.to_string(),
+            from_project_id: None,
+            to: to.to_string(),
+            to_project_id: None,
+            relation_type: relation_type.to_string(),
+            kind: ChangeKind::Deleted,
+        };
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        {
+        self.write(move |write_txn, events| {
             let mut table = write_txn.open_table(RELATIONS)?;
-            table.remove(key.as_str())?;
-        }
-        write_txn.commit()?;
-
-        Ok(())
+            if let Some(removed) = table.remove(key.as_str())? {
+                // The stored relation names the projects at its ends
+                events.push(match Relation::decode(removed.value()) {
+                    Ok(relation) => ChangeEvent::relation(&relation, ChangeKind::Deleted),
+                    Err(_) => deleted,
+                });
+            }
+            Ok(())
+        })
+        .await
     }
 
     async fn delete_relations_for_entity(
@@ -326,158 +515,96 @@ impl StorageBackend for RedbStorage {
         project_id: &ProjectId,
     ) -> StorageResult<()> {
         let prefix = format!("{}:", project_id);
+        let entity_name = entity_name.to_string();
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-
-        let keys_to_delete: Vec<String> = {
-            let table = write_txn.open_table(RELATIONS)?;
-            let mut keys = Vec::new();
-            for entry in table.iter()? {
-                let (key, value) = entry?;
-                if key.value().starts_with(&prefix) {
-                    let relation: Relation = serde_json::from_slice(value.value())?;
-                    if relation.from_name == entity_name || relation.to_name == entity_name {
-                        keys.push(key.value().to_string());
-                    }
-                }
-            }
-            keys
-        };
-
-        {
+        self.write(move |write_txn, events| {
             let mut table = write_txn.open_table(RELATIONS)?;
-            for key in keys_to_delete {
-                table.remove(key.as_str())?;
+            let relations: Vec<Relation> = Self::records(&table, &prefix)?;
+            for relation in relations {
+                if relation.from_name == entity_name || relation.to_name == entity_name {
+                    table.remove(Self::relation_key(&relation).as_str())?;
+                    events.push(ChangeEvent::relation(&relation, ChangeKind::Deleted));
+                }
             }
-        }
-        write_txn.commit()?;
-
-        Ok(())
+            Ok(())
+        })
+        .await
     }
 
     async fn save_project(&self, project: &Project) -> StorageResult<()> {
-        let value = serde_json::to_vec(project)?;
+        let name = project.name.clone();
+        let value = project.encode(self.format)?;
+        let event = ChangeEvent::project(project, ChangeKind::Created);
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        {
+        self.write(move |write_txn, events| {
             let mut table = write_txn.open_table(PROJECTS)?;
-            table.insert(project.name.as_str(), value.as_slice())?;
-        }
-        write_txn.commit()?;
-
-        Ok(())
+            let replaced = table.insert(name.as_str(), value.as_slice())?.is_some();
+            events.push(event.with_kind(ChangeKind::of_upsert(replaced)));
+            Ok(())
+        })
+        .await
     }
 
     async fn get_project(&self, name: &str) -> StorageResult<Option<Project>> {
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(PROJECTS)?;
-
-        if let Some(value) = table.get(name)? {
-            let project: Project = serde_json::from_slice(value.value())?;
-            Ok(Some(project))
-        } else {
-            Ok(None)
-        }
+        let name = name.to_string();
+
+        self.read(move |read_txn| {
+            let table = read_txn.open_table(PROJECTS)?;
+            let project = match table.get(name.as_str())? {
+                Some(value) => Some(Project::decode(value.value())?),
+                None => None,
+            };
+            Ok(project)
+        })
+        .await
     }
 
     async fn get_project_by_id(&self, id: &ProjectId) -> StorageResult<Option<Project>> {
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(PROJECTS)?;
-
-        for entry in table.iter()? {
-            let (_, value) = entry?;
-            let project: Project = serde_json::from_slice(value.value())?;
-            if &project.id == id {
-                return Ok(Some(project));
-            }
-        }
-
-        Ok(None)
+        let projects = self.get_all_projects().await?;
+        Ok(projects.into_iter().find(|project| &project.id == id))
     }
 
     async fn get_all_projects(&self) -> StorageResult<Vec<Project>> {
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let read_txn = db
-            .begin_read()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let table = read_txn.open_table(PROJECTS)?;
-
-        let mut projects = Vec::new();
-        for entry in table.iter()? {
-            let (_, value) = entry?;
-            let project: Project = serde_json::from_slice(value.value())?;
-            projects.push(project);
-        }
-
-        Ok(projects)
+        self.read(|read_txn| Self::records(&read_txn.open_table(PROJECTS)?, ""))
+            .await
     }
 
+    /// Deletes the project with its entities and relations in one transaction
     async fn delete_project(&self, name: &str) -> StorageResult<()> {
-        // First get the project to find its ID
-        let project = match self.get_project(name).await? {
-            Some(p) => p,
-            None => return Ok(()),
-        };
-
-        // Delete all entities and relations for this project
-        let entities = self.get_all_entities(&project.id).await?;
-        for entity in &entities {
-            self.delete_entity(&entity.name, &project.id).await?;
-        }
+        let name = name.to_string();
 
-        let relations = self.get_all_relations(&project.id).await?;
-        for relation in &relations {
-            self.delete_relation(
-                &relation.from_name,
-                &relation.to_name,
-                &relation.relation_type,
-                &project.id,
-            )
-            .await?;
-        }
+        self.write(move |write_txn, events| {
+            let mut projects = write_txn.open_table(PROJECTS)?;
+            let project = match projects.get(name.as_str())? {
+                Some(value) => Project::decode(value.value())?,
+                None => return Ok(()),
+            };
 
-        // Delete the project itself
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        {
-            let mut table = write_txn.open_table(PROJECTS)?;
-            table.remove(name)?;
-        }
-        write_txn.commit()?;
-
-        Ok(())
+            let prefix = format!("{}:", project.id);
+            {
+                let mut table = write_txn.open_table(ENTITIES)?;
+                for key in Self::keys(&table, &prefix)? {
+                    table.remove(key.as_str())?;
+                    events.push(ChangeEvent::Entity {
+                        project_id: project.id.clone(),
+                        name: key[prefix.len()..].to_string(),
+                        kind: ChangeKind::Deleted,
+                    });
+                }
+            }
+            {
+                let mut table = write_txn.open_table(RELATIONS)?;
+                let relations: Vec<Relation> = Self::records(&table, &prefix)?;
+                for relation in relations {
+                    table.remove(Self::relation_key(&relation).as_str())?;
+                    events.push(ChangeEvent::relation(&relation, ChangeKind::Deleted));
+                }
+            }
+            projects.remove(name.as_str())?;
+            events.push(ChangeEvent::project(&project, ChangeKind::Deleted));
+            Ok(())
+        })
+        .await
     }
 
     async fn save_graph(&self, graph: &Graph, _project_id: &ProjectId) -> StorageResult<()> {
@@ -492,26 +619,25 @@ impl StorageBackend for RedbStorage {
             return Ok(());
         }
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        {
-            let mut table = write_txn.open_table(ENTITIES)?;
-            for entity in entities {
+        let records = entities
+            .iter()
+            .map(|entity| {
                 let key = Self::make_entity_key(&entity.project_id, &entity.name);
-                let value = serde_json::to_vec(entity)?;
-                table.insert(key.as_str(), value.as_slice())?;
+                let event = ChangeEvent::entity(entity, ChangeKind::Created);
+                Ok((key, entity.encode(self.format)?, event))
+            })
+            .collect::<StorageResult<Vec<_>>>()?;
+        let count = records.len();
+        self.write(move |write_txn, events| {
+            let mut table = write_txn.open_table(ENTITIES)?;
+            for (key, value, event) in records {
+                let replaced = table.insert(key.as_str(), value.as_slice())?.is_some();
+                events.push(event.with_kind(ChangeKind::of_upsert(replaced)));
             }
-        }
-        write_txn.commit()?;
-        tracing::debug!(
-            "Batch saved {} entities in single transaction",
-            entities.len()
-        );
+            Ok(())
+        })
+        .await?;
+        tracing::debug!("Batch saved {} entities in single transaction", count);
 
         Ok(())
     }
@@ -521,39 +647,167 @@ impl StorageBackend for RedbStorage {
             return Ok(());
         }
 
-        let db = self
-            .db
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        {
+        let records = relations
+            .iter()
+            .map(|relation| {
+                let value = relation.encode(self.format)?;
+                let event = ChangeEvent::relation(relation, ChangeKind::Created);
+                Ok((Self::relation_key(relation), value, event))
+            })
+            .collect::<StorageResult<Vec<_>>>()?;
+        let count = records.len();
+        self.write(move |write_txn, events| {
             let mut table = write_txn.open_table(RELATIONS)?;
-            for relation in relations {
-                let key = Self::make_relation_key(
-                    &relation.project_id,
-                    &relation.from_name,
-                    &relation.to_name,
-                    &relation.relation_type,
-                );
-                let value = serde_json::to_vec(relation)?;
-                table.insert(key.as_str(), value.as_slice())?;
+            for (key, value, event) in records {
+                let replaced = table.insert(key.as_str(), value.as_slice())?.is_some();
+                events.push(event.with_kind(ChangeKind::of_upsert(replaced)));
             }
-        }
-        write_txn.commit()?;
-        tracing::debug!(
-            "Batch saved {} relations in single transaction",
-            relations.len()
-        );
+            Ok(())
+        })
+        .await?;
+        tracing::debug!("Batch saved {} relations in single transaction", count);
+
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> StorageResult<RecordScan> {
+        self.read(|read_txn| {
+            let mut undecodable = Vec::new();
+            let projects = Self::scan_table(read_txn, RecordKind::Project, &mut undecodable)?;
+            let entities = Self::scan_table(read_txn, RecordKind::Entity, &mut undecodable)?;
+            let relations = Self::scan_table(read_txn, RecordKind::Relation, &mut undecodable)?;
+
+            Ok(RecordScan {
+                projects,
+                entities,
+                relations,
+                undecodable,
+            })
+        })
+        .await
+    }
+
+    async fn apply_repair(&self, plan: &RepairPlan) -> StorageResult<()> {
+        let plan = plan.clone();
+
+        let format = self.format;
+
+        // Records removed undecoded have nothing to report in the change log
+        self.write(move |write_txn, events| {
+            for record in &plan.remove_records {
+                let mut table = write_txn.open_table(Self::table_for(record.kind))?;
+                table.remove(record.key.as_str())?;
+            }
+            {
+                let mut table = write_txn.open_table(RELATIONS)?;
+                for relation in &plan.delete_relations {
+                    if table
+                        .remove(Self::relation_key(relation).as_str())?
+                        .is_some()
+                    {
+                        events.push(ChangeEvent::relation(relation, ChangeKind::Deleted));
+                    }
+                }
+                for relation in plan.save_relations {
+                    let value = relation.encode(format)?;
+                    let replaced = table
+                        .insert(Self::relation_key(&relation).as_str(), value.as_slice())?
+                        .is_some();
+                    events.push(ChangeEvent::relation(
+                        &relation,
+                        ChangeKind::of_upsert(replaced),
+                    ));
+                }
+            }
+            {
+                let mut table = write_txn.open_table(ENTITIES)?;
+                for entity in plan.save_entities {
+                    let key = Self::make_entity_key(&entity.project_id, &entity.name);
+                    let value = entity.encode(format)?;
+                    let replaced = table.insert(key.as_str(), value.as_slice())?.is_some();
+                    events.push(ChangeEvent::entity(
+                        &entity,
+                        ChangeKind::of_upsert(replaced),
+                    ));
+                }
+            }
+            Ok(())
+        })
+        .await?;
+        tracing::debug!("Applied repair plan in single transaction");
 
         Ok(())
     }
+
+    async fn append_audit(&self, entry: &AuditEntry) -> StorageResult<u64> {
+        let entry = entry.clone();
+
+        self.write(move |write_txn, _| {
+            let mut table = write_txn.open_table(AUDIT_LOG)?;
+            let seq = match table.last()? {
+                Some((last, _)) => last.value() + 1,
+                None => 1,
+            };
+            let value = serde_json::to_vec(&AuditEntry { seq, ..entry })?;
+            table.insert(seq, value.as_slice())?;
+            Ok(seq)
+        })
+        .await
+    }
+
+    async fn audit_entries(&self, filter: &AuditFilter) -> StorageResult<Vec<AuditEntry>> {
+        let entries = self
+            .read(|read_txn| {
+                let table = read_txn.open_table(AUDIT_LOG)?;
+
+                let mut entries = Vec::new();
+                for entry in table.iter()? {
+                    let (_, value) = entry?;
+                    entries.push(serde_json::from_slice(value.value())?);
+                }
+                Ok(entries)
+            })
+            .await?;
+
+        Ok(filter.apply(entries))
+    }
+
+    async fn changes_since(&self, after: u64, limit: usize) -> StorageResult<Vec<ChangeRecord>> {
+        self.read(move |read_txn| {
+            let table = read_txn.open_table(CHANGES)?;
+            check_trimmed(after, table.first()?.map(|(seq, _)| seq.value()))?;
+
+            let mut records = Vec::new();
+            for entry in table.range(after + 1..)?.take(limit) {
+                let (_, value) = entry?;
+                records.push(serde_json::from_slice(value.value())?);
+            }
+            Ok(records)
+        })
+        .await
+    }
+
+    async fn latest_change(&self) -> StorageResult<u64> {
+        self.read(|read_txn| {
+            let table = read_txn.open_table(CHANGES)?;
+            let seq = match table.last()? {
+                Some((last, _)) => last.value(),
+                None => 0,
+            };
+            Ok(seq)
+        })
+        .await
+    }
+
+    fn change_signal(&self) -> Option<watch::Receiver<u64>> {
+        Some(self.signal.subscribe())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audit::{Actor, Transport};
     use tempfile::tempdir;
 
     #[tokio::test]
@@ -585,4 +839,246 @@ mod tests {
         let retrieved = storage.get_entity("TestEntity", &project.id).await.unwrap();
         assert!(retrieved.is_none());
     }
+
+    #[tokio::test]
+    async fn test_redb_audit_log() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.redb");
+        let actor = Actor::new(Transport::Cli);
+
+        {
+            let storage = RedbStorage::open(&db_path).unwrap();
+            for operation in ["entity add", "entity delete"] {
+                let entry = AuditEntry::new(
+                    operation,
+                    serde_json::Value::Null,
+                    Vec::new(),
+                    actor.clone(),
+                );
+                storage.append_audit(&entry).await.unwrap();
+            }
+        }
+
+        // Sequence numbers continue after reopening
+        let storage = RedbStorage::open(&db_path).unwrap();
+        let entry = AuditEntry::new("import", serde_json::Value::Null, Vec::new(), actor);
+        assert_eq!(storage.append_audit(&entry).await.unwrap(), 3);
+
+        let entries = storage.audit_entries(&AuditFilter::new()).await.unwrap();
+        let seqs: Vec<u64> = entries.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![1, 2, 3]);
+        let latest = AuditFilter::new().with_actor("cli").with_limit(1);
+        assert_eq!(
+            storage.audit_entries(&latest).await.unwrap()[0].operation,
+            "import"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redb_undecodable_records() {
+        let dir = tempdir().unwrap();
+        let storage = RedbStorage::open(dir.path().join("test.redb")).unwrap();
+
+        let project = Project::new("test-project");
+        storage.save_project(&project).await.unwrap();
+        storage
+            .save_entity(&Entity::new(project.id.clone(), "Good", "test"))
+            .await
+            .unwrap();
+
+        {
+            let write_txn = storage.db.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(ENTITIES).unwrap();
+                table.insert("garbage", b"not json".as_slice()).unwrap();
+                table.insert("newer", [0x02, 0x00].as_slice()).unwrap();
+            }
+            write_txn.commit().unwrap();
+        }
+
+        let scan = storage.scan_records().await.unwrap();
+        assert_eq!(scan.entities.len(), 1);
+        assert_eq!(scan.undecodable.len(), 2);
+
+        // Only the corrupt record is removed; the newer one waits for an upgrade
+        let report = crate::doctor::diagnose(scan, None);
+        assert_eq!(report.fixable_count(), 1);
+        assert_eq!(report.plan.remove_records.len(), 1);
+        assert_eq!(report.plan.remove_records[0].key, "garbage");
+        storage.apply_repair(&report.plan).await.unwrap();
+
+        let scan = storage.scan_records().await.unwrap();
+        assert_eq!(scan.entities.len(), 1);
+        assert_eq!(scan.undecodable.len(), 1);
+        assert_eq!(scan.undecodable[0].key, "newer");
+        assert!(scan.undecodable[0].newer_layout);
+    }
+
+    #[tokio::test]
+    async fn test_redb_reads_during_write() {
+        let dir = tempdir().unwrap();
+        let storage = RedbStorage::open(dir.path().join("test.redb")).unwrap();
+        let project = Project::new("test-project");
+        storage.save_project(&project).await.unwrap();
+        storage
+            .save_entity(&Entity::new(project.id.clone(), "Committed", "test"))
+            .await
+            .unwrap();
+
+        // An open write transaction does not hold up readers, which see the
+        // last commit
+        let write_txn = storage.db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(ENTITIES).unwrap();
+            let pending = Entity::new(project.id.clone(), "Pending", "test");
+            let key = RedbStorage::make_entity_key(&project.id, "Pending");
+            let value = serde_json::to_vec(&pending).unwrap();
+            table.insert(key.as_str(), value.as_slice()).unwrap();
+        }
+        let read = storage.get_all_entities(&project.id);
+        let entities = tokio::time::timeout(std::time::Duration::from_secs(5), read)
+            .await
+            .expect("read waited for the writer")
+            .unwrap();
+        let names: Vec<String> = entities.into_iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["Committed"]);
+
+        write_txn.commit().unwrap();
+        assert_eq!(
+            storage.get_all_entities(&project.id).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redb_change_log() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.redb");
+        let project = Project::new("test-project");
+
+        {
+            let storage = RedbStorage::open(&db_path).unwrap();
+            let mut signal = storage.change_signal().unwrap();
+            storage.save_project(&project).await.unwrap();
+            storage
+                .save_entity(&Entity::new(project.id.clone(), "Alice", "person"))
+                .await
+                .unwrap();
+            storage
+                .save_relation(&Relation::from_names(
+                    project.id.clone(),
+                    "Alice",
+                    "Bob",
+                    "knows",
+                ))
+                .await
+                .unwrap();
+            // Deleting what is not there changes nothing
+            storage.delete_entity("Nobody", &project.id).await.unwrap();
+            assert_eq!(*signal.borrow_and_update(), 3);
+        }
+
+        // Sequence numbers continue after reopening
+        let storage = RedbStorage::open(&db_path).unwrap();
+        assert_eq!(*storage.change_signal().unwrap().borrow(), 3);
+        storage.delete_project("test-project").await.unwrap();
+
+        let records = storage.changes_since(0, usize::MAX).await.unwrap();
+        let seqs: Vec<u64> = records.iter().map(|r| r.seq).collect();
+        assert_eq!(seqs, vec![1, 2, 3, 4, 5, 6]);
+        let kinds: Vec<ChangeKind> = records.iter().map(|r| r.event.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::Created,
+                ChangeKind::Created,
+                ChangeKind::Created,
+                ChangeKind::Deleted,
+                ChangeKind::Deleted,
+                ChangeKind::Deleted,
+            ]
+        );
+        assert!(matches!(
+            records[3].event,
+            ChangeEvent::Entity { ref name, .. } if name == "Alice"
+        ));
+        assert!(matches!(
+            records[4].event,
+            ChangeEvent::Relation { ref to, .. } if to == "Bob"
+        ));
+        assert_eq!(
+            records[5].event,
+            ChangeEvent::project(&project, ChangeKind::Deleted)
+        );
+        assert_eq!(storage.latest_change().await.unwrap(), 6);
+
+        // Writing a record again updates it
+        storage.save_project(&project).await.unwrap();
+        storage.save_project(&project).await.unwrap();
+        let records = storage.changes_since(6, usize::MAX).await.unwrap();
+        assert_eq!(records[1].event.kind(), ChangeKind::Updated);
+
+        let page = storage.changes_since(4, 1).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].seq, 5);
+    }
+
+    #[tokio::test]
+    async fn test_redb_change_log_is_trimmed() {
+        let dir = tempdir().unwrap();
+        let storage = RedbStorage::open(dir.path().join("test.redb"))
+            .unwrap()
+            .with_change_capacity(3);
+        let project = Project::new("test-project");
+        storage.save_project(&project).await.unwrap();
+        for name in ["Alice", "Bob", "Carol", "Dave"] {
+            storage
+                .save_entity(&Entity::new(project.id.clone(), name, "person"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(storage.latest_change().await.unwrap(), 5);
+        let records = storage.changes_since(2, 100).await.unwrap();
+        let seqs: Vec<u64> = records.iter().map(|record| record.seq).collect();
+        assert_eq!(seqs, [3, 4, 5]);
+        assert!(matches!(
+            storage.changes_since(1, 100).await,
+            Err(StorageError::ChangesTrimmed(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_redb_migrates_json_records() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.redb");
+        let project = Project::new("test-project");
+        let mut entity = Entity::new(project.id.clone(), "Alice", "person");
+        entity.add_observation("Works on the storage layer");
+
+        // A database written before records were versioned
+        {
+            let storage = RedbStorage::open_with_format(&db_path, RecordFormat::Json).unwrap();
+            assert_eq!(storage.get_schema_version().unwrap(), CURRENT_VERSION);
+            storage.save_project(&project).await.unwrap();
+            storage.save_entity(&entity).await.unwrap();
+            storage.set_schema_version(1).unwrap();
+        }
+
+        let storage = RedbStorage::open(&db_path).unwrap();
+        assert_eq!(storage.get_schema_version().unwrap(), CURRENT_VERSION);
+        let key = RedbStorage::make_entity_key(&project.id, "Alice");
+        let read_txn = storage.db.begin_read().unwrap();
+        let table = read_txn.open_table(ENTITIES).unwrap();
+        let value = table.get(key.as_str()).unwrap().unwrap();
+        assert_ne!(value.value().first(), Some(&b'{'));
+
+        let loaded = storage.get_entity("Alice", &project.id).await.unwrap();
+        assert_eq!(
+            loaded.unwrap().observations[0].content,
+            "Works on the storage layer"
+        );
+        let loaded = storage.get_project("test-project").await.unwrap();
+        assert_eq!(loaded.unwrap().id, project.id);
+    }
 }
@@ -1,28 +1,116 @@
 //! SQLite storage backend
-
+//!
+//! A database file is opened in WAL mode with one writer connection and a
+//! pool of read-only connections, so reads run in parallel with each other
+//! and with the writer. Queries run on the blocking pool. Every write is
+//! one transaction, which also appends what it changed to the change log.
+
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::blocking;
+use crate::changelog::{check_trimmed, first_kept, ChangeEvent, ChangeRecord, CHANGE_LOG_CAPACITY};
+use crate::changes::ChangeKind;
+use crate::doctor::{RecordKind, RecordScan, RepairPlan, UndecodableRecord};
+use crate::encoding::{RecordFormat, StoredRecord};
 use crate::error::{StorageError, StorageResult};
+use crate::migration::{Migratable, CURRENT_VERSION};
 use crate::traits::StorageBackend;
 use async_trait::async_trait;
+use chrono::SecondsFormat;
 use parsnip_core::{Entity, Graph, Project, ProjectId, Relation};
-use rusqlite::{params, Connection};
-use std::path::Path;
-use std::sync::Mutex;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Transaction};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{watch, Semaphore};
+
+/// How long a connection waits for a lock held by another process
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most read-only connections kept open
+const MAX_READERS: usize = 8;
 
 /// SQLite storage backend
 pub struct SqliteStorage {
-    conn: Mutex<Connection>,
+    writer: Arc<tokio::sync::Mutex<Connection>>,
+    /// None for in-memory databases, which only the writer can see
+    readers: Option<Readers>,
+    /// Format new and migrated records are written in
+    format: RecordFormat,
+    /// Sequence number of the last change this handle committed
+    signal: watch::Sender<u64>,
+    change_capacity: u64,
+}
+
+/// Read-only connections, each lent to one query at a time
+struct Readers {
+    path: PathBuf,
+    idle: Arc<Mutex<Vec<Connection>>>,
+    permits: Arc<Semaphore>,
+}
+
+impl Readers {
+    fn open(path: &Path, count: usize) -> StorageResult<Self> {
+        let idle = (0..count)
+            .map(|_| Self::connect(path))
+            .collect::<StorageResult<Vec<_>>>()?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            idle: Arc::new(Mutex::new(idle)),
+            permits: Arc::new(Semaphore::new(count)),
+        })
+    }
+
+    fn connect(path: &Path) -> StorageResult<Connection> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        Ok(conn)
+    }
 }
 
 impl SqliteStorage {
     /// Open or create a SQLite database at the given path
     pub fn open(path: impl AsRef<Path>) -> StorageResult<Self> {
+        Self::open_with_format(path, RecordFormat::default())
+    }
+
+    /// Open or create a SQLite database that writes records in `format`,
+    /// migrating an older database first
+    pub fn open_with_format(path: impl AsRef<Path>, format: RecordFormat) -> StorageResult<Self> {
+        let path = path.as_ref();
         let conn = Connection::open(path).map_err(|e| StorageError::Database(e.to_string()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .map_err(|e| StorageError::Database(e.to_string()))?;
 
+        // Readers see the last commit while a write is under way
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        if !mode.eq_ignore_ascii_case("wal") {
+            tracing::warn!("SQLite database {:?} is in {} mode, not WAL", path, mode);
+        }
+        conn.execute_batch("PRAGMA synchronous = NORMAL")
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        Self::init_tables(&conn)?;
+        let last_change = Self::last_change(&conn)?;
+
+        let readers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .clamp(2, MAX_READERS);
         let storage = Self {
-            conn: Mutex::new(conn),
+            writer: Arc::new(tokio::sync::Mutex::new(conn)),
+            readers: Some(Readers::open(path, readers)?),
+            format,
+            signal: watch::channel(last_change).0,
+            change_capacity: CHANGE_LOG_CAPACITY,
         };
-        storage.init_tables()?;
-
+        storage.migrate_to_latest()?;
         Ok(storage)
     }
 
@@ -30,21 +118,24 @@ impl SqliteStorage {
     pub fn in_memory() -> StorageResult<Self> {
         let conn =
             Connection::open_in_memory().map_err(|e| StorageError::Database(e.to_string()))?;
-
-        let storage = Self {
-            conn: Mutex::new(conn),
-        };
-        storage.init_tables()?;
-
-        Ok(storage)
+        Self::init_tables(&conn)?;
+
+        Ok(Self {
+            writer: Arc::new(tokio::sync::Mutex::new(conn)),
+            readers: None,
+            format: RecordFormat::default(),
+            signal: watch::channel(0).0,
+            change_capacity: CHANGE_LOG_CAPACITY,
+        })
     }
 
-    fn init_tables(&self) -> StorageResult<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    /// Keep this many change log records rather than [`CHANGE_LOG_CAPACITY`]
+    pub fn with_change_capacity(mut self, capacity: u64) -> Self {
+        self.change_capacity = capacity;
+        self
+    }
 
+    fn init_tables(conn: &Connection) -> StorageResult<()> {
         conn.execute_batch(
             r#"
             CREATE TABLE IF NOT EXISTS projects (
@@ -68,6 +159,21 @@ impl SqliteStorage {
                 PRIMARY KEY (project_id, from_name, to_name, relation_type)
             );
 
+            -- Append-only; timestamp is fixed-width RFC 3339 so it sorts as text
+            CREATE TABLE IF NOT EXISTS audit_log (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                data TEXT NOT NULL
+            );
+
+            -- AUTOINCREMENT never hands out a sequence number twice, even
+            -- once the oldest rows are trimmed
+            CREATE TABLE IF NOT EXISTS changes (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                data TEXT NOT NULL
+            );
+
             CREATE INDEX IF NOT EXISTS idx_entities_project ON entities(project_id);
             CREATE INDEX IF NOT EXISTS idx_relations_project ON relations(project_id);
             CREATE INDEX IF NOT EXISTS idx_relations_from ON relations(project_id, from_name);
@@ -76,151 +182,290 @@ impl SqliteStorage {
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
-        Ok(())
-    }
-}
+        // The schema version is kept in user_version, which starts at 0. A
+        // new database starts at the current version; one with records
+        // predates versioning
+        if Self::user_version(conn)? == 0 {
+            let empty: bool = conn
+                .query_row(
+                    "SELECT NOT EXISTS (SELECT 1 FROM projects) \
+                     AND NOT EXISTS (SELECT 1 FROM entities) \
+                     AND NOT EXISTS (SELECT 1 FROM relations)",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            let version = if empty { CURRENT_VERSION } else { 1 };
+            Self::set_user_version(conn, version)?;
+        }
 
-#[async_trait]
-impl StorageBackend for SqliteStorage {
-    async fn initialize(&self) -> StorageResult<()> {
         Ok(())
     }
 
-    async fn close(&self) -> StorageResult<()> {
-        Ok(())
+    fn user_version(conn: &Connection) -> StorageResult<u32> {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| StorageError::Database(e.to_string()))
     }
 
-    async fn health_check(&self) -> StorageResult<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        conn.execute("SELECT 1", [])
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        Ok(true)
+    fn set_user_version(conn: &Connection, version: u32) -> StorageResult<()> {
+        conn.execute_batch(&format!("PRAGMA user_version = {}", version))
+            .map_err(|e| StorageError::Database(e.to_string()))
     }
 
-    async fn save_entity(&self, entity: &Entity) -> StorageResult<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let data = serde_json::to_string(entity)?;
-
-        conn.execute(
-            "INSERT OR REPLACE INTO entities (project_id, name, data) VALUES (?1, ?2, ?3)",
-            params![entity.project_id.to_string(), entity.name, data],
-        )
-        .map_err(|e| StorageError::Database(e.to_string()))?;
+    /// Write every row of a table again in `format`, leaving the ones that
+    /// do not decode for the doctor
+    fn reencode<T: StoredRecord>(
+        tx: &Transaction,
+        table: &str,
+        format: RecordFormat,
+    ) -> StorageResult<usize> {
+        let rows = {
+            let mut stmt = tx
+                .prepare(&format!("SELECT rowid, data FROM {}", table))
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map([], |row| {
+                    let record = Self::decode_column::<T>(row.get_ref(1)?);
+                    Ok((row.get::<_, i64>(0)?, record))
+                })
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            let mut records = Vec::new();
+            for row in rows {
+                let (rowid, record) = row.map_err(|e| StorageError::Database(e.to_string()))?;
+                if let Ok(record) = record {
+                    records.push((rowid, Self::encode_column(&record, format)?));
+                }
+            }
+            records
+        };
 
-        Ok(())
+        for (rowid, data) in &rows {
+            tx.execute(
+                &format!("UPDATE {} SET data = ?1 WHERE rowid = ?2", table),
+                params![data, rowid],
+            )
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        }
+        Ok(rows.len())
     }
 
-    async fn get_entity(
-        &self,
-        name: &str,
-        project_id: &ProjectId,
-    ) -> StorageResult<Option<Entity>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    /// A record as stored in a `data` column: text when it is JSON, so
+    /// `json_extract` keeps working on it, a blob otherwise
+    fn encode_column(record: &impl StoredRecord, format: RecordFormat) -> StorageResult<Value> {
+        let bytes = record.encode(format)?;
+        Ok(match format {
+            RecordFormat::Json => Value::Text(
+                String::from_utf8(bytes).map_err(|e| StorageError::Encoding(e.to_string()))?,
+            ),
+            RecordFormat::Binary | RecordFormat::Compressed => Value::Blob(bytes),
+        })
+    }
 
-        let mut stmt = conn
-            .prepare("SELECT data FROM entities WHERE project_id = ?1 AND name = ?2")
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    fn decode_column<T: StoredRecord>(value: ValueRef<'_>) -> StorageResult<T> {
+        match value {
+            ValueRef::Text(bytes) | ValueRef::Blob(bytes) => T::decode(bytes),
+            other => Err(StorageError::Encoding(format!(
+                "expected a record, found {:?}",
+                other.data_type()
+            ))),
+        }
+    }
 
-        let result = stmt.query_row(params![project_id.to_string(), name], |row| {
-            let data: String = row.get(0)?;
-            Ok(data)
-        });
+    /// Run `f` with a read-only connection on the blocking pool, waiting
+    /// for one to be free
+    async fn read<T, F>(&self, f: F) -> StorageResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> StorageResult<T> + Send + 'static,
+    {
+        let Some(readers) = &self.readers else {
+            return self.with_writer(move |conn| f(conn)).await;
+        };
 
-        match result {
-            Ok(data) => {
-                let entity: Entity = serde_json::from_str(&data)?;
-                Ok(Some(entity))
+        let permit = readers
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| StorageError::Connection(e.to_string()))?;
+        let idle = readers.idle.clone();
+        let path = readers.path.clone();
+        blocking::run(move || {
+            let conn = idle
+                .lock()
+                .map_err(|e| StorageError::Connection(e.to_string()))?
+                .pop();
+            // One was lost to a panicking query
+            let conn = match conn {
+                Some(conn) => conn,
+                None => Readers::connect(&path)?,
+            };
+            let result = f(&conn);
+            if let Ok(mut idle) = idle.lock() {
+                idle.push(conn);
             }
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(StorageError::Database(e.to_string())),
-        }
+            drop(permit);
+            result
+        })
+        .await
     }
 
-    async fn get_all_entities(&self, project_id: &ProjectId) -> StorageResult<Vec<Entity>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    /// The writer connection, for migrations, which run before anything
+    /// else has the storage
+    fn lock_writer(&self) -> StorageResult<tokio::sync::MutexGuard<'_, Connection>> {
+        self.writer
+            .try_lock()
+            .map_err(|_| StorageError::Migration("the database is in use".to_string()))
+    }
 
-        let mut stmt = conn
-            .prepare("SELECT data FROM entities WHERE project_id = ?1")
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    /// Run `f` with the writer connection on the blocking pool, once the
+    /// writes before it are done
+    async fn with_writer<T, F>(&self, f: F) -> StorageResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> StorageResult<T> + Send + 'static,
+    {
+        let mut conn = self.writer.clone().lock_owned().await;
+        blocking::run(move || f(&mut conn)).await
+    }
 
-        let rows = stmt
-            .query_map(params![project_id.to_string()], |row| {
-                let data: String = row.get(0)?;
-                Ok(data)
+    /// Run `f` in a transaction of the writer connection, committing when it
+    /// succeeds and rolling back when it fails
+    ///
+    /// The events `f` pushes are appended to the change log in the same
+    /// transaction.
+    async fn write<T, F>(&self, f: F) -> StorageResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Transaction, &mut Vec<ChangeEvent>) -> StorageResult<T> + Send + 'static,
+    {
+        let capacity = self.change_capacity;
+        let (result, last_change) = self
+            .with_writer(move |conn| {
+                let tx = conn
+                    .transaction()
+                    .map_err(|e| StorageError::Transaction(e.to_string()))?;
+                let mut events = Vec::new();
+                let result = f(&tx, &mut events)?;
+                let last_change = Self::log_changes(&tx, events, capacity)?;
+                tx.commit()
+                    .map_err(|e| StorageError::Transaction(e.to_string()))?;
+                Ok((result, last_change))
             })
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+            .await?;
 
-        let mut entities = Vec::new();
-        for row in rows {
-            let data = row.map_err(|e| StorageError::Database(e.to_string()))?;
-            let entity: Entity = serde_json::from_str(&data)?;
-            entities.push(entity);
+        if let Some(seq) = last_change {
+            self.signal.send_replace(seq);
         }
+        Ok(result)
+    }
 
-        Ok(entities)
+    /// Append events to the change log, trimming it to `capacity` records,
+    /// and return the last sequence number
+    fn log_changes(
+        tx: &Transaction,
+        events: Vec<ChangeEvent>,
+        capacity: u64,
+    ) -> StorageResult<Option<u64>> {
+        let mut last = None;
+        for event in events {
+            let record = ChangeRecord::new(event);
+            let timestamp = record
+                .timestamp
+                .to_rfc3339_opts(SecondsFormat::Micros, true);
+            tx.execute(
+                "INSERT INTO changes (timestamp, data) VALUES (?1, ?2)",
+                params![timestamp, serde_json::to_string(&record)?],
+            )
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+            last = Some(tx.last_insert_rowid() as u64);
+        }
+        if let Some(seq) = last {
+            tx.execute(
+                "DELETE FROM changes WHERE seq < ?1",
+                params![first_kept(seq, capacity) as i64],
+            )
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+        }
+        Ok(last)
     }
 
-    async fn get_all_entities_all_projects(&self) -> StorageResult<Vec<Entity>> {
-        let conn = self
-            .conn
-            .lock()
+    fn last_change(conn: &Connection) -> StorageResult<u64> {
+        let seq: i64 = conn
+            .query_row("SELECT COALESCE(MAX(seq), 0) FROM changes", [], |row| {
+                row.get(0)
+            })
             .map_err(|e| StorageError::Database(e.to_string()))?;
+        Ok(seq as u64)
+    }
 
+    /// Decode the `data` column of every row a query returns
+    fn query_data<T: StoredRecord>(
+        conn: &Connection,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> StorageResult<Vec<T>> {
         let mut stmt = conn
-            .prepare("SELECT data FROM entities")
+            .prepare(sql)
             .map_err(|e| StorageError::Database(e.to_string()))?;
 
         let rows = stmt
-            .query_map([], |row| {
-                let data: String = row.get(0)?;
-                Ok(data)
-            })
+            .query_map(params, |row| Ok(Self::decode_column(row.get_ref(0)?)))
             .map_err(|e| StorageError::Database(e.to_string()))?;
 
-        let mut entities = Vec::new();
+        let mut records = Vec::new();
         for row in rows {
-            let data = row.map_err(|e| StorageError::Database(e.to_string()))?;
-            let entity: Entity = serde_json::from_str(&data)?;
-            entities.push(entity);
+            records.push(row.map_err(|e| StorageError::Database(e.to_string()))??);
         }
 
-        Ok(entities)
+        Ok(records)
     }
 
-    async fn delete_entity(&self, name: &str, project_id: &ProjectId) -> StorageResult<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-
+    /// Insert or replace an entity, returning the change to log
+    fn insert_entity(
+        conn: &Connection,
+        entity: &Entity,
+        format: RecordFormat,
+    ) -> StorageResult<ChangeEvent> {
+        let data = Self::encode_column(entity, format)?;
+        let replaced = conn
+            .query_row(
+                "SELECT 1 FROM entities WHERE project_id = ?1 AND name = ?2",
+                params![entity.project_id.to_string(), entity.name],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(|e| StorageError::Database(e.to_string()))?
+            .is_some();
         conn.execute(
-            "DELETE FROM entities WHERE project_id = ?1 AND name = ?2",
-            params![project_id.to_string(), name],
+            "INSERT OR REPLACE INTO entities (project_id, name, data) VALUES (?1, ?2, ?3)",
+            params![entity.project_id.to_string(), entity.name, data],
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
-
-        Ok(())
+        Ok(ChangeEvent::entity(entity, ChangeKind::of_upsert(replaced)))
     }
 
-    async fn save_relation(&self, relation: &Relation) -> StorageResult<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-        let data = serde_json::to_string(relation)?;
-
+    /// Insert or replace a relation, returning the change to log
+    fn insert_relation(
+        conn: &Connection,
+        relation: &Relation,
+        format: RecordFormat,
+    ) -> StorageResult<ChangeEvent> {
+        let data = Self::encode_column(relation, format)?;
+        let replaced = conn
+            .query_row(
+                "SELECT 1 FROM relations WHERE project_id = ?1 AND from_name = ?2 AND to_name = ?3 AND relation_type = ?4",
+                params![
+                    relation.project_id.to_string(),
+                    relation.from_name,
+                    relation.to_name,
+                    relation.relation_type
+                ],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(|e| StorageError::Database(e.to_string()))?
+            .is_some();
         conn.execute(
             "INSERT OR REPLACE INTO relations (project_id, from_name, to_name, relation_type, data) VALUES (?1, ?2, ?3, ?4, ?5)",
             params![
@@ -229,125 +474,276 @@ impl StorageBackend for SqliteStorage {
                 relation.to_name,
                 relation.relation_type,
                 data
-            ]
-        ).map_err(|e| StorageError::Database(e.to_string()))?;
-
-        Ok(())
+            ],
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+        Ok(ChangeEvent::relation(
+            relation,
+            ChangeKind::of_upsert(replaced),
+        ))
     }
 
-    async fn get_relations_for_entity(
-        &self,
-        entity_name: &str,
+    /// Delete one relation, returning the change to log if it was there
+    fn remove_relation(
+        conn: &Connection,
         project_id: &ProjectId,
-    ) -> StorageResult<Vec<Relation>> {
-        let conn = self
-            .conn
-            .lock()
+        from: &str,
+        to: &str,
+        relation_type: &str,
+    ) -> StorageResult<Option<ChangeEvent>> {
+        let key = params![project_id.to_string(), from, to, relation_type];
+        // The stored relation names the projects at its ends
+        let stored: Option<Relation> = Self::query_data(
+            conn,
+            "SELECT data FROM relations WHERE project_id = ?1 AND from_name = ?2 AND to_name = ?3 AND relation_type = ?4",
+            key,
+        )
+        .ok()
+        .and_then(|relations| relations.into_iter().next());
+        let deleted = conn
+            .execute(
+                "DELETE FROM relations WHERE project_id = ?1 AND from_name = ?2 AND to_name = ?3 AND relation_type = ?4",
+                key,
+            )
             .map_err(|e| StorageError::Database(e.to_string()))?;
+        if deleted == 0 {
+            return Ok(None);
+        }
+        Ok(Some(match stored {
+            Some(relation) => ChangeEvent::relation(&relation, ChangeKind::Deleted),
+            None => ChangeEvent::Relation {
+                project_id: project_id.clone(),
+                from: from.to_string(),
+                from_project_id: None,
+                to: to.to_string(),
+                to_project_id: None,
+                relation_type: relation_type.to_string(),
+                kind: ChangeKind::Deleted,
+            },
+        }))
+    }
 
-        let mut stmt = conn.prepare(
-            "SELECT data FROM relations WHERE project_id = ?1 AND (from_name = ?2 OR to_name = ?2)"
-        ).map_err(|e| StorageError::Database(e.to_string()))?;
+    fn table_for(kind: RecordKind) -> &'static str {
+        match kind {
+            RecordKind::Project => "projects",
+            RecordKind::Entity => "entities",
+            RecordKind::Relation => "relations",
+        }
+    }
+
+    /// Decode every row in a table, setting aside the ones that fail
+    ///
+    /// Undecodable rows are keyed by rowid.
+    fn scan_table<T: StoredRecord>(
+        conn: &Connection,
+        kind: RecordKind,
+        undecodable: &mut Vec<UndecodableRecord>,
+    ) -> StorageResult<Vec<T>> {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT rowid, data FROM {}",
+                Self::table_for(kind)
+            ))
+            .map_err(|e| StorageError::Database(e.to_string()))?;
 
         let rows = stmt
-            .query_map(params![project_id.to_string(), entity_name], |row| {
-                let data: String = row.get(0)?;
-                Ok(data)
+            .query_map([], |row| {
+                let rowid: i64 = row.get(0)?;
+                Ok((rowid, Self::decode_column(row.get_ref(1)?)))
             })
             .map_err(|e| StorageError::Database(e.to_string()))?;
 
-        let mut relations = Vec::new();
+        let mut records = Vec::new();
         for row in rows {
-            let data = row.map_err(|e| StorageError::Database(e.to_string()))?;
-            let relation: Relation = serde_json::from_str(&data)?;
-            relations.push(relation);
+            let (rowid, record) = row.map_err(|e| StorageError::Database(e.to_string()))?;
+            match record {
+                Ok(record) => records.push(record),
+                Err(e) => undecodable.push(UndecodableRecord::new(kind, rowid.to_string(), &e)),
+            }
         }
 
-        Ok(relations)
+        Ok(records)
     }
+}
 
-    async fn get_all_relations(&self, project_id: &ProjectId) -> StorageResult<Vec<Relation>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-
-        let mut stmt = conn
-            .prepare("SELECT data FROM relations WHERE project_id = ?1")
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+impl Migratable for SqliteStorage {
+    fn get_schema_version(&self) -> StorageResult<u32> {
+        Self::user_version(&*self.lock_writer()?)
+    }
 
-        let rows = stmt
-            .query_map(params![project_id.to_string()], |row| {
-                let data: String = row.get(0)?;
-                Ok(data)
-            })
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    fn set_schema_version(&self, version: u32) -> StorageResult<()> {
+        Self::set_user_version(&*self.lock_writer()?, version)
+    }
 
-        let mut relations = Vec::new();
-        for row in rows {
-            let data = row.map_err(|e| StorageError::Database(e.to_string()))?;
-            let relation: Relation = serde_json::from_str(&data)?;
-            relations.push(relation);
+    fn run_migration(&self, version: u32) -> StorageResult<()> {
+        match version {
+            2 => {
+                let mut conn = self.lock_writer()?;
+                let tx = conn
+                    .transaction()
+                    .map_err(|e| StorageError::Transaction(e.to_string()))?;
+                let count = Self::reencode::<Project>(&tx, "projects", self.format)?
+                    + Self::reencode::<Entity>(&tx, "entities", self.format)?
+                    + Self::reencode::<Relation>(&tx, "relations", self.format)?;
+                tx.commit()
+                    .map_err(|e| StorageError::Transaction(e.to_string()))?;
+                tracing::info!("Rewrote {} records as {}", count, self.format);
+                Ok(())
+            }
+            _ => Ok(()),
         }
+    }
+}
 
-        Ok(relations)
+#[async_trait]
+impl StorageBackend for SqliteStorage {
+    async fn initialize(&self) -> StorageResult<()> {
+        Ok(())
     }
 
-    async fn get_all_relations_all_projects(&self) -> StorageResult<Vec<Relation>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn close(&self) -> StorageResult<()> {
+        Ok(())
+    }
 
-        let mut stmt = conn
-            .prepare("SELECT data FROM relations")
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn health_check(&self) -> StorageResult<bool> {
+        self.read(|conn| {
+            conn.query_row("SELECT 1", [], |_| Ok(()))
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            Ok(true)
+        })
+        .await
+    }
 
-        let rows = stmt
-            .query_map([], |row| {
-                let data: String = row.get(0)?;
-                Ok(data)
-            })
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn save_entity(&self, entity: &Entity) -> StorageResult<()> {
+        let entity = entity.clone();
+        let format = self.format;
+        self.write(move |tx, events| {
+            events.push(Self::insert_entity(tx, &entity, format)?);
+            Ok(())
+        })
+        .await
+    }
 
-        let mut relations = Vec::new();
-        for row in rows {
-            let data = row.map_err(|e| StorageError::Database(e.to_string()))?;
-            let relation: Relation = serde_json::from_str(&data)?;
-            relations.push(relation);
-        }
+    async fn get_entity(
+        &self,
+        name: &str,
+        project_id: &ProjectId,
+    ) -> StorageResult<Option<Entity>> {
+        let project_id = project_id.to_string();
+        let name = name.to_string();
+
+        self.read(move |conn| {
+            let entities = Self::query_data(
+                conn,
+                "SELECT data FROM entities WHERE project_id = ?1 AND name = ?2",
+                params![project_id, name],
+            )?;
+            Ok(entities.into_iter().next())
+        })
+        .await
+    }
 
-        Ok(relations)
+    async fn get_all_entities(&self, project_id: &ProjectId) -> StorageResult<Vec<Entity>> {
+        let project_id = project_id.to_string();
+
+        self.read(move |conn| {
+            Self::query_data(
+                conn,
+                "SELECT data FROM entities WHERE project_id = ?1",
+                params![project_id],
+            )
+        })
+        .await
     }
 
-    async fn get_relations_for_entity_global(
+    async fn get_all_entities_all_projects(&self) -> StorageResult<Vec<Entity>> {
+        self.read(|conn| Self::query_data(conn, "SELECT data FROM entities", []))
+            .await
+    }
+
+    async fn delete_entity(&self, name: &str, project_id: &ProjectId) -> StorageResult<()> {
+        let project_id = project_id.clone();
+        let name = name.to_string();
+
+        self.write(move |tx, events| {
+            let deleted = tx
+                .execute(
+                    "DELETE FROM entities WHERE project_id = ?1 AND name = ?2",
+                    params![project_id.to_string(), name],
+                )
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            if deleted > 0 {
+                events.push(ChangeEvent::Entity {
+                    project_id,
+                    name,
+                    kind: ChangeKind::Deleted,
+                });
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_relation(&self, relation: &Relation) -> StorageResult<()> {
+        let relation = relation.clone();
+        let format = self.format;
+        self.write(move |tx, events| {
+            events.push(Self::insert_relation(tx, &relation, format)?);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_relations_for_entity(
         &self,
         entity_name: &str,
+        project_id: &ProjectId,
     ) -> StorageResult<Vec<Relation>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-
-        let mut stmt = conn
-            .prepare("SELECT data FROM relations WHERE from_name = ?1 OR to_name = ?1")
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+        let project_id = project_id.to_string();
+        let entity_name = entity_name.to_string();
+
+        self.read(move |conn| {
+            Self::query_data(
+                conn,
+                "SELECT data FROM relations WHERE project_id = ?1 AND (from_name = ?2 OR to_name = ?2)",
+                params![project_id, entity_name],
+            )
+        })
+        .await
+    }
 
-        let rows = stmt
-            .query_map(params![entity_name], |row| {
-                let data: String = row.get(0)?;
-                Ok(data)
-            })
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn get_all_relations(&self, project_id: &ProjectId) -> StorageResult<Vec<Relation>> {
+        let project_id = project_id.to_string();
+
+        self.read(move |conn| {
+            Self::query_data(
+                conn,
+                "SELECT data FROM relations WHERE project_id = ?1",
+                params![project_id],
+            )
+        })
+        .await
+    }
 
-        let mut relations = Vec::new();
-        for row in rows {
-            let data = row.map_err(|e| StorageError::Database(e.to_string()))?;
-            let relation: Relation = serde_json::from_str(&data)?;
-            relations.push(relation);
-        }
+    async fn get_all_relations_all_projects(&self) -> StorageResult<Vec<Relation>> {
+        self.read(|conn| Self::query_data(conn, "SELECT data FROM relations", []))
+            .await
+    }
 
-        Ok(relations)
+    async fn get_relations_for_entity_global(
+        &self,
+        entity_name: &str,
+    ) -> StorageResult<Vec<Relation>> {
+        let entity_name = entity_name.to_string();
+
+        self.read(move |conn| {
+            Self::query_data(
+                conn,
+                "SELECT data FROM relations WHERE from_name = ?1 OR to_name = ?1",
+                params![entity_name],
+            )
+        })
+        .await
     }
 
     async fn delete_relation(
@@ -357,17 +753,25 @@ impl StorageBackend for SqliteStorage {
         relation_type: &str,
         project_id: &ProjectId,
     ) -> StorageResult<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-
-        conn.execute(
-            "DELETE FROM relations WHERE project_id = ?1 AND from_name = ?2 AND to_name = ?3 AND relation_type = ?4",
-            params![project_id.to_string(), from, to, relation_type]
-        ).map_err(|e| StorageError::Database(e.to_string()))?;
-
-        Ok(())
+        let key = (
+            project_id.clone(),
+            from.to_string(),
+            to.to_string(),
+            relation_type.to_string(),
+        );
+
+        self.write(move |tx, events| {
+            let (project_id, from, to, relation_type) = key;
+            events.extend(Self::remove_relation(
+                tx,
+                &project_id,
+                &from,
+                &to,
+                &relation_type,
+            )?);
+            Ok(())
+        })
+        .await
     }
 
     async fn delete_relations_for_entity(
@@ -375,162 +779,359 @@ impl StorageBackend for SqliteStorage {
         entity_name: &str,
         project_id: &ProjectId,
     ) -> StorageResult<()> {
-        let conn = self
-            .conn
-            .lock()
+        let project_id = project_id.to_string();
+        let entity_name = entity_name.to_string();
+
+        self.write(move |tx, events| {
+            let relations: Vec<Relation> = Self::query_data(
+                tx,
+                "SELECT data FROM relations WHERE project_id = ?1 AND (from_name = ?2 OR to_name = ?2)",
+                params![project_id, entity_name],
+            )?;
+            tx.execute(
+                "DELETE FROM relations WHERE project_id = ?1 AND (from_name = ?2 OR to_name = ?2)",
+                params![project_id, entity_name],
+            )
             .map_err(|e| StorageError::Database(e.to_string()))?;
-
-        conn.execute(
-            "DELETE FROM relations WHERE project_id = ?1 AND (from_name = ?2 OR to_name = ?2)",
-            params![project_id.to_string(), entity_name],
-        )
-        .map_err(|e| StorageError::Database(e.to_string()))?;
-
-        Ok(())
+            events.extend(
+                relations
+                    .iter()
+                    .map(|relation| ChangeEvent::relation(relation, ChangeKind::Deleted)),
+            );
+            Ok(())
+        })
+        .await
     }
 
     async fn save_project(&self, project: &Project) -> StorageResult<()> {
-        let conn = self
-            .conn
-            .lock()
+        let data = Self::encode_column(project, self.format)?;
+        let project_name = project.name.clone();
+        let event = ChangeEvent::project(project, ChangeKind::Created);
+
+        self.write(move |tx, events| {
+            let existed = tx
+                .query_row(
+                    "SELECT 1 FROM projects WHERE name = ?1",
+                    params![project_name],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map_err(|e| StorageError::Database(e.to_string()))?
+                .is_some();
+            tx.execute(
+                "INSERT OR REPLACE INTO projects (name, data) VALUES (?1, ?2)",
+                params![project_name, data],
+            )
             .map_err(|e| StorageError::Database(e.to_string()))?;
-        let data = serde_json::to_string(project)?;
+            events.push(event.with_kind(ChangeKind::of_upsert(existed)));
+            Ok(())
+        })
+        .await
+    }
 
-        conn.execute(
-            "INSERT OR REPLACE INTO projects (name, data) VALUES (?1, ?2)",
-            params![project.name, data],
-        )
-        .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn get_project(&self, name: &str) -> StorageResult<Option<Project>> {
+        let name = name.to_string();
+
+        self.read(move |conn| {
+            let projects = Self::query_data(
+                conn,
+                "SELECT data FROM projects WHERE name = ?1",
+                params![name],
+            )?;
+            Ok(projects.into_iter().next())
+        })
+        .await
+    }
 
-        Ok(())
+    async fn get_project_by_id(&self, id: &ProjectId) -> StorageResult<Option<Project>> {
+        let projects = self.get_all_projects().await?;
+        Ok(projects.into_iter().find(|project| &project.id == id))
     }
 
-    async fn get_project(&self, name: &str) -> StorageResult<Option<Project>> {
-        let conn = self
-            .conn
-            .lock()
+    async fn get_all_projects(&self) -> StorageResult<Vec<Project>> {
+        self.read(|conn| Self::query_data(conn, "SELECT data FROM projects", []))
+            .await
+    }
+
+    /// Deletes the project with its entities and relations in one transaction
+    async fn delete_project(&self, name: &str) -> StorageResult<()> {
+        let name = name.to_string();
+
+        self.write(move |tx, events| {
+            let projects: Vec<Project> = Self::query_data(
+                tx,
+                "SELECT data FROM projects WHERE name = ?1",
+                params![name],
+            )?;
+            let Some(project) = projects.into_iter().next() else {
+                return Ok(());
+            };
+            let project_id = project.id.to_string();
+
+            // Note what goes before deleting all entities and relations for
+            // this project
+            let names = {
+                let mut stmt = tx
+                    .prepare("SELECT name FROM entities WHERE project_id = ?1")
+                    .map_err(|e| StorageError::Database(e.to_string()))?;
+                let rows = stmt
+                    .query_map(params![project_id], |row| row.get::<_, String>(0))
+                    .map_err(|e| StorageError::Database(e.to_string()))?;
+                rows.collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| StorageError::Database(e.to_string()))?
+            };
+            let relations: Vec<Relation> = Self::query_data(
+                tx,
+                "SELECT data FROM relations WHERE project_id = ?1",
+                params![project_id],
+            )?;
+
+            tx.execute(
+                "DELETE FROM entities WHERE project_id = ?1",
+                params![project_id],
+            )
             .map_err(|e| StorageError::Database(e.to_string()))?;
 
-        let mut stmt = conn
-            .prepare("SELECT data FROM projects WHERE name = ?1")
+            tx.execute(
+                "DELETE FROM relations WHERE project_id = ?1",
+                params![project_id],
+            )
             .map_err(|e| StorageError::Database(e.to_string()))?;
 
-        let result = stmt.query_row(params![name], |row| {
-            let data: String = row.get(0)?;
-            Ok(data)
-        });
+            // Delete the project itself
+            tx.execute("DELETE FROM projects WHERE name = ?1", params![name])
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+
+            events.extend(names.into_iter().map(|name| ChangeEvent::Entity {
+                project_id: project.id.clone(),
+                name,
+                kind: ChangeKind::Deleted,
+            }));
+            events.extend(
+                relations
+                    .iter()
+                    .map(|relation| ChangeEvent::relation(relation, ChangeKind::Deleted)),
+            );
+            events.push(ChangeEvent::project(&project, ChangeKind::Deleted));
+            Ok(())
+        })
+        .await
+    }
 
-        match result {
-            Ok(data) => {
-                let project: Project = serde_json::from_str(&data)?;
-                Ok(Some(project))
-            }
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(StorageError::Database(e.to_string())),
-        }
+    async fn save_graph(&self, graph: &Graph, _project_id: &ProjectId) -> StorageResult<()> {
+        self.save_entities_batch(&graph.entities).await?;
+        self.save_relations_batch(&graph.relations).await?;
+        Ok(())
     }
 
-    async fn get_project_by_id(&self, id: &ProjectId) -> StorageResult<Option<Project>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn save_entities_batch(&self, entities: &[Entity]) -> StorageResult<()> {
+        if entities.is_empty() {
+            return Ok(());
+        }
 
-        let mut stmt = conn
-            .prepare("SELECT data FROM projects")
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+        let entities = entities.to_vec();
+        let format = self.format;
+        self.write(move |tx, events| {
+            for entity in entities {
+                events.push(Self::insert_entity(tx, &entity, format)?);
+            }
+            tracing::debug!(
+                "Batch saved {} entities in single transaction",
+                events.len()
+            );
+            Ok(())
+        })
+        .await
+    }
 
-        let rows = stmt
-            .query_map([], |row| {
-                let data: String = row.get(0)?;
-                Ok(data)
-            })
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn save_relations_batch(&self, relations: &[Relation]) -> StorageResult<()> {
+        if relations.is_empty() {
+            return Ok(());
+        }
 
-        for row in rows {
-            let data = row.map_err(|e| StorageError::Database(e.to_string()))?;
-            let project: Project = serde_json::from_str(&data)?;
-            if &project.id == id {
-                return Ok(Some(project));
+        let relations = relations.to_vec();
+        let format = self.format;
+        self.write(move |tx, events| {
+            for relation in relations {
+                events.push(Self::insert_relation(tx, &relation, format)?);
             }
-        }
+            tracing::debug!(
+                "Batch saved {} relations in single transaction",
+                events.len()
+            );
+            Ok(())
+        })
+        .await
+    }
 
-        Ok(None)
+    async fn scan_records(&self) -> StorageResult<RecordScan> {
+        self.read(|conn| {
+            // One snapshot for all three tables
+            let tx = conn
+                .unchecked_transaction()
+                .map_err(|e| StorageError::Transaction(e.to_string()))?;
+
+            let mut undecodable = Vec::new();
+            let projects = Self::scan_table(&tx, RecordKind::Project, &mut undecodable)?;
+            let entities = Self::scan_table(&tx, RecordKind::Entity, &mut undecodable)?;
+            let relations = Self::scan_table(&tx, RecordKind::Relation, &mut undecodable)?;
+
+            Ok(RecordScan {
+                projects,
+                entities,
+                relations,
+                undecodable,
+            })
+        })
+        .await
     }
 
-    async fn get_all_projects(&self) -> StorageResult<Vec<Project>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn apply_repair(&self, plan: &RepairPlan) -> StorageResult<()> {
+        let plan = plan.clone();
+        let format = self.format;
+
+        // Rows removed undecoded have nothing to report in the change log
+        self.write(move |tx, events| {
+            for record in &plan.remove_records {
+                let rowid: i64 = record.key.parse().map_err(|_| {
+                    StorageError::Database(format!("invalid rowid: {}", record.key))
+                })?;
+                tx.execute(
+                    &format!(
+                        "DELETE FROM {} WHERE rowid = ?1",
+                        Self::table_for(record.kind)
+                    ),
+                    params![rowid],
+                )
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+            }
 
-        let mut stmt = conn
-            .prepare("SELECT data FROM projects")
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+            for relation in &plan.delete_relations {
+                events.extend(Self::remove_relation(
+                    tx,
+                    &relation.project_id,
+                    &relation.from_name,
+                    &relation.to_name,
+                    &relation.relation_type,
+                )?);
+            }
 
-        let rows = stmt
-            .query_map([], |row| {
-                let data: String = row.get(0)?;
-                Ok(data)
-            })
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+            for entity in &plan.save_entities {
+                events.push(Self::insert_entity(tx, entity, format)?);
+            }
 
-        let mut projects = Vec::new();
-        for row in rows {
-            let data = row.map_err(|e| StorageError::Database(e.to_string()))?;
-            let project: Project = serde_json::from_str(&data)?;
-            projects.push(project);
-        }
+            for relation in &plan.save_relations {
+                events.push(Self::insert_relation(tx, relation, format)?);
+            }
 
-        Ok(projects)
+            Ok(())
+        })
+        .await
     }
 
-    async fn delete_project(&self, name: &str) -> StorageResult<()> {
-        // First get the project to find its ID
-        let project = match self.get_project(name).await? {
-            Some(p) => p,
-            None => return Ok(()),
-        };
+    /// Stores the entry without its `seq`; the row's `seq` is the one read back
+    async fn append_audit(&self, entry: &AuditEntry) -> StorageResult<u64> {
+        let data = serde_json::to_string(entry)?;
+        let timestamp = entry.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true);
 
-        let conn = self
-            .conn
-            .lock()
+        self.write(move |tx, _| {
+            tx.execute(
+                "INSERT INTO audit_log (timestamp, data) VALUES (?1, ?2)",
+                params![timestamp, data],
+            )
             .map_err(|e| StorageError::Database(e.to_string()))?;
 
-        // Delete all entities and relations for this project
-        conn.execute(
-            "DELETE FROM entities WHERE project_id = ?1",
-            params![project.id.to_string()],
-        )
-        .map_err(|e| StorageError::Database(e.to_string()))?;
+            Ok(tx.last_insert_rowid() as u64)
+        })
+        .await
+    }
 
-        conn.execute(
-            "DELETE FROM relations WHERE project_id = ?1",
-            params![project.id.to_string()],
-        )
-        .map_err(|e| StorageError::Database(e.to_string()))?;
+    async fn audit_entries(&self, filter: &AuditFilter) -> StorageResult<Vec<AuditEntry>> {
+        let since = filter
+            .since
+            .map(|since| since.to_rfc3339_opts(SecondsFormat::Micros, true))
+            .unwrap_or_default();
+
+        let entries = self
+            .read(move |conn| {
+                let mut stmt = conn
+                    .prepare("SELECT seq, data FROM audit_log WHERE timestamp >= ?1 ORDER BY seq")
+                    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+                let rows = stmt
+                    .query_map(params![since], |row| {
+                        let seq: i64 = row.get(0)?;
+                        let data: String = row.get(1)?;
+                        Ok((seq, data))
+                    })
+                    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+                let mut entries = Vec::new();
+                for row in rows {
+                    let (seq, data) = row.map_err(|e| StorageError::Database(e.to_string()))?;
+                    let mut entry: AuditEntry = serde_json::from_str(&data)?;
+                    entry.seq = seq as u64;
+                    entries.push(entry);
+                }
+                Ok(entries)
+            })
+            .await?;
 
-        // Delete the project itself
-        conn.execute("DELETE FROM projects WHERE name = ?1", params![name])
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+        Ok(filter.apply(entries))
+    }
 
-        Ok(())
+    /// Stores records without their `seq`, like the audit log
+    async fn changes_since(&self, after: u64, limit: usize) -> StorageResult<Vec<ChangeRecord>> {
+        // A negative LIMIT has none
+        let limit = i64::try_from(limit).unwrap_or(-1);
+
+        self.read(move |conn| {
+            // The oldest record comes from the same snapshot as the page;
+            // the latest is never trimmed, so an empty page has no gap
+            let mut stmt = conn
+                .prepare(
+                    "SELECT seq, data, (SELECT MIN(seq) FROM changes) FROM changes
+                     WHERE seq > ?1 ORDER BY seq LIMIT ?2",
+                )
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+
+            let rows = stmt
+                .query_map(params![after as i64, limit], |row| {
+                    let seq: i64 = row.get(0)?;
+                    let data: String = row.get(1)?;
+                    let first: i64 = row.get(2)?;
+                    Ok((seq, data, first))
+                })
+                .map_err(|e| StorageError::Database(e.to_string()))?;
+
+            let mut records = Vec::new();
+            for row in rows {
+                let (seq, data, first) = row.map_err(|e| StorageError::Database(e.to_string()))?;
+                if records.is_empty() {
+                    check_trimmed(after, Some(first as u64))?;
+                }
+                let mut record: ChangeRecord = serde_json::from_str(&data)?;
+                record.seq = seq as u64;
+                records.push(record);
+            }
+            Ok(records)
+        })
+        .await
     }
 
-    async fn save_graph(&self, graph: &Graph, _project_id: &ProjectId) -> StorageResult<()> {
-        for entity in &graph.entities {
-            self.save_entity(entity).await?;
-        }
-        for relation in &graph.relations {
-            self.save_relation(relation).await?;
-        }
-        Ok(())
+    async fn latest_change(&self) -> StorageResult<u64> {
+        self.read(Self::last_change).await
+    }
+
+    fn change_signal(&self) -> Option<watch::Receiver<u64>> {
+        Some(self.signal.subscribe())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audit::{Actor, Transport};
 
     #[tokio::test]
     async fn test_sqlite_storage() {
@@ -576,4 +1177,213 @@ mod tests {
         assert_eq!(relations.len(), 1);
         assert_eq!(relations[0].relation_type, "works_at");
     }
+
+    #[tokio::test]
+    async fn test_sqlite_audit_log() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        let actor = Actor::new(Transport::Stdio).with_client("cursor");
+
+        let mut old = AuditEntry::new(
+            "create_entities",
+            serde_json::Value::Null,
+            Vec::new(),
+            actor,
+        );
+        old.timestamp = chrono::Utc::now() - chrono::Duration::days(2);
+        storage.append_audit(&old).await.unwrap();
+        let recent = AuditEntry {
+            timestamp: chrono::Utc::now(),
+            operation: "delete_entities".to_string(),
+            ..old
+        };
+        assert_eq!(storage.append_audit(&recent).await.unwrap(), 2);
+
+        let since = AuditFilter::new().with_since(chrono::Utc::now() - chrono::Duration::days(1));
+        let entries = storage.audit_entries(&since).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].seq, 2);
+        assert_eq!(entries[0].operation, "delete_entities");
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_undecodable_records() {
+        let storage = SqliteStorage::in_memory().unwrap();
+
+        let project = Project::new("test");
+        storage.save_project(&project).await.unwrap();
+        storage
+            .writer
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO entities (project_id, name, data) VALUES (?1, 'Broken', '{')",
+                params![project.id.to_string()],
+            )
+            .unwrap();
+
+        let scan = storage.scan_records().await.unwrap();
+        assert_eq!(scan.projects.len(), 1);
+        assert_eq!(scan.undecodable.len(), 1);
+
+        let plan = RepairPlan {
+            remove_records: scan.undecodable,
+            ..Default::default()
+        };
+        storage.apply_repair(&plan).await.unwrap();
+        assert!(storage
+            .get_all_entities(&project.id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_reads_during_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SqliteStorage::open(dir.path().join("test.sqlite")).unwrap();
+        let project = Project::new("test");
+        storage.save_project(&project).await.unwrap();
+        storage
+            .save_entity(&Entity::new(project.id.clone(), "Committed", "test"))
+            .await
+            .unwrap();
+
+        // Readers see the last commit while the writer is mid-transaction
+        let pending = Entity::new(project.id.clone(), "Pending", "test");
+        {
+            let writer = storage.writer.lock().await;
+            writer.execute_batch("BEGIN IMMEDIATE").unwrap();
+            SqliteStorage::insert_entity(&writer, &pending, storage.format).unwrap();
+
+            let read = storage.get_all_entities(&project.id);
+            let entities = tokio::time::timeout(Duration::from_secs(5), read)
+                .await
+                .expect("read waited for the writer")
+                .unwrap();
+            let names: Vec<String> = entities.into_iter().map(|e| e.name).collect();
+            assert_eq!(names, vec!["Committed"]);
+
+            writer.execute_batch("COMMIT").unwrap();
+        }
+        assert_eq!(
+            storage.get_all_entities(&project.id).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_change_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.sqlite");
+        let project = Project::new("test");
+
+        {
+            let storage = SqliteStorage::open(&db_path).unwrap();
+            storage.save_project(&project).await.unwrap();
+            storage.save_project(&project).await.unwrap();
+            storage
+                .save_entities_batch(&[
+                    Entity::new(project.id.clone(), "Alice", "person"),
+                    Entity::new(project.id.clone(), "Bob", "person"),
+                ])
+                .await
+                .unwrap();
+            let relation = Relation::from_names(project.id.clone(), "Alice", "Bob", "knows");
+            storage.save_relation(&relation).await.unwrap();
+            storage
+                .delete_relations_for_entity("Bob", &project.id)
+                .await
+                .unwrap();
+            // Nothing left to delete, so nothing logged
+            storage
+                .delete_relation("Alice", "Bob", "knows", &project.id)
+                .await
+                .unwrap();
+        }
+
+        let storage = SqliteStorage::open(&db_path).unwrap();
+        assert_eq!(*storage.change_signal().unwrap().borrow(), 6);
+        storage.delete_entity("Alice", &project.id).await.unwrap();
+
+        let records = storage.changes_since(0, 100).await.unwrap();
+        let changes: Vec<String> = records
+            .iter()
+            .map(|r| {
+                let json = serde_json::to_value(r).unwrap();
+                format!("{} {}", json["type"], json["kind"])
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "\"project\" \"created\"",
+                "\"project\" \"updated\"",
+                "\"entity\" \"created\"",
+                "\"entity\" \"created\"",
+                "\"relation\" \"created\"",
+                "\"relation\" \"deleted\"",
+                "\"entity\" \"deleted\"",
+            ]
+        );
+        assert_eq!(storage.latest_change().await.unwrap(), 7);
+        assert_eq!(records[6].seq, 7);
+        assert_eq!(storage.changes_since(7, 100).await.unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_change_log_is_trimmed() {
+        let storage = SqliteStorage::in_memory().unwrap().with_change_capacity(3);
+        let project = Project::new("test-project");
+        storage.save_project(&project).await.unwrap();
+        for name in ["Alice", "Bob", "Carol", "Dave"] {
+            storage
+                .save_entity(&Entity::new(project.id.clone(), name, "person"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(storage.latest_change().await.unwrap(), 5);
+        let records = storage.changes_since(2, 100).await.unwrap();
+        let seqs: Vec<u64> = records.iter().map(|record| record.seq).collect();
+        assert_eq!(seqs, [3, 4, 5]);
+        assert!(matches!(
+            storage.changes_since(1, 100).await,
+            Err(StorageError::ChangesTrimmed(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_migrates_json_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let project = Project::new("test-project");
+        let mut entity = Entity::new(project.id.clone(), "Alice", "person");
+        entity.add_observation("Works on the storage layer");
+
+        // A database written before records were versioned
+        {
+            let storage = SqliteStorage::open_with_format(&db_path, RecordFormat::Json).unwrap();
+            assert_eq!(storage.get_schema_version().unwrap(), CURRENT_VERSION);
+            storage.save_project(&project).await.unwrap();
+            storage.save_entity(&entity).await.unwrap();
+            storage.set_schema_version(1).unwrap();
+        }
+
+        let storage = SqliteStorage::open(&db_path).unwrap();
+        assert_eq!(storage.get_schema_version().unwrap(), CURRENT_VERSION);
+        let kind: String = storage
+            .lock_writer()
+            .unwrap()
+            .query_row("SELECT typeof(data) FROM entities", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kind, "blob");
+
+        let loaded = storage.get_entity("Alice", &project.id).await.unwrap();
+        assert_eq!(
+            loaded.unwrap().observations[0].content,
+            "Works on the storage layer"
+        );
+        let loaded = storage.get_project("test-project").await.unwrap();
+        assert_eq!(loaded.unwrap().id, project.id);
+    }
 }
@@ -1,8 +1,13 @@
 //! Storage backend trait definitions
 
-use crate::error::StorageResult;
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::changelog::{ChangeRecord, ChangeSubscription};
+use crate::doctor::{RecordScan, RepairPlan};
+use crate::error::{StorageError, StorageResult};
 use async_trait::async_trait;
 use parsnip_core::{Entity, Graph, Project, ProjectId, Relation};
+use std::sync::Arc;
+use tokio::sync::watch;
 
 /// Trait for storage backend implementations
 #[async_trait]
@@ -132,4 +137,78 @@ pub trait StorageBackend: Send + Sync {
         }
         Ok(())
     }
+
+    // ─────────────────────────────────────────────────────────────────────────
+    // Maintenance
+    // ─────────────────────────────────────────────────────────────────────────
+
+    /// Read every record, collecting ones that fail to decode instead of failing
+    /// Default implementation cannot see undecodable records
+    async fn scan_records(&self) -> StorageResult<RecordScan> {
+        Ok(RecordScan {
+            projects: self.get_all_projects().await?,
+            entities: self.get_all_entities_all_projects().await?,
+            relations: self.get_all_relations_all_projects().await?,
+            undecodable: Vec::new(),
+        })
+    }
+
+    /// Apply a repair plan (should use a single transaction)
+    /// Default implementation applies each change in turn
+    async fn apply_repair(&self, plan: &RepairPlan) -> StorageResult<()> {
+        if !plan.remove_records.is_empty() {
+            return Err(StorageError::Transaction(
+                "backend cannot remove raw records".to_string(),
+            ));
+        }
+        // Delete before re-saving, since not every backend upserts relations
+        for relation in plan.delete_relations.iter().chain(&plan.save_relations) {
+            self.delete_relation(
+                &relation.from_name,
+                &relation.to_name,
+                &relation.relation_type,
+                &relation.project_id,
+            )
+            .await?;
+        }
+        self.save_entities_batch(&plan.save_entities).await?;
+        self.save_relations_batch(&plan.save_relations).await?;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────────────────────────────────────
+    // Audit Log
+    // ─────────────────────────────────────────────────────────────────────────
+
+    /// Append an entry to the audit log, returning its sequence number
+    /// The entry's own `seq` is ignored
+    async fn append_audit(&self, entry: &AuditEntry) -> StorageResult<u64>;
+
+    /// Audit entries passing a filter, oldest first
+    async fn audit_entries(&self, filter: &AuditFilter) -> StorageResult<Vec<AuditEntry>>;
+
+    // ─────────────────────────────────────────────────────────────────────────
+    // Change Log
+    // ─────────────────────────────────────────────────────────────────────────
+
+    /// Change log records with a sequence number above `after`, oldest
+    /// first, at most `limit` of them
+    async fn changes_since(&self, after: u64, limit: usize) -> StorageResult<Vec<ChangeRecord>>;
+
+    /// Sequence number of the latest change log record; 0 while it is empty
+    async fn latest_change(&self) -> StorageResult<u64>;
+
+    /// Receiver of the sequence number of the latest change this handle
+    /// wrote, for waking subscribers without polling
+    /// Default implementation has none, so subscribers poll
+    fn change_signal(&self) -> Option<watch::Receiver<u64>> {
+        None
+    }
+}
+
+impl dyn StorageBackend {
+    /// Follow the change log from after sequence number `after`
+    pub fn subscribe_changes(self: Arc<Self>, after: u64) -> ChangeSubscription {
+        ChangeSubscription::new(self, after)
+    }
 }